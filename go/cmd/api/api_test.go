@@ -355,6 +355,159 @@ func TestGetTransactionsFiltersAndPagination(t *testing.T) {
 	}
 }
 
+// fetchPage requests path and returns the event ids and next cursor.
+func fetchPage(t *testing.T, store *EventStore, path string) ([]string, string) {
+	t.Helper()
+	req := httptest.NewRequest(http.MethodGet, path, nil)
+	r := httptest.NewRecorder()
+	getTransactions(store, r, req)
+	if r.Code != http.StatusOK {
+		t.Fatalf("expected 200 for %s, got %d", path, r.Code)
+	}
+	var events []*Event
+	if err := json.NewDecoder(r.Body).Decode(&events); err != nil {
+		t.Fatalf("decode error: %v", err)
+	}
+	ids := make([]string, 0, len(events))
+	for _, ev := range events {
+		ids = append(ids, ev.EventID)
+	}
+	return ids, r.Header().Get(nextCursorHeader)
+}
+
+func TestCursorPaginationIsStable(t *testing.T) {
+	store := NewEventStore(1000, 100)
+	for i := 0; i < 5; i++ {
+		store.Add(makeEvent(strconv.Itoa(i), "from", "to", "1", "", ""))
+	}
+
+	ids, cursor := fetchPage(t, store, "/transactions?chain=solana&limit=2")
+	if strings.Join(ids, ",") != "4,3" || cursor == "" {
+		t.Fatalf("unexpected first page %v (cursor %q)", ids, cursor)
+	}
+	// Events arriving between pages don't shift the next one.
+	store.Add(makeEvent("5", "from", "to", "1", "", ""))
+	ids, cursor = fetchPage(t, store, "/transactions?chain=solana&limit=2&cursor="+cursor)
+	if strings.Join(ids, ",") != "2,1" {
+		t.Fatalf("expected 2,1 after the cursor, got %v", ids)
+	}
+	ids, _ = fetchPage(t, store, "/transactions?chain=solana&limit=2&cursor="+cursor)
+	if strings.Join(ids, ",") != "0" {
+		t.Fatalf("expected the last event, got %v", ids)
+	}
+
+	// Incremental sync: resume oldest-first from a saved cursor.
+	_, saved := fetchPage(t, store, "/transactions?chain=solana&limit=3&sort_order=asc")
+	store.Add(makeEvent("6", "from", "to", "1", "", ""))
+	ids, next := fetchPage(t, store, "/transactions?chain=solana&sort_order=asc&cursor="+saved)
+	if strings.Join(ids, ",") != "3,4,5,6" {
+		t.Fatalf("expected events after the saved cursor, got %v", ids)
+	}
+	ids, _ = fetchPage(t, store, "/transactions?chain=solana&sort_order=asc&cursor="+next)
+	if len(ids) != 0 {
+		t.Fatalf("expected no events after the last cursor, got %v", ids)
+	}
+}
+
+// evmEvent is an event at a block and log index of chain.
+func evmEvent(chain, id string, block, index uint64) *Event {
+	ev := makeEvent(id, "from", "to", "1", "", "")
+	ev.Chain = chain
+	ev.BlockNumber = &block
+	ev.LogIndex = &index
+	return ev
+}
+
+func TestCursorsFollowChainBlockAndLogIndex(t *testing.T) {
+	store := NewEventStore(1000, 100)
+	// Ingested out of chain order, as after a backfill or a reconnect.
+	store.Add(evmEvent("ethereum", "e-11-0", 11, 0))
+	store.Add(evmEvent("ethereum", "e-10-7", 10, 7))
+	store.Add(evmEvent("polygon", "p-5-0", 5, 0))
+	store.Add(evmEvent("ethereum", "e-10-2", 10, 2))
+	slot := uint64(3)
+	sol := makeEvent("s-3", "from", "to", "1", "", "")
+	sol.Slot = &slot
+	store.Add(sol)
+
+	// Without a chain, events keep their ingestion order and get no cursor.
+	ids, cursor := fetchPage(t, store, "/transactions?limit=3")
+	if strings.Join(ids, ",") != "s-3,e-10-2,p-5-0" || cursor != "" {
+		t.Fatalf("expected newest first without a cursor, got %v (cursor %q)", ids, cursor)
+	}
+
+	ids, cursor = fetchPage(t, store, "/transactions?chain=ethereum&sort_order=asc&limit=2")
+	if strings.Join(ids, ",") != "e-10-2,e-10-7" {
+		t.Fatalf("expected block and log index order, got %v", ids)
+	}
+	want := position{Chain: "ethereum", Block: 10, Index: 7, EventID: "e-10-7"}
+	if got, err := decodeCursor(cursor); err != nil || got != want {
+		t.Fatalf("expected cursor at %+v, got %+v (%v)", want, got, err)
+	}
+	// A late event before the cursor isn't returned; one after it is.
+	store.Add(evmEvent("ethereum", "e-9-0", 9, 0))
+	store.Add(evmEvent("ethereum", "e-10-8", 10, 8))
+	ids, _ = fetchPage(t, store, "/transactions?chain=ethereum&sort_order=asc&cursor="+cursor)
+	if strings.Join(ids, ",") != "e-10-8,e-11-0" {
+		t.Fatalf("expected the events after the cursor, got %v", ids)
+	}
+
+	// Descending pages walk the same order backwards.
+	ids, cursor = fetchPage(t, store, "/transactions?chain=ethereum&limit=3")
+	if strings.Join(ids, ",") != "e-11-0,e-10-8,e-10-7" {
+		t.Fatalf("expected the reverse order, got %v", ids)
+	}
+	ids, _ = fetchPage(t, store, "/transactions?chain=ethereum&cursor="+cursor)
+	if strings.Join(ids, ",") != "e-10-2,e-9-0" {
+		t.Fatalf("expected the earlier ethereum events, got %v", ids)
+	}
+}
+
+func TestCursorValidationAndWalletPages(t *testing.T) {
+	store := NewEventStore(1000, 100)
+	store.Add(makeEvent("1", "alice", "bob", "1", "", ""))
+	store.Add(makeEvent("2", "carol", "dave", "1", "", ""))
+	store.Add(makeEvent("3", "bob", "alice", "1", "", ""))
+
+	valid := encodeCursor(position{Chain: "solana", EventID: "1"})
+	// A truncated token, a v1 cursor, a negative block and a missing id.
+	for _, bad := range []string{"not-base64!", valid[:2], "djE6LTE", "djI6ZXRoZXJldW06LTE6MDpldGg6MHhhYQ", "djI6ZXRoZXJldW06MTow"} {
+		req := httptest.NewRequest(http.MethodGet, "/transactions?chain=solana&cursor="+bad, nil)
+		r := httptest.NewRecorder()
+		getTransactions(store, r, req)
+		if r.Code != http.StatusBadRequest {
+			t.Fatalf("expected 400 for cursor %q, got %d", bad, r.Code)
+		}
+	}
+	// A valid cursor without its chain, or with another one.
+	for _, query := range []string{"cursor=" + valid, "chain=ethereum&cursor=" + valid} {
+		req := httptest.NewRequest(http.MethodGet, "/transactions?"+query, nil)
+		r := httptest.NewRecorder()
+		getTransactions(store, r, req)
+		if r.Code != http.StatusBadRequest {
+			t.Fatalf("expected 400 for %q, got %d", query, r.Code)
+		}
+	}
+
+	req := httptest.NewRequest(http.MethodGet, "/wallet/alice/transactions?chain=solana&limit=1", nil)
+	req = withChiParam(req, "address", "alice")
+	r := httptest.NewRecorder()
+	getWalletTransactions(store, r, req)
+	cursor := r.Header().Get(nextCursorHeader)
+
+	req = httptest.NewRequest(http.MethodGet, "/wallet/alice/transactions?chain=solana&cursor="+cursor, nil)
+	req = withChiParam(req, "address", "alice")
+	r = httptest.NewRecorder()
+	getWalletTransactions(store, r, req)
+	var events []*Event
+	if err := json.NewDecoder(r.Body).Decode(&events); err != nil {
+		t.Fatalf("decode error: %v", err)
+	}
+	if len(events) != 1 || events[0].EventID != "1" {
+		t.Fatalf("expected alice's older event after the cursor, got %+v", events)
+	}
+}
+
 func TestEventStoreConcurrency(t *testing.T) {
 	store := NewEventStore(10000, 1000)
 
@@ -7,10 +7,12 @@ package main
 
 import (
 	"context"
+	"encoding/base64"
 	"encoding/json"
 	"fmt"
 	"net/http"
 	"os"
+	"sort"
 	"strconv"
 	"strings"
 	"sync"
@@ -25,6 +27,10 @@ import (
 const (
 	maxEvents          = 1000
 	maxEventsPerWallet = 100
+
+	// nextCursorHeader carries the cursor of the last event of a page.
+	nextCursorHeader = "X-Next-Cursor"
+	cursorPrefix     = "v2:"
 )
 
 type Health struct {
@@ -53,6 +59,60 @@ type Event struct {
 	EventType string  `json:"event_type"`
 	Slot      *uint64 `json:"slot,omitempty"`
 	Token     *Token  `json:"token,omitempty"`
+
+	// BlockNumber and LogIndex locate EVM events in their chain; LogIndex is
+	// only set for events decoded from a log.
+	BlockNumber *uint64 `json:"block_number,omitempty"`
+	LogIndex    *uint64 `json:"log_index,omitempty"`
+}
+
+// position is the key list endpoints filtered to one chain order and page
+// by: the event's chain, its block (slot on Solana), its log index and,
+// between events sharing those, its id. Cursors encode the position of a
+// page's last event. Unfiltered listings keep ingestion order and have no
+// cursor.
+type position struct {
+	Chain   string
+	Block   uint64
+	Index   uint64
+	EventID string
+}
+
+// position returns where e sorts; missing block or index count as 0.
+func (e *Event) position() position {
+	p := position{Chain: e.Chain, EventID: e.EventID}
+	if e.BlockNumber != nil {
+		p.Block = *e.BlockNumber
+	} else if e.Slot != nil {
+		p.Block = *e.Slot
+	}
+	if e.LogIndex != nil {
+		p.Index = *e.LogIndex
+	}
+	return p
+}
+
+// less orders positions by chain, block, log index and event id, comparing
+// strings bytewise like the `COLLATE "C"` columns of positionColumns.
+func (p position) less(o position) bool {
+	if p.Chain != o.Chain {
+		return p.Chain < o.Chain
+	}
+	if p.Block != o.Block {
+		return p.Block < o.Block
+	}
+	if p.Index != o.Index {
+		return p.Index < o.Index
+	}
+	return p.EventID < o.EventID
+}
+
+// positionColumns computes an event's position in SQL.
+var positionColumns = []string{
+	`chain COLLATE "C"`,
+	"COALESCE(block_number, slot, 0)",
+	"COALESCE(log_index, 0)",
+	`event_id COLLATE "C"`,
 }
 
 // EventFilter holds filter, sort, and pagination parameters for list queries.
@@ -68,6 +128,10 @@ type EventFilter struct {
 	SortOrder string
 	Limit     int
 	Offset    int
+	// Cursor, when set, restricts results to events after it in the sort
+	// order (earlier positions by default, later ones with SortOrder "asc").
+	// It is only accepted along with Chain.
+	Cursor *position
 }
 
 type EventStore struct {
@@ -149,7 +213,7 @@ func (s *EventStore) GetByWallet(address string, filter EventFilter) []*Event {
 		// We intentionally keep filtering minimal to avoid complexity.
 		q := `
 			SELECT event_id, chain, network, tx_hash, timestamp, from_addr, to_addr, value, event_type, slot,
-				   token_address, token_symbol, token_decimals
+				   block_number, log_index, token_address, token_symbol, token_decimals
 			FROM events
 			WHERE (LOWER(from_addr) = $1 OR LOWER(to_addr) = $1)
 		`
@@ -175,8 +239,10 @@ func (s *EventStore) GetByWallet(address string, filter EventFilter) []*Event {
 			args = append(args, strings.ToLower(filter.To))
 			idx++
 		}
-		// Order and paginate using created_at for stability
-		q += fmt.Sprintf(" ORDER BY created_at DESC LIMIT $%d OFFSET $%d", idx, idx+1)
+		// Order and paginate by position within a chain for stable cursors,
+		// by created_at (server-side timestamp) otherwise
+		q, args, idx = appendCursor(q, args, idx, filter)
+		q += fmt.Sprintf(" ORDER BY %s LIMIT $%d OFFSET $%d", sqlOrderBy(filter), idx, idx+1)
 		if filter.Limit == 0 {
 			filter.Limit = 50
 		}
@@ -190,22 +256,24 @@ func (s *EventStore) GetByWallet(address string, filter EventFilter) []*Event {
 			out := make([]*Event, 0)
 			for rows.Next() {
 				var ev Event
-				var slot *int64
+				var slot, blockNumber, logIndex *int64
 				var tokAddr, tokSym *string
 				var tokDec *int32
 				if err := rows.Scan(&ev.EventID, &ev.Chain, &ev.Network, &ev.TxHash, &ev.Timestamp,
-					&ev.From, &ev.To, &ev.Value, &ev.EventType, &slot, &tokAddr, &tokSym, &tokDec); err != nil {
+					&ev.From, &ev.To, &ev.Value, &ev.EventType, &slot, &blockNumber, &logIndex,
+					&tokAddr, &tokSym, &tokDec); err != nil {
 					log.WithError(err).Warn("db scan failed")
 					continue
 				}
-				if slot != nil {
-					// G115: Safe conversion - values from DB are already validated
-					if *slot < 0 {
-						log.Warnf("negative slot value in DB: %d", *slot)
-						continue
-					}
-					s := uint64(*slot)
-					ev.Slot = &s
+				var ok bool
+				if ev.Slot, ok = fromBigint("slot", slot); !ok {
+					continue
+				}
+				if ev.BlockNumber, ok = fromBigint("block_number", blockNumber); !ok {
+					continue
+				}
+				if ev.LogIndex, ok = fromBigint("log_index", logIndex); !ok {
+					continue
 				}
 				if tokAddr != nil || tokSym != nil || tokDec != nil {
 					ev.Token = &Token{Address: getOrEmpty(tokAddr), Symbol: getOrEmpty(tokSym)}
@@ -252,14 +320,7 @@ func (s *EventStore) GetByWallet(address string, filter EventFilter) []*Event {
 		filteredEvents = append(filteredEvents, event)
 	}
 
-	if filter.Offset >= len(filteredEvents) {
-		return []*Event{}
-	}
-	end := filter.Offset + filter.Limit
-	if end > len(filteredEvents) {
-		end = len(filteredEvents)
-	}
-	return filteredEvents[filter.Offset:end]
+	return paginate(filteredEvents, filter)
 }
 
 func (s *EventStore) GetRecent(filter EventFilter) []*Event {
@@ -269,7 +330,7 @@ func (s *EventStore) GetRecent(filter EventFilter) []*Event {
 
 		q := `
 			SELECT event_id, chain, network, tx_hash, timestamp, from_addr, to_addr, value, event_type, slot,
-				   token_address, token_symbol, token_decimals
+				   block_number, log_index, token_address, token_symbol, token_decimals
 			FROM events
 			WHERE 1=1
 		`
@@ -295,8 +356,10 @@ func (s *EventStore) GetRecent(filter EventFilter) []*Event {
 			args = append(args, strings.ToLower(filter.To))
 			idx++
 		}
-		// Order by created_at desc for recency
-		q += fmt.Sprintf(" ORDER BY created_at DESC LIMIT $%d OFFSET $%d", idx, idx+1)
+		// Order by position within a chain, by created_at otherwise; latest
+		// first unless sort_order=asc
+		q, args, idx = appendCursor(q, args, idx, filter)
+		q += fmt.Sprintf(" ORDER BY %s LIMIT $%d OFFSET $%d", sqlOrderBy(filter), idx, idx+1)
 		if filter.Limit == 0 {
 			filter.Limit = 50
 		}
@@ -308,22 +371,24 @@ func (s *EventStore) GetRecent(filter EventFilter) []*Event {
 			out := make([]*Event, 0)
 			for rows.Next() {
 				var ev Event
-				var slot *int64
+				var slot, blockNumber, logIndex *int64
 				var tokAddr, tokSym *string
 				var tokDec *int32
 				if err := rows.Scan(&ev.EventID, &ev.Chain, &ev.Network, &ev.TxHash, &ev.Timestamp,
-					&ev.From, &ev.To, &ev.Value, &ev.EventType, &slot, &tokAddr, &tokSym, &tokDec); err != nil {
+					&ev.From, &ev.To, &ev.Value, &ev.EventType, &slot, &blockNumber, &logIndex,
+					&tokAddr, &tokSym, &tokDec); err != nil {
 					log.WithError(err).Warn("db scan failed")
 					continue
 				}
-				if slot != nil {
-					// G115: Safe conversion - values from DB are already validated
-					if *slot < 0 {
-						log.Warnf("negative slot value in DB: %d", *slot)
-						continue
-					}
-					s := uint64(*slot)
-					ev.Slot = &s
+				var ok bool
+				if ev.Slot, ok = fromBigint("slot", slot); !ok {
+					continue
+				}
+				if ev.BlockNumber, ok = fromBigint("block_number", blockNumber); !ok {
+					continue
+				}
+				if ev.LogIndex, ok = fromBigint("log_index", logIndex); !ok {
+					continue
 				}
 				if tokAddr != nil || tokSym != nil || tokDec != nil {
 					ev.Token = &Token{Address: getOrEmpty(tokAddr), Symbol: getOrEmpty(tokSym)}
@@ -345,16 +410,154 @@ func (s *EventStore) GetRecent(filter EventFilter) []*Event {
 	// Fallback in-memory
 	s.mu.RLock()
 	defer s.mu.RUnlock()
-	filteredEvents := make([]*Event, len(s.events))
-	copy(filteredEvents, s.events)
-	if filter.Offset >= len(filteredEvents) {
+	events := s.events
+	if filter.Chain != "" {
+		events = make([]*Event, 0, len(s.events))
+		for _, event := range s.events {
+			if event.Chain == filter.Chain {
+				events = append(events, event)
+			}
+		}
+	}
+	return paginate(events, filter)
+}
+
+// paginate applies the sort order, cursor, offset and limit of filter to
+// events, held newest first. Events of one chain are sorted by position,
+// others keep their ingestion order. It always returns a fresh slice.
+func paginate(events []*Event, filter EventFilter) []*Event {
+	ascending := isAscending(filter)
+	page := make([]*Event, 0, len(events))
+	for i := range events {
+		event := events[i]
+		if ascending {
+			event = events[len(events)-1-i]
+		}
+		if filter.Cursor != nil {
+			at := event.position()
+			if ascending && !filter.Cursor.less(at) || !ascending && !at.less(*filter.Cursor) {
+				continue
+			}
+		}
+		page = append(page, event)
+	}
+	if filter.Chain != "" {
+		sort.SliceStable(page, func(i, j int) bool {
+			if ascending {
+				return page[i].position().less(page[j].position())
+			}
+			return page[j].position().less(page[i].position())
+		})
+	}
+
+	if filter.Offset >= len(page) {
 		return []*Event{}
 	}
 	end := filter.Offset + filter.Limit
-	if end > len(filteredEvents) {
-		end = len(filteredEvents)
+	if end > len(page) {
+		end = len(page)
+	}
+	return page[filter.Offset:end]
+}
+
+// isAscending reports whether filter asks for oldest events first.
+func isAscending(filter EventFilter) bool {
+	return strings.EqualFold(filter.SortOrder, "asc")
+}
+
+// sqlOrderBy is the ORDER BY clause in filter's order: by position for a
+// single chain, by ingestion time otherwise.
+func sqlOrderBy(filter EventFilter) string {
+	direction := "DESC"
+	if isAscending(filter) {
+		direction = "ASC"
+	}
+	if filter.Chain == "" {
+		return "created_at " + direction
+	}
+	columns := make([]string, len(positionColumns))
+	for i, column := range positionColumns {
+		columns[i] = column + " " + direction
 	}
-	return filteredEvents[filter.Offset:end]
+	return strings.Join(columns, ", ")
+}
+
+// appendCursor adds the keyset condition of filter's cursor to a query.
+func appendCursor(q string, args []interface{}, idx int, filter EventFilter) (string, []interface{}, int) {
+	if filter.Cursor == nil {
+		return q, args, idx
+	}
+	op := "<"
+	if isAscending(filter) {
+		op = ">"
+	}
+	q += fmt.Sprintf(" AND (%s) %s ($%d, $%d, $%d, $%d)",
+		strings.Join(positionColumns, ", "), op, idx, idx+1, idx+2, idx+3)
+	c := filter.Cursor
+	// G115: Safe conversion - decodeCursor only accepts values in int64 range
+	return q, append(args, c.Chain, int64(c.Block), int64(c.Index), c.EventID), idx + 4
+}
+
+// encodeCursor turns a position into the opaque token clients pass back as
+// `cursor`.
+func encodeCursor(p position) string {
+	raw := fmt.Sprintf("%s%s:%d:%d:%s", cursorPrefix, p.Chain, p.Block, p.Index, p.EventID)
+	return base64.RawURLEncoding.EncodeToString([]byte(raw))
+}
+
+// decodeCursor parses a token produced by encodeCursor. Event ids contain
+// colons, so the id is everything after the third one.
+func decodeCursor(token string) (position, error) {
+	invalid := fmt.Errorf("invalid cursor %q", token)
+	raw, err := base64.RawURLEncoding.DecodeString(token)
+	if err != nil || !strings.HasPrefix(string(raw), cursorPrefix) {
+		return position{}, invalid
+	}
+	parts := strings.SplitN(strings.TrimPrefix(string(raw), cursorPrefix), ":", 4)
+	if len(parts) != 4 || parts[0] == "" {
+		return position{}, invalid
+	}
+	block, err := strconv.ParseInt(parts[1], 10, 64)
+	if err != nil || block < 0 {
+		return position{}, invalid
+	}
+	index, err := strconv.ParseInt(parts[2], 10, 64)
+	if err != nil || index < 0 {
+		return position{}, invalid
+	}
+	return position{Chain: parts[0], Block: uint64(block), Index: uint64(index), EventID: parts[3]}, nil
+}
+
+// parseCursor reads the `cursor` query parameter into filter. Positions
+// only order events within a chain, so a cursor needs filter.Chain, set to
+// the chain it was issued for.
+func parseCursor(r *http.Request, filter *EventFilter) error {
+	token := r.URL.Query().Get("cursor")
+	if token == "" {
+		return nil
+	}
+	p, err := decodeCursor(token)
+	if err != nil {
+		return err
+	}
+	if filter.Chain == "" {
+		return fmt.Errorf("cursor requires chain")
+	}
+	if p.Chain != filter.Chain {
+		return fmt.Errorf("cursor is for chain %q, not %q", p.Chain, filter.Chain)
+	}
+	filter.Cursor = &p
+	return nil
+}
+
+// writeEventPage encodes a page of events. For a single chain it exposes
+// the cursor of its last event so clients can resume after it.
+func writeEventPage(w http.ResponseWriter, events []*Event, filter EventFilter) {
+	if len(events) > 0 && filter.Chain != "" {
+		w.Header().Set(nextCursorHeader, encodeCursor(events[len(events)-1].position()))
+	}
+	w.Header().Set("Content-Type", "application/json")
+	_ = json.NewEncoder(w).Encode(events)
 }
 
 // NewHub creates a simple in-process broadcaster for Server-Sent Events.
@@ -500,8 +703,11 @@ func getWalletTransactions(store *EventStore, w http.ResponseWriter, r *http.Req
 			filter.Offset = offset
 		}
 	}
-
 	filter.Chain = r.URL.Query().Get("chain")
+	if err := parseCursor(r, &filter); err != nil {
+		http.Error(w, err.Error(), http.StatusBadRequest)
+		return
+	}
 	filter.Token = r.URL.Query().Get("token")
 	filter.From = r.URL.Query().Get("from")
 	filter.To = r.URL.Query().Get("to")
@@ -524,9 +730,10 @@ func getWalletTransactions(store *EventStore, w http.ResponseWriter, r *http.Req
 		}
 	}
 
+	filter.SortOrder = r.URL.Query().Get("sort_order")
+
 	events := store.GetByWallet(address, filter)
-	w.Header().Set("Content-Type", "application/json")
-	_ = json.NewEncoder(w).Encode(events)
+	writeEventPage(w, events, filter)
 }
 
 // getTransactions returns recent events across all wallets with filters.
@@ -546,8 +753,11 @@ func getTransactions(store *EventStore, w http.ResponseWriter, r *http.Request)
 			filter.Offset = offset
 		}
 	}
-
 	filter.Chain = r.URL.Query().Get("chain")
+	if err := parseCursor(r, &filter); err != nil {
+		http.Error(w, err.Error(), http.StatusBadRequest)
+		return
+	}
 	filter.Token = r.URL.Query().Get("token")
 	filter.From = r.URL.Query().Get("from")
 	filter.To = r.URL.Query().Get("to")
@@ -574,8 +784,7 @@ func getTransactions(store *EventStore, w http.ResponseWriter, r *http.Request)
 	filter.SortOrder = r.URL.Query().Get("sort_order")
 
 	events := store.GetRecent(filter)
-	w.Header().Set("Content-Type", "application/json")
-	_ = json.NewEncoder(w).Encode(events)
+	writeEventPage(w, events, filter)
 }
 
 // main bootstraps the API server, wiring Redis, optional Postgres, routes, and
@@ -685,20 +894,28 @@ func initDB(ctx context.Context, db *pgxpool.Pool) error {
 		CREATE INDEX IF NOT EXISTS idx_events_from ON events (LOWER(from_addr));
 		CREATE INDEX IF NOT EXISTS idx_events_to ON events (LOWER(to_addr));
 		CREATE INDEX IF NOT EXISTS idx_events_created ON events (created_at DESC);
+		ALTER TABLE events ADD COLUMN IF NOT EXISTS block_number BIGINT NULL;
+		ALTER TABLE events ADD COLUMN IF NOT EXISTS log_index BIGINT NULL;
+		CREATE INDEX IF NOT EXISTS idx_events_position ON events (
+			(chain COLLATE "C"), (COALESCE(block_number, slot, 0)), (COALESCE(log_index, 0)), (event_id COLLATE "C")
+		);
 	`)
 	return err
 }
 
 // persistEvent stores a single event idempotently (on event_id).
 func persistEvent(ctx context.Context, db *pgxpool.Pool, ev *Event) error {
-	var slot *int64
-	if ev.Slot != nil {
-		// G115: Safe conversion - Solana slots fit in int64 range
-		if *ev.Slot > uint64(^uint64(0)>>1) {
-			return fmt.Errorf("slot value too large: %d", *ev.Slot)
-		}
-		tmp := int64(*ev.Slot)
-		slot = &tmp
+	slot, err := toBigint("slot", ev.Slot)
+	if err != nil {
+		return err
+	}
+	blockNumber, err := toBigint("block_number", ev.BlockNumber)
+	if err != nil {
+		return err
+	}
+	logIndex, err := toBigint("log_index", ev.LogIndex)
+	if err != nil {
+		return err
 	}
 	var tokAddr, tokSym *string
 	var tokDec *int32
@@ -711,17 +928,45 @@ func persistEvent(ctx context.Context, db *pgxpool.Pool, ev *Event) error {
 		tokSym = &ts
 		tokDec = &td
 	}
-	_, err := db.Exec(ctx, `
-		INSERT INTO events (event_id, chain, network, tx_hash, timestamp, from_addr, to_addr, value, event_type, slot, token_address, token_symbol, token_decimals)
-		VALUES ($1,$2,$3,$4,$5,$6,$7,$8,$9,$10,$11,$12,$13)
+	_, err = db.Exec(ctx, `
+		INSERT INTO events (event_id, chain, network, tx_hash, timestamp, from_addr, to_addr, value, event_type, slot, block_number, log_index, token_address, token_symbol, token_decimals)
+		VALUES ($1,$2,$3,$4,$5,$6,$7,$8,$9,$10,$11,$12,$13,$14,$15)
 		ON CONFLICT (event_id) DO NOTHING
 	`,
 		ev.EventID, ev.Chain, ev.Network, ev.TxHash, ev.Timestamp,
-		ev.From, ev.To, ev.Value, ev.EventType, slot, tokAddr, tokSym, tokDec,
+		ev.From, ev.To, ev.Value, ev.EventType, slot, blockNumber, logIndex, tokAddr, tokSym, tokDec,
 	)
 	return err
 }
 
+// toBigint converts an optional unsigned column value for a BIGINT column.
+func toBigint(name string, v *uint64) (*int64, error) {
+	if v == nil {
+		return nil, nil
+	}
+	// G115: Safe conversion - checked against the int64 range first
+	if *v > uint64(^uint64(0)>>1) {
+		return nil, fmt.Errorf("%s value too large: %d", name, *v)
+	}
+	tmp := int64(*v)
+	return &tmp, nil
+}
+
+// fromBigint reads an optional BIGINT column back, reporting false for
+// negative values, which persistEvent never writes.
+func fromBigint(name string, v *int64) (*uint64, bool) {
+	if v == nil {
+		return nil, true
+	}
+	if *v < 0 {
+		log.Warnf("negative %s value in DB: %d", name, *v)
+		return nil, false
+	}
+	// G115: Safe conversion - checked non-negative above
+	tmp := uint64(*v)
+	return &tmp, true
+}
+
 // getOrEmpty safely dereferences an optional string.
 func getOrEmpty(s *string) string {
 	if s == nil {
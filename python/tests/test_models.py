@@ -0,0 +1,350 @@
+import json
+from decimal import Decimal
+from pathlib import Path
+
+import pytest
+
+from tracker_client import Event, EventFilter, decode
+
+GOLDEN = Path(__file__).resolve().parents[2] / "tests" / "golden" / "replay-eth-anvil.events.json"
+
+
+def golden_events():
+    return [Event.from_dict(e) for e in json.loads(GOLDEN.read_text())]
+
+
+def test_golden_events_round_trip():
+    raw = json.loads(GOLDEN.read_text())
+    events = golden_events()
+    assert [e.to_dict() for e in events] == raw
+    assert events[0].amount() == Decimal(1)
+    assert [e.sequence for e in events] == [1, 2]
+    assert events[1].token.decimals == 18
+
+
+def test_decode_single_and_batch():
+    single = b'{"event_id":"sol:abc","chain":"solana","slot":7,"spam":true,"future_field":1}'
+    (event,) = decode(single)
+    assert event.slot == 7 and event.spam
+
+    batch = b'{"event_id":"a"}\n{"event_id":"b"}\n'
+    assert [e.event_id for e in decode(batch)] == ["a", "b"]
+
+
+def test_decode_zstd_batch():
+    zstandard = pytest.importorskip("zstandard")
+    batch = b'{"event_id":"a"}\n{"event_id":"b"}\n'
+    compressed = zstandard.ZstdCompressor(level=3).compress(batch)
+    assert len(decode(compressed)) == 2
+
+
+def test_filter():
+    transfer, erc20 = golden_events()
+    f = EventFilter(
+        event_types=["erc20_transfer"],
+        addresses=["0x70997970C51812dc3A010C7d01b50e0d17dc79C8"],
+    )
+    assert f.matches(erc20)
+    assert not f.matches(transfer)
+    assert not EventFilter(chains=["solana"]).matches(erc20)
+
+
+def test_region_filter():
+    raw = b'{"event_id":"a","chain":"ethereum","region":"eu-west","instance_id":"tracker-eu-1"}'
+    (event,) = decode(raw)
+    assert event.instance_id == "tracker-eu-1"
+    assert EventFilter(regions=["eu-west"]).matches(event)
+    assert not EventFilter(regions=["us-east"]).matches(event)
+    assert not EventFilter(regions=["eu-west"]).matches(Event(event_id="b"))
+
+
+def test_heartbeat_round_trip():
+    raw = {
+        "event_id": "heartbeat:solana:Abc:1700000000",
+        "chain": "solana",
+        "event_type": "address_heartbeat",
+        "heartbeat": {"address": "Abc", "checkpoint": 7},
+    }
+    (event,) = decode(json.dumps(raw).encode())
+    assert event.heartbeat.checkpoint == 7
+    assert event.heartbeat.last_activity_at is None
+    assert event.involves("Abc")
+    assert {k: v for k, v in event.to_dict().items() if k in raw} == raw
+
+
+def test_portfolio_round_trip():
+    raw = {
+        "event_id": "portfolio:treasury:1700000000",
+        "event_type": "portfolio_snapshot",
+        "portfolio": {
+            "name": "treasury",
+            "block": 100,
+            "total_value": "3000.00",
+            "positions": [
+                {"asset": "ETH", "balance": "1.5", "price": "2000", "value": "3000.00"},
+                {"asset": "PEPE", "balance": "7"},
+            ],
+        },
+    }
+    (event,) = decode(json.dumps(raw).encode())
+    assert event.portfolio.slot is None
+    assert event.portfolio.positions[1].value is None
+    assert event.amount() is None
+    assert {k: v for k, v in event.to_dict().items() if k in raw} == raw
+
+
+def test_alert_round_trip():
+    raw = {
+        "event_id": "eth:0xabc:unexpected_activity",
+        "chain": "ethereum",
+        "event_type": "unexpected_activity",
+        "alert": {"rule": "cold_storage_outgoing", "severity": "critical", "address": "0xab"},
+    }
+    (event,) = decode(json.dumps(raw).encode())
+    assert event.alert.severity == "critical"
+    assert event.alert.label is None
+    assert {k: v for k, v in event.to_dict().items() if k in raw} == raw
+
+
+def test_sweep_round_trip():
+    raw = {
+        "event_id": "sweep:ethereum:0xff:0xabc",
+        "chain": "ethereum",
+        "to": "0xff",
+        "value": "4000000",
+        "event_type": "sweep_detected",
+        "sweep": {
+            "destination": "0xff",
+            "sources": ["0x01", "0x02"],
+            "tx_hashes": ["0xdef", "0xabc"],
+            "window_secs": 600,
+        },
+    }
+    (event,) = decode(json.dumps(raw).encode())
+    assert event.sweep.sources == ["0x01", "0x02"]
+    assert {k: v for k, v in event.to_dict().items() if k in raw} == raw
+
+
+def test_validator_round_trip():
+    raw = {
+        "event_id": "validator:Vote1:commission_changed:250000000",
+        "chain": "solana",
+        "from": "Node1",
+        "to": "Vote1",
+        "event_type": "validator_event",
+        "validator": {
+            "change": "commission_changed",
+            "vote_account": "Vote1",
+            "identity": "Node1",
+            "delinquent": False,
+            "commission": 10,
+            "previous_commission": 5,
+            "activated_stake": "1200000000000000",
+            "last_vote": 250000000,
+        },
+    }
+    (event,) = decode(json.dumps(raw).encode())
+    assert event.validator.previous_commission == 5
+    assert {k: v for k, v in event.to_dict().items() if k in raw} == raw
+
+
+def test_liquid_staking_round_trip():
+    raw = {
+        "event_id": "5sig:lst_redeem",
+        "chain": "solana",
+        "from": "Owner1",
+        "value": "1000000000",
+        "event_type": "lst_redeem",
+        "liquid_staking": {
+            "protocol": "marinade",
+            "underlying_symbol": "SOL",
+            "underlying_decimals": 9,
+        },
+    }
+    (event,) = decode(json.dumps(raw).encode())
+    assert event.liquid_staking.underlying_value is None
+    assert {k: v for k, v in event.to_dict().items() if k in raw} == raw
+
+
+def test_lending_round_trip():
+    raw = {
+        "event_id": "eth:0xabc:3:0x01",
+        "chain": "ethereum",
+        "from": "0x01",
+        "to": "0x5d3a536e4d6dbd6114cc1ead35777bab948e3643",
+        "value": "250",
+        "event_type": "lending_repay",
+        "lending": {
+            "protocol": "compound_v2",
+            "market": "0x5d3a536e4d6dbd6114cc1ead35777bab948e3643",
+            "account": "0x01",
+            "account_debt": "750",
+        },
+    }
+    (event,) = decode(json.dumps(raw).encode())
+    assert event.lending.health_factor is None
+    assert {k: v for k, v in event.to_dict().items() if k in raw} == raw
+
+
+def test_simulation_round_trip():
+    raw = {
+        "event_id": "eth:0xabc:0x01:pending",
+        "chain": "ethereum",
+        "from": "0x01",
+        "to": "0xa0b86991c6218b36c1d19d4a2e9eb0ce3606eb48",
+        "value": "0",
+        "event_type": "pending_transfer",
+        "simulation": {
+            "method": "trace",
+            "success": True,
+            "gas_used": 51000,
+            "effects": [
+                {
+                    "kind": "transfer",
+                    "token": "0xa0b86991c6218b36c1d19d4a2e9eb0ce3606eb48",
+                    "from": "0x01",
+                    "to": "0x02",
+                    "value": "250",
+                }
+            ],
+        },
+    }
+    (event,) = decode(json.dumps(raw).encode())
+    assert event.simulation.effects[0].to_address == "0x02"
+    assert event.simulation.revert_reason is None
+    assert {k: v for k, v in event.to_dict().items() if k in raw} == raw
+
+
+def test_stuck_round_trip():
+    raw = {
+        "event_id": "stuck:ethereum:0x01:5:pending",
+        "chain": "ethereum",
+        "from": "0x01",
+        "to": "",
+        "value": "",
+        "event_type": "stuck_transaction",
+        "stuck": {
+            "reason": "pending",
+            "nonce": 5,
+            "confirmed_nonce": 5,
+            "pending_nonce": 7,
+            "pending_secs": 600,
+        },
+    }
+    (event,) = decode(json.dumps(raw).encode())
+    assert event.stuck.pending_secs == 600
+    assert event.stuck.queued_nonce is None
+    assert {k: v for k, v in event.to_dict().items() if k in raw} == raw
+
+
+def test_fee_spike_round_trip():
+    raw = {
+        "event_id": "eth:0xabc:0x01:fee_spike",
+        "chain": "ethereum",
+        "from": "0x01",
+        "to": "0x02",
+        "value": "0",
+        "event_type": "fee_spike",
+        "alert": {"rule": "fee_spike", "severity": "high", "address": "0x01"},
+        "fee_spike": {
+            "gas_used": 21000,
+            "gas_price": "400000000000",
+            "base_fee": "20000000000",
+            "fee": "8400000000000000",
+            "overpayment": "7980000000000000",
+        },
+    }
+    (event,) = decode(json.dumps(raw).encode())
+    assert event.fee_spike.gas_used == 21000
+    assert event.alert.rule == "fee_spike"
+    assert {k: v for k, v in event.to_dict().items() if k in raw} == raw
+
+
+def test_substrate_block_round_trip():
+    raw = {
+        "event_id": "polkadot:18000000-7",
+        "chain": "polkadot",
+        "network": "polkadot",
+        "tx_hash": "18000000-2",
+        "from": "14E5nqKAp3oAJcmzgZhUD2RcptBeUBScxKHgJKU4HPNcKVf3",
+        "to": "15oF4uVJwmo4TdGW7VfQxNLavjCXviqxT9S1MgbjMNHr6Sp5",
+        "value": "25000000000",
+        "event_type": "transfer",
+        "substrate": {
+            "block_number": 18000000,
+            "block_hash": "0xabc",
+            "event_index": 7,
+            "extrinsic_index": 2,
+        },
+    }
+    (event,) = decode(json.dumps(raw).encode())
+    assert event.substrate.block_number == 18000000
+    assert event.substrate.era is None
+    assert {k: v for k, v in event.to_dict().items() if k in raw} == raw
+
+
+def test_expectation_round_trip():
+    raw = {
+        "event_id": "expectation:order-1:missed",
+        "chain": "ethereum",
+        "event_type": "expected_transfer_missed",
+        "expectation": {
+            "id": "order-1",
+            "chain": "ethereum",
+            "address": "0xab",
+            "min_amount": "5",
+            "deadline": "2025-01-01T00:00:00Z",
+        },
+    }
+    (event,) = decode(json.dumps(raw).encode())
+    assert event.expectation.id == "order-1"
+    assert event.expectation.token is None
+    assert {k: v for k, v in event.to_dict().items() if k in raw} == raw
+
+
+def test_explorer_url_round_trip():
+    raw = {
+        "event_id": "eth:0xabc",
+        "chain": "ethereum",
+        "explorer_url": {
+            "tx": "https://etherscan.io/tx/0xabc",
+            "to": "https://etherscan.io/address/0x02",
+        },
+    }
+    (event,) = decode(json.dumps(raw).encode())
+    assert event.explorer_url.to_address == "https://etherscan.io/address/0x02"
+    assert event.explorer_url.from_address is None
+    assert {k: v for k, v in event.to_dict().items() if k in raw} == raw
+
+
+def test_decode_warnings_round_trip():
+    raw = {"event_id": "eth:0xabc:log1", "chain": "ethereum", "decode_warnings": ["extra_data"]}
+    (event,) = decode(json.dumps(raw).encode())
+    assert event.decode_warnings == ["extra_data"]
+    assert {k: v for k, v in event.to_dict().items() if k in raw} == raw
+    (standard,) = decode(b'{"event_id":"a"}')
+    assert "decode_warnings" not in standard.to_dict()
+
+
+def test_token_behavior_round_trip():
+    raw = {
+        "event_id": "eth:0xabc:log1",
+        "value": "1000",
+        "token_behavior": "fee_on_transfer",
+        "received_value": "970",
+    }
+    (event,) = decode(json.dumps(raw).encode())
+    assert event.token_behavior == "fee_on_transfer"
+    assert event.received_value == "970"
+    assert {k: v for k, v in event.to_dict().items() if k in raw} == raw
+    (standard,) = decode(b'{"event_id":"a"}')
+    assert "token_behavior" not in standard.to_dict()
+
+
+def test_amount_prefers_asset_decimals():
+    (event,) = decode(
+        b'{"event_id":"a","chain":"ethereum","value":"2500000",'
+        b'"asset_symbol":"USDC","asset_decimals":6,"value_formatted":"2.5"}'
+    )
+    assert event.asset_symbol == "USDC"
+    assert event.amount() == Decimal("2.5")
@@ -0,0 +1,27 @@
+"""Client for consuming cross-chain tracker events from Python.
+
+Mirrors the Rust ``tracker-client`` crate: typed models for the normalized
+event schema, a decoder for per-event and batched (optionally zstd-compressed)
+messages, and a Redis Pub/Sub subscriber that reconnects on its own.
+
+    from tracker_client import EventFilter, Subscriber
+
+    sub = Subscriber("redis://localhost:6379", "cross_chain_events",
+                     filter=EventFilter(chains=["ethereum"], exclude_spam=True))
+    for event in sub:
+        print(event.event_type, event.tx_hash, event.amount())
+"""
+from .models import Event, Expectation, GapRange, Heartbeat, Token, decode, to_dataframe
+from .subscriber import EventFilter, Subscriber
+
+__all__ = [
+    "Event",
+    "EventFilter",
+    "Expectation",
+    "GapRange",
+    "Heartbeat",
+    "Subscriber",
+    "Token",
+    "decode",
+    "to_dataframe",
+]
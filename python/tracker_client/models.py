@@ -0,0 +1,503 @@
+"""Typed models for the tracker's normalized event schema.
+
+Keep in sync with ``Event`` in ``rust/src/lib.rs`` (see also
+``docs/api.md``). Unknown fields are ignored and optional fields default when
+absent, so projected payloads (``fields = [...]``) decode too.
+"""
+from __future__ import annotations
+
+import json
+from dataclasses import asdict, dataclass, field, fields
+from decimal import Decimal
+from typing import Any, Dict, List, Optional
+
+ZSTD_MAGIC = b"\x28\xb5\x2f\xfd"
+
+
+@dataclass(frozen=True)
+class Token:
+    address: str = ""
+    symbol: str = ""
+    decimals: int = 0
+
+
+@dataclass(frozen=True)
+class GapRange:
+    """Ethereum blocks reprocessed by the tracker's gap auditor."""
+
+    from_block: int
+    to_block: int
+
+
+@dataclass(frozen=True)
+class Heartbeat:
+    """Monitoring status carried by ``address_heartbeat`` events."""
+
+    address: str = ""
+    #: RFC 3339; None if the tracker saw no activity since it started
+    last_activity_at: Optional[str] = None
+    last_tx_hash: Optional[str] = None
+    #: last processed block (Ethereum) or slot (Solana)
+    checkpoint: Optional[int] = None
+
+
+@dataclass(frozen=True)
+class Expectation:
+    """The expected transfer resolved by ``expected_transfer_*`` events."""
+
+    id: str = ""
+    chain: str = ""
+    #: receiving address
+    address: str = ""
+    #: token contract; None for the native currency
+    token: Optional[str] = None
+    #: smallest unit, as a decimal string
+    min_amount: Optional[str] = None
+    #: exact amount to match, after fingerprinting; set instead of min_amount
+    amount: Optional[str] = None
+    #: Solana expectations: the memo the payment must carry
+    memo: Optional[str] = None
+    #: RFC 3339
+    deadline: str = ""
+
+
+@dataclass(frozen=True)
+class Position:
+    """Balance of one asset across a portfolio's addresses."""
+
+    #: logical asset, or the symbol if the asset isn't mapped
+    asset: str = ""
+    #: in whole units, e.g. ``"1.5"``
+    balance: str = ""
+    #: configured price per whole unit; None if there is none
+    price: Optional[str] = None
+    #: balance × price, to two decimals
+    value: Optional[str] = None
+
+
+@dataclass(frozen=True)
+class Portfolio:
+    """Valuation carried by ``portfolio_snapshot`` events."""
+
+    name: str = ""
+    #: Ethereum block / Solana slot the balances were read at
+    block: Optional[int] = None
+    slot: Optional[int] = None
+    #: sum of the priced positions' values
+    total_value: str = ""
+    positions: List[Position] = field(default_factory=list)
+
+
+@dataclass(frozen=True)
+class Alert:
+    """The rule that fired, carried by ``unexpected_activity``, ``liquidation_risk``,
+    ``stuck_transaction`` and ``fee_spike`` events."""
+
+    #: ``cold_storage_outgoing``, ``liquidation_risk``, ``stuck_transaction`` or ``fee_spike``
+    rule: str = ""
+    #: ``low``, ``medium``, ``high`` or ``critical``
+    severity: str = ""
+    address: str = ""
+    #: the address book label, if the entry has one
+    label: Optional[str] = None
+
+
+@dataclass(frozen=True)
+class Sweep:
+    """Transfers aggregated into a ``sweep_detected`` event."""
+
+    destination: str = ""
+    #: watched addresses that sent, in order of their first transfer
+    sources: List[str] = field(default_factory=list)
+    tx_hashes: List[str] = field(default_factory=list)
+    window_secs: int = 0
+
+
+@dataclass(frozen=True)
+class Validator:
+    """What changed about a monitored Solana validator (``validator_event``)."""
+
+    #: ``delinquent``, ``recovered``, ``commission_changed`` or ``stake_changed``
+    change: str = ""
+    vote_account: str = ""
+    identity: str = ""
+    delinquent: bool = False
+    commission: int = 0
+    previous_commission: Optional[int] = None
+    #: in lamports
+    activated_stake: str = "0"
+    previous_activated_stake: Optional[str] = None
+    last_vote: int = 0
+
+
+@dataclass(frozen=True)
+class LiquidStaking:
+    """Asset behind a liquid staking mint or redemption (``lst_mint``/``lst_redeem``)."""
+
+    #: ``lido``, ``marinade`` or ``jito``
+    protocol: str = ""
+    #: ``ETH`` or ``SOL``
+    underlying_symbol: str = ""
+    underlying_decimals: int = 0
+    #: in base units; absent when paid out later
+    underlying_value: Optional[str] = None
+
+
+@dataclass(frozen=True)
+class Lending:
+    """Aave or Compound position (``lending_*`` and ``liquidation_risk`` events)."""
+
+    #: ``aave_v3`` or ``compound_v2``
+    protocol: str = ""
+    #: the Aave pool or Compound cToken
+    market: str = ""
+    #: the supplier, borrower or liquidated account
+    account: str = ""
+    #: liquidations only; value in base units
+    collateral_asset: Optional[str] = None
+    collateral_value: Optional[str] = None
+    #: Aave borrows only, in ray (1e27 = 100% a year)
+    borrow_rate: Optional[str] = None
+    #: Compound only: debt in the market after the action, in base units
+    account_debt: Optional[str] = None
+    #: Aave only: USD with 8 decimals
+    total_collateral_base: Optional[str] = None
+    total_debt_base: Optional[str] = None
+    #: Aave only, with 18 decimals; absent without debt
+    health_factor: Optional[str] = None
+
+
+@dataclass(frozen=True)
+class ExplorerUrls:
+    """Block explorer links for the transaction and the from/to addresses."""
+
+    tx: Optional[str] = None
+    from_address: Optional[str] = None
+    to_address: Optional[str] = None
+
+
+@dataclass(frozen=True)
+class Effect:
+    """ERC-20 transfer or approval a simulated transaction would emit."""
+
+    #: ``transfer`` or ``approval``
+    kind: str = ""
+    #: the token contract
+    token: str = ""
+    #: sender, or owner of an approval
+    from_address: str = ""
+    #: recipient, or spender of an approval
+    to_address: str = ""
+    #: raw amount, or allowance
+    value: str = "0"
+
+
+@dataclass(frozen=True)
+class Simulation:
+    """Predicted outcome of a pending transaction (``pending_transfer`` events)."""
+
+    #: ``trace``, or ``call`` without gas or effects
+    method: str = ""
+    success: bool = False
+    #: why it would fail, e.g. ``execution reverted``
+    error: Optional[str] = None
+    #: the ``Error(string)`` message of a revert
+    revert_reason: Optional[str] = None
+    gas_used: Optional[int] = None
+    effects: List[Effect] = field(default_factory=list)
+
+
+@dataclass(frozen=True)
+class Stuck:
+    """Why a watched address's transaction is stuck (``stuck_transaction`` events)."""
+
+    #: ``pending`` or ``nonce_gap``
+    reason: str = ""
+    #: the nonce that isn't getting mined, or the first missing one
+    nonce: int = 0
+    confirmed_nonce: int = 0
+    pending_nonce: int = 0
+    #: ``pending`` only: how long transactions have waited at ``confirmed_nonce``
+    pending_secs: Optional[int] = None
+    #: ``nonce_gap`` only: the lowest nonce waiting for the gap
+    queued_nonce: Optional[int] = None
+
+
+@dataclass(frozen=True)
+class FeeSpike:
+    """What a transaction paid for gas (``fee_spike`` events). Amounts are in wei."""
+
+    gas_used: int = 0
+    #: effective price per gas
+    gas_price: str = "0"
+    #: base fee per gas of the block
+    base_fee: str = "0"
+    #: ``gas_used`` times ``gas_price``
+    fee: str = "0"
+    #: what was paid above the base fee
+    overpayment: str = "0"
+
+
+@dataclass(frozen=True)
+class SubstrateBlock:
+    """Where an event of a ``[[substrate_networks]]`` chain sits in its block."""
+
+    block_number: int = 0
+    block_hash: str = ""
+    #: position of the event among its block's events
+    event_index: int = 0
+    #: position of the emitting extrinsic; absent for events outside one
+    extrinsic_index: Optional[int] = None
+    #: active staking era, on chains with staking
+    era: Optional[int] = None
+
+
+def _nested(cls, data: Dict[str, Any]):
+    known = {f.name for f in fields(cls)}
+    return cls(**{k: v for k, v in data.items() if k in known})
+
+
+@dataclass(frozen=True)
+class Event:
+    event_id: str = ""
+    #: ``ethereum``, ``solana``, an ``[[evm_networks]]`` chain, ``tron`` or a
+    #: ``[[substrate_networks]]`` chain
+    chain: str = ""
+    network: str = ""
+    #: 1, 2, 3, ... per chain and network; a jump means missed events
+    sequence: Optional[int] = None
+    #: region of the publishing listener, in active-active deployments
+    region: Optional[str] = None
+    #: publishing listener; ``sequence`` is counted per instance
+    instance_id: Optional[str] = None
+    #: transaction hash, or signature on Solana
+    tx_hash: str = ""
+    #: Unix seconds on Ethereum, RFC 3339 on Solana
+    timestamp: str = ""
+    from_address: str = ""
+    to_address: str = ""
+    #: amount in the smallest unit (wei, lamports, token base units)
+    value: str = ""
+    #: asset ``value`` is in: the token's symbol or the native currency
+    asset_symbol: Optional[str] = None
+    asset_decimals: Optional[int] = None
+    #: ``value`` scaled by ``asset_decimals``, e.g. ``"1.5"``
+    value_formatted: Optional[str] = None
+    #: cross-chain asset the token is mapped to in ``[[assets]]``, e.g. ``"USDC"``
+    logical_asset: Optional[str] = None
+    #: ``transfer``, ``erc20_transfer``, ``solana_tx``, ``gap_repaired``,
+    #: ``address_heartbeat``, ``expected_transfer_received``,
+    #: ``expected_transfer_missed``, ``transaction_retracted``,
+    #: ``portfolio_snapshot``, ``unexpected_activity``, ``sweep_detected``,
+    #: ``validator_event``, ``lst_mint``, ``lst_redeem``, ``lending_supply``,
+    #: ``lending_borrow``, ``lending_repay``, ``lending_liquidation``,
+    #: ``liquidation_risk``, ``pending_transfer``, ``stuck_transaction`` or ``fee_spike``
+    event_type: str = ""
+    slot: Optional[int] = None
+    #: block of an EVM transfer, ERC-20 or lending event, or of a Tron or
+    #: Substrate event
+    block_number: Optional[int] = None
+    #: index of the log within its block (ERC-20 and lending events), or of
+    #: the event among its block's events on Substrate chains
+    log_index: Optional[int] = None
+    token: Optional[Token] = None
+    #: SPL Memo text of a Solana transaction
+    memo: Optional[str] = None
+    #: 4-byte function selector of an Ethereum transaction's calldata
+    selector: Optional[str] = None
+    #: signature for ``selector``, if it is a well-known method
+    method: Optional[str] = None
+    spam: bool = False
+    spam_reasons: List[str] = field(default_factory=list)
+    #: what was non-standard about an ERC-20 ``Transfer`` log, e.g. ``"extra_data"``
+    decode_warnings: List[str] = field(default_factory=list)
+    #: ``"fee_on_transfer"`` or ``"rebasing"``, for tokens whose balances don't
+    #: move by the logged amounts
+    token_behavior: Optional[str] = None
+    #: raw amount the recipient of a fee-on-transfer token received
+    received_value: Optional[str] = None
+    gap: Optional[GapRange] = None
+    heartbeat: Optional[Heartbeat] = None
+    expectation: Optional[Expectation] = None
+    portfolio: Optional[Portfolio] = None
+    alert: Optional[Alert] = None
+    sweep: Optional[Sweep] = None
+    validator: Optional[Validator] = None
+    liquid_staking: Optional[LiquidStaking] = None
+    lending: Optional[Lending] = None
+    #: ``pending_transfer`` events with simulation only
+    simulation: Optional[Simulation] = None
+    #: ``stuck_transaction`` events only
+    stuck: Optional[Stuck] = None
+    #: ``fee_spike`` events only
+    fee_spike: Optional[FeeSpike] = None
+    #: ``[[substrate_networks]]`` chains only
+    substrate: Optional[SubstrateBlock] = None
+    #: history published by the cold start bootstrap rather than seen live
+    backfill: bool = False
+    #: block explorer links, when the chain's explorer is known
+    explorer_url: Optional[ExplorerUrls] = None
+    traceparent: Optional[str] = None
+
+    @classmethod
+    def from_dict(cls, data: Dict[str, Any]) -> "Event":
+        # `from`/`to` are Python keywords, so they map to *_address.
+        renamed = {"from": "from_address", "to": "to_address"}
+        known = {f.name for f in fields(cls)}
+        kwargs = {}
+        for key, value in data.items():
+            name = renamed.get(key, key)
+            if name in known:
+                kwargs[name] = value
+        if kwargs.get("token") is not None:
+            kwargs["token"] = Token(**kwargs["token"])
+        if kwargs.get("gap") is not None:
+            kwargs["gap"] = GapRange(**kwargs["gap"])
+        if kwargs.get("heartbeat") is not None:
+            kwargs["heartbeat"] = _nested(Heartbeat, kwargs["heartbeat"])
+        if kwargs.get("expectation") is not None:
+            kwargs["expectation"] = _nested(Expectation, kwargs["expectation"])
+        if kwargs.get("portfolio") is not None:
+            portfolio = dict(kwargs["portfolio"])
+            portfolio["positions"] = [_nested(Position, p) for p in portfolio.get("positions", [])]
+            kwargs["portfolio"] = _nested(Portfolio, portfolio)
+        if kwargs.get("alert") is not None:
+            kwargs["alert"] = _nested(Alert, kwargs["alert"])
+        if kwargs.get("sweep") is not None:
+            kwargs["sweep"] = _nested(Sweep, kwargs["sweep"])
+        if kwargs.get("validator") is not None:
+            kwargs["validator"] = _nested(Validator, kwargs["validator"])
+        if kwargs.get("liquid_staking") is not None:
+            kwargs["liquid_staking"] = _nested(LiquidStaking, kwargs["liquid_staking"])
+        if kwargs.get("lending") is not None:
+            kwargs["lending"] = _nested(Lending, kwargs["lending"])
+        if kwargs.get("simulation") is not None:
+            simulation = dict(kwargs["simulation"])
+            simulation["effects"] = [
+                _nested(Effect, {renamed.get(k, k): v for k, v in e.items()})
+                for e in simulation.get("effects", [])
+            ]
+            kwargs["simulation"] = _nested(Simulation, simulation)
+        if kwargs.get("stuck") is not None:
+            kwargs["stuck"] = _nested(Stuck, kwargs["stuck"])
+        if kwargs.get("fee_spike") is not None:
+            kwargs["fee_spike"] = _nested(FeeSpike, kwargs["fee_spike"])
+        if kwargs.get("substrate") is not None:
+            kwargs["substrate"] = _nested(SubstrateBlock, kwargs["substrate"])
+        if kwargs.get("explorer_url") is not None:
+            links = kwargs["explorer_url"]
+            kwargs["explorer_url"] = ExplorerUrls(
+                tx=links.get("tx"), from_address=links.get("from"), to_address=links.get("to")
+            )
+        return cls(**kwargs)
+
+    def to_dict(self) -> Dict[str, Any]:
+        """The event in wire format (``from``/``to`` keys, optional fields omitted)."""
+        data = asdict(self)
+        data["from"] = data.pop("from_address")
+        data["to"] = data.pop("to_address")
+        if data["explorer_url"] is not None:
+            links = data["explorer_url"]
+            links["from"] = links.pop("from_address")
+            links["to"] = links.pop("to_address")
+        nested = (
+            "heartbeat",
+            "expectation",
+            "portfolio",
+            "alert",
+            "sweep",
+            "validator",
+            "liquid_staking",
+            "lending",
+            "stuck",
+            "fee_spike",
+            "substrate",
+        )
+        for key in nested + ("explorer_url",):
+            if data[key] is not None:
+                data[key] = {k: v for k, v in data[key].items() if v is not None}
+        if data["portfolio"] is not None:
+            data["portfolio"]["positions"] = [
+                {k: v for k, v in p.items() if v is not None}
+                for p in data["portfolio"]["positions"]
+            ]
+        if data["simulation"] is not None:
+            simulation = {k: v for k, v in data["simulation"].items() if v is not None}
+            effects = simulation.pop("effects")
+            if effects:
+                simulation["effects"] = [
+                    {{"from_address": "from", "to_address": "to"}.get(k, k): v for k, v in e.items()}
+                    for e in effects
+                ]
+            data["simulation"] = simulation
+        return {
+            k: v
+            for k, v in data.items()
+            if v is not None and not (k in ("spam", "spam_reasons", "decode_warnings", "backfill") and not v)
+        }
+
+    def amount(self) -> Optional[Decimal]:
+        """``value`` scaled by ``asset_decimals``, or else by the token's
+        decimals (18 for ETH, 9 for SOL).
+
+        None when there is no value, e.g. ``solana_tx`` and ``gap_repaired``
+        events.
+        """
+        if not self.value:
+            return None
+        if self.asset_decimals is not None:
+            decimals = self.asset_decimals
+        elif self.token is not None:
+            decimals = self.token.decimals
+        elif self.chain == "solana":
+            decimals = 9
+        else:
+            decimals = 18
+        return Decimal(self.value).scaleb(-decimals)
+
+    def involves(self, address: str) -> bool:
+        """Whether ``address`` is the sender or recipient, or the monitored
+        address of a heartbeat (EVM: case-insensitive)."""
+        candidates = [self.from_address, self.to_address]
+        if self.heartbeat is not None:
+            candidates.append(self.heartbeat.address)
+        if address.startswith("0x"):
+            return address.lower() in [c.lower() for c in candidates]
+        return address in candidates
+
+
+def decode(message: bytes) -> List[Event]:
+    """Decode one Pub/Sub message: a single event, a newline-delimited batch,
+    or a zstd-compressed batch (needs the ``zstd`` extra)."""
+    if isinstance(message, str):
+        message = message.encode()
+    if message.startswith(ZSTD_MAGIC):
+        try:
+            import zstandard
+        except ImportError as e:  # pragma: no cover - depends on extras
+            raise RuntimeError(
+                "received a zstd batch; install tracker-client[zstd]"
+            ) from e
+        message = zstandard.ZstdDecompressor().decompressobj().decompress(message)
+    return [
+        Event.from_dict(json.loads(line))
+        for line in message.splitlines()
+        if line.strip()
+    ]
+
+
+def to_dataframe(events: List[Event]):
+    """Events as a pandas DataFrame, one column per wire field plus ``amount``.
+
+    Needs the ``pandas`` extra.
+    """
+    import pandas as pd
+
+    rows = []
+    for event in events:
+        row = event.to_dict()
+        row["amount"] = event.amount()
+        if event.token is not None:
+            row["token_symbol"] = event.token.symbol
+        rows.append(row)
+    return pd.DataFrame(rows)
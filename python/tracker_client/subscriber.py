@@ -0,0 +1,88 @@
+"""Redis Pub/Sub subscriber with automatic reconnection.
+
+Pub/Sub has no replay, so events published while disconnected are lost;
+dedup on ``event_id`` and backfill from the Go API's ``/transactions``
+endpoint if every event matters.
+"""
+from __future__ import annotations
+
+import logging
+import time
+from dataclasses import dataclass, field
+from typing import Iterator, List
+
+import redis
+
+from .models import Event, decode
+
+log = logging.getLogger(__name__)
+
+MAX_BACKOFF_SECS = 30.0
+
+
+@dataclass
+class EventFilter:
+    """Empty lists match everything; the default filter passes all events.
+
+    ``regions`` keeps one copy of an active-active deployment's events;
+    events without a region don't match it.
+    """
+
+    chains: List[str] = field(default_factory=list)
+    event_types: List[str] = field(default_factory=list)
+    addresses: List[str] = field(default_factory=list)
+    exclude_spam: bool = False
+    regions: List[str] = field(default_factory=list)
+
+    def matches(self, event: Event) -> bool:
+        return (
+            (not self.chains or event.chain in self.chains)
+            and (not self.event_types or event.event_type in self.event_types)
+            and (not self.addresses or any(event.involves(a) for a in self.addresses))
+            and (not self.regions or event.region in self.regions)
+            and not (self.exclude_spam and event.spam)
+        )
+
+
+class Subscriber:
+    """Iterate over events from a tracker destination channel.
+
+    Iteration blocks for new events and reconnects with exponential backoff
+    (up to 30s) when Redis goes away. Messages that fail to decode are logged
+    and skipped.
+    """
+
+    def __init__(self, redis_url: str, channel: str, filter: EventFilter = None):
+        self.redis_url = redis_url
+        self.channel = channel
+        self.filter = filter or EventFilter()
+
+    def __iter__(self) -> Iterator[Event]:
+        backoff = 0.5
+        while True:
+            try:
+                client = redis.Redis.from_url(self.redis_url)
+                pubsub = client.pubsub(ignore_subscribe_messages=True)
+                pubsub.subscribe(self.channel)
+                log.info("Subscribed to %s", self.channel)
+                backoff = 0.5
+                for message in pubsub.listen():
+                    if message.get("type") != "message":
+                        continue
+                    try:
+                        events = decode(message["data"])
+                    except (ValueError, TypeError) as e:
+                        log.warning("Skipping undecodable message on %s: %s", self.channel, e)
+                        continue
+                    for event in events:
+                        if self.filter.matches(event):
+                            yield event
+            except redis.ConnectionError as e:
+                log.warning(
+                    "Subscription to %s failed: %s; reconnecting in %.1fs",
+                    self.channel,
+                    e,
+                    backoff,
+                )
+            time.sleep(backoff)
+            backoff = min(backoff * 2, MAX_BACKOFF_SECS)
@@ -0,0 +1,7 @@
+fn main() {
+    // The gRPC services are optional so default builds don't need protoc.
+    #[cfg(feature = "grpc")]
+    tonic_build::configure()
+        .compile(&["proto/admin.proto", "proto/events.proto"], &["proto"])
+        .expect("failed to compile proto/admin.proto and proto/events.proto");
+}
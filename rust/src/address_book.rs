@@ -0,0 +1,266 @@
+//! Known addresses (`[[address_book]]` in `CONFIG_FILE`) and the
+//! `unexpected_activity` alerts raised on them.
+//!
+//! An entry labels an address and can mark it as cold storage. Cold wallets
+//! should never send anything, so any outgoing transaction from one is
+//! published a second time as an `unexpected_activity` alert with the
+//! entry's severity:
+//!
+//! ```toml
+//! [[address_book]]
+//! address = "ethereum:0x70997970C51812dc3A010C7d01b50e0d17dc79C8"
+//! label = "Treasury cold wallet"
+//! cold_storage = true
+//! severity = "critical"
+//! ```
+//!
+//! Outgoing means the address is the `from` of an Ethereum transaction or
+//! ERC-20 transfer, or signed a Solana transaction. The alert is a copy of
+//! the triggering event with event id `<original id>:unexpected_activity`,
+//! so consumers can look the transaction up from either.
+use std::collections::HashMap;
+
+use anyhow::{anyhow, bail};
+use serde::{Deserialize, Serialize};
+
+use crate::event_type::EventType;
+use crate::token_filter::normalize;
+use crate::Event;
+
+/// Rule name of alerts for outgoing cold-storage transactions.
+pub const COLD_STORAGE_OUTGOING: &str = "cold_storage_outgoing";
+
+/// Event types that represent activity of an address. Alerts and other
+/// derived events are copies and don't trigger the rule again.
+const ACTIVITY_TYPES: &[EventType] = &[
+    EventType::Transfer,
+    EventType::Erc20Transfer,
+    EventType::SolanaTx,
+];
+
+/// How urgently an alert needs attention.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    Low,
+    Medium,
+    #[default]
+    High,
+    Critical,
+}
+
+/// One `[[address_book]]` entry in the config file.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AddressEntry {
+    /// `<chain>:<address>`, e.g. `solana:9xQe...`.
+    pub address: String,
+    #[serde(default)]
+    pub label: Option<String>,
+    #[serde(default)]
+    pub cold_storage: bool,
+    /// Severity of the entry's alerts.
+    #[serde(default)]
+    pub severity: Severity,
+}
+
+/// Payload of an `unexpected_activity` event.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct Alert {
+    pub rule: String,
+    pub severity: Severity,
+    /// The address book entry's address.
+    pub address: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+}
+
+/// Address book entries by `(chain, normalized address)`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AddressBook {
+    entries: HashMap<(String, String), AddressEntry>,
+}
+
+impl AddressBook {
+    /// Build the book, rejecting malformed and duplicate addresses.
+    pub fn new(entries: &[AddressEntry]) -> anyhow::Result<Self> {
+        let mut book = HashMap::new();
+        for entry in entries {
+            let (chain, address) = entry
+                .address
+                .split_once(':')
+                .filter(|(chain, _)| matches!(*chain, "ethereum" | "solana"))
+                .ok_or_else(|| {
+                    anyhow!(
+                        "address book entry '{}' must be ethereum:<address> or solana:<address>",
+                        entry.address
+                    )
+                })?;
+            let key = (chain.to_string(), normalize(address));
+            if book.insert(key, entry.clone()).is_some() {
+                bail!("address {} is in the address book twice", entry.address);
+            }
+        }
+        Ok(AddressBook { entries: book })
+    }
+
+    fn get(&self, chain: &str, address: &str) -> Option<&AddressEntry> {
+        self.entries.get(&(chain.to_string(), normalize(address)))
+    }
+
+    /// The label of `address` on `chain`, if the book has one.
+    pub fn label(&self, chain: &str, address: &str) -> Option<&str> {
+        self.get(chain, address)?.label.as_deref()
+    }
+
+    /// The `unexpected_activity` alert for `event`, if it is an outgoing
+    /// transaction of a cold-storage address.
+    pub fn unexpected_activity(&self, event: &Event) -> Option<Event> {
+        if self.entries.is_empty() || !ACTIVITY_TYPES.contains(&event.event_type) {
+            return None;
+        }
+        let sender = match event.chain.as_str() {
+            "solana" => event
+                .watched_address
+                .as_deref()
+                .filter(|_| event.signed_by_watched)?,
+            _ => event.from.as_str(),
+        };
+        let entry = self
+            .get(&event.chain, sender)
+            .filter(|entry| entry.cold_storage)?;
+        Some(Event {
+            event_id: format!("{}:unexpected_activity", event.event_id),
+            event_type: EventType::UnexpectedActivity,
+            alert: Some(Alert {
+                rule: COLD_STORAGE_OUTGOING.to_string(),
+                severity: entry.severity,
+                address: sender.to_string(),
+                label: entry.label.clone(),
+            }),
+            ..event.clone()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::publisher::Publisher;
+    use crate::trace_context::TraceContext;
+
+    const COLD: &str = "0x70997970C51812dc3A010C7d01b50e0d17dc79C8";
+
+    fn book() -> AddressBook {
+        AddressBook::new(&[
+            AddressEntry {
+                address: format!("ethereum:{}", COLD),
+                label: Some("Treasury cold wallet".into()),
+                cold_storage: true,
+                severity: Severity::Critical,
+            },
+            AddressEntry {
+                address: "solana:Cold111".into(),
+                label: None,
+                cold_storage: true,
+                severity: Severity::default(),
+            },
+            AddressEntry {
+                address: "ethereum:0x02".into(),
+                label: Some("Hot wallet".into()),
+                cold_storage: false,
+                severity: Severity::default(),
+            },
+        ])
+        .unwrap()
+    }
+
+    fn eth_transfer(from: &str, to: &str) -> Event {
+        Event {
+            event_id: "eth:0xabc".into(),
+            chain: "ethereum".into(),
+            from: from.into(),
+            to: to.into(),
+            event_type: EventType::Transfer,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_address_book_validation() {
+        let entry = |address: &str| AddressEntry {
+            address: address.into(),
+            label: None,
+            cold_storage: true,
+            severity: Severity::Low,
+        };
+        let err = AddressBook::new(&[entry("0x01")]).unwrap_err();
+        assert!(err.to_string().contains("'0x01'"), "{}", err);
+        assert!(AddressBook::new(&[entry("ethereum:0xAB"), entry("ethereum:0xab")]).is_err());
+    }
+
+    #[test]
+    fn test_outgoing_cold_storage_transfer_alerts() {
+        let book = book();
+        let event = eth_transfer(&COLD.to_ascii_lowercase(), "0x03");
+        let alert = book.unexpected_activity(&event).unwrap();
+        assert_eq!(alert.event_id, "eth:0xabc:unexpected_activity");
+        assert_eq!(alert.event_type, EventType::UnexpectedActivity);
+        assert_eq!(alert.tx_hash, event.tx_hash);
+        let json = serde_json::to_value(&alert).unwrap();
+        assert_eq!(
+            json["alert"],
+            serde_json::json!({
+                "rule": "cold_storage_outgoing",
+                "severity": "critical",
+                "address": COLD.to_ascii_lowercase(),
+                "label": "Treasury cold wallet",
+            })
+        );
+
+        // Incoming transfers, hot wallets and alerts themselves don't.
+        assert!(book
+            .unexpected_activity(&eth_transfer("0x03", COLD))
+            .is_none());
+        assert!(book
+            .unexpected_activity(&eth_transfer("0x02", "0x03"))
+            .is_none());
+        assert!(book.unexpected_activity(&alert).is_none());
+    }
+
+    #[test]
+    fn test_solana_alerts_only_when_signed() {
+        let book = book();
+        let mut event = Event {
+            chain: "solana".into(),
+            event_type: EventType::SolanaTx,
+            watched_address: Some("Cold111".into()),
+            ..Default::default()
+        };
+        assert!(book.unexpected_activity(&event).is_none());
+
+        event.signed_by_watched = true;
+        let alert = book.unexpected_activity(&event).unwrap();
+        assert_eq!(alert.alert.unwrap().severity, Severity::High);
+    }
+
+    #[tokio::test]
+    async fn test_publisher_follows_event_with_alert() {
+        let (publisher, mut rx) = Publisher::in_memory();
+        let publisher = publisher.with_address_book(book());
+        publisher
+            .publish(
+                &eth_transfer(&COLD.to_ascii_lowercase(), "0x03"),
+                TraceContext::new_root(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(rx.recv().await.unwrap().event_id, "eth:0xabc");
+        let alert: serde_json::Value =
+            serde_json::from_str(&rx.recv().await.unwrap().payload).unwrap();
+        assert_eq!(alert["event_type"], "unexpected_activity");
+        assert_eq!(alert["alert"]["severity"], "critical");
+        assert!(rx.try_recv().is_err());
+    }
+}
@@ -0,0 +1,1071 @@
+//! Admin HTTP API for operating a running listener.
+//!
+//! Started only when `ADMIN_BIND_ADDR` is set. Endpoints:
+//!
+//! - `GET /admin/chains` — pause state and checkpoint of every chain
+//! - `POST /admin/chains/{chain}/pause` — stop a chain's trackers after the
+//!   block or transaction in flight
+//! - `POST /admin/chains/{chain}/resume` — reconnect and continue from the
+//!   checkpoint
+//! - `GET /admin/expectations` — pending expected transfers
+//! - `POST /admin/expectations` — register an expected transfer (see
+//!   `expectations.rs`); 400 with the reason if it is invalid
+//! - `DELETE /admin/expectations/{id}` — cancel a pending expectation
+//! - `GET /admin/webhooks` — webhook subscriptions
+//! - `POST /admin/webhooks` — subscribe an endpoint to published events (see
+//!   `webhooks.rs`); 400 with the reason if it is invalid
+//! - `DELETE /admin/webhooks/{id}` — remove a subscription
+//! - `GET /admin/webhooks/{id}/deliveries` — the subscription's delivery
+//!   log, most recent first
+//! - `POST /admin/webhooks/{id}/redeliver` — send a logged range of events
+//!   again; 400 if the range isn't in the log
+//! - `POST /admin/webhooks/{id}/ack` — the consumer confirms it processed
+//!   some event ids; answers with its offset. 400 if the subscription has no
+//!   `ack_deadline_secs`
+//! - `GET /admin/webhooks/{id}/offset` — the consumer's offset and the
+//!   events it hasn't acknowledged
+//! - `GET /admin/history/{chain}/{address}?at={block or slot}` — the
+//!   address's balances at a past height (see `history.rs`); 400 for a
+//!   malformed address, 502 if the node can't answer
+//! - `GET /admin/rpc-errors` — malformed RPC responses since startup, by
+//!   chain and kind (see `rpc_schema.rs`)
+//! - `GET /admin/rpc` — the Ethereum endpoints, which one tracking uses and
+//!   the last consistency check of each (see `rpc_guard.rs`)
+//! - `GET /admin/event-types` — every `event_type` with what it means (see
+//!   `event_type.rs`)
+//! - `GET /admin/events?chain=&address=&since=` — recently published
+//!   events, newest first; 400 for a malformed `since` (see `inspect.rs`).
+//!   Also served as `GET /events/recent`
+//! - `GET /admin/status` — each chain's status with its head, lag and last
+//!   publish time
+//! - `GET /admin/watched` — watched addresses with their labels and last
+//!   activity
+//! - `POST /admin/watched/{chain}/{address}` — start watching an Ethereum or
+//!   Solana address (see `watchlist.rs`); 201 with the chain's watched
+//!   addresses, 200 if it already was watched, 400 if it is malformed
+//! - `DELETE /admin/watched/{chain}/{address}` — stop watching it; 404 if it
+//!   wasn't watched
+//! - `POST /admin/watched/{chain}/{address}/backfill` — publish the watched
+//!   address's indexer history as backfill (see `bootstrap.rs`); 400 without
+//!   an indexer key for the chain, 502 if the indexer fails
+//! - `GET /admin/dashboard` — events per minute, value transferred per hour
+//!   and publish lag, shaped for the bundled Grafana dashboard (see
+//!   `dashboard.rs`)
+//! - `POST /admin/streams/{destination}/replay` — copy a stream
+//!   destination's entries after an id to a replay stream, including
+//!   trimmed ones from its archive (see `streams.rs`); 404 if the
+//!   destination isn't a stream, 400 for a malformed id
+//! - `GET /admin/quarantine` — events that failed every attempt, newest
+//!   first (see `quarantine.rs`)
+//! - `POST /admin/quarantine/{id}/requeue` — send a quarantined event
+//!   through the stage it failed again; 404 if there is no such event, 503
+//!   if its destination's queue is full
+//! - `GET /admin/retention` — runs of the `retention` job since startup, per
+//!   destination and for the checkpoints, with what each removed (see
+//!   `retention.rs`)
+//!
+//! `{chain}` is the event `chain` name (`ethereum`, `solana`, an
+//! `[[evm_networks]]` or `[[substrate_networks]]` chain such as `polygon`,
+//! or `tron`). If `ADMIN_TOKEN` is set, requests must send
+//! `Authorization: Bearer <token>`.
+//! The chain and watch list operations are also available over gRPC with
+//! the `grpc` feature (see `grpc.rs`).
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use axum::extract::{Path, Query, State};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::routing::{delete, get, post};
+use axum::{Json, Router};
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+use tracing::info;
+
+use crate::bootstrap::Backfill;
+use crate::control::{ChainControl, Controls};
+use crate::dashboard::{Dashboard, DashboardData};
+use crate::event_type::EventType;
+use crate::expectations::{Expectation, Expectations, NewExpectation};
+use crate::history::{AddressState, BadRequest, History};
+use crate::inspect::{EventsQuery, Inspect, WatchedAddress};
+use crate::publisher::Publisher;
+use crate::quarantine::QuarantinedEvent;
+use crate::retention::{self, TargetStats};
+use crate::rpc_guard::{EndpointHealth, RpcGuard};
+use crate::rpc_schema::{self, FailureCount};
+use crate::streams::{Replay, ReplayRequest, Streams};
+use crate::watchlist::{WatchLists, WatchedAddresses};
+use crate::webhooks::{
+    Acknowledgement, ConsumerOffset, DeliveryRecord, NewSubscription, RedeliverRange, Subscription,
+    Webhooks,
+};
+
+#[derive(Clone)]
+pub struct AdminState {
+    pub controls: Controls,
+    pub last_eth_block: Arc<Mutex<Option<u64>>>,
+    pub last_sol_slot: Arc<Mutex<Option<u64>>>,
+    /// Last processed block of each `[[evm_networks]]` and
+    /// `[[substrate_networks]]` chain, and Tron's checkpoint, by chain name.
+    pub last_evm_blocks: HashMap<&'static str, Arc<Mutex<Option<u64>>>>,
+    pub token: Option<String>,
+    pub expectations: Expectations,
+    pub webhooks: Webhooks,
+    pub history: History,
+    pub streams: Streams,
+    /// `REGION` and `INSTANCE_ID`, reported with each chain's status.
+    pub region: Option<String>,
+    pub instance_id: String,
+    pub rpc: RpcGuard,
+    pub dashboard: Dashboard,
+    pub inspect: Inspect,
+    /// Requeues quarantined events and publishes backfills.
+    pub publisher: Publisher,
+    /// The Ethereum and Solana addresses the trackers follow.
+    pub watched: WatchLists,
+    pub backfill: Backfill,
+}
+
+/// Response of a backfill.
+#[derive(Debug, Serialize)]
+pub(crate) struct Backfilled {
+    /// Events published; the rest had been published before.
+    pub published: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct ChainStatus {
+    pub chain: &'static str,
+    pub paused: bool,
+    /// Whether the tracker's last RPC call succeeded.
+    pub connected: bool,
+    /// Last processed block (EVM and Substrate chains), slot (Solana) or
+    /// block timestamp in milliseconds (Tron).
+    pub checkpoint: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub region: Option<String>,
+    pub instance_id: String,
+}
+
+impl AdminState {
+    pub(crate) async fn status(&self, control: &ChainControl) -> ChainStatus {
+        let last = match control.chain() {
+            chain if chain == self.controls.eth.chain() => Some(&self.last_eth_block),
+            chain if chain == self.controls.sol.chain() => Some(&self.last_sol_slot),
+            chain => self.last_evm_blocks.get(chain),
+        };
+        let checkpoint = match last {
+            Some(last) => *last.lock().await,
+            None => None,
+        };
+        ChainStatus {
+            chain: control.chain(),
+            paused: control.is_paused(),
+            connected: control.is_connected(),
+            checkpoint,
+            region: self.region.clone(),
+            instance_id: self.instance_id.clone(),
+        }
+    }
+
+    /// Status of every chain, in `GET /admin/chains` order.
+    pub(crate) async fn statuses(&self) -> Vec<ChainStatus> {
+        let mut chains = Vec::new();
+        for control in self.controls.all() {
+            chains.push(self.status(control).await);
+        }
+        chains
+    }
+
+    /// Backfill `address` of `chain`. `None` if it isn't watched.
+    pub(crate) async fn backfill(
+        &self,
+        chain: &str,
+        address: &str,
+    ) -> Option<anyhow::Result<Backfilled>> {
+        if !self.watched.get(chain)?.contains(address) {
+            return None;
+        }
+        let published = self.backfill.run(chain, address, &self.publisher).await;
+        Some(published.map(|published| Backfilled { published }))
+    }
+
+    /// Check an `Authorization` header value against `ADMIN_TOKEN`.
+    pub(crate) fn authorized(&self, authorization: Option<&str>) -> bool {
+        match &self.token {
+            None => true,
+            Some(token) => {
+                authorization.and_then(|v| v.strip_prefix("Bearer ")) == Some(token.as_str())
+            }
+        }
+    }
+
+    fn authorize(&self, headers: &HeaderMap) -> Result<(), StatusCode> {
+        let presented = headers
+            .get(header::AUTHORIZATION)
+            .and_then(|v| v.to_str().ok());
+        if self.authorized(presented) {
+            Ok(())
+        } else {
+            Err(StatusCode::UNAUTHORIZED)
+        }
+    }
+}
+
+pub fn router(state: AdminState) -> Router {
+    Router::new()
+        .route("/admin/chains", get(list_chains))
+        .route("/admin/chains/:chain/pause", post(pause_chain))
+        .route("/admin/chains/:chain/resume", post(resume_chain))
+        .route(
+            "/admin/expectations",
+            get(list_expectations).post(create_expectation),
+        )
+        .route("/admin/expectations/:id", delete(cancel_expectation))
+        .route("/admin/webhooks", get(list_webhooks).post(create_webhook))
+        .route("/admin/webhooks/:id", delete(delete_webhook))
+        .route("/admin/webhooks/:id/deliveries", get(list_deliveries))
+        .route("/admin/webhooks/:id/redeliver", post(redeliver))
+        .route("/admin/webhooks/:id/ack", post(acknowledge))
+        .route("/admin/webhooks/:id/offset", get(consumer_offset))
+        .route("/admin/history/:chain/:address", get(address_history))
+        .route("/admin/rpc-errors", get(list_rpc_errors))
+        .route("/admin/rpc", get(list_rpc_endpoints))
+        .route("/admin/event-types", get(list_event_types))
+        .route("/admin/dashboard", get(dashboard_data))
+        .route("/admin/events", get(recent_events))
+        .route("/events/recent", get(recent_events))
+        .route("/admin/status", get(chain_status))
+        .route("/admin/watched", get(list_watched))
+        .route(
+            "/admin/watched/:chain/:address",
+            post(watch_address).delete(unwatch_address),
+        )
+        .route(
+            "/admin/watched/:chain/:address/backfill",
+            post(backfill_address),
+        )
+        .route("/admin/streams/:destination/replay", post(replay_stream))
+        .route("/admin/quarantine", get(list_quarantine))
+        .route("/admin/quarantine/:id/requeue", post(requeue))
+        .route("/admin/retention", get(list_retention))
+        .with_state(state)
+}
+
+/// Serve the admin API until the process exits.
+pub async fn serve(addr: &str, state: AdminState) -> anyhow::Result<()> {
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+    info!("Admin API listening on {}", listener.local_addr()?);
+    axum::serve(listener, router(state)).await?;
+    Ok(())
+}
+
+async fn list_chains(
+    State(state): State<AdminState>,
+    headers: HeaderMap,
+) -> Result<Json<Vec<ChainStatus>>, StatusCode> {
+    state.authorize(&headers)?;
+    Ok(Json(state.statuses().await))
+}
+
+async fn pause_chain(
+    State(state): State<AdminState>,
+    Path(chain): Path<String>,
+    headers: HeaderMap,
+) -> Result<Json<ChainStatus>, StatusCode> {
+    state.authorize(&headers)?;
+    let control = state.controls.get(&chain).ok_or(StatusCode::NOT_FOUND)?;
+    control.pause();
+    Ok(Json(state.status(control).await))
+}
+
+async fn resume_chain(
+    State(state): State<AdminState>,
+    Path(chain): Path<String>,
+    headers: HeaderMap,
+) -> Result<Json<ChainStatus>, StatusCode> {
+    state.authorize(&headers)?;
+    let control = state.controls.get(&chain).ok_or(StatusCode::NOT_FOUND)?;
+    control.resume();
+    Ok(Json(state.status(control).await))
+}
+
+async fn list_expectations(
+    State(state): State<AdminState>,
+    headers: HeaderMap,
+) -> Result<Json<Vec<Expectation>>, StatusCode> {
+    state.authorize(&headers)?;
+    Ok(Json(state.expectations.list()))
+}
+
+async fn create_expectation(
+    State(state): State<AdminState>,
+    headers: HeaderMap,
+    Json(new): Json<NewExpectation>,
+) -> Result<(StatusCode, Json<Expectation>), (StatusCode, String)> {
+    state
+        .authorize(&headers)
+        .map_err(|status| (status, String::new()))?;
+    match state.expectations.register(new, chrono::Utc::now()) {
+        Ok(expectation) => Ok((StatusCode::CREATED, Json(expectation))),
+        Err(e) => Err((StatusCode::BAD_REQUEST, e.to_string())),
+    }
+}
+
+async fn cancel_expectation(
+    State(state): State<AdminState>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+) -> StatusCode {
+    if let Err(status) = state.authorize(&headers) {
+        return status;
+    }
+    if state.expectations.cancel(&id) {
+        StatusCode::NO_CONTENT
+    } else {
+        StatusCode::NOT_FOUND
+    }
+}
+
+async fn list_webhooks(
+    State(state): State<AdminState>,
+    headers: HeaderMap,
+) -> Result<Json<Vec<Subscription>>, StatusCode> {
+    state.authorize(&headers)?;
+    Ok(Json(state.webhooks.list()))
+}
+
+async fn create_webhook(
+    State(state): State<AdminState>,
+    headers: HeaderMap,
+    Json(new): Json<NewSubscription>,
+) -> Result<(StatusCode, Json<Subscription>), (StatusCode, String)> {
+    state
+        .authorize(&headers)
+        .map_err(|status| (status, String::new()))?;
+    match state.webhooks.register(new) {
+        Ok(subscription) => Ok((StatusCode::CREATED, Json(subscription))),
+        Err(e) => Err((StatusCode::BAD_REQUEST, format!("{:#}", e))),
+    }
+}
+
+async fn delete_webhook(
+    State(state): State<AdminState>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+) -> Result<StatusCode, (StatusCode, String)> {
+    state
+        .authorize(&headers)
+        .map_err(|status| (status, String::new()))?;
+    match state.webhooks.unregister(&id) {
+        Ok(true) => Ok(StatusCode::NO_CONTENT),
+        Ok(false) => Err((StatusCode::NOT_FOUND, String::new())),
+        Err(e) => Err((StatusCode::INTERNAL_SERVER_ERROR, format!("{:#}", e))),
+    }
+}
+
+async fn list_deliveries(
+    State(state): State<AdminState>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+) -> Result<Json<Vec<DeliveryRecord>>, StatusCode> {
+    state.authorize(&headers)?;
+    state
+        .webhooks
+        .deliveries(&id)
+        .map(Json)
+        .ok_or(StatusCode::NOT_FOUND)
+}
+
+/// `POST /admin/webhooks/{id}/redeliver` response.
+#[derive(Debug, Serialize)]
+struct Redelivery {
+    /// Events queued again; fewer than requested if the queue filled up.
+    queued: usize,
+}
+
+async fn redeliver(
+    State(state): State<AdminState>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+    Json(range): Json<RedeliverRange>,
+) -> Result<Json<Redelivery>, (StatusCode, String)> {
+    state
+        .authorize(&headers)
+        .map_err(|status| (status, String::new()))?;
+    if state.webhooks.get(&id).is_none() {
+        return Err((StatusCode::NOT_FOUND, String::new()));
+    }
+    match state.webhooks.redeliver(&id, &range) {
+        Ok(queued) => Ok(Json(Redelivery { queued })),
+        Err(e) => Err((StatusCode::BAD_REQUEST, e.to_string())),
+    }
+}
+
+async fn acknowledge(
+    State(state): State<AdminState>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+    Json(ack): Json<Acknowledgement>,
+) -> Result<Json<ConsumerOffset>, (StatusCode, String)> {
+    state
+        .authorize(&headers)
+        .map_err(|status| (status, String::new()))?;
+    if state.webhooks.get(&id).is_none() {
+        return Err((StatusCode::NOT_FOUND, String::new()));
+    }
+    match state.webhooks.acknowledge(&id, &ack.event_ids) {
+        Ok(offset) => Ok(Json(offset)),
+        Err(e) => Err((StatusCode::BAD_REQUEST, e.to_string())),
+    }
+}
+
+async fn consumer_offset(
+    State(state): State<AdminState>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+) -> Result<Json<ConsumerOffset>, StatusCode> {
+    state.authorize(&headers)?;
+    state
+        .webhooks
+        .offset(&id)
+        .map(Json)
+        .ok_or(StatusCode::NOT_FOUND)
+}
+
+async fn list_retention(
+    State(state): State<AdminState>,
+    headers: HeaderMap,
+) -> Result<Json<Vec<TargetStats>>, StatusCode> {
+    state.authorize(&headers)?;
+    Ok(Json(retention::stats()))
+}
+
+async fn list_rpc_errors(
+    State(state): State<AdminState>,
+    headers: HeaderMap,
+) -> Result<Json<Vec<FailureCount>>, StatusCode> {
+    state.authorize(&headers)?;
+    Ok(Json(rpc_schema::failures()))
+}
+
+async fn list_rpc_endpoints(
+    State(state): State<AdminState>,
+    headers: HeaderMap,
+) -> Result<Json<Vec<EndpointHealth>>, StatusCode> {
+    state.authorize(&headers)?;
+    Ok(Json(state.rpc.health()))
+}
+
+/// An entry of `GET /admin/event-types`.
+#[derive(Debug, Serialize)]
+struct EventTypeInfo {
+    name: EventType,
+    description: &'static str,
+}
+
+async fn list_event_types(
+    State(state): State<AdminState>,
+    headers: HeaderMap,
+) -> Result<Json<Vec<EventTypeInfo>>, StatusCode> {
+    state.authorize(&headers)?;
+    Ok(Json(
+        EventType::ALL
+            .into_iter()
+            .map(|name| EventTypeInfo {
+                name,
+                description: name.description(),
+            })
+            .collect(),
+    ))
+}
+
+async fn dashboard_data(
+    State(state): State<AdminState>,
+    headers: HeaderMap,
+) -> Result<Json<DashboardData>, StatusCode> {
+    state.authorize(&headers)?;
+    Ok(Json(state.dashboard.data(chrono::Utc::now().timestamp())))
+}
+
+async fn recent_events(
+    State(state): State<AdminState>,
+    Query(query): Query<EventsQuery>,
+    headers: HeaderMap,
+) -> Result<Json<Vec<serde_json::Value>>, (StatusCode, String)> {
+    state
+        .authorize(&headers)
+        .map_err(|status| (status, String::new()))?;
+    state
+        .inspect
+        .events(&query)
+        .map(Json)
+        .map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))
+}
+
+/// An entry of `GET /admin/status`.
+#[derive(Debug, Serialize)]
+struct ChainInspection {
+    #[serde(flatten)]
+    status: ChainStatus,
+    /// Latest block or slot of the node; null if it didn't answer.
+    head: Option<u64>,
+    /// Blocks or slots the checkpoint is behind `head`.
+    lag: Option<u64>,
+    /// When the chain last published an event (RFC 3339).
+    last_event_at: Option<String>,
+}
+
+async fn chain_status(
+    State(state): State<AdminState>,
+    headers: HeaderMap,
+) -> Result<Json<Vec<ChainInspection>>, StatusCode> {
+    state.authorize(&headers)?;
+    let (eth_head, sol_head) = tokio::join!(state.rpc.head(), state.inspect.sol_head());
+    let mut chains = Vec::new();
+    // Heads are only fetched for Ethereum and Solana.
+    let heads = [eth_head, sol_head]
+        .into_iter()
+        .chain(std::iter::repeat(None));
+    for (status, head) in state.statuses().await.into_iter().zip(heads) {
+        let lag = head
+            .zip(status.checkpoint)
+            .map(|(head, checkpoint)| head.saturating_sub(checkpoint));
+        let last_event_at = state
+            .inspect
+            .last_published(status.chain)
+            .map(|at| at.to_rfc3339());
+        chains.push(ChainInspection {
+            status,
+            head,
+            lag,
+            last_event_at,
+        });
+    }
+    Ok(Json(chains))
+}
+
+async fn list_watched(
+    State(state): State<AdminState>,
+    headers: HeaderMap,
+) -> Result<Json<Vec<WatchedAddress>>, StatusCode> {
+    state.authorize(&headers)?;
+    Ok(Json(state.inspect.watched()))
+}
+
+async fn watch_address(
+    State(state): State<AdminState>,
+    Path((chain, address)): Path<(String, String)>,
+    headers: HeaderMap,
+) -> Result<(StatusCode, Json<WatchedAddresses>), (StatusCode, String)> {
+    state
+        .authorize(&headers)
+        .map_err(|status| (status, String::new()))?;
+    let list = state
+        .watched
+        .get(&chain)
+        .ok_or((StatusCode::NOT_FOUND, String::new()))?;
+    match list.add(&address) {
+        Ok(true) => Ok((StatusCode::CREATED, Json(list.snapshot()))),
+        Ok(false) => Ok((StatusCode::OK, Json(list.snapshot()))),
+        Err(e) => Err((StatusCode::BAD_REQUEST, e.to_string())),
+    }
+}
+
+async fn unwatch_address(
+    State(state): State<AdminState>,
+    Path((chain, address)): Path<(String, String)>,
+    headers: HeaderMap,
+) -> Result<Json<WatchedAddresses>, StatusCode> {
+    state.authorize(&headers)?;
+    let list = state.watched.get(&chain).ok_or(StatusCode::NOT_FOUND)?;
+    if !list.remove(&address) {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    Ok(Json(list.snapshot()))
+}
+
+async fn backfill_address(
+    State(state): State<AdminState>,
+    Path((chain, address)): Path<(String, String)>,
+    headers: HeaderMap,
+) -> Result<Json<Backfilled>, (StatusCode, String)> {
+    state
+        .authorize(&headers)
+        .map_err(|status| (status, String::new()))?;
+    match state.backfill(&chain, &address).await {
+        Some(Ok(backfilled)) => Ok(Json(backfilled)),
+        None => Err((StatusCode::NOT_FOUND, String::new())),
+        Some(Err(e)) if e.is::<BadRequest>() => Err((StatusCode::BAD_REQUEST, e.to_string())),
+        Some(Err(e)) => Err((StatusCode::BAD_GATEWAY, format!("{:#}", e))),
+    }
+}
+
+async fn replay_stream(
+    State(state): State<AdminState>,
+    Path(destination): Path<String>,
+    headers: HeaderMap,
+    Json(req): Json<ReplayRequest>,
+) -> Result<Json<Replay>, (StatusCode, String)> {
+    state
+        .authorize(&headers)
+        .map_err(|status| (status, String::new()))?;
+    match state.streams.replay(&destination, &req).await {
+        Ok(Some(replay)) => Ok(Json(replay)),
+        Ok(None) => Err((StatusCode::NOT_FOUND, String::new())),
+        Err(e) if e.is::<BadRequest>() => Err((StatusCode::BAD_REQUEST, e.to_string())),
+        Err(e) => Err((StatusCode::BAD_GATEWAY, format!("{:#}", e))),
+    }
+}
+
+async fn list_quarantine(
+    State(state): State<AdminState>,
+    headers: HeaderMap,
+) -> Result<Json<Vec<QuarantinedEvent>>, StatusCode> {
+    state.authorize(&headers)?;
+    Ok(Json(state.publisher.quarantine().list()))
+}
+
+async fn requeue(
+    State(state): State<AdminState>,
+    Path(id): Path<u64>,
+    headers: HeaderMap,
+) -> Result<StatusCode, (StatusCode, String)> {
+    state
+        .authorize(&headers)
+        .map_err(|status| (status, String::new()))?;
+    match state.publisher.requeue(id).await {
+        Ok(true) => Ok(StatusCode::NO_CONTENT),
+        Ok(false) => Err((StatusCode::NOT_FOUND, String::new())),
+        Err(e) => Err((StatusCode::SERVICE_UNAVAILABLE, format!("{:#}", e))),
+    }
+}
+
+/// `GET /admin/history/{chain}/{address}` query.
+#[derive(Debug, Deserialize)]
+struct HistoryQuery {
+    /// Block (Ethereum) or slot (Solana).
+    at: u64,
+}
+
+async fn address_history(
+    State(state): State<AdminState>,
+    Path((chain, address)): Path<(String, String)>,
+    Query(query): Query<HistoryQuery>,
+    headers: HeaderMap,
+) -> Result<Json<AddressState>, (StatusCode, String)> {
+    state
+        .authorize(&headers)
+        .map_err(|status| (status, String::new()))?;
+    match state.history.state(&chain, &address, query.at).await {
+        Ok(address_state) => Ok(Json(address_state)),
+        Err(e) if e.is::<BadRequest>() => Err((StatusCode::BAD_REQUEST, e.to_string())),
+        Err(e) => Err((StatusCode::BAD_GATEWAY, format!("{:#}", e))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::quarantine::{Retry, Stage};
+    use axum::body::{to_bytes, Body};
+    use axum::http::Request;
+    use tower::ServiceExt;
+
+    fn state(token: Option<&str>) -> AdminState {
+        AdminState {
+            controls: Controls::default(),
+            last_eth_block: Arc::new(Mutex::new(Some(42))),
+            last_sol_slot: Arc::new(Mutex::new(None)),
+            last_evm_blocks: HashMap::new(),
+            token: token.map(str::to_string),
+            expectations: Expectations::default(),
+            webhooks: Webhooks::default(),
+            history: History::default(),
+            streams: Streams::default(),
+            region: None,
+            instance_id: "tracker".into(),
+            rpc: RpcGuard::default(),
+            dashboard: Dashboard::default(),
+            inspect: Inspect::default(),
+            publisher: Publisher::in_memory().0,
+            watched: WatchLists::default(),
+            backfill: Backfill::default(),
+        }
+    }
+
+    async fn call(state: &AdminState, method: &str, uri: &str) -> (StatusCode, String) {
+        let req = Request::builder()
+            .method(method)
+            .uri(uri)
+            .body(Body::empty())
+            .unwrap();
+        let res = router(state.clone()).oneshot(req).await.unwrap();
+        let status = res.status();
+        let body = to_bytes(res.into_body(), usize::MAX).await.unwrap();
+        (status, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_pause_and_resume_chain() {
+        let state = state(None);
+
+        let (status, body) = call(&state, "POST", "/admin/chains/ethereum/pause").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            body,
+            r#"{"chain":"ethereum","paused":true,"connected":false,"checkpoint":42}"#
+        );
+        assert!(state.controls.eth.is_paused());
+        assert!(!state.controls.sol.is_paused());
+
+        let (status, _) = call(&state, "POST", "/admin/chains/ethereum/resume").await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(!state.controls.eth.is_paused());
+    }
+
+    #[tokio::test]
+    async fn test_list_and_unknown_chain() {
+        let state = state(None);
+        let (status, body) = call(&state, "GET", "/admin/chains").await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains(concat!(
+            r#"{"chain":"solana","paused":false,"connected":false,"checkpoint":null,"#,
+            r#""instance_id":"tracker"}"#
+        )));
+
+        let (status, _) = call(&state, "POST", "/admin/chains/bitcoin/pause").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_evm_network_chain() {
+        let mut state = state(None);
+        let polygon = state
+            .controls
+            .add_evm("polygon", &tokio_util::sync::CancellationToken::new());
+        state
+            .last_evm_blocks
+            .insert("polygon", Arc::new(Mutex::new(Some(7))));
+
+        let (status, body) = call(&state, "POST", "/admin/chains/polygon/pause").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            body,
+            concat!(
+                r#"{"chain":"polygon","paused":true,"connected":false,"checkpoint":7,"#,
+                r#""instance_id":"tracker"}"#
+            )
+        );
+        assert!(polygon.is_paused());
+        let (_, body) = call(&state, "GET", "/admin/chains").await;
+        assert_eq!(body.matches(r#""chain":"#).count(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_token_required_when_configured() {
+        let state = state(Some("s3cret"));
+        let (status, _) = call(&state, "POST", "/admin/chains/solana/pause").await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert!(!state.controls.sol.is_paused());
+
+        let req = Request::builder()
+            .method("POST")
+            .uri("/admin/chains/solana/pause")
+            .header(header::AUTHORIZATION, "Bearer s3cret")
+            .body(Body::empty())
+            .unwrap();
+        let res = router(state.clone()).oneshot(req).await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        assert!(state.controls.sol.is_paused());
+    }
+
+    #[tokio::test]
+    async fn test_expectations_crud() {
+        let mut state = state(None);
+        state.expectations = Expectations::new(&crate::config::Config {
+            watched_addresses_eth: vec!["0x01".into()],
+            ..Default::default()
+        });
+        let post = |body: &str| {
+            Request::builder()
+                .method("POST")
+                .uri("/admin/expectations")
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(body.to_string()))
+                .unwrap()
+        };
+
+        let res = router(state.clone())
+            .oneshot(post(
+                r#"{"id":"order-1","chain":"ethereum","address":"0x01","min_amount":"5","deadline":"2999-01-01T00:00:00Z"}"#,
+            ))
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::CREATED);
+        let res = router(state.clone())
+            .oneshot(post(
+                r#"{"chain":"ethereum","address":"0x02","min_amount":"5","deadline":"2999-01-01T00:00:00Z"}"#,
+            ))
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+
+        let (_, body) = call(&state, "GET", "/admin/expectations").await;
+        assert_eq!(
+            body,
+            r#"[{"id":"order-1","chain":"ethereum","address":"0x01","min_amount":"5","deadline":"2999-01-01T00:00:00Z"}]"#
+        );
+        let (status, _) = call(&state, "DELETE", "/admin/expectations/order-1").await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        let (status, _) = call(&state, "DELETE", "/admin/expectations/order-1").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_history_errors() {
+        let state = state(None);
+        let (status, body) = call(&state, "GET", "/admin/history/bitcoin/abc?at=1").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(body.contains("unknown chain"));
+
+        let (status, _) = call(&state, "GET", "/admin/history/ethereum/0x01").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        // No node configured.
+        let uri = format!(
+            "/admin/history/ethereum/{:?}?at=1",
+            ethers::types::Address::zero()
+        );
+        let (status, _) = call(&state, "GET", &uri).await;
+        assert_eq!(status, StatusCode::BAD_GATEWAY);
+    }
+
+    #[tokio::test]
+    async fn test_rpc_errors() {
+        let log = ethers::types::Log::default();
+        assert!(rpc_schema::eth_log(&log).is_err());
+        let (status, body) = call(&state(None), "GET", "/admin/rpc-errors").await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(
+            body.contains(r#"{"chain":"ethereum","kind":"missing transactionHash","count":"#),
+            "{}",
+            body
+        );
+    }
+
+    #[tokio::test]
+    async fn test_rpc_endpoints() {
+        let (status, body) = call(&state(None), "GET", "/admin/rpc").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            body,
+            r#"[{"url":"","active":true,"status":"unchecked","head":null}]"#
+        );
+    }
+
+    #[tokio::test]
+    async fn test_event_types() {
+        let (status, body) = call(&state(None), "GET", "/admin/event-types").await;
+        assert_eq!(status, StatusCode::OK);
+        let types: Vec<serde_json::Value> = serde_json::from_str(&body).unwrap();
+        assert_eq!(types.len(), EventType::ALL.len());
+        assert_eq!(types[1]["name"], "erc20_transfer");
+        assert!(types.iter().all(|t| t["description"].as_str().is_some()));
+    }
+
+    #[tokio::test]
+    async fn test_inspection_endpoints() {
+        let state = state(None);
+        let (status, body) = call(&state, "GET", "/admin/status").await;
+        assert_eq!(status, StatusCode::OK);
+        let chains: Vec<serde_json::Value> = serde_json::from_str(&body).unwrap();
+        assert_eq!(chains[0]["chain"], "ethereum");
+        assert_eq!(chains[0]["checkpoint"], 42);
+        // No node to ask in tests.
+        assert_eq!(chains[0]["head"], serde_json::Value::Null);
+        assert_eq!(chains[1]["lag"], serde_json::Value::Null);
+
+        let (status, body) = call(&state, "GET", "/admin/events?chain=ethereum").await;
+        assert_eq!((status, body.as_str()), (StatusCode::OK, "[]"));
+        let (status, body) = call(&state, "GET", "/events/recent").await;
+        assert_eq!((status, body.as_str()), (StatusCode::OK, "[]"));
+        let (status, _) = call(&state, "GET", "/admin/events?since=yesterday").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let (status, body) = call(&state, "GET", "/admin/watched").await;
+        assert_eq!((status, body.as_str()), (StatusCode::OK, "[]"));
+    }
+
+    #[tokio::test]
+    async fn test_watch_list_changes() {
+        const ADDRESS: &str = "0x70997970c51812dc3a010c7d01b50e0d17dc79c8";
+        let state = state(None);
+        let uri = format!("/admin/watched/ethereum/{}", ADDRESS);
+
+        let (status, body) = call(&state, "POST", &uri).await;
+        assert_eq!(status, StatusCode::CREATED);
+        assert_eq!(
+            body,
+            format!(r#"{{"chain":"ethereum","addresses":["{}"]}}"#, ADDRESS)
+        );
+        let (status, _) = call(&state, "POST", &uri).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(state.watched.eth.contains(ADDRESS));
+        let (status, _) = call(&state, "POST", "/admin/watched/ethereum/0xnope").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let (status, _) = call(&state, "POST", "/admin/watched/bitcoin/1abc").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        // No indexer key is configured.
+        let (status, body) = call(&state, "POST", &format!("{}/backfill", uri)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(body.contains("BOOTSTRAP_ETHERSCAN_API_KEY"));
+
+        let (status, body) = call(&state, "DELETE", &uri).await;
+        assert_eq!(
+            (status, body.as_str()),
+            (StatusCode::OK, r#"{"chain":"ethereum","addresses":[]}"#)
+        );
+        let (status, _) = call(&state, "DELETE", &uri).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        let (status, _) = call(&state, "POST", &format!("{}/backfill", uri)).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_dashboard() {
+        let state = state(None);
+        let now = chrono::Utc::now().timestamp();
+        state.dashboard.record(
+            &crate::Event {
+                chain: "ethereum".into(),
+                timestamp: (now - 3).to_string(),
+                ..Default::default()
+            },
+            now,
+        );
+        let (status, body) = call(&state, "GET", "/admin/dashboard").await;
+        assert_eq!(status, StatusCode::OK);
+        let data: serde_json::Value = serde_json::from_str(&body).unwrap();
+        let events = data["events_per_minute"].as_array().unwrap();
+        let total: u64 = events
+            .iter()
+            .map(|row| row["events"].as_u64().unwrap())
+            .sum();
+        assert_eq!(total, 1);
+        assert_eq!(data["lag"][0]["chain"], "ethereum");
+        assert_eq!(data["value_per_hour"], serde_json::json!([]));
+    }
+
+    #[tokio::test]
+    async fn test_replay_needs_a_stream_destination() {
+        let req = Request::builder()
+            .method("POST")
+            .uri("/admin/streams/realtime/replay")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(r#"{"from":"0-0","consumer":"ledger"}"#))
+            .unwrap();
+        let res = router(state(None)).oneshot(req).await.unwrap();
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_webhooks_crud() {
+        let state = state(None);
+        let post = |body: &str| {
+            Request::builder()
+                .method("POST")
+                .uri("/admin/webhooks")
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(body.to_string()))
+                .unwrap()
+        };
+
+        let res = router(state.clone())
+            .oneshot(post(
+                r#"{"id":"ops","url":"https://hooks.example/tracker","chains":["ethereum"]}"#,
+            ))
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::CREATED);
+        let res = router(state.clone())
+            .oneshot(post(r#"{"url":"file:///etc/passwd"}"#))
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+
+        let (_, body) = call(&state, "GET", "/admin/webhooks").await;
+        assert_eq!(
+            body,
+            r#"[{"id":"ops","url":"https://hooks.example/tracker","chains":["ethereum"]}]"#
+        );
+        let (status, body) = call(&state, "GET", "/admin/webhooks/ops/deliveries").await;
+        assert_eq!((status, body.as_str()), (StatusCode::OK, "[]"));
+        let redeliver = Request::builder()
+            .method("POST")
+            .uri("/admin/webhooks/ops/redeliver")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(r#"{"from_event_id":"ethereum:0x01:0"}"#))
+            .unwrap();
+        let res = router(state.clone()).oneshot(redeliver).await.unwrap();
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+        let ack = Request::builder()
+            .method("POST")
+            .uri("/admin/webhooks/ops/ack")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(r#"{"event_ids":["ethereum:0x01:0"]}"#))
+            .unwrap();
+        let res = router(state.clone()).oneshot(ack).await.unwrap();
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+        let (status, body) = call(&state, "GET", "/admin/webhooks/ops/offset").await;
+        assert_eq!(
+            (status, body.as_str()),
+            (
+                StatusCode::OK,
+                r#"{"offset":null,"unacknowledged":0,"acknowledged":0}"#
+            )
+        );
+
+        let (status, _) = call(&state, "DELETE", "/admin/webhooks/ops").await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        let (status, _) = call(&state, "GET", "/admin/webhooks/ops/deliveries").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        let (status, _) = call(&state, "DELETE", "/admin/webhooks/ops").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_quarantine_list_and_requeue() {
+        let (publisher, mut rx) = Publisher::in_memory();
+        let state = AdminState {
+            publisher,
+            ..state(None)
+        };
+        let (status, body) = call(&state, "GET", "/admin/quarantine").await;
+        assert_eq!((status, body.as_str()), (StatusCode::OK, "[]"));
+
+        let id = state.publisher.quarantine().add(
+            "ethereum:0x01:0",
+            Stage::Deliver,
+            Some("in_memory"),
+            8,
+            "connection refused",
+            "{}".into(),
+            Retry::Deliver {
+                shard: 0,
+                key: String::new(),
+                order_key: None,
+                route: None,
+            },
+        );
+        let (_, body) = call(&state, "GET", "/admin/quarantine").await;
+        assert!(body.starts_with(r#"[{"id":1,"event_id":"ethereum:0x01:0","stage":"deliver""#));
+
+        let uri = format!("/admin/quarantine/{}/requeue", id);
+        let (status, _) = call(&state, "POST", &uri).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        assert_eq!(rx.recv().await.unwrap().event_id, "ethereum:0x01:0");
+        let (status, _) = call(&state, "POST", &uri).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+}
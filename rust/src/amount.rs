@@ -0,0 +1,131 @@
+//! Token and native amounts in their smallest unit.
+//!
+//! Event values are published as decimal strings because ERC-20 amounts are
+//! 256-bit and routinely exceed what JSON numbers, `u64` or `i128` can hold.
+//! Every path that decodes, parses or formats a value goes through
+//! [`Amount`], which keeps the full `U256` and the asset's decimals, so a
+//! large amount is never truncated or wrapped on the way to the payload.
+use std::fmt;
+
+use ethers::types::U256;
+
+/// An amount in an asset's smallest unit (wei, lamports, token base units)
+/// and the asset's decimals. Displays as the raw decimal integer, the format
+/// of an event's `value`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Amount {
+    raw: U256,
+    decimals: u8,
+}
+
+impl Amount {
+    pub fn new(raw: impl Into<U256>, decimals: u8) -> Self {
+        Amount {
+            raw: raw.into(),
+            decimals,
+        }
+    }
+
+    /// Parse a decimal integer string such as an event's `value`. `None` if
+    /// it is empty, has anything but digits, or doesn't fit in 256 bits.
+    pub fn parse(raw: &str, decimals: u8) -> Option<Self> {
+        if raw.is_empty() || !raw.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        Some(Amount::new(U256::from_dec_str(raw).ok()?, decimals))
+    }
+
+    /// Decode the first 32-byte ABI word of `data`, e.g. the data of an
+    /// ERC-20 `Transfer` log. Shorter data is read as a big-endian integer;
+    /// anything after the first word is ignored rather than overflowing.
+    pub fn from_abi_word(data: &[u8], decimals: u8) -> Self {
+        Amount::new(
+            U256::from_big_endian(data.get(..32).unwrap_or(data)),
+            decimals,
+        )
+    }
+
+    pub fn raw(&self) -> U256 {
+        self.raw
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.raw.is_zero()
+    }
+
+    /// The amount in whole units, without trailing zeros: `1500000` with 6
+    /// decimals is `1.5`.
+    pub fn formatted(&self) -> String {
+        let decimals = self.decimals as usize;
+        let digits = self.raw.to_string();
+        let padded = format!("{:0>width$}", digits, width = decimals + 1);
+        let (whole, fraction) = padded.split_at(padded.len() - decimals);
+        let fraction = fraction.trim_end_matches('0');
+        if fraction.is_empty() {
+            whole.to_string()
+        } else {
+            format!("{}.{}", whole, fraction)
+        }
+    }
+}
+
+impl fmt::Display for Amount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.raw)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const U256_MAX: &str =
+        "115792089237316195423570985008687907853269984665640564039457584007913129639935";
+
+    #[test]
+    fn test_u256_extremes_round_trip() {
+        let max = Amount::parse(U256_MAX, 18).unwrap();
+        assert_eq!(max.raw(), U256::MAX);
+        assert_eq!(max.to_string(), U256_MAX);
+        assert_eq!(
+            max.formatted(),
+            "115792089237316195423570985008687907853269984665640564039457.584007913129639935"
+        );
+        // One past U256::MAX doesn't fit.
+        let overflow = U256_MAX.replace("935", "936");
+        assert_eq!(Amount::parse(&overflow, 18), None);
+
+        let above_u128 = Amount::new(U256::from(u128::MAX) + 1, 0);
+        assert_eq!(Amount::parse(&above_u128.to_string(), 0), Some(above_u128));
+        assert_eq!(Amount::new(U256::zero(), 18).formatted(), "0");
+    }
+
+    #[test]
+    fn test_formatting_and_parsing() {
+        assert_eq!(Amount::new(1_500_000u64, 6).formatted(), "1.5");
+        assert_eq!(Amount::new(1_000u64, 18).formatted(), "0.000000000000001");
+        assert_eq!(Amount::new(42u64, 0).formatted(), "42");
+        assert_eq!(Amount::new(1u64, 255).formatted().len(), 257);
+        assert_eq!(Amount::parse("0042", 0), Some(Amount::new(42u64, 0)));
+        assert_eq!(Amount::parse("", 18), None);
+        assert_eq!(Amount::parse("-1", 18), None);
+        assert_eq!(Amount::parse("1e18", 18), None);
+    }
+
+    #[test]
+    fn test_abi_word_decoding() {
+        let mut word = [0u8; 32];
+        word[0] = 0x80;
+        let top_bit = Amount::from_abi_word(&word, 18);
+        assert_eq!(top_bit.raw(), U256::one() << 255);
+
+        // Extra words are ignored instead of panicking.
+        let mut data = vec![0xff; 32];
+        data.extend([1u8; 32]);
+        assert_eq!(Amount::from_abi_word(&data, 0).raw(), U256::MAX);
+        assert_eq!(
+            Amount::from_abi_word(&[0x01, 0x00], 0).raw(),
+            U256::from(256)
+        );
+    }
+}
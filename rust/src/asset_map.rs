@@ -0,0 +1,157 @@
+//! Logical assets across chains (`[[assets]]` in `CONFIG_FILE`).
+//!
+//! The same asset lives at a different contract on every chain, and bridged
+//! versions carry their own symbols (USDC on Ethereum, on Solana, USDC.e on
+//! Avalanche). An `[[assets]]` entry names the tokens that count as one
+//! asset, and the publisher sets `logical_asset` on every event in one of
+//! them, so consumers can aggregate balances and reports by asset instead of
+//! by contract:
+//!
+//! ```toml
+//! [[assets]]
+//! name = "USDC"
+//! tokens = [
+//!   "mainnet:0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48",
+//!   "solana:EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v",
+//!   "avalanche:0xA7D7079b0FEaD91F3e65f86E8915Cb59c1a4C664",
+//! ]
+//!
+//! [[assets]]
+//! name = "ETH"
+//! tokens = ["ethereum:native", "mainnet:0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2"]
+//! ```
+//!
+//! Each token is `<scope>:<contract or mint>`, where the scope is an event
+//! `network` (`mainnet`, `avalanche`, `devnet`) or `chain` (`ethereum`,
+//! `solana`), and `native` stands for the scope's native currency. A network
+//! scope takes precedence over a chain scope for the same token.
+use std::collections::HashMap;
+
+use anyhow::{anyhow, bail};
+use serde::Deserialize;
+
+use crate::token_filter::normalize;
+
+/// The token of native currency transfers.
+pub const NATIVE: &str = "native";
+
+/// One `[[assets]]` entry in the config file.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AssetMapping {
+    pub name: String,
+    pub tokens: Vec<String>,
+}
+
+/// Logical asset name by scope and token.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AssetMap {
+    assets: HashMap<(String, String), String>,
+}
+
+impl AssetMap {
+    /// Build the map, rejecting malformed tokens and tokens mapped to more
+    /// than one asset.
+    pub fn new(mappings: &[AssetMapping]) -> anyhow::Result<Self> {
+        let mut assets = HashMap::new();
+        for mapping in mappings {
+            if mapping.name.trim().is_empty() {
+                bail!("asset mapping with an empty name");
+            }
+            for token in &mapping.tokens {
+                let (scope, address) = token.split_once(':').ok_or_else(|| {
+                    anyhow!(
+                        "asset {}: token '{}' must be <network or chain>:<address>",
+                        mapping.name,
+                        token
+                    )
+                })?;
+                let key = (scope.trim().to_ascii_lowercase(), normalize(address));
+                if let Some(other) = assets.insert(key, mapping.name.clone()) {
+                    bail!(
+                        "token '{}' is mapped to both {} and {}",
+                        token,
+                        other,
+                        mapping.name
+                    );
+                }
+            }
+        }
+        Ok(AssetMap { assets })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.assets.is_empty()
+    }
+
+    /// The logical asset of `token` (a contract or mint, `None` for the
+    /// native currency) on `network` of `chain`, if it is mapped.
+    pub fn logical_asset(&self, chain: &str, network: &str, token: Option<&str>) -> Option<&str> {
+        let token = token.map_or_else(|| NATIVE.to_string(), normalize);
+        [network, chain]
+            .iter()
+            .find_map(|scope| {
+                self.assets
+                    .get(&(scope.to_ascii_lowercase(), token.clone()))
+            })
+            .map(String::as_str)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mapping(name: &str, tokens: &[&str]) -> AssetMapping {
+        AssetMapping {
+            name: name.into(),
+            tokens: tokens.iter().map(|t| t.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_lookup_by_network_then_chain() {
+        let map = AssetMap::new(&[
+            mapping(
+                "USDC",
+                &[
+                    "mainnet:0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48",
+                    "solana:EPjFWdd5",
+                ],
+            ),
+            mapping("ETH", &["ethereum:native", "Polygon:0x7ceB"]),
+        ])
+        .unwrap();
+        let usdc = Some("0xa0b86991c6218b36c1d19d4a2e9eb0ce3606eb48");
+        assert_eq!(map.logical_asset("ethereum", "mainnet", usdc), Some("USDC"));
+        assert_eq!(map.logical_asset("ethereum", "sepolia", usdc), None);
+        assert_eq!(
+            map.logical_asset("solana", "devnet", Some("EPjFWdd5")),
+            Some("USDC")
+        );
+        // Mints are case-sensitive.
+        assert_eq!(
+            map.logical_asset("solana", "devnet", Some("epjfwdd5")),
+            None
+        );
+        assert_eq!(map.logical_asset("ethereum", "sepolia", None), Some("ETH"));
+        assert_eq!(
+            map.logical_asset("ethereum", "polygon", Some("0x7CEB")),
+            Some("ETH")
+        );
+        assert_eq!(map.logical_asset("solana", "devnet", None), None);
+    }
+
+    #[test]
+    fn test_invalid_mappings() {
+        assert!(AssetMap::new(&[mapping("USDC", &["0xA0b8"])]).is_err());
+        assert!(AssetMap::new(&[mapping(" ", &[])]).is_err());
+        let err = AssetMap::new(&[
+            mapping("USDC", &["mainnet:0xA0b8"]),
+            mapping("USDT", &["mainnet:0xa0B8"]),
+        ])
+        .unwrap_err();
+        assert!(err.to_string().contains("both USDC and USDT"), "{}", err);
+        assert!(AssetMap::new(&[]).unwrap().is_empty());
+    }
+}
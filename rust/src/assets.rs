@@ -0,0 +1,207 @@
+//! Asset symbol, decimals and human-readable amount of published events
+//! (`asset_symbol`, `asset_decimals`, `value_formatted`).
+//!
+//! `value` is always in the asset's smallest unit (wei, lamports, token base
+//! units), which leaves consumers hard-coding decimals per chain. The
+//! publisher names the asset `value` is denominated in and formats it: a
+//! token transfer's asset is its token, anything else is in the chain's
+//! native currency, taken from the chain registry entry on Ethereum (ETH
+//! when the chain is unknown) and the `[[evm_networks]]` chains, SOL on
+//! Solana, TRX on Tron and the configured currency of each
+//! `[[substrate_networks]]` chain. Tokens and currencies listed
+//! in an `[[assets]]` mapping also get `logical_asset`. Events without a
+//! value get none of the fields.
+use std::collections::HashMap;
+
+use crate::amount::Amount;
+use crate::asset_map::AssetMap;
+use crate::config::Config;
+use crate::tron::TRX_DECIMALS;
+use crate::Event;
+
+/// Decimals of SOL (lamports per SOL is 10^9).
+pub const SOL_DECIMALS: u8 = 9;
+
+/// Native currency of each chain.
+#[derive(Debug, Clone)]
+pub struct Assets {
+    eth_symbol: String,
+    eth_decimals: u8,
+    /// Symbol and decimals by `[[evm_networks]]` and `[[substrate_networks]]`
+    /// chain.
+    evm: HashMap<String, (String, u8)>,
+    asset_map: AssetMap,
+}
+
+impl Default for Assets {
+    fn default() -> Self {
+        Assets {
+            eth_symbol: "ETH".into(),
+            eth_decimals: 18,
+            evm: HashMap::new(),
+            asset_map: AssetMap::default(),
+        }
+    }
+}
+
+impl Assets {
+    pub fn new(cfg: &Config) -> Self {
+        let mut assets = match &cfg.eth_chain {
+            Some(chain) => Assets {
+                eth_symbol: chain.native_symbol.clone(),
+                eth_decimals: chain.native_decimals,
+                ..Default::default()
+            },
+            None => Assets::default(),
+        };
+        assets.evm = cfg
+            .evm_networks
+            .iter()
+            .map(|n| {
+                let native = (n.info.native_symbol.clone(), n.info.native_decimals);
+                (n.chain.clone(), native)
+            })
+            .chain(
+                cfg.substrate_networks
+                    .iter()
+                    .map(|n| (n.chain.clone(), (n.symbol.clone(), n.decimals))),
+            )
+            .collect();
+        assets.asset_map = cfg.asset_map.clone();
+        assets
+    }
+
+    /// Symbol and decimals of the native currency of `chain` (an event
+    /// `chain` name).
+    pub fn native(&self, chain: &str) -> Option<(String, u8)> {
+        match chain {
+            "ethereum" => Some((self.eth_symbol.clone(), self.eth_decimals)),
+            "solana" => Some(("SOL".to_string(), SOL_DECIMALS)),
+            "tron" => Some(("TRX".to_string(), TRX_DECIMALS)),
+            chain => self.evm.get(chain).cloned(),
+        }
+    }
+
+    /// Set the asset fields of `event` from its token or chain. Leaves them
+    /// unset if it has no numeric value or its chain is unknown.
+    pub fn fill(&self, event: &mut Event) {
+        let Some((symbol, decimals)) = event
+            .token
+            .as_ref()
+            .map(|token| (token.symbol.clone(), token.decimals))
+            .or_else(|| self.native(&event.chain))
+        else {
+            return;
+        };
+        let Some(amount) = Amount::parse(&event.value, decimals) else {
+            return;
+        };
+        event.asset_symbol = Some(symbol);
+        event.asset_decimals = Some(decimals);
+        event.value_formatted = Some(amount.formatted());
+        event.logical_asset = self
+            .asset_map
+            .logical_asset(
+                &event.chain,
+                &event.network,
+                event.token.as_ref().map(|token| token.address.as_str()),
+            )
+            .map(str::to_string);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asset_map::AssetMapping;
+    use crate::chains::ChainRegistry;
+    use crate::evm_networks::{EvmNetwork, EvmNetworkConfig};
+    use crate::Token;
+
+    #[test]
+    fn test_fill_native_and_token_assets() {
+        let polygon = Assets::new(&Config {
+            eth_chain: ChainRegistry::default().get(137).cloned(),
+            ..Default::default()
+        });
+        let mut native = Event {
+            chain: "ethereum".into(),
+            value: "2500000000000000000".into(),
+            ..Default::default()
+        };
+        polygon.fill(&mut native);
+        assert_eq!(native.asset_symbol.as_deref(), Some("POL"));
+        assert_eq!(native.asset_decimals, Some(18));
+        assert_eq!(native.value_formatted.as_deref(), Some("2.5"));
+
+        let mut token = Event {
+            chain: "solana".into(),
+            value: "1234".into(),
+            token: Some(Token {
+                address: "Mint1".into(),
+                symbol: "USDC".into(),
+                decimals: 6,
+            }),
+            ..Default::default()
+        };
+        polygon.fill(&mut token);
+        assert_eq!(token.asset_symbol.as_deref(), Some("USDC"));
+        assert_eq!(token.value_formatted.as_deref(), Some("0.001234"));
+
+        let mut solana_tx = Event {
+            chain: "solana".into(),
+            ..Default::default()
+        };
+        polygon.fill(&mut solana_tx);
+        assert_eq!(solana_tx.asset_symbol, None);
+    }
+
+    #[test]
+    fn test_evm_network_native_currency() {
+        let bnb = EvmNetworkConfig {
+            chain: "bnb".into(),
+            network: None,
+            chain_id: None,
+            rpc_url: "https://bsc.example".into(),
+            watched_addresses: Vec::new(),
+        };
+        let assets = Assets::new(&Config {
+            evm_networks: EvmNetwork::resolve(&[bnb], &ChainRegistry::default()).unwrap(),
+            ..Default::default()
+        });
+        assert_eq!(assets.native("bnb"), Some(("BNB".to_string(), 18)));
+        assert_eq!(assets.native("polygon"), None);
+        assert_eq!(assets.native("tron"), Some(("TRX".to_string(), 6)));
+    }
+
+    #[test]
+    fn test_fill_logical_asset() {
+        let assets = Assets::new(&Config {
+            asset_map: AssetMap::new(&[AssetMapping {
+                name: "USDC".into(),
+                tokens: vec!["avalanche:0xA7D7".into(), "solana:Mint1".into()],
+            }])
+            .unwrap(),
+            ..Default::default()
+        });
+        let usdc_e = |network: &str| Event {
+            chain: "ethereum".into(),
+            network: network.into(),
+            value: "1".into(),
+            token: Some(Token {
+                address: "0xa7d7".into(),
+                symbol: "USDC.e".into(),
+                decimals: 6,
+            }),
+            ..Default::default()
+        };
+        let mut bridged = usdc_e("avalanche");
+        assets.fill(&mut bridged);
+        assert_eq!(bridged.asset_symbol.as_deref(), Some("USDC.e"));
+        assert_eq!(bridged.logical_asset.as_deref(), Some("USDC"));
+
+        let mut other_network = usdc_e("mainnet");
+        assets.fill(&mut other_network);
+        assert_eq!(other_network.logical_asset, None);
+    }
+}
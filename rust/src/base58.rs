@@ -0,0 +1,65 @@
+//! Base58, as in Tron and Substrate (SS58) addresses: the Bitcoin alphabet,
+//! without checksums, which each address format adds its own way.
+
+const ALPHABET: &[u8; 58] = b"123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+
+/// `bytes` in base58, leading zero bytes as `1`s.
+pub fn encode(bytes: &[u8]) -> String {
+    // Base 58 digits, least significant first.
+    let mut digits: Vec<u8> = Vec::new();
+    for &byte in bytes {
+        let mut carry = byte as u32;
+        for digit in digits.iter_mut() {
+            carry += (*digit as u32) << 8;
+            *digit = (carry % 58) as u8;
+            carry /= 58;
+        }
+        while carry > 0 {
+            digits.push((carry % 58) as u8);
+            carry /= 58;
+        }
+    }
+    let zeros = bytes.iter().take_while(|&&b| b == 0).count();
+    std::iter::repeat('1')
+        .take(zeros)
+        .chain(digits.iter().rev().map(|&d| ALPHABET[d as usize] as char))
+        .collect()
+}
+
+/// The bytes of base58 `s`; `None` if it has a character outside the
+/// alphabet.
+pub fn decode(s: &str) -> Option<Vec<u8>> {
+    // Bytes, least significant first.
+    let mut bytes: Vec<u8> = Vec::new();
+    for c in s.bytes() {
+        let mut carry = ALPHABET.iter().position(|&a| a == c)? as u32;
+        for byte in bytes.iter_mut() {
+            carry += (*byte as u32) * 58;
+            *byte = carry as u8;
+            carry >>= 8;
+        }
+        while carry > 0 {
+            bytes.push(carry as u8);
+            carry >>= 8;
+        }
+    }
+    let zeros = s.bytes().take_while(|&c| c == b'1').count();
+    bytes.extend(std::iter::repeat(0).take(zeros));
+    bytes.reverse();
+    Some(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip_keeps_leading_zeros() {
+        assert_eq!(encode(b"hello world"), "StV1DL6CwTryKyV");
+        assert_eq!(decode("StV1DL6CwTryKyV").unwrap(), b"hello world");
+        assert_eq!(encode(&[0, 0, 1]), "112");
+        assert_eq!(decode("112").unwrap(), [0, 0, 1]);
+        assert_eq!(encode(&[]), "");
+        assert!(decode("0OIl").is_none());
+    }
+}
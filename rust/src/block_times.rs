@@ -0,0 +1,131 @@
+//! Ethereum block timestamps shared by the subscription trackers.
+//!
+//! ERC-20 logs don't carry their block's timestamp, and a busy block holds
+//! many matching logs. The native tracker records the timestamp of every
+//! block it fetches and the ERC-20 tracker looks it up before asking the
+//! node. Entries are keyed by block hash, so a reorged block never lends its
+//! timestamp to its replacement, and the least recently used is dropped
+//! once the cache is full.
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex as StdMutex};
+
+use ethers::providers::{JsonRpcClient, Middleware, Provider};
+use ethers::types::H256;
+
+/// Blocks remembered per chain; a few minutes of mainnet blocks.
+pub const BLOCK_TIMES_CAPACITY: usize = 256;
+
+#[derive(Debug, Default)]
+struct Lru {
+    capacity: usize,
+    timestamps: HashMap<H256, u64>,
+    /// Least recently used first.
+    order: VecDeque<H256>,
+}
+
+impl Lru {
+    fn touch(&mut self, hash: H256) {
+        if let Some(pos) = self.order.iter().position(|h| *h == hash) {
+            self.order.remove(pos);
+        }
+        self.order.push_back(hash);
+    }
+
+    fn get(&mut self, hash: H256) -> Option<u64> {
+        let timestamp = *self.timestamps.get(&hash)?;
+        self.touch(hash);
+        Some(timestamp)
+    }
+
+    fn insert(&mut self, hash: H256, timestamp: u64) {
+        self.timestamps.insert(hash, timestamp);
+        self.touch(hash);
+        while self.order.len() > self.capacity {
+            if let Some(oldest) = self.order.pop_front() {
+                self.timestamps.remove(&oldest);
+            }
+        }
+    }
+}
+
+/// Block hash to timestamp cache. Cheap to clone; all clones share state.
+#[derive(Debug, Clone)]
+pub struct BlockTimes {
+    lru: Arc<StdMutex<Lru>>,
+}
+
+impl Default for BlockTimes {
+    fn default() -> Self {
+        BlockTimes::new(BLOCK_TIMES_CAPACITY)
+    }
+}
+
+impl BlockTimes {
+    pub fn new(capacity: usize) -> Self {
+        BlockTimes {
+            lru: Arc::new(StdMutex::new(Lru {
+                capacity: capacity.max(1),
+                ..Default::default()
+            })),
+        }
+    }
+
+    pub fn get(&self, hash: H256) -> Option<u64> {
+        self.lru.lock().unwrap().get(hash)
+    }
+
+    pub fn insert(&self, hash: H256, timestamp: u64) {
+        self.lru.lock().unwrap().insert(hash, timestamp);
+    }
+
+    /// The block's timestamp, from the cache or else from the node. `None`
+    /// if the node doesn't know the block or the request fails.
+    pub async fn timestamp<P: JsonRpcClient>(
+        &self,
+        provider: &Provider<P>,
+        hash: H256,
+    ) -> Option<u64> {
+        if let Some(timestamp) = self.get(hash) {
+            return Some(timestamp);
+        }
+        let block = provider.get_block(hash).await.ok()??;
+        let timestamp = block.timestamp.as_u64();
+        self.insert(hash, timestamp);
+        Some(timestamp)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hit_and_miss() {
+        let times = BlockTimes::default();
+        times.insert(H256::from_low_u64_be(1), 1_700_000_000);
+        assert_eq!(times.get(H256::from_low_u64_be(1)), Some(1_700_000_000));
+        assert_eq!(times.get(H256::from_low_u64_be(2)), None);
+    }
+
+    #[test]
+    fn test_least_recently_used_is_evicted() {
+        let times = BlockTimes::new(2);
+        times.insert(H256::from_low_u64_be(1), 10);
+        times.insert(H256::from_low_u64_be(2), 20);
+        // Reading block 1 makes block 2 the oldest.
+        assert_eq!(times.get(H256::from_low_u64_be(1)), Some(10));
+        times.insert(H256::from_low_u64_be(3), 30);
+
+        assert_eq!(times.get(H256::from_low_u64_be(2)), None);
+        assert_eq!(times.get(H256::from_low_u64_be(1)), Some(10));
+        assert_eq!(times.get(H256::from_low_u64_be(3)), Some(30));
+    }
+
+    #[test]
+    fn test_clones_share_entries() {
+        let times = BlockTimes::new(4);
+        let other = times.clone();
+        other.insert(H256::from_low_u64_be(7), 70);
+        assert_eq!(times.get(H256::from_low_u64_be(7)), Some(70));
+    }
+}
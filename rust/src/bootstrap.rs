@@ -0,0 +1,522 @@
+//! Cold start from an indexer (`BOOTSTRAP_ETHERSCAN_API_KEY`,
+//! `BOOTSTRAP_HELIUS_API_KEY`).
+//!
+//! Live tracking only sees activity from the moment it starts. When an
+//! indexer key is configured, the listener first publishes the most recent
+//! `BOOTSTRAP_LIMIT` transfers of every watched address, oldest first, from
+//! Etherscan (native and ERC-20 transfers) or Helius (Solana transactions),
+//! so consumers start with the address's recent history. Then the trackers
+//! start as usual.
+//!
+//! Bootstrapped events are shaped like live ones and use the same event ids,
+//! so the dedup set keeps the trackers from publishing them again. They are
+//! marked `backfill`: destinations receive them, but they raise no alerts,
+//! sweeps or expectation matches, aren't sent to webhooks and don't count
+//! towards balance reconciliation, as none of that is about old activity. Each
+//! address is bootstrapped once: a Redis marker (`tracker:bootstrap:...`)
+//! is set after its history is published, and later starts skip it. An
+//! address whose bootstrap fails is retried on the next start. ERC-20 rows
+//! without a `logIndex` are skipped unless `DEDUP_KEY=tx`, as their event id
+//! can't be derived.
+//!
+//! A backfill can also be requested for one watched address at any time
+//! (`POST /admin/watched/{chain}/{address}/backfill`), e.g. after adding it
+//! at runtime. It publishes the address's indexer history the same way,
+//! whether or not it was bootstrapped before, and leaves the marker alone.
+use std::sync::Arc;
+
+use anyhow::{anyhow, Context};
+use ethers::types::{Address, U256};
+use redis::aio::MultiplexedConnection;
+use serde::Deserialize;
+use serde_json::Value;
+use tokio::sync::Mutex;
+use tracing::{info, warn};
+
+use crate::config::Config;
+use crate::dedup::{DedupKey, DedupSet};
+use crate::event_type::EventType;
+use crate::helius::{self, Helius};
+use crate::history::BadRequest;
+use crate::publisher::Publisher;
+use crate::selectors;
+use crate::spam::{SpamFilter, TransferSignals};
+use crate::token_filter::TokenFilter;
+use crate::trace_context::TraceContext;
+use crate::{Event, Token};
+
+const MARKER_PREFIX: &str = "tracker:bootstrap:";
+
+/// A row of Etherscan's `txlist` or `tokentx` result.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct EtherscanTx {
+    time_stamp: String,
+    #[serde(default)]
+    block_number: Option<String>,
+    hash: String,
+    from: String,
+    to: String,
+    value: String,
+    #[serde(default)]
+    input: Option<String>,
+    #[serde(default)]
+    contract_address: Option<String>,
+    #[serde(default)]
+    token_symbol: Option<String>,
+    #[serde(default)]
+    token_decimal: Option<String>,
+    #[serde(default)]
+    log_index: Option<String>,
+}
+
+fn parse_address(s: &str) -> Option<Address> {
+    s.parse().ok()
+}
+
+/// Parse a decimal or `0x` hex number as Etherscan returns them.
+fn parse_number(s: &str) -> Option<u64> {
+    match s.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16).ok(),
+        None => s.parse().ok(),
+    }
+}
+
+/// Event for a `txlist` (native) or, with `erc20`, a `tokentx` row. `None`
+/// if the row doesn't touch `watched` or is dropped by the filters.
+#[allow(clippy::too_many_arguments)]
+fn etherscan_event(
+    row: &EtherscanTx,
+    erc20: bool,
+    watched: Address,
+    network: &str,
+    key: DedupKey,
+    tokens: &TokenFilter,
+    spam: &SpamFilter,
+) -> Option<Event> {
+    let from = parse_address(&row.from)?;
+    // Contract creations have no recipient.
+    let to = parse_address(&row.to).unwrap_or_default();
+    if from != watched && to != watched {
+        return None;
+    }
+    let value = U256::from_dec_str(&row.value).ok()?;
+    let log_index = row.log_index.as_deref().and_then(parse_number);
+    if erc20 && log_index.is_none() && key != DedupKey::Tx {
+        // Without it the id would collide with the native transfer's.
+        return None;
+    }
+    let event_id = key.event_id(
+        "eth",
+        &row.hash,
+        if erc20 { log_index } else { None },
+        &format!("{:?}", watched),
+    );
+    let mut event = Event {
+        event_id,
+        chain: "ethereum".into(),
+        network: network.to_string(),
+        tx_hash: row.hash.clone(),
+        timestamp: row.time_stamp.clone(),
+        from: format!("{:?}", from),
+        to: format!("{:?}", to),
+        value: value.to_string(),
+        event_type: EventType::Transfer,
+        block_number: row.block_number.as_deref().and_then(parse_number),
+        watched_address: Some(format!("{:?}", watched)),
+        ..Default::default()
+    };
+    if !erc20 {
+        let input = row.input.as_deref().unwrap_or_default();
+        let input = ethers::utils::hex::decode(input.trim_start_matches("0x")).unwrap_or_default();
+        event.selector = selectors::selector(&input);
+        event.method = event
+            .selector
+            .as_deref()
+            .and_then(selectors::method)
+            .map(str::to_string);
+        return Some(event);
+    }
+
+    let contract = parse_address(row.contract_address.as_deref()?)?;
+    if !tokens.allows(&format!("{:?}", contract)) {
+        return None;
+    }
+    let symbol = row
+        .token_symbol
+        .clone()
+        .filter(|s| !s.is_empty())
+        .unwrap_or_else(|| "UNKNOWN".to_string());
+    let decimals = row
+        .token_decimal
+        .as_deref()
+        .and_then(|d| d.parse().ok())
+        .unwrap_or(18);
+    let signals = TransferSignals {
+        unsolicited: from != watched,
+        zero_value: value.is_zero(),
+        symbol: Some(&symbol),
+        metadata_unverified: symbol == "UNKNOWN",
+    };
+    event.event_type = EventType::Erc20Transfer;
+    event.log_index = log_index;
+    event.token = Some(Token {
+        address: format!("{:?}", contract),
+        symbol: symbol.clone(),
+        decimals,
+    });
+    spam.apply(&mut event, &signals).then_some(event)
+}
+
+/// Etherscan's `result` array, or the error it returned instead.
+fn etherscan_rows(response: Value) -> anyhow::Result<Vec<EtherscanTx>> {
+    match response.get("result") {
+        Some(Value::Array(_)) => Ok(serde_json::from_value(response["result"].clone())?),
+        Some(Value::String(message)) => Err(anyhow!("etherscan: {}", message)),
+        _ => Err(anyhow!("etherscan: unexpected response {}", response)),
+    }
+}
+
+struct Indexer<'a> {
+    cfg: &'a Config,
+    http: reqwest::Client,
+    key: DedupKey,
+}
+
+impl Indexer<'_> {
+    async fn etherscan(&self, api_key: &str, watched: &str) -> anyhow::Result<Vec<Event>> {
+        let address = parse_address(watched).context("invalid watched address")?;
+        let mut events = Vec::new();
+        for (action, erc20) in [("txlist", false), ("tokentx", true)] {
+            let limit = self.cfg.bootstrap_limit.to_string();
+            let response: Value = self
+                .http
+                .get(&self.cfg.bootstrap_etherscan_url)
+                .query(&[
+                    ("module", "account"),
+                    ("action", action),
+                    ("address", watched),
+                    ("page", "1"),
+                    ("offset", limit.as_str()),
+                    ("sort", "desc"),
+                    ("apikey", api_key),
+                ])
+                .send()
+                .await?
+                .error_for_status()?
+                .json()
+                .await?;
+            events.extend(etherscan_rows(response)?.iter().filter_map(|row| {
+                etherscan_event(
+                    row,
+                    erc20,
+                    address,
+                    &self.cfg.eth_network,
+                    self.key,
+                    &self.cfg.eth_tokens,
+                    &self.cfg.spam,
+                )
+            }));
+        }
+        // Oldest first; ERC-20 transfers after the transaction's native one.
+        events.sort_by_key(|e| e.timestamp.parse::<u64>().unwrap_or_default());
+        Ok(events)
+    }
+
+    async fn helius(&self, client: &Helius, watched: &str) -> anyhow::Result<Vec<Event>> {
+        let mut events: Vec<Event> = client
+            .transactions(watched, self.cfg.bootstrap_limit)
+            .await?
+            .iter()
+            .filter_map(|tx| {
+                helius::event(
+                    tx,
+                    watched,
+                    &self.cfg.sol_network,
+                    self.key,
+                    &self.cfg.sol_tokens,
+                    &self.cfg.spam,
+                )
+            })
+            .collect();
+        events.sort_by_key(|e| e.slot);
+        Ok(events)
+    }
+}
+
+fn marker_key(chain: &str, network: &str, address: &str) -> String {
+    format!("{}{}:{}:{}", MARKER_PREFIX, chain, network, address)
+}
+
+async fn is_done(con: &mut MultiplexedConnection, marker: &str) -> anyhow::Result<bool> {
+    Ok(redis::cmd("EXISTS").arg(marker).query_async(con).await?)
+}
+
+async fn mark_done(con: &mut MultiplexedConnection, marker: &str) -> anyhow::Result<()> {
+    let now = chrono::Utc::now().to_rfc3339();
+    redis::cmd("SET")
+        .arg(marker)
+        .arg(now)
+        .query_async::<_, ()>(con)
+        .await?;
+    Ok(())
+}
+
+/// Publish events not yet in the dedup set as backfill and record them
+/// there.
+async fn publish(
+    events: Vec<Event>,
+    processed_txs: &Arc<Mutex<DedupSet>>,
+    publisher: &Publisher,
+) -> anyhow::Result<usize> {
+    let mut published = 0;
+    for mut event in events {
+        if processed_txs.lock().await.contains(&event.event_id) {
+            continue;
+        }
+        event.backfill = true;
+        publisher.publish(&event, TraceContext::new_root()).await?;
+        processed_txs.lock().await.insert(event.event_id);
+        published += 1;
+    }
+    Ok(published)
+}
+
+/// Backfills of single addresses on request, from the bootstrap indexers.
+#[derive(Clone, Default)]
+pub struct Backfill {
+    cfg: Arc<Config>,
+    processed_txs: Arc<Mutex<DedupSet>>,
+}
+
+impl Backfill {
+    /// `processed_txs` is the trackers' dedup set.
+    pub fn new(cfg: &Config, processed_txs: &Arc<Mutex<DedupSet>>) -> Self {
+        Backfill {
+            cfg: Arc::new(cfg.clone()),
+            processed_txs: Arc::clone(processed_txs),
+        }
+    }
+
+    /// Publish the indexer history of `address` on `chain` as backfill and
+    /// return how many events were new. Errors with [`BadRequest`] for an
+    /// unknown chain or one without an indexer key.
+    pub async fn run(
+        &self,
+        chain: &str,
+        address: &str,
+        publisher: &Publisher,
+    ) -> anyhow::Result<usize> {
+        let cfg = self.cfg.as_ref();
+        let api_key = |key: &Option<String>, name: &str| {
+            key.clone()
+                .ok_or_else(|| BadRequest(format!("backfilling {} needs {}", chain, name)))
+        };
+        let indexer = Indexer {
+            cfg,
+            http: reqwest::Client::new(),
+            key: self.processed_txs.lock().await.key(),
+        };
+        let events = match chain {
+            "ethereum" => {
+                let api_key = api_key(
+                    &cfg.bootstrap_etherscan_api_key,
+                    "BOOTSTRAP_ETHERSCAN_API_KEY",
+                )?;
+                indexer.etherscan(&api_key, address).await?
+            }
+            "solana" => {
+                let api_key = api_key(&cfg.bootstrap_helius_api_key, "BOOTSTRAP_HELIUS_API_KEY")?;
+                let client = Helius::new(&cfg.helius_url, &api_key);
+                indexer.helius(&client, address).await?
+            }
+            other => return Err(BadRequest(format!("unknown chain '{}'", other)).into()),
+        };
+        let published = publish(events, &self.processed_txs, publisher).await?;
+        info!("Backfilled {} {} with {} events", chain, address, published);
+        Ok(published)
+    }
+}
+
+/// Bootstrap every watched address of the enabled chains that has an
+/// indexer configured and hasn't been bootstrapped before.
+pub async fn run(
+    cfg: &Config,
+    processed_txs: &Arc<Mutex<DedupSet>>,
+    publisher: &Publisher,
+) -> anyhow::Result<()> {
+    let mut con = redis::Client::open(cfg.redis_url.as_str())?
+        .get_multiplexed_async_connection()
+        .await
+        .context("bootstrap markers need Redis")?;
+    let indexer = Indexer {
+        cfg,
+        http: reqwest::Client::new(),
+        key: processed_txs.lock().await.key(),
+    };
+
+    let eth_key = cfg
+        .bootstrap_etherscan_api_key
+        .as_deref()
+        .filter(|_| cfg.eth_enabled);
+    let sol_key = cfg
+        .bootstrap_helius_api_key
+        .as_deref()
+        .filter(|_| cfg.sol_enabled);
+    let chains = [
+        (
+            "ethereum",
+            &cfg.eth_network,
+            &cfg.watched_addresses_eth,
+            eth_key,
+        ),
+        (
+            "solana",
+            &cfg.sol_network,
+            &cfg.watched_addresses_sol,
+            sol_key,
+        ),
+    ];
+    for (chain, network, addresses, api_key) in chains {
+        let Some(api_key) = api_key else { continue };
+        for address in addresses {
+            let marker = marker_key(chain, network, address);
+            if is_done(&mut con, &marker).await? {
+                info!("{} {} already bootstrapped", chain, address);
+                continue;
+            }
+            let events = match chain {
+                "ethereum" => indexer.etherscan(api_key, address).await,
+                _ => {
+                    let client = Helius::new(&cfg.helius_url, api_key);
+                    indexer.helius(&client, address).await
+                }
+            };
+            let published = match events {
+                Ok(events) => publish(events, processed_txs, publisher).await,
+                Err(e) => Err(e),
+            };
+            match published {
+                Ok(n) => {
+                    info!("Bootstrapped {} {} with {} events", chain, address, n);
+                    mark_done(&mut con, &marker).await?;
+                }
+                Err(e) => warn!(
+                    "Bootstrap of {} {} failed, retrying on next start: {:?}",
+                    chain, address, e
+                ),
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spam::SpamMode;
+
+    const WATCHED: &str = "0x70997970c51812dc3a010c7d01b50e0d17dc79c8";
+
+    fn row(json: Value) -> EtherscanTx {
+        serde_json::from_value(json).unwrap()
+    }
+
+    #[test]
+    fn test_etherscan_native_and_token_rows() {
+        let watched = parse_address(WATCHED).unwrap();
+        let native = row(serde_json::json!({
+            "blockNumber": "19000000",
+            "timeStamp": "1700000000",
+            "hash": "0xaa",
+            "from": WATCHED,
+            "to": "0x0000000000000000000000000000000000000002",
+            "value": "1000",
+            "input": "0xa9059cbb0000",
+        }));
+        let event = etherscan_event(
+            &native,
+            false,
+            watched,
+            "mainnet",
+            DedupKey::Log,
+            &TokenFilter::default(),
+            &SpamFilter::default(),
+        )
+        .unwrap();
+        assert_eq!(event.event_id, "eth:0xaa");
+        assert_eq!(event.event_type, EventType::Transfer);
+        assert_eq!(event.timestamp, "1700000000");
+        assert_eq!(event.method.as_deref(), Some("transfer(address,uint256)"));
+        assert_eq!(event.block_number, Some(19000000));
+
+        let token = row(serde_json::json!({
+            "timeStamp": "1700000000",
+            "hash": "0xaa",
+            "from": "0x0000000000000000000000000000000000000002",
+            "to": WATCHED,
+            "value": "5",
+            "contractAddress": "0x0000000000000000000000000000000000000003",
+            "tokenSymbol": "USDC",
+            "tokenDecimal": "6",
+            "logIndex": "0x4",
+        }));
+        let event = etherscan_event(
+            &token,
+            true,
+            watched,
+            "mainnet",
+            DedupKey::Log,
+            &TokenFilter::default(),
+            &SpamFilter::default(),
+        )
+        .unwrap();
+        assert_eq!(event.event_id, "eth:0xaa:log4");
+        assert_eq!(event.log_index, Some(4));
+        assert_eq!(event.event_type, EventType::Erc20Transfer);
+        assert_eq!(event.token.unwrap().decimals, 6);
+
+        // Rows of other addresses and dropped spam aren't published.
+        let tokens = TokenFilter::default();
+        let other = parse_address("0x0000000000000000000000000000000000000009").unwrap();
+        let spam = SpamFilter::default();
+        assert!(etherscan_event(
+            &token,
+            true,
+            other,
+            "mainnet",
+            DedupKey::Log,
+            &tokens,
+            &spam
+        )
+        .is_none());
+        let poisoning = EtherscanTx {
+            value: "0".into(),
+            ..token
+        };
+        let drop = SpamFilter::new(SpamMode::Drop, &[]);
+        assert!(etherscan_event(
+            &poisoning,
+            true,
+            watched,
+            "mainnet",
+            DedupKey::Log,
+            &tokens,
+            &drop
+        )
+        .is_none());
+    }
+
+    #[test]
+    fn test_etherscan_errors_are_reported() {
+        let ok =
+            serde_json::json!({"status": "0", "message": "No transactions found", "result": []});
+        assert!(etherscan_rows(ok).unwrap().is_empty());
+        let err =
+            serde_json::json!({"status": "0", "message": "NOTOK", "result": "Invalid API Key"});
+        assert_eq!(
+            etherscan_rows(err).unwrap_err().to_string(),
+            "etherscan: Invalid API Key"
+        );
+    }
+}
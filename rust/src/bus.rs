@@ -0,0 +1,130 @@
+//! In-process event bus.
+//!
+//! Every event the publisher accepts is broadcast on the bus after it has
+//! been queued for the Redis destinations, enriched and serialized exactly
+//! as published. In-process consumers (webhooks today) subscribe to it
+//! instead of being called by the publisher, so adding one doesn't touch the
+//! trackers or the publish path.
+//!
+//! The bus never applies backpressure: a subscriber that falls more than
+//! [`BUS_CAPACITY`] events behind skips the oldest ones and logs how many it
+//! missed. Destinations that must not lose events keep their own bounded
+//! queues in the publisher.
+use std::sync::Arc;
+
+use tokio::sync::broadcast::{self, error::RecvError};
+use tracing::{warn, Span};
+
+use crate::Event;
+
+/// Events buffered for the slowest subscriber.
+pub const BUS_CAPACITY: usize = 4096;
+
+/// A published event as it went out.
+#[derive(Debug)]
+pub struct BusEvent {
+    /// The event after enrichment.
+    pub event: Event,
+    /// Its published JSON, including `traceparent`.
+    pub payload: String,
+    /// The event's `publish` span, carrying its trace context.
+    pub span: Span,
+}
+
+/// Broadcast handle. Cheap to clone; all clones share subscribers.
+#[derive(Debug, Clone)]
+pub struct EventBus {
+    tx: broadcast::Sender<Arc<BusEvent>>,
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        EventBus::new(BUS_CAPACITY)
+    }
+}
+
+impl EventBus {
+    pub fn new(capacity: usize) -> Self {
+        let (tx, _) = broadcast::channel(capacity.max(1));
+        EventBus { tx }
+    }
+
+    /// Broadcast `event` to the current subscribers, if any.
+    pub fn publish(&self, event: BusEvent) {
+        // Fails only when nobody is subscribed.
+        let _ = self.tx.send(Arc::new(event));
+    }
+
+    /// Receive every event published from now on. `name` identifies the
+    /// subscriber in logs.
+    pub fn subscribe(&self, name: &'static str) -> BusSubscriber {
+        BusSubscriber {
+            name,
+            rx: self.tx.subscribe(),
+        }
+    }
+}
+
+/// One consumer's view of the bus.
+pub struct BusSubscriber {
+    name: &'static str,
+    rx: broadcast::Receiver<Arc<BusEvent>>,
+}
+
+impl BusSubscriber {
+    /// The next event, skipping past any this subscriber was too slow for.
+    /// `None` once every [`EventBus`] handle is gone.
+    pub async fn recv(&mut self) -> Option<Arc<BusEvent>> {
+        loop {
+            match self.rx.recv().await {
+                Ok(event) => return Some(event),
+                Err(RecvError::Lagged(missed)) => warn!(
+                    "Event bus subscriber {} fell behind and missed {} events",
+                    self.name, missed
+                ),
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bus_event(id: &str) -> BusEvent {
+        BusEvent {
+            event: Event {
+                event_id: id.into(),
+                ..Default::default()
+            },
+            payload: String::new(),
+            span: Span::none(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_every_subscriber_receives_events() {
+        let bus = EventBus::default();
+        bus.publish(bus_event("before"));
+        let mut a = bus.subscribe("a");
+        let mut b = bus.subscribe("b");
+        bus.publish(bus_event("e1"));
+
+        assert_eq!(a.recv().await.unwrap().event.event_id, "e1");
+        assert_eq!(b.recv().await.unwrap().event.event_id, "e1");
+        drop(bus);
+        assert!(a.recv().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_lagging_subscriber_skips_oldest() {
+        let bus = EventBus::new(2);
+        let mut slow = bus.subscribe("slow");
+        for id in ["e1", "e2", "e3"] {
+            bus.publish(bus_event(id));
+        }
+        assert_eq!(slow.recv().await.unwrap().event.event_id, "e2");
+        assert_eq!(slow.recv().await.unwrap().event.event_id, "e3");
+    }
+}
@@ -0,0 +1,627 @@
+//! Record/replay of RPC traffic for deterministic regression runs.
+//!
+//! `tracker record` runs the HTTP polling trackers for a fixed window while
+//! appending every JSON-RPC request/response pair to `eth.jsonl` and
+//! `sol.jsonl` in the output directory. `tracker replay` feeds those files
+//! back through the same block/transaction processing code with no network
+//! access and writes the resulting events as JSON lines, so normalization
+//! changes can be diffed against a known capture.
+use anyhow::{anyhow, Context};
+use async_trait::async_trait;
+use ethers::providers::{Http, JsonRpcClient, JsonRpcError, Provider, ProviderError, RpcError};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use solana_client::client_error::{ClientError, ClientErrorKind};
+use solana_client::http_sender::HttpSender;
+use solana_client::rpc_client::{RpcClient, RpcClientConfig};
+use solana_client::rpc_request::RpcRequest;
+use solana_client::rpc_sender::{RpcSender, RpcTransportStats};
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::pubkey::Pubkey;
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use tokio::time::Duration;
+use tracing::{info, warn};
+
+use crate::config::Config;
+use crate::control::ChainControl;
+use crate::dedup::DedupSet;
+use crate::gaps::BlockLedger;
+use crate::headers::HeaderCache;
+use crate::publisher::Publisher;
+use crate::token_metadata::{SplMetadata, TokenMetadata};
+
+const ETH_FILE: &str = "eth.jsonl";
+const SOL_FILE: &str = "sol.jsonl";
+
+/// One recorded JSON-RPC call.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Exchange {
+    method: String,
+    params: Value,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    result: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+/// Appends exchanges to a JSON-lines capture file.
+#[derive(Debug)]
+pub struct CaptureWriter {
+    out: Mutex<BufWriter<File>>,
+}
+
+impl CaptureWriter {
+    pub fn create(path: &Path) -> anyhow::Result<Arc<Self>> {
+        let file = File::create(path)
+            .with_context(|| format!("failed to create capture file {}", path.display()))?;
+        Ok(Arc::new(CaptureWriter {
+            out: Mutex::new(BufWriter::new(file)),
+        }))
+    }
+
+    fn write(&self, method: &str, params: Value, outcome: Result<&Value, String>) {
+        let (result, error) = match outcome {
+            Ok(v) => (Some(v.clone()), None),
+            Err(e) => (None, Some(e)),
+        };
+        let exchange = Exchange {
+            method: method.to_string(),
+            params,
+            result,
+            error,
+        };
+        let mut out = self.out.lock().expect("capture writer poisoned");
+        if let Err(e) = serde_json::to_writer(&mut *out, &exchange)
+            .map_err(std::io::Error::from)
+            .and_then(|_| out.write_all(b"\n"))
+        {
+            warn!("Failed to write RPC capture entry: {:?}", e);
+        }
+    }
+
+    pub fn flush(&self) -> std::io::Result<()> {
+        self.out.lock().expect("capture writer poisoned").flush()
+    }
+}
+
+/// A loaded capture. Responses for identical requests are served in the order
+/// they were recorded; once exhausted the last response keeps being returned.
+#[derive(Debug)]
+pub struct Capture {
+    exchanges: Vec<Exchange>,
+    queues: Mutex<HashMap<String, VecDeque<Exchange>>>,
+}
+
+fn request_key(method: &str, params: &Value) -> String {
+    format!("{} {}", method, params)
+}
+
+impl Capture {
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let file = File::open(path)
+            .with_context(|| format!("failed to open capture file {}", path.display()))?;
+        Self::from_reader(BufReader::new(file))
+    }
+
+    pub fn from_reader<R: BufRead>(reader: R) -> anyhow::Result<Self> {
+        let mut exchanges = Vec::new();
+        for (i, line) in reader.lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let exchange: Exchange = serde_json::from_str(&line)
+                .with_context(|| format!("invalid capture entry on line {}", i + 1))?;
+            exchanges.push(exchange);
+        }
+
+        let mut queues: HashMap<String, VecDeque<Exchange>> = HashMap::new();
+        for exchange in &exchanges {
+            queues
+                .entry(request_key(&exchange.method, &exchange.params))
+                .or_default()
+                .push_back(exchange.clone());
+        }
+        Ok(Capture {
+            exchanges,
+            queues: Mutex::new(queues),
+        })
+    }
+
+    /// Next recorded outcome for this request, or None if never recorded.
+    fn next(&self, method: &str, params: &Value) -> Option<Result<Value, String>> {
+        let mut queues = self.queues.lock().expect("capture poisoned");
+        let queue = queues.get_mut(&request_key(method, params))?;
+        let exchange = if queue.len() > 1 {
+            queue.pop_front()?
+        } else {
+            queue.front()?.clone()
+        };
+        Some(match (exchange.result, exchange.error) {
+            (_, Some(e)) => Err(e),
+            (Some(v), None) => Ok(v),
+            (None, None) => Ok(Value::Null),
+        })
+    }
+
+    /// Recorded exchanges for a method, in capture order.
+    fn calls<'a>(&'a self, method: &'a str) -> impl Iterator<Item = &'a Exchange> + 'a {
+        self.exchanges.iter().filter(move |e| e.method == method)
+    }
+}
+
+/// Error type for the recording/replaying ethers transports.
+#[derive(Debug)]
+pub enum CaptureError {
+    Provider(ProviderError),
+    Serde(serde_json::Error),
+    /// The call failed when it was recorded.
+    Recorded(String),
+    /// The call was never recorded.
+    Missing(String),
+}
+
+impl fmt::Display for CaptureError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CaptureError::Provider(e) => write!(f, "{}", e),
+            CaptureError::Serde(e) => write!(f, "{}", e),
+            CaptureError::Recorded(e) => write!(f, "recorded error: {}", e),
+            CaptureError::Missing(req) => write!(f, "no recorded response for {}", req),
+        }
+    }
+}
+
+impl std::error::Error for CaptureError {}
+
+impl RpcError for CaptureError {
+    fn as_error_response(&self) -> Option<&JsonRpcError> {
+        match self {
+            CaptureError::Provider(e) => e.as_error_response(),
+            _ => None,
+        }
+    }
+
+    fn as_serde_error(&self) -> Option<&serde_json::Error> {
+        match self {
+            CaptureError::Serde(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<CaptureError> for ProviderError {
+    fn from(e: CaptureError) -> Self {
+        match e {
+            CaptureError::Provider(e) => e,
+            other => ProviderError::CustomError(other.to_string()),
+        }
+    }
+}
+
+/// ethers transport that forwards to `inner` and records every exchange.
+#[derive(Debug)]
+pub struct RecordingClient<C> {
+    inner: C,
+    writer: Arc<CaptureWriter>,
+}
+
+impl<C> RecordingClient<C> {
+    pub fn new(inner: C, writer: Arc<CaptureWriter>) -> Self {
+        RecordingClient { inner, writer }
+    }
+}
+
+#[async_trait]
+impl<C: JsonRpcClient> JsonRpcClient for RecordingClient<C> {
+    type Error = CaptureError;
+
+    async fn request<T, R>(&self, method: &str, params: T) -> Result<R, Self::Error>
+    where
+        T: fmt::Debug + Serialize + Send + Sync,
+        R: DeserializeOwned + Send,
+    {
+        let params_value = serde_json::to_value(&params).map_err(CaptureError::Serde)?;
+        match self.inner.request::<T, Value>(method, params).await {
+            Ok(value) => {
+                self.writer.write(method, params_value, Ok(&value));
+                serde_json::from_value(value).map_err(CaptureError::Serde)
+            }
+            Err(e) => {
+                let e: ProviderError = e.into();
+                self.writer.write(method, params_value, Err(e.to_string()));
+                Err(CaptureError::Provider(e))
+            }
+        }
+    }
+}
+
+/// ethers transport that answers purely from a capture.
+#[derive(Debug)]
+pub struct ReplayClient {
+    capture: Arc<Capture>,
+}
+
+#[async_trait]
+impl JsonRpcClient for ReplayClient {
+    type Error = CaptureError;
+
+    async fn request<T, R>(&self, method: &str, params: T) -> Result<R, Self::Error>
+    where
+        T: fmt::Debug + Serialize + Send + Sync,
+        R: DeserializeOwned + Send,
+    {
+        let params = serde_json::to_value(&params).map_err(CaptureError::Serde)?;
+        match self.capture.next(method, &params) {
+            Some(Ok(value)) => serde_json::from_value(value).map_err(CaptureError::Serde),
+            Some(Err(e)) => Err(CaptureError::Recorded(e)),
+            None => Err(CaptureError::Missing(request_key(method, &params))),
+        }
+    }
+}
+
+/// Solana RPC sender that forwards over HTTP and records every exchange.
+pub struct RecordingSender {
+    inner: HttpSender,
+    writer: Arc<CaptureWriter>,
+}
+
+#[async_trait]
+impl RpcSender for RecordingSender {
+    async fn send(
+        &self,
+        request: RpcRequest,
+        params: Value,
+    ) -> solana_client::client_error::Result<Value> {
+        let method = request.to_string();
+        let res = self.inner.send(request, params.clone()).await;
+        match &res {
+            Ok(value) => self.writer.write(&method, params, Ok(value)),
+            Err(e) => self.writer.write(&method, params, Err(e.to_string())),
+        }
+        res
+    }
+
+    fn get_transport_stats(&self) -> RpcTransportStats {
+        self.inner.get_transport_stats()
+    }
+
+    fn url(&self) -> String {
+        self.inner.url()
+    }
+}
+
+/// Solana RPC sender that answers purely from a capture.
+pub struct ReplaySender {
+    capture: Arc<Capture>,
+}
+
+#[async_trait]
+impl RpcSender for ReplaySender {
+    async fn send(
+        &self,
+        request: RpcRequest,
+        params: Value,
+    ) -> solana_client::client_error::Result<Value> {
+        let method = request.to_string();
+        match self.capture.next(&method, &params) {
+            Some(Ok(value)) => Ok(value),
+            Some(Err(e)) => Err(ClientError::from(ClientErrorKind::Custom(e))),
+            None => Err(ClientError::from(ClientErrorKind::Custom(format!(
+                "no recorded response for {}",
+                request_key(&method, &params)
+            )))),
+        }
+    }
+
+    fn get_transport_stats(&self) -> RpcTransportStats {
+        RpcTransportStats::default()
+    }
+
+    fn url(&self) -> String {
+        "replay".to_string()
+    }
+}
+
+fn solana_rpc_client(sender: impl RpcSender + Send + Sync + 'static) -> RpcClient {
+    RpcClient::new_sender(
+        sender,
+        RpcClientConfig::with_commitment(CommitmentConfig::confirmed()),
+    )
+}
+
+/// Run the HTTP polling trackers for `duration` while recording all RPC
+/// traffic into `out_dir`. Events are published as usual.
+pub async fn record(
+    cfg: &Config,
+    publisher: Publisher,
+    out_dir: &Path,
+    duration: Duration,
+) -> anyhow::Result<()> {
+    std::fs::create_dir_all(out_dir)
+        .with_context(|| format!("failed to create {}", out_dir.display()))?;
+    let processed_txs = Arc::new(tokio::sync::Mutex::new(DedupSet::new(cfg.dedup_key)));
+    let mut writers = Vec::new();
+    let mut tasks = Vec::new();
+
+    if cfg.eth_enabled {
+        if !cfg.eth_rpc_url.starts_with("http") {
+            return Err(anyhow!("record mode requires an HTTP ETH_RPC_URL"));
+        }
+        let writer = CaptureWriter::create(&out_dir.join(ETH_FILE))?;
+        let http = Http::from_str(&cfg.eth_rpc_url)?;
+        let provider = Arc::new(Provider::new(RecordingClient::new(
+            http,
+            Arc::clone(&writer),
+        )));
+        writers.push(writer);
+        tasks.push(tokio::spawn(crate::poll_eth_blocks(
+            provider,
+            None,
+            cfg.watched_addresses_eth.clone(),
+            cfg.eth_network.clone(),
+            TokenMetadata::new(cfg.eth_chain.clone()),
+            cfg.eth_tokens.clone(),
+            cfg.spam.clone(),
+            Arc::clone(&processed_txs),
+            Arc::new(tokio::sync::Mutex::new(None)),
+            Arc::new(tokio::sync::Mutex::new(BlockLedger::default())),
+            HeaderCache::default(),
+            cfg.checkpoints.max_catchup_blocks,
+            cfg.eth_confirmations,
+            ChainControl::new("ethereum"),
+            publisher.clone(),
+        )));
+    }
+
+    if cfg.sol_enabled {
+        if cfg.sol_rpc_url.starts_with("ws") {
+            return Err(anyhow!("record mode requires an HTTP SOL_RPC_URL"));
+        }
+        let writer = CaptureWriter::create(&out_dir.join(SOL_FILE))?;
+        let rpc_client = Arc::new(solana_rpc_client(RecordingSender {
+            inner: HttpSender::new(cfg.sol_rpc_url.clone()),
+            writer: Arc::clone(&writer),
+        }));
+        writers.push(writer);
+        let network = cfg.sol_network.clone();
+        let watched = cfg.watched_addresses_sol.clone();
+        let tokens = cfg.sol_tokens.clone();
+        let spam = cfg.spam.clone();
+        let processed_txs = Arc::clone(&processed_txs);
+        let publisher = publisher.clone();
+        tasks.push(tokio::spawn(async move {
+            crate::poll_solana_transfers(
+                rpc_client,
+                &network,
+                &watched,
+                tokens,
+                spam,
+                SplMetadata::default(),
+                processed_txs,
+                Arc::new(tokio::sync::Mutex::new(None)),
+                ChainControl::new("solana"),
+                publisher,
+            )
+            .await
+        }));
+    }
+
+    info!(
+        "Recording RPC traffic to {} for {:?}",
+        out_dir.display(),
+        duration
+    );
+    tokio::time::sleep(duration).await;
+    for task in &tasks {
+        task.abort();
+    }
+    for writer in &writers {
+        writer.flush()?;
+    }
+    info!("Recording finished");
+    Ok(())
+}
+
+/// Re-run block and transaction processing against a capture and return the
+/// serialized events in publish order.
+pub async fn replay(cfg: &Config, capture_dir: &Path) -> anyhow::Result<Vec<String>> {
+    let (publisher, mut rx) = Publisher::in_memory();
+    let collector = tokio::spawn(async move {
+        let mut events = Vec::new();
+        while let Some(delivery) = rx.recv().await {
+            events.push(delivery.payload);
+        }
+        events
+    });
+
+    let eth_path = capture_dir.join(ETH_FILE);
+    if cfg.eth_enabled && eth_path.exists() {
+        let capture = Arc::new(Capture::load(&eth_path)?);
+        replay_eth(capture, cfg, &publisher).await?;
+    }
+    let sol_path = capture_dir.join(SOL_FILE);
+    if cfg.sol_enabled && sol_path.exists() {
+        let capture = Arc::new(Capture::load(&sol_path)?);
+        replay_sol(capture, cfg, &publisher).await?;
+    }
+
+    drop(publisher);
+    Ok(collector.await?)
+}
+
+/// Process every block the capture fetched, in ascending order.
+pub(crate) async fn replay_eth(
+    capture: Arc<Capture>,
+    cfg: &Config,
+    publisher: &Publisher,
+) -> anyhow::Result<()> {
+    let mut blocks: Vec<u64> = capture
+        .calls("eth_getBlockByNumber")
+        .filter_map(|e| e.params.get(0)?.as_str().map(str::to_string))
+        .filter_map(|hex| u64::from_str_radix(hex.trim_start_matches("0x"), 16).ok())
+        .collect();
+    blocks.sort_unstable();
+    blocks.dedup();
+
+    let provider = Arc::new(Provider::new(ReplayClient { capture }));
+    let watched: Vec<ethers::types::Address> = cfg
+        .watched_addresses_eth
+        .iter()
+        .filter_map(|s| s.parse().ok())
+        .collect();
+    let processed_txs = Arc::new(tokio::sync::Mutex::new(DedupSet::new(cfg.dedup_key)));
+
+    let token_metadata = TokenMetadata::new(cfg.eth_chain.clone());
+    for block_num in blocks {
+        crate::process_eth_block(
+            &provider,
+            block_num,
+            &watched,
+            "ethereum",
+            &cfg.eth_network,
+            &token_metadata,
+            &cfg.eth_tokens,
+            &cfg.spam,
+            &processed_txs,
+            publisher,
+        )
+        .await?;
+    }
+    Ok(())
+}
+
+/// Process every signature the capture listed for each watched address.
+pub(crate) async fn replay_sol(
+    capture: Arc<Capture>,
+    cfg: &Config,
+    publisher: &Publisher,
+) -> anyhow::Result<()> {
+    let mut work: Vec<(Pubkey, String)> = Vec::new();
+    for exchange in capture.calls("getSignaturesForAddress") {
+        let address = match exchange.params.get(0).and_then(Value::as_str) {
+            Some(a) => Pubkey::from_str(a)?,
+            None => continue,
+        };
+        let signatures = exchange
+            .result
+            .as_ref()
+            .and_then(Value::as_array)
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+        for sig in signatures {
+            if let Some(sig) = sig.get("signature").and_then(Value::as_str) {
+                work.push((address, sig.to_string()));
+            }
+        }
+    }
+
+    let rpc_client = solana_rpc_client(ReplaySender {
+        capture: Arc::clone(&capture),
+    });
+    let processed_txs = Arc::new(tokio::sync::Mutex::new(DedupSet::new(cfg.dedup_key)));
+    let last_slot = Arc::new(tokio::sync::Mutex::new(None));
+    let spl_metadata = SplMetadata::default();
+    for (address, signature) in work {
+        if let Err(e) = crate::process_solana_transaction(
+            &rpc_client,
+            &cfg.sol_network,
+            signature.clone(),
+            &address,
+            &cfg.sol_tokens,
+            &cfg.spam,
+            &spl_metadata,
+            Arc::clone(&processed_txs),
+            Arc::clone(&last_slot),
+            publisher,
+        )
+        .await
+        {
+            warn!("Replay of solana tx {} failed: {:?}", signature, e);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn repo_path(parts: &[&str]) -> PathBuf {
+        parts.iter().fold(
+            PathBuf::from(env!("CARGO_MANIFEST_DIR")).join(".."),
+            |p, s| p.join(s),
+        )
+    }
+
+    #[test]
+    fn test_capture_serves_responses_in_order_then_repeats_last() {
+        let raw = r#"{"method":"eth_blockNumber","params":[],"result":"0x1"}
+{"method":"eth_blockNumber","params":[],"result":"0x2"}
+{"method":"eth_call","params":[{"to":"0x01"},"latest"],"error":"execution reverted"}
+"#;
+        let capture = Capture::from_reader(raw.as_bytes()).unwrap();
+        let params = serde_json::json!([]);
+
+        assert_eq!(
+            capture.next("eth_blockNumber", &params),
+            Some(Ok(Value::from("0x1")))
+        );
+        assert_eq!(
+            capture.next("eth_blockNumber", &params),
+            Some(Ok(Value::from("0x2")))
+        );
+        assert_eq!(
+            capture.next("eth_blockNumber", &params),
+            Some(Ok(Value::from("0x2")))
+        );
+        assert_eq!(
+            capture.next("eth_call", &serde_json::json!([{"to": "0x01"}, "latest"])),
+            Some(Err("execution reverted".to_string()))
+        );
+        assert_eq!(capture.next("eth_getLogs", &params), None);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_replay_eth_capture_matches_golden() {
+        let capture = Arc::new(
+            Capture::load(&repo_path(&["tests", "fixtures", "replay", ETH_FILE])).unwrap(),
+        );
+        let cfg = Config {
+            eth_enabled: true,
+            eth_network: "anvil".to_string(),
+            watched_addresses_eth: vec!["0x70997970C51812dc3A010C7d01b50e0d17dc79C8".to_string()],
+            ..Default::default()
+        };
+
+        let (publisher, mut rx) = Publisher::in_memory();
+        replay_eth(capture, &cfg, &publisher).await.unwrap();
+        drop(publisher);
+
+        let mut events = Vec::new();
+        while let Some(delivery) = rx.recv().await {
+            let mut event = serde_json::from_str::<Value>(&delivery.payload).unwrap();
+            // Trace context is random per publish, so it can't be golden.
+            let traceparent = event.as_object_mut().unwrap().remove("traceparent");
+            assert!(traceparent.is_some(), "published event has no traceparent");
+            events.push(event);
+        }
+
+        let golden = repo_path(&["tests", "golden", "replay-eth-anvil.events.json"]);
+        if std::env::var("UPDATE_GOLDENS").is_ok() {
+            std::fs::write(&golden, serde_json::to_string_pretty(&events).unwrap()).unwrap();
+            return;
+        }
+        let expected: Vec<Value> =
+            serde_json::from_str(&std::fs::read_to_string(golden).unwrap()).unwrap();
+        assert_eq!(events, expected, "Replayed events do not match golden file");
+    }
+}
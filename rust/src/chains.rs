@@ -0,0 +1,337 @@
+//! Registry of EVM chains (`ETH_CHAIN_ID`, `CHAIN_REGISTRY_FILE`).
+//!
+//! Each entry holds a chain's name, native asset, block explorer and a few
+//! well-known token contracts. The ETH trackers resolve their chain from
+//! `ETH_CHAIN_ID`, or else from `ETH_NETWORK`, and use it for defaults: a
+//! transfer of a listed token takes its symbol and decimals from the entry
+//! instead of calling the contract. Chains and tokens not built in can be
+//! added with a JSON file:
+//!
+//! ```json
+//! [
+//!   {
+//!     "chain_id": 43114,
+//!     "name": "avalanche",
+//!     "networks": ["avalanche", "avax"],
+//!     "native_symbol": "AVAX",
+//!     "native_decimals": 18,
+//!     "explorer_url": "https://snowtrace.io",
+//!     "tokens": [
+//!       {"address": "0xB97EF9Ef8734C71904D8002F8b6Bc66Dd9c48a6E", "symbol": "USDC", "decimals": 6}
+//!     ]
+//!   }
+//! ]
+//! ```
+//!
+//! An entry with a built-in chain id replaces that chain's fields and adds
+//! its tokens to the built-in ones. A token's optional `behavior`,
+//! `fee_on_transfer` or `rebasing`, tags its transfers (see `erc20.rs`).
+use anyhow::Context;
+use ethers::types::Address;
+use serde::Deserialize;
+
+use crate::erc20::TokenBehavior;
+
+/// A token contract listed for a chain.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct KnownToken {
+    pub address: Address,
+    pub symbol: String,
+    pub decimals: u8,
+    /// Set for tokens whose balances don't move by the logged amounts.
+    #[serde(default)]
+    pub behavior: Option<TokenBehavior>,
+}
+
+/// One EVM chain.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct ChainInfo {
+    pub chain_id: u64,
+    pub name: String,
+    /// `ETH_NETWORK` values that select this chain.
+    #[serde(default)]
+    pub networks: Vec<String>,
+    pub native_symbol: String,
+    pub native_decimals: u8,
+    /// Block explorer base URL, without a trailing slash.
+    #[serde(default)]
+    pub explorer_url: Option<String>,
+    #[serde(default)]
+    pub tokens: Vec<KnownToken>,
+}
+
+impl ChainInfo {
+    /// The listed token at `address`, if any.
+    pub fn token(&self, address: Address) -> Option<&KnownToken> {
+        self.tokens.iter().find(|t| t.address == address)
+    }
+}
+
+/// Address, symbol and decimals.
+type TokenRow = (&'static str, &'static str, u8);
+/// Chain id, name, networks, native symbol, explorer and tokens.
+type ChainRow = (
+    u64,
+    &'static str,
+    &'static [&'static str],
+    &'static str,
+    Option<&'static str>,
+    &'static [TokenRow],
+);
+
+/// The built-in chains. All their native assets have 18 decimals.
+const BUILTIN: &[ChainRow] = &[
+    (
+        1,
+        "ethereum",
+        &["mainnet", "ethereum", "homestead"],
+        "ETH",
+        Some("https://etherscan.io"),
+        &[
+            ("0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48", "USDC", 6),
+            ("0xdAC17F958D2ee523a2206206994597C13D831ec7", "USDT", 6),
+            ("0x6B175474E89094C44Da98b954EedeAC495271d0F", "DAI", 18),
+            ("0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2", "WETH", 18),
+            ("0x2260FAC5E5542a773Aa44fBCfeDf7C193bc2C599", "WBTC", 8),
+        ],
+    ),
+    (
+        11155111,
+        "sepolia",
+        &["sepolia"],
+        "ETH",
+        Some("https://sepolia.etherscan.io"),
+        &[("0x1c7D4B196Cb0C7B01d743Fbc6116a902379C7238", "USDC", 6)],
+    ),
+    (
+        17000,
+        "holesky",
+        &["holesky"],
+        "ETH",
+        Some("https://holesky.etherscan.io"),
+        &[],
+    ),
+    (
+        137,
+        "polygon",
+        &["polygon", "matic"],
+        "POL",
+        Some("https://polygonscan.com"),
+        &[
+            ("0x3c499c542cEF5E3811e1192ce70d8cC03d5c3359", "USDC", 6),
+            ("0xc2132D05D31c914a87C6611C10748AEb04B58e8F", "USDT", 6),
+            ("0x7ceB23fD6bC0adD59E62ac25578270cFf1b9f619", "WETH", 18),
+            ("0x0d500B1d8E8eF31E21C99d1Db9A6444d3ADf1270", "WPOL", 18),
+        ],
+    ),
+    (
+        42161,
+        "arbitrum",
+        &["arbitrum", "arbitrum-one"],
+        "ETH",
+        Some("https://arbiscan.io"),
+        &[
+            ("0xaf88d065e77c8cC2239327C5EDb3A432268e5831", "USDC", 6),
+            ("0xFd086bC7CD5C481DCC9C85ebE478A1C0b69FCbb9", "USDT", 6),
+            ("0x82aF49447D8a07e3bd95BD0d56f35241523fBab1", "WETH", 18),
+        ],
+    ),
+    (
+        10,
+        "optimism",
+        &["optimism"],
+        "ETH",
+        Some("https://optimistic.etherscan.io"),
+        &[
+            ("0x0b2C639c533813f4Aa9D7837CAf62653d097Ff85", "USDC", 6),
+            ("0x94b008aA00579c1307B0EF2c499aD98a8ce58e58", "USDT", 6),
+            ("0x4200000000000000000000000000000000000006", "WETH", 18),
+        ],
+    ),
+    (
+        8453,
+        "base",
+        &["base"],
+        "ETH",
+        Some("https://basescan.org"),
+        &[
+            ("0x833589fCD6eDb6E08f4c7C32D4f71b54bdA02913", "USDC", 6),
+            ("0x4200000000000000000000000000000000000006", "WETH", 18),
+        ],
+    ),
+    (
+        56,
+        "bsc",
+        &["bsc", "bnb"],
+        "BNB",
+        Some("https://bscscan.com"),
+        &[
+            ("0x55d398326f99059fF775485246999027B3197955", "USDT", 18),
+            ("0x8AC76a51cc950d9822D68b83fE1Ad97B32Cd580d", "USDC", 18),
+            ("0xbb4CdB9CBd36B01bD1cBaEBF2De08d9173bc095c", "WBNB", 18),
+        ],
+    ),
+    (
+        31337,
+        "anvil",
+        &["anvil", "hardhat", "localhost"],
+        "ETH",
+        None,
+        &[],
+    ),
+];
+
+/// The built-in chains plus any loaded from a file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChainRegistry {
+    chains: Vec<ChainInfo>,
+}
+
+impl Default for ChainRegistry {
+    fn default() -> Self {
+        let chains = BUILTIN
+            .iter()
+            .map(
+                |(chain_id, name, networks, symbol, explorer, tokens)| ChainInfo {
+                    chain_id: *chain_id,
+                    name: name.to_string(),
+                    networks: networks.iter().map(|n| n.to_string()).collect(),
+                    native_symbol: symbol.to_string(),
+                    native_decimals: 18,
+                    explorer_url: explorer.map(str::to_string),
+                    tokens: tokens
+                        .iter()
+                        .map(|(address, symbol, decimals)| KnownToken {
+                            address: address.parse().expect("valid built-in token address"),
+                            symbol: symbol.to_string(),
+                            decimals: *decimals,
+                            behavior: None,
+                        })
+                        .collect(),
+                },
+            )
+            .collect();
+        ChainRegistry { chains }
+    }
+}
+
+impl ChainRegistry {
+    /// The built-in chains extended with the JSON file at `path`.
+    pub fn load(path: &str) -> anyhow::Result<Self> {
+        let raw = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read chain registry {}", path))?;
+        let chains: Vec<ChainInfo> = serde_json::from_str(&raw)
+            .with_context(|| format!("failed to parse chain registry {}", path))?;
+        let mut registry = ChainRegistry::default();
+        registry.extend(chains);
+        Ok(registry)
+    }
+
+    /// Add `chains`, merging each into the existing entry with its id.
+    pub fn extend(&mut self, chains: Vec<ChainInfo>) {
+        for mut chain in chains {
+            chain.explorer_url = chain
+                .explorer_url
+                .map(|url| url.trim_end_matches('/').to_string());
+            match self
+                .chains
+                .iter_mut()
+                .find(|c| c.chain_id == chain.chain_id)
+            {
+                Some(existing) => {
+                    let mut tokens = std::mem::take(&mut existing.tokens);
+                    tokens.retain(|t| chain.token(t.address).is_none());
+                    tokens.append(&mut chain.tokens);
+                    *existing = ChainInfo { tokens, ..chain };
+                }
+                None => self.chains.push(chain),
+            }
+        }
+    }
+
+    pub fn get(&self, chain_id: u64) -> Option<&ChainInfo> {
+        self.chains.iter().find(|c| c.chain_id == chain_id)
+    }
+
+    /// The chain `network` names, compared case-insensitively.
+    pub fn by_network(&self, network: &str) -> Option<&ChainInfo> {
+        self.chains
+            .iter()
+            .find(|c| c.networks.iter().any(|n| n.eq_ignore_ascii_case(network)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builtin_lookup() {
+        let registry = ChainRegistry::default();
+        let mainnet = registry.by_network("Mainnet").unwrap();
+        assert_eq!(mainnet.chain_id, 1);
+        assert_eq!(mainnet.native_symbol, "ETH");
+        let usdc: Address = "0xa0b86991c6218b36c1d19d4a2e9eb0ce3606eb48"
+            .parse()
+            .unwrap();
+        assert_eq!(mainnet.token(usdc).unwrap().decimals, 6);
+        assert_eq!(registry.get(137).unwrap().name, "polygon");
+        assert!(registry.by_network("unknown").is_none());
+    }
+
+    #[test]
+    fn test_file_entries_merge_into_builtin() {
+        let chains: Vec<ChainInfo> = serde_json::from_value(serde_json::json!([
+            {
+                "chain_id": 1,
+                "name": "ethereum",
+                "networks": ["mainnet"],
+                "native_symbol": "ETH",
+                "native_decimals": 18,
+                "explorer_url": "https://eth.blockscout.com/",
+                "tokens": [
+                    {
+                        "address": "0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48",
+                        "symbol": "USDC.e",
+                        "decimals": 6
+                    },
+                    {
+                        "address": "0x0000000000000000000000000000000000000001",
+                        "symbol": "TKN",
+                        "decimals": 9,
+                        "behavior": "fee_on_transfer"
+                    }
+                ]
+            },
+            {
+                "chain_id": 43114,
+                "name": "avalanche",
+                "native_symbol": "AVAX",
+                "native_decimals": 18
+            }
+        ]))
+        .unwrap();
+        let mut registry = ChainRegistry::default();
+        registry.extend(chains);
+
+        let mainnet = registry.get(1).unwrap();
+        assert_eq!(
+            mainnet.explorer_url.as_deref(),
+            Some("https://eth.blockscout.com")
+        );
+        // Built-in tokens are kept; listed ones replace them.
+        assert_eq!(mainnet.tokens.len(), 6);
+        let usdc = "0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48"
+            .parse()
+            .unwrap();
+        assert_eq!(mainnet.token(usdc).unwrap().symbol, "USDC.e");
+        assert_eq!(mainnet.token(usdc).unwrap().behavior, None);
+        let tkn = Address::from_low_u64_be(1);
+        assert_eq!(
+            mainnet.token(tkn).unwrap().behavior,
+            Some(TokenBehavior::FeeOnTransfer)
+        );
+        let avalanche = registry.get(43114).unwrap();
+        assert!(avalanche.tokens.is_empty() && avalanche.explorer_url.is_none());
+    }
+}
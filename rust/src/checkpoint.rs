@@ -0,0 +1,439 @@
+//! Tracker positions that survive restarts (`CHECKPOINT_STORE`).
+//!
+//! The last processed Ethereum block and Solana slot, the last block of
+//! each `[[evm_networks]]` and `[[substrate_networks]]` chain and Tron's
+//! block timestamp (see `tron.rs`) are restored before the trackers start,
+//! and saved by the `checkpoint_save` job and once more on shutdown (see
+//! `shutdown.rs`). Positions are keyed by `<chain>:<network>`, so one store can serve several deployments and
+//! switching `ETH_NETWORK` never resumes from another chain's block:
+//!
+//! - `redis` (default): fields of the hash `CHECKPOINT_KEY` on `REDIS_URL`.
+//! - `file`: a JSON object in `CHECKPOINT_FILE`.
+//! - `none`: nothing is persisted; every start is a first start.
+//!
+//! The publisher's per-chain [sequence numbers](crate::sequence) are saved
+//! alongside, as `sequence:<chain>:<network>`, and those of filtered
+//! destinations as `sequence:<chain>:<network>@<destination>`.
+//!
+//! A restored Ethereum checkpoint far behind the head would make the poller
+//! and the ERC-20 backfill replay a long range; at most `MAX_CATCHUP_BLOCKS`
+//! blocks are caught up and older ones are skipped with a warning.
+//!
+//! With `CHECKPOINT_RETENTION_DAYS` set, every saved position is stamped
+//! with its save time as `saved_at:<key>`, and the `retention` job (see
+//! `retention.rs`) removes positions no deployment has saved for that many
+//! days, such as those of a network that was switched off.
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
+
+use anyhow::{anyhow, Context};
+use tokio::sync::Mutex;
+use tracing::{info, warn};
+
+use crate::config::Config;
+use crate::sequence::Sequences;
+
+/// Checkpoint of an `[[evm_networks]]`, `[[substrate_networks]]` or the
+/// Tron tracker, with its `<chain>:<network>` key.
+pub type EvmCheckpoint = (String, Arc<Mutex<Option<u64>>>);
+
+/// Where checkpoints are persisted.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum CheckpointStore {
+    None,
+    /// Hash key on `REDIS_URL`.
+    #[default]
+    Redis,
+    File,
+}
+
+impl FromStr for CheckpointStore {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "none" => Ok(CheckpointStore::None),
+            "redis" => Ok(CheckpointStore::Redis),
+            "file" => Ok(CheckpointStore::File),
+            other => Err(anyhow!(
+                "invalid CHECKPOINT_STORE '{}' (expected redis, file or none)",
+                other
+            )),
+        }
+    }
+}
+
+/// Checkpoint store settings.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CheckpointConfig {
+    pub store: CheckpointStore,
+    /// Redis hash holding the positions (`CHECKPOINT_KEY`).
+    pub key: String,
+    /// JSON file holding the positions (`CHECKPOINT_FILE`).
+    pub file: PathBuf,
+    /// Most blocks caught up after a restart (`MAX_CATCHUP_BLOCKS`); 0 for
+    /// no limit.
+    pub max_catchup_blocks: u64,
+    /// Days a position is kept without being saved
+    /// (`CHECKPOINT_RETENTION_DAYS`); 0 keeps them forever.
+    pub retention_days: u64,
+}
+
+impl Default for CheckpointConfig {
+    fn default() -> Self {
+        CheckpointConfig {
+            store: CheckpointStore::default(),
+            key: "tracker:checkpoints".to_string(),
+            file: PathBuf::from("tracker-checkpoints.json"),
+            max_catchup_blocks: 10_000,
+            retention_days: 0,
+        }
+    }
+}
+
+/// Positions by `<chain>:<network>`.
+type Positions = BTreeMap<String, u64>;
+
+/// Prefix of the Unix second a position was last saved at.
+const SAVED_AT_PREFIX: &str = "saved_at:";
+
+fn eth_key(cfg: &Config) -> String {
+    format!("ethereum:{}", cfg.eth_network)
+}
+
+fn sol_key(cfg: &Config) -> String {
+    format!("solana:{}", cfg.sol_network)
+}
+
+async fn read(cfg: &Config) -> anyhow::Result<Positions> {
+    match cfg.checkpoints.store {
+        CheckpointStore::None => Ok(Positions::new()),
+        CheckpointStore::Redis => {
+            let client = redis::Client::open(cfg.redis_url.as_str())?;
+            let mut con = client.get_multiplexed_async_connection().await?;
+            let positions: Positions = redis::cmd("HGETALL")
+                .arg(&cfg.checkpoints.key)
+                .query_async(&mut con)
+                .await?;
+            Ok(positions)
+        }
+        CheckpointStore::File => read_file(&cfg.checkpoints.file),
+    }
+}
+
+fn read_file(path: &Path) -> anyhow::Result<Positions> {
+    match std::fs::read_to_string(path) {
+        Ok(raw) => serde_json::from_str(&raw)
+            .with_context(|| format!("invalid checkpoint file {}", path.display())),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Positions::new()),
+        Err(e) => Err(e).with_context(|| format!("failed to read {}", path.display())),
+    }
+}
+
+/// Replace `path` atomically, keeping positions of other networks.
+fn write_file(path: &Path, positions: &Positions) -> anyhow::Result<()> {
+    let mut merged = read_file(path)?;
+    merged.extend(positions.iter().map(|(k, v)| (k.clone(), *v)));
+    replace_file(path, &merged)
+}
+
+fn replace_file(path: &Path, positions: &Positions) -> anyhow::Result<()> {
+    let tmp = path.with_extension("tmp");
+    std::fs::write(&tmp, serde_json::to_string_pretty(positions)?)
+        .and_then(|()| std::fs::rename(&tmp, path))
+        .with_context(|| format!("failed to write {}", path.display()))
+}
+
+/// Add or overwrite `positions` in the store.
+async fn write(cfg: &Config, positions: &Positions) -> anyhow::Result<()> {
+    match cfg.checkpoints.store {
+        CheckpointStore::None => Ok(()),
+        CheckpointStore::Redis => {
+            let client = redis::Client::open(cfg.redis_url.as_str())?;
+            let mut con = client.get_multiplexed_async_connection().await?;
+            let mut cmd = redis::cmd("HSET");
+            cmd.arg(&cfg.checkpoints.key);
+            for (key, position) in positions {
+                cmd.arg(key).arg(position);
+            }
+            cmd.query_async::<_, ()>(&mut con).await?;
+            Ok(())
+        }
+        CheckpointStore::File => write_file(&cfg.checkpoints.file, positions),
+    }
+}
+
+/// Load the saved checkpoints of the configured networks into the trackers'
+/// positions, and the saved sequence numbers into `sequences`. Called once,
+/// before the trackers start.
+pub async fn restore(
+    cfg: &Config,
+    last_eth_block: &Arc<Mutex<Option<u64>>>,
+    last_sol_slot: &Arc<Mutex<Option<u64>>>,
+    evm: &[EvmCheckpoint],
+    sequences: &Sequences,
+) -> anyhow::Result<()> {
+    let positions = read(cfg).await?;
+    sequences.restore(&positions);
+    if let Some(block) = positions.get(&eth_key(cfg)) {
+        info!("Resuming {} from block {}", eth_key(cfg), block);
+        *last_eth_block.lock().await = Some(*block);
+    }
+    if let Some(slot) = positions.get(&sol_key(cfg)) {
+        info!("Resuming {} from slot {}", sol_key(cfg), slot);
+        *last_sol_slot.lock().await = Some(*slot);
+    }
+    for (key, last_block) in evm {
+        if let Some(block) = positions.get(key) {
+            info!("Resuming {} from block {}", key, block);
+            *last_block.lock().await = Some(*block);
+        }
+    }
+    Ok(())
+}
+
+/// Persist the current positions and sequence numbers. Chains without a
+/// position yet are left untouched in the store.
+pub async fn save(
+    cfg: &Config,
+    last_eth_block: &Arc<Mutex<Option<u64>>>,
+    last_sol_slot: &Arc<Mutex<Option<u64>>>,
+    evm: &[EvmCheckpoint],
+    sequences: &Sequences,
+) -> anyhow::Result<()> {
+    let mut positions = sequences.reserved();
+    if let Some(block) = *last_eth_block.lock().await {
+        positions.insert(eth_key(cfg), block);
+    }
+    if let Some(slot) = *last_sol_slot.lock().await {
+        positions.insert(sol_key(cfg), slot);
+    }
+    for (key, last_block) in evm {
+        if let Some(block) = *last_block.lock().await {
+            positions.insert(key.clone(), block);
+        }
+    }
+    if positions.is_empty() {
+        return Ok(());
+    }
+    if cfg.checkpoints.retention_days > 0 {
+        let now = chrono::Utc::now().timestamp().max(0) as u64;
+        let stamps: Vec<String> = positions
+            .keys()
+            .map(|key| format!("{}{}", SAVED_AT_PREFIX, key))
+            .collect();
+        positions.extend(stamps.into_iter().map(|stamp| (stamp, now)));
+    }
+    write(cfg, &positions).await
+}
+
+/// Remove the positions, sequence numbers included, not saved within
+/// `CHECKPOINT_RETENTION_DAYS` of `now` (Unix seconds), with their stamps.
+/// Positions saved before retention was configured have no stamp; they are
+/// stamped `now` and expire from there. Returns how many positions were
+/// removed.
+pub async fn expire(cfg: &Config, now: u64) -> anyhow::Result<u64> {
+    let retention_secs = cfg.checkpoints.retention_days.saturating_mul(86_400);
+    if retention_secs == 0 {
+        return Ok(0);
+    }
+    let positions = read(cfg).await?;
+    let (stale, unstamped) = stale_positions(&positions, now.saturating_sub(retention_secs));
+    if !unstamped.is_empty() {
+        let stamps: Positions = unstamped
+            .iter()
+            .map(|key| (format!("{}{}", SAVED_AT_PREFIX, key), now))
+            .collect();
+        write(cfg, &stamps).await?;
+    }
+    if stale.is_empty() {
+        return Ok(0);
+    }
+    let fields: Vec<String> = stale
+        .iter()
+        .flat_map(|key| [key.clone(), format!("{}{}", SAVED_AT_PREFIX, key)])
+        .collect();
+    match cfg.checkpoints.store {
+        CheckpointStore::None => {}
+        CheckpointStore::Redis => {
+            let client = redis::Client::open(cfg.redis_url.as_str())?;
+            let mut con = client.get_multiplexed_async_connection().await?;
+            redis::cmd("HDEL")
+                .arg(&cfg.checkpoints.key)
+                .arg(&fields)
+                .query_async::<_, ()>(&mut con)
+                .await?;
+        }
+        CheckpointStore::File => {
+            let mut kept = read_file(&cfg.checkpoints.file)?;
+            kept.retain(|key, _| !fields.contains(key));
+            replace_file(&cfg.checkpoints.file, &kept)?;
+        }
+    }
+    for key in &stale {
+        info!(
+            "Removed checkpoint {}, not saved in {} days",
+            key, cfg.checkpoints.retention_days
+        );
+    }
+    Ok(stale.len() as u64)
+}
+
+/// Positions last saved before `cutoff`, and positions without a save time.
+fn stale_positions(positions: &Positions, cutoff: u64) -> (Vec<String>, Vec<String>) {
+    let (mut stale, mut unstamped) = (Vec::new(), Vec::new());
+    for key in positions.keys() {
+        if key.starts_with(SAVED_AT_PREFIX) {
+            continue;
+        }
+        match positions.get(&format!("{}{}", SAVED_AT_PREFIX, key)) {
+            Some(saved_at) if *saved_at < cutoff => stale.push(key.clone()),
+            Some(_) => {}
+            None => unstamped.push(key.clone()),
+        }
+    }
+    (stale, unstamped)
+}
+
+/// Block after which to resume, given the checkpoint `prev` and the chain
+/// head `current`: `prev`, unless that is more than `max_catchup` blocks
+/// behind.
+pub fn catch_up_from(prev: u64, current: u64, max_catchup: u64) -> u64 {
+    if max_catchup == 0 || current.saturating_sub(prev) <= max_catchup {
+        return prev;
+    }
+    let from = current - max_catchup;
+    warn!(
+        "ETH checkpoint {} is {} blocks behind head {}; skipping blocks {} to {} \
+         (MAX_CATCHUP_BLOCKS={})",
+        prev,
+        current - prev,
+        current,
+        prev + 1,
+        from,
+        max_catchup
+    );
+    from
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_store() {
+        assert_eq!(
+            "Redis".parse::<CheckpointStore>().unwrap(),
+            CheckpointStore::Redis
+        );
+        assert_eq!(
+            "file".parse::<CheckpointStore>().unwrap(),
+            CheckpointStore::File
+        );
+        assert_eq!(
+            "none".parse::<CheckpointStore>().unwrap(),
+            CheckpointStore::None
+        );
+        assert!("sqlite".parse::<CheckpointStore>().is_err());
+    }
+
+    #[test]
+    fn test_catch_up_is_bounded() {
+        assert_eq!(catch_up_from(100, 150, 1_000), 100);
+        assert_eq!(catch_up_from(100, 1_100, 1_000), 100);
+        assert_eq!(catch_up_from(100, 5_000, 1_000), 4_000);
+        assert_eq!(catch_up_from(100, 5_000, 0), 100);
+        // A chain behind the checkpoint is the poller's regression case.
+        assert_eq!(catch_up_from(100, 50, 1_000), 100);
+    }
+
+    #[tokio::test]
+    async fn test_file_store_round_trip() {
+        let path = std::env::temp_dir().join("tracker_checkpoint_test.json");
+        std::fs::write(&path, r#"{"ethereum:sepolia": 7}"#).unwrap();
+        let cfg = Config {
+            eth_network: "mainnet".into(),
+            sol_network: "devnet".into(),
+            checkpoints: CheckpointConfig {
+                store: CheckpointStore::File,
+                file: path.clone(),
+                ..Default::default()
+            },
+            ..Config::default()
+        };
+
+        let block = Arc::new(Mutex::new(Some(19_000_000)));
+        let slot = Arc::new(Mutex::new(None));
+        let sequences = Sequences::default();
+        sequences.restore(&Positions::from([("sequence:ethereum:mainnet".into(), 41)]));
+        let polygon: Vec<EvmCheckpoint> = vec![(
+            "polygon:mainnet".into(),
+            Arc::new(Mutex::new(Some(55_000_000))),
+        )];
+        save(&cfg, &block, &slot, &polygon, &sequences)
+            .await
+            .unwrap();
+
+        let (block, slot) = (Arc::new(Mutex::new(None)), Arc::new(Mutex::new(None)));
+        let polygon: Vec<EvmCheckpoint> = vec![("polygon:mainnet".into(), Arc::default())];
+        let sequences = Sequences::default();
+        restore(&cfg, &block, &slot, &polygon, &sequences)
+            .await
+            .unwrap();
+        let saved = read_file(&path).unwrap();
+        std::fs::remove_file(&path).ok();
+        assert_eq!(*block.lock().await, Some(19_000_000));
+        assert_eq!(*slot.lock().await, None);
+        assert_eq!(*polygon[0].1.lock().await, Some(55_000_000));
+        assert_eq!(
+            sequences.positions().get("sequence:ethereum:mainnet"),
+            Some(&(41 + crate::sequence::RESERVE))
+        );
+        // Other networks' positions are kept.
+        assert_eq!(saved.get("ethereum:sepolia"), Some(&7));
+    }
+
+    #[tokio::test]
+    async fn test_stale_positions_expire() {
+        let path = std::env::temp_dir().join("tracker_checkpoint_expire_test.json");
+        std::fs::write(
+            &path,
+            r#"{"ethereum:sepolia": 7, "saved_at:ethereum:sepolia": 1000,
+                "sequence:ethereum:sepolia": 3, "saved_at:sequence:ethereum:sepolia": 1000,
+                "ethereum:mainnet": 19000000, "saved_at:ethereum:mainnet": 90000,
+                "polygon:mainnet": 55000000}"#,
+        )
+        .unwrap();
+        let cfg = Config {
+            checkpoints: CheckpointConfig {
+                store: CheckpointStore::File,
+                file: path.clone(),
+                retention_days: 1,
+                ..Default::default()
+            },
+            ..Config::default()
+        };
+
+        let removed = expire(&cfg, 100_000).await.unwrap();
+        let kept = read_file(&path).unwrap();
+        // Unstamped positions expire a retention period after first seen.
+        let later = expire(&cfg, 100_000 + 86_401).await.unwrap();
+        let left = read_file(&path).unwrap();
+        std::fs::remove_file(&path).ok();
+        assert_eq!(removed, 2);
+        assert_eq!(
+            kept.keys().collect::<Vec<_>>(),
+            [
+                "ethereum:mainnet",
+                "polygon:mainnet",
+                "saved_at:ethereum:mainnet",
+                "saved_at:polygon:mainnet"
+            ]
+        );
+        assert_eq!(kept.get("saved_at:polygon:mainnet"), Some(&100_000));
+        assert_eq!(later, 2);
+        assert!(left.is_empty());
+    }
+}
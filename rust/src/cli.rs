@@ -0,0 +1,229 @@
+use clap::{Parser, Subcommand};
+use std::path::PathBuf;
+
+use crate::graph::GraphFormat;
+use crate::ledger::{LedgerFormat, NumberLocale};
+
+/// Command line interface for the listener binary.
+///
+/// Running without a subcommand starts the trackers, which keeps the
+/// existing `cargo run` / container entrypoint behaviour unchanged.
+#[derive(Debug, Parser)]
+#[command(name = "tracker", version, about = "Cross-chain transaction tracker")]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+    /// Configuration profile: `dev`, `staging`, `prod` or a profile file.
+    /// The environment and `.env` override its values.
+    #[arg(long, global = true, env = "TRACKER_PROFILE")]
+    pub profile: Option<String>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Verify RPC endpoints, Redis and watched addresses, then exit.
+    Doctor,
+    /// Run the HTTP pollers for a fixed window, recording all RPC traffic.
+    Record {
+        /// Directory to write `eth.jsonl` / `sol.jsonl` captures into.
+        #[arg(long)]
+        out: PathBuf,
+        /// How long to record for, in seconds.
+        #[arg(long, default_value_t = 60)]
+        duration_secs: u64,
+    },
+    /// Re-run event processing against a recorded capture, offline.
+    Replay {
+        /// Directory containing a capture written by `record`.
+        capture: PathBuf,
+        /// Write events as JSON lines here instead of stdout.
+        #[arg(long)]
+        out: Option<PathBuf>,
+    },
+    /// Print TypeScript types and zod validators for the event payload.
+    Typescript {
+        /// Write to this file instead of stdout.
+        #[arg(long)]
+        out: Option<PathBuf>,
+        /// Emit only the interfaces, without zod validators.
+        #[arg(long)]
+        no_zod: bool,
+    },
+    /// Convert published events into double-entry ledger transactions.
+    Ledger {
+        /// Events as JSON lines, e.g. written by `replay --out`; `-` reads
+        /// stdin.
+        input: PathBuf,
+        #[arg(long, value_enum, default_value_t = LedgerFormat::Beancount)]
+        format: LedgerFormat,
+        /// IANA time zone transactions are dated in, e.g. `Europe/Berlin`.
+        #[arg(
+            long,
+            env = "REPORT_TIMEZONE",
+            default_value = "UTC",
+            value_parser = crate::ledger::parse_timezone
+        )]
+        timezone: chrono_tz::Tz,
+        /// Number format of CSV amounts.
+        #[arg(long, value_enum, env = "REPORT_LOCALE", default_value_t = NumberLocale::Plain)]
+        locale: NumberLocale,
+        /// Write to this file instead of stdout.
+        #[arg(long)]
+        out: Option<PathBuf>,
+    },
+    /// Export the address-relationship graph of published transfers.
+    Graph {
+        /// Events as JSON lines, e.g. written by `replay --out`; `-` reads
+        /// stdin.
+        input: PathBuf,
+        #[arg(long, value_enum, default_value_t = GraphFormat::Graphml)]
+        format: GraphFormat,
+        /// Write to this file instead of stdout.
+        #[arg(long)]
+        out: Option<PathBuf>,
+    },
+    /// Terminal dashboard for a running listener: chain status, event rates
+    /// and recent events.
+    Tui {
+        /// Redis instance the listener publishes to.
+        #[arg(long, env = "REDIS_URL", default_value = "redis://127.0.0.1:6379")]
+        redis_url: String,
+        /// Destination channel to follow.
+        #[arg(long, default_value = "cross_chain_events")]
+        channel: String,
+        /// Base URL of the listener's admin API (`ADMIN_BIND_ADDR`).
+        #[arg(long, default_value = "http://127.0.0.1:9090")]
+        admin_url: String,
+        #[arg(long, env = "ADMIN_TOKEN", hide_env_values = true)]
+        admin_token: Option<String>,
+    },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_no_subcommand_runs_service() {
+        let cli = Cli::try_parse_from(["tracker"]).unwrap();
+        assert!(cli.command.is_none());
+    }
+
+    #[test]
+    fn test_profile_flag() {
+        let cli = Cli::try_parse_from(["tracker", "doctor", "--profile", "dev"]).unwrap();
+        assert!(matches!(cli.command, Some(Command::Doctor)));
+        assert_eq!(cli.profile.as_deref(), Some("dev"));
+    }
+
+    #[test]
+    fn test_doctor_subcommand() {
+        let cli = Cli::try_parse_from(["tracker", "doctor"]).unwrap();
+        assert!(matches!(cli.command, Some(Command::Doctor)));
+    }
+
+    #[test]
+    fn test_record_and_replay_subcommands() {
+        let cli = Cli::try_parse_from(["tracker", "record", "--out", "cap"]).unwrap();
+        match cli.command {
+            Some(Command::Record { out, duration_secs }) => {
+                assert_eq!(out, PathBuf::from("cap"));
+                assert_eq!(duration_secs, 60);
+            }
+            other => panic!("unexpected command: {:?}", other),
+        }
+
+        let cli =
+            Cli::try_parse_from(["tracker", "replay", "cap", "--out", "events.jsonl"]).unwrap();
+        match cli.command {
+            Some(Command::Replay { capture, out }) => {
+                assert_eq!(capture, PathBuf::from("cap"));
+                assert_eq!(out, Some(PathBuf::from("events.jsonl")));
+            }
+            other => panic!("unexpected command: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_typescript_subcommand() {
+        let cli = Cli::try_parse_from(["tracker", "typescript", "--no-zod"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Some(Command::Typescript {
+                out: None,
+                no_zod: true
+            })
+        ));
+    }
+
+    #[test]
+    fn test_ledger_subcommand() {
+        let cli = Cli::try_parse_from(["tracker", "ledger", "events.jsonl"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Some(Command::Ledger {
+                format: LedgerFormat::Beancount,
+                out: None,
+                ..
+            })
+        ));
+        let cli = Cli::try_parse_from([
+            "tracker",
+            "ledger",
+            "-",
+            "--format",
+            "csv",
+            "--timezone",
+            "America/New_York",
+            "--locale",
+            "de",
+        ])
+        .unwrap();
+        assert!(matches!(
+            cli.command,
+            Some(Command::Ledger {
+                format: LedgerFormat::Csv,
+                timezone: chrono_tz::Tz::America__New_York,
+                locale: NumberLocale::De,
+                ..
+            })
+        ));
+        assert!(Cli::try_parse_from(["tracker", "ledger", "-", "--timezone", "CEST"]).is_err());
+        assert!(Cli::try_parse_from(["tracker", "ledger", "-", "--format", "xlsx"]).is_err());
+    }
+
+    #[test]
+    fn test_graph_subcommand() {
+        let cli = Cli::try_parse_from(["tracker", "graph", "events.jsonl"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Some(Command::Graph {
+                format: GraphFormat::Graphml,
+                out: None,
+                ..
+            })
+        ));
+        let cli = Cli::try_parse_from(["tracker", "graph", "-", "--format", "json"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Some(Command::Graph {
+                format: GraphFormat::Json,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_tui_subcommand() {
+        let cli = Cli::try_parse_from(["tracker", "tui", "--channel", "eth_only"]).unwrap();
+        match cli.command {
+            Some(Command::Tui {
+                channel, admin_url, ..
+            }) => {
+                assert_eq!(channel, "eth_only");
+                assert_eq!(admin_url, "http://127.0.0.1:9090");
+            }
+            other => panic!("unexpected command: {:?}", other),
+        }
+    }
+}
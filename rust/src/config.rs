@@ -1,119 +1,1387 @@
-use anyhow::{Context, Result};
+use anyhow::{anyhow, Context, Result};
 use dotenvy::dotenv;
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use crate::address_book::{AddressBook, AddressEntry};
+use crate::asset_map::{AssetMap, AssetMapping};
+use crate::chains::{ChainInfo, ChainRegistry};
+use crate::checkpoint::{CheckpointConfig, CheckpointStore};
+use crate::confirmations::SolCommitment;
+use crate::dedup::DedupKey;
+use crate::dedup_store::DedupStoreKind;
+use crate::event_type::EventType;
+use crate::evm_networks::{EvmNetwork, EvmNetworkConfig};
+use crate::fee_spike::FeeSpikeConfig;
+use crate::health::{HealthConfig, DEFAULT_MAX_LAG_ETH};
+use crate::inspect::DEFAULT_RECENT_EVENTS;
+use crate::kill_switch::{KillSwitchConfig, KillSwitchMode};
+use crate::liquidation_risk::LiquidationRiskConfig;
+use crate::memory::MemoryBudget;
+use crate::portfolio::{PortfolioConfig, Portfolios};
+use crate::publisher::PAYLOAD_FIELDS;
+use crate::quarantine::{QuarantineConfig, DEFAULT_MAX_EVENTS, DEFAULT_RETRY_BUDGET};
+use crate::scheduler;
+use crate::shutdown;
+use crate::sink::{self, PartitionKey, SinkKind};
+use crate::spam::{SpamFilter, SpamMode};
+use crate::stuck::StuckConfig;
+use crate::substrate::{SubstrateNetwork, SubstrateNetworkConfig};
+use crate::sweeps::SweepConfig;
+use crate::token_filter::TokenFilter;
+use crate::tron::TronConfig;
+use crate::validators::ValidatorConfig;
 
 /// Runtime configuration for the listener service loaded from environment.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct Config {
+    /// Whether the Ethereum trackers run at all (`ETH_ENABLED`).
+    pub eth_enabled: bool,
+    /// Whether the Solana trackers run at all (`SOL_ENABLED`).
+    pub sol_enabled: bool,
     pub eth_rpc_url: String,
     pub sol_rpc_url: String,
+    /// Where Solana activity is read from (`SOL_SOURCE`).
+    pub sol_source: SolSource,
+    /// Helius API key for `SOL_SOURCE=helius` (`HELIUS_API_KEY`).
+    pub helius_api_key: Option<String>,
+    /// Yellowstone gRPC endpoint for `SOL_SOURCE=geyser` (`GEYSER_ENDPOINT`).
+    pub geyser_endpoint: Option<String>,
+    /// `x-token` sent to the Yellowstone endpoint (`GEYSER_X_TOKEN`).
+    pub geyser_x_token: Option<String>,
     pub redis_url: String,
     pub watched_addresses_eth: Vec<String>,
     pub watched_addresses_sol: Vec<String>,
     pub eth_network: String,
     pub sol_network: String,
+    /// Registry entry of the tracked EVM chain, from `ETH_CHAIN_ID` or else
+    /// `ETH_NETWORK`, with chains added by `CHAIN_REGISTRY_FILE`. `None` for
+    /// networks the registry doesn't know.
+    pub eth_chain: Option<ChainInfo>,
+    /// Solana explorer for `explorer_url` links (`SOL_EXPLORER_URL`).
+    pub sol_explorer_url: String,
+    /// ERC-20 contracts to include/exclude (`ETH_TOKEN_ALLOWLIST` /
+    /// `ETH_TOKEN_DENYLIST`).
+    pub eth_tokens: TokenFilter,
+    /// Resolve the metadata of the `ETH_TOKEN_ALLOWLIST` tokens before the
+    /// ETH trackers start (`TOKEN_WARMUP`).
+    pub token_warmup: bool,
+    /// SPL mints to include/exclude (`SOL_TOKEN_ALLOWLIST` /
+    /// `SOL_TOKEN_DENYLIST`).
+    pub sol_tokens: TokenFilter,
+    /// Spam heuristics for unsolicited token transfers (`SPAM_FILTER`,
+    /// `SPAM_SYMBOL_PATTERNS`).
+    pub spam: SpamFilter,
     #[allow(dead_code)]
     pub poll_interval_secs: u64,
     #[allow(dead_code)]
     pub log_level: Option<String>,
+    /// How long published event ids are remembered for deduplication.
+    pub dedup_retention_secs: u64,
+    /// How event ids / dedup keys are built (`DEDUP_KEY`).
+    pub dedup_key: DedupKey,
+    /// Where event ids are claimed before publishing, so restarts and
+    /// replicas don't publish the same event twice (`DEDUP_STORE`;
+    /// `DISTRIBUTED_DEDUP=true` selects Redis).
+    pub dedup_store: DedupStoreKind,
+    /// Key prefix of Redis claims (`DISTRIBUTED_DEDUP_PREFIX`).
+    pub distributed_dedup_prefix: String,
+    /// Claim log of the file store (`DEDUP_STORE_FILE`).
+    pub dedup_store_file: PathBuf,
+    /// Deployment region this listener runs in (`REGION`, e.g. `eu-west`).
+    /// Tags every event, and scopes Redis dedup claims so each region of an
+    /// active-active deployment publishes its own copy.
+    pub region: Option<String>,
+    /// Name of this listener in events and health data (`INSTANCE_ID`;
+    /// defaults to `HOSTNAME`, then `tracker`).
+    pub instance_id: String,
+    /// Largest block span per `eth_getLogs` request during backfill
+    /// (`ETH_LOGS_CHUNK_SIZE`). Narrowed automatically when a provider
+    /// rejects a range.
+    pub eth_logs_chunk_size: u64,
+    /// Optional archive endpoint (`ETH_ARCHIVE_RPC_URL`, HTTP) used only for
+    /// historical ranges the primary node has pruned.
+    pub eth_archive_rpc_url: Option<String>,
+    /// More endpoints of the same Ethereum network
+    /// (`ETH_RPC_BACKUP_URLS`). The `rpc_consistency` job compares them
+    /// with `ETH_RPC_URL` and pins tracking to the healthiest.
+    pub eth_rpc_backup_urls: Vec<String>,
+    /// Blocks an endpoint's head may trail the highest head before the
+    /// consistency guard counts it as lagging (`ETH_RPC_MAX_HEAD_LAG`).
+    pub eth_rpc_max_head_lag: u64,
+    /// Calls per JSON-RPC batch request in the ETH HTTP poller
+    /// (`ETH_RPC_BATCH_SIZE`). 0 sends one request per call.
+    pub eth_rpc_batch_size: u64,
+    /// Track native transfers with Alchemy's `alchemy_minedTransactions`
+    /// subscription instead of full blocks (`ETH_ALCHEMY_MINED_TRANSACTIONS`:
+    /// `auto`, the default, enables it for `*.alchemy.com` websocket URLs).
+    pub eth_alchemy_mined_transactions: bool,
+    /// Blocks mined on top of a block before its events are published
+    /// (`ETH_CONFIRMATIONS`, default 0); see [`crate::confirmations`].
+    pub eth_confirmations: u64,
+    /// Publish `pending_transfer` events for mempool transactions of
+    /// watched addresses (`ETH_PENDING_TRANSFERS`); see [`crate::pending`].
+    pub eth_pending_transfers: bool,
+    /// Simulate those transactions first (`ETH_PENDING_SIMULATION`).
+    pub eth_pending_simulation: bool,
+    /// Commitment the Solana trackers read at (`SOL_COMMITMENT`).
+    pub sol_commitment: SolCommitment,
+    /// Etherscan API key for bootstrapping watched ETH addresses on first
+    /// start (`BOOTSTRAP_ETHERSCAN_API_KEY`); no bootstrap when unset.
+    pub bootstrap_etherscan_api_key: Option<String>,
+    /// Etherscan-compatible API endpoint (`BOOTSTRAP_ETHERSCAN_URL`).
+    pub bootstrap_etherscan_url: String,
+    /// Helius API key for bootstrapping watched Solana addresses
+    /// (`BOOTSTRAP_HELIUS_API_KEY`); no bootstrap when unset.
+    pub bootstrap_helius_api_key: Option<String>,
+    /// Helius API base URL (`HELIUS_URL`), for bootstrap and the Helius
+    /// source.
+    pub helius_url: String,
+    /// Most recent transfers published per address (`BOOTSTRAP_LIMIT`).
+    pub bootstrap_limit: u64,
+    /// Address for the admin HTTP API (`ADMIN_BIND_ADDR`, e.g.
+    /// `127.0.0.1:9090`). The API is not started when unset.
+    pub admin_bind_addr: Option<String>,
+    /// Address for the gRPC admin and event services (`GRPC_BIND_ADDR`,
+    /// e.g. `127.0.0.1:9091`). Needs the `grpc` feature; not started when
+    /// unset.
+    pub grpc_bind_addr: Option<String>,
+    /// Liveness and readiness probes (`HEALTH_BIND_ADDR`,
+    /// `HEALTH_MAX_LAG_ETH`, `HEALTH_MAX_LAG_SOL`).
+    pub health: HealthConfig,
+    /// Published events kept in memory per chain for `GET /admin/events`
+    /// (`RECENT_EVENTS`, default 1000); 0 keeps none.
+    pub recent_events: usize,
+    /// Directory the `graph_export` job writes the transfer graph to
+    /// (`GRAPH_EXPORT_DIR`); off when unset.
+    pub graph_export_dir: Option<PathBuf>,
+    /// Worker threads of a dedicated runtime per chain
+    /// (`CHAIN_RUNTIME_THREADS`). All chains share the main runtime when
+    /// unset or 0.
+    pub chain_runtime_threads: Option<usize>,
+    /// How long a SIGTERM or SIGINT waits for the trackers, the maintenance
+    /// jobs and the destination queues before exiting
+    /// (`SHUTDOWN_TIMEOUT_SECS`, default 25).
+    pub shutdown_timeout_secs: u64,
+    /// Failed publishes before an event is quarantined
+    /// (`EVENT_RETRY_BUDGET`, default 3), and quarantined events kept
+    /// (`QUARANTINE_MAX`, default 1000), and where they are saved
+    /// (`QUARANTINE_FILE`).
+    pub quarantine: QuarantineConfig,
+    /// Memory budgets enforced by the `memory_report` job; unlimited when
+    /// unset or 0.
+    pub memory: MemoryBudget,
+    /// Bearer token required by the admin API when set (`ADMIN_TOKEN`).
+    pub admin_token: Option<String>,
+    /// Where webhook subscriptions are saved (`WEBHOOKS_FILE`), with their
+    /// acknowledgement state next to it. They are kept in memory only when
+    /// unset.
+    pub webhooks_file: Option<String>,
+    /// Maintenance job schedule overrides from `SCHEDULE_<JOB>` variables,
+    /// keyed by lowercase job name.
+    pub schedules: HashMap<String, String>,
+    /// Output destinations events are fanned out to. Defaults to the single
+    /// `cross_chain_events` channel on `REDIS_URL`.
+    pub destinations: Vec<DestinationConfig>,
+    /// Tokens that count as one logical asset across chains, from the
+    /// `[[assets]]` entries of the config file.
+    pub asset_map: AssetMap,
+    /// Groups of watched addresses valued by the `portfolio_snapshot` job,
+    /// from the `[[portfolios]]` and `[prices]` entries of the config file.
+    pub portfolios: Portfolios,
+    /// Labelled addresses from the `[[address_book]]` entries of the config
+    /// file; outgoing transactions of cold-storage ones raise alerts.
+    pub address_book: AddressBook,
+    /// Aggregate outgoing transfers of watched addresses into
+    /// `sweep_detected` events, from the `[sweep_detection]` section of the
+    /// config file; off when absent.
+    pub sweep_detection: Option<SweepConfig>,
+    /// Solana validators whose delinquency, commission and stake changes are
+    /// published as `validator_event` events, from the
+    /// `[validator_monitoring]` section of the config file; off when absent.
+    pub validator_monitoring: Option<ValidatorConfig>,
+    /// Health factor below which watched Aave borrowers raise
+    /// `liquidation_risk` alerts, from the `[liquidation_risk]` section of the
+    /// config file; off when absent.
+    pub liquidation_risk: Option<LiquidationRiskConfig>,
+    /// How long transactions of watched Ethereum addresses may be pending
+    /// before `stuck_transaction` events, from the `[stuck_transactions]`
+    /// section of the config file; off when absent.
+    pub stuck_transactions: Option<StuckConfig>,
+    /// Gas price over base fee above which transactions of watched senders
+    /// raise `fee_spike` alerts, from the `[fee_spike]` section of the config
+    /// file; off when absent.
+    pub fee_spike: Option<FeeSpikeConfig>,
+    /// EVM chains tracked next to Ethereum, each over its own endpoint, from
+    /// the `[[evm_networks]]` entries of the config file.
+    pub evm_networks: Vec<EvmNetwork>,
+    /// TRX and TRC-20 transfers of watched Tron addresses, from the `[tron]`
+    /// section of the config file; off when absent.
+    pub tron: Option<TronConfig>,
+    /// Substrate chains followed over subxt, from the `[[substrate_networks]]`
+    /// entries of the config file.
+    pub substrate_networks: Vec<SubstrateNetwork>,
+    /// Redis keys that stop publishing during incidents (`KILL_SWITCH_KEY`).
+    pub kill_switch: KillSwitchConfig,
+    /// Where the ETH block and Solana slot checkpoints are persisted
+    /// (`CHECKPOINT_STORE`) and how far a restart may catch up.
+    pub checkpoints: CheckpointConfig,
 }
 
-impl Config {
-    /// Load configuration from environment variables, optionally reading a
-    /// `.env` file for missing values. Required keys will produce an error
-    /// if not present. Optional lists accept comma‑separated values.
-    pub fn from_env() -> Result<Self> {
-        // Prefer existing environment variables set by the process. Only
-        // load a .env file if a required variable is missing. This avoids
-        // dotenv overriding values tests set via std::env::set_var.
-        fn get_required(name: &str) -> Result<String> {
-            if let Ok(v) = std::env::var(name) {
-                return Ok(v);
-            }
+/// What a destination does when its queue is full.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Overflow {
+    /// Wait for space, applying backpressure to the trackers.
+    #[default]
+    Block,
+    /// Drop the event for this destination only and log a warning.
+    Drop,
+}
+
+/// How a destination spreads events across its delivery workers.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OrderingMode {
+    /// Round-robin across workers; no ordering guarantee when `workers > 1`.
+    #[default]
+    Unordered,
+    /// Events for the same watched address always go to the same worker, so
+    /// they are delivered in the order they were published.
+    PerAddress,
+}
+
+/// Solana data source (`SOL_SOURCE`).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SolSource {
+    /// Signature polling (or websocket) against `SOL_RPC_URL`.
+    #[default]
+    Rpc,
+    /// Helius enhanced transactions; needs `HELIUS_API_KEY`.
+    Helius,
+    /// Yellowstone gRPC stream; needs `GEYSER_ENDPOINT` and the `geyser`
+    /// feature.
+    Geyser,
+}
+
+impl FromStr for SolSource {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "rpc" => Ok(SolSource::Rpc),
+            "helius" => Ok(SolSource::Helius),
+            "geyser" => Ok(SolSource::Geyser),
+            other => Err(anyhow!(
+                "SOL_SOURCE must be rpc, helius or geyser, got '{}'",
+                other
+            )),
+        }
+    }
+}
+
+/// Compression applied to batched payloads.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Compression {
+    #[default]
+    None,
+    Zstd,
+}
+
+/// One `[[destinations]]` entry in the config file.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DestinationConfig {
+    pub name: String,
+    /// Transport of the destination's messages.
+    #[serde(default)]
+    pub sink: SinkKind,
+    /// Redis instance for this destination; defaults to `REDIS_URL`.
+    #[serde(default)]
+    pub redis_url: Option<String>,
+    /// Pub/Sub channel of a `redis` sink, or a template such as
+    /// `events.{chain}.{event_type}` (see [`sink::route`]).
+    #[serde(default)]
+    pub channel: String,
+    /// File a `file` sink appends to.
+    #[serde(default)]
+    pub path: Option<PathBuf>,
+    /// Stream key of a `stream` sink.
+    #[serde(default)]
+    pub stream: String,
+    /// File a `stream` sink also appends its entries to, for replays of
+    /// trimmed entries.
+    #[serde(default)]
+    pub archive: Option<PathBuf>,
+    /// Entries a `stream` sink keeps, approximately (`XADD MAXLEN ~`); 0
+    /// never trims.
+    #[serde(default = "default_max_len")]
+    pub max_len: u64,
+    /// Bootstrap servers of a `kafka` sink, comma-separated.
+    #[serde(default)]
+    pub brokers: String,
+    /// Topic of a `kafka` sink, or a template such as `events-{chain}`.
+    #[serde(default)]
+    pub topic: String,
+    /// Message key of a `kafka` sink.
+    #[serde(default)]
+    pub partition_key: PartitionKey,
+    /// Extra librdkafka producer settings of a `kafka` sink.
+    #[serde(default)]
+    pub kafka: BTreeMap<String, String>,
+    /// NATS server of a `nats` sink; defaults to `NATS_URL`.
+    #[serde(default)]
+    pub nats_url: Option<String>,
+    /// Subject template of a `nats` sink, e.g. `events.{chain}.{event_type}`.
+    #[serde(default)]
+    pub subject: String,
+    /// Publish through JetStream and wait for the stream's acknowledgement.
+    #[serde(default)]
+    pub jetstream: bool,
+    /// Database of a `postgres` sink; defaults to `POSTGRES_URL`.
+    #[serde(default)]
+    pub postgres_url: Option<String>,
+    /// Table a `postgres` sink upserts events into.
+    #[serde(default = "default_table")]
+    pub table: String,
+    /// Endpoints a `webhook` sink POSTs every message to.
+    #[serde(default)]
+    pub urls: Vec<String>,
+    /// HMAC key of a `webhook` sink's signatures; defaults to
+    /// `WEBHOOK_SECRET`.
+    #[serde(default)]
+    pub secret: Option<String>,
+    /// File a `webhook` sink appends deliveries that failed every attempt
+    /// to.
+    #[serde(default)]
+    pub dead_letter: Option<PathBuf>,
+    /// Only deliver events for these chains (empty = all).
+    #[serde(default)]
+    pub chains: Vec<String>,
+    /// Only deliver these event types (empty = all).
+    #[serde(default)]
+    pub event_types: Vec<EventType>,
+    /// Payload fields to send, in this order (empty = the full event). Lets
+    /// bandwidth-sensitive channels drop enrichment such as `token`.
+    #[serde(default)]
+    pub fields: Vec<String>,
+    #[serde(default = "default_queue_size")]
+    pub queue_size: usize,
+    #[serde(default)]
+    pub overflow: Overflow,
+    /// Number of concurrent delivery workers, each with its own queue.
+    #[serde(default = "default_workers")]
+    pub workers: usize,
+    #[serde(default)]
+    pub ordering: OrderingMode,
+    #[serde(default = "default_retry_attempts")]
+    pub retry_attempts: usize,
+    #[serde(default = "default_retry_base_ms")]
+    pub retry_base_ms: u64,
+    /// Publish events in batches of up to this many (0 = one message per
+    /// event). A batch is newline-delimited JSON.
+    #[serde(default)]
+    pub batch_max_events: usize,
+    /// Flush a partial batch after this long.
+    #[serde(default = "default_batch_max_delay_ms")]
+    pub batch_max_delay_ms: u64,
+    /// Compression for batches; requires `batch_max_events`.
+    #[serde(default)]
+    pub compression: Compression,
+    /// Days a `file`, `stream` or `postgres` sink keeps events before the
+    /// `retention` job removes them (0 = forever; see `retention.rs`).
+    #[serde(default)]
+    pub retention_days: u64,
+}
+
+fn default_max_len() -> u64 {
+    100_000
+}
+
+fn default_queue_size() -> usize {
+    1024
+}
+
+fn default_workers() -> usize {
+    1
+}
+
+fn default_retry_attempts() -> usize {
+    8
+}
+
+fn default_retry_base_ms() -> u64 {
+    500
+}
+
+fn default_batch_max_delay_ms() -> u64 {
+    1000
+}
+
+fn default_table() -> String {
+    "tracker_events".to_string()
+}
+
+impl Default for DestinationConfig {
+    /// The historical single output: Pub/Sub channel `cross_chain_events`.
+    fn default() -> Self {
+        DestinationConfig {
+            name: "default".to_string(),
+            sink: SinkKind::default(),
+            redis_url: None,
+            channel: "cross_chain_events".to_string(),
+            path: None,
+            stream: String::new(),
+            archive: None,
+            max_len: default_max_len(),
+            brokers: String::new(),
+            topic: String::new(),
+            partition_key: PartitionKey::default(),
+            kafka: BTreeMap::new(),
+            nats_url: None,
+            subject: String::new(),
+            jetstream: false,
+            postgres_url: None,
+            table: default_table(),
+            urls: Vec::new(),
+            secret: None,
+            dead_letter: None,
+            chains: Vec::new(),
+            event_types: Vec::new(),
+            fields: Vec::new(),
+            queue_size: default_queue_size(),
+            overflow: Overflow::default(),
+            workers: default_workers(),
+            ordering: OrderingMode::default(),
+            retry_attempts: default_retry_attempts(),
+            retry_base_ms: default_retry_base_ms(),
+            batch_max_events: 0,
+            batch_max_delay_ms: default_batch_max_delay_ms(),
+            compression: Compression::default(),
+            retention_days: 0,
+        }
+    }
+}
+
+/// Optional TOML file pointed to by `CONFIG_FILE`. Environment variables take
+/// precedence over anything set here.
+///
+/// ```toml
+/// redis_url = "redis://localhost:6379"
+///
+/// [ethereum]
+/// enabled = true
+/// rpc_url = "wss://sepolia.infura.io/ws/v3/<id>"
+/// network = "sepolia"
+/// watched_addresses = ["0x..."]
+/// token_denylist = ["0x..."]
+///
+/// [solana]
+/// enabled = false
+///
+/// [[destinations]]
+/// name = "alerts"
+/// channel = "eth_erc20_alerts"
+/// chains = ["ethereum"]
+/// event_types = ["erc20_transfer"]
+/// overflow = "drop"
+///
+/// [[assets]]
+/// name = "USDC"
+/// tokens = ["mainnet:0xA0b8...", "solana:EPjF..."]
+///
+/// [[portfolios]]
+/// name = "treasury"
+/// addresses = ["ethereum:0x...", "solana:..."]
+///
+/// [prices]
+/// USDC = 1.0
+///
+/// [[address_book]]
+/// address = "ethereum:0x..."
+/// label = "Treasury cold wallet"
+/// cold_storage = true
+///
+/// [sweep_detection]
+/// min_sources = 5
+///
+/// [validator_monitoring]
+/// validators = ["<vote account or identity>"]
+///
+/// [liquidation_risk]
+/// threshold = 1.1
+///
+/// [stuck_transactions]
+/// pending_secs = 600
+///
+/// [fee_spike]
+/// multiple = 3.0
+///
+/// [[evm_networks]]
+/// chain = "polygon"
+/// rpc_url = "wss://polygon-mainnet.g.alchemy.com/v2/<key>"
+/// watched_addresses = ["0x..."]
+///
+/// [tron]
+/// watched_addresses = ["T..."]
+///
+/// [[substrate_networks]]
+/// chain = "polkadot"
+/// rpc_url = "wss://rpc.polkadot.io"
+/// watched_addresses = ["1..."]
+/// ```
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct FileConfig {
+    redis_url: Option<String>,
+    ethereum: ChainSection,
+    solana: ChainSection,
+    destinations: Vec<DestinationConfig>,
+    assets: Vec<AssetMapping>,
+    portfolios: Vec<PortfolioConfig>,
+    prices: HashMap<String, f64>,
+    address_book: Vec<AddressEntry>,
+    sweep_detection: Option<SweepConfig>,
+    validator_monitoring: Option<ValidatorConfig>,
+    liquidation_risk: Option<LiquidationRiskConfig>,
+    stuck_transactions: Option<StuckConfig>,
+    fee_spike: Option<FeeSpikeConfig>,
+    evm_networks: Vec<EvmNetworkConfig>,
+    tron: Option<TronConfig>,
+    substrate_networks: Vec<SubstrateNetworkConfig>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct ChainSection {
+    enabled: Option<bool>,
+    rpc_url: Option<String>,
+    network: Option<String>,
+    watched_addresses: Option<Vec<String>>,
+    token_allowlist: Option<Vec<String>>,
+    token_denylist: Option<Vec<String>>,
+}
+
+impl FileConfig {
+    fn load(path: &str) -> Result<Self> {
+        let raw = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read config file {}", path))?;
+        toml::from_str(&raw).with_context(|| format!("failed to parse config file {}", path))
+    }
+}
+
+/// Prefer existing environment variables set by the process. Only load a
+/// .env file if the variable is missing. This avoids dotenv overriding values
+/// tests set via std::env::set_var.
+fn get_var(name: &str) -> Option<String> {
+    match std::env::var(name) {
+        Ok(v) => Some(v),
+        Err(_) => {
             // try loading from .env once
             dotenv().ok();
-            std::env::var(name).context(format!("{} must be set", name))
+            std::env::var(name).ok()
         }
+    }
+}
+
+/// Optional numeric settings: if present use it (and parse), otherwise default.
+fn get_u64(name: &str, default: u64) -> Result<u64> {
+    match get_var(name) {
+        Some(s) => s
+            .trim()
+            .parse::<u64>()
+            .with_context(|| format!("{} must be a number, got {:?}", name, s)),
+        None => Ok(default),
+    }
+}
 
-        let eth_rpc_url = get_required("ETH_RPC_URL")?;
-        let sol_rpc_url = get_required("SOL_RPC_URL")?;
-        let redis_url = get_required("REDIS_URL")?;
+/// Whether `url` is an Alchemy websocket endpoint.
+/// A size in MB as bytes; `None` for 0.
+fn megabytes(mb: u64) -> Option<usize> {
+    (mb > 0).then_some(mb as usize * 1024 * 1024)
+}
 
-        // For optional comma-separated lists, prefer existing env then try .env
-        let watched_addresses_eth = match std::env::var("WATCHED_ADDRESSES_ETH") {
-            Ok(s) => {
-                if s.is_empty() {
-                    Vec::new()
-                } else {
-                    s.split(',').map(|s| s.trim().to_string()).collect()
-                }
-            }
-            Err(_) => {
-                dotenv().ok();
-                std::env::var("WATCHED_ADDRESSES_ETH")
-                    .map(|s| {
-                        if s.is_empty() {
-                            Vec::new()
-                        } else {
-                            s.split(',').map(|s| s.trim().to_string()).collect()
-                        }
-                    })
-                    .unwrap_or_default()
+pub(crate) fn is_alchemy_ws(url: &str) -> bool {
+    let Some(rest) = url
+        .strip_prefix("wss://")
+        .or_else(|| url.strip_prefix("ws://"))
+    else {
+        return false;
+    };
+    let host = rest.split(['/', ':']).next().unwrap_or_default();
+    host == "alchemy.com" || host.ends_with(".alchemy.com")
+}
+
+fn parse_bool(name: &str, raw: &str) -> Result<bool> {
+    match raw.trim().to_ascii_lowercase().as_str() {
+        "1" | "true" | "yes" | "on" => Ok(true),
+        "0" | "false" | "no" | "off" => Ok(false),
+        _ => Err(anyhow!("{} must be a boolean, got '{}'", name, raw)),
+    }
+}
+
+/// Comma-separated list; an empty string yields an empty list.
+fn parse_list(s: &str) -> Vec<String> {
+    if s.is_empty() {
+        Vec::new()
+    } else {
+        s.split(',').map(|s| s.trim().to_string()).collect()
+    }
+}
+
+/// A problem found loading the configuration.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigProblem {
+    /// A required variable is unset, with the config file key that can set
+    /// it instead.
+    Missing {
+        name: String,
+        file_key: Option<String>,
+    },
+    /// A value is malformed or conflicts with another.
+    Invalid(String),
+}
+
+/// Every problem found loading the configuration, so that a failed start
+/// reports all of them rather than the first.
+#[derive(Debug, Default)]
+pub struct ConfigErrors(Vec<ConfigProblem>);
+
+impl ConfigErrors {
+    fn missing(&mut self, name: &str, file_key: Option<&str>) {
+        self.0.push(ConfigProblem::Missing {
+            name: name.to_string(),
+            file_key: file_key.map(str::to_string),
+        });
+    }
+
+    fn push(&mut self, e: anyhow::Error) {
+        self.0.push(ConfigProblem::Invalid(format!("{:#}", e)));
+    }
+
+    /// `res`'s value, or the type's default after recording the error. The
+    /// default only feeds later checks: any error fails the load.
+    fn take<T: Default>(&mut self, res: Result<T>) -> T {
+        res.unwrap_or_else(|e| {
+            self.push(e);
+            T::default()
+        })
+    }
+
+    pub fn problems(&self) -> &[ConfigProblem] {
+        &self.0
+    }
+
+    fn into_result(self) -> Result<()> {
+        if self.0.is_empty() {
+            Ok(())
+        } else {
+            Err(self.into())
+        }
+    }
+}
+
+impl fmt::Display for ConfigErrors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let count = self.0.len();
+        write!(
+            f,
+            "invalid configuration ({} problem{}):",
+            count,
+            if count == 1 { "" } else { "s" }
+        )?;
+        for problem in &self.0 {
+            match problem {
+                ConfigProblem::Missing {
+                    name,
+                    file_key: Some(key),
+                } => write!(f, "\n  missing  {} (or {} in CONFIG_FILE)", name, key)?,
+                ConfigProblem::Missing { name, .. } => write!(f, "\n  missing  {}", name)?,
+                ConfigProblem::Invalid(message) => write!(f, "\n  invalid  {}", message)?,
             }
-        };
+        }
+        Ok(())
+    }
+}
 
-        let watched_addresses_sol = match std::env::var("WATCHED_ADDRESSES_SOL") {
-            Ok(s) => {
-                if s.is_empty() {
-                    Vec::new()
-                } else {
-                    s.split(',').map(|s| s.trim().to_string()).collect()
-                }
-            }
-            Err(_) => {
-                dotenv().ok();
-                std::env::var("WATCHED_ADDRESSES_SOL")
-                    .map(|s| {
-                        if s.is_empty() {
-                            Vec::new()
-                        } else {
-                            s.split(',').map(|s| s.trim().to_string()).collect()
-                        }
-                    })
-                    .unwrap_or_default()
+impl std::error::Error for ConfigErrors {}
+
+/// Settings for one chain after merging env and file values.
+struct ChainSettings {
+    enabled: bool,
+    rpc_url: String,
+    network: String,
+    watched_addresses: Vec<String>,
+    tokens: TokenFilter,
+}
+
+/// Resolve a chain's settings from `<PREFIX>_*` env vars, falling back to the
+/// matching config file section. RPC URL and network are only required when
+/// the chain is enabled.
+fn chain_settings(
+    prefix: &str,
+    file_section: &str,
+    section: ChainSection,
+    errors: &mut ConfigErrors,
+) -> ChainSettings {
+    let enabled_var = format!("{}_ENABLED", prefix);
+    let enabled = match get_var(&enabled_var) {
+        Some(raw) => errors.take(parse_bool(&enabled_var, &raw)),
+        None => section.enabled.unwrap_or(true),
+    };
+
+    let mut required =
+        |name: String, key: &str, from_file: Option<String>| match get_var(&name).or(from_file) {
+            Some(v) => v,
+            None => {
+                if enabled {
+                    errors.missing(&name, Some(&format!("[{}] {}", file_section, key)));
+                }
+                String::new()
             }
         };
+    let rpc_url = required(format!("{}_RPC_URL", prefix), "rpc_url", section.rpc_url);
+    let network = required(format!("{}_NETWORK", prefix), "network", section.network);
+
+    let list = |name: String, from_file: Option<Vec<String>>| match get_var(&name) {
+        Some(s) => parse_list(&s),
+        None => from_file.unwrap_or_default(),
+    };
+    let watched_addresses = list(
+        format!("WATCHED_ADDRESSES_{}", prefix),
+        section.watched_addresses,
+    );
+    let tokens = TokenFilter::new(
+        &list(
+            format!("{}_TOKEN_ALLOWLIST", prefix),
+            section.token_allowlist,
+        ),
+        &list(format!("{}_TOKEN_DENYLIST", prefix), section.token_denylist),
+    );
+
+    ChainSettings {
+        enabled,
+        rpc_url,
+        network,
+        watched_addresses,
+        tokens,
+    }
+}
+
+impl Config {
+    /// Load configuration from environment variables, optionally reading a
+    /// `.env` file and the TOML file named by `CONFIG_FILE` for missing
+    /// values. Required keys will produce an error if not present; per-chain
+    /// keys are only required for enabled chains. Optional lists accept
+    /// comma‑separated values.
+    ///
+    /// Every missing or malformed value is reported at once, as a
+    /// [`ConfigErrors`]; only a config file that can't be read or parsed
+    /// fails on its own.
+    pub fn from_env() -> Result<Self> {
+        let file = match get_var("CONFIG_FILE") {
+            Some(path) => FileConfig::load(&path)?,
+            None => FileConfig::default(),
+        };
+        let mut errors = ConfigErrors::default();
+
+        let eth = chain_settings("ETH", "ethereum", file.ethereum, &mut errors);
+        let sol = chain_settings("SOL", "solana", file.solana, &mut errors);
+        if !eth.enabled
+            && !sol.enabled
+            && file.evm_networks.is_empty()
+            && file.tron.is_none()
+            && file.substrate_networks.is_empty()
+        {
+            errors.push(anyhow!(
+                "at least one chain must be enabled (ETH_ENABLED / SOL_ENABLED)"
+            ));
+        }
 
-        let eth_network = get_required("ETH_NETWORK")?;
-        let sol_network = get_required("SOL_NETWORK")?;
+        let redis_url = get_var("REDIS_URL").or(file.redis_url).unwrap_or_else(|| {
+            errors.missing("REDIS_URL", Some("redis_url"));
+            String::new()
+        });
 
-        // POLL_INTERVAL_SECS: if present use it (and parse), otherwise try .env
-        let poll_interval_secs = match std::env::var("POLL_INTERVAL_SECS") {
-            Ok(s) => s
-                .parse::<u64>()
-                .context("POLL_INTERVAL_SECS must be a number")?,
-            Err(_) => {
-                dotenv().ok();
-                match std::env::var("POLL_INTERVAL_SECS") {
-                    Ok(s2) => s2
-                        .parse::<u64>()
-                        .context("POLL_INTERVAL_SECS must be a number")?,
-                    Err(_) => 10u64,
+        let poll_interval_secs = errors.take(get_u64("POLL_INTERVAL_SECS", 10));
+        let dedup_retention_secs = errors.take(get_u64("DEDUP_RETENTION_SECS", 86_400));
+        let dedup_key: DedupKey = match get_var("DEDUP_KEY") {
+            Some(raw) => errors.take(raw.parse()),
+            None => DedupKey::default(),
+        };
+        let distributed_dedup = match get_var("DISTRIBUTED_DEDUP") {
+            Some(raw) => errors.take(parse_bool("DISTRIBUTED_DEDUP", &raw)),
+            None => false,
+        };
+        let dedup_store = match (get_var("DEDUP_STORE"), distributed_dedup) {
+            (None, false) => DedupStoreKind::Memory,
+            (None, true) => DedupStoreKind::Redis,
+            (Some(raw), distributed_dedup) => {
+                let kind: DedupStoreKind = errors.take(raw.parse());
+                if distributed_dedup && kind != DedupStoreKind::Redis {
+                    errors.push(anyhow!(
+                        "DISTRIBUTED_DEDUP=true needs DEDUP_STORE=redis, got {}",
+                        raw
+                    ));
                 }
+                kind
             }
         };
+        let dedup_store_file = get_var("DEDUP_STORE_FILE")
+            .map(PathBuf::from)
+            .unwrap_or_else(|| PathBuf::from("tracker-dedup.log"));
+        let token_warmup = match get_var("TOKEN_WARMUP") {
+            Some(raw) => errors.take(parse_bool("TOKEN_WARMUP", &raw)),
+            None => false,
+        };
+        let distributed_dedup_prefix =
+            get_var("DISTRIBUTED_DEDUP_PREFIX").unwrap_or_else(|| "tracker:dedup:".to_string());
+        let region = get_var("REGION");
+        let instance_id = get_var("INSTANCE_ID")
+            .or_else(|| get_var("HOSTNAME"))
+            .unwrap_or_else(|| "tracker".to_string());
+        let eth_logs_chunk_size = errors.take(get_u64("ETH_LOGS_CHUNK_SIZE", 2_000));
+        let eth_archive_rpc_url = get_var("ETH_ARCHIVE_RPC_URL");
+        let eth_rpc_backup_urls = parse_list(&get_var("ETH_RPC_BACKUP_URLS").unwrap_or_default());
+        let eth_rpc_max_head_lag = errors.take(get_u64("ETH_RPC_MAX_HEAD_LAG", 5));
+        let eth_rpc_batch_size = errors.take(get_u64("ETH_RPC_BATCH_SIZE", 0));
+        let chains = match get_var("CHAIN_REGISTRY_FILE") {
+            Some(path) => errors.take(ChainRegistry::load(&path)),
+            None => ChainRegistry::default(),
+        };
+        let eth_chain = match get_var("ETH_CHAIN_ID") {
+            Some(raw) => errors.take(
+                raw.trim()
+                    .parse::<u64>()
+                    .with_context(|| format!("ETH_CHAIN_ID must be a number, got {:?}", raw))
+                    .and_then(|chain_id| {
+                        chains.get(chain_id).cloned().with_context(|| {
+                            format!("ETH_CHAIN_ID {} is not in the chain registry", chain_id)
+                        })
+                    })
+                    .map(Some),
+            ),
+            None => chains.by_network(&eth.network).cloned(),
+        };
+        let sol_explorer_url =
+            get_var("SOL_EXPLORER_URL").unwrap_or_else(|| "https://solscan.io".to_string());
+        let eth_alchemy_mined_transactions = match get_var("ETH_ALCHEMY_MINED_TRANSACTIONS")
+            .filter(|raw| !raw.trim().eq_ignore_ascii_case("auto"))
+        {
+            Some(raw) => errors.take(parse_bool("ETH_ALCHEMY_MINED_TRANSACTIONS", &raw)),
+            None => is_alchemy_ws(&eth.rpc_url),
+        };
+        let eth_confirmations = errors.take(get_u64("ETH_CONFIRMATIONS", 0));
+        let eth_pending_transfers = match get_var("ETH_PENDING_TRANSFERS") {
+            Some(raw) => errors.take(parse_bool("ETH_PENDING_TRANSFERS", &raw)),
+            None => false,
+        };
+        let eth_pending_simulation = match get_var("ETH_PENDING_SIMULATION") {
+            Some(raw) => errors.take(parse_bool("ETH_PENDING_SIMULATION", &raw)),
+            None => false,
+        };
+        if eth_pending_transfers && eth.rpc_url.starts_with("http") {
+            errors.push(anyhow!(
+                "ETH_PENDING_TRANSFERS needs a websocket or IPC ETH_RPC_URL: HTTP polling \
+                 doesn't see the mempool"
+            ));
+        }
+        if eth_pending_simulation && !eth_pending_transfers {
+            errors.push(anyhow!(
+                "ETH_PENDING_SIMULATION requires ETH_PENDING_TRANSFERS=true"
+            ));
+        }
+        let sol_commitment: SolCommitment = match get_var("SOL_COMMITMENT") {
+            Some(raw) => errors.take(raw.parse()),
+            None => SolCommitment::default(),
+        };
+        let bootstrap_etherscan_api_key = get_var("BOOTSTRAP_ETHERSCAN_API_KEY");
+        let bootstrap_etherscan_url = get_var("BOOTSTRAP_ETHERSCAN_URL")
+            .unwrap_or_else(|| "https://api.etherscan.io/api".to_string());
+        let bootstrap_helius_api_key = get_var("BOOTSTRAP_HELIUS_API_KEY");
+        let sol_source: SolSource = match get_var("SOL_SOURCE") {
+            Some(raw) => errors.take(raw.parse()),
+            None => SolSource::default(),
+        };
+        let helius_api_key = get_var("HELIUS_API_KEY");
+        if sol_source == SolSource::Helius && helius_api_key.is_none() {
+            errors.push(anyhow!("SOL_SOURCE=helius requires HELIUS_API_KEY"));
+        }
+        let geyser_endpoint = get_var("GEYSER_ENDPOINT");
+        let geyser_x_token = get_var("GEYSER_X_TOKEN");
+        if sol_source == SolSource::Geyser && geyser_endpoint.is_none() {
+            errors.push(anyhow!("SOL_SOURCE=geyser requires GEYSER_ENDPOINT"));
+        }
+        let helius_url =
+            get_var("HELIUS_URL").unwrap_or_else(|| "https://api.helius.xyz".to_string());
+        let bootstrap_limit = errors.take(get_u64("BOOTSTRAP_LIMIT", 100));
+        let admin_bind_addr = get_var("ADMIN_BIND_ADDR");
+        let grpc_bind_addr = get_var("GRPC_BIND_ADDR");
+        let health = HealthConfig {
+            bind_addr: get_var("HEALTH_BIND_ADDR"),
+            max_lag_eth: errors.take(get_u64("HEALTH_MAX_LAG_ETH", DEFAULT_MAX_LAG_ETH)),
+            max_lag_sol: errors.take(get_u64("HEALTH_MAX_LAG_SOL", 0)),
+        };
+        let recent_events = errors.take(get_u64("RECENT_EVENTS", DEFAULT_RECENT_EVENTS)) as usize;
+        let graph_export_dir = get_var("GRAPH_EXPORT_DIR").map(PathBuf::from);
+        let admin_token = get_var("ADMIN_TOKEN");
+        let chain_runtime_threads = match errors.take(get_u64("CHAIN_RUNTIME_THREADS", 0)) {
+            0 => None,
+            threads => Some(threads as usize),
+        };
+        let shutdown_timeout_secs = errors.take(get_u64(
+            "SHUTDOWN_TIMEOUT_SECS",
+            shutdown::DEFAULT_TIMEOUT_SECS,
+        ));
+        let quarantine = QuarantineConfig {
+            retry_budget: errors
+                .take(get_u64("EVENT_RETRY_BUDGET", DEFAULT_RETRY_BUDGET))
+                .max(1),
+            max_events: errors.take(get_u64("QUARANTINE_MAX", DEFAULT_MAX_EVENTS)) as usize,
+            file: get_var("QUARANTINE_FILE"),
+        };
+        let webhooks_file = get_var("WEBHOOKS_FILE");
+        let kill_switch_defaults = KillSwitchConfig::default();
+        let kill_switch = KillSwitchConfig {
+            key: get_var("KILL_SWITCH_KEY").unwrap_or(kill_switch_defaults.key),
+            mode: match get_var("KILL_SWITCH_MODE") {
+                Some(raw) => errors.take(raw.parse()),
+                None => KillSwitchMode::default(),
+            },
+            max_buffered: errors.take(get_u64(
+                "KILL_SWITCH_BUFFER",
+                kill_switch_defaults.max_buffered as u64,
+            )) as usize,
+        };
+        let checkpoint_defaults = CheckpointConfig::default();
+        let checkpoints = CheckpointConfig {
+            store: match get_var("CHECKPOINT_STORE") {
+                Some(raw) => errors.take(raw.parse()),
+                None => CheckpointStore::default(),
+            },
+            key: get_var("CHECKPOINT_KEY").unwrap_or(checkpoint_defaults.key),
+            file: get_var("CHECKPOINT_FILE")
+                .map(PathBuf::from)
+                .unwrap_or(checkpoint_defaults.file),
+            max_catchup_blocks: errors.take(get_u64(
+                "MAX_CATCHUP_BLOCKS",
+                checkpoint_defaults.max_catchup_blocks,
+            )),
+            retention_days: errors.take(get_u64("CHECKPOINT_RETENTION_DAYS", 0)),
+        };
+        let memory = MemoryBudget {
+            dedup_bytes: megabytes(errors.take(get_u64("DEDUP_MEMORY_MB", 0))),
+            webhook_log_bytes: megabytes(errors.take(get_u64("WEBHOOK_LOG_MEMORY_MB", 0))),
+        };
+        let spam_mode: SpamMode = match get_var("SPAM_FILTER") {
+            Some(raw) => errors.take(raw.parse()),
+            None => SpamMode::default(),
+        };
+        let spam = SpamFilter::new(
+            spam_mode,
+            &parse_list(&get_var("SPAM_SYMBOL_PATTERNS").unwrap_or_default()),
+        );
 
         let log_level = std::env::var("LOG_LEVEL").ok();
 
+        dotenv().ok();
+        let schedules: HashMap<String, String> = std::env::vars()
+            .filter_map(|(k, v)| {
+                k.strip_prefix("SCHEDULE_")
+                    .map(|job| (job.to_ascii_lowercase(), v))
+            })
+            .collect();
+        for e in scheduler::check_overrides(&schedules) {
+            errors.push(e);
+        }
+
+        let output = get_var("REDIS_OUTPUT");
+        let nats_url = get_var("NATS_URL");
+        let nats_subject = get_var("NATS_SUBJECT");
+        let nats_jetstream = match get_var("NATS_JETSTREAM") {
+            Some(raw) => Some(errors.take(parse_bool("NATS_JETSTREAM", &raw))),
+            None => None,
+        };
+        let postgres_url = get_var("POSTGRES_URL");
+        let webhook_urls = parse_list(&get_var("WEBHOOK_URLS").unwrap_or_default());
+        let webhook_secret = get_var("WEBHOOK_SECRET");
+        let webhook_dead_letter = get_var("WEBHOOK_DEAD_LETTER").map(PathBuf::from);
+        let env_destinations = file.destinations.is_empty();
+        let mut destinations = if env_destinations {
+            let stream = DestinationConfig {
+                name: "stream".to_string(),
+                sink: SinkKind::Stream,
+                stream: get_var("REDIS_STREAM").unwrap_or_else(|| "cross_chain_events".into()),
+                max_len: errors.take(get_u64("REDIS_STREAM_MAXLEN", default_max_len())),
+                ..Default::default()
+            };
+            match output.as_deref().map(str::to_ascii_lowercase).as_deref() {
+                None | Some("pubsub") => vec![DestinationConfig::default()],
+                Some("stream") => vec![stream],
+                Some("both") => vec![DestinationConfig::default(), stream],
+                Some(other) => {
+                    errors.push(anyhow!(
+                        "invalid REDIS_OUTPUT '{}' (expected pubsub, stream or both)",
+                        other
+                    ));
+                    Vec::new()
+                }
+            }
+        } else {
+            if output.is_some() {
+                errors.push(anyhow!(
+                    "REDIS_OUTPUT only applies without [[destinations]]; add a stream \
+                     destination to the config file instead"
+                ));
+            }
+            if !webhook_urls.is_empty() || webhook_dead_letter.is_some() {
+                errors.push(anyhow!(
+                    "WEBHOOK_URLS and WEBHOOK_DEAD_LETTER only apply without [[destinations]]; \
+                     add a webhook destination to the config file instead"
+                ));
+            }
+            if nats_subject.is_some() || nats_jetstream.is_some() {
+                errors.push(anyhow!(
+                    "NATS_SUBJECT and NATS_JETSTREAM only apply without [[destinations]]; add a \
+                     nats destination to the config file instead"
+                ));
+            }
+            file.destinations
+        };
+        if env_destinations && nats_url.is_some() {
+            destinations.push(DestinationConfig {
+                name: "nats".to_string(),
+                sink: SinkKind::Nats,
+                subject: nats_subject.unwrap_or_else(|| "events.{chain}.{event_type}".into()),
+                jetstream: nats_jetstream.unwrap_or(false),
+                ..Default::default()
+            });
+        }
+        if env_destinations && postgres_url.is_some() {
+            destinations.push(DestinationConfig {
+                name: "postgres".to_string(),
+                sink: SinkKind::Postgres,
+                ..Default::default()
+            });
+        }
+        if env_destinations && !webhook_urls.is_empty() {
+            destinations.push(DestinationConfig {
+                name: "webhook".to_string(),
+                sink: SinkKind::Webhook,
+                urls: webhook_urls,
+                dead_letter: webhook_dead_letter,
+                ..Default::default()
+            });
+        }
+        for dest in &mut destinations {
+            if dest.sink == SinkKind::Webhook && dest.secret.is_none() {
+                dest.secret = webhook_secret.clone();
+            }
+            if dest.sink == SinkKind::Nats && dest.nats_url.is_none() {
+                dest.nats_url = nats_url.clone();
+            }
+            if dest.sink == SinkKind::Postgres && dest.postgres_url.is_none() {
+                dest.postgres_url = postgres_url.clone();
+            }
+        }
+        for dest in &destinations {
+            if let Some(field) = dest
+                .fields
+                .iter()
+                .find(|f| !PAYLOAD_FIELDS.contains(&f.as_str()))
+            {
+                errors.push(anyhow!(
+                    "destination {}: unknown payload field '{}' (expected one of {})",
+                    dest.name,
+                    field,
+                    PAYLOAD_FIELDS.join(", ")
+                ));
+            }
+            if dest.sink == SinkKind::Redis && dest.channel.is_empty() {
+                errors.push(anyhow!(
+                    "destination {}: redis sink needs a channel",
+                    dest.name
+                ));
+            }
+            if dest.sink == SinkKind::Stream && dest.stream.is_empty() {
+                errors.push(anyhow!(
+                    "destination {}: stream sink needs a stream",
+                    dest.name
+                ));
+            }
+            if dest.sink == SinkKind::Kafka && (dest.brokers.is_empty() || dest.topic.is_empty()) {
+                errors.push(anyhow!(
+                    "destination {}: kafka sink needs brokers and a topic",
+                    dest.name
+                ));
+            }
+            if dest.sink == SinkKind::Redis && sink::is_template(&dest.channel) {
+                if let Err(e) = sink::check_template(&dest.channel) {
+                    errors.push(anyhow!("destination {}: {}", dest.name, e));
+                }
+                if dest.batch_max_events > 0 {
+                    errors.push(anyhow!(
+                        "destination {}: a channel template routes each event on its own \
+                         and can't batch",
+                        dest.name
+                    ));
+                }
+            }
+            if dest.sink == SinkKind::Kafka && !dest.topic.is_empty() {
+                if let Err(e) = sink::check_topic(&dest.topic) {
+                    errors.push(anyhow!("destination {}: {}", dest.name, e));
+                }
+            }
+            if dest.sink == SinkKind::Kafka && dest.batch_max_events > 0 {
+                errors.push(anyhow!(
+                    "destination {}: kafka sink batches by itself; use linger.ms in its kafka \
+                     settings instead of batch_max_events",
+                    dest.name
+                ));
+            }
+            if dest.sink == SinkKind::Nats {
+                if dest.nats_url.is_none() {
+                    errors.push(anyhow!(
+                        "destination {}: nats sink needs a nats_url or NATS_URL",
+                        dest.name
+                    ));
+                }
+                if let Err(e) = sink::check_subject(&dest.subject) {
+                    errors.push(anyhow!("destination {}: {}", dest.name, e));
+                }
+                if dest.batch_max_events > 0 {
+                    errors.push(anyhow!(
+                        "destination {}: nats sink publishes each event to its own subject \
+                         and can't batch",
+                        dest.name
+                    ));
+                }
+            }
+            if dest.sink == SinkKind::Postgres {
+                if dest.postgres_url.is_none() {
+                    errors.push(anyhow!(
+                        "destination {}: postgres sink needs a postgres_url or POSTGRES_URL",
+                        dest.name
+                    ));
+                }
+                if let Err(e) = sink::check_table(&dest.table) {
+                    errors.push(anyhow!("destination {}: {}", dest.name, e));
+                }
+                if !dest.fields.is_empty() {
+                    errors.push(anyhow!(
+                        "destination {}: postgres sink stores the full event and can't set fields",
+                        dest.name
+                    ));
+                }
+                if dest.compression != Compression::None {
+                    errors.push(anyhow!(
+                        "destination {}: postgres sink can't be compressed",
+                        dest.name
+                    ));
+                }
+            }
+            if dest.sink == SinkKind::Webhook {
+                if dest.urls.is_empty() {
+                    errors.push(anyhow!(
+                        "destination {}: webhook sink needs urls",
+                        dest.name
+                    ));
+                }
+                for url in &dest.urls {
+                    match reqwest::Url::parse(url) {
+                        Ok(parsed) if matches!(parsed.scheme(), "http" | "https") => {}
+                        _ => errors.push(anyhow!(
+                            "destination {}: invalid webhook url '{}' (expected http or https)",
+                            dest.name,
+                            url
+                        )),
+                    }
+                }
+                if dest.secret.as_deref().unwrap_or_default().is_empty() {
+                    errors.push(anyhow!(
+                        "destination {}: webhook sink needs a secret or WEBHOOK_SECRET",
+                        dest.name
+                    ));
+                }
+                if dest.compression != Compression::None {
+                    errors.push(anyhow!(
+                        "destination {}: webhook sink can't be compressed",
+                        dest.name
+                    ));
+                }
+            }
+            if dest.dead_letter.is_some() && dest.sink != SinkKind::Webhook {
+                errors.push(anyhow!(
+                    "destination {}: only a webhook sink has a dead_letter file",
+                    dest.name
+                ));
+            }
+            if dest.archive.is_some() && dest.sink != SinkKind::Stream {
+                errors.push(anyhow!(
+                    "destination {}: only a stream sink has an archive",
+                    dest.name
+                ));
+            }
+            if dest.archive.is_some() && dest.compression != Compression::None {
+                errors.push(anyhow!(
+                    "destination {}: an archived stream can't be compressed",
+                    dest.name
+                ));
+            }
+            if dest.retention_days > 0
+                && !matches!(
+                    dest.sink,
+                    SinkKind::File | SinkKind::Stream | SinkKind::Postgres
+                )
+            {
+                errors.push(anyhow!(
+                    "destination {}: only file, stream and postgres sinks store events to expire",
+                    dest.name
+                ));
+            }
+            if dest.sink == SinkKind::File && dest.compression != Compression::None {
+                errors.push(anyhow!(
+                    "destination {}: file sink can't be compressed",
+                    dest.name
+                ));
+            }
+            if dest.compression != Compression::None && dest.batch_max_events == 0 {
+                errors.push(anyhow!(
+                    "destination {}: compression requires batch_max_events",
+                    dest.name
+                ));
+            }
+        }
+        let asset_map =
+            errors.take(AssetMap::new(&file.assets).context("invalid [[assets]] in config file"));
+        let portfolios = errors.take(
+            Portfolios::new(&file.portfolios, file.prices)
+                .context("invalid [[portfolios]] in config file"),
+        );
+        let address_book = errors.take(
+            AddressBook::new(&file.address_book).context("invalid [[address_book]] in config file"),
+        );
+        if let Some(sweeps) = &file.sweep_detection {
+            errors.take(
+                sweeps
+                    .validate()
+                    .context("invalid [sweep_detection] in config file"),
+            );
+        }
+        if let Some(validators) = &file.validator_monitoring {
+            errors.take(
+                validators
+                    .validate()
+                    .context("invalid [validator_monitoring] in config file"),
+            );
+        }
+        if let Some(risk) = &file.liquidation_risk {
+            errors.take(
+                risk.validate()
+                    .context("invalid [liquidation_risk] in config file"),
+            );
+        }
+        if let Some(stuck) = &file.stuck_transactions {
+            errors.take(
+                stuck
+                    .validate()
+                    .context("invalid [stuck_transactions] in config file"),
+            );
+        }
+        if let Some(spike) = &file.fee_spike {
+            errors.take(
+                spike
+                    .validate()
+                    .context("invalid [fee_spike] in config file"),
+            );
+        }
+        let evm_networks = errors.take(
+            EvmNetwork::resolve(&file.evm_networks, &chains)
+                .context("invalid [[evm_networks]] in config file"),
+        );
+        if let Some(tron) = &file.tron {
+            errors.take(tron.validate().context("invalid [tron] in config file"));
+        }
+        let substrate_networks = errors.take(
+            SubstrateNetwork::resolve(&file.substrate_networks, &evm_networks)
+                .context("invalid [[substrate_networks]] in config file"),
+        );
+        errors.into_result()?;
+
         Ok(Config {
-            eth_rpc_url,
-            sol_rpc_url,
+            eth_enabled: eth.enabled,
+            sol_enabled: sol.enabled,
+            eth_rpc_url: eth.rpc_url,
+            sol_rpc_url: sol.rpc_url,
+            sol_source,
+            helius_api_key,
+            geyser_endpoint,
+            geyser_x_token,
             redis_url,
-            watched_addresses_eth,
-            watched_addresses_sol,
-            eth_network,
-            sol_network,
+            watched_addresses_eth: eth.watched_addresses,
+            watched_addresses_sol: sol.watched_addresses,
+            eth_network: eth.network,
+            eth_chain,
+            sol_explorer_url,
+            sol_network: sol.network,
+            eth_tokens: eth.tokens,
+            token_warmup,
+            sol_tokens: sol.tokens,
+            spam,
             poll_interval_secs,
             log_level,
+            dedup_retention_secs,
+            dedup_key,
+            dedup_store,
+            distributed_dedup_prefix,
+            dedup_store_file,
+            region,
+            instance_id,
+            eth_logs_chunk_size,
+            eth_archive_rpc_url,
+            eth_rpc_backup_urls,
+            eth_rpc_max_head_lag,
+            eth_rpc_batch_size,
+            eth_alchemy_mined_transactions,
+            eth_confirmations,
+            eth_pending_transfers,
+            eth_pending_simulation,
+            sol_commitment,
+            bootstrap_etherscan_api_key,
+            bootstrap_etherscan_url,
+            bootstrap_helius_api_key,
+            helius_url,
+            bootstrap_limit,
+            admin_bind_addr,
+            grpc_bind_addr,
+            health,
+            recent_events,
+            graph_export_dir,
+            chain_runtime_threads,
+            shutdown_timeout_secs,
+            quarantine,
+            memory,
+            admin_token,
+            webhooks_file,
+            schedules,
+            destinations,
+            asset_map,
+            portfolios,
+            address_book,
+            sweep_detection: file.sweep_detection,
+            validator_monitoring: file.validator_monitoring,
+            liquidation_risk: file.liquidation_risk,
+            stuck_transactions: file.stuck_transactions,
+            fee_spike: file.fee_spike,
+            evm_networks,
+            tron: file.tron,
+            substrate_networks,
+            kill_switch,
+            checkpoints,
         })
     }
 }
@@ -128,12 +1396,85 @@ mod tests {
         std::env::remove_var("ETH_RPC_URL");
         std::env::remove_var("SOL_RPC_URL");
         std::env::remove_var("REDIS_URL");
+        std::env::remove_var("REDIS_OUTPUT");
+        std::env::remove_var("REDIS_STREAM");
+        std::env::remove_var("REDIS_STREAM_MAXLEN");
+        std::env::remove_var("NATS_URL");
+        std::env::remove_var("POSTGRES_URL");
+        std::env::remove_var("WEBHOOK_URLS");
+        std::env::remove_var("WEBHOOK_SECRET");
+        std::env::remove_var("WEBHOOK_DEAD_LETTER");
+        std::env::remove_var("NATS_SUBJECT");
+        std::env::remove_var("NATS_JETSTREAM");
         std::env::remove_var("WATCHED_ADDRESSES_ETH");
         std::env::remove_var("WATCHED_ADDRESSES_SOL");
         std::env::remove_var("ETH_NETWORK");
         std::env::remove_var("SOL_NETWORK");
         std::env::remove_var("POLL_INTERVAL_SECS");
         std::env::remove_var("LOG_LEVEL");
+        std::env::remove_var("DEDUP_RETENTION_SECS");
+        std::env::remove_var("DEDUP_KEY");
+        std::env::remove_var("DISTRIBUTED_DEDUP");
+        std::env::remove_var("DISTRIBUTED_DEDUP_PREFIX");
+        std::env::remove_var("DEDUP_STORE");
+        std::env::remove_var("DEDUP_STORE_FILE");
+        std::env::remove_var("REGION");
+        std::env::remove_var("INSTANCE_ID");
+        std::env::remove_var("ETH_LOGS_CHUNK_SIZE");
+        std::env::remove_var("ETH_ARCHIVE_RPC_URL");
+        std::env::remove_var("ETH_RPC_BACKUP_URLS");
+        std::env::remove_var("ETH_RPC_MAX_HEAD_LAG");
+        std::env::remove_var("ETH_RPC_BATCH_SIZE");
+        std::env::remove_var("ETH_ALCHEMY_MINED_TRANSACTIONS");
+        std::env::remove_var("ETH_CONFIRMATIONS");
+        std::env::remove_var("ETH_PENDING_TRANSFERS");
+        std::env::remove_var("ETH_PENDING_SIMULATION");
+        std::env::remove_var("SOL_COMMITMENT");
+        std::env::remove_var("ETH_CHAIN_ID");
+        std::env::remove_var("CHAIN_REGISTRY_FILE");
+        std::env::remove_var("SOL_EXPLORER_URL");
+        std::env::remove_var("BOOTSTRAP_ETHERSCAN_API_KEY");
+        std::env::remove_var("BOOTSTRAP_HELIUS_API_KEY");
+        std::env::remove_var("BOOTSTRAP_LIMIT");
+        std::env::remove_var("SOL_SOURCE");
+        std::env::remove_var("HELIUS_API_KEY");
+        std::env::remove_var("GEYSER_ENDPOINT");
+        std::env::remove_var("ADMIN_BIND_ADDR");
+        std::env::remove_var("GRPC_BIND_ADDR");
+        std::env::remove_var("HEALTH_BIND_ADDR");
+        std::env::remove_var("HEALTH_MAX_LAG_ETH");
+        std::env::remove_var("HEALTH_MAX_LAG_SOL");
+        std::env::remove_var("RECENT_EVENTS");
+        std::env::remove_var("GRAPH_EXPORT_DIR");
+        std::env::remove_var("SHUTDOWN_TIMEOUT_SECS");
+        std::env::remove_var("EVENT_RETRY_BUDGET");
+        std::env::remove_var("QUARANTINE_MAX");
+        std::env::remove_var("QUARANTINE_FILE");
+        std::env::remove_var("ADMIN_TOKEN");
+        std::env::remove_var("WEBHOOKS_FILE");
+        std::env::remove_var("CHECKPOINT_STORE");
+        std::env::remove_var("KILL_SWITCH_KEY");
+        std::env::remove_var("KILL_SWITCH_MODE");
+        std::env::remove_var("KILL_SWITCH_BUFFER");
+        std::env::remove_var("CHECKPOINT_KEY");
+        std::env::remove_var("CHECKPOINT_FILE");
+        std::env::remove_var("MAX_CATCHUP_BLOCKS");
+        std::env::remove_var("CHECKPOINT_RETENTION_DAYS");
+        std::env::remove_var("CHAIN_RUNTIME_THREADS");
+        std::env::remove_var("DEDUP_MEMORY_MB");
+        std::env::remove_var("WEBHOOK_LOG_MEMORY_MB");
+        std::env::remove_var("SCHEDULE_DEDUP_COMPACTION");
+        std::env::remove_var("SCHEDULE_DEDUP_COMPATION");
+        std::env::remove_var("ETH_ENABLED");
+        std::env::remove_var("SOL_ENABLED");
+        std::env::remove_var("CONFIG_FILE");
+        std::env::remove_var("ETH_TOKEN_ALLOWLIST");
+        std::env::remove_var("TOKEN_WARMUP");
+        std::env::remove_var("ETH_TOKEN_DENYLIST");
+        std::env::remove_var("SOL_TOKEN_ALLOWLIST");
+        std::env::remove_var("SOL_TOKEN_DENYLIST");
+        std::env::remove_var("SPAM_FILTER");
+        std::env::remove_var("SPAM_SYMBOL_PATTERNS");
     }
 
     #[test]
@@ -172,11 +1513,103 @@ mod tests {
         assert_eq!(cfg.watched_addresses_eth.len(), 2);
         assert_eq!(cfg.watched_addresses_sol.len(), 2);
         assert_eq!(cfg.poll_interval_secs, 42);
+        assert_eq!(cfg.dedup_retention_secs, 86_400);
+        assert_eq!(cfg.dedup_key, DedupKey::Log);
+        assert_eq!(cfg.dedup_store, DedupStoreKind::Memory);
+        assert_eq!(cfg.distributed_dedup_prefix, "tracker:dedup:");
+        assert_eq!(cfg.eth_logs_chunk_size, 2_000);
+        assert_eq!(cfg.eth_archive_rpc_url, None);
+        assert!(cfg.eth_rpc_backup_urls.is_empty());
+        assert_eq!(cfg.eth_rpc_max_head_lag, 5);
+        assert_eq!(cfg.eth_rpc_batch_size, 0);
+        assert!(!cfg.eth_alchemy_mined_transactions);
+        assert_eq!(cfg.eth_confirmations, 0);
+        assert!(!cfg.eth_pending_transfers);
+        assert!(!cfg.eth_pending_simulation);
+        assert_eq!(cfg.sol_commitment, SolCommitment::Confirmed);
+        assert_eq!(cfg.eth_chain.map(|chain| chain.chain_id), Some(1));
+        assert_eq!(cfg.sol_explorer_url, "https://solscan.io");
+        assert_eq!(cfg.bootstrap_etherscan_api_key, None);
+        assert_eq!(cfg.bootstrap_limit, 100);
+        assert_eq!(cfg.sol_source, SolSource::Rpc);
+        assert_eq!(cfg.admin_bind_addr, None);
+        assert_eq!(cfg.grpc_bind_addr, None);
+        assert_eq!(cfg.recent_events, 1000);
+        assert_eq!(cfg.graph_export_dir, None);
+        assert_eq!(cfg.webhooks_file, None);
+        assert_eq!(cfg.chain_runtime_threads, None);
+        assert_eq!(cfg.shutdown_timeout_secs, 25);
+        assert_eq!(cfg.quarantine, QuarantineConfig::default());
+        assert!(!cfg.token_warmup);
+        assert_eq!(cfg.memory, MemoryBudget::default());
+        assert_eq!(cfg.checkpoints, CheckpointConfig::default());
+        assert_eq!(cfg.kill_switch, KillSwitchConfig::default());
+        assert_eq!(cfg.health, HealthConfig::default());
 
         // Clean up after test
         cleanup_env();
     }
 
+    #[test]
+    #[serial]
+    fn test_confirmation_settings() {
+        cleanup_env();
+        std::env::set_var("ETH_RPC_URL", "wss://example.eth");
+        std::env::set_var("SOL_RPC_URL", "https://example.sol");
+        std::env::set_var("REDIS_URL", "redis://localhost");
+        std::env::set_var("ETH_CONFIRMATIONS", "12");
+        std::env::set_var("SOL_COMMITMENT", "finalized");
+        let cfg = Config::from_env();
+        std::env::set_var("SOL_COMMITMENT", "processed");
+        let processed = Config::from_env();
+        cleanup_env();
+
+        let cfg = cfg.expect("config should load");
+        assert_eq!(cfg.eth_confirmations, 12);
+        assert_eq!(cfg.sol_commitment, SolCommitment::Finalized);
+        let err = processed.expect_err("should be rejected").to_string();
+        assert!(err.contains("SOL_COMMITMENT processed"), "{}", err);
+    }
+
+    #[test]
+    #[serial]
+    fn test_pending_transfer_settings() {
+        cleanup_env();
+        std::env::set_var("ETH_RPC_URL", "wss://example.eth");
+        std::env::set_var("SOL_RPC_URL", "https://example.sol");
+        std::env::set_var("REDIS_URL", "redis://localhost");
+        std::env::set_var("ETH_PENDING_SIMULATION", "true");
+        let without_pending = Config::from_env();
+        std::env::set_var("ETH_PENDING_TRANSFERS", "true");
+        let cfg = Config::from_env();
+        cleanup_env();
+
+        let err = without_pending
+            .expect_err("simulation alone should be rejected")
+            .to_string();
+        assert!(err.contains("requires ETH_PENDING_TRANSFERS"), "{}", err);
+        let cfg = cfg.expect("config should load");
+        assert!(cfg.eth_pending_transfers);
+        assert!(cfg.eth_pending_simulation);
+    }
+
+    #[test]
+    #[serial]
+    fn test_helius_source_requires_api_key() {
+        cleanup_env();
+        std::env::set_var("ETH_RPC_URL", "wss://example.eth");
+        std::env::set_var("SOL_RPC_URL", "https://example.sol");
+        std::env::set_var("REDIS_URL", "redis://localhost");
+        std::env::set_var("SOL_SOURCE", "helius");
+        let missing = Config::from_env();
+        std::env::set_var("HELIUS_API_KEY", "key");
+        let res = Config::from_env();
+        cleanup_env();
+
+        assert!(missing.is_err());
+        assert_eq!(res.unwrap().sol_source, SolSource::Helius);
+    }
+
     #[test]
     #[serial]
     fn test_config_from_env_invalid_poll_interval() {
@@ -203,4 +1636,1052 @@ mod tests {
             res
         );
     }
+
+    #[test]
+    #[serial]
+    fn test_config_schedule_overrides() {
+        cleanup_env();
+
+        std::env::set_var("ETH_RPC_URL", "wss://example.eth");
+        std::env::set_var("SOL_RPC_URL", "wss://example.sol");
+        std::env::set_var("REDIS_URL", "redis://localhost");
+        std::env::set_var("ETH_NETWORK", "mainnet");
+        std::env::set_var("SOL_NETWORK", "mainnet");
+        std::env::set_var("SCHEDULE_DEDUP_COMPACTION", "@every 5m");
+        std::env::set_var("DEDUP_RETENTION_SECS", "3600");
+        std::env::set_var("DEDUP_KEY", "address");
+        std::env::set_var("DISTRIBUTED_DEDUP", "true");
+
+        let res = Config::from_env();
+        cleanup_env();
+
+        let cfg = res.expect("config should load");
+        assert_eq!(
+            cfg.schedules.get("dedup_compaction").map(String::as_str),
+            Some("@every 5m")
+        );
+        assert_eq!(cfg.dedup_retention_secs, 3600);
+        assert_eq!(cfg.dedup_key, DedupKey::Address);
+        assert_eq!(cfg.dedup_store, DedupStoreKind::Redis);
+    }
+
+    #[test]
+    #[serial]
+    fn test_config_rejects_unknown_schedule() {
+        cleanup_env();
+        std::env::set_var("ETH_RPC_URL", "wss://example.eth");
+        std::env::set_var("SOL_RPC_URL", "wss://example.sol");
+        std::env::set_var("REDIS_URL", "redis://localhost");
+        std::env::set_var("SCHEDULE_DEDUP_COMPATION", "@every 5m");
+        let res = Config::from_env();
+        cleanup_env();
+
+        let err = format!("{:#}", res.unwrap_err());
+        assert!(
+            err.contains("unknown maintenance job dedup_compation"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    #[serial]
+    fn test_config_dedup_store() {
+        cleanup_env();
+        std::env::set_var("ETH_RPC_URL", "wss://example.eth");
+        std::env::set_var("SOL_RPC_URL", "wss://example.sol");
+        std::env::set_var("REDIS_URL", "redis://localhost");
+        std::env::set_var("DEDUP_STORE", "file");
+        std::env::set_var("DEDUP_STORE_FILE", "/var/lib/tracker/dedup.log");
+        let res = Config::from_env();
+        std::env::set_var("DISTRIBUTED_DEDUP", "true");
+        let conflicting = Config::from_env();
+        cleanup_env();
+
+        let cfg = res.expect("config should load");
+        assert_eq!(cfg.dedup_store, DedupStoreKind::File);
+        assert_eq!(
+            cfg.dedup_store_file,
+            PathBuf::from("/var/lib/tracker/dedup.log")
+        );
+        assert!(conflicting.is_err());
+    }
+
+    #[test]
+    #[serial]
+    fn test_config_rpc_backups() {
+        cleanup_env();
+        std::env::set_var("ETH_RPC_URL", "wss://primary.example");
+        std::env::set_var("SOL_RPC_URL", "wss://example.sol");
+        std::env::set_var("REDIS_URL", "redis://localhost");
+        std::env::set_var(
+            "ETH_RPC_BACKUP_URLS",
+            "https://backup-1.example, wss://backup-2.example",
+        );
+        std::env::set_var("ETH_RPC_MAX_HEAD_LAG", "3");
+        let res = Config::from_env();
+        cleanup_env();
+
+        let cfg = res.expect("config should load");
+        assert_eq!(
+            cfg.eth_rpc_backup_urls,
+            ["https://backup-1.example", "wss://backup-2.example"]
+        );
+        assert_eq!(cfg.eth_rpc_max_head_lag, 3);
+    }
+
+    #[test]
+    #[serial]
+    fn test_config_region() {
+        cleanup_env();
+        std::env::set_var("ETH_RPC_URL", "wss://example.eth");
+        std::env::set_var("SOL_RPC_URL", "wss://example.sol");
+        std::env::set_var("REDIS_URL", "redis://localhost");
+        std::env::set_var("REGION", "eu-west");
+        std::env::set_var("INSTANCE_ID", "tracker-eu-1");
+        let res = Config::from_env();
+        cleanup_env();
+
+        let cfg = res.expect("config should load");
+        assert_eq!(cfg.region.as_deref(), Some("eu-west"));
+        assert_eq!(cfg.instance_id, "tracker-eu-1");
+    }
+
+    #[test]
+    #[serial]
+    fn test_config_kill_switch() {
+        cleanup_env();
+        std::env::set_var("ETH_RPC_URL", "wss://example.eth");
+        std::env::set_var("SOL_RPC_URL", "wss://example.sol");
+        std::env::set_var("REDIS_URL", "redis://localhost");
+        std::env::set_var("KILL_SWITCH_KEY", "prod:tracker:pause");
+        std::env::set_var("KILL_SWITCH_MODE", "drop");
+        let res = Config::from_env();
+        std::env::set_var("KILL_SWITCH_MODE", "sometimes");
+        let invalid = Config::from_env();
+        cleanup_env();
+
+        let kill_switch = res.expect("config should load").kill_switch;
+        assert_eq!(kill_switch.key, "prod:tracker:pause");
+        assert_eq!(kill_switch.mode, KillSwitchMode::Drop);
+        assert_eq!(kill_switch.max_buffered, 10_000);
+        assert!(invalid.is_err());
+    }
+
+    #[test]
+    #[serial]
+    fn test_config_health() {
+        cleanup_env();
+        std::env::set_var("ETH_RPC_URL", "wss://example.eth");
+        std::env::set_var("SOL_RPC_URL", "wss://example.sol");
+        std::env::set_var("REDIS_URL", "redis://localhost");
+        std::env::set_var("HEALTH_BIND_ADDR", "0.0.0.0:8081");
+        std::env::set_var("HEALTH_MAX_LAG_SOL", "9000");
+        let res = Config::from_env();
+        std::env::set_var("HEALTH_MAX_LAG_ETH", "ten");
+        let invalid = Config::from_env();
+        cleanup_env();
+
+        let health = res.expect("config should load").health;
+        assert_eq!(health.bind_addr.as_deref(), Some("0.0.0.0:8081"));
+        assert_eq!(health.max_lag_eth, DEFAULT_MAX_LAG_ETH);
+        assert_eq!(health.max_lag_sol, 9000);
+        assert!(invalid.is_err());
+    }
+
+    #[test]
+    #[serial]
+    fn test_config_shutdown_timeout() {
+        cleanup_env();
+        std::env::set_var("ETH_RPC_URL", "wss://example.eth");
+        std::env::set_var("SOL_RPC_URL", "wss://example.sol");
+        std::env::set_var("REDIS_URL", "redis://localhost");
+        std::env::set_var("SHUTDOWN_TIMEOUT_SECS", "5");
+        let res = Config::from_env();
+        std::env::set_var("SHUTDOWN_TIMEOUT_SECS", "5s");
+        let invalid = Config::from_env();
+        cleanup_env();
+
+        assert_eq!(res.expect("config should load").shutdown_timeout_secs, 5);
+        assert!(invalid.is_err());
+    }
+
+    #[test]
+    #[serial]
+    fn test_config_quarantine() {
+        cleanup_env();
+        std::env::set_var("ETH_RPC_URL", "wss://example.eth");
+        std::env::set_var("SOL_RPC_URL", "wss://example.sol");
+        std::env::set_var("REDIS_URL", "redis://localhost");
+        std::env::set_var("EVENT_RETRY_BUDGET", "0");
+        std::env::set_var("QUARANTINE_MAX", "50");
+        std::env::set_var("QUARANTINE_FILE", "quarantine.json");
+        let res = Config::from_env();
+        cleanup_env();
+
+        let quarantine = res.expect("config should load").quarantine;
+        // Every event gets at least one attempt.
+        assert_eq!(quarantine.retry_budget, 1);
+        assert_eq!(quarantine.max_events, 50);
+        assert_eq!(quarantine.file.as_deref(), Some("quarantine.json"));
+    }
+
+    #[test]
+    #[serial]
+    fn test_config_checkpoint_store() {
+        cleanup_env();
+        std::env::set_var("ETH_RPC_URL", "wss://example.eth");
+        std::env::set_var("SOL_RPC_URL", "wss://example.sol");
+        std::env::set_var("REDIS_URL", "redis://localhost");
+        std::env::set_var("CHECKPOINT_STORE", "file");
+        std::env::set_var("CHECKPOINT_FILE", "/var/lib/tracker/checkpoints.json");
+        std::env::set_var("MAX_CATCHUP_BLOCKS", "0");
+        std::env::set_var("CHECKPOINT_RETENTION_DAYS", "30");
+        let res = Config::from_env();
+        std::env::set_var("CHECKPOINT_STORE", "postgres");
+        let invalid = Config::from_env();
+        cleanup_env();
+
+        let checkpoints = res.expect("config should load").checkpoints;
+        assert_eq!(checkpoints.store, CheckpointStore::File);
+        assert_eq!(
+            checkpoints.file,
+            PathBuf::from("/var/lib/tracker/checkpoints.json")
+        );
+        assert_eq!(checkpoints.max_catchup_blocks, 0);
+        assert_eq!(checkpoints.retention_days, 30);
+        assert!(invalid.is_err());
+    }
+
+    #[test]
+    #[serial]
+    fn test_config_eth_only_without_sol_vars() {
+        cleanup_env();
+
+        std::env::set_var("ETH_RPC_URL", "http://localhost:8545");
+        std::env::set_var("ETH_NETWORK", "anvil");
+        std::env::set_var("REDIS_URL", "redis://localhost");
+        std::env::set_var("SOL_ENABLED", "false");
+
+        let res = Config::from_env();
+        cleanup_env();
+
+        let cfg = res.expect("SOL vars should not be required when disabled");
+        assert!(cfg.eth_enabled);
+        assert!(!cfg.sol_enabled);
+        assert!(cfg.sol_rpc_url.is_empty());
+    }
+
+    #[test]
+    #[serial]
+    fn test_config_enabled_chain_still_requires_vars() {
+        cleanup_env();
+
+        std::env::set_var("ETH_ENABLED", "false");
+        std::env::set_var("REDIS_URL", "redis://localhost");
+
+        let res = Config::from_env();
+        cleanup_env();
+
+        let err = res.expect_err("SOL is enabled by default and needs SOL_RPC_URL");
+        assert!(err.to_string().contains("SOL_RPC_URL"));
+    }
+
+    #[test]
+    #[serial]
+    fn test_config_reports_every_problem() {
+        cleanup_env();
+
+        std::env::set_var("ETH_RPC_URL", "wss://example.eth");
+        std::env::set_var("ETH_NETWORK", "mainnet");
+        std::env::set_var("POLL_INTERVAL_SECS", "10s");
+        std::env::set_var("DEDUP_KEY", "block");
+        std::env::set_var("REDIS_OUTPUT", "kafka");
+
+        let res = Config::from_env();
+        cleanup_env();
+
+        let err = res.expect_err("config should be rejected");
+        let errors = err
+            .downcast_ref::<ConfigErrors>()
+            .expect("aggregate errors");
+        assert!(errors.problems().contains(&ConfigProblem::Missing {
+            name: "SOL_RPC_URL".into(),
+            file_key: Some("[solana] rpc_url".into()),
+        }));
+        let summary = err.to_string();
+        assert!(
+            summary.starts_with("invalid configuration ("),
+            "{}",
+            summary
+        );
+        assert!(errors.problems().len() >= 5, "{}", summary);
+        assert!(summary.contains("\n  missing  REDIS_URL (or redis_url in CONFIG_FILE)"));
+        assert!(summary.contains("\n  invalid  POLL_INTERVAL_SECS must be a number, got \"10s\""));
+        assert!(summary.contains("DEDUP_KEY"));
+        assert!(summary.contains("REDIS_OUTPUT"));
+    }
+
+    #[test]
+    #[serial]
+    fn test_config_rejects_all_chains_disabled() {
+        cleanup_env();
+
+        std::env::set_var("ETH_ENABLED", "0");
+        std::env::set_var("SOL_ENABLED", "no");
+        std::env::set_var("REDIS_URL", "redis://localhost");
+
+        let res = Config::from_env();
+        cleanup_env();
+
+        assert!(res.is_err());
+    }
+
+    #[test]
+    #[serial]
+    fn test_config_file_sections_with_env_override() {
+        cleanup_env();
+
+        let path = std::env::temp_dir().join("tracker_config_sections_test.toml");
+        std::fs::write(
+            &path,
+            r#"
+redis_url = "redis://file:6379"
+
+[ethereum]
+enabled = false
+
+[solana]
+rpc_url = "https://api.devnet.solana.com"
+network = "devnet"
+watched_addresses = ["Addr1"]
+token_allowlist = ["MintA"]
+"#,
+        )
+        .unwrap();
+        std::env::set_var("CONFIG_FILE", &path);
+        std::env::set_var("SOL_NETWORK", "mainnet");
+        std::env::set_var("SOL_TOKEN_DENYLIST", "MintB");
+
+        let res = Config::from_env();
+        cleanup_env();
+        std::fs::remove_file(&path).ok();
+
+        let cfg = res.expect("config file should load");
+        assert!(!cfg.eth_enabled);
+        assert!(cfg.sol_enabled);
+        assert_eq!(cfg.redis_url, "redis://file:6379");
+        assert_eq!(cfg.sol_rpc_url, "https://api.devnet.solana.com");
+        assert_eq!(cfg.sol_network, "mainnet");
+        assert_eq!(cfg.watched_addresses_sol, vec!["Addr1".to_string()]);
+        assert_eq!(
+            cfg.sol_tokens,
+            TokenFilter::new(&["MintA".to_string()], &["MintB".to_string()])
+        );
+        assert_eq!(cfg.destinations, vec![DestinationConfig::default()]);
+    }
+
+    #[test]
+    #[serial]
+    fn test_config_file_destinations() {
+        cleanup_env();
+
+        let path = std::env::temp_dir().join("tracker_config_destinations_test.toml");
+        std::fs::write(
+            &path,
+            r#"
+[[destinations]]
+name = "realtime"
+channel = "cross_chain_events"
+
+[[destinations]]
+name = "alerts"
+redis_url = "redis://alerts:6379"
+channel = "erc20_alerts"
+chains = ["ethereum"]
+event_types = ["erc20_transfer"]
+fields = ["event_id", "chain", "tx_hash", "value"]
+overflow = "drop"
+queue_size = 16
+workers = 4
+ordering = "per_address"
+
+[[destinations]]
+name = "archive"
+channel = "cross_chain_events_archive"
+batch_max_events = 500
+batch_max_delay_ms = 2000
+compression = "zstd"
+
+[[destinations]]
+name = "ledger"
+sink = "file"
+path = "/var/lib/tracker/events.jsonl"
+retention_days = 365
+
+[[destinations]]
+name = "durable"
+sink = "stream"
+stream = "cross_chain_events_stream"
+archive = "/var/lib/tracker/stream-archive.jsonl"
+"#,
+        )
+        .unwrap();
+        std::env::set_var("CONFIG_FILE", &path);
+        std::env::set_var("ETH_RPC_URL", "http://localhost:8545");
+        std::env::set_var("ETH_NETWORK", "anvil");
+        std::env::set_var("SOL_ENABLED", "false");
+        std::env::set_var("REDIS_URL", "redis://localhost");
+
+        let res = Config::from_env();
+        cleanup_env();
+        std::fs::remove_file(&path).ok();
+
+        let cfg = res.expect("config file should load");
+        assert_eq!(cfg.destinations.len(), 5);
+        assert_eq!(cfg.destinations[0].retry_attempts, 8);
+        let alerts = &cfg.destinations[1];
+        assert_eq!(alerts.redis_url.as_deref(), Some("redis://alerts:6379"));
+        assert_eq!(alerts.event_types, vec![EventType::Erc20Transfer]);
+        assert_eq!(alerts.overflow, Overflow::Drop);
+        assert_eq!(alerts.queue_size, 16);
+        assert_eq!(alerts.workers, 4);
+        assert_eq!(alerts.ordering, OrderingMode::PerAddress);
+        assert_eq!(alerts.fields, vec!["event_id", "chain", "tx_hash", "value"]);
+        assert_eq!(alerts.batch_max_events, 0);
+        let archive = &cfg.destinations[2];
+        assert_eq!(archive.batch_max_events, 500);
+        assert_eq!(archive.batch_max_delay_ms, 2000);
+        assert_eq!(archive.compression, Compression::Zstd);
+        assert_eq!(archive.sink, SinkKind::Redis);
+        let ledger = &cfg.destinations[3];
+        assert_eq!(ledger.sink, SinkKind::File);
+        assert_eq!(
+            ledger.path.as_deref(),
+            Some(std::path::Path::new("/var/lib/tracker/events.jsonl"))
+        );
+        assert_eq!(ledger.retention_days, 365);
+        let durable = &cfg.destinations[4];
+        assert_eq!(durable.sink, SinkKind::Stream);
+        assert_eq!(durable.stream, "cross_chain_events_stream");
+        assert!(durable.archive.is_some());
+        assert_eq!(durable.max_len, 100_000);
+        assert_eq!(durable.retention_days, 0);
+    }
+
+    #[test]
+    #[serial]
+    fn test_config_redis_output() {
+        cleanup_env();
+        std::env::set_var("ETH_RPC_URL", "http://localhost:8545");
+        std::env::set_var("ETH_NETWORK", "anvil");
+        std::env::set_var("SOL_ENABLED", "false");
+        std::env::set_var("REDIS_URL", "redis://localhost");
+        std::env::set_var("REDIS_OUTPUT", "both");
+        std::env::set_var("REDIS_STREAM", "events");
+        std::env::set_var("REDIS_STREAM_MAXLEN", "5000");
+        let both = Config::from_env();
+        std::env::set_var("REDIS_OUTPUT", "stream");
+        std::env::remove_var("REDIS_STREAM_MAXLEN");
+        let stream = Config::from_env();
+        std::env::set_var("REDIS_OUTPUT", "kafka");
+        let invalid = Config::from_env();
+        cleanup_env();
+
+        let both = both.expect("config should load").destinations;
+        assert_eq!(both.len(), 2);
+        assert_eq!(both[0], DestinationConfig::default());
+        assert_eq!(both[1].sink, SinkKind::Stream);
+        assert_eq!(both[1].stream, "events");
+        assert_eq!(both[1].max_len, 5000);
+        let stream = stream.expect("config should load").destinations;
+        assert_eq!(stream.len(), 1);
+        assert_eq!(stream[0].max_len, 100_000);
+        assert!(invalid.is_err());
+    }
+
+    #[test]
+    #[serial]
+    fn test_config_kafka_destination() {
+        cleanup_env();
+        std::env::set_var("ETH_RPC_URL", "http://localhost:8545");
+        std::env::set_var("ETH_NETWORK", "anvil");
+        std::env::set_var("SOL_ENABLED", "false");
+        std::env::set_var("REDIS_URL", "redis://localhost");
+
+        let path = std::env::temp_dir().join("tracker_config_kafka_test.toml");
+        let mut results = Vec::new();
+        for destination in [
+            "brokers = \"kafka-1:9092,kafka-2:9092\"\ntopic = \"chain-events\"\n\
+             partition_key = \"chain\"\nkafka = { \"compression.type\" = \"lz4\" }",
+            "brokers = \"kafka-1:9092\"",
+            "brokers = \"kafka-1:9092\"\ntopic = \"chain-events\"\nbatch_max_events = 100",
+        ] {
+            std::fs::write(
+                &path,
+                format!(
+                    "[[destinations]]\nname = \"pipeline\"\nsink = \"kafka\"\n{}\n",
+                    destination
+                ),
+            )
+            .unwrap();
+            std::env::set_var("CONFIG_FILE", &path);
+            results.push(Config::from_env());
+        }
+        cleanup_env();
+        std::fs::remove_file(&path).ok();
+
+        let cfg = results.remove(0).expect("config should load");
+        let pipeline = &cfg.destinations[0];
+        assert_eq!(pipeline.sink, SinkKind::Kafka);
+        assert_eq!(pipeline.brokers, "kafka-1:9092,kafka-2:9092");
+        assert_eq!(pipeline.topic, "chain-events");
+        assert_eq!(pipeline.partition_key, PartitionKey::Chain);
+        assert_eq!(pipeline.kafka["compression.type"], "lz4");
+        let errors: Vec<String> = results
+            .into_iter()
+            .map(|res| res.expect_err("should be rejected").to_string())
+            .collect();
+        assert!(errors[0].contains("brokers and a topic"), "{}", errors[0]);
+        assert!(errors[1].contains("linger.ms"), "{}", errors[1]);
+    }
+
+    #[test]
+    #[serial]
+    fn test_config_routing_templates() {
+        cleanup_env();
+        std::env::set_var("ETH_RPC_URL", "http://localhost:8545");
+        std::env::set_var("ETH_NETWORK", "anvil");
+        std::env::set_var("SOL_ENABLED", "false");
+        std::env::set_var("REDIS_URL", "redis://localhost");
+
+        let path = std::env::temp_dir().join("tracker_config_routing_test.toml");
+        let mut results = Vec::new();
+        for destination in [
+            "channel = \"events.{chain}.{event_type}\"",
+            "sink = \"kafka\"\nbrokers = \"kafka-1:9092\"\ntopic = \"events-{chain}\"",
+            "channel = \"events.{token}\"",
+            "channel = \"events.{chain}\"\nbatch_max_events = 100",
+            "sink = \"kafka\"\nbrokers = \"kafka-1:9092\"\ntopic = \"events/{chain}\"",
+        ] {
+            std::fs::write(
+                &path,
+                format!("[[destinations]]\nname = \"routed\"\n{}\n", destination),
+            )
+            .unwrap();
+            std::env::set_var("CONFIG_FILE", &path);
+            results.push(Config::from_env());
+        }
+        cleanup_env();
+        std::fs::remove_file(&path).ok();
+
+        let channel = &results.remove(0).expect("config should load").destinations[0];
+        assert_eq!(channel.channel, "events.{chain}.{event_type}");
+        let topic = &results.remove(0).expect("config should load").destinations[0];
+        assert_eq!(topic.topic, "events-{chain}");
+        let errors: Vec<String> = results
+            .into_iter()
+            .map(|res| res.expect_err("should be rejected").to_string())
+            .collect();
+        assert!(errors[0].contains("{token}"), "{}", errors[0]);
+        assert!(errors[1].contains("can't batch"), "{}", errors[1]);
+        assert!(errors[2].contains("invalid topic"), "{}", errors[2]);
+    }
+
+    #[test]
+    #[serial]
+    fn test_config_nats() {
+        cleanup_env();
+        std::env::set_var("ETH_RPC_URL", "http://localhost:8545");
+        std::env::set_var("ETH_NETWORK", "anvil");
+        std::env::set_var("SOL_ENABLED", "false");
+        std::env::set_var("REDIS_URL", "redis://localhost");
+        std::env::set_var("NATS_URL", "nats://localhost:4222");
+        std::env::set_var("NATS_JETSTREAM", "true");
+        let from_env = Config::from_env();
+
+        let path = std::env::temp_dir().join("tracker_config_nats_test.toml");
+        let mut results = Vec::new();
+        for subject in ["chain.{network}.{event_type}", "chain.{token}"] {
+            std::fs::write(
+                &path,
+                format!(
+                    "[[destinations]]\nname = \"bus\"\nsink = \"nats\"\nsubject = \"{}\"\n",
+                    subject
+                ),
+            )
+            .unwrap();
+            std::env::set_var("CONFIG_FILE", &path);
+            std::env::remove_var("NATS_JETSTREAM");
+            results.push(Config::from_env());
+        }
+        std::env::set_var("NATS_SUBJECT", "events.{chain}");
+        let conflicting = Config::from_env();
+        cleanup_env();
+        std::fs::remove_file(&path).ok();
+
+        let destinations = from_env.expect("config should load").destinations;
+        assert_eq!(destinations.len(), 2);
+        assert_eq!(destinations[0], DestinationConfig::default());
+        assert_eq!(destinations[1].sink, SinkKind::Nats);
+        assert_eq!(destinations[1].subject, "events.{chain}.{event_type}");
+        assert!(destinations[1].jetstream);
+        let bus = &results.remove(0).expect("config should load").destinations[0];
+        assert_eq!(bus.nats_url.as_deref(), Some("nats://localhost:4222"));
+        assert!(!bus.jetstream);
+        let err = results
+            .remove(0)
+            .expect_err("should be rejected")
+            .to_string();
+        assert!(err.contains("{token}"), "{}", err);
+        assert!(conflicting.is_err());
+    }
+
+    #[test]
+    #[serial]
+    fn test_config_postgres() {
+        cleanup_env();
+        std::env::set_var("ETH_RPC_URL", "http://localhost:8545");
+        std::env::set_var("ETH_NETWORK", "anvil");
+        std::env::set_var("SOL_ENABLED", "false");
+        std::env::set_var("REDIS_URL", "redis://localhost");
+        std::env::set_var("POSTGRES_URL", "postgres://tracker@localhost/tracker");
+        let from_env = Config::from_env();
+
+        let path = std::env::temp_dir().join("tracker_config_postgres_test.toml");
+        let mut results = Vec::new();
+        for destination in [
+            "table = \"tracker.transfers\"",
+            "table = \"Transfers\"",
+            "fields = [\"event_id\"]",
+        ] {
+            std::fs::write(
+                &path,
+                format!(
+                    "[[destinations]]\nname = \"db\"\nsink = \"postgres\"\n{}\n",
+                    destination
+                ),
+            )
+            .unwrap();
+            std::env::set_var("CONFIG_FILE", &path);
+            results.push(Config::from_env());
+        }
+        std::env::remove_var("POSTGRES_URL");
+        let without_url = Config::from_env();
+        cleanup_env();
+        std::fs::remove_file(&path).ok();
+
+        let destinations = from_env.expect("config should load").destinations;
+        assert_eq!(destinations.len(), 2);
+        assert_eq!(destinations[1].sink, SinkKind::Postgres);
+        assert_eq!(destinations[1].table, "tracker_events");
+        let db = &results.remove(0).expect("config should load").destinations[0];
+        assert_eq!(
+            db.postgres_url.as_deref(),
+            Some("postgres://tracker@localhost/tracker")
+        );
+        assert_eq!(db.table, "tracker.transfers");
+        let errors: Vec<String> = results
+            .into_iter()
+            .map(|res| res.expect_err("should be rejected").to_string())
+            .collect();
+        assert!(errors[0].contains("invalid table"), "{}", errors[0]);
+        assert!(errors[1].contains("full event"), "{}", errors[1]);
+        let err = without_url.expect_err("should be rejected").to_string();
+        assert!(err.contains("POSTGRES_URL"), "{}", err);
+    }
+
+    #[test]
+    #[serial]
+    fn test_config_webhook() {
+        cleanup_env();
+        std::env::set_var("ETH_RPC_URL", "http://localhost:8545");
+        std::env::set_var("ETH_NETWORK", "anvil");
+        std::env::set_var("SOL_ENABLED", "false");
+        std::env::set_var("REDIS_URL", "redis://localhost");
+        std::env::set_var(
+            "WEBHOOK_URLS",
+            "https://hooks.example/a, https://hooks.example/b",
+        );
+        std::env::set_var("WEBHOOK_DEAD_LETTER", "/var/lib/tracker/dead-letters.jsonl");
+        let without_secret = Config::from_env();
+        std::env::set_var("WEBHOOK_SECRET", "s3cret");
+        let from_env = Config::from_env();
+        std::env::remove_var("WEBHOOK_URLS");
+        std::env::remove_var("WEBHOOK_DEAD_LETTER");
+
+        let path = std::env::temp_dir().join("tracker_config_webhook_test.toml");
+        let mut results = Vec::new();
+        for destination in [
+            "urls = [\"https://hooks.example/c\"]",
+            "urls = [\"ftp://hooks.example/c\"]",
+            "urls = []",
+        ] {
+            std::fs::write(
+                &path,
+                format!(
+                    "[[destinations]]\nname = \"hooks\"\nsink = \"webhook\"\n{}\n",
+                    destination
+                ),
+            )
+            .unwrap();
+            std::env::set_var("CONFIG_FILE", &path);
+            results.push(Config::from_env());
+        }
+        cleanup_env();
+        std::fs::remove_file(&path).ok();
+
+        let err = without_secret.expect_err("should be rejected").to_string();
+        assert!(err.contains("WEBHOOK_SECRET"), "{}", err);
+        let destinations = from_env.expect("config should load").destinations;
+        assert_eq!(destinations.len(), 2);
+        assert_eq!(destinations[1].sink, SinkKind::Webhook);
+        assert_eq!(
+            destinations[1].urls,
+            ["https://hooks.example/a", "https://hooks.example/b"]
+        );
+        assert_eq!(destinations[1].secret.as_deref(), Some("s3cret"));
+        assert_eq!(
+            destinations[1].dead_letter,
+            Some(PathBuf::from("/var/lib/tracker/dead-letters.jsonl"))
+        );
+        let hooks = &results.remove(0).expect("config should load").destinations[0];
+        assert_eq!(hooks.secret.as_deref(), Some("s3cret"));
+        let errors: Vec<String> = results
+            .into_iter()
+            .map(|res| res.expect_err("should be rejected").to_string())
+            .collect();
+        assert!(errors[0].contains("ftp://"), "{}", errors[0]);
+        assert!(errors[1].contains("needs urls"), "{}", errors[1]);
+    }
+
+    #[test]
+    #[serial]
+    fn test_config_rejects_misconfigured_stream() {
+        cleanup_env();
+        std::env::set_var("ETH_RPC_URL", "http://localhost:8545");
+        std::env::set_var("ETH_NETWORK", "anvil");
+        std::env::set_var("SOL_ENABLED", "false");
+        std::env::set_var("REDIS_URL", "redis://localhost");
+
+        let path = std::env::temp_dir().join("tracker_config_stream_test.toml");
+        let mut errors = Vec::new();
+        for destination in [
+            "sink = \"stream\"",
+            "channel = \"events\"\narchive = \"events.jsonl\"",
+            "sink = \"stream\"\nstream = \"events\"\narchive = \"events.jsonl\"\n\
+             batch_max_events = 10\ncompression = \"zstd\"",
+            "channel = \"events\"\nretention_days = 90",
+        ] {
+            std::fs::write(
+                &path,
+                format!("[[destinations]]\nname = \"durable\"\n{}\n", destination),
+            )
+            .unwrap();
+            std::env::set_var("CONFIG_FILE", &path);
+            errors.push(
+                Config::from_env()
+                    .expect_err("should be rejected")
+                    .to_string(),
+            );
+        }
+        cleanup_env();
+        std::fs::remove_file(&path).ok();
+
+        assert!(errors[0].contains("needs a stream"), "{}", errors[0]);
+        assert!(errors[1].contains("archive"), "{}", errors[1]);
+        assert!(errors[2].contains("compressed"), "{}", errors[2]);
+        assert!(errors[3].contains("events to expire"), "{}", errors[3]);
+    }
+
+    #[test]
+    #[serial]
+    fn test_config_rejects_unknown_payload_field() {
+        cleanup_env();
+
+        let path = std::env::temp_dir().join("tracker_config_fields_test.toml");
+        std::fs::write(
+            &path,
+            r#"
+[[destinations]]
+name = "minimal"
+channel = "minimal_events"
+fields = ["event_id", "txhash"]
+"#,
+        )
+        .unwrap();
+        std::env::set_var("CONFIG_FILE", &path);
+        std::env::set_var("ETH_RPC_URL", "http://localhost:8545");
+        std::env::set_var("ETH_NETWORK", "anvil");
+        std::env::set_var("SOL_ENABLED", "false");
+        std::env::set_var("REDIS_URL", "redis://localhost");
+
+        let res = Config::from_env();
+        cleanup_env();
+        std::fs::remove_file(&path).ok();
+
+        let err = res.expect_err("unknown field should be rejected");
+        assert!(err.to_string().contains("'txhash'"), "{}", err);
+    }
+
+    #[test]
+    #[serial]
+    fn test_config_file_assets() {
+        cleanup_env();
+
+        let path = std::env::temp_dir().join("tracker_config_assets_test.toml");
+        std::fs::write(
+            &path,
+            r#"
+[[assets]]
+name = "USDC"
+tokens = ["mainnet:0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48", "solana:EPjFWdd5"]
+
+[[portfolios]]
+name = "treasury"
+addresses = ["ethereum:0x70997970C51812dc3A010C7d01b50e0d17dc79C8"]
+
+[prices]
+USDC = 1.0
+
+[[address_book]]
+address = "ethereum:0x70997970C51812dc3A010C7d01b50e0d17dc79C8"
+cold_storage = true
+severity = "critical"
+
+[sweep_detection]
+min_sources = 3
+
+[validator_monitoring]
+validators = ["Vote111111111111111111111111111111111111111"]
+
+[liquidation_risk]
+
+[stuck_transactions]
+pending_secs = 300
+
+[fee_spike]
+min_overpayment = 0.05
+
+[tron]
+network = "nile"
+watched_addresses = ["T9yD14Nj9j7xAB4dbGeiX9h8unkKHxuWwb"]
+
+[[substrate_networks]]
+chain = "kusama"
+rpc_url = "wss://kusama-rpc.polkadot.io"
+watched_addresses = ["HNZata7iMYWmk5RvZRTiAsSDhV8366zq2YGb3tLH5Upf74F"]
+"#,
+        )
+        .unwrap();
+        std::env::set_var("CONFIG_FILE", &path);
+        std::env::set_var("ETH_RPC_URL", "http://localhost:8545");
+        std::env::set_var("ETH_NETWORK", "anvil");
+        std::env::set_var("SOL_ENABLED", "false");
+        std::env::set_var("REDIS_URL", "redis://localhost");
+
+        let res = Config::from_env();
+        cleanup_env();
+        std::fs::remove_file(&path).ok();
+
+        let cfg = res.expect("config file should load");
+        let usdc = Some("0xa0b86991c6218b36c1d19d4a2e9eb0ce3606eb48");
+        assert_eq!(
+            cfg.asset_map.logical_asset("ethereum", "mainnet", usdc),
+            Some("USDC")
+        );
+        assert_eq!(
+            cfg.asset_map
+                .logical_asset("solana", "mainnet", Some("EPjFWdd5")),
+            Some("USDC")
+        );
+        assert!(!cfg.portfolios.is_empty());
+        assert_ne!(cfg.address_book, AddressBook::default());
+        assert_eq!(
+            cfg.sweep_detection,
+            Some(SweepConfig {
+                min_sources: 3,
+                window_secs: 600,
+            })
+        );
+        assert_eq!(
+            cfg.validator_monitoring,
+            Some(ValidatorConfig {
+                validators: vec!["Vote111111111111111111111111111111111111111".into()],
+                min_stake_change: 10_000,
+            })
+        );
+        assert_eq!(
+            cfg.liquidation_risk,
+            Some(LiquidationRiskConfig { threshold: 1.1 })
+        );
+        assert_eq!(
+            cfg.stuck_transactions,
+            Some(StuckConfig { pending_secs: 300 })
+        );
+        assert_eq!(
+            cfg.fee_spike,
+            Some(FeeSpikeConfig {
+                multiple: 3.0,
+                min_overpayment: 0.05,
+            })
+        );
+        let tron = cfg.tron.expect("[tron] should load");
+        assert_eq!(tron.key(), "tron:nile");
+        assert_eq!(tron.api_url, "https://api.trongrid.io");
+        let kusama = &cfg.substrate_networks[0];
+        assert_eq!(kusama.key(), "kusama:kusama");
+        assert_eq!((kusama.symbol.as_str(), kusama.decimals), ("KSM", 12));
+    }
+
+    #[test]
+    #[serial]
+    fn test_config_file_evm_networks() {
+        cleanup_env();
+
+        let path = std::env::temp_dir().join("tracker_config_evm_networks_test.toml");
+        let write = |entries: &str| std::fs::write(&path, entries).unwrap();
+        std::env::set_var("CONFIG_FILE", &path);
+        std::env::set_var("ETH_RPC_URL", "http://localhost:8545");
+        std::env::set_var("ETH_NETWORK", "anvil");
+        std::env::set_var("SOL_ENABLED", "false");
+        std::env::set_var("REDIS_URL", "redis://localhost");
+
+        write(
+            r#"
+[[evm_networks]]
+chain = "polygon"
+rpc_url = "wss://polygon.example"
+watched_addresses = ["0x70997970C51812dc3A010C7d01b50e0d17dc79C8"]
+
+[[evm_networks]]
+chain = "arbitrum"
+network = "arbitrum-one"
+rpc_url = "https://arbitrum.example"
+"#,
+        );
+        let res = Config::from_env();
+        write(
+            r#"
+[[evm_networks]]
+chain = "polygon"
+rpc_url = "wss://polygon.example"
+
+[[evm_networks]]
+chain = "polygon"
+rpc_url = "wss://polygon-2.example"
+"#,
+        );
+        let duplicate = Config::from_env();
+        cleanup_env();
+        std::fs::remove_file(&path).ok();
+
+        let cfg = res.expect("config file should load");
+        let networks: Vec<_> = cfg
+            .evm_networks
+            .iter()
+            .map(|n| (n.key(), n.info.chain_id))
+            .collect();
+        assert_eq!(
+            networks,
+            [
+                ("polygon:polygon".to_string(), 137),
+                ("arbitrum:arbitrum-one".to_string(), 42161)
+            ]
+        );
+        let err = duplicate.expect_err("duplicate chains should be rejected");
+        assert!(err.to_string().contains("[[evm_networks]]"), "{}", err);
+    }
+
+    #[test]
+    #[serial]
+    fn test_config_spam_filter() {
+        cleanup_env();
+
+        std::env::set_var("ETH_RPC_URL", "http://localhost:8545");
+        std::env::set_var("ETH_NETWORK", "anvil");
+        std::env::set_var("SOL_ENABLED", "false");
+        std::env::set_var("REDIS_URL", "redis://localhost");
+        std::env::set_var("SPAM_FILTER", "drop");
+        std::env::set_var("SPAM_SYMBOL_PATTERNS", "pepe,bonus");
+
+        let res = Config::from_env();
+        std::env::set_var("SPAM_FILTER", "sometimes");
+        let invalid = Config::from_env();
+        cleanup_env();
+
+        let cfg = res.expect("config should load");
+        assert_eq!(
+            cfg.spam,
+            SpamFilter::new(SpamMode::Drop, &["pepe".to_string(), "bonus".to_string()])
+        );
+        assert!(invalid.is_err());
+    }
+
+    #[test]
+    #[serial]
+    fn test_alchemy_mined_transactions_auto_detect() {
+        cleanup_env();
+
+        std::env::set_var("ETH_RPC_URL", "wss://eth-mainnet.g.alchemy.com/v2/key");
+        std::env::set_var("ETH_NETWORK", "mainnet");
+        std::env::set_var("SOL_ENABLED", "false");
+        std::env::set_var("REDIS_URL", "redis://localhost");
+        let auto = Config::from_env();
+        std::env::set_var("ETH_ALCHEMY_MINED_TRANSACTIONS", "false");
+        let off = Config::from_env();
+        std::env::set_var("ETH_RPC_URL", "wss://mainnet.infura.io/ws/v3/key");
+        std::env::set_var("ETH_ALCHEMY_MINED_TRANSACTIONS", "auto");
+        let other = Config::from_env();
+        cleanup_env();
+
+        assert!(
+            auto.expect("config should load")
+                .eth_alchemy_mined_transactions
+        );
+        assert!(
+            !off.expect("config should load")
+                .eth_alchemy_mined_transactions
+        );
+        assert!(
+            !other
+                .expect("config should load")
+                .eth_alchemy_mined_transactions
+        );
+        assert!(!is_alchemy_ws("https://eth-mainnet.g.alchemy.com/v2/key"));
+        assert!(!is_alchemy_ws("wss://alchemy.com.example.org/"));
+    }
+
+    #[test]
+    #[serial]
+    fn test_eth_chain_id_and_registry_file() {
+        cleanup_env();
+
+        let path = std::env::temp_dir().join("tracker_chain_registry_test.json");
+        std::fs::write(
+            &path,
+            r#"[{"chain_id": 43114, "name": "avalanche", "native_symbol": "AVAX",
+                "native_decimals": 18}]"#,
+        )
+        .unwrap();
+        std::env::set_var("ETH_RPC_URL", "http://localhost:8545");
+        std::env::set_var("ETH_NETWORK", "c-chain");
+        std::env::set_var("SOL_ENABLED", "false");
+        std::env::set_var("REDIS_URL", "redis://localhost");
+        let unknown_network = Config::from_env();
+        std::env::set_var("ETH_CHAIN_ID", "43114");
+        let unregistered = Config::from_env();
+        std::env::set_var("CHAIN_REGISTRY_FILE", &path);
+        let registered = Config::from_env();
+        cleanup_env();
+        std::fs::remove_file(&path).ok();
+
+        assert!(unknown_network
+            .expect("config should load")
+            .eth_chain
+            .is_none());
+        assert!(unregistered.is_err());
+        let chain = registered.expect("config should load").eth_chain.unwrap();
+        assert_eq!(chain.native_symbol, "AVAX");
+    }
 }
@@ -0,0 +1,256 @@
+//! Confirmation depth before events are published (`ETH_CONFIRMATIONS`,
+//! `SOL_COMMITMENT`).
+//!
+//! With `ETH_CONFIRMATIONS=N` a block is only handled once N more blocks
+//! have been mined on top of it. HTTP polling stops N blocks short of the
+//! head, and the `newHeads` tracker handles the block N below each new head,
+//! fetched by number, so a block that was reorged out in the meantime is
+//! never seen. Log subscriptions deliver logs at the tip, so those wait in
+//! [`PendingLogs`] until their block is deep enough. Then the transaction's
+//! receipt is fetched again, and a log whose transaction is no longer in
+//! that block is dropped; if the transaction was mined again elsewhere, the
+//! subscription delivers its new log. `alchemy_minedTransactions` also
+//! pushes at the tip, so with confirmations the `newHeads` tracker is used
+//! instead.
+//!
+//! `SOL_COMMITMENT` is the commitment the Solana trackers list signatures
+//! and fetch transactions at. A transaction that hasn't reached it isn't
+//! listed yet and is picked up by a later poll. `confirmed` (default)
+//! relies on the `solana_finality` job to retract the rare transaction that
+//! never finalizes; `finalized` publishes some seconds later but never needs
+//! a retraction, the job finds its events final on the first check.
+//! `processed` is rejected:
+//! Solana RPC doesn't serve transactions at that commitment.
+use std::collections::BTreeMap;
+use std::ops::RangeInclusive;
+use std::str::FromStr;
+
+use anyhow::anyhow;
+use ethers::providers::Middleware;
+use ethers::types::Log;
+use solana_sdk::commitment_config::CommitmentConfig;
+use tracing::{info, warn};
+
+/// Logs held for confirmations. Once full, further logs are handled at the
+/// tip.
+pub const PENDING_CAPACITY: usize = 50_000;
+
+/// How often held logs are checked against the head.
+pub const RECHECK_INTERVAL_SECS: u64 = 4;
+
+/// Commitment of the Solana trackers (`SOL_COMMITMENT`).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SolCommitment {
+    #[default]
+    Confirmed,
+    Finalized,
+}
+
+impl SolCommitment {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            SolCommitment::Confirmed => "confirmed",
+            SolCommitment::Finalized => "finalized",
+        }
+    }
+
+    pub fn config(self) -> CommitmentConfig {
+        match self {
+            SolCommitment::Confirmed => CommitmentConfig::confirmed(),
+            SolCommitment::Finalized => CommitmentConfig::finalized(),
+        }
+    }
+}
+
+impl FromStr for SolCommitment {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "confirmed" => Ok(SolCommitment::Confirmed),
+            "finalized" => Ok(SolCommitment::Finalized),
+            "processed" => Err(anyhow!(
+                "SOL_COMMITMENT processed isn't supported: Solana RPC doesn't serve transactions \
+                 at that commitment; use confirmed or finalized"
+            )),
+            other => Err(anyhow!(
+                "SOL_COMMITMENT must be confirmed or finalized, got '{}'",
+                other
+            )),
+        }
+    }
+}
+
+/// Blocks to handle when `head` arrives, `confirmations` below it. `next` is
+/// the first block not handled yet, `None` before the first head.
+pub fn confirmed_blocks(next: Option<u64>, head: u64, confirmations: u64) -> RangeInclusive<u64> {
+    match head.checked_sub(confirmations) {
+        Some(target) => next.unwrap_or(target)..=target,
+        // Nothing is deep enough yet.
+        None => 1..=0,
+    }
+}
+
+/// Logs waiting for their block to be `confirmations` deep, by block number.
+#[derive(Debug, Default)]
+pub struct PendingLogs {
+    confirmations: u64,
+    logs: BTreeMap<u64, Vec<Log>>,
+    len: usize,
+}
+
+impl PendingLogs {
+    pub fn new(confirmations: u64) -> Self {
+        PendingLogs {
+            confirmations,
+            ..Default::default()
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Hold `log` until its block is deep enough, or hand it back to be
+    /// handled now: without confirmations, for a log without a block, or
+    /// once full. A removed log, from a block reorged out, drops the held
+    /// copy instead.
+    pub fn hold(&mut self, log: Log) -> Option<Log> {
+        let Some(block) = log.block_number.map(|b| b.as_u64()) else {
+            return Some(log);
+        };
+        if self.confirmations == 0 {
+            return Some(log);
+        }
+        if log.removed == Some(true) {
+            if let Some(held) = self.logs.get_mut(&block) {
+                let before = held.len();
+                held.retain(|h| {
+                    (h.transaction_hash, h.log_index) != (log.transaction_hash, log.log_index)
+                });
+                self.len -= before - held.len();
+                if held.is_empty() {
+                    self.logs.remove(&block);
+                }
+            }
+            return None;
+        }
+        if self.len >= PENDING_CAPACITY {
+            return Some(log);
+        }
+        self.logs.entry(block).or_default().push(log);
+        self.len += 1;
+        None
+    }
+
+    /// Take the logs whose block is at least `confirmations` below `head`,
+    /// oldest first.
+    pub fn due(&mut self, head: u64) -> Vec<Log> {
+        let Some(deepest) = head.checked_sub(self.confirmations) else {
+            return Vec::new();
+        };
+        let later = self.logs.split_off(&(deepest + 1));
+        let due: Vec<Log> = std::mem::replace(&mut self.logs, later)
+            .into_values()
+            .flatten()
+            .collect();
+        self.len -= due.len();
+        due
+    }
+}
+
+/// Take the held logs that are deep enough below the current head and whose
+/// transactions are still in their blocks. Logs that couldn't be checked
+/// stay held.
+pub async fn release<M: Middleware>(provider: &M, pending: &mut PendingLogs) -> Vec<Log> {
+    let head = match provider.get_block_number().await {
+        Ok(head) => head.as_u64(),
+        Err(e) => {
+            warn!("Failed to get the head for held logs: {}", e);
+            return Vec::new();
+        }
+    };
+    let mut ready = Vec::new();
+    for log in pending.due(head) {
+        let Some(tx_hash) = log.transaction_hash else {
+            ready.push(log);
+            continue;
+        };
+        match provider.get_transaction_receipt(tx_hash).await {
+            Ok(Some(receipt)) if receipt.block_hash == log.block_hash => ready.push(log),
+            Ok(_) => info!(
+                "Dropping log of {:?}: no longer in block {:?}",
+                tx_hash, log.block_number
+            ),
+            Err(e) => {
+                warn!("Failed to recheck {:?}: {}", tx_hash, e);
+                ready.extend(pending.hold(log));
+            }
+        }
+    }
+    ready
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::types::H256;
+
+    fn log(block: u64, tx: u64) -> Log {
+        Log {
+            block_number: Some(block.into()),
+            transaction_hash: Some(H256::from_low_u64_be(tx)),
+            log_index: Some(0u64.into()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_logs_wait_for_depth() {
+        let mut pending = PendingLogs::new(3);
+        assert!(pending.hold(log(100, 1)).is_none());
+        assert!(pending.hold(log(101, 2)).is_none());
+        assert!(pending.hold(log(102, 3)).is_none());
+        assert!(pending.due(102).is_empty());
+        let due = pending.due(104);
+        assert_eq!(
+            due.iter()
+                .map(|l| l.block_number.unwrap().as_u64())
+                .collect::<Vec<_>>(),
+            [100, 101]
+        );
+        assert!(!pending.is_empty());
+
+        // A removed log drops its held copy.
+        let mut removed = log(102, 3);
+        removed.removed = Some(true);
+        assert!(pending.hold(removed).is_none());
+        assert!(pending.is_empty());
+
+        // Without confirmations nothing is held.
+        assert!(PendingLogs::new(0).hold(log(100, 1)).is_some());
+    }
+
+    #[test]
+    fn test_confirmed_blocks() {
+        assert_eq!(confirmed_blocks(None, 110, 10), 100..=100);
+        assert_eq!(confirmed_blocks(Some(101), 113, 10), 101..=103);
+        assert!(confirmed_blocks(Some(104), 113, 10).is_empty());
+        assert!(confirmed_blocks(None, 5, 10).is_empty());
+    }
+
+    #[test]
+    fn test_sol_commitment() {
+        assert_eq!(
+            "Finalized".parse::<SolCommitment>().unwrap(),
+            SolCommitment::Finalized
+        );
+        let err = "processed".parse::<SolCommitment>().unwrap_err();
+        assert!(
+            err.to_string().contains("confirmed or finalized"),
+            "{}",
+            err
+        );
+        assert!("fast".parse::<SolCommitment>().is_err());
+    }
+}
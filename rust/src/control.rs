@@ -0,0 +1,289 @@
+//! Runtime pause/resume switches for the chain trackers.
+//!
+//! Trackers check their chain's switch between units of work (blocks, logs,
+//! signatures), so a pause lets the block or transaction in flight finish and
+//! leaves the in-memory checkpoint pointing at the last processed height.
+//! Subscription-based trackers also drop their connection while paused, so
+//! the RPC provider can be taken down for maintenance; on resume they
+//! reconnect and backfill from the checkpoint.
+//!
+//! Shutting down (see `shutdown.rs`) stops the trackers at the same points,
+//! but they return instead of waiting for a resume.
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use tokio::sync::watch;
+use tokio::time::{sleep, Duration};
+use tokio_util::sync::CancellationToken;
+use tracing::info;
+
+/// Pause switch for one chain. Cheap to clone; all clones share state.
+#[derive(Debug, Clone)]
+pub struct ChainControl {
+    chain: &'static str,
+    paused: watch::Sender<bool>,
+    /// Whether the tracker's last RPC round trip succeeded, for `/admin/chains`.
+    connected: Arc<AtomicBool>,
+    /// Cancelled when the listener shuts down.
+    shutdown: CancellationToken,
+}
+
+impl ChainControl {
+    pub fn new(chain: &'static str) -> Self {
+        Self::with_shutdown(chain, CancellationToken::new())
+    }
+
+    /// A switch whose trackers stop for good once `shutdown` is cancelled.
+    pub fn with_shutdown(chain: &'static str, shutdown: CancellationToken) -> Self {
+        let (paused, _) = watch::channel(false);
+        ChainControl {
+            chain,
+            paused,
+            connected: Arc::new(AtomicBool::new(false)),
+            shutdown,
+        }
+    }
+
+    pub fn chain(&self) -> &'static str {
+        self.chain
+    }
+
+    pub fn is_paused(&self) -> bool {
+        *self.paused.borrow()
+    }
+
+    pub fn is_shutting_down(&self) -> bool {
+        self.shutdown.is_cancelled()
+    }
+
+    /// Whether trackers should stop after the unit of work in flight: the
+    /// chain is paused or the listener is shutting down.
+    pub fn is_stopping(&self) -> bool {
+        self.is_paused() || self.is_shutting_down()
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.connected.load(Ordering::Relaxed)
+    }
+
+    /// Record the outcome of the latest connect or RPC call.
+    pub fn set_connected(&self, connected: bool) {
+        self.connected.store(connected, Ordering::Relaxed);
+    }
+
+    /// Returns false if the chain was already paused.
+    pub fn pause(&self) -> bool {
+        let changed = self
+            .paused
+            .send_if_modified(|p| !std::mem::replace(p, true));
+        if changed {
+            info!("{} tracking paused", self.chain);
+        }
+        changed
+    }
+
+    /// Returns false if the chain was not paused.
+    pub fn resume(&self) -> bool {
+        let changed = self
+            .paused
+            .send_if_modified(|p| std::mem::replace(p, false));
+        if changed {
+            info!("{} tracking resumed", self.chain);
+        }
+        changed
+    }
+
+    /// Wait until the chain is not paused, or the listener shuts down;
+    /// callers check `is_shutting_down` next. Returns immediately if running.
+    pub async fn wait_resumed(&self) {
+        let mut rx = self.paused.subscribe();
+        // The sender lives in `self`, so the channel can't close here.
+        tokio::select! {
+            _ = rx.wait_for(|paused| !*paused) => {}
+            _ = self.shutdown.cancelled() => {}
+        }
+    }
+
+    /// Resolves once the chain is paused or the listener shuts down. Used to
+    /// interrupt idle waits on a subscription stream.
+    pub async fn stopping(&self) {
+        let mut rx = self.paused.subscribe();
+        tokio::select! {
+            _ = rx.wait_for(|paused| *paused) => {}
+            _ = self.shutdown.cancelled() => {}
+        }
+    }
+
+    /// Resolves once the listener shuts down.
+    pub async fn shutting_down(&self) {
+        self.shutdown.cancelled().await
+    }
+
+    /// Sleep between retries or polls, waking early on shutdown.
+    pub async fn sleep(&self, duration: Duration) {
+        tokio::select! {
+            _ = sleep(duration) => {}
+            _ = self.shutdown.cancelled() => {}
+        }
+    }
+}
+
+/// The switches for every chain, shared between the trackers and the admin
+/// API.
+#[derive(Debug, Clone)]
+pub struct Controls {
+    pub eth: ChainControl,
+    pub sol: ChainControl,
+    /// The `[[evm_networks]]` chains, in config order.
+    pub evm: Vec<ChainControl>,
+    /// Tron, when `[tron]` is configured.
+    pub tron: Option<ChainControl>,
+    /// The `[[substrate_networks]]` chains, in config order.
+    pub substrate: Vec<ChainControl>,
+}
+
+impl Default for Controls {
+    fn default() -> Self {
+        Controls::new(&CancellationToken::new())
+    }
+}
+
+impl Controls {
+    /// Switches whose trackers stop once `shutdown` is cancelled.
+    pub fn new(shutdown: &CancellationToken) -> Self {
+        Controls {
+            eth: ChainControl::with_shutdown("ethereum", shutdown.child_token()),
+            sol: ChainControl::with_shutdown("solana", shutdown.child_token()),
+            evm: Vec::new(),
+            tron: None,
+            substrate: Vec::new(),
+        }
+    }
+
+    /// Add the switch of an `[[evm_networks]]` chain, stopping once
+    /// `shutdown` is cancelled.
+    pub fn add_evm(&mut self, chain: &'static str, shutdown: &CancellationToken) -> ChainControl {
+        let control = ChainControl::with_shutdown(chain, shutdown.child_token());
+        self.evm.push(control.clone());
+        control
+    }
+
+    /// Add the switch of the Tron tracker, stopping once `shutdown` is
+    /// cancelled.
+    pub fn add_tron(&mut self, shutdown: &CancellationToken) -> ChainControl {
+        let control = ChainControl::with_shutdown("tron", shutdown.child_token());
+        self.tron = Some(control.clone());
+        control
+    }
+
+    /// Add the switch of a `[[substrate_networks]]` chain, stopping once
+    /// `shutdown` is cancelled.
+    pub fn add_substrate(
+        &mut self,
+        chain: &'static str,
+        shutdown: &CancellationToken,
+    ) -> ChainControl {
+        let control = ChainControl::with_shutdown(chain, shutdown.child_token());
+        self.substrate.push(control.clone());
+        control
+    }
+
+    /// Every switch: Ethereum, Solana, the extra EVM chains, Tron, then the
+    /// Substrate chains.
+    pub fn all(&self) -> impl Iterator<Item = &ChainControl> {
+        [&self.eth, &self.sol]
+            .into_iter()
+            .chain(&self.evm)
+            .chain(&self.tron)
+            .chain(&self.substrate)
+    }
+
+    /// Look up a chain by its event `chain` name.
+    pub fn get(&self, chain: &str) -> Option<&ChainControl> {
+        self.all().find(|c| c.chain == chain)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::time::{timeout, Duration};
+
+    #[test]
+    fn test_pause_and_resume_are_idempotent() {
+        let control = ChainControl::new("ethereum");
+        assert!(!control.is_paused());
+        assert!(control.pause());
+        assert!(!control.pause());
+        assert!(control.is_paused());
+        assert!(control.resume());
+        assert!(!control.resume());
+    }
+
+    #[test]
+    fn test_connected_flag_is_shared_between_clones() {
+        let control = ChainControl::new("ethereum");
+        assert!(!control.is_connected());
+        control.clone().set_connected(true);
+        assert!(control.is_connected());
+    }
+
+    #[tokio::test]
+    async fn test_wait_resumed_blocks_while_paused() {
+        let control = ChainControl::new("solana");
+        control.pause();
+
+        let waiter = {
+            let control = control.clone();
+            tokio::spawn(async move { control.wait_resumed().await })
+        };
+        assert!(timeout(Duration::from_millis(50), control.wait_resumed())
+            .await
+            .is_err());
+
+        control.resume();
+        timeout(Duration::from_secs(1), waiter)
+            .await
+            .expect("resume should wake waiters")
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_stops_paused_and_running_chains() {
+        let shutdown = CancellationToken::new();
+        let controls = Controls::new(&shutdown);
+        controls.eth.pause();
+        assert!(controls.eth.is_stopping());
+        assert!(!controls.sol.is_stopping());
+
+        let waiter = {
+            let control = controls.eth.clone();
+            tokio::spawn(async move { control.wait_resumed().await })
+        };
+        shutdown.cancel();
+        timeout(Duration::from_secs(1), waiter)
+            .await
+            .expect("shutdown should wake waiters")
+            .unwrap();
+        assert!(controls.sol.is_shutting_down());
+        assert!(controls.sol.is_stopping());
+        timeout(Duration::from_secs(1), controls.sol.stopping())
+            .await
+            .unwrap();
+        // Shutting down isn't a pause: the admin API still reports it as is.
+        assert!(!controls.sol.is_paused());
+    }
+
+    #[test]
+    fn test_lookup_by_chain_name() {
+        let shutdown = CancellationToken::new();
+        let mut controls = Controls::new(&shutdown);
+        let polygon = controls.add_evm("polygon", &shutdown);
+        assert_eq!(controls.get("solana").unwrap().chain(), "solana");
+        assert!(controls.get("bitcoin").is_none());
+        polygon.pause();
+        assert!(controls.get("polygon").unwrap().is_paused());
+        shutdown.cancel();
+        assert!(polygon.is_shutting_down());
+    }
+}
@@ -0,0 +1,330 @@
+//! Pre-aggregated series for the bundled Grafana dashboard
+//! (`infra/grafana/tracker-dashboard.json`), served at
+//! `GET /admin/dashboard`.
+//!
+//! The aggregator follows the event bus, so it sees every published event
+//! and nothing the destinations dropped or filtered. It keeps three series in
+//! memory, each a list of rows with an RFC 3339 `time` (the start of the
+//! bucket) and its labels, the long format Grafana's Infinity data source
+//! groups into one line per label set:
+//!
+//! - `events_per_minute`: events published per chain, for the last hour.
+//!   Minutes without events are reported as 0 for every chain seen.
+//! - `value_per_hour`: value transferred per chain and asset, in whole
+//!   units, for the last day. Only `transfer`, `erc20_transfer` and
+//!   `solana_tx` events with a value count, and spam doesn't; the asset is
+//!   the `logical_asset` if the event has one, else its `asset_symbol`.
+//! - `lag`: seconds from the block's timestamp to publishing, averaged and
+//!   maximal per chain and minute, for the last hour.
+//!
+//! The series restart empty with the listener.
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::{Arc, Mutex as StdMutex};
+
+use chrono::{DateTime, Utc};
+use ethers::types::U256;
+use serde::Serialize;
+
+use crate::amount::Amount;
+use crate::bus::BusSubscriber;
+use crate::event_type::EventType;
+use crate::Event;
+
+/// Minutes of `events_per_minute` and `lag` kept.
+pub const MINUTES: i64 = 60;
+/// Hours of `value_per_hour` kept.
+pub const HOURS: i64 = 24;
+
+/// Counts of one chain in one minute.
+#[derive(Debug, Default, Clone, Copy)]
+struct Minute {
+    events: u64,
+    lag_total: u64,
+    lag_samples: u64,
+    lag_max: u64,
+}
+
+#[derive(Debug, Default)]
+struct Series {
+    /// By minute start (Unix seconds) and chain.
+    minutes: BTreeMap<(i64, String), Minute>,
+    /// Raw amounts by hour start, chain, asset and decimals.
+    hours: BTreeMap<(i64, String, String, u8), U256>,
+}
+
+/// A row of `events_per_minute`.
+#[derive(Debug, Serialize, PartialEq)]
+pub struct EventsRow {
+    pub time: String,
+    pub chain: String,
+    pub events: u64,
+}
+
+/// A row of `value_per_hour`.
+#[derive(Debug, Serialize, PartialEq)]
+pub struct ValueRow {
+    pub time: String,
+    pub chain: String,
+    pub asset: String,
+    pub value: f64,
+}
+
+/// A row of `lag`.
+#[derive(Debug, Serialize, PartialEq)]
+pub struct LagRow {
+    pub time: String,
+    pub chain: String,
+    pub avg_secs: f64,
+    pub max_secs: u64,
+}
+
+/// `GET /admin/dashboard` response.
+#[derive(Debug, Serialize)]
+pub struct DashboardData {
+    pub events_per_minute: Vec<EventsRow>,
+    pub value_per_hour: Vec<ValueRow>,
+    pub lag: Vec<LagRow>,
+}
+
+/// The aggregated series. Cheap to clone; all clones share them.
+#[derive(Debug, Clone, Default)]
+pub struct Dashboard {
+    series: Arc<StdMutex<Series>>,
+}
+
+impl Dashboard {
+    /// Aggregate the events `events` receives from now on.
+    pub fn start(mut events: BusSubscriber) -> Self {
+        let dashboard = Dashboard::default();
+        let series = dashboard.clone();
+        tokio::spawn(async move {
+            while let Some(published) = events.recv().await {
+                series.record(&published.event, Utc::now().timestamp());
+            }
+        });
+        dashboard
+    }
+
+    /// Count `event`, published at `now` (Unix seconds).
+    pub fn record(&self, event: &Event, now: i64) {
+        let minute = now - now.rem_euclid(60);
+        let hour = now - now.rem_euclid(3600);
+        let mut series = self.series.lock().unwrap();
+        let counts = series
+            .minutes
+            .entry((minute, event.chain.clone()))
+            .or_default();
+        counts.events += 1;
+        if let Ok(timestamp) = event.timestamp.parse::<i64>() {
+            let lag = (now - timestamp).max(0) as u64;
+            counts.lag_total += lag;
+            counts.lag_samples += 1;
+            counts.lag_max = counts.lag_max.max(lag);
+        }
+        if let Some((asset, decimals, raw)) = transferred(event) {
+            *series
+                .hours
+                .entry((hour, event.chain.clone(), asset, decimals))
+                .or_default() += raw;
+        }
+        series
+            .minutes
+            .retain(|(at, _), _| *at > minute - MINUTES * 60);
+        series.hours.retain(|(at, ..), _| *at > hour - HOURS * 3600);
+    }
+
+    /// The series as of `now` (Unix seconds).
+    pub fn data(&self, now: i64) -> DashboardData {
+        let minute = now - now.rem_euclid(60);
+        let series = self.series.lock().unwrap();
+        let chains: BTreeSet<&String> = series.minutes.keys().map(|(_, chain)| chain).collect();
+        let mut events_per_minute = Vec::new();
+        let mut lag = Vec::new();
+        for at in (0..MINUTES).rev().map(|i| minute - i * 60) {
+            for chain in &chains {
+                let counts = series
+                    .minutes
+                    .get(&(at, chain.to_string()))
+                    .copied()
+                    .unwrap_or_default();
+                events_per_minute.push(EventsRow {
+                    time: rfc3339(at),
+                    chain: chain.to_string(),
+                    events: counts.events,
+                });
+                if counts.lag_samples > 0 {
+                    lag.push(LagRow {
+                        time: rfc3339(at),
+                        chain: chain.to_string(),
+                        avg_secs: counts.lag_total as f64 / counts.lag_samples as f64,
+                        max_secs: counts.lag_max,
+                    });
+                }
+            }
+        }
+        // Amounts of one asset with different decimals are added up scaled.
+        let mut values: BTreeMap<(i64, &String, &String), f64> = BTreeMap::new();
+        for ((at, chain, asset, decimals), raw) in &series.hours {
+            let value = Amount::new(*raw, *decimals)
+                .formatted()
+                .parse::<f64>()
+                .unwrap_or_default();
+            *values.entry((*at, chain, asset)).or_default() += value;
+        }
+        let value_per_hour = values
+            .into_iter()
+            .map(|((at, chain, asset), value)| ValueRow {
+                time: rfc3339(at),
+                chain: chain.clone(),
+                asset: asset.clone(),
+                value,
+            })
+            .collect();
+        DashboardData {
+            events_per_minute,
+            value_per_hour,
+            lag,
+        }
+    }
+}
+
+/// Asset, decimals and raw amount `event` transferred, if it counts towards
+/// `value_per_hour`.
+fn transferred(event: &Event) -> Option<(String, u8, U256)> {
+    let transfer = matches!(
+        event.event_type,
+        EventType::Transfer | EventType::Erc20Transfer | EventType::SolanaTx
+    );
+    if !transfer || event.spam {
+        return None;
+    }
+    let asset = event
+        .logical_asset
+        .clone()
+        .or_else(|| event.asset_symbol.clone())?;
+    let decimals = event.asset_decimals?;
+    let raw = Amount::parse(&event.value, 0)?.raw();
+    (!raw.is_zero()).then_some((asset, decimals, raw))
+}
+
+fn rfc3339(at: i64) -> String {
+    DateTime::<Utc>::from_timestamp(at, 0)
+        .unwrap_or_default()
+        .to_rfc3339()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: i64 = 1_700_000_000;
+
+    fn event(chain: &str, event_type: EventType, value: &str, timestamp: i64) -> Event {
+        Event {
+            chain: chain.into(),
+            event_type,
+            value: value.into(),
+            timestamp: timestamp.to_string(),
+            asset_symbol: Some("USDC".into()),
+            asset_decimals: Some(6),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_events_per_minute_and_lag() {
+        let dashboard = Dashboard::default();
+        dashboard.record(&event("ethereum", EventType::Transfer, "1", NOW - 12), NOW);
+        dashboard.record(&event("ethereum", EventType::Transfer, "1", NOW - 4), NOW);
+        dashboard.record(
+            &event("solana", EventType::SolanaTx, "1", NOW - 2),
+            NOW - 60,
+        );
+
+        let data = dashboard.data(NOW);
+        // A row per minute of the last hour for each chain seen.
+        assert_eq!(data.events_per_minute.len(), 2 * MINUTES as usize);
+        let last = &data.events_per_minute[data.events_per_minute.len() - 2..];
+        assert_eq!(last[0].chain, "ethereum");
+        assert_eq!(last[0].events, 2);
+        assert_eq!(last[1].events, 0);
+        assert_eq!(last[0].time, "2023-11-14T22:13:00+00:00");
+
+        assert_eq!(
+            data.lag,
+            [
+                LagRow {
+                    time: "2023-11-14T22:12:00+00:00".into(),
+                    chain: "solana".into(),
+                    avg_secs: 0.0,
+                    max_secs: 0,
+                },
+                LagRow {
+                    time: "2023-11-14T22:13:00+00:00".into(),
+                    chain: "ethereum".into(),
+                    avg_secs: 8.0,
+                    max_secs: 12,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_value_per_hour() {
+        let dashboard = Dashboard::default();
+        dashboard.record(
+            &event("ethereum", EventType::Erc20Transfer, "1500000", NOW),
+            NOW,
+        );
+        let mut bsc = event("bsc", EventType::Erc20Transfer, "2000000000000000000", NOW);
+        bsc.asset_decimals = Some(18);
+        bsc.logical_asset = Some("USD Coin".into());
+        dashboard.record(&bsc, NOW);
+        let mut spam = event("ethereum", EventType::Erc20Transfer, "7000000", NOW);
+        spam.spam = true;
+        dashboard.record(&spam, NOW);
+        dashboard.record(
+            &event("ethereum", EventType::SweepDetected, "9000000", NOW),
+            NOW,
+        );
+        dashboard.record(
+            &event("ethereum", EventType::Transfer, "2500000", NOW),
+            NOW + 60,
+        );
+
+        let data = dashboard.data(NOW + 60);
+        assert_eq!(
+            data.value_per_hour,
+            [
+                ValueRow {
+                    time: "2023-11-14T22:00:00+00:00".into(),
+                    chain: "bsc".into(),
+                    asset: "USD Coin".into(),
+                    value: 2.0,
+                },
+                ValueRow {
+                    time: "2023-11-14T22:00:00+00:00".into(),
+                    chain: "ethereum".into(),
+                    asset: "USDC".into(),
+                    value: 4.0,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_old_buckets_are_dropped() {
+        let dashboard = Dashboard::default();
+        dashboard.record(&event("ethereum", EventType::Transfer, "1", NOW), NOW);
+        let later = NOW + HOURS * 3600 + 3600;
+        dashboard.record(&event("solana", EventType::SolanaTx, "1", later), later);
+
+        let data = dashboard.data(later);
+        assert!(data
+            .events_per_minute
+            .iter()
+            .all(|row| row.chain == "solana"));
+        assert_eq!(data.value_per_hour.len(), 1);
+        assert_eq!(data.value_per_hour[0].chain, "solana");
+    }
+}
@@ -0,0 +1,237 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use anyhow::bail;
+
+/// How event ids, and so dedup keys, are derived (`DEDUP_KEY`).
+///
+/// The id formats of each strategy are part of the payload contract: they
+/// only change in a major release, so consumers may store and join on them.
+/// Switching strategy changes the ids of events published afterwards only;
+/// an event seen just before the switch may be published again under its
+/// new id.
+///
+/// The formats below are Ethereum's; other EVM chains use their chain name
+/// instead of `eth` (see [`evm_prefix`]).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DedupKey {
+    /// One event per transaction: `eth:{tx}`, `sol:{signature}`. ERC-20
+    /// transfers after the first one in a transaction are dropped.
+    Tx,
+    /// One event per transfer: `eth:{tx}` for native transfers,
+    /// `eth:{tx}:log{index}` for ERC-20 logs, `sol:{signature}`.
+    #[default]
+    Log,
+    /// Like `log`, suffixed with the watched address the event was matched
+    /// for (`eth:{tx}:log{index}:{address}`), so a Solana transaction touching
+    /// several watched addresses is published once per address.
+    Address,
+}
+
+/// Id prefix of an EVM chain's events: `eth` on Ethereum, and the chain
+/// name (`polygon`, `base`, ...) on `[[evm_networks]]` chains, so the same
+/// transaction hash on two chains gets two ids.
+pub fn evm_prefix(chain: &str) -> &str {
+    match chain {
+        "ethereum" => "eth",
+        other => other,
+    }
+}
+
+impl FromStr for DedupKey {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "tx" => Ok(DedupKey::Tx),
+            "log" => Ok(DedupKey::Log),
+            "address" => Ok(DedupKey::Address),
+            other => bail!(
+                "unknown DEDUP_KEY '{}' (expected tx, log or address)",
+                other
+            ),
+        }
+    }
+}
+
+impl DedupKey {
+    /// Event id for a transaction `tx` on the chain with id prefix `chain`
+    /// (`eth`, `sol`). `log_index` is set for ERC-20 log events; `watched` is
+    /// the watched address the event is attributed to.
+    pub fn event_id(self, chain: &str, tx: &str, log_index: Option<u64>, watched: &str) -> String {
+        let mut id = format!("{}:{}", chain, tx);
+        if let (Some(index), DedupKey::Log | DedupKey::Address) = (log_index, self) {
+            write!(id, ":log{}", index).unwrap();
+        }
+        if self == DedupKey::Address {
+            write!(id, ":{}", watched).unwrap();
+        }
+        id
+    }
+}
+
+/// Estimated heap bytes of a dedup entry besides its id: the timestamp, the
+/// id's `String` header and the hash table's per-slot overhead.
+const ENTRY_OVERHEAD: usize = 64;
+
+/// In-memory record of published event ids.
+///
+/// Each id remembers when it was inserted so the maintenance scheduler can
+/// compact entries older than the configured retention window instead of
+/// letting the set grow for the lifetime of the process.
+#[derive(Debug, Default)]
+pub struct DedupSet {
+    seen: HashMap<String, Instant>,
+    key: DedupKey,
+}
+
+impl DedupSet {
+    pub fn new(key: DedupKey) -> Self {
+        DedupSet {
+            seen: HashMap::new(),
+            key,
+        }
+    }
+
+    /// The strategy trackers use to build event ids checked against this set.
+    pub fn key(&self) -> DedupKey {
+        self.key
+    }
+
+    pub fn contains(&self, event_id: &str) -> bool {
+        self.seen.contains_key(event_id)
+    }
+
+    /// Record an event id. Returns false if it was already present.
+    pub fn insert(&mut self, event_id: String) -> bool {
+        self.seen.insert(event_id, Instant::now()).is_none()
+    }
+
+    pub fn len(&self) -> usize {
+        self.seen.len()
+    }
+
+    /// Drop ids inserted more than `retention` ago and return how many were
+    /// removed.
+    pub fn compact(&mut self, retention: Duration) -> usize {
+        let before = self.seen.len();
+        let now = Instant::now();
+        self.seen
+            .retain(|_, inserted| now.duration_since(*inserted) < retention);
+        self.seen.shrink_to_fit();
+        before - self.seen.len()
+    }
+
+    /// Estimated memory held by the set, in bytes.
+    pub fn estimated_bytes(&self) -> usize {
+        self.seen.keys().map(|id| id.len() + ENTRY_OVERHEAD).sum()
+    }
+
+    /// Drop the oldest ids until the set fits in `max_bytes` and return how
+    /// many were removed. An evicted id is published again if it is seen
+    /// again.
+    pub fn evict_to(&mut self, max_bytes: usize) -> usize {
+        let mut excess = self.estimated_bytes().saturating_sub(max_bytes);
+        if excess == 0 {
+            return 0;
+        }
+        let mut by_age: Vec<(Instant, &String)> =
+            self.seen.iter().map(|(id, at)| (*at, id)).collect();
+        by_age.sort();
+        let mut evicted = Vec::new();
+        for (_, id) in by_age {
+            if excess == 0 {
+                break;
+            }
+            excess = excess.saturating_sub(id.len() + ENTRY_OVERHEAD);
+            evicted.push(id.clone());
+        }
+        for id in &evicted {
+            self.seen.remove(id);
+        }
+        self.seen.shrink_to_fit();
+        evicted.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_insert_and_contains() {
+        let mut set = DedupSet::default();
+        assert!(set.insert("eth:0x01".to_string()));
+        assert!(!set.insert("eth:0x01".to_string()));
+        assert!(set.contains("eth:0x01"));
+        assert!(!set.contains("eth:0x02"));
+        assert_eq!(set.len(), 1);
+    }
+
+    #[test]
+    fn test_compact_respects_retention() {
+        let mut set = DedupSet::default();
+        set.insert("sol:a".to_string());
+        set.insert("sol:b".to_string());
+
+        assert_eq!(set.compact(Duration::from_secs(3600)), 0);
+        assert_eq!(set.len(), 2);
+
+        assert_eq!(set.compact(Duration::ZERO), 2);
+        assert!(!set.contains("sol:a"));
+    }
+
+    #[test]
+    fn test_evict_to_drops_oldest_first() {
+        let mut set = DedupSet::default();
+        for id in ["eth:0x01", "eth:0x02", "eth:0x03"] {
+            set.insert(id.to_string());
+            std::thread::sleep(Duration::from_millis(2));
+        }
+        let entry = "eth:0x01".len() + ENTRY_OVERHEAD;
+        assert_eq!(set.estimated_bytes(), 3 * entry);
+
+        assert_eq!(set.evict_to(3 * entry), 0);
+        assert_eq!(set.evict_to(2 * entry - 1), 2);
+        assert!(set.contains("eth:0x03"));
+        assert!(!set.contains("eth:0x01"));
+        assert_eq!(set.len(), 1);
+    }
+
+    #[test]
+    fn test_default_key_formats_are_unchanged() {
+        let key = DedupSet::default().key();
+        assert_eq!(key, DedupKey::Log);
+        assert_eq!(key.event_id("eth", "0xab", None, "0x01"), "eth:0xab");
+        assert_eq!(
+            key.event_id("eth", "0xab", Some(3), "0x01"),
+            "eth:0xab:log3"
+        );
+        assert_eq!(key.event_id("sol", "5wLk", None, "Abc"), "sol:5wLk");
+        assert_eq!(
+            key.event_id(evm_prefix("ethereum"), "0xab", None, "0x01"),
+            "eth:0xab"
+        );
+        assert_eq!(
+            key.event_id(evm_prefix("polygon"), "0xab", Some(3), "0x01"),
+            "polygon:0xab:log3"
+        );
+    }
+
+    #[test]
+    fn test_tx_and_address_keys() {
+        let tx: DedupKey = "tx".parse().unwrap();
+        assert_eq!(tx.event_id("eth", "0xab", Some(3), "0x01"), "eth:0xab");
+
+        let address: DedupKey = " Address ".parse().unwrap();
+        assert_eq!(
+            address.event_id("eth", "0xab", Some(3), "0x01"),
+            "eth:0xab:log3:0x01"
+        );
+        assert_eq!(address.event_id("sol", "5wLk", None, "Abc"), "sol:5wLk:Abc");
+
+        assert!("block".parse::<DedupKey>().is_err());
+    }
+}
@@ -0,0 +1,287 @@
+//! Durable record of published event ids (`DEDUP_STORE`).
+//!
+//! The trackers' in-memory [`DedupSet`](crate::dedup::DedupSet) is fast but
+//! starts empty after every restart, so events seen just before a crash are
+//! published again. With a store configured, the publisher also claims each
+//! event id there before publishing and skips ids that are already claimed,
+//! for `DEDUP_RETENTION_SECS`:
+//!
+//! - `memory` (default): no store, the in-memory set only.
+//! - `redis`: `SET NX` with a TTL on `REDIS_URL` (see
+//!   [`crate::distributed_dedup`]); shared by the replicas of a region.
+//!   `DISTRIBUTED_DEDUP=true` selects it too.
+//! - `file`: an append-only log in `DEDUP_STORE_FILE`, for a single instance
+//!   without Redis persistence. Compacted at startup and as it grows.
+//!
+//! A store that can't be reached fails open: the event is published
+//! unclaimed, since a duplicate is recoverable downstream and a lost event
+//! is not.
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex as StdMutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::{anyhow, Context};
+use async_trait::async_trait;
+use tracing::info;
+
+use crate::config::Config;
+use crate::distributed_dedup::DistributedDedup;
+
+/// Which [`DedupStore`] the publisher claims event ids in.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DedupStoreKind {
+    #[default]
+    Memory,
+    Redis,
+    File,
+}
+
+impl FromStr for DedupStoreKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "memory" => Ok(DedupStoreKind::Memory),
+            "redis" => Ok(DedupStoreKind::Redis),
+            "file" => Ok(DedupStoreKind::File),
+            other => Err(anyhow!(
+                "invalid DEDUP_STORE '{}' (expected memory, redis or file)",
+                other
+            )),
+        }
+    }
+}
+
+/// Event ids claimed for publishing, kept for the retention window.
+#[async_trait]
+pub trait DedupStore: Send + Sync + 'static {
+    /// Claim `event_id`. Returns false if it is already claimed.
+    async fn claim(&self, event_id: &str) -> anyhow::Result<bool>;
+
+    /// Give up a claim after failing to publish.
+    async fn release(&self, event_id: &str) -> anyhow::Result<()>;
+
+    /// Where claims go, for logs.
+    fn describe(&self) -> String;
+}
+
+/// Open the store `cfg` selects; `None` for `memory`.
+pub fn open(cfg: &Config) -> anyhow::Result<Option<Arc<dyn DedupStore>>> {
+    let retention = Duration::from_secs(cfg.dedup_retention_secs);
+    let store: Arc<dyn DedupStore> = match cfg.dedup_store {
+        DedupStoreKind::Memory => return Ok(None),
+        DedupStoreKind::Redis => Arc::new(DistributedDedup::new(
+            redis::Client::open(cfg.redis_url.as_str())?,
+            &claim_prefix(cfg),
+            retention,
+            &cfg.instance_id,
+        )),
+        DedupStoreKind::File => Arc::new(FileDedupStore::open(
+            cfg.dedup_store_file.clone(),
+            retention,
+        )?),
+    };
+    info!("Dedup store: {}", store.describe());
+    Ok(Some(store))
+}
+
+/// Redis claim prefix, scoped to `REGION` when set: regions don't see each
+/// other's claims, so each one publishes every event and consumers pick a
+/// region.
+fn claim_prefix(cfg: &Config) -> String {
+    match &cfg.region {
+        Some(region) => format!("{}{}:", cfg.distributed_dedup_prefix, region),
+        None => cfg.distributed_dedup_prefix.clone(),
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+/// Log lines per live entry that trigger a compaction.
+const COMPACT_RATIO: usize = 2;
+
+#[derive(Debug)]
+struct Log {
+    /// Claim time per event id, in unix seconds.
+    claimed: HashMap<String, u64>,
+    file: File,
+    /// Lines in the file, including superseded ones.
+    lines: usize,
+}
+
+/// Claims in an append-only file: `<unix secs> <event id>` per claim and
+/// `- <event id>` per release.
+#[derive(Debug)]
+pub struct FileDedupStore {
+    path: PathBuf,
+    retention: Duration,
+    log: StdMutex<Log>,
+}
+
+impl FileDedupStore {
+    /// Load the claims of `path` that are still within `retention`, creating
+    /// the file if needed.
+    pub fn open(path: PathBuf, retention: Duration) -> anyhow::Result<Self> {
+        let mut claimed = HashMap::new();
+        match std::fs::read_to_string(&path) {
+            Ok(raw) => {
+                for line in raw.lines() {
+                    match line.split_once(' ') {
+                        Some(("-", id)) => {
+                            claimed.remove(id);
+                        }
+                        Some((at, id)) => {
+                            if let Ok(at) = at.parse::<u64>() {
+                                claimed.insert(id.to_string(), at);
+                            }
+                        }
+                        None => {}
+                    }
+                }
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e).with_context(|| format!("failed to read {}", path.display())),
+        }
+        let cutoff = unix_now().saturating_sub(retention.as_secs());
+        claimed.retain(|_, at| *at > cutoff);
+        let file = rewrite(&path, &claimed)?;
+        Ok(FileDedupStore {
+            path,
+            retention,
+            log: StdMutex::new(Log {
+                lines: claimed.len(),
+                claimed,
+                file,
+            }),
+        })
+    }
+
+    fn append(&self, log: &mut Log, line: String) -> anyhow::Result<()> {
+        writeln!(log.file, "{}", line)
+            .with_context(|| format!("failed to write {}", self.path.display()))?;
+        log.lines += 1;
+        if log.lines > COMPACT_RATIO * log.claimed.len() + 1_000 {
+            let cutoff = unix_now().saturating_sub(self.retention.as_secs());
+            log.claimed.retain(|_, at| *at > cutoff);
+            log.file = rewrite(&self.path, &log.claimed)?;
+            log.lines = log.claimed.len();
+        }
+        Ok(())
+    }
+}
+
+/// Replace `path` with one line per claim and reopen it for appending.
+fn rewrite(path: &Path, claimed: &HashMap<String, u64>) -> anyhow::Result<File> {
+    let tmp = path.with_extension("tmp");
+    let mut out = String::new();
+    for (id, at) in claimed {
+        out.push_str(&format!("{} {}\n", at, id));
+    }
+    std::fs::write(&tmp, out)
+        .and_then(|()| std::fs::rename(&tmp, path))
+        .with_context(|| format!("failed to write {}", path.display()))?;
+    OpenOptions::new()
+        .append(true)
+        .open(path)
+        .with_context(|| format!("failed to open {}", path.display()))
+}
+
+#[async_trait]
+impl DedupStore for FileDedupStore {
+    async fn claim(&self, event_id: &str) -> anyhow::Result<bool> {
+        let now = unix_now();
+        let mut log = self.log.lock().unwrap();
+        if let Some(at) = log.claimed.get(event_id) {
+            if now.saturating_sub(*at) < self.retention.as_secs() {
+                return Ok(false);
+            }
+        }
+        log.claimed.insert(event_id.to_string(), now);
+        self.append(&mut log, format!("{} {}", now, event_id))?;
+        Ok(true)
+    }
+
+    async fn release(&self, event_id: &str) -> anyhow::Result<()> {
+        let mut log = self.log.lock().unwrap();
+        if log.claimed.remove(event_id).is_some() {
+            self.append(&mut log, format!("- {}", event_id))?;
+        }
+        Ok(())
+    }
+
+    fn describe(&self) -> String {
+        format!("file {}", self.path.display())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn path(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(name);
+        std::fs::remove_file(&path).ok();
+        path
+    }
+
+    #[test]
+    fn test_parse_kind() {
+        assert_eq!(
+            "Redis".parse::<DedupStoreKind>().unwrap(),
+            DedupStoreKind::Redis
+        );
+        assert_eq!(
+            "file".parse::<DedupStoreKind>().unwrap(),
+            DedupStoreKind::File
+        );
+        assert!("sled".parse::<DedupStoreKind>().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_file_store_survives_reopen() {
+        let path = path("tracker_dedup_store_test.log");
+        let retention = Duration::from_secs(3600);
+        let store = FileDedupStore::open(path.clone(), retention).unwrap();
+        assert!(store.claim("eth:0xab").await.unwrap());
+        assert!(!store.claim("eth:0xab").await.unwrap());
+        assert!(store.claim("eth:0xcd").await.unwrap());
+        store.release("eth:0xcd").await.unwrap();
+        drop(store);
+
+        let store = FileDedupStore::open(path.clone(), retention).unwrap();
+        let claimed_ab = store.claim("eth:0xab").await.unwrap();
+        let claimed_cd = store.claim("eth:0xcd").await.unwrap();
+        std::fs::remove_file(&path).ok();
+        assert!(!claimed_ab);
+        assert!(claimed_cd);
+    }
+
+    #[tokio::test]
+    async fn test_file_store_forgets_expired_claims() {
+        let path = path("tracker_dedup_store_expiry_test.log");
+        let old = unix_now() - 7200;
+        std::fs::write(
+            &path,
+            format!("{} eth:0xold\n{} eth:0xnew\n", old, unix_now()),
+        )
+        .unwrap();
+
+        let store = FileDedupStore::open(path.clone(), Duration::from_secs(3600)).unwrap();
+        let rewritten = std::fs::read_to_string(&path).unwrap();
+        let claimed_old = store.claim("eth:0xold").await.unwrap();
+        let claimed_new = store.claim("eth:0xnew").await.unwrap();
+        std::fs::remove_file(&path).ok();
+        assert!(!rewritten.contains("eth:0xold"));
+        assert!(claimed_old);
+        assert!(!claimed_new);
+    }
+}
@@ -0,0 +1,137 @@
+//! Cross-replica deduplication through Redis (`DEDUP_STORE=redis`, or
+//! `DISTRIBUTED_DEDUP`).
+//!
+//! Replicas running hot-hot see the same chain activity and would each
+//! publish every event. Before publishing, the publisher claims the event id
+//! with `SET <prefix><event_id> <instance> NX PX <ttl>`; only the replica
+//! whose claim succeeds publishes. The TTL is `DEDUP_RETENTION_SECS`, so the
+//! shared window matches the in-memory one.
+//!
+//! If Redis can't be reached the event is published anyway: a duplicate is
+//! recoverable downstream via `event_id`, a lost event is not. A claim is
+//! released again if the event can't be queued, so another replica can pick
+//! it up; once queued, a delivery that fails after retries is not retried by
+//! the other replicas.
+//!
+//! With `REGION` set the prefix gets a `<region>:` suffix, so replicas
+//! dedupe within their region while every region of an active-active
+//! deployment still publishes once.
+use std::time::Duration;
+
+use async_trait::async_trait;
+use redis::aio::MultiplexedConnection;
+use tokio::sync::Mutex;
+
+use crate::dedup_store::DedupStore;
+
+/// Releases a claim only if this instance still holds it.
+const RELEASE_SCRIPT: &str = r#"
+if redis.call("GET", KEYS[1]) == ARGV[1] then
+    return redis.call("DEL", KEYS[1])
+end
+return 0
+"#;
+
+pub struct DistributedDedup {
+    client: redis::Client,
+    connection: Mutex<Option<MultiplexedConnection>>,
+    prefix: String,
+    ttl: Duration,
+    /// Claim owner, so a replica can only release its own claims.
+    instance: String,
+}
+
+impl DistributedDedup {
+    pub fn new(client: redis::Client, prefix: &str, ttl: Duration, instance_id: &str) -> Self {
+        DistributedDedup {
+            client,
+            connection: Mutex::new(None),
+            prefix: prefix.to_string(),
+            ttl,
+            instance: format!("{}-{:016x}", instance_id, rand::random::<u64>()),
+        }
+    }
+
+    fn key(&self, event_id: &str) -> String {
+        format!("{}{}", self.prefix, event_id)
+    }
+
+    /// Reuse one multiplexed connection, reconnecting after an error.
+    async fn connection(&self) -> anyhow::Result<MultiplexedConnection> {
+        let mut slot = self.connection.lock().await;
+        if let Some(con) = slot.as_ref() {
+            return Ok(con.clone());
+        }
+        let con = self.client.get_multiplexed_async_connection().await?;
+        *slot = Some(con.clone());
+        Ok(con)
+    }
+
+    async fn run<T: redis::FromRedisValue>(&self, cmd: &redis::Cmd) -> anyhow::Result<T> {
+        let mut con = self.connection().await?;
+        match cmd.query_async(&mut con).await {
+            Ok(v) => Ok(v),
+            Err(e) => {
+                *self.connection.lock().await = None;
+                Err(e.into())
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl DedupStore for DistributedDedup {
+    /// Try to claim `event_id` for this replica. Returns false if another
+    /// replica (or an earlier publish of ours) already holds it.
+    async fn claim(&self, event_id: &str) -> anyhow::Result<bool> {
+        let mut cmd = redis::cmd("SET");
+        cmd.arg(self.key(event_id))
+            .arg(&self.instance)
+            .arg("NX")
+            .arg("PX")
+            .arg(self.ttl.as_millis().max(1) as u64);
+        let reply: Option<String> = self.run(&cmd).await?;
+        Ok(reply.is_some())
+    }
+
+    /// Give up a claim after failing to publish, if we still hold it.
+    async fn release(&self, event_id: &str) -> anyhow::Result<()> {
+        let mut cmd = redis::cmd("EVAL");
+        cmd.arg(RELEASE_SCRIPT)
+            .arg(1)
+            .arg(self.key(event_id))
+            .arg(&self.instance);
+        let _: i64 = self.run(&cmd).await?;
+        Ok(())
+    }
+
+    fn describe(&self) -> String {
+        format!("redis, key prefix {}", self.prefix)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dedup() -> DistributedDedup {
+        let client = redis::Client::open("redis://127.0.0.1:1").unwrap();
+        DistributedDedup::new(client, "tracker:dedup:", Duration::from_secs(60), "tracker")
+    }
+
+    #[test]
+    fn test_keys_are_prefixed_event_ids() {
+        assert_eq!(dedup().key("eth:0xab:log3"), "tracker:dedup:eth:0xab:log3");
+    }
+
+    #[test]
+    fn test_instances_are_distinct() {
+        assert_ne!(dedup().instance, dedup().instance);
+    }
+
+    #[tokio::test]
+    async fn test_unreachable_redis_is_an_error() {
+        // Nothing listens on port 1; callers treat this as "publish anyway".
+        assert!(dedup().claim("eth:0xab").await.is_err());
+    }
+}
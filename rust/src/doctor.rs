@@ -0,0 +1,367 @@
+//! Pre-deployment self-test (`tracker doctor`).
+//!
+//! Runs a series of independent checks against the configured endpoints and
+//! prints a pass/fail report. Every check is bounded by a timeout so a
+//! black-holed endpoint cannot hang the command.
+use std::fmt;
+use std::future::Future;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use ethers::providers::{Http, Ipc, Middleware, Provider, Ws};
+use ethers::types::{Address, BlockNumber, Filter};
+use solana_client::rpc_client::RpcClient;
+use solana_sdk::pubkey::Pubkey;
+use tokio::time::{timeout, Duration};
+
+use crate::config::Config;
+
+const CHECK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Outcome of a single doctor check.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CheckResult {
+    pub name: String,
+    pub ok: bool,
+    pub detail: String,
+}
+
+/// Collected results of all checks, rendered as a table by `Display`.
+#[derive(Debug, Default)]
+pub struct Report {
+    pub checks: Vec<CheckResult>,
+}
+
+impl Report {
+    fn pass(&mut self, name: impl Into<String>, detail: impl Into<String>) {
+        self.checks.push(CheckResult {
+            name: name.into(),
+            ok: true,
+            detail: detail.into(),
+        });
+    }
+
+    fn fail(&mut self, name: impl Into<String>, detail: impl Into<String>) {
+        self.checks.push(CheckResult {
+            name: name.into(),
+            ok: false,
+            detail: detail.into(),
+        });
+    }
+
+    /// True when every check succeeded.
+    pub fn passed(&self) -> bool {
+        self.checks.iter().all(|c| c.ok)
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for c in &self.checks {
+            let status = if c.ok { "PASS" } else { "FAIL" };
+            writeln!(f, "[{}] {}: {}", status, c.name, c.detail)?;
+        }
+        let failed = self.checks.iter().filter(|c| !c.ok).count();
+        write!(
+            f,
+            "{} checks, {} passed, {} failed",
+            self.checks.len(),
+            self.checks.len() - failed,
+            failed
+        )
+    }
+}
+
+/// Run a fallible check with the shared timeout and record the outcome.
+async fn record<F, E>(report: &mut Report, name: &str, fut: F)
+where
+    F: Future<Output = Result<String, E>>,
+    E: fmt::Display,
+{
+    match timeout(CHECK_TIMEOUT, fut).await {
+        Ok(Ok(detail)) => report.pass(name, detail),
+        Ok(Err(e)) => report.fail(name, e.to_string()),
+        Err(_) => report.fail(name, format!("timed out after {:?}", CHECK_TIMEOUT)),
+    }
+}
+
+/// Run every check for the given configuration.
+pub async fn run(cfg: &Config) -> Report {
+    let mut report = Report::default();
+
+    check_watched_addresses(cfg, &mut report);
+    if cfg.eth_enabled {
+        check_eth(cfg, &mut report).await;
+        if let Some(url) = &cfg.eth_archive_rpc_url {
+            check_eth_archive(url, &mut report).await;
+        }
+    } else {
+        report.pass("eth", "disabled, skipped");
+    }
+    if cfg.sol_enabled {
+        check_sol(cfg, &mut report).await;
+    } else {
+        report.pass("sol", "disabled, skipped");
+    }
+    check_redis(cfg, &mut report).await;
+
+    report
+}
+
+/// Validate that every watched address parses for its chain.
+fn check_watched_addresses(cfg: &Config, report: &mut Report) {
+    let bad_eth: Vec<&String> = cfg
+        .watched_addresses_eth
+        .iter()
+        .filter(|a| Address::from_str(a).is_err())
+        .collect();
+    if bad_eth.is_empty() {
+        report.pass(
+            "eth watched addresses",
+            format!("{} valid", cfg.watched_addresses_eth.len()),
+        );
+    } else {
+        report.fail("eth watched addresses", format!("invalid: {:?}", bad_eth));
+    }
+
+    let bad_sol: Vec<&String> = cfg
+        .watched_addresses_sol
+        .iter()
+        .filter(|a| Pubkey::from_str(a).is_err())
+        .collect();
+    if bad_sol.is_empty() {
+        report.pass(
+            "sol watched addresses",
+            format!("{} valid", cfg.watched_addresses_sol.len()),
+        );
+    } else {
+        report.fail("sol watched addresses", format!("invalid: {:?}", bad_sol));
+    }
+}
+
+async fn check_eth(cfg: &Config, report: &mut Report) {
+    if let Some(path) = cfg.eth_rpc_url.strip_prefix("ipc://") {
+        let ipc = match timeout(CHECK_TIMEOUT, Ipc::connect(path)).await {
+            Ok(Ok(ipc)) => ipc,
+            Ok(Err(e)) => {
+                report.fail("eth connect", e.to_string());
+                return;
+            }
+            Err(_) => {
+                report.fail("eth connect", "timed out");
+                return;
+            }
+        };
+        report.pass("eth connect", format!("ipc {}", path));
+        let provider = Provider::new(ipc);
+        check_eth_methods(&provider, report).await;
+        record(report, "eth_subscribe", async {
+            provider.subscribe_blocks().await.map(|_| "ok".to_string())
+        })
+        .await;
+    } else if cfg.eth_rpc_url.starts_with("ws") {
+        let ws = match timeout(CHECK_TIMEOUT, Ws::connect(cfg.eth_rpc_url.clone())).await {
+            Ok(Ok(ws)) => ws,
+            Ok(Err(e)) => {
+                report.fail("eth connect", e.to_string());
+                return;
+            }
+            Err(_) => {
+                report.fail("eth connect", "timed out");
+                return;
+            }
+        };
+        report.pass("eth connect", format!("websocket {}", cfg.eth_rpc_url));
+        let provider = Provider::new(ws);
+        check_eth_methods(&provider, report).await;
+        // The websocket trackers rely on eth_subscribe for blocks and logs.
+        record(report, "eth_subscribe", async {
+            provider.subscribe_blocks().await.map(|_| "ok".to_string())
+        })
+        .await;
+    } else {
+        match Provider::<Http>::try_from(cfg.eth_rpc_url.clone()) {
+            Ok(provider) => {
+                report.pass("eth connect", format!("http {}", cfg.eth_rpc_url));
+                check_eth_methods(&provider, report).await;
+            }
+            Err(e) => report.fail("eth connect", e.to_string()),
+        }
+    }
+}
+
+/// Confirm the archive endpoint serves state from early in the chain, which a
+/// pruned node would refuse.
+async fn check_eth_archive(url: &str, report: &mut Report) {
+    let provider = match Provider::<Http>::try_from(url) {
+        Ok(p) => p,
+        Err(e) => {
+            report.fail("eth archive", e.to_string());
+            return;
+        }
+    };
+    record(report, "eth archive", async {
+        provider
+            .get_balance(
+                Address::zero(),
+                Some(BlockNumber::Number(1u64.into()).into()),
+            )
+            .await
+            .map(|_| format!("historical state available at {}", url))
+    })
+    .await;
+}
+
+/// Exercise the JSON-RPC methods the trackers depend on.
+async fn check_eth_methods<M: Middleware>(provider: &M, report: &mut Report) {
+    record(report, "eth_chainId", async {
+        provider
+            .get_chainid()
+            .await
+            .map(|id| format!("chain id {}", id))
+    })
+    .await;
+    record(report, "eth_blockNumber", async {
+        provider
+            .get_block_number()
+            .await
+            .map(|bn| format!("latest block {}", bn))
+    })
+    .await;
+    record(report, "eth_getBlockByNumber", async {
+        provider
+            .get_block_with_txs(BlockNumber::Latest)
+            .await
+            .map(|b| match b {
+                Some(b) => format!("{} txs in latest block", b.transactions.len()),
+                None => "latest block not returned".to_string(),
+            })
+    })
+    .await;
+    record(report, "eth_getLogs", async {
+        let filter = Filter::new()
+            .from_block(BlockNumber::Latest)
+            .to_block(BlockNumber::Latest)
+            .event("Transfer(address,address,uint256)");
+        provider
+            .get_logs(&filter)
+            .await
+            .map(|logs| format!("{} transfer logs in latest block", logs.len()))
+    })
+    .await;
+}
+
+async fn check_sol(cfg: &Config, report: &mut Report) {
+    let rpc_url = cfg
+        .sol_rpc_url
+        .replace("ws:", "http:")
+        .replace("wss:", "https:");
+    let rpc_client = Arc::new(RpcClient::new(rpc_url));
+
+    record(report, "sol getVersion", {
+        let rpc_client = Arc::clone(&rpc_client);
+        blocking(move || {
+            rpc_client
+                .get_version()
+                .map(|v| format!("solana-core {}", v.solana_core))
+        })
+    })
+    .await;
+    record(report, "sol getSlot", {
+        let rpc_client = Arc::clone(&rpc_client);
+        blocking(move || rpc_client.get_slot().map(|s| format!("slot {}", s)))
+    })
+    .await;
+
+    // Pollers call getSignaturesForAddress per watched address; probe with the
+    // first configured one, falling back to the system program.
+    let probe = cfg
+        .watched_addresses_sol
+        .iter()
+        .find_map(|a| Pubkey::from_str(a).ok())
+        .unwrap_or_default();
+    record(report, "sol getSignaturesForAddress", {
+        let rpc_client = Arc::clone(&rpc_client);
+        blocking(move || {
+            rpc_client
+                .get_signatures_for_address(&probe)
+                .map(|s| format!("{} signatures for {}", s.len(), probe))
+        })
+    })
+    .await;
+}
+
+/// Ping `REDIS_URL` and every destination that points at its own Redis.
+async fn check_redis(cfg: &Config, report: &mut Report) {
+    let mut urls = vec![cfg.redis_url.clone()];
+    for dest in &cfg.destinations {
+        if let Some(url) = &dest.redis_url {
+            if !urls.contains(url) {
+                urls.push(url.clone());
+            }
+        }
+    }
+
+    for url in urls {
+        record(report, &format!("redis ping {}", url), async {
+            let client = redis::Client::open(url.as_str())?;
+            let mut con = client.get_multiplexed_async_connection().await?;
+            let pong: String = redis::cmd("PING").query_async(&mut con).await?;
+            Ok::<_, redis::RedisError>(pong)
+        })
+        .await;
+    }
+}
+
+/// Run a blocking solana RPC call off the async runtime.
+async fn blocking<F, T>(f: F) -> anyhow::Result<T>
+where
+    F: FnOnce() -> Result<T, solana_client::client_error::ClientError> + Send + 'static,
+    T: Send + 'static,
+{
+    Ok(tokio::task::spawn_blocking(f).await??)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cfg_with(eth: Vec<&str>, sol: Vec<&str>) -> Config {
+        Config {
+            watched_addresses_eth: eth.into_iter().map(String::from).collect(),
+            watched_addresses_sol: sol.into_iter().map(String::from).collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_watched_address_validation() {
+        let cfg = cfg_with(
+            vec![
+                "0x0000000000000000000000000000000000000001",
+                "not-an-address",
+            ],
+            vec!["11111111111111111111111111111111"],
+        );
+        let mut report = Report::default();
+        check_watched_addresses(&cfg, &mut report);
+
+        assert_eq!(report.checks.len(), 2);
+        assert!(!report.checks[0].ok);
+        assert!(report.checks[0].detail.contains("not-an-address"));
+        assert!(report.checks[1].ok);
+        assert!(!report.passed());
+    }
+
+    #[test]
+    fn test_report_rendering() {
+        let mut report = Report::default();
+        report.pass("redis ping", "PONG");
+        report.fail("eth_getLogs", "method not allowed");
+
+        let out = report.to_string();
+        assert!(out.contains("[PASS] redis ping: PONG"));
+        assert!(out.contains("[FAIL] eth_getLogs: method not allowed"));
+        assert!(out.ends_with("2 checks, 1 passed, 1 failed"));
+    }
+}
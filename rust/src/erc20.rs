@@ -0,0 +1,279 @@
+//! Decoding of ERC-20 `Transfer` logs.
+//!
+//! A standard token emits `Transfer(address indexed from, address indexed
+//! to, uint256 value)`: three topics, the addresses left-padded to 32 bytes,
+//! and one 32-byte data word. Tokens that don't follow the standard still get
+//! decoded the way the trackers always have, but instead of publishing the
+//! result as if nothing were wrong, the event's `decode_warnings` say what
+//! was off, so consumers can hold such transfers for review:
+//!
+//! - `missing_data`: no data at all; `value` is 0.
+//! - `short_data`: less than a word; `value` is read from the bytes there.
+//! - `extra_data`: more than a word; only the first is the `value`.
+//! - `dirty_address_topic`: an address topic has non-zero padding, which
+//!   `from` or `to` drops.
+//! - `decimals_overflow`: the token reports more decimals than any 256-bit
+//!   amount has digits, so `value_formatted` is meaningless.
+//!
+//! Logs with another number of topics, e.g. ERC-721 transfers with an
+//! indexed token id, aren't ERC-20 transfers and aren't decoded.
+//!
+//! Some tokens are standard on the wire but don't move what the log says.
+//! Tokens listed in the chain registry with a `behavior` tag their events
+//! with `token_behavior`:
+//!
+//! - `fee_on_transfer`: the token keeps a cut, so the recipient gets less
+//!   than `value`. The event's `received_value` is the recipient's
+//!   `balanceOf` change over the block, when that is positive and no more
+//!   than `value`; other transfers of the token to or from the recipient in
+//!   the same block make it unreliable, and it is left out.
+//! - `rebasing`: balances change without transfers, so summing `value`s
+//!   drifts from the real balance. There is no `received_value`.
+use ethers::providers::Middleware;
+use ethers::types::transaction::eip2718::TypedTransaction;
+use ethers::types::{Address, BlockId, Log, NameOrAddress, TransactionRequest, H256, U256};
+use serde::{Deserialize, Serialize};
+
+use crate::amount::Amount;
+
+/// Most decimals a 256-bit amount can have digits for.
+const MAX_DECIMALS: u8 = 77;
+
+/// Something non-standard about a decoded `Transfer` log.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DecodeWarning {
+    MissingData,
+    ShortData,
+    ExtraData,
+    DirtyAddressTopic,
+    DecimalsOverflow,
+}
+
+/// How a token's balances move, from its chain registry entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TokenBehavior {
+    /// Transfers deduct a fee, so the recipient gets less than `value`.
+    FeeOnTransfer,
+    /// Balances grow or shrink between transfers.
+    Rebasing,
+}
+
+/// A decoded ERC-20 `Transfer` log.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TransferLog {
+    pub from: Address,
+    pub to: Address,
+    pub value: U256,
+    /// What was wrong with the log itself.
+    warnings: Vec<DecodeWarning>,
+}
+
+impl TransferLog {
+    /// Decode `log`, which matched the `Transfer` signature. `None` unless
+    /// it has the three topics of an ERC-20 transfer.
+    pub fn decode(log: &Log) -> Option<Self> {
+        if log.topics.len() != 3 {
+            return None;
+        }
+        let mut warnings = Vec::new();
+        if log.topics[1..]
+            .iter()
+            .any(|topic| !is_padded_address(topic))
+        {
+            warnings.push(DecodeWarning::DirtyAddressTopic);
+        }
+        match log.data.len() {
+            0 => warnings.push(DecodeWarning::MissingData),
+            1..=31 => warnings.push(DecodeWarning::ShortData),
+            32 => {}
+            _ => warnings.push(DecodeWarning::ExtraData),
+        }
+        Some(TransferLog {
+            from: Address::from(log.topics[1]),
+            to: Address::from(log.topics[2]),
+            value: Amount::from_abi_word(&log.data, 0).raw(),
+            warnings,
+        })
+    }
+
+    /// The value in a token with `decimals`.
+    pub fn amount(&self, decimals: u8) -> Amount {
+        Amount::new(self.value, decimals)
+    }
+
+    /// What the recipient of a token with `behavior` received, if it is a
+    /// fee-on-transfer token and its balances at `block` and the block
+    /// before can tell. Makes no calls for other tokens.
+    pub async fn received<M: Middleware>(
+        &self,
+        provider: &M,
+        token: Address,
+        behavior: Option<TokenBehavior>,
+        block: Option<u64>,
+    ) -> Option<U256> {
+        let (Some(TokenBehavior::FeeOnTransfer), Some(block)) = (behavior, block) else {
+            return None;
+        };
+        let before = balance_of(provider, token, self.to, block.checked_sub(1)?).await?;
+        let after = balance_of(provider, token, self.to, block).await?;
+        received(self.value, before, after)
+    }
+
+    /// The event's `decode_warnings` for a token with `decimals`.
+    pub fn warnings(&self, decimals: u8) -> Vec<DecodeWarning> {
+        let mut warnings = self.warnings.clone();
+        if decimals > MAX_DECIMALS {
+            warnings.push(DecodeWarning::DecimalsOverflow);
+        }
+        warnings
+    }
+}
+
+/// The balance change from `before` to `after`, if it can be what a transfer
+/// of `value` delivered.
+fn received(value: U256, before: U256, after: U256) -> Option<U256> {
+    let delta = after.checked_sub(before)?;
+    (!delta.is_zero() && delta <= value).then_some(delta)
+}
+
+/// Calldata of ERC-20 `balanceOf(owner)`.
+pub fn balance_of_call(owner: Address) -> Vec<u8> {
+    // balanceOf(address) - function selector 0x70a08231
+    let mut data = vec![0x70, 0xa0, 0x82, 0x31];
+    data.extend_from_slice(&[0u8; 12]);
+    data.extend_from_slice(owner.as_bytes());
+    data
+}
+
+/// `balanceOf(owner)` of `token` at `block`, or `None` if the call failed.
+async fn balance_of<M: Middleware>(
+    provider: &M,
+    token: Address,
+    owner: Address,
+    block: u64,
+) -> Option<U256> {
+    let tx = TypedTransaction::Legacy(TransactionRequest {
+        to: Some(NameOrAddress::Address(token)),
+        data: Some(balance_of_call(owner).into()),
+        ..Default::default()
+    });
+    let bytes = provider
+        .call(&tx, Some(BlockId::Number(block.into())))
+        .await
+        .ok()?;
+    (bytes.len() >= 32).then(|| Amount::from_abi_word(&bytes, 0).raw())
+}
+
+/// Whether `topic` is an address left-padded with zeros.
+fn is_padded_address(topic: &H256) -> bool {
+    topic.as_bytes()[..12].iter().all(|b| *b == 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use DecodeWarning::*;
+
+    fn log(topics: Vec<H256>, data: Vec<u8>) -> Log {
+        Log {
+            topics,
+            data: data.into(),
+            ..Default::default()
+        }
+    }
+
+    fn address_topic(byte: u8) -> H256 {
+        H256::from(Address::repeat_byte(byte))
+    }
+
+    fn word(value: u64) -> Vec<u8> {
+        let mut word = [0u8; 32];
+        U256::from(value).to_big_endian(&mut word);
+        word.to_vec()
+    }
+
+    #[test]
+    fn test_standard_transfer() {
+        let topics = vec![H256::zero(), address_topic(1), address_topic(2)];
+        let transfer = TransferLog::decode(&log(topics, word(1_500_000))).unwrap();
+        assert_eq!(transfer.from, Address::repeat_byte(1));
+        assert_eq!(transfer.to, Address::repeat_byte(2));
+        assert_eq!(transfer.amount(6).formatted(), "1.5");
+        assert!(transfer.warnings(6).is_empty());
+    }
+
+    #[test]
+    fn test_non_standard_data() {
+        let topics = vec![H256::zero(), address_topic(1), address_topic(2)];
+        let missing = TransferLog::decode(&log(topics.clone(), Vec::new())).unwrap();
+        assert_eq!(missing.value, U256::zero());
+        assert_eq!(missing.warnings(18), [MissingData]);
+
+        let short = TransferLog::decode(&log(topics.clone(), vec![0x01, 0x00])).unwrap();
+        assert_eq!(short.value, U256::from(256));
+        assert_eq!(short.warnings(18), [ShortData]);
+
+        let mut data = word(42);
+        data.extend(word(7));
+        let extra = TransferLog::decode(&log(topics, data)).unwrap();
+        assert_eq!(extra.value, U256::from(42));
+        assert_eq!(extra.warnings(18), [ExtraData]);
+    }
+
+    #[test]
+    fn test_dirty_topics_and_decimals() {
+        let mut dirty = address_topic(2);
+        dirty.0[0] = 0xff;
+        let topics = vec![H256::zero(), address_topic(1), dirty];
+        let transfer = TransferLog::decode(&log(topics, word(1))).unwrap();
+        assert_eq!(transfer.to, Address::repeat_byte(2));
+        assert_eq!(
+            transfer.warnings(255),
+            [DirtyAddressTopic, DecimalsOverflow]
+        );
+        assert_eq!(transfer.warnings(77), [DirtyAddressTopic]);
+    }
+
+    #[tokio::test]
+    async fn test_received_from_balance_delta() {
+        let value = U256::from(1_000);
+        assert_eq!(
+            received(value, U256::from(50), U256::from(1_020)),
+            Some(U256::from(970))
+        );
+        // More than the transfer, or nothing: other activity in the block.
+        assert_eq!(received(value, U256::from(50), U256::from(2_050)), None);
+        assert_eq!(received(value, U256::from(50), U256::from(50)), None);
+        assert_eq!(received(value, U256::from(50), U256::from(10)), None);
+
+        // Only fee-on-transfer tokens are looked up.
+        let (provider, _mock) = ethers::providers::Provider::mocked();
+        let topics = vec![H256::zero(), address_topic(1), address_topic(2)];
+        let transfer = TransferLog::decode(&log(topics, word(1_000))).unwrap();
+        let token = Address::repeat_byte(9);
+        assert_eq!(
+            transfer.received(&provider, token, None, Some(10)).await,
+            None
+        );
+        let rebasing = Some(TokenBehavior::Rebasing);
+        assert_eq!(
+            transfer
+                .received(&provider, token, rebasing, Some(10))
+                .await,
+            None
+        );
+    }
+
+    #[test]
+    fn test_nft_transfers_are_not_decoded() {
+        let topics = vec![
+            H256::zero(),
+            address_topic(1),
+            address_topic(2),
+            H256::from_low_u64_be(7),
+        ];
+        assert_eq!(TransferLog::decode(&log(topics, Vec::new())), None);
+    }
+}
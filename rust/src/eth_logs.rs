@@ -0,0 +1,231 @@
+//! Chunked `eth_getLogs` over large block ranges.
+//!
+//! Providers cap log queries by block span (commonly 2k blocks) or by result
+//! count (e.g. 10k logs). Ranges are fetched in chunks of at most
+//! `ETH_LOGS_CHUNK_SIZE` blocks; when a provider rejects a chunk as too large
+//! the chunk is halved and retried, then grown back after successes.
+//!
+//! Pruned (non-archive) nodes may refuse historical ranges outright. Those
+//! errors are detected and, if `ETH_ARCHIVE_RPC_URL` is set, the range is
+//! re-fetched from the archive endpoint; head tracking stays on the primary
+//! node either way.
+use anyhow::anyhow;
+use ethers::providers::{Http, Middleware, Provider};
+use ethers::types::{Filter, Log};
+use std::fmt::Display;
+use std::future::Future;
+use tracing::{debug, warn};
+
+/// Error fragments providers use when a log query spans too many blocks or
+/// returns too many results (geth, Erigon, Alchemy, Infura, QuickNode, ...).
+const RANGE_LIMIT_HINTS: &[&str] = &[
+    "query returned more than",
+    "more than 10000 results",
+    "block range",
+    "range too large",
+    "range is too large",
+    "exceed maximum block range",
+    "too many blocks",
+    "limit exceeded",
+    "log response size exceeded",
+    "response size",
+];
+
+/// Error fragments from nodes that no longer hold the requested history.
+const PRUNED_HINTS: &[&str] = &[
+    "missing trie node",
+    "history has been pruned",
+    "pruned history",
+    "required historical state unavailable",
+    "historical state",
+    "state is not available",
+    "not available in pruned",
+];
+
+/// Whether an RPC error means the node has pruned the requested history.
+pub fn is_pruned_history_error(message: &str) -> bool {
+    let message = message.to_lowercase();
+    PRUNED_HINTS.iter().any(|hint| message.contains(hint))
+}
+
+/// Whether an RPC error means the requested log range should be narrowed.
+pub fn is_range_limit_error(message: &str) -> bool {
+    let message = message.to_lowercase();
+    RANGE_LIMIT_HINTS.iter().any(|hint| message.contains(hint))
+}
+
+/// Fetch `[from, to]` inclusive in chunks of at most `max_chunk` blocks.
+///
+/// `fetch(start, end)` is called per chunk. Range-limit errors halve the chunk
+/// (down to a single block, after which the error is returned); any other
+/// error is returned immediately.
+pub async fn fetch_chunked<T, E, F, Fut>(
+    from: u64,
+    to: u64,
+    max_chunk: u64,
+    mut fetch: F,
+) -> Result<Vec<T>, E>
+where
+    E: Display,
+    F: FnMut(u64, u64) -> Fut,
+    Fut: Future<Output = Result<Vec<T>, E>>,
+{
+    let max_chunk = max_chunk.max(1);
+    let mut chunk = max_chunk;
+    let mut start = from;
+    let mut out = Vec::new();
+
+    while start <= to {
+        let end = start.saturating_add(chunk - 1).min(to);
+        match fetch(start, end).await {
+            Ok(items) => {
+                out.extend(items);
+                start = end + 1;
+                chunk = (chunk * 2).min(max_chunk);
+            }
+            Err(e) if chunk > 1 && is_range_limit_error(&e.to_string()) => {
+                chunk = (chunk / 2).max(1);
+                debug!(
+                    "Log range {}..={} rejected ({}); retrying with chunk size {}",
+                    start, end, e, chunk
+                );
+            }
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(out)
+}
+
+/// `eth_getLogs` for `filter` over `[from, to]` with adaptive chunking.
+pub async fn get_logs_chunked<M: Middleware>(
+    provider: &M,
+    filter: &Filter,
+    from: u64,
+    to: u64,
+    max_chunk: u64,
+) -> anyhow::Result<Vec<Log>> {
+    fetch_chunked(from, to, max_chunk, |start, end| {
+        let filter = filter.clone().from_block(start).to_block(end);
+        async move { provider.get_logs(&filter).await.map_err(|e| e.to_string()) }
+    })
+    .await
+    .map_err(|e| anyhow::anyhow!("eth_getLogs failed: {}", e))
+}
+
+/// Backfill `[from, to]`, falling back to `archive` if the primary node has
+/// pruned that history. Without an archive endpoint a pruned range yields an
+/// error explaining how to fix it rather than the raw RPC message.
+pub async fn backfill_logs<M: Middleware>(
+    provider: &M,
+    archive: Option<&Provider<Http>>,
+    filter: &Filter,
+    from: u64,
+    to: u64,
+    max_chunk: u64,
+) -> anyhow::Result<Vec<Log>> {
+    match get_logs_chunked(provider, filter, from, to, max_chunk).await {
+        Err(e) if is_pruned_history_error(&e.to_string()) => match archive {
+            Some(archive) => {
+                warn!(
+                    "Primary ETH node has pruned blocks {} to {}; using archive endpoint",
+                    from, to
+                );
+                get_logs_chunked(archive, filter, from, to, max_chunk).await
+            }
+            None => Err(anyhow!(
+                "ETH node has pruned history for blocks {} to {} ({}). \
+                 Point ETH_ARCHIVE_RPC_URL at an archive node to backfill this range",
+                from,
+                to,
+                e
+            )),
+        },
+        res => res,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+
+    #[test]
+    fn test_range_limit_error_detection() {
+        assert!(is_range_limit_error(
+            "query returned more than 10000 results"
+        ));
+        assert!(is_range_limit_error(
+            "(code: -32600) eth_getLogs block range is too large"
+        ));
+        assert!(!is_range_limit_error("connection refused"));
+    }
+
+    #[test]
+    fn test_pruned_history_error_detection() {
+        assert!(is_pruned_history_error(
+            "(code: -32000, message: missing trie node 1a2b... (path ), data: None)"
+        ));
+        assert!(is_pruned_history_error(
+            "eth_getLogs failed: history has been pruned for this block"
+        ));
+        assert!(!is_pruned_history_error(
+            "query returned more than 10000 results"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_chunks_cover_range_without_overlap() {
+        let calls = RefCell::new(Vec::new());
+        let blocks = fetch_chunked(1, 10, 4, |s, e| {
+            calls.borrow_mut().push((s, e));
+            async move { Ok::<_, String>((s..=e).collect::<Vec<u64>>()) }
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(blocks, (1..=10).collect::<Vec<u64>>());
+        assert_eq!(*calls.borrow(), vec![(1, 4), (5, 8), (9, 10)]);
+    }
+
+    #[tokio::test]
+    async fn test_halves_on_limit_error_then_grows_back() {
+        let calls = RefCell::new(Vec::new());
+        // Provider rejects any span wider than 2 blocks.
+        let blocks = fetch_chunked(0, 7, 8, |s, e| {
+            calls.borrow_mut().push((s, e));
+            async move {
+                if e - s + 1 > 2 {
+                    Err("query returned more than 10000 results".to_string())
+                } else {
+                    Ok((s..=e).collect::<Vec<u64>>())
+                }
+            }
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(blocks, (0..=7).collect::<Vec<u64>>());
+        assert_eq!(
+            *calls.borrow(),
+            vec![
+                (0, 7),
+                (0, 3),
+                (0, 1),
+                (2, 5),
+                (2, 3),
+                (4, 7),
+                (4, 5),
+                (6, 7)
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_other_errors_are_returned() {
+        let res = fetch_chunked(0, 100, 10, |_, _| async {
+            Err::<Vec<u64>, _>("connection refused".to_string())
+        })
+        .await;
+        assert_eq!(res.unwrap_err(), "connection refused");
+    }
+}
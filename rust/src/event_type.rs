@@ -0,0 +1,244 @@
+//! The `event_type` taxonomy of published events.
+//!
+//! The serialized names are part of the payload contract, like the event id
+//! formats: a type is only ever added, never renamed or removed outside a
+//! major release, so consumers may match on them. Consumers should still
+//! ignore types they don't know, since new ones can appear in a minor
+//! release. `GET /admin/event-types` lists the types with their
+//! descriptions.
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum EventType {
+    /// Native currency transfer (ETH, TRX on Tron, `balances.Transfer` on
+    /// Substrate chains), or any transaction from or to a watched Ethereum
+    /// address.
+    #[default]
+    Transfer,
+    /// ERC-20 `Transfer` log, or TRC-20 transfer on Tron, from or to a
+    /// watched address.
+    Erc20Transfer,
+    /// Solana transaction touching a watched address.
+    SolanaTx,
+    /// Blocks the gap auditor reprocessed.
+    GapRepaired,
+    /// Periodic monitoring status of a watched address.
+    AddressHeartbeat,
+    /// A transfer that matched a pending expectation.
+    ExpectedTransferReceived,
+    /// An expectation whose deadline passed without a matching transfer.
+    ExpectedTransferMissed,
+    /// A Solana transaction that was published but never finalized.
+    TransactionRetracted,
+    /// Valuation of a configured portfolio.
+    PortfolioSnapshot,
+    /// Outgoing transaction of a cold-storage address.
+    UnexpectedActivity,
+    /// Outgoing transfers of several watched addresses to one destination.
+    SweepDetected,
+    /// Delinquency, commission or stake change of a monitored Solana validator.
+    ValidatorEvent,
+    /// Liquid staking token minted for a watched address.
+    LstMint,
+    /// Liquid staking token redeemed by a watched address.
+    LstRedeem,
+    /// Deposit into an Aave or Compound market involving a watched address.
+    LendingSupply,
+    /// Loan from an Aave or Compound market involving a watched address.
+    LendingBorrow,
+    /// Loan repayment to an Aave or Compound market involving a watched
+    /// address.
+    LendingRepay,
+    /// Liquidation of an Aave or Compound position involving a watched
+    /// address.
+    LendingLiquidation,
+    /// Health factor of a watched borrower below the alert threshold.
+    LiquidationRisk,
+    /// Transaction from or to a watched Ethereum address seen in the
+    /// mempool, before it is mined.
+    PendingTransfer,
+    /// Transaction of a watched Ethereum address pending too long, or held
+    /// back by a nonce gap.
+    StuckTransaction,
+    /// Copy of a transaction of a watched sender that paid many times the
+    /// base fee.
+    FeeSpike,
+}
+
+impl EventType {
+    /// Every type, in the order they were introduced.
+    pub const ALL: [EventType; 22] = [
+        EventType::Transfer,
+        EventType::Erc20Transfer,
+        EventType::SolanaTx,
+        EventType::GapRepaired,
+        EventType::AddressHeartbeat,
+        EventType::ExpectedTransferReceived,
+        EventType::ExpectedTransferMissed,
+        EventType::TransactionRetracted,
+        EventType::PortfolioSnapshot,
+        EventType::UnexpectedActivity,
+        EventType::SweepDetected,
+        EventType::ValidatorEvent,
+        EventType::LstMint,
+        EventType::LstRedeem,
+        EventType::LendingSupply,
+        EventType::LendingBorrow,
+        EventType::LendingRepay,
+        EventType::LendingLiquidation,
+        EventType::LiquidationRisk,
+        EventType::PendingTransfer,
+        EventType::StuckTransaction,
+        EventType::FeeSpike,
+    ];
+
+    /// The serialized name.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            EventType::Transfer => "transfer",
+            EventType::Erc20Transfer => "erc20_transfer",
+            EventType::SolanaTx => "solana_tx",
+            EventType::GapRepaired => "gap_repaired",
+            EventType::AddressHeartbeat => "address_heartbeat",
+            EventType::ExpectedTransferReceived => "expected_transfer_received",
+            EventType::ExpectedTransferMissed => "expected_transfer_missed",
+            EventType::TransactionRetracted => "transaction_retracted",
+            EventType::PortfolioSnapshot => "portfolio_snapshot",
+            EventType::UnexpectedActivity => "unexpected_activity",
+            EventType::SweepDetected => "sweep_detected",
+            EventType::ValidatorEvent => "validator_event",
+            EventType::LstMint => "lst_mint",
+            EventType::LstRedeem => "lst_redeem",
+            EventType::LendingSupply => "lending_supply",
+            EventType::LendingBorrow => "lending_borrow",
+            EventType::LendingRepay => "lending_repay",
+            EventType::LendingLiquidation => "lending_liquidation",
+            EventType::LiquidationRisk => "liquidation_risk",
+            EventType::PendingTransfer => "pending_transfer",
+            EventType::StuckTransaction => "stuck_transaction",
+            EventType::FeeSpike => "fee_spike",
+        }
+    }
+
+    /// What an event of this type means, for the admin API.
+    pub fn description(self) -> &'static str {
+        match self {
+            EventType::Transfer => {
+                "Native transfer, or any other transaction from or to a watched Ethereum address"
+            }
+            EventType::Erc20Transfer => {
+                "ERC-20 Transfer log, or TRC-20 transfer on Tron, from or to a watched address"
+            }
+            EventType::SolanaTx => "Solana transaction touching a watched address",
+            EventType::GapRepaired => "Blocks the gap auditor found missing and reprocessed",
+            EventType::AddressHeartbeat => "Periodic monitoring status of a watched address",
+            EventType::ExpectedTransferReceived => {
+                "Transfer matching a registered expectation, published in addition to it"
+            }
+            EventType::ExpectedTransferMissed => {
+                "Expectation whose deadline passed without a matching transfer"
+            }
+            EventType::TransactionRetracted => {
+                "Copy of a published Solana event whose transaction never finalized"
+            }
+            EventType::PortfolioSnapshot => {
+                "Balances and value of a configured portfolio of watched addresses"
+            }
+            EventType::UnexpectedActivity => {
+                "Copy of an outgoing transaction of a cold-storage address, with an alert"
+            }
+            EventType::SweepDetected => {
+                "Transfers of several watched addresses to one destination within a window"
+            }
+            EventType::ValidatorEvent => {
+                "Delinquency, commission or stake change of a monitored Solana validator"
+            }
+            EventType::LstMint => {
+                "Copy of a watched address's liquid staking mint, with the amount staked"
+            }
+            EventType::LstRedeem => {
+                "Copy of a watched address's liquid staking redemption, with the amount paid out"
+            }
+            EventType::LendingSupply => "Deposit into an Aave or Compound market",
+            EventType::LendingBorrow => "Loan from an Aave or Compound market",
+            EventType::LendingRepay => "Loan repayment to an Aave or Compound market",
+            EventType::LendingLiquidation => {
+                "Liquidation of an Aave or Compound position, with the collateral seized"
+            }
+            EventType::LiquidationRisk => {
+                "Health factor of a watched Aave borrower below the alert threshold"
+            }
+            EventType::PendingTransfer => {
+                "Transaction of a watched Ethereum address in the mempool, not yet mined"
+            }
+            EventType::StuckTransaction => {
+                "Transaction of a watched Ethereum address pending too long or behind a nonce gap"
+            }
+            EventType::FeeSpike => {
+                "Copy of a watched sender's transaction that paid many times the base fee, with an alert"
+            }
+        }
+    }
+}
+
+impl fmt::Display for EventType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_all_lists_every_type_once() {
+        // Fails to compile when a type is added: add it to `ALL` too.
+        let position = |event_type: EventType| match event_type {
+            EventType::Transfer => 0,
+            EventType::Erc20Transfer => 1,
+            EventType::SolanaTx => 2,
+            EventType::GapRepaired => 3,
+            EventType::AddressHeartbeat => 4,
+            EventType::ExpectedTransferReceived => 5,
+            EventType::ExpectedTransferMissed => 6,
+            EventType::TransactionRetracted => 7,
+            EventType::PortfolioSnapshot => 8,
+            EventType::UnexpectedActivity => 9,
+            EventType::SweepDetected => 10,
+            EventType::ValidatorEvent => 11,
+            EventType::LstMint => 12,
+            EventType::LstRedeem => 13,
+            EventType::LendingSupply => 14,
+            EventType::LendingBorrow => 15,
+            EventType::LendingRepay => 16,
+            EventType::LendingLiquidation => 17,
+            EventType::LiquidationRisk => 18,
+            EventType::PendingTransfer => 19,
+            EventType::StuckTransaction => 20,
+            EventType::FeeSpike => 21,
+        };
+        for (i, event_type) in EventType::ALL.iter().enumerate() {
+            assert_eq!(position(*event_type), i, "{}", event_type);
+        }
+    }
+
+    #[test]
+    fn test_serialized_names_are_stable() {
+        for event_type in EventType::ALL {
+            let json = serde_json::to_value(event_type).unwrap();
+            assert_eq!(json, event_type.as_str());
+            let parsed: EventType = serde_json::from_value(json).unwrap();
+            assert_eq!(parsed, event_type);
+        }
+        assert_eq!(
+            serde_json::to_string(&EventType::Erc20Transfer).unwrap(),
+            r#""erc20_transfer""#
+        );
+        assert!(serde_json::from_str::<EventType>(r#""erc20""#).is_err());
+        assert_eq!(format!("{:<10}|", EventType::SolanaTx), "solana_tx |");
+    }
+}
@@ -0,0 +1,227 @@
+//! Extra EVM networks tracked next to Ethereum (`[[evm_networks]]` in the
+//! config file).
+//!
+//! Each entry runs its own ETH tracker over its own endpoint: native
+//! transfers and ERC-20 logs of the entry's watched addresses, published
+//! with the entry's `chain` and `network`. The trackers share the ETH
+//! settings (confirmations, batch size, token and spam filters), pause and
+//! resume at `/admin/chains/<chain>`, and checkpoint under
+//! `<chain>:<network>`.
+//!
+//! ```toml
+//! [[evm_networks]]
+//! chain = "polygon"
+//! rpc_url = "wss://polygon-mainnet.g.alchemy.com/v2/<key>"
+//! watched_addresses = ["0x..."]
+//!
+//! [[evm_networks]]
+//! chain = "bnb"
+//! chain_id = 56
+//! rpc_url = "https://bsc-dataseed.bnbchain.org"
+//! watched_addresses = ["0x..."]
+//! ```
+//!
+//! `network` defaults to the chain name, which is how `ETH_NETWORK` names
+//! these chains, so `[[assets]]` scopes and the registry work the same for
+//! them. The chain registry entry (see [`crate::chains`]) is the one with
+//! `chain_id`, or else the one with a network named `network`, and gives the
+//! events their native currency, explorer links and well-known tokens.
+//!
+//! Backup and archive endpoints, the mempool tracker, lending decoding and
+//! the scheduled jobs (gap audit, RPC consistency, heartbeats, portfolio
+//! snapshots, liquidation risk, stuck transactions) only cover the
+//! `ETH_RPC_URL` chain.
+use anyhow::{anyhow, bail, Context};
+use ethers::types::Address;
+use serde::Deserialize;
+
+use crate::chains::{ChainInfo, ChainRegistry};
+use crate::config::Config;
+
+/// One `[[evm_networks]]` entry as written.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EvmNetworkConfig {
+    /// Event `chain` name, e.g. `polygon`.
+    pub chain: String,
+    /// Event `network` name; defaults to `chain`.
+    #[serde(default)]
+    pub network: Option<String>,
+    /// Registry chain id; defaults to the registry entry of `network`.
+    #[serde(default)]
+    pub chain_id: Option<u64>,
+    pub rpc_url: String,
+    #[serde(default)]
+    pub watched_addresses: Vec<String>,
+}
+
+/// A validated entry.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EvmNetwork {
+    pub chain: String,
+    pub network: String,
+    pub info: ChainInfo,
+    pub rpc_url: String,
+    pub watched_addresses: Vec<String>,
+}
+
+impl EvmNetwork {
+    /// Validate `entries` and look up their chains in `chains`.
+    pub fn resolve(
+        entries: &[EvmNetworkConfig],
+        chains: &ChainRegistry,
+    ) -> anyhow::Result<Vec<EvmNetwork>> {
+        let mut networks: Vec<EvmNetwork> = Vec::new();
+        for entry in entries {
+            let network = EvmNetwork::new(entry, chains)
+                .with_context(|| format!("evm network {:?}", entry.chain))?;
+            if networks.iter().any(|n| n.chain == network.chain) {
+                bail!("evm network {:?} is listed twice", network.chain);
+            }
+            networks.push(network);
+        }
+        Ok(networks)
+    }
+
+    fn new(entry: &EvmNetworkConfig, chains: &ChainRegistry) -> anyhow::Result<EvmNetwork> {
+        let chain = entry.chain.trim().to_ascii_lowercase();
+        if chain.is_empty()
+            || !chain
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        {
+            bail!("chain must be a name of letters, digits, '-' and '_'");
+        }
+        if matches!(chain.as_str(), "ethereum" | "solana" | "tron") {
+            bail!("chain {} is configured with its own settings", chain);
+        }
+        let network = match &entry.network {
+            Some(network) if network.trim().is_empty() => bail!("network must not be empty"),
+            Some(network) => network.trim().to_string(),
+            None => chain.clone(),
+        };
+        let rpc_url = entry.rpc_url.trim().to_string();
+        if !["ws://", "wss://", "http://", "https://", "ipc://"]
+            .iter()
+            .any(|scheme| rpc_url.starts_with(scheme))
+        {
+            bail!("rpc_url must be a ws(s)://, http(s):// or ipc:// URL");
+        }
+        let info = match entry.chain_id {
+            Some(chain_id) => chains
+                .get(chain_id)
+                .with_context(|| format!("chain_id {} is not in the chain registry", chain_id))?,
+            None => chains.by_network(&network).ok_or_else(|| {
+                anyhow!(
+                    "{} is not a chain registry network; set chain_id or add it with \
+                     CHAIN_REGISTRY_FILE",
+                    network
+                )
+            })?,
+        };
+        for address in &entry.watched_addresses {
+            address
+                .parse::<Address>()
+                .with_context(|| format!("invalid watched address {:?}", address))?;
+        }
+        Ok(EvmNetwork {
+            chain,
+            network,
+            info: info.clone(),
+            rpc_url,
+            watched_addresses: entry.watched_addresses.clone(),
+        })
+    }
+
+    /// Checkpoint key of the network's tracker.
+    pub fn key(&self) -> String {
+        format!("{}:{}", self.chain, self.network)
+    }
+
+    /// The ETH settings of `base` with this network's endpoint, chain and
+    /// watched addresses, for its tracker.
+    pub fn config(&self, base: &Config) -> Config {
+        Config {
+            eth_enabled: true,
+            eth_rpc_url: self.rpc_url.clone(),
+            eth_network: self.network.clone(),
+            eth_chain: Some(self.info.clone()),
+            watched_addresses_eth: self.watched_addresses.clone(),
+            eth_archive_rpc_url: None,
+            eth_rpc_backup_urls: Vec::new(),
+            eth_alchemy_mined_transactions: crate::config::is_alchemy_ws(&self.rpc_url),
+            eth_pending_transfers: false,
+            eth_pending_simulation: false,
+            ..base.clone()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(chain: &str) -> EvmNetworkConfig {
+        EvmNetworkConfig {
+            chain: chain.to_string(),
+            network: None,
+            chain_id: None,
+            rpc_url: "wss://polygon-mainnet.g.alchemy.com/v2/key".to_string(),
+            watched_addresses: vec!["0x742d35Cc6634C0532925a3b844Bc454e4438f44e".to_string()],
+        }
+    }
+
+    #[test]
+    fn test_resolve_by_name_and_chain_id() {
+        let chains = ChainRegistry::default();
+        let bnb = EvmNetworkConfig {
+            network: Some("mainnet".to_string()),
+            chain_id: Some(56),
+            ..entry("bnb")
+        };
+        let networks = EvmNetwork::resolve(&[entry("Polygon"), bnb], &chains).unwrap();
+        assert_eq!(networks[0].chain, "polygon");
+        assert_eq!(networks[0].info.chain_id, 137);
+        assert_eq!(networks[0].key(), "polygon:polygon");
+        assert_eq!(networks[1].key(), "bnb:mainnet");
+        assert_eq!(networks[1].info.native_symbol, "BNB");
+
+        let cfg = networks[0].config(&Config {
+            eth_rpc_url: "wss://eth.example".to_string(),
+            eth_rpc_backup_urls: vec!["wss://backup.example".to_string()],
+            eth_confirmations: 12,
+            ..Default::default()
+        });
+        assert_eq!(cfg.eth_network, "polygon");
+        assert_eq!(cfg.eth_chain.map(|c| c.chain_id), Some(137));
+        assert!(cfg.eth_rpc_backup_urls.is_empty());
+        assert!(cfg.eth_alchemy_mined_transactions);
+        assert_eq!(cfg.eth_confirmations, 12);
+    }
+
+    #[test]
+    fn test_resolve_errors() {
+        let chains = ChainRegistry::default();
+        let resolve = |entries: &[EvmNetworkConfig]| {
+            format!("{:#}", EvmNetwork::resolve(entries, &chains).unwrap_err())
+        };
+        assert!(resolve(&[entry("polygon"), entry("polygon")]).contains("listed twice"));
+        assert!(resolve(&[entry("ethereum")]).contains("its own settings"));
+        assert!(resolve(&[entry("fantom")]).contains("not a chain registry network"));
+        let unknown_id = EvmNetworkConfig {
+            chain_id: Some(250),
+            ..entry("fantom")
+        };
+        assert!(resolve(&[unknown_id]).contains("chain_id 250"));
+        let bad_url = EvmNetworkConfig {
+            rpc_url: "polygon.example".to_string(),
+            ..entry("polygon")
+        };
+        assert!(resolve(&[bad_url]).contains("rpc_url"));
+        let bad_address = EvmNetworkConfig {
+            watched_addresses: vec!["0x01".to_string()],
+            ..entry("polygon")
+        };
+        assert!(resolve(&[bad_address]).contains("invalid watched address"));
+    }
+}
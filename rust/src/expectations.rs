@@ -0,0 +1,664 @@
+//! Expected incoming transfers (`/admin/expectations`).
+//!
+//! A payment processor registers the transfer it is waiting for: chain,
+//! receiving address, token (native if omitted), a minimum or exact amount in
+//! the token's smallest unit, and a deadline. The first published transfer to
+//! that address that meets the amount by the deadline (by its block
+//! timestamp) resolves the expectation with an `expected_transfer_received`
+//! event; the `expectation_deadlines` job resolves the rest with
+//! `expected_transfer_missed` once their deadline has passed. A transfer
+//! resolves at most one expectation, the one with the earliest deadline.
+//!
+//! On Solana a transfer is a `solana_tx` event whose `to` is the address,
+//! with the SPL mint as its token. An expectation can also, or instead, name
+//! a memo: only a watched transaction for the address whose SPL Memo equals
+//! it resolves the expectation, which is how exchange-style deposit flows
+//! identify the payer.
+//!
+//! To reconcile payments to orders when many customers pay the same price
+//! to one address, an exact amount can be fingerprinted: the listener adds a
+//! small offset, unique among the address's pending expectations for that
+//! token, and returns the amount the customer should be asked to pay.
+//!
+//! Expectations live in memory and are lost on restart.
+use std::collections::{BTreeMap, HashSet};
+use std::sync::{Arc, Mutex as StdMutex};
+
+use anyhow::{anyhow, bail};
+use chrono::{DateTime, Utc};
+use ethers::types::U256;
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::config::Config;
+use crate::event_type::EventType;
+use crate::publisher::Publisher;
+use crate::trace_context::TraceContext;
+use crate::Event;
+
+/// Event types that can satisfy an expectation.
+const TRANSFER_TYPES: &[EventType] = &[
+    EventType::Transfer,
+    EventType::Erc20Transfer,
+    EventType::SolanaTx,
+];
+
+/// `POST /admin/expectations` body.
+#[derive(Deserialize, Debug, Default)]
+pub struct NewExpectation {
+    /// Caller-chosen id, e.g. an order number. Generated if omitted.
+    pub id: Option<String>,
+    pub chain: String,
+    pub address: String,
+    /// Token contract or SPL mint; omit for the chain's native currency.
+    pub token: Option<String>,
+    /// Smallest acceptable amount, in the token's smallest unit.
+    pub min_amount: Option<String>,
+    /// Exact amount, in the token's smallest unit. Exactly one of
+    /// `min_amount` and `amount` must be given.
+    pub amount: Option<String>,
+    /// Add an offset in `1..fingerprint` to `amount` that makes it unique
+    /// among the pending expectations for the same address and token.
+    pub fingerprint: Option<u64>,
+    /// Solana only: the SPL Memo the payment must carry. Without an amount,
+    /// any transaction with the memo matches.
+    pub memo: Option<String>,
+    /// RFC 3339.
+    pub deadline: String,
+}
+
+/// A registered expectation; also the `expectation` payload of the events
+/// that resolve it.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct Expectation {
+    pub id: String,
+    pub chain: String,
+    pub address: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_amount: Option<String>,
+    /// Exact amount to match, after fingerprinting.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub amount: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memo: Option<String>,
+    pub deadline: DateTime<Utc>,
+}
+
+impl Expectation {
+    fn matches(&self, event: &Event) -> bool {
+        if event.chain != self.chain || event.spam {
+            return false;
+        }
+        if event_time(&event.timestamp).is_some_and(|t| t > self.deadline) {
+            return false;
+        }
+        if let Some(memo) = &self.memo {
+            if event.event_type != EventType::SolanaTx
+                || event.watched_address.as_deref() != Some(self.address.as_str())
+                || event.memo.as_deref() != Some(memo.as_str())
+            {
+                return false;
+            }
+            if self.min_amount.is_none() && self.amount.is_none() {
+                return true;
+            }
+        }
+        if !TRANSFER_TYPES.contains(&event.event_type) || !self.same_address(&event.to) {
+            return false;
+        }
+        let token = event.token.as_ref().map(|t| t.address.as_str());
+        let same_token = match (&self.token, token) {
+            (None, None) => true,
+            (Some(want), Some(got)) => self.same_address_as(want, got),
+            _ => false,
+        };
+        let Ok(value) = U256::from_dec_str(&event.value) else {
+            return false;
+        };
+        let amount_ok = match (&self.min_amount, &self.amount) {
+            (Some(min), _) => U256::from_dec_str(min).is_ok_and(|min| value >= min),
+            (None, Some(exact)) => U256::from_dec_str(exact).is_ok_and(|exact| value == exact),
+            (None, None) => false,
+        };
+        same_token && amount_ok
+    }
+
+    fn same_address(&self, address: &str) -> bool {
+        self.same_address_as(&self.address, address)
+    }
+
+    /// Ethereum addresses are compared ignoring their checksum case; Solana
+    /// keys are case-sensitive base58.
+    fn same_address_as(&self, a: &str, b: &str) -> bool {
+        match self.chain.as_str() {
+            "ethereum" => a.eq_ignore_ascii_case(b),
+            _ => a == b,
+        }
+    }
+
+    fn same_recipient(&self, other: &Expectation) -> bool {
+        self.chain == other.chain && self.address == other.address && self.token == other.token
+    }
+}
+
+fn parse_amount(name: &str, value: &str) -> anyhow::Result<U256> {
+    U256::from_dec_str(value).map_err(|_| anyhow!("{} must be an integer, got {:?}", name, value))
+}
+
+/// `base` plus an offset in `1..range` that no other pending expectation for
+/// the same recipient is waiting for. The search starts at a random offset so
+/// amounts don't reveal how many orders are open.
+fn fingerprint<'a>(
+    pending: impl Iterator<Item = &'a Expectation>,
+    base: U256,
+    range: u64,
+) -> anyhow::Result<U256> {
+    let taken: HashSet<U256> = pending
+        .filter_map(|e| e.amount.as_deref())
+        .filter_map(|a| U256::from_dec_str(a).ok())
+        .collect();
+    let slots = range - 1;
+    let start = rand::random::<u64>() % slots;
+    (0..slots)
+        .map(|k| base + U256::from(1 + (start + k) % slots))
+        .find(|a| !taken.contains(a))
+        .ok_or_else(|| anyhow!("no unique amount left within fingerprint {}", range))
+}
+
+/// Parse an event timestamp: Unix seconds (Ethereum) or RFC 3339 (Solana).
+fn event_time(timestamp: &str) -> Option<DateTime<Utc>> {
+    match timestamp.parse::<i64>() {
+        Ok(secs) => DateTime::from_timestamp(secs, 0),
+        Err(_) => DateTime::parse_from_rfc3339(timestamp)
+            .ok()
+            .map(|t| t.with_timezone(&Utc)),
+    }
+}
+
+/// Pending expectations by id. Cheap to clone; all clones share state.
+#[derive(Debug, Clone, Default)]
+pub struct Expectations {
+    pending: Arc<StdMutex<BTreeMap<String, Expectation>>>,
+    /// Watched Ethereum addresses, lowercased; expectations must name one.
+    watched_eth: Arc<Vec<String>>,
+    watched_sol: Arc<Vec<String>>,
+    eth_network: Arc<String>,
+    sol_network: Arc<String>,
+}
+
+impl Expectations {
+    pub fn new(cfg: &Config) -> Self {
+        Expectations {
+            pending: Arc::default(),
+            watched_eth: Arc::new(
+                cfg.watched_addresses_eth
+                    .iter()
+                    .map(|a| a.to_lowercase())
+                    .collect(),
+            ),
+            watched_sol: Arc::new(cfg.watched_addresses_sol.clone()),
+            eth_network: Arc::new(cfg.eth_network.clone()),
+            sol_network: Arc::new(cfg.sol_network.clone()),
+        }
+    }
+
+    /// Validate and store an expectation.
+    pub fn register(&self, new: NewExpectation, now: DateTime<Utc>) -> anyhow::Result<Expectation> {
+        let (address, token, watched) = match new.chain.as_str() {
+            "ethereum" => {
+                if new.memo.is_some() {
+                    bail!("memo matching is only supported on solana");
+                }
+                let token = new.token.map(|t| t.to_lowercase());
+                (new.address.to_lowercase(), token, &self.watched_eth)
+            }
+            "solana" => (new.address.clone(), new.token, &self.watched_sol),
+            other => bail!("unknown chain {:?}", other),
+        };
+        if !watched.contains(&address) {
+            bail!("{} is not a watched address", new.address);
+        }
+        let base = match (&new.min_amount, &new.amount) {
+            (None, None) if new.memo.is_some() => {
+                if token.is_some() || new.fingerprint.is_some() {
+                    bail!("token and fingerprint need an amount");
+                }
+                None
+            }
+            (Some(min), None) => {
+                parse_amount("min_amount", min)?;
+                if new.fingerprint.is_some() {
+                    bail!("fingerprint needs an exact amount");
+                }
+                None
+            }
+            (None, Some(exact)) => Some(parse_amount("amount", exact)?),
+            (None, None) if new.chain == "solana" => {
+                bail!("a memo or one of min_amount and amount is required")
+            }
+            _ => bail!("exactly one of min_amount and amount is required"),
+        };
+        if new.fingerprint.is_some_and(|range| range < 2) {
+            bail!("fingerprint must be at least 2");
+        }
+        let deadline = DateTime::parse_from_rfc3339(&new.deadline)
+            .map_err(|e| anyhow!("invalid deadline {:?}: {}", new.deadline, e))?
+            .with_timezone(&Utc);
+        if deadline <= now {
+            bail!("deadline {} is in the past", new.deadline);
+        }
+        let id = new
+            .id
+            .unwrap_or_else(|| format!("{:016x}", rand::random::<u64>()));
+        let mut expectation = Expectation {
+            id: id.clone(),
+            chain: new.chain,
+            address,
+            token,
+            min_amount: new.min_amount,
+            amount: new.amount,
+            memo: new.memo,
+            deadline,
+        };
+        let mut pending = self.pending.lock().unwrap();
+        if pending.contains_key(&id) {
+            bail!("expectation {} already exists", id);
+        }
+        if let (Some(base), Some(range)) = (base, new.fingerprint) {
+            let same = pending.values().filter(|e| e.same_recipient(&expectation));
+            expectation.amount = Some(fingerprint(same, base, range)?.to_string());
+        }
+        pending.insert(id, expectation.clone());
+        info!("Registered expectation {}", expectation.id);
+        Ok(expectation)
+    }
+
+    pub fn list(&self) -> Vec<Expectation> {
+        self.pending.lock().unwrap().values().cloned().collect()
+    }
+
+    /// Drop a pending expectation. Returns false if there was none.
+    pub fn cancel(&self, id: &str) -> bool {
+        self.pending.lock().unwrap().remove(id).is_some()
+    }
+
+    /// Remove and return the expectation `event` satisfies, if any; the one
+    /// with the earliest deadline if several do.
+    fn take_match(&self, event: &Event) -> Option<Expectation> {
+        let mut pending = self.pending.lock().unwrap();
+        let id = pending
+            .values()
+            .filter(|e| e.matches(event))
+            .min_by_key(|e| e.deadline)?
+            .id
+            .clone();
+        pending.remove(&id)
+    }
+
+    /// Remove and return the expectations whose deadline has passed.
+    fn take_expired(&self, now: DateTime<Utc>) -> Vec<Expectation> {
+        let mut pending = self.pending.lock().unwrap();
+        let ids: Vec<String> = pending
+            .values()
+            .filter(|e| e.deadline <= now)
+            .map(|e| e.id.clone())
+            .collect();
+        ids.iter().filter_map(|id| pending.remove(id)).collect()
+    }
+
+    /// Put back an expectation whose resolving event couldn't be published.
+    pub fn restore(&self, expectation: Expectation) {
+        self.pending
+            .lock()
+            .unwrap()
+            .insert(expectation.id.clone(), expectation);
+    }
+
+    /// The `expected_transfer_received` event for the expectation `event`
+    /// resolves, if any. It carries the matching transfer's fields.
+    pub fn resolve(&self, event: &Event) -> Option<Event> {
+        let expectation = self.take_match(event)?;
+        info!(
+            "Expectation {} received in {}",
+            expectation.id, event.tx_hash
+        );
+        let mut received = event.clone();
+        received.event_id = format!("expectation:{}:received", expectation.id);
+        received.event_type = EventType::ExpectedTransferReceived;
+        received.expectation = Some(expectation);
+        Some(received)
+    }
+
+    fn missed_event(&self, expectation: Expectation, now: DateTime<Utc>) -> Event {
+        Event {
+            event_id: format!("expectation:{}:missed", expectation.id),
+            chain: expectation.chain.clone(),
+            network: if expectation.chain == "solana" {
+                self.sol_network.to_string()
+            } else {
+                self.eth_network.to_string()
+            },
+            timestamp: now.to_rfc3339(),
+            to: expectation.address.clone(),
+            event_type: EventType::ExpectedTransferMissed,
+            watched_address: Some(expectation.address.clone()),
+            expectation: Some(expectation),
+            ..Default::default()
+        }
+    }
+}
+
+/// Publish `expected_transfer_missed` for every expectation past its
+/// deadline (`expectation_deadlines` job). Expectations whose event can't be
+/// published are kept for the next run.
+pub async fn expire(publisher: &Publisher) -> anyhow::Result<()> {
+    let expectations = publisher.expectations();
+    let now = Utc::now();
+    let expired = expectations.take_expired(now);
+    for (i, expectation) in expired.iter().enumerate() {
+        info!("Expectation {} missed its deadline", expectation.id);
+        let event = expectations.missed_event(expectation.clone(), now);
+        if let Err(e) = publisher.publish(&event, TraceContext::new_root()).await {
+            for rest in &expired[i..] {
+                expectations.restore(rest.clone());
+            }
+            return Err(e);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Token;
+
+    const WATCHED: &str = "0x70997970c51812dc3a010c7d01b50e0d17dc79c8";
+    const SOL_WATCHED: &str = "7xkZG8s8pJ1kG9gA4q3j5Rm4PpG7mVq79k6h4n8P1yqT";
+    const USDC: &str = "0xa0b86991c6218b36c1d19d4a2e9eb0ce3606eb48";
+
+    fn expectations() -> Expectations {
+        Expectations::new(&Config {
+            eth_network: "mainnet".into(),
+            watched_addresses_eth: vec![WATCHED.to_uppercase().replace("0X", "0x")],
+            sol_network: "devnet".into(),
+            watched_addresses_sol: vec![SOL_WATCHED.into()],
+            ..Default::default()
+        })
+    }
+
+    fn now() -> DateTime<Utc> {
+        DateTime::from_timestamp(1_700_000_000, 0).unwrap()
+    }
+
+    fn new(token: Option<&str>, min_amount: &str) -> NewExpectation {
+        NewExpectation {
+            id: Some("order-1".into()),
+            chain: "ethereum".into(),
+            address: WATCHED.into(),
+            token: token.map(str::to_string),
+            min_amount: Some(min_amount.into()),
+            deadline: "2023-11-14T23:00:00Z".into(),
+            ..Default::default()
+        }
+    }
+
+    fn transfer(value: &str, token: Option<&str>) -> Event {
+        Event {
+            event_id: "eth:0xabc:log0".into(),
+            chain: "ethereum".into(),
+            tx_hash: "0xabc".into(),
+            // 2023-11-14T22:30:00Z
+            timestamp: "1700001000".into(),
+            to: WATCHED.into(),
+            value: value.into(),
+            event_type: if token.is_some() {
+                EventType::Erc20Transfer
+            } else {
+                EventType::Transfer
+            },
+            token: token.map(|t| Token {
+                address: t.into(),
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_register_validates() {
+        let exp = expectations();
+        let err = |new: NewExpectation| exp.register(new, now()).unwrap_err().to_string();
+
+        assert!(err(NewExpectation {
+            chain: "solana".into(),
+            address: SOL_WATCHED.into(),
+            min_amount: None,
+            ..new(None, "")
+        })
+        .contains("a memo or one of"));
+        assert!(err(NewExpectation {
+            chain: "solana".into(),
+            address: SOL_WATCHED.into(),
+            min_amount: None,
+            memo: Some("1042".into()),
+            ..new(Some("Mint1"), "")
+        })
+        .contains("need an amount"));
+        assert!(err(NewExpectation {
+            memo: Some("1042".into()),
+            ..new(None, "1")
+        })
+        .contains("only supported on solana"));
+        assert!(err(NewExpectation {
+            address: "0x01".into(),
+            ..new(None, "1")
+        })
+        .contains("not a watched address"));
+        assert!(err(new(None, "1.5")).contains("min_amount"));
+        assert!(err(NewExpectation {
+            amount: Some("1".into()),
+            ..new(None, "1")
+        })
+        .contains("exactly one of"));
+        assert!(err(NewExpectation {
+            fingerprint: Some(100),
+            ..new(None, "1")
+        })
+        .contains("needs an exact amount"));
+        assert!(err(NewExpectation {
+            deadline: "2023-01-01T00:00:00Z".into(),
+            ..new(None, "1")
+        })
+        .contains("in the past"));
+
+        exp.register(new(None, "1"), now()).unwrap();
+        assert!(err(new(None, "1")).contains("already exists"));
+        assert_eq!(exp.list().len(), 1);
+        assert!(exp.cancel("order-1"));
+        assert!(exp.list().is_empty());
+    }
+
+    #[test]
+    fn test_resolve_needs_token_amount_and_deadline() {
+        let exp = expectations();
+        exp.register(new(Some(&USDC.to_uppercase()), "1000"), now())
+            .unwrap();
+
+        assert!(exp.resolve(&transfer("5000", None)).is_none());
+        assert!(exp.resolve(&transfer("999", Some(USDC))).is_none());
+        let mut late = transfer("5000", Some(USDC));
+        late.timestamp = "1700006400".into();
+        assert!(exp.resolve(&late).is_none());
+
+        let received = exp.resolve(&transfer("1000", Some(USDC))).unwrap();
+        assert_eq!(received.event_id, "expectation:order-1:received");
+        assert_eq!(received.event_type, EventType::ExpectedTransferReceived);
+        assert_eq!(received.tx_hash, "0xabc");
+        assert!(exp.list().is_empty());
+        // Resolved once only.
+        assert!(exp.resolve(&transfer("1000", Some(USDC))).is_none());
+    }
+
+    #[test]
+    fn test_fingerprinted_amounts_are_unique_and_exact() {
+        let exp = expectations();
+        let mut amounts = Vec::new();
+        for id in ["a", "b", "c"] {
+            let registered = exp
+                .register(
+                    NewExpectation {
+                        id: Some(id.into()),
+                        min_amount: None,
+                        amount: Some("25000000".into()),
+                        fingerprint: Some(4),
+                        ..new(Some(USDC), "")
+                    },
+                    now(),
+                )
+                .unwrap();
+            amounts.push(registered.amount.unwrap());
+        }
+        amounts.sort();
+        assert_eq!(amounts, ["25000001", "25000002", "25000003"]);
+        let full = exp.register(
+            NewExpectation {
+                id: Some("d".into()),
+                min_amount: None,
+                amount: Some("25000000".into()),
+                fingerprint: Some(4),
+                ..new(Some(USDC), "")
+            },
+            now(),
+        );
+        assert!(full.unwrap_err().to_string().contains("no unique amount"));
+
+        // Only the exact amount matches, and only its own expectation.
+        assert!(exp.resolve(&transfer("25000000", Some(USDC))).is_none());
+        let paid = exp.list().into_iter().find(|e| e.id == "b").unwrap();
+        let received = exp
+            .resolve(&transfer(paid.amount.as_deref().unwrap(), Some(USDC)))
+            .unwrap();
+        assert_eq!(received.expectation.unwrap().id, "b");
+        assert_eq!(exp.list().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_expire_publishes_missed() {
+        let (publisher, mut rx) = Publisher::in_memory();
+        let publisher = publisher.with_expectations(expectations());
+        let mut pending = new(None, "1");
+        pending.deadline = (Utc::now() + chrono::Duration::hours(1)).to_rfc3339();
+        publisher
+            .expectations()
+            .register(pending, Utc::now())
+            .unwrap();
+        publisher
+            .expectations()
+            .register(
+                NewExpectation {
+                    id: Some("order-2".into()),
+                    ..new(None, "1")
+                },
+                now(),
+            )
+            .unwrap();
+
+        expire(&publisher).await.unwrap();
+        let missed: serde_json::Value =
+            serde_json::from_str(&rx.recv().await.unwrap().payload).unwrap();
+        assert_eq!(missed["event_type"], "expected_transfer_missed");
+        assert_eq!(missed["network"], "mainnet");
+        assert_eq!(missed["expectation"]["id"], "order-2");
+        assert!(rx.try_recv().is_err());
+        assert_eq!(publisher.expectations().list().len(), 1);
+    }
+
+    #[test]
+    fn test_solana_expectation_matches_memo() {
+        let exp = expectations();
+        exp.register(
+            NewExpectation {
+                chain: "solana".into(),
+                address: SOL_WATCHED.into(),
+                min_amount: None,
+                memo: Some("deposit 1042".into()),
+                ..new(None, "")
+            },
+            now(),
+        )
+        .unwrap();
+        let tx = |memo: &str| Event {
+            chain: "solana".into(),
+            tx_hash: "5sig".into(),
+            timestamp: "2023-11-14T22:30:00+00:00".into(),
+            event_type: EventType::SolanaTx,
+            memo: Some(memo.into()),
+            watched_address: Some(SOL_WATCHED.into()),
+            ..Default::default()
+        };
+
+        assert!(exp.resolve(&tx("deposit 1043")).is_none());
+        let received = exp.resolve(&tx("deposit 1042")).unwrap();
+        assert_eq!(received.memo.as_deref(), Some("deposit 1042"));
+        assert_eq!(received.expectation.unwrap().id, "order-1");
+    }
+
+    #[test]
+    fn test_solana_expectation_matches_transfer() {
+        const MINT: &str = "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v";
+        let exp = expectations();
+        let register = |id: &str, memo: Option<&str>| {
+            exp.register(
+                NewExpectation {
+                    id: Some(id.into()),
+                    chain: "solana".into(),
+                    address: SOL_WATCHED.into(),
+                    memo: memo.map(str::to_string),
+                    ..new(Some(MINT), "1000000")
+                },
+                now(),
+            )
+            .unwrap()
+        };
+        // Mints are base58 and kept as given.
+        assert_eq!(register("plain", None).token.as_deref(), Some(MINT));
+        register("memo", Some("deposit 1042"));
+        let tx = |to: &str, value: &str, mint: &str| Event {
+            chain: "solana".into(),
+            tx_hash: "5sig".into(),
+            timestamp: "2023-11-14T22:30:00+00:00".into(),
+            to: to.into(),
+            value: value.into(),
+            event_type: EventType::SolanaTx,
+            token: Some(Token {
+                address: mint.into(),
+                ..Default::default()
+            }),
+            watched_address: Some(SOL_WATCHED.into()),
+            ..Default::default()
+        };
+
+        assert!(exp.resolve(&tx(SOL_WATCHED, "999999", MINT)).is_none());
+        assert!(exp
+            .resolve(&tx(SOL_WATCHED, "1000000", &MINT.to_lowercase()))
+            .is_none());
+        // Not received by the address, e.g. sent from it.
+        assert!(exp.resolve(&tx("", "1000000", MINT)).is_none());
+        let received = exp.resolve(&tx(SOL_WATCHED, "1000000", MINT)).unwrap();
+        assert_eq!(received.expectation.unwrap().id, "plain");
+
+        // With a memo, the amount alone isn't enough.
+        assert!(exp.resolve(&tx(SOL_WATCHED, "1000000", MINT)).is_none());
+        let with_memo = Event {
+            memo: Some("deposit 1042".into()),
+            ..tx(SOL_WATCHED, "1500000", MINT)
+        };
+        assert_eq!(
+            exp.resolve(&with_memo).unwrap().expectation.unwrap().id,
+            "memo"
+        );
+    }
+}
@@ -0,0 +1,240 @@
+//! Block explorer links in published events (`explorer_url`).
+//!
+//! Alerting consumers want clickable links, and building them needs the
+//! explorer of the exact chain and cluster the event came from. The publisher
+//! fills them in just before an event is queued: Ethereum links, and those of
+//! the `[[evm_networks]]` chains, use the explorer of the chain registry
+//! entry (see [`crate::chains`]), Solana links
+//! use `SOL_EXPLORER_URL` with a `cluster` parameter off mainnet, and Tron
+//! and `[[substrate_networks]]` links the `explorer_url` of their section
+//! (Tronscan and Subscan by default). Events
+//! of a chain without a known explorer carry no links.
+use std::collections::HashMap;
+
+use serde::Serialize;
+
+use crate::config::Config;
+use crate::Event;
+
+/// Explorer pages of an event's transaction and counterparties.
+#[derive(Serialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct ExplorerUrls {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tx: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub from: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub to: Option<String>,
+}
+
+/// One explorer's URL layout.
+#[derive(Debug, Clone)]
+struct Explorer {
+    base: String,
+    /// Path segment of transaction pages.
+    tx_path: &'static str,
+    /// Path segment of address pages.
+    address_path: &'static str,
+    /// Query appended to every link, e.g. `?cluster=devnet`.
+    query: String,
+}
+
+impl Explorer {
+    fn link(&self, path: &str, id: &str) -> Option<String> {
+        (!id.is_empty()).then(|| format!("{}/{}/{}{}", self.base, path, id, self.query))
+    }
+}
+
+/// Link builder for the configured chains.
+#[derive(Debug, Clone, Default)]
+pub struct Explorers {
+    eth: Option<Explorer>,
+    sol: Option<Explorer>,
+    /// By `[[evm_networks]]` and `[[substrate_networks]]` chain.
+    evm: HashMap<String, Explorer>,
+    tron: Option<Explorer>,
+}
+
+impl Explorers {
+    pub fn new(cfg: &Config) -> Self {
+        let evm_explorer = |base: String| Explorer {
+            base,
+            tx_path: "tx",
+            address_path: "address",
+            query: String::new(),
+        };
+        let eth = cfg
+            .eth_chain
+            .as_ref()
+            .and_then(|chain| chain.explorer_url.clone())
+            .map(evm_explorer);
+        let evm = cfg
+            .evm_networks
+            .iter()
+            .filter_map(|n| {
+                let base = n.info.explorer_url.clone()?;
+                Some((n.chain.clone(), evm_explorer(base)))
+            })
+            .chain(cfg.substrate_networks.iter().map(|n| {
+                let subscan = Explorer {
+                    base: n.explorer_url.clone(),
+                    tx_path: "extrinsic",
+                    address_path: "account",
+                    query: String::new(),
+                };
+                (n.chain.clone(), subscan)
+            }))
+            .collect();
+        let sol = Some(Explorer {
+            base: cfg.sol_explorer_url.trim_end_matches('/').to_string(),
+            tx_path: "tx",
+            address_path: "account",
+            query: match cfg.sol_network.as_str() {
+                "mainnet" | "mainnet-beta" => String::new(),
+                cluster => format!("?cluster={}", cluster),
+            },
+        });
+        let tron = cfg.tron.as_ref().map(|tron| Explorer {
+            base: tron.explorer_url.trim_end_matches('/').to_string(),
+            tx_path: "transaction",
+            address_path: "address",
+            query: String::new(),
+        });
+        Explorers {
+            eth,
+            sol,
+            evm,
+            tron,
+        }
+    }
+
+    /// Links for `event`, or `None` if its chain has no explorer or it has
+    /// neither a transaction nor counterparties.
+    pub fn links(&self, event: &Event) -> Option<ExplorerUrls> {
+        let explorer = match event.chain.as_str() {
+            "ethereum" => self.eth.as_ref(),
+            "solana" => self.sol.as_ref(),
+            "tron" => self.tron.as_ref(),
+            chain => self.evm.get(chain),
+        }?;
+        let urls = ExplorerUrls {
+            tx: explorer.link(explorer.tx_path, &event.tx_hash),
+            from: explorer.link(explorer.address_path, &event.from),
+            to: explorer.link(explorer.address_path, &event.to),
+        };
+        (urls != ExplorerUrls::default()).then_some(urls)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chains::ChainRegistry;
+
+    fn explorers(sol_network: &str) -> Explorers {
+        Explorers::new(&Config {
+            eth_chain: ChainRegistry::default().get(1).cloned(),
+            sol_network: sol_network.into(),
+            sol_explorer_url: "https://solscan.io/".into(),
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn test_ethereum_links_use_registry_explorer() {
+        let event = Event {
+            chain: "ethereum".into(),
+            tx_hash: "0xabc".into(),
+            from: "0x01".into(),
+            to: "0x02".into(),
+            ..Default::default()
+        };
+        let urls = explorers("mainnet").links(&event).unwrap();
+        assert_eq!(urls.tx.as_deref(), Some("https://etherscan.io/tx/0xabc"));
+        assert_eq!(
+            urls.from.as_deref(),
+            Some("https://etherscan.io/address/0x01")
+        );
+        assert_eq!(
+            urls.to.as_deref(),
+            Some("https://etherscan.io/address/0x02")
+        );
+    }
+
+    #[test]
+    fn test_solana_cluster_and_missing_parts() {
+        let event = Event {
+            chain: "solana".into(),
+            tx_hash: "5sig".into(),
+            ..Default::default()
+        };
+        let urls = explorers("devnet").links(&event).unwrap();
+        assert_eq!(
+            urls.tx.as_deref(),
+            Some("https://solscan.io/tx/5sig?cluster=devnet")
+        );
+        assert_eq!((urls.from, urls.to), (None, None));
+
+        let heartbeat = Event {
+            chain: "solana".into(),
+            ..Default::default()
+        };
+        assert!(explorers("mainnet").links(&heartbeat).is_none());
+        let unknown_chain = Event {
+            chain: "ethereum".into(),
+            tx_hash: "0xabc".into(),
+            ..Default::default()
+        };
+        assert!(Explorers::default().links(&unknown_chain).is_none());
+    }
+
+    #[test]
+    fn test_tron_links_use_tronscan_layout() {
+        let tron: crate::tron::TronConfig = toml::from_str("").unwrap();
+        let explorers = Explorers::new(&Config {
+            tron: Some(tron),
+            ..Default::default()
+        });
+        let event = Event {
+            chain: "tron".into(),
+            tx_hash: "c0ffee".into(),
+            to: "TR7NHqjeKQxGTCi8q8ZY4pL8otSzgjLj6t".into(),
+            ..Default::default()
+        };
+        let urls = explorers.links(&event).unwrap();
+        assert_eq!(
+            urls.tx.as_deref(),
+            Some("https://tronscan.org/#/transaction/c0ffee")
+        );
+        assert_eq!(
+            urls.to.as_deref(),
+            Some("https://tronscan.org/#/address/TR7NHqjeKQxGTCi8q8ZY4pL8otSzgjLj6t")
+        );
+    }
+
+    #[test]
+    fn test_substrate_links_use_subscan_layout() {
+        use crate::substrate::{SubstrateNetwork, SubstrateNetworkConfig};
+        let polkadot: SubstrateNetworkConfig =
+            toml::from_str("chain = \"polkadot\"\nrpc_url = \"wss://rpc.polkadot.io\"").unwrap();
+        let explorers = Explorers::new(&Config {
+            substrate_networks: SubstrateNetwork::resolve(&[polkadot], &[]).unwrap(),
+            ..Default::default()
+        });
+        let event = Event {
+            chain: "polkadot".into(),
+            tx_hash: "18000000-2".into(),
+            from: "15oF4uVJwmo4TdGW7VfQxNLavjCXviqxT9S1MgbjMNHr6Sp5".into(),
+            ..Default::default()
+        };
+        let urls = explorers.links(&event).unwrap();
+        assert_eq!(
+            urls.tx.as_deref(),
+            Some("https://polkadot.subscan.io/extrinsic/18000000-2")
+        );
+        assert_eq!(
+            urls.from.as_deref(),
+            Some("https://polkadot.subscan.io/account/15oF4uVJwmo4TdGW7VfQxNLavjCXviqxT9S1MgbjMNHr6Sp5")
+        );
+    }
+}
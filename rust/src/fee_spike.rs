@@ -0,0 +1,308 @@
+//! Fee spike alerts for watched senders (`[fee_spike]` in `CONFIG_FILE`).
+//!
+//! A transaction that pays many times the block's base fee is usually a
+//! mistake (a gas price typed in the wrong unit, a wallet bug) or a sign
+//! that someone else is signing for the address. When a watched address
+//! sends a transaction whose effective gas price is above `multiple` times
+//! the base fee of its block, and it paid at least `min_overpayment` ETH
+//! more than the base fee would have cost, the publisher follows its
+//! `transfer` event with a `fee_spike` copy:
+//!
+//! ```toml
+//! [fee_spike]
+//! multiple = 3.0          # default
+//! min_overpayment = 0.01  # ETH, default 0
+//! ```
+//!
+//! `fee_spike` holds the gas used, the effective gas price, the base fee,
+//! the total fee and the overpayment estimate: what was paid above the base
+//! fee, priority fee included. `alert` has the rule `fee_spike`, severity
+//! `high`, and the address book label of the sender. The trackers read the
+//! receipt of every transaction sent by a watched address for this, so it
+//! costs one more request per outgoing transaction when blocks are fetched
+//! without receipts (`newHeads`, `alchemy_minedTransactions`). Blocks
+//! without a base fee (pre-London chains) are never alerted on.
+use anyhow::bail;
+use ethers::providers::Middleware;
+use ethers::types::{Transaction, TransactionReceipt, U256};
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+use crate::address_book::{AddressBook, Alert, Severity};
+use crate::config::Config;
+use crate::event_type::EventType;
+use crate::Event;
+
+/// Rule name of `fee_spike` alerts.
+pub const FEE_SPIKE: &str = "fee_spike";
+
+fn default_multiple() -> f64 {
+    3.0
+}
+
+/// The `[fee_spike]` section of the config file.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FeeSpikeConfig {
+    /// Effective gas price over base fee above which a transaction is
+    /// alerted.
+    #[serde(default = "default_multiple")]
+    pub multiple: f64,
+    /// Least overpayment alerted, in ETH.
+    #[serde(default)]
+    pub min_overpayment: f64,
+}
+
+impl FeeSpikeConfig {
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if !self.multiple.is_finite() || self.multiple < 1.0 {
+            bail!("multiple must be at least 1");
+        }
+        if !self.min_overpayment.is_finite() || self.min_overpayment < 0.0 {
+            bail!("min_overpayment must not be negative");
+        }
+        Ok(())
+    }
+}
+
+/// What a mined transaction paid for gas. Carried on its `transfer` event
+/// for the publisher's check; never serialized.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct GasPaid {
+    pub gas_used: U256,
+    /// Effective price per gas, priority fee included.
+    pub gas_price: U256,
+    pub base_fee: U256,
+}
+
+impl GasPaid {
+    /// From `tx`'s receipt and the base fee of its block. `None` if the
+    /// receipt lacks the gas used, or the price can't be told.
+    pub fn of(tx: &Transaction, receipt: &TransactionReceipt, base_fee: U256) -> Option<Self> {
+        let gas_price = receipt
+            .effective_gas_price
+            .or_else(|| effective_gas_price(tx, base_fee))?;
+        Some(GasPaid {
+            gas_used: receipt.gas_used?,
+            gas_price,
+            base_fee,
+        })
+    }
+}
+
+/// The price per gas `tx` paid in a block with `base_fee`: the gas price of
+/// a legacy transaction, or the base fee plus the priority fee, capped at
+/// the max fee, of an EIP-1559 one.
+fn effective_gas_price(tx: &Transaction, base_fee: U256) -> Option<U256> {
+    match (tx.max_fee_per_gas, tx.max_priority_fee_per_gas) {
+        (Some(max_fee), Some(tip)) => Some(max_fee.min(base_fee.saturating_add(tip))),
+        _ => tx.gas_price,
+    }
+}
+
+/// Fetch what `tx` paid: its receipt, and the base fee of its block unless
+/// given. `None` if either can't be had.
+pub async fn fetch<M: Middleware>(
+    provider: &M,
+    tx: &Transaction,
+    base_fee: Option<U256>,
+) -> Option<GasPaid> {
+    let receipt = match provider.get_transaction_receipt(tx.hash).await {
+        Ok(receipt) => receipt?,
+        Err(e) => {
+            debug!("Failed to fetch receipt for {:?}: {}", tx.hash, e);
+            return None;
+        }
+    };
+    let base_fee = match base_fee {
+        Some(base_fee) => base_fee,
+        None => match provider.get_block(receipt.block_hash?).await {
+            Ok(block) => block?.base_fee_per_gas?,
+            Err(e) => {
+                debug!("Failed to fetch block of {:?}: {}", tx.hash, e);
+                return None;
+            }
+        },
+    };
+    GasPaid::of(tx, &receipt, base_fee)
+}
+
+/// Payload of a `fee_spike` event. Amounts are in wei.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct FeeSpike {
+    pub gas_used: u64,
+    /// Effective price per gas.
+    pub gas_price: String,
+    /// Base fee per gas of the block.
+    pub base_fee: String,
+    /// `gas_used` times `gas_price`.
+    pub fee: String,
+    /// What was paid above the base fee.
+    pub overpayment: String,
+}
+
+/// Thresholds of the check, off without a `[fee_spike]` section.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FeeGuard {
+    limits: Option<(f64, U256)>,
+}
+
+impl FeeGuard {
+    pub fn new(cfg: &Config) -> Self {
+        FeeGuard {
+            limits: cfg.fee_spike.map(|spike| {
+                let min_overpayment =
+                    ethers::utils::parse_ether(spike.min_overpayment).unwrap_or_default();
+                (spike.multiple, min_overpayment)
+            }),
+        }
+    }
+
+    /// Whether transactions are checked at all, so the trackers know to
+    /// fetch what they paid.
+    pub fn enabled(&self) -> bool {
+        self.limits.is_some()
+    }
+
+    /// The `fee_spike` alert for `event`, if it is a transaction of a
+    /// watched sender that paid too much over the base fee.
+    pub fn check(&self, event: &Event, book: &AddressBook) -> Option<Event> {
+        let (multiple, min_overpayment) = self.limits?;
+        if event.event_type != EventType::Transfer {
+            return None;
+        }
+        let gas = event.gas?;
+        if gas.base_fee.is_zero() || gas.gas_price <= gas.base_fee {
+            return None;
+        }
+        let ratio = gas.gas_price.low_u128() as f64 / gas.base_fee.low_u128() as f64;
+        let overpayment = gas.gas_used.saturating_mul(gas.gas_price - gas.base_fee);
+        if ratio <= multiple || overpayment < min_overpayment {
+            return None;
+        }
+        Some(Event {
+            event_id: format!("{}:fee_spike", event.event_id),
+            event_type: EventType::FeeSpike,
+            alert: Some(Alert {
+                rule: FEE_SPIKE.to_string(),
+                severity: Severity::High,
+                address: event.from.clone(),
+                label: book.label(&event.chain, &event.from).map(str::to_string),
+            }),
+            fee_spike: Some(FeeSpike {
+                gas_used: gas.gas_used.low_u64(),
+                gas_price: gas.gas_price.to_string(),
+                base_fee: gas.base_fee.to_string(),
+                fee: gas.gas_used.saturating_mul(gas.gas_price).to_string(),
+                overpayment: overpayment.to_string(),
+            }),
+            gas: None,
+            ..event.clone()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::address_book::AddressEntry;
+
+    const SENDER: &str = "0x70997970c51812dc3a010c7d01b50e0d17dc79c8";
+    const GWEI: u64 = 1_000_000_000;
+
+    fn guard(multiple: f64, min_overpayment: f64) -> FeeGuard {
+        FeeGuard::new(&Config {
+            fee_spike: Some(FeeSpikeConfig {
+                multiple,
+                min_overpayment,
+            }),
+            ..Default::default()
+        })
+    }
+
+    fn transfer(gas_price_gwei: u64, base_fee_gwei: u64) -> Event {
+        Event {
+            event_id: "eth:0xabc".into(),
+            chain: "ethereum".into(),
+            tx_hash: "0xabc".into(),
+            from: SENDER.into(),
+            event_type: EventType::Transfer,
+            gas: Some(GasPaid {
+                gas_used: 21_000.into(),
+                gas_price: (gas_price_gwei * GWEI).into(),
+                base_fee: (base_fee_gwei * GWEI).into(),
+            }),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_spike_alerted_with_overpayment() {
+        let book = AddressBook::new(&[AddressEntry {
+            address: format!("ethereum:{}", SENDER),
+            label: Some("Treasury hot wallet".into()),
+            cold_storage: false,
+            severity: Severity::Low,
+        }])
+        .unwrap();
+        let spike = guard(3.0, 0.0)
+            .check(&transfer(400, 20), &book)
+            .expect("20x the base fee");
+        assert_eq!(spike.event_id, "eth:0xabc:fee_spike");
+        assert_eq!(spike.event_type, EventType::FeeSpike);
+        assert_eq!(spike.gas, None);
+        let alert = spike.alert.unwrap();
+        assert_eq!(alert.severity, Severity::High);
+        assert_eq!(alert.label.as_deref(), Some("Treasury hot wallet"));
+        let fee = spike.fee_spike.unwrap();
+        assert_eq!(fee.fee, (21_000 * 400 * GWEI).to_string());
+        assert_eq!(fee.overpayment, (21_000 * 380 * GWEI).to_string());
+    }
+
+    #[test]
+    fn test_ordinary_fees_pass() {
+        let book = AddressBook::default();
+        assert!(guard(3.0, 0.0).check(&transfer(22, 20), &book).is_none());
+        assert!(guard(3.0, 0.0).check(&transfer(60, 20), &book).is_none());
+        // 10x, but only 21000 * 9 gwei over.
+        assert!(guard(3.0, 0.01).check(&transfer(10, 1), &book).is_none());
+        assert!(FeeGuard::default()
+            .check(&transfer(400, 20), &book)
+            .is_none());
+        let erc20 = Event {
+            event_type: EventType::Erc20Transfer,
+            ..transfer(400, 20)
+        };
+        assert!(guard(3.0, 0.0).check(&erc20, &book).is_none());
+    }
+
+    #[test]
+    fn test_effective_gas_price() {
+        let eip1559 = Transaction {
+            max_fee_per_gas: Some((100 * GWEI).into()),
+            max_priority_fee_per_gas: Some((2 * GWEI).into()),
+            ..Default::default()
+        };
+        let receipt = TransactionReceipt {
+            gas_used: Some(21_000.into()),
+            ..Default::default()
+        };
+        let paid = GasPaid::of(&eip1559, &receipt, (30 * GWEI).into()).unwrap();
+        assert_eq!(paid.gas_price, U256::from(32 * GWEI));
+        let capped = GasPaid::of(&eip1559, &receipt, (120 * GWEI).into()).unwrap();
+        assert_eq!(capped.gas_price, U256::from(100 * GWEI));
+        let legacy = Transaction {
+            gas_price: Some((50 * GWEI).into()),
+            ..Default::default()
+        };
+        let paid = GasPaid::of(&legacy, &receipt, (30 * GWEI).into()).unwrap();
+        assert_eq!(paid.gas_price, U256::from(50 * GWEI));
+
+        assert!(FeeSpikeConfig {
+            multiple: 0.5,
+            min_overpayment: 0.0
+        }
+        .validate()
+        .is_err());
+    }
+}
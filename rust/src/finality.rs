@@ -0,0 +1,277 @@
+//! Finality checks for Solana events (`solana_finality` scheduler job).
+//!
+//! The Solana trackers publish transactions at `confirmed` commitment, and a
+//! confirmed block can still be on a fork the cluster abandons. The publisher
+//! remembers every `solana_tx` event until its transaction is finalized; the
+//! job looks the signatures up with history at `finalized` commitment and
+//! forgets the ones that made it. A transaction that is on no fork once the
+//! finalized slot is past its blockhash's validity window can't land anymore,
+//! so its events are retracted: each is published again with `event_type`
+//! `transaction_retracted` and event id `<original id>:retracted`. A
+//! transaction that landed in a different block than the one it was first
+//! seen in keeps waiting for that block to finalize. Skipped slots have no
+//! block and no transactions, so they need no handling.
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex as StdMutex};
+
+use solana_client::rpc_client::RpcClient;
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::signature::Signature;
+use solana_transaction_status::TransactionStatus;
+use tracing::{debug, info, warn};
+
+use crate::event_type::EventType;
+use crate::publisher::Publisher;
+use crate::trace_context::TraceContext;
+use crate::Event;
+
+/// Transactions awaiting finality. Once full, further transactions are
+/// published without a check.
+pub const PENDING_CAPACITY: usize = 50_000;
+
+/// Slots a recent blockhash stays valid for. A transaction can only land
+/// within this many slots of the block it was first seen in.
+pub const BLOCKHASH_VALIDITY_SLOTS: u64 = 150;
+
+/// Signatures per `getSignatureStatuses` call, the RPC maximum.
+const STATUS_BATCH: usize = 256;
+
+#[derive(Debug, Clone)]
+struct PendingTx {
+    slot: u64,
+    events: Vec<Event>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Outcome {
+    Finalized,
+    Pending,
+    Retracted,
+}
+
+/// What to do about a transaction first seen in `slot`, given its status and
+/// the cluster's finalized slot.
+fn outcome(slot: u64, status: Option<&TransactionStatus>, finalized_slot: u64) -> Outcome {
+    match status {
+        Some(status) if status.satisfies_commitment(CommitmentConfig::finalized()) => {
+            Outcome::Finalized
+        }
+        Some(_) => Outcome::Pending,
+        None if finalized_slot > slot + BLOCKHASH_VALIDITY_SLOTS => Outcome::Retracted,
+        None => Outcome::Pending,
+    }
+}
+
+/// A copy of `event` saying its transaction never finalized.
+fn retraction(event: &Event) -> Event {
+    Event {
+        event_id: format!("{}:retracted", event.event_id),
+        event_type: EventType::TransactionRetracted,
+        ..event.clone()
+    }
+}
+
+/// Solana events published but not yet finalized, recorded by the
+/// publisher. Cheap to clone; all clones share state.
+#[derive(Debug, Clone, Default)]
+pub struct Finality {
+    pending: Arc<StdMutex<HashMap<Signature, PendingTx>>>,
+}
+
+impl Finality {
+    /// Remember a `solana_tx` event until its transaction is finalized.
+    pub fn record(&self, event: &Event) {
+        if event.chain != "solana" || event.event_type != EventType::SolanaTx {
+            return;
+        }
+        let (Some(slot), Ok(signature)) = (event.slot, Signature::from_str(&event.tx_hash)) else {
+            return;
+        };
+        let mut pending = self.pending.lock().unwrap();
+        if pending.len() >= PENDING_CAPACITY && !pending.contains_key(&signature) {
+            debug!("Not tracking finality of {}: too many pending", signature);
+            return;
+        }
+        let tx = pending.entry(signature).or_insert(PendingTx {
+            slot,
+            events: Vec::new(),
+        });
+        if !tx.events.iter().any(|e| e.event_id == event.event_id) {
+            tx.events.push(event.clone());
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.pending.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Pending signatures first seen at or below `finalized_slot`, with
+    /// their slots.
+    fn due(&self, finalized_slot: u64) -> Vec<(Signature, u64)> {
+        self.pending
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(_, tx)| tx.slot <= finalized_slot)
+            .map(|(signature, tx)| (*signature, tx.slot))
+            .collect()
+    }
+
+    fn take(&self, signature: &Signature) -> Option<PendingTx> {
+        self.pending.lock().unwrap().remove(signature)
+    }
+
+    fn restore(&self, signature: Signature, tx: PendingTx) {
+        self.pending.lock().unwrap().insert(signature, tx);
+    }
+}
+
+/// Look up the pending transactions the finalized chain has caught up with,
+/// forgetting finalized ones and retracting the events of dropped ones.
+pub async fn check(rpc: &Arc<RpcClient>, publisher: &Publisher) -> anyhow::Result<()> {
+    let finality = publisher.finality();
+    if finality.is_empty() {
+        return Ok(());
+    }
+    let finalized_slot = tokio::task::spawn_blocking({
+        let rpc = Arc::clone(rpc);
+        move || rpc.get_slot_with_commitment(CommitmentConfig::finalized())
+    })
+    .await??;
+    let due = finality.due(finalized_slot);
+    let (mut finalized, mut retracted) = (0, 0);
+    for batch in due.chunks(STATUS_BATCH) {
+        let signatures: Vec<Signature> = batch.iter().map(|(signature, _)| *signature).collect();
+        let statuses = tokio::task::spawn_blocking({
+            let rpc = Arc::clone(rpc);
+            move || rpc.get_signature_statuses_with_history(&signatures)
+        })
+        .await??
+        .value;
+        for ((signature, slot), status) in batch.iter().zip(&statuses) {
+            match outcome(*slot, status.as_ref(), finalized_slot) {
+                Outcome::Pending => {}
+                Outcome::Finalized => {
+                    finality.take(signature);
+                    finalized += 1;
+                }
+                Outcome::Retracted => {
+                    let Some(tx) = finality.take(signature) else {
+                        continue;
+                    };
+                    warn!(
+                        "Solana tx {} from slot {} never finalized, retracting {} events",
+                        signature,
+                        slot,
+                        tx.events.len()
+                    );
+                    let mut failed = None;
+                    for event in &tx.events {
+                        if let Err(e) = publisher
+                            .publish(&retraction(event), TraceContext::new_root())
+                            .await
+                        {
+                            failed = Some(e);
+                            break;
+                        }
+                    }
+                    if let Some(e) = failed {
+                        finality.restore(*signature, tx);
+                        return Err(e);
+                    }
+                    retracted += 1;
+                }
+            }
+        }
+    }
+    info!(
+        "Solana finality at slot {}: {} finalized, {} retracted, {} pending",
+        finalized_slot,
+        finalized,
+        retracted,
+        finality.len()
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_transaction_status::TransactionConfirmationStatus;
+
+    fn solana_tx(slot: u64, watched: &str) -> Event {
+        let signature = Signature::from([7; 64]).to_string();
+        Event {
+            event_id: format!("sol:{}:{}", signature, watched),
+            chain: "solana".into(),
+            tx_hash: signature,
+            event_type: EventType::SolanaTx,
+            slot: Some(slot),
+            watched_address: Some(watched.into()),
+            ..Default::default()
+        }
+    }
+
+    fn status(confirmation: TransactionConfirmationStatus) -> TransactionStatus {
+        TransactionStatus {
+            slot: 100,
+            confirmations: None,
+            status: Ok(()),
+            err: None,
+            confirmation_status: Some(confirmation),
+        }
+    }
+
+    #[test]
+    fn test_outcomes() {
+        let finalized = status(TransactionConfirmationStatus::Finalized);
+        let confirmed = status(TransactionConfirmationStatus::Confirmed);
+        assert_eq!(outcome(100, Some(&finalized), 100), Outcome::Finalized);
+        assert_eq!(outcome(100, Some(&confirmed), 1_000), Outcome::Pending);
+        // Not found, but the blockhash may still be valid.
+        assert_eq!(
+            outcome(100, None, 100 + BLOCKHASH_VALIDITY_SLOTS),
+            Outcome::Pending
+        );
+        assert_eq!(
+            outcome(100, None, 101 + BLOCKHASH_VALIDITY_SLOTS),
+            Outcome::Retracted
+        );
+    }
+
+    #[test]
+    fn test_records_solana_txs_once_per_event() {
+        let finality = Finality::default();
+        finality.record(&solana_tx(100, "A"));
+        finality.record(&solana_tx(100, "A"));
+        finality.record(&solana_tx(100, "B"));
+        finality.record(&retraction(&solana_tx(100, "C")));
+        finality.record(&Event {
+            chain: "ethereum".into(),
+            event_type: EventType::Transfer,
+            slot: Some(1),
+            ..Default::default()
+        });
+        assert_eq!(finality.len(), 1);
+        assert!(finality.due(99).is_empty());
+        let due = finality.due(100);
+        assert_eq!(due.len(), 1);
+        assert_eq!(finality.take(&due[0].0).unwrap().events.len(), 2);
+        assert_eq!(finality.len(), 0);
+    }
+
+    #[test]
+    fn test_retraction_shape() {
+        let event = solana_tx(100, "A");
+        let retracted = retraction(&event);
+        assert_eq!(retracted.event_id, format!("{}:retracted", event.event_id));
+        assert_eq!(retracted.event_type, EventType::TransactionRetracted);
+        assert_eq!(retracted.tx_hash, event.tx_hash);
+        assert_eq!(retracted.slot, Some(100));
+    }
+}
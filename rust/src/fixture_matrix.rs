@@ -0,0 +1,515 @@
+//! Golden fixture matrix: one synthetic RPC capture per chain and kind of
+//! transaction the trackers normalize (native, ERC-20, NFT, SPL, failed,
+//! contract deployment), replayed through the same code as `tracker replay`
+//! and compared with `tests/golden/matrix/<chain>-<case>.events.json`.
+//!
+//! Run with `UPDATE_GOLDENS=1` to (re)write the goldens after an intended
+//! change to the normalized output, then review the diff. A new case only
+//! needs an entry in `cases()`; a new tracker event type also goes in
+//! `TRACKER_EVENT_TYPES`, and the coverage test fails until a case produces
+//! it.
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use serde_json::{json, Value};
+use solana_sdk::signature::Signature;
+
+use crate::capture::{replay_eth, replay_sol, Capture};
+use crate::config::Config;
+use crate::event_type::EventType;
+use crate::publisher::Publisher;
+
+/// Event types published by the block and transaction pipelines. The other
+/// types come from scheduler jobs, which work from tracker state rather than
+/// RPC responses.
+const TRACKER_EVENT_TYPES: &[EventType] = &[
+    EventType::Transfer,
+    EventType::Erc20Transfer,
+    EventType::SolanaTx,
+];
+
+const WATCHED_ETH: &str = "0x70997970c51812dc3a010c7d01b50e0d17dc79c8";
+const OTHER_ETH: &str = "0xf39fd6e51aad88f6f4ce6ab8827279cfffb92266";
+const ERC20_TOKEN: &str = "0x5fbdb2315678afecb367f032d93f642f64180aa3";
+const NFT_CONTRACT: &str = "0xe7f1725e7734ce288f8367e1bb143e90bb3f0512";
+const BLOCK_HASH: &str = "0x1111111111111111111111111111111111111111111111111111111111111111";
+const TRANSFER_TOPIC: &str = "0xddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef";
+
+const WATCHED_SOL: &str = "9B5XszUGdMaxCZ7uSQhPzdks5ZQSmWxrmzCSvtJ6Ns6g";
+const OTHER_SOL: &str = "2wmVCSfPxGPjrnMMn7rchp4uaeoTqN39mXFC2zhPdri9";
+const WATCHED_TOKEN_ACCOUNT: &str = "GgBaCs3NCBuZN12kCJgAW63ydqohFkHEdfdEXBPzLHq";
+const OTHER_TOKEN_ACCOUNT: &str = "LbUiWL3xVV8hTFYBVdbTNrpDo41NKS6o3LHHuDzjfcY";
+const USDC_MINT: &str = "EPjFWdd5AufqSSqeM2qN1xizBChXkzwn9N2r6ZyHT1v";
+const SYSTEM_PROGRAM: &str = "11111111111111111111111111111111";
+const TOKEN_PROGRAM: &str = "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA";
+const MEMO_PROGRAM: &str = "MemoSq4gqABAXKb96qnH8TysNcWxMyWCqXgDLGmfcHr";
+const SLOT: u64 = 222_000_001;
+
+/// Block time of every case, 2023-10-19T09:02:24Z.
+const BLOCK_TIME: u64 = 1_697_706_144;
+
+struct Case {
+    chain: &'static str,
+    name: &'static str,
+    /// Capture entries, as `tracker record` writes them.
+    exchanges: Vec<Value>,
+}
+
+impl Case {
+    fn golden(&self) -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("..")
+            .join("tests")
+            .join("golden")
+            .join("matrix")
+            .join(format!("{}-{}.events.json", self.chain, self.name))
+    }
+}
+
+fn cases() -> Vec<Case> {
+    vec![
+        eth_case(
+            "native",
+            eth_tx(1, OTHER_ETH, Some(WATCHED_ETH), "0xde0b6b3a7640000", "0x"),
+            1,
+            Vec::new(),
+        ),
+        eth_case(
+            "erc20",
+            eth_tx(
+                2,
+                OTHER_ETH,
+                Some(ERC20_TOKEN),
+                "0x0",
+                &format!("0xa9059cbb{}{}", word(WATCHED_ETH), word("0xf4240")),
+            ),
+            1,
+            vec![json!({
+                "address": ERC20_TOKEN,
+                "topics": [
+                    TRANSFER_TOPIC,
+                    format!("0x{}", word(OTHER_ETH)),
+                    format!("0x{}", word(WATCHED_ETH)),
+                ],
+                "data": format!("0x{}", word("0xf4240")),
+                "logIndex": "0x0",
+            })],
+        ),
+        // ERC-721 `Transfer` logs index the token id too, so only the
+        // transaction itself is published.
+        eth_case(
+            "nft",
+            eth_tx(
+                3,
+                WATCHED_ETH,
+                Some(NFT_CONTRACT),
+                "0x0",
+                &format!(
+                    "0x42842e0e{}{}{}",
+                    word(WATCHED_ETH),
+                    word(OTHER_ETH),
+                    word("0x7")
+                ),
+            ),
+            1,
+            vec![json!({
+                "address": NFT_CONTRACT,
+                "topics": [
+                    TRANSFER_TOPIC,
+                    format!("0x{}", word(WATCHED_ETH)),
+                    format!("0x{}", word(OTHER_ETH)),
+                    format!("0x{}", word("0x7")),
+                ],
+                "data": "0x",
+                "logIndex": "0x0",
+            })],
+        ),
+        eth_case(
+            "failed",
+            eth_tx(4, WATCHED_ETH, Some(OTHER_ETH), "0x6f05b59d3b20000", "0x"),
+            0,
+            Vec::new(),
+        ),
+        eth_case(
+            "contract-deploy",
+            eth_tx(
+                5,
+                WATCHED_ETH,
+                None,
+                "0x0",
+                "0x6080604052348015600f57600080fd5b50",
+            ),
+            1,
+            Vec::new(),
+        ),
+        sol_case(
+            "native",
+            1,
+            vec![
+                sol_transfer(WATCHED_SOL, OTHER_SOL),
+                json!({
+                    "program": "spl-memo",
+                    "programId": MEMO_PROGRAM,
+                    "parsed": "invoice 42",
+                    "stackHeight": null,
+                }),
+            ],
+            None,
+            (Vec::new(), Vec::new()),
+        ),
+        sol_case(
+            "spl",
+            2,
+            vec![json!({
+                "program": "spl-token",
+                "programId": TOKEN_PROGRAM,
+                "parsed": {
+                    "type": "transferChecked",
+                    "info": {
+                        "source": WATCHED_TOKEN_ACCOUNT,
+                        "destination": OTHER_TOKEN_ACCOUNT,
+                        "authority": WATCHED_SOL,
+                        "mint": USDC_MINT,
+                        "tokenAmount": usdc_amount(5_000_000),
+                    },
+                },
+                "stackHeight": null,
+            })],
+            None,
+            (
+                vec![usdc_balance(2, WATCHED_SOL, 5_000_000)],
+                vec![
+                    usdc_balance(2, WATCHED_SOL, 0),
+                    usdc_balance(3, OTHER_SOL, 5_000_000),
+                ],
+            ),
+        ),
+        sol_case(
+            "failed",
+            3,
+            vec![sol_transfer(WATCHED_SOL, OTHER_SOL)],
+            Some(json!({"InstructionError": [0, {"Custom": 1}]})),
+            (Vec::new(), Vec::new()),
+        ),
+    ]
+}
+
+/// `value` (an address or `0x` number) as a 32-byte ABI word, without `0x`.
+fn word(value: &str) -> String {
+    format!("{:0>64}", value.trim_start_matches("0x"))
+}
+
+fn eth_hash(n: u8) -> String {
+    format!("0x{}", format!("{:02x}", n).repeat(32))
+}
+
+fn eth_tx(n: u8, from: &str, to: Option<&str>, value: &str, input: &str) -> Value {
+    json!({
+        "hash": eth_hash(n),
+        "nonce": "0x0",
+        "blockHash": BLOCK_HASH,
+        "blockNumber": "0x1",
+        "transactionIndex": "0x0",
+        "from": from,
+        "to": to,
+        "value": value,
+        "gasPrice": "0x3b9aca00",
+        "gas": "0x5208",
+        "input": input,
+        "v": "0x1",
+        "r": "0x1",
+        "s": "0x1",
+        "type": "0x0",
+        "chainId": "0x7a69",
+    })
+}
+
+/// A capture of block 1 holding just `tx`, whose receipt has `status` and
+/// `logs` (given without their block and transaction fields).
+fn eth_case(name: &'static str, tx: Value, status: u8, logs: Vec<Value>) -> Case {
+    let hash = tx["hash"].clone();
+    let logs: Vec<Value> = logs
+        .into_iter()
+        .map(|mut log| {
+            let fields = log.as_object_mut().unwrap();
+            fields.insert("blockHash".into(), BLOCK_HASH.into());
+            fields.insert("blockNumber".into(), "0x1".into());
+            fields.insert("transactionHash".into(), hash.clone());
+            fields.insert("transactionIndex".into(), "0x0".into());
+            fields.insert("removed".into(), false.into());
+            log
+        })
+        .collect();
+    let contract_address = match tx["to"] {
+        Value::Null => json!(ERC20_TOKEN),
+        _ => Value::Null,
+    };
+    let bloom = format!("0x{}", "0".repeat(512));
+    let receipt = json!({
+        "transactionHash": hash,
+        "transactionIndex": "0x0",
+        "blockHash": BLOCK_HASH,
+        "blockNumber": "0x1",
+        "from": tx["from"],
+        "to": tx["to"],
+        "cumulativeGasUsed": "0x5208",
+        "gasUsed": "0x5208",
+        "contractAddress": contract_address,
+        "logs": logs,
+        "status": format!("0x{:x}", status),
+        "logsBloom": bloom,
+        "type": "0x0",
+        "effectiveGasPrice": "0x3b9aca00",
+    });
+    let block = json!({
+        "hash": BLOCK_HASH,
+        "parentHash": format!("0x{}", "0".repeat(64)),
+        "sha3Uncles": format!("0x{}", "0".repeat(64)),
+        "miner": format!("0x{}", "0".repeat(40)),
+        "stateRoot": format!("0x{}", "0".repeat(64)),
+        "transactionsRoot": format!("0x{}", "0".repeat(64)),
+        "receiptsRoot": format!("0x{}", "0".repeat(64)),
+        "number": "0x1",
+        "gasUsed": "0x5208",
+        "gasLimit": "0x1c9c380",
+        "extraData": "0x",
+        "logsBloom": bloom,
+        "timestamp": format!("0x{:x}", BLOCK_TIME),
+        "difficulty": "0x0",
+        "totalDifficulty": "0x0",
+        "uncles": [],
+        "size": "0x2d0",
+        "mixHash": format!("0x{}", "0".repeat(64)),
+        "nonce": "0x0000000000000000",
+        "baseFeePerGas": "0x3b9aca00",
+        "transactions": [tx],
+    });
+    Case {
+        chain: "ethereum",
+        name,
+        exchanges: vec![
+            json!({"method": "eth_getBlockByNumber", "params": ["0x1", true], "result": block}),
+            json!({"method": "eth_getTransactionReceipt", "params": [hash], "result": receipt}),
+        ],
+    }
+}
+
+fn sol_transfer(from: &str, to: &str) -> Value {
+    json!({
+        "program": "system",
+        "programId": SYSTEM_PROGRAM,
+        "parsed": {
+            "type": "transfer",
+            "info": {"source": from, "destination": to, "lamports": 100_000_000},
+        },
+        "stackHeight": null,
+    })
+}
+
+fn usdc_amount(amount: u64) -> Value {
+    let ui_amount = amount as f64 / 1e6;
+    json!({
+        "amount": amount.to_string(),
+        "decimals": 6,
+        "uiAmount": ui_amount,
+        "uiAmountString": ui_amount.to_string(),
+    })
+}
+
+fn usdc_balance(account_index: u8, owner: &str, amount: u64) -> Value {
+    json!({
+        "accountIndex": account_index,
+        "mint": USDC_MINT,
+        "uiTokenAmount": usdc_amount(amount),
+        "owner": owner,
+        "programId": TOKEN_PROGRAM,
+    })
+}
+
+/// A capture listing one transaction for the watched address, signed by it
+/// and with signature `[n; 64]`, failed with `err` if given.
+fn sol_case(
+    name: &'static str,
+    n: u8,
+    instructions: Vec<Value>,
+    err: Option<Value>,
+    (pre_token_balances, post_token_balances): (Vec<Value>, Vec<Value>),
+) -> Case {
+    let signature = Signature::from([n; 64]).to_string();
+    let key = |pubkey: &str, signer: bool, writable: bool| {
+        json!({
+            "pubkey": pubkey,
+            "signer": signer,
+            "writable": writable,
+            "source": "transaction",
+        })
+    };
+    let account_keys = vec![
+        key(WATCHED_SOL, true, true),
+        key(OTHER_SOL, false, true),
+        key(WATCHED_TOKEN_ACCOUNT, false, true),
+        key(OTHER_TOKEN_ACCOUNT, false, true),
+        key(SYSTEM_PROGRAM, false, false),
+        key(TOKEN_PROGRAM, false, false),
+        key(MEMO_PROGRAM, false, false),
+    ];
+    let status = match &err {
+        Some(err) => json!({"Err": err}),
+        None => json!({"Ok": null}),
+    };
+    let transaction = json!({
+        "slot": SLOT,
+        "blockTime": BLOCK_TIME,
+        "transaction": {
+            "signatures": [signature],
+            "message": {
+                "accountKeys": account_keys,
+                "recentBlockhash": "CJp7CYwBYzqMQdpNJvGwz3GnwqYapU8yYJwQTQE4J3Nj",
+                "instructions": instructions,
+            },
+        },
+        "meta": {
+            "err": err,
+            "status": status,
+            "fee": 5000,
+            "preBalances": [1_100_005_000u64, 0, 2_039_280, 2_039_280, 1, 1, 1],
+            "postBalances": [1_000_000_000u64, 100_000_000, 2_039_280, 2_039_280, 1, 1, 1],
+            "innerInstructions": [],
+            "logMessages": [],
+            "preTokenBalances": pre_token_balances,
+            "postTokenBalances": post_token_balances,
+            "rewards": [],
+            "computeUnitsConsumed": 150,
+        },
+    });
+    let config = json!({
+        "encoding": "jsonParsed",
+        "commitment": "confirmed",
+        "maxSupportedTransactionVersion": 0,
+    });
+    Case {
+        chain: "solana",
+        name,
+        exchanges: vec![
+            json!({
+                "method": "getSignaturesForAddress",
+                "params": [WATCHED_SOL],
+                "result": [{
+                    "signature": signature,
+                    "slot": SLOT,
+                    "err": err,
+                    "memo": null,
+                    "blockTime": BLOCK_TIME,
+                    "confirmationStatus": "confirmed",
+                }],
+            }),
+            json!({
+                "method": "getTransaction",
+                "params": [signature, config],
+                "result": transaction,
+            }),
+        ],
+    }
+}
+
+/// Replay a case and return its published events without their random
+/// `traceparent`.
+async fn replay(case: &Case) -> Vec<Value> {
+    let lines: Vec<String> = case.exchanges.iter().map(Value::to_string).collect();
+    let capture = Arc::new(Capture::from_reader(lines.join("\n").as_bytes()).unwrap());
+    let cfg = Config {
+        eth_enabled: true,
+        eth_network: "anvil".to_string(),
+        watched_addresses_eth: vec![WATCHED_ETH.to_string()],
+        sol_enabled: true,
+        sol_network: "devnet".to_string(),
+        watched_addresses_sol: vec![WATCHED_SOL.to_string()],
+        ..Default::default()
+    };
+
+    let (publisher, mut rx) = Publisher::in_memory();
+    match case.chain {
+        "ethereum" => replay_eth(capture, &cfg, &publisher).await.unwrap(),
+        "solana" => replay_sol(capture, &cfg, &publisher).await.unwrap(),
+        chain => panic!("no replay for chain {}", chain),
+    }
+    drop(publisher);
+
+    let mut events = Vec::new();
+    while let Some(delivery) = rx.recv().await {
+        let mut event = serde_json::from_str::<Value>(&delivery.payload).unwrap();
+        event.as_object_mut().unwrap().remove("traceparent");
+        events.push(event);
+    }
+    events
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_matrix_matches_goldens() {
+    let update = std::env::var("UPDATE_GOLDENS").is_ok();
+    let mut mismatched = Vec::new();
+    for case in cases() {
+        let events = replay(&case).await;
+        let golden = case.golden();
+        if update {
+            std::fs::create_dir_all(golden.parent().unwrap()).unwrap();
+            let json = serde_json::to_string_pretty(&events).unwrap();
+            std::fs::write(&golden, json + "\n").unwrap();
+            continue;
+        }
+        let expected = std::fs::read_to_string(&golden).unwrap_or_else(|_| {
+            panic!(
+                "no golden {}; run with UPDATE_GOLDENS=1 to write it",
+                golden.display()
+            )
+        });
+        let expected: Vec<Value> = serde_json::from_str(&expected).unwrap();
+        if events != expected {
+            mismatched.push(format!(
+                "{}-{}: got {}",
+                case.chain,
+                case.name,
+                serde_json::to_string_pretty(&events).unwrap()
+            ));
+        }
+    }
+    assert!(
+        mismatched.is_empty(),
+        "replayed events differ from goldens (UPDATE_GOLDENS=1 rewrites them):\n{}",
+        mismatched.join("\n")
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_matrix_covers_every_tracker_event_type() {
+    let mut seen = HashSet::new();
+    for case in cases() {
+        let events = replay(&case).await;
+        assert!(
+            !events.is_empty(),
+            "{}-{} published nothing",
+            case.chain,
+            case.name
+        );
+        for event in &events {
+            let event_type: EventType =
+                serde_json::from_value(event["event_type"].clone()).unwrap();
+            assert!(
+                TRACKER_EVENT_TYPES.contains(&event_type),
+                "{}-{} published {}, missing from TRACKER_EVENT_TYPES",
+                case.chain,
+                case.name,
+                event_type
+            );
+            seen.insert(event_type);
+        }
+    }
+    for event_type in TRACKER_EVENT_TYPES {
+        assert!(
+            seen.contains(event_type),
+            "no fixture matrix case publishes {}",
+            event_type
+        );
+    }
+}
@@ -0,0 +1,307 @@
+//! Gap detection and self-healing for Ethereum block coverage.
+//!
+//! The trackers record every block they finish in a [`BlockLedger`]. Blocks
+//! can be missed without the tracker noticing: the websocket block
+//! subscription has no replay after a reconnect or pause, and the HTTP poller
+//! moves on when a block fails to process. The `gap_audit` scheduler job looks
+//! for holes in the ledger, reprocesses the missing blocks through the normal
+//! block pipeline (dedup drops anything already published) and emits a
+//! `gap_repaired` event per hole.
+//!
+//! Solana is not audited: its trackers re-read each watched address's
+//! signature history on every pass, and slots without activity for an address
+//! are normal, so slot holes don't indicate missed events.
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use ethers::providers::{Http, JsonRpcClient, Middleware, Provider, Ws};
+use ethers::types::Address;
+use serde::Serialize;
+use tokio::sync::Mutex;
+use tracing::{debug, info, warn};
+
+use crate::config::Config;
+use crate::control::ChainControl;
+use crate::dedup::DedupSet;
+use crate::event_type::EventType;
+use crate::publisher::Publisher;
+use crate::spam::SpamFilter;
+use crate::token_filter::TokenFilter;
+use crate::token_metadata::TokenMetadata;
+use crate::trace_context::TraceContext;
+use crate::Event;
+
+/// Blocks older than this far behind the highest recorded block are
+/// forgotten, which bounds both memory and how far back repairs reach.
+const LEDGER_WINDOW: u64 = 50_000;
+/// Upper bound on blocks reprocessed per audit run, so one large hole can't
+/// monopolize the RPC provider.
+const MAX_REPAIR_BLOCKS_PER_RUN: u64 = 1_000;
+
+/// Block range covered by a `gap_repaired` event.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GapRange {
+    pub from_block: u64,
+    pub to_block: u64,
+}
+
+/// Processed block numbers, stored as disjoint inclusive ranges.
+#[derive(Debug, Default)]
+pub struct BlockLedger {
+    ranges: BTreeMap<u64, u64>,
+}
+
+impl BlockLedger {
+    pub fn record(&mut self, block: u64) {
+        self.record_range(block, block);
+    }
+
+    pub fn record_range(&mut self, mut start: u64, mut end: u64) {
+        // Absorb every range that overlaps or touches [start, end].
+        let touching: Vec<(u64, u64)> = self
+            .ranges
+            .range(..=end.saturating_add(1))
+            .rev()
+            .take_while(|(_, e)| e.saturating_add(1) >= start)
+            .map(|(s, e)| (*s, *e))
+            .collect();
+        for (s, e) in touching {
+            self.ranges.remove(&s);
+            start = start.min(s);
+            end = end.max(e);
+        }
+        self.ranges.insert(start, end);
+
+        let floor = end.saturating_sub(LEDGER_WINDOW);
+        self.forget_below(floor);
+    }
+
+    fn forget_below(&mut self, floor: u64) {
+        let old: Vec<u64> = self.ranges.range(..floor).map(|(s, _)| *s).collect();
+        for s in old {
+            let e = self.ranges.remove(&s).unwrap_or(s);
+            if e >= floor {
+                self.ranges.insert(floor, e);
+            }
+        }
+    }
+
+    /// Holes between recorded ranges, oldest first.
+    pub fn gaps(&self) -> Vec<GapRange> {
+        self.ranges
+            .iter()
+            .zip(self.ranges.iter().skip(1))
+            .map(|((_, end), (next, _))| GapRange {
+                from_block: end + 1,
+                to_block: next - 1,
+            })
+            .collect()
+    }
+}
+
+/// The `gap_audit` job: connect to `ETH_RPC_URL` with the same transport as
+/// the trackers and repair any holes in the ledger. Skipped while Ethereum is
+/// paused, since the provider may be under maintenance.
+pub async fn audit(
+    cfg: &Config,
+    ledger: &Arc<Mutex<BlockLedger>>,
+    control: &ChainControl,
+    processed_txs: &Arc<Mutex<DedupSet>>,
+    publisher: &Publisher,
+) -> anyhow::Result<()> {
+    if control.is_paused() {
+        debug!("Skipping gap audit while Ethereum tracking is paused");
+        return Ok(());
+    }
+    if ledger.lock().await.gaps().is_empty() {
+        return Ok(());
+    }
+    let watched: Vec<Address> = cfg
+        .watched_addresses_eth
+        .iter()
+        .filter_map(|s| s.parse().ok())
+        .collect();
+    let url = cfg.eth_rpc_url.as_str();
+    let subscriptions = !url.starts_with("http");
+    if subscriptions && watched.is_empty() {
+        // The block pipeline treats an empty list as "track everything"
+        // (local testing); the subscription trackers don't.
+        debug!("Skipping gap audit: no watched ETH addresses");
+        return Ok(());
+    }
+    let token_metadata = TokenMetadata::new(cfg.eth_chain.clone());
+    if let Some(path) = url.strip_prefix("ipc://") {
+        let provider = Arc::new(Provider::connect_ipc(path).await?);
+        repair_gaps(
+            &provider,
+            ledger,
+            &watched,
+            &cfg.eth_network,
+            &token_metadata,
+            &cfg.eth_tokens,
+            &cfg.spam,
+            processed_txs,
+            publisher,
+        )
+        .await
+    } else if url.starts_with("ws") {
+        let provider = Arc::new(Provider::<Ws>::connect(url).await?);
+        repair_gaps(
+            &provider,
+            ledger,
+            &watched,
+            &cfg.eth_network,
+            &token_metadata,
+            &cfg.eth_tokens,
+            &cfg.spam,
+            processed_txs,
+            publisher,
+        )
+        .await
+    } else {
+        let provider = Arc::new(Provider::<Http>::try_from(url)?);
+        repair_gaps(
+            &provider,
+            ledger,
+            &watched,
+            &cfg.eth_network,
+            &token_metadata,
+            &cfg.eth_tokens,
+            &cfg.spam,
+            processed_txs,
+            publisher,
+        )
+        .await
+    }
+}
+
+/// Reprocess the blocks in each hole of the ledger and publish a
+/// `gap_repaired` event for every hole that was fully repaired.
+///
+/// Holes at or above the provider's head are skipped (e.g. after a local
+/// chain reset). A block that still fails stays a hole for the next run.
+#[allow(clippy::too_many_arguments)]
+pub async fn repair_gaps<P: JsonRpcClient + 'static>(
+    provider: &Arc<Provider<P>>,
+    ledger: &Arc<Mutex<BlockLedger>>,
+    watched_addresses: &[Address],
+    network: &str,
+    token_metadata: &TokenMetadata,
+    tokens: &TokenFilter,
+    spam: &SpamFilter,
+    processed_txs: &Arc<Mutex<DedupSet>>,
+    publisher: &Publisher,
+) -> anyhow::Result<()> {
+    let gaps = ledger.lock().await.gaps();
+    if gaps.is_empty() {
+        return Ok(());
+    }
+    let head = provider.get_block_number().await?.as_u64();
+    let mut budget = MAX_REPAIR_BLOCKS_PER_RUN;
+
+    for gap in gaps {
+        if gap.to_block >= head || budget == 0 {
+            continue;
+        }
+        let to_block = gap.to_block.min(gap.from_block + budget - 1);
+        info!(
+            "Repairing ETH gap: blocks {} to {}",
+            gap.from_block, to_block
+        );
+        let mut repaired = true;
+        for block_num in gap.from_block..=to_block {
+            budget -= 1;
+            match crate::process_eth_block(
+                provider,
+                block_num,
+                watched_addresses,
+                "ethereum",
+                network,
+                token_metadata,
+                tokens,
+                spam,
+                processed_txs,
+                publisher,
+            )
+            .await
+            {
+                Ok(_) => ledger.lock().await.record(block_num),
+                Err(e) => {
+                    warn!("Gap repair failed for block {}: {:?}", block_num, e);
+                    repaired = false;
+                }
+            }
+        }
+        if repaired && to_block == gap.to_block {
+            publisher
+                .publish(&gap_repaired_event(network, gap), TraceContext::new_root())
+                .await?;
+        }
+    }
+    Ok(())
+}
+
+fn gap_repaired_event(network: &str, gap: GapRange) -> Event {
+    Event {
+        event_id: format!("gap:ethereum:{}-{}", gap.from_block, gap.to_block),
+        chain: "ethereum".into(),
+        network: network.to_string(),
+        timestamp: chrono::Utc::now().to_rfc3339(),
+        event_type: EventType::GapRepaired,
+        gap: Some(gap),
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gap(from_block: u64, to_block: u64) -> GapRange {
+        GapRange {
+            from_block,
+            to_block,
+        }
+    }
+
+    #[test]
+    fn test_contiguous_blocks_have_no_gaps() {
+        let mut ledger = BlockLedger::default();
+        for b in [5, 3, 4, 6] {
+            ledger.record(b);
+        }
+        assert!(ledger.gaps().is_empty());
+    }
+
+    #[test]
+    fn test_holes_are_reported_and_closed() {
+        let mut ledger = BlockLedger::default();
+        ledger.record_range(100, 110);
+        ledger.record(115);
+        ledger.record_range(120, 130);
+        assert_eq!(ledger.gaps(), vec![gap(111, 114), gap(116, 119)]);
+
+        ledger.record_range(111, 114);
+        ledger.record_range(116, 119);
+        assert!(ledger.gaps().is_empty());
+    }
+
+    #[test]
+    fn test_old_blocks_fall_out_of_window() {
+        let mut ledger = BlockLedger::default();
+        ledger.record(10);
+        ledger.record(20);
+        ledger.record(20 + LEDGER_WINDOW + 5);
+        assert!(ledger.gaps().is_empty());
+    }
+
+    #[test]
+    fn test_gap_repaired_event_shape() {
+        let event = gap_repaired_event("mainnet", gap(7, 9));
+        let json = serde_json::to_value(&event).unwrap();
+        assert_eq!(json["event_id"], "gap:ethereum:7-9");
+        assert_eq!(json["event_type"], "gap_repaired");
+        assert_eq!(json["gap"]["from_block"], 7);
+        assert_eq!(json["gap"]["to_block"], 9);
+    }
+}
@@ -0,0 +1,466 @@
+//! Yellowstone (Geyser) gRPC source for Solana (`SOL_SOURCE=geyser`, built
+//! with the `geyser` feature).
+//!
+//! For deployments next to their own validator: instead of polling
+//! signatures over RPC, the tracker keeps one subscription open on the
+//! validator's Yellowstone plugin (`GEYSER_ENDPOINT`, optionally
+//! authenticated with `GEYSER_X_TOKEN`) for confirmed, non-vote transactions
+//! that include any watched address, and publishes them as they stream in.
+//! Events have the shape and ids of the RPC poller's `solana_tx` events,
+//! memo included. Updates carry no token metadata, so only native SOL
+//! received fills `to` and `value`.
+//!
+//! Transaction updates carry no block time, so `timestamp` is the time the
+//! update was received, normally within a second or two of the block.
+//! A dropped stream is reopened after 5s; transactions confirmed in between
+//! are not replayed.
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use anyhow::anyhow;
+use chrono::Utc;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signature;
+use tokio::sync::Mutex;
+use tokio::time::Duration;
+use tokio_stream::StreamExt;
+use tracing::{error, info, warn};
+use yellowstone_grpc_client::GeyserGrpcClient;
+use yellowstone_grpc_proto::prelude::{
+    subscribe_update::UpdateOneof, CommitmentLevel, SubscribeRequest,
+    SubscribeRequestFilterTransactions, SubscribeUpdateTransaction, TokenBalance,
+};
+
+use crate::confirmations::SolCommitment;
+use crate::control::ChainControl;
+use crate::dedup::{DedupKey, DedupSet};
+use crate::event_type::EventType;
+use crate::publisher::Publisher;
+use crate::solana_parser::MEMO_PROGRAM_IDS;
+use crate::spam::{SpamFilter, TransferSignals};
+use crate::token_filter::TokenFilter;
+use crate::trace_context::TraceContext;
+use crate::Event;
+
+/// An SPL balance from the transaction metadata.
+#[derive(Debug, Clone, PartialEq)]
+struct TokenAmount {
+    owner: String,
+    mint: String,
+    amount: u128,
+}
+
+/// What the tracker uses of a transaction update.
+#[derive(Debug, Clone, Default, PartialEq)]
+struct GeyserTx {
+    signature: String,
+    slot: u64,
+    /// Static keys followed by keys loaded from lookup tables.
+    account_keys: Vec<String>,
+    signers: usize,
+    memo: Option<String>,
+    pre_tokens: Vec<TokenAmount>,
+    post_tokens: Vec<TokenAmount>,
+    /// Lamports of each account key, before and after.
+    pre_lamports: Vec<u64>,
+    post_lamports: Vec<u64>,
+    fee: u64,
+}
+
+fn key_string(bytes: &[u8]) -> Option<String> {
+    Pubkey::try_from(bytes).ok().map(|k| k.to_string())
+}
+
+fn token_amounts(balances: &[TokenBalance]) -> Vec<TokenAmount> {
+    balances
+        .iter()
+        .map(|b| TokenAmount {
+            owner: b.owner.clone(),
+            mint: b.mint.clone(),
+            amount: b
+                .ui_token_amount
+                .as_ref()
+                .and_then(|a| a.amount.parse().ok())
+                .unwrap_or(0),
+        })
+        .collect()
+}
+
+impl GeyserTx {
+    fn from_update(update: SubscribeUpdateTransaction) -> Option<Self> {
+        let info = update.transaction?;
+        let message = info.transaction?.message?;
+        let meta = info.meta.unwrap_or_default();
+        let signature = Signature::try_from(info.signature.as_slice())
+            .ok()?
+            .to_string();
+        let mut account_keys: Vec<String> = message
+            .account_keys
+            .iter()
+            .filter_map(|k| key_string(k))
+            .collect();
+        let static_keys = account_keys.len();
+        account_keys.extend(
+            meta.loaded_writable_addresses
+                .iter()
+                .chain(&meta.loaded_readonly_addresses)
+                .filter_map(|k| key_string(k)),
+        );
+        let memos: Vec<String> = message
+            .instructions
+            .iter()
+            .filter(|ix| {
+                account_keys[..static_keys]
+                    .get(ix.program_id_index as usize)
+                    .is_some_and(|id| MEMO_PROGRAM_IDS.contains(&id.as_str()))
+            })
+            .filter_map(|ix| String::from_utf8(ix.data.clone()).ok())
+            .collect();
+        Some(GeyserTx {
+            signature,
+            slot: update.slot,
+            signers: message
+                .header
+                .map_or(0, |h| h.num_required_signatures as usize),
+            account_keys,
+            memo: (!memos.is_empty()).then(|| memos.join("\n")),
+            pre_tokens: token_amounts(&meta.pre_token_balances),
+            post_tokens: token_amounts(&meta.post_token_balances),
+            pre_lamports: meta.pre_balances,
+            post_lamports: meta.post_balances,
+            fee: meta.fee,
+        })
+    }
+
+    fn mints(&self) -> Vec<&str> {
+        let mut mints: Vec<&str> = Vec::new();
+        for b in self.pre_tokens.iter().chain(&self.post_tokens) {
+            if !mints.contains(&b.mint.as_str()) {
+                mints.push(&b.mint);
+            }
+        }
+        mints
+    }
+
+    /// Lamports `owner` received, net of the fee if it paid it. `None` if its
+    /// balance didn't grow or the transaction moved SPL tokens, whose amounts
+    /// this source doesn't report.
+    fn lamports_received(&self, owner: &str) -> Option<u64> {
+        if !self.pre_tokens.is_empty() || !self.post_tokens.is_empty() {
+            return None;
+        }
+        let index = self.account_keys.iter().position(|k| k == owner)?;
+        let pre = *self.pre_lamports.get(index)?;
+        let post = *self.post_lamports.get(index)?;
+        let fee = if index == 0 { self.fee } else { 0 };
+        (post + fee)
+            .checked_sub(pre)
+            .filter(|&received| received > 0)
+    }
+
+    /// Same rule as the RPC poller: `owner` holds SPL balances here and none
+    /// of them changed.
+    fn balance_unchanged(&self, owner: &str) -> bool {
+        let totals = |balances: &[TokenAmount]| {
+            let mut totals: HashMap<&str, u128> = HashMap::new();
+            for b in balances.iter().filter(|b| b.owner == owner) {
+                *totals.entry(b.mint.as_str()).or_default() += b.amount;
+            }
+            totals
+        };
+        let pre = totals(&self.pre_tokens);
+        let post = totals(&self.post_tokens);
+        !post.is_empty()
+            && post
+                .iter()
+                .all(|(mint, amount)| pre.get(mint).copied().unwrap_or(0) == *amount)
+    }
+}
+
+/// One `solana_tx` event per watched address the transaction touches, minus
+/// those the token and spam filters drop.
+fn events(
+    tx: &GeyserTx,
+    watched_addresses: &[String],
+    network: &str,
+    key: DedupKey,
+    tokens: &TokenFilter,
+    spam: &SpamFilter,
+) -> Vec<Event> {
+    let mints = tx.mints();
+    if !tokens.allows_any(mints.iter().copied()) {
+        return Vec::new();
+    }
+    let timestamp = Utc::now().to_rfc3339();
+    let signers = &tx.account_keys[..tx.signers.min(tx.account_keys.len())];
+    watched_addresses
+        .iter()
+        .filter(|watched| tx.account_keys.contains(*watched))
+        .filter_map(|watched| {
+            let received = tx.lamports_received(watched);
+            let mut event = Event {
+                event_id: key.event_id("sol", &tx.signature, None, watched),
+                chain: "solana".into(),
+                network: network.to_string(),
+                tx_hash: tx.signature.clone(),
+                timestamp: timestamp.clone(),
+                to: received.map(|_| watched.clone()).unwrap_or_default(),
+                value: received.map(|v| v.to_string()).unwrap_or_default(),
+                event_type: EventType::SolanaTx,
+                slot: Some(tx.slot),
+                memo: tx.memo.clone(),
+                watched_address: Some(watched.clone()),
+                signed_by_watched: signers.contains(watched),
+                ..Default::default()
+            };
+            let signals = TransferSignals {
+                unsolicited: !mints.is_empty() && !signers.contains(watched),
+                zero_value: tx.balance_unchanged(watched),
+                symbol: None,
+                metadata_unverified: false,
+            };
+            spam.apply(&mut event, &signals).then_some(event)
+        })
+        .collect()
+}
+
+fn subscription(watched_addresses: &[String], commitment: SolCommitment) -> SubscribeRequest {
+    let commitment = match commitment {
+        SolCommitment::Confirmed => CommitmentLevel::Confirmed,
+        SolCommitment::Finalized => CommitmentLevel::Finalized,
+    };
+    SubscribeRequest {
+        transactions: HashMap::from([(
+            "watched".to_string(),
+            SubscribeRequestFilterTransactions {
+                vote: Some(false),
+                account_include: watched_addresses.to_vec(),
+                ..Default::default()
+            },
+        )]),
+        commitment: Some(commitment as i32),
+        ..Default::default()
+    }
+}
+
+/// Stream transactions until the stream ends, fails or the chain is paused.
+#[allow(clippy::too_many_arguments)]
+async fn stream(
+    endpoint: &str,
+    x_token: Option<String>,
+    network: &str,
+    watched_addresses: &[String],
+    tokens: &TokenFilter,
+    spam: &SpamFilter,
+    commitment: SolCommitment,
+    processed_txs: &Arc<Mutex<DedupSet>>,
+    last_slot: &Arc<Mutex<Option<u64>>>,
+    control: &ChainControl,
+    publisher: &Publisher,
+) -> anyhow::Result<()> {
+    let mut client = GeyserGrpcClient::build_from_shared(endpoint.to_string())?
+        .x_token(x_token)?
+        .connect()
+        .await?;
+    let updates = client
+        .subscribe_once(subscription(watched_addresses, commitment))
+        .await?;
+    tokio::pin!(updates);
+    control.set_connected(true);
+    info!("Subscribed to Geyser transactions at {}", endpoint);
+
+    loop {
+        let update = tokio::select! {
+            update = updates.next() => update,
+            _ = control.stopping() => return Ok(()),
+        };
+        let Some(update) = update else {
+            return Err(anyhow!("Geyser stream ended"));
+        };
+        let Some(UpdateOneof::Transaction(update)) = update?.update_oneof else {
+            continue;
+        };
+        let Some(tx) = GeyserTx::from_update(update) else {
+            continue;
+        };
+        let trace = TraceContext::new_root();
+        let key = processed_txs.lock().await.key();
+        for event in events(&tx, watched_addresses, network, key, tokens, spam) {
+            if processed_txs.lock().await.contains(&event.event_id) {
+                continue;
+            }
+            if let Err(e) = publisher.publish(&event, trace).await {
+                error!("Failed to publish event: {:?}", e);
+            } else {
+                processed_txs.lock().await.insert(event.event_id);
+            }
+        }
+        let mut last = last_slot.lock().await;
+        if !last.is_some_and(|slot| tx.slot <= slot) {
+            *last = Some(tx.slot);
+        }
+    }
+}
+
+/// Keep a Geyser subscription open for the watched addresses, reconnecting
+/// after failures. Stands in for the RPC poller; pausing closes the stream.
+#[allow(clippy::too_many_arguments)]
+pub async fn track(
+    endpoint: &str,
+    x_token: Option<String>,
+    network: &str,
+    watched_addresses: &[String],
+    tokens: &TokenFilter,
+    spam: &SpamFilter,
+    commitment: SolCommitment,
+    processed_txs: Arc<Mutex<DedupSet>>,
+    last_slot: Arc<Mutex<Option<u64>>>,
+    control: ChainControl,
+    publisher: Publisher,
+) {
+    if watched_addresses.is_empty() {
+        info!("No Solana addresses to watch.");
+        return;
+    }
+    loop {
+        control.wait_resumed().await;
+        if control.is_shutting_down() {
+            return;
+        }
+        let res = stream(
+            endpoint,
+            x_token.clone(),
+            network,
+            watched_addresses,
+            tokens,
+            spam,
+            commitment,
+            &processed_txs,
+            &last_slot,
+            &control,
+            &publisher,
+        )
+        .await;
+        control.set_connected(false);
+        match res {
+            Ok(()) => info!("Geyser stream closed while paused or shutting down"),
+            Err(e) => {
+                warn!("Geyser stream failed: {:?}. Reconnecting in 5s.", e);
+                control.sleep(Duration::from_secs(5)).await;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spam::SpamMode;
+
+    const WATCHED: &str = "4Nd1mBQtrMJVYVfKf2PJy9NZUZdTAsp7D4xWLs4gDB4T";
+    const OTHER: &str = "9xQeWvG816bUx9EPjHmaT23yvVM2ZWbrrpZb9PusVFin";
+
+    fn tx() -> GeyserTx {
+        GeyserTx {
+            signature: "5sig".into(),
+            slot: 42,
+            account_keys: vec![OTHER.into(), WATCHED.into()],
+            signers: 1,
+            memo: Some("invoice-7".into()),
+            ..Default::default()
+        }
+    }
+
+    fn amount(owner: &str, amount: u128) -> TokenAmount {
+        TokenAmount {
+            owner: owner.into(),
+            mint: "Mint1".into(),
+            amount,
+        }
+    }
+
+    #[test]
+    fn test_events_per_touched_watched_address() {
+        let watched = vec![WATCHED.to_string(), "Untouched".to_string()];
+        let events = events(
+            &tx(),
+            &watched,
+            "mainnet",
+            DedupKey::Address,
+            &TokenFilter::default(),
+            &SpamFilter::default(),
+        );
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].event_id, format!("sol:5sig:{}", WATCHED));
+        assert_eq!(events[0].slot, Some(42));
+        assert_eq!(events[0].memo.as_deref(), Some("invoice-7"));
+        assert!(!events[0].spam);
+        assert_eq!((events[0].to.as_str(), events[0].value.as_str()), ("", ""));
+    }
+
+    #[test]
+    fn test_native_transfer_fills_recipient_and_amount() {
+        let mut transfer = tx();
+        transfer.pre_lamports = vec![10_000_000, 0];
+        transfer.post_lamports = vec![7_995_000, 2_000_000];
+        transfer.fee = 5_000;
+        assert_eq!(transfer.lamports_received(WATCHED), Some(2_000_000));
+        // The sender's balance fell by more than the fee.
+        assert_eq!(transfer.lamports_received(OTHER), None);
+
+        let watched = vec![WATCHED.to_string()];
+        let events = events(
+            &transfer,
+            &watched,
+            "mainnet",
+            DedupKey::Address,
+            &TokenFilter::default(),
+            &SpamFilter::default(),
+        );
+        assert_eq!(
+            (events[0].to.as_str(), events[0].value.as_str()),
+            (WATCHED, "2000000")
+        );
+
+        transfer.post_tokens = vec![amount(WATCHED, 6)];
+        assert_eq!(transfer.lamports_received(WATCHED), None);
+    }
+
+    #[test]
+    fn test_unchanged_token_balance_is_zero_value_spam() {
+        let mut poisoned = tx();
+        poisoned.pre_tokens = vec![amount(WATCHED, 5)];
+        poisoned.post_tokens = vec![amount(WATCHED, 5), amount(OTHER, 0)];
+        assert!(poisoned.balance_unchanged(WATCHED));
+        assert_eq!(poisoned.mints(), vec!["Mint1"]);
+
+        let watched = vec![WATCHED.to_string()];
+        let drop = SpamFilter::new(SpamMode::Drop, &[]);
+        let tokens = TokenFilter::default();
+        assert!(events(
+            &poisoned,
+            &watched,
+            "mainnet",
+            DedupKey::Log,
+            &tokens,
+            &drop
+        )
+        .is_empty());
+
+        let mut received = poisoned.clone();
+        received.post_tokens = vec![amount(WATCHED, 6)];
+        assert!(!received.balance_unchanged(WATCHED));
+        assert_eq!(
+            events(
+                &received,
+                &watched,
+                "mainnet",
+                DedupKey::Log,
+                &tokens,
+                &drop
+            )
+            .len(),
+            1
+        );
+    }
+}
@@ -0,0 +1,455 @@
+//! Address-relationship graph export (`tracker graph`, `GRAPH_EXPORT_DIR`).
+//!
+//! Aggregates transfers into a directed graph for graph analytics tools:
+//! one node per address and chain, one edge per sender, recipient and asset
+//! with the number of transfers, the raw total and when the first and last
+//! were seen. `transfer`, `erc20_transfer` and `solana_tx` events with both
+//! a sender and a recipient count; spam-tagged ones don't. Nodes carry the
+//! address book label, so entities known to operations stand out.
+//!
+//! `tracker graph` builds the graph from stored events, as JSON lines (e.g.
+//! `tracker replay --out`, or an archive of a destination channel). With
+//! `GRAPH_EXPORT_DIR` set, the listener also aggregates the events it
+//! publishes and the `graph_export` job writes `graph.graphml` and
+//! `graph.json` there every hour (`SCHEDULE_GRAPH_EXPORT`), replacing the
+//! previous files. That graph covers what was published since the listener
+//! started, up to [`MAX_EDGES`] edges; later flows between new pairs are
+//! left out, while the existing edges keep counting.
+//!
+//! GraphML is read by Gephi, Cytoscape, yEd and NetworkX. The JSON form is
+//! `{"nodes": [...], "edges": [...]}` with the same attributes, and `source`
+//! and `target` on edges referring to node `id`s (`<chain>:<address>`).
+use std::collections::BTreeMap;
+use std::io::{BufRead, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex as StdMutex};
+
+use anyhow::Context;
+use clap::ValueEnum;
+use ethers::types::U256;
+use serde::Serialize;
+use tracing::{info, warn};
+use tracker_client::Event;
+
+use crate::address_book::AddressBook;
+use crate::bus::BusSubscriber;
+
+/// Edges the listener's graph holds at most.
+pub const MAX_EDGES: usize = 100_000;
+
+/// Event types that are transfers between two addresses.
+const TRANSFER_TYPES: &[&str] = &["transfer", "erc20_transfer", "solana_tx"];
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum GraphFormat {
+    #[default]
+    Graphml,
+    Json,
+}
+
+/// Flows between one sender and one recipient in one asset.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Edge {
+    /// Token contract or mint; `None` for the native currency.
+    token: Option<String>,
+    transfers: u64,
+    /// Sum of the raw amounts.
+    value: U256,
+    first_seen: String,
+    last_seen: String,
+}
+
+/// `(chain, from, to, asset)`.
+type EdgeKey = (String, String, String, String);
+
+/// The transfer graph.
+#[derive(Debug, Clone, Default)]
+pub struct TransferGraph {
+    edges: BTreeMap<EdgeKey, Edge>,
+    /// Transfers left out because the graph was full.
+    dropped: u64,
+}
+
+impl TransferGraph {
+    /// Add `event` if it is a transfer between two addresses.
+    pub fn add(&mut self, event: &Event) -> bool {
+        self.add_limited(event, usize::MAX)
+    }
+
+    fn add_limited(&mut self, event: &Event, max_edges: usize) -> bool {
+        if !TRANSFER_TYPES.contains(&event.event_type.as_str())
+            || event.spam
+            || event.from.is_empty()
+            || event.to.is_empty()
+        {
+            return false;
+        }
+        let Ok(value) = U256::from_dec_str(if event.value.is_empty() {
+            "0"
+        } else {
+            &event.value
+        }) else {
+            return false;
+        };
+        let token = event.token.as_ref().map(|token| token.address.clone());
+        let asset = event
+            .logical_asset
+            .clone()
+            .or_else(|| event.asset_symbol.clone())
+            .or_else(|| token.clone())
+            .unwrap_or_else(|| "native".to_string());
+        let key = (
+            event.chain.clone(),
+            event.from.clone(),
+            event.to.clone(),
+            asset,
+        );
+        if !self.edges.contains_key(&key) && self.edges.len() >= max_edges {
+            self.dropped += 1;
+            return false;
+        }
+        let edge = self.edges.entry(key).or_insert_with(|| Edge {
+            token,
+            transfers: 0,
+            value: U256::zero(),
+            first_seen: event.timestamp.clone(),
+            last_seen: event.timestamp.clone(),
+        });
+        edge.transfers += 1;
+        edge.value = edge.value.saturating_add(value);
+        edge.last_seen = event.timestamp.clone();
+        true
+    }
+
+    /// Nodes and edges with `labels` from the address book.
+    fn export(&self, labels: &AddressBook) -> GraphExport {
+        let mut nodes: BTreeMap<String, Node> = BTreeMap::new();
+        let mut edges = Vec::with_capacity(self.edges.len());
+        for ((chain, from, to, asset), edge) in &self.edges {
+            for address in [from, to] {
+                let id = format!("{}:{}", chain, address);
+                nodes.entry(id.clone()).or_insert_with(|| Node {
+                    id,
+                    chain: chain.clone(),
+                    address: address.clone(),
+                    label: labels.label(chain, address).map(str::to_string),
+                });
+            }
+            edges.push(EdgeExport {
+                source: format!("{}:{}", chain, from),
+                target: format!("{}:{}", chain, to),
+                chain: chain.clone(),
+                asset: asset.clone(),
+                token: edge.token.clone(),
+                transfers: edge.transfers,
+                value: edge.value.to_string(),
+                first_seen: edge.first_seen.clone(),
+                last_seen: edge.last_seen.clone(),
+            });
+        }
+        GraphExport {
+            nodes: nodes.into_values().collect(),
+            edges,
+        }
+    }
+
+    /// Write the graph to `out` in `format`.
+    pub fn write(
+        &self,
+        format: GraphFormat,
+        labels: &AddressBook,
+        out: &mut impl Write,
+    ) -> anyhow::Result<()> {
+        let graph = self.export(labels);
+        match format {
+            GraphFormat::Json => {
+                serde_json::to_writer(&mut *out, &graph)?;
+                writeln!(out)?;
+            }
+            GraphFormat::Graphml => graph.write_graphml(out)?,
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct Node {
+    id: String,
+    chain: String,
+    address: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    label: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct EdgeExport {
+    source: String,
+    target: String,
+    chain: String,
+    asset: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    token: Option<String>,
+    transfers: u64,
+    value: String,
+    first_seen: String,
+    last_seen: String,
+}
+
+#[derive(Debug, Serialize)]
+struct GraphExport {
+    nodes: Vec<Node>,
+    edges: Vec<EdgeExport>,
+}
+
+impl GraphExport {
+    fn write_graphml(&self, out: &mut impl Write) -> std::io::Result<()> {
+        writeln!(out, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+        writeln!(
+            out,
+            r#"<graphml xmlns="http://graphml.graphdrawing.org/xmlns">"#
+        )?;
+        let keys: [(&str, &str, &str, &str); 10] = [
+            ("n_chain", "node", "chain", "string"),
+            ("n_address", "node", "address", "string"),
+            ("n_label", "node", "label", "string"),
+            ("e_chain", "edge", "chain", "string"),
+            ("e_asset", "edge", "asset", "string"),
+            ("e_token", "edge", "token", "string"),
+            ("e_transfers", "edge", "transfers", "long"),
+            ("e_value", "edge", "value", "string"),
+            ("e_first_seen", "edge", "first_seen", "string"),
+            ("e_last_seen", "edge", "last_seen", "string"),
+        ];
+        for (id, domain, name, ty) in keys {
+            writeln!(
+                out,
+                r#"  <key id="{}" for="{}" attr.name="{}" attr.type="{}"/>"#,
+                id, domain, name, ty
+            )?;
+        }
+        writeln!(out, r#"  <graph id="transfers" edgedefault="directed">"#)?;
+        for node in &self.nodes {
+            writeln!(out, r#"    <node id="{}">"#, escaped(&node.id))?;
+            data(out, "n_chain", &node.chain)?;
+            data(out, "n_address", &node.address)?;
+            if let Some(label) = &node.label {
+                data(out, "n_label", label)?;
+            }
+            writeln!(out, "    </node>")?;
+        }
+        for edge in &self.edges {
+            writeln!(
+                out,
+                r#"    <edge source="{}" target="{}">"#,
+                escaped(&edge.source),
+                escaped(&edge.target)
+            )?;
+            data(out, "e_chain", &edge.chain)?;
+            data(out, "e_asset", &edge.asset)?;
+            if let Some(token) = &edge.token {
+                data(out, "e_token", token)?;
+            }
+            data(out, "e_transfers", &edge.transfers.to_string())?;
+            data(out, "e_value", &edge.value)?;
+            data(out, "e_first_seen", &edge.first_seen)?;
+            data(out, "e_last_seen", &edge.last_seen)?;
+            writeln!(out, "    </edge>")?;
+        }
+        writeln!(out, "  </graph>")?;
+        writeln!(out, "</graphml>")
+    }
+}
+
+fn data(out: &mut impl Write, key: &str, value: &str) -> std::io::Result<()> {
+    writeln!(
+        out,
+        r#"      <data key="{}">{}</data>"#,
+        key,
+        escaped(value)
+    )
+}
+
+fn escaped(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Build the graph of the events in `input`, as JSON lines, and write it to
+/// `out`. Returns the number of transfers in it.
+pub fn export(
+    input: impl BufRead,
+    format: GraphFormat,
+    out: &mut impl Write,
+) -> anyhow::Result<usize> {
+    let mut graph = TransferGraph::default();
+    let mut added = 0;
+    for (i, line) in input.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let event: Event = serde_json::from_str(&line)
+            .with_context(|| format!("line {} is not an event", i + 1))?;
+        if graph.add(&event) {
+            added += 1;
+        }
+    }
+    graph.write(format, &AddressBook::default(), out)?;
+    Ok(added)
+}
+
+/// The listener's graph of the events it publishes. Cheap to clone; all
+/// clones share the graph.
+#[derive(Debug, Clone, Default)]
+pub struct LiveGraph {
+    graph: Arc<StdMutex<TransferGraph>>,
+    labels: AddressBook,
+}
+
+impl LiveGraph {
+    /// Aggregate the events `events` receives from now on.
+    pub fn start(mut events: BusSubscriber, labels: AddressBook) -> Self {
+        let live = LiveGraph {
+            graph: Arc::default(),
+            labels,
+        };
+        let graph = Arc::clone(&live.graph);
+        tokio::spawn(async move {
+            while let Some(published) = events.recv().await {
+                let Ok(event) = serde_json::from_str::<Event>(&published.payload) else {
+                    continue;
+                };
+                graph.lock().unwrap().add_limited(&event, MAX_EDGES);
+            }
+        });
+        live
+    }
+
+    /// Write `graph.graphml` and `graph.json` into `dir`. Each file is
+    /// written next to its final name and renamed over it, so readers never
+    /// see a partial graph.
+    pub fn write(&self, dir: &Path) -> anyhow::Result<()> {
+        let graph = self.graph.lock().unwrap().clone();
+        if graph.dropped > 0 {
+            warn!(
+                "Transfer graph is full ({} edges); left out {} transfers",
+                MAX_EDGES, graph.dropped
+            );
+        }
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("failed to create {}", dir.display()))?;
+        for (format, name) in [
+            (GraphFormat::Graphml, "graph.graphml"),
+            (GraphFormat::Json, "graph.json"),
+        ] {
+            let path = dir.join(name);
+            let tmp = dir.join(format!(".{}.tmp", name));
+            let mut file = std::io::BufWriter::new(
+                std::fs::File::create(&tmp)
+                    .with_context(|| format!("failed to create {}", tmp.display()))?,
+            );
+            graph.write(format, &self.labels, &mut file)?;
+            file.flush()?;
+            drop(file);
+            std::fs::rename(&tmp, &path)
+                .with_context(|| format!("failed to replace {}", path.display()))?;
+        }
+        info!(
+            "Wrote transfer graph with {} edges to {}",
+            graph.edges.len(),
+            dir.display()
+        );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::address_book::{AddressEntry, Severity};
+
+    fn line(from: &str, to: &str, value: &str, timestamp: &str) -> String {
+        serde_json::json!({
+            "event_id": format!("eth:{}:{}", from, timestamp),
+            "chain": "ethereum",
+            "network": "mainnet",
+            "tx_hash": "0xabc",
+            "timestamp": timestamp,
+            "from": from,
+            "to": to,
+            "value": value,
+            "event_type": "erc20_transfer",
+            "asset_symbol": "USDC",
+            "token": {"address": "0xa0b8", "symbol": "USDC", "decimals": 6},
+        })
+        .to_string()
+    }
+
+    #[test]
+    fn test_flows_aggregate_per_pair_and_asset() {
+        let mut spam: serde_json::Value =
+            serde_json::from_str(&line("0x01", "0x02", "1", "3")).unwrap();
+        spam["spam"] = true.into();
+        let heartbeat = r#"{"event_id":"hb","chain":"ethereum","event_type":"address_heartbeat"}"#;
+        let input = [
+            line("0x01", "0x02", "100", "1"),
+            line("0x01", "0x02", "250", "2"),
+            line("0x02", "0x03", "50", "2"),
+            spam.to_string(),
+            heartbeat.to_string(),
+        ]
+        .join("\n");
+        let mut out = Vec::new();
+        let added = export(input.as_bytes(), GraphFormat::Json, &mut out).unwrap();
+        assert_eq!(added, 3);
+        let graph: serde_json::Value = serde_json::from_slice(&out).unwrap();
+        assert_eq!(graph["nodes"].as_array().unwrap().len(), 3);
+        let edges = graph["edges"].as_array().unwrap();
+        assert_eq!(edges.len(), 2);
+        assert_eq!(edges[0]["source"], "ethereum:0x01");
+        assert_eq!(edges[0]["asset"], "USDC");
+        assert_eq!(edges[0]["token"], "0xa0b8");
+        assert_eq!(edges[0]["transfers"], 2);
+        assert_eq!(edges[0]["value"], "350");
+        assert_eq!(
+            (&edges[0]["first_seen"], &edges[0]["last_seen"]),
+            (&"1".into(), &"2".into())
+        );
+    }
+
+    #[test]
+    fn test_graphml_with_labels() {
+        let mut graph = TransferGraph::default();
+        let event: Event = serde_json::from_str(&line("0x01", "0x02", "1", "1")).unwrap();
+        graph.add(&event);
+        let labels = AddressBook::new(&[AddressEntry {
+            address: "ethereum:0x01".into(),
+            label: Some("Treasury <hot>".into()),
+            cold_storage: false,
+            severity: Severity::Low,
+        }])
+        .unwrap();
+        let mut out = Vec::new();
+        graph
+            .write(GraphFormat::Graphml, &labels, &mut out)
+            .unwrap();
+        let xml = String::from_utf8(out).unwrap();
+        assert!(xml.contains(r#"<edge source="ethereum:0x01" target="ethereum:0x02">"#));
+        assert!(xml.contains(r#"<data key="n_label">Treasury &lt;hot&gt;</data>"#));
+        assert!(xml.contains(r#"<data key="e_transfers">1</data>"#));
+    }
+
+    #[test]
+    fn test_full_graph_keeps_counting_existing_edges() {
+        let mut graph = TransferGraph::default();
+        let event =
+            |from: &str| -> Event { serde_json::from_str(&line(from, "0x09", "1", "1")).unwrap() };
+        assert!(graph.add_limited(&event("0x01"), 1));
+        assert!(!graph.add_limited(&event("0x02"), 1));
+        assert!(graph.add_limited(&event("0x01"), 1));
+        assert_eq!(graph.edges.len(), 1);
+        assert_eq!(graph.dropped, 1);
+    }
+}
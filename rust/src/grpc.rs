@@ -0,0 +1,708 @@
+//! gRPC services (`grpc` feature): the admin service, mirroring the HTTP
+//! admin API, and the live event stream.
+//!
+//! Started only when `GRPC_BIND_ADDR` is set. Shares [`AdminState`] with the
+//! HTTP API, so both see and change the same pause switches and watch lists,
+//! and the same `ADMIN_TOKEN` applies: calls must send
+//! `authorization: Bearer <token>` metadata. The protobuf definitions live
+//! in `proto/admin.proto` and `proto/events.proto`.
+//!
+//! `SubscribeEvents` streams every event published after the call that
+//! matches its filter, from the in-process [`EventBus`]. Like the bus, it
+//! never slows publishing down: a client that falls behind skips events.
+use ethers::types::U256;
+use serde::Serialize;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::{Request, Response, Status};
+use tracing::info;
+
+use crate::admin::{self, AdminState};
+use crate::bus::{BusEvent, EventBus};
+use crate::control::ChainControl;
+use crate::event_type::EventType;
+use crate::history::BadRequest;
+use crate::watchlist;
+use crate::webhooks::Subscription;
+
+#[allow(clippy::all)]
+pub mod proto {
+    tonic::include_proto!("tracker.admin.v1");
+}
+
+#[allow(clippy::all)]
+pub mod events_proto {
+    tonic::include_proto!("tracker.events.v1");
+}
+
+use events_proto::events_server::{Events, EventsServer};
+use events_proto::EventFilter;
+use proto::admin_server::{Admin, AdminServer};
+use proto::{
+    AddressRequest, BackfillResponse, ChainRequest, ChainStatus, ListChainsRequest,
+    ListChainsResponse, WatchedAddresses,
+};
+
+/// Events buffered per `SubscribeEvents` call before the bus takes over.
+const STREAM_BUFFER: usize = 256;
+
+impl From<admin::ChainStatus> for ChainStatus {
+    fn from(status: admin::ChainStatus) -> Self {
+        ChainStatus {
+            chain: status.chain.to_string(),
+            paused: status.paused,
+            connected: status.connected,
+            checkpoint: status.checkpoint,
+            region: status.region,
+            instance_id: status.instance_id,
+        }
+    }
+}
+
+impl From<watchlist::WatchedAddresses> for WatchedAddresses {
+    fn from(watched: watchlist::WatchedAddresses) -> Self {
+        WatchedAddresses {
+            chain: watched.chain.to_string(),
+            addresses: watched.addresses,
+        }
+    }
+}
+
+pub struct AdminService {
+    state: AdminState,
+}
+
+impl AdminService {
+    pub fn new(state: AdminState) -> Self {
+        AdminService { state }
+    }
+
+    fn authorize<T>(&self, request: &Request<T>) -> Result<(), Status> {
+        authorize(&self.state, request)
+    }
+
+    /// Apply `op` to the requested chain's switch and return its status.
+    async fn update(
+        &self,
+        request: Request<ChainRequest>,
+        op: fn(&ChainControl) -> bool,
+    ) -> Result<Response<ChainStatus>, Status> {
+        self.authorize(&request)?;
+        let chain = request.into_inner().chain;
+        let control = self
+            .state
+            .controls
+            .get(&chain)
+            .ok_or_else(|| Status::not_found(format!("unknown chain {:?}", chain)))?;
+        op(control);
+        Ok(Response::new(self.state.status(control).await.into()))
+    }
+
+    /// The requested chain's watch list and the address.
+    fn watch_list(
+        &self,
+        request: Request<AddressRequest>,
+    ) -> Result<(&watchlist::WatchList, String), Status> {
+        self.authorize(&request)?;
+        let AddressRequest { chain, address } = request.into_inner();
+        let list = self
+            .state
+            .watched
+            .get(&chain)
+            .ok_or_else(|| Status::not_found(format!("unknown chain {:?}", chain)))?;
+        Ok((list, address))
+    }
+}
+
+#[tonic::async_trait]
+impl Admin for AdminService {
+    async fn list_chains(
+        &self,
+        request: Request<ListChainsRequest>,
+    ) -> Result<Response<ListChainsResponse>, Status> {
+        self.authorize(&request)?;
+        let chains = self
+            .state
+            .statuses()
+            .await
+            .into_iter()
+            .map(Into::into)
+            .collect();
+        Ok(Response::new(ListChainsResponse { chains }))
+    }
+
+    async fn pause_chain(
+        &self,
+        request: Request<ChainRequest>,
+    ) -> Result<Response<ChainStatus>, Status> {
+        self.update(request, ChainControl::pause).await
+    }
+
+    async fn resume_chain(
+        &self,
+        request: Request<ChainRequest>,
+    ) -> Result<Response<ChainStatus>, Status> {
+        self.update(request, ChainControl::resume).await
+    }
+
+    async fn add_watched_address(
+        &self,
+        request: Request<AddressRequest>,
+    ) -> Result<Response<WatchedAddresses>, Status> {
+        let (list, address) = self.watch_list(request)?;
+        list.add(&address)
+            .map_err(|e| Status::invalid_argument(e.to_string()))?;
+        Ok(Response::new(list.snapshot().into()))
+    }
+
+    async fn remove_watched_address(
+        &self,
+        request: Request<AddressRequest>,
+    ) -> Result<Response<WatchedAddresses>, Status> {
+        let (list, address) = self.watch_list(request)?;
+        if !list.remove(&address) {
+            return Err(Status::not_found(format!("{} is not watched", address)));
+        }
+        Ok(Response::new(list.snapshot().into()))
+    }
+
+    async fn backfill(
+        &self,
+        request: Request<AddressRequest>,
+    ) -> Result<Response<BackfillResponse>, Status> {
+        let (list, address) = self.watch_list(request)?;
+        match self.state.backfill(list.chain(), &address).await {
+            Some(Ok(backfilled)) => Ok(Response::new(BackfillResponse {
+                published: backfilled.published as u64,
+            })),
+            None => Err(Status::not_found(format!("{} is not watched", address))),
+            Some(Err(e)) if e.is::<BadRequest>() => Err(Status::failed_precondition(e.to_string())),
+            Some(Err(e)) => Err(Status::unavailable(format!("{:#}", e))),
+        }
+    }
+}
+
+pub struct EventsService {
+    state: AdminState,
+    bus: EventBus,
+}
+
+impl EventsService {
+    pub fn new(state: AdminState, bus: EventBus) -> Self {
+        EventsService { state, bus }
+    }
+}
+
+#[tonic::async_trait]
+impl Events for EventsService {
+    type SubscribeEventsStream = ReceiverStream<Result<events_proto::Event, Status>>;
+
+    async fn subscribe_events(
+        &self,
+        request: Request<EventFilter>,
+    ) -> Result<Response<Self::SubscribeEventsStream>, Status> {
+        authorize(&self.state, &request)?;
+        let filter = subscription(request.into_inner())?;
+        let mut events = self.bus.subscribe("grpc");
+        let (tx, rx) = mpsc::channel::<Result<events_proto::Event, Status>>(STREAM_BUFFER);
+        tokio::spawn(async move {
+            while let Some(published) = events.recv().await {
+                if !filter.matches(&published.event) {
+                    continue;
+                }
+                // Fails once the client has gone away.
+                if tx.send(Ok(published.as_ref().into())).await.is_err() {
+                    break;
+                }
+            }
+        });
+        Ok(Response::new(ReceiverStream::new(rx)))
+    }
+}
+
+/// Check the `authorization` metadata of `request` against `ADMIN_TOKEN`.
+fn authorize<T>(state: &AdminState, request: &Request<T>) -> Result<(), Status> {
+    let presented = request
+        .metadata()
+        .get("authorization")
+        .and_then(|v| v.to_str().ok());
+    if state.authorized(presented) {
+        Ok(())
+    } else {
+        Err(Status::unauthenticated("missing or invalid admin token"))
+    }
+}
+
+/// `filter` as a webhook subscription filter, which matches the same way.
+fn subscription(filter: EventFilter) -> Result<Subscription, Status> {
+    let event_types = filter
+        .event_types
+        .iter()
+        .map(|name| {
+            EventType::ALL
+                .into_iter()
+                .find(|t| t.as_str() == name)
+                .ok_or_else(|| Status::invalid_argument(format!("unknown event type {:?}", name)))
+        })
+        .collect::<Result<_, _>>()?;
+    if let Some(min) = &filter.min_value {
+        U256::from_dec_str(min).map_err(|_| {
+            Status::invalid_argument(format!("min_value must be an integer, got {:?}", min))
+        })?;
+    }
+    Ok(Subscription {
+        id: String::new(),
+        url: String::new(),
+        addresses: filter.addresses,
+        chains: filter.chains,
+        event_types,
+        min_value: filter.min_value,
+        ack_deadline_secs: None,
+        max_redeliveries: None,
+    })
+}
+
+/// The serialized name of a unit enum variant, e.g. `extra_data`.
+fn name<T: Serialize>(value: &T) -> String {
+    match serde_json::to_value(value) {
+        Ok(serde_json::Value::String(name)) => name,
+        _ => String::new(),
+    }
+}
+
+impl From<&BusEvent> for events_proto::Event {
+    fn from(published: &BusEvent) -> Self {
+        use events_proto as pb;
+        let event = published.event.clone();
+        let traceparent = serde_json::from_str::<serde_json::Value>(&published.payload)
+            .ok()
+            .and_then(|payload| payload["traceparent"].as_str().map(str::to_string));
+        pb::Event {
+            event_id: event.event_id,
+            chain: event.chain,
+            network: event.network,
+            sequence: event.sequence,
+            region: event.region,
+            instance_id: event.instance_id,
+            tx_hash: event.tx_hash,
+            timestamp: event.timestamp,
+            from: event.from,
+            to: event.to,
+            value: event.value,
+            asset_symbol: event.asset_symbol,
+            asset_decimals: event.asset_decimals.map(u32::from),
+            value_formatted: event.value_formatted,
+            logical_asset: event.logical_asset,
+            event_type: event.event_type.to_string(),
+            slot: event.slot,
+            block_number: event.block_number,
+            log_index: event.log_index,
+            token: event.token.map(|token| pb::Token {
+                address: token.address,
+                symbol: token.symbol,
+                decimals: token.decimals.into(),
+            }),
+            memo: event.memo,
+            selector: event.selector,
+            method: event.method,
+            spam: event.spam,
+            spam_reasons: event.spam_reasons,
+            decode_warnings: event.decode_warnings.iter().map(name).collect(),
+            token_behavior: event.token_behavior.as_ref().map(name),
+            received_value: event.received_value,
+            gap: event.gap.map(|gap| pb::GapRange {
+                from_block: gap.from_block,
+                to_block: gap.to_block,
+            }),
+            heartbeat: event.heartbeat.map(|heartbeat| pb::Heartbeat {
+                address: heartbeat.address,
+                last_activity_at: heartbeat.last_activity_at,
+                last_tx_hash: heartbeat.last_tx_hash,
+                checkpoint: heartbeat.checkpoint,
+            }),
+            expectation: event.expectation.map(|expectation| pb::Expectation {
+                id: expectation.id,
+                chain: expectation.chain,
+                address: expectation.address,
+                token: expectation.token,
+                min_amount: expectation.min_amount,
+                amount: expectation.amount,
+                memo: expectation.memo,
+                deadline: expectation.deadline.to_rfc3339(),
+            }),
+            portfolio: event.portfolio.map(|snapshot| pb::Portfolio {
+                name: snapshot.name,
+                block: snapshot.block,
+                slot: snapshot.slot,
+                total_value: snapshot.total_value,
+                positions: snapshot
+                    .positions
+                    .into_iter()
+                    .map(|position| pb::Position {
+                        asset: position.asset,
+                        balance: position.balance,
+                        price: position.price,
+                        value: position.value,
+                    })
+                    .collect(),
+            }),
+            alert: event.alert.map(|alert| pb::Alert {
+                rule: alert.rule,
+                severity: name(&alert.severity),
+                address: alert.address,
+                label: alert.label,
+            }),
+            sweep: event.sweep.map(|sweep| pb::Sweep {
+                destination: sweep.destination,
+                sources: sweep.sources,
+                tx_hashes: sweep.tx_hashes,
+                window_secs: sweep.window_secs,
+            }),
+            validator: event.validator.map(|validator| pb::Validator {
+                change: name(&validator.change),
+                vote_account: validator.vote_account,
+                identity: validator.identity,
+                delinquent: validator.delinquent,
+                commission: u32::from(validator.commission),
+                previous_commission: validator.previous_commission.map(u32::from),
+                activated_stake: validator.activated_stake,
+                previous_activated_stake: validator.previous_activated_stake,
+                last_vote: validator.last_vote,
+            }),
+            liquid_staking: event.liquid_staking.map(|staking| pb::LiquidStaking {
+                protocol: staking.protocol,
+                underlying_symbol: staking.underlying_symbol,
+                underlying_decimals: u32::from(staking.underlying_decimals),
+                underlying_value: staking.underlying_value,
+            }),
+            lending: event.lending.map(|lending| pb::Lending {
+                protocol: lending.protocol,
+                market: lending.market,
+                account: lending.account,
+                collateral_asset: lending.collateral_asset,
+                collateral_value: lending.collateral_value,
+                borrow_rate: lending.borrow_rate,
+                account_debt: lending.account_debt,
+                total_collateral_base: lending.total_collateral_base,
+                total_debt_base: lending.total_debt_base,
+                health_factor: lending.health_factor,
+            }),
+            simulation: event.simulation.map(|simulation| pb::Simulation {
+                method: name(&simulation.method),
+                success: simulation.success,
+                error: simulation.error,
+                revert_reason: simulation.revert_reason,
+                gas_used: simulation.gas_used,
+                effects: simulation
+                    .effects
+                    .into_iter()
+                    .map(|effect| pb::Effect {
+                        kind: name(&effect.kind),
+                        token: effect.token,
+                        from: effect.from,
+                        to: effect.to,
+                        value: effect.value,
+                    })
+                    .collect(),
+            }),
+            stuck: event.stuck.map(|stuck| pb::Stuck {
+                reason: name(&stuck.reason),
+                nonce: stuck.nonce,
+                confirmed_nonce: stuck.confirmed_nonce,
+                pending_nonce: stuck.pending_nonce,
+                pending_secs: stuck.pending_secs,
+                queued_nonce: stuck.queued_nonce,
+            }),
+            fee_spike: event.fee_spike.map(|spike| pb::FeeSpike {
+                gas_used: spike.gas_used,
+                gas_price: spike.gas_price,
+                base_fee: spike.base_fee,
+                fee: spike.fee,
+                overpayment: spike.overpayment,
+            }),
+            substrate: event.substrate.map(|block| pb::SubstrateBlock {
+                block_number: block.block_number,
+                block_hash: block.block_hash,
+                event_index: block.event_index,
+                extrinsic_index: block.extrinsic_index,
+                era: block.era,
+            }),
+            backfill: event.backfill,
+            explorer_url: event.explorer_url.map(|urls| pb::ExplorerUrls {
+                tx: urls.tx,
+                from: urls.from,
+                to: urls.to,
+            }),
+            traceparent,
+            payload_json: published.payload.clone(),
+        }
+    }
+}
+
+/// Serve the gRPC services until the process exits.
+pub async fn serve(addr: &str, state: AdminState, bus: EventBus) -> anyhow::Result<()> {
+    let addr = addr.parse()?;
+    info!("gRPC admin and event services listening on {}", addr);
+    tonic::transport::Server::builder()
+        .add_service(AdminServer::new(AdminService::new(state.clone())))
+        .add_service(EventsServer::new(EventsService::new(state, bus)))
+        .serve(addr)
+        .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::control::Controls;
+    use crate::expectations::Expectations;
+    use crate::history::History;
+    use crate::rpc_guard::RpcGuard;
+    use crate::streams::Streams;
+    use crate::watchlist::WatchLists;
+    use crate::webhooks::Webhooks;
+    use std::sync::Arc;
+    use tokio::sync::Mutex;
+    use tokio_stream::StreamExt;
+    use tonic::Code;
+    use tracing::Span;
+
+    fn service(token: Option<&str>) -> AdminService {
+        AdminService::new(AdminState {
+            controls: Controls::default(),
+            last_eth_block: Arc::new(Mutex::new(Some(42))),
+            last_sol_slot: Arc::new(Mutex::new(None)),
+            last_evm_blocks: Default::default(),
+            token: token.map(str::to_string),
+            expectations: Expectations::default(),
+            webhooks: Webhooks::default(),
+            history: History::default(),
+            streams: Streams::default(),
+            region: None,
+            instance_id: "tracker".into(),
+            rpc: RpcGuard::default(),
+            dashboard: crate::dashboard::Dashboard::default(),
+            inspect: crate::inspect::Inspect::default(),
+            publisher: crate::publisher::Publisher::in_memory().0,
+            watched: WatchLists::default(),
+            backfill: crate::bootstrap::Backfill::default(),
+        })
+    }
+
+    fn state(token: Option<&str>) -> AdminState {
+        service(token).state
+    }
+
+    fn chain(name: &str) -> Request<ChainRequest> {
+        Request::new(ChainRequest {
+            chain: name.to_string(),
+        })
+    }
+
+    #[tokio::test]
+    async fn test_pause_resume_and_list() {
+        let svc = service(None);
+        let status = svc
+            .pause_chain(chain("ethereum"))
+            .await
+            .unwrap()
+            .into_inner();
+        assert!(status.paused);
+        assert_eq!(status.checkpoint, Some(42));
+        assert!(svc.state.controls.eth.is_paused());
+
+        let list = svc
+            .list_chains(Request::new(ListChainsRequest {}))
+            .await
+            .unwrap()
+            .into_inner();
+        let names: Vec<&str> = list.chains.iter().map(|c| c.chain.as_str()).collect();
+        assert_eq!(names, ["ethereum", "solana"]);
+        assert_eq!(list.chains[1].checkpoint, None);
+
+        let status = svc
+            .resume_chain(chain("ethereum"))
+            .await
+            .unwrap()
+            .into_inner();
+        assert!(!status.paused);
+    }
+
+    #[tokio::test]
+    async fn test_unknown_chain_is_not_found() {
+        let err = service(None)
+            .pause_chain(chain("bitcoin"))
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), Code::NotFound);
+    }
+
+    #[tokio::test]
+    async fn test_token_required_when_configured() {
+        let svc = service(Some("s3cret"));
+        let err = svc.pause_chain(chain("solana")).await.unwrap_err();
+        assert_eq!(err.code(), Code::Unauthenticated);
+        assert!(!svc.state.controls.sol.is_paused());
+
+        let mut request = chain("solana");
+        request
+            .metadata_mut()
+            .insert("authorization", "Bearer s3cret".parse().unwrap());
+        svc.pause_chain(request).await.unwrap();
+        assert!(svc.state.controls.sol.is_paused());
+    }
+
+    fn address(chain: &str, address: &str) -> Request<AddressRequest> {
+        Request::new(AddressRequest {
+            chain: chain.to_string(),
+            address: address.to_string(),
+        })
+    }
+
+    #[tokio::test]
+    async fn test_watch_list_rpcs() {
+        const ADDRESS: &str = "0x70997970c51812dc3a010c7d01b50e0d17dc79c8";
+        let svc = service(None);
+        let watched = svc
+            .add_watched_address(address("ethereum", ADDRESS))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(watched.chain, "ethereum");
+        assert_eq!(watched.addresses, [ADDRESS]);
+        assert!(svc.state.watched.eth.contains(ADDRESS));
+
+        let err = svc
+            .add_watched_address(address("ethereum", "0xnope"))
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), Code::InvalidArgument);
+        let err = svc
+            .add_watched_address(address("bitcoin", "1abc"))
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), Code::NotFound);
+
+        // No indexer key is configured.
+        let err = svc
+            .backfill(address("ethereum", ADDRESS))
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), Code::FailedPrecondition);
+
+        let watched = svc
+            .remove_watched_address(address("ethereum", ADDRESS))
+            .await
+            .unwrap()
+            .into_inner();
+        assert!(watched.addresses.is_empty());
+        let err = svc
+            .remove_watched_address(address("ethereum", ADDRESS))
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), Code::NotFound);
+        let err = svc
+            .backfill(address("ethereum", ADDRESS))
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), Code::NotFound);
+    }
+
+    fn published(event: crate::Event) -> BusEvent {
+        let mut payload = serde_json::to_value(&event).unwrap();
+        payload["traceparent"] = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01".into();
+        BusEvent {
+            event,
+            payload: payload.to_string(),
+            span: Span::none(),
+        }
+    }
+
+    #[test]
+    fn test_event_conversion() {
+        let event = crate::Event {
+            event_id: "eth:0xabc:log3".into(),
+            chain: "ethereum".into(),
+            value: "1000".into(),
+            event_type: EventType::Erc20Transfer,
+            asset_decimals: Some(6),
+            token: Some(crate::Token {
+                address: "0xusdc".into(),
+                symbol: "USDC".into(),
+                decimals: 6,
+            }),
+            decode_warnings: vec![crate::erc20::DecodeWarning::ExtraData],
+            token_behavior: Some(crate::erc20::TokenBehavior::FeeOnTransfer),
+            received_value: Some("970".into()),
+            ..Default::default()
+        };
+        let message = events_proto::Event::from(&published(event));
+        assert_eq!(message.event_id, "eth:0xabc:log3");
+        assert_eq!(message.event_type, "erc20_transfer");
+        assert_eq!(message.asset_decimals, Some(6));
+        assert_eq!(message.token.unwrap().symbol, "USDC");
+        assert_eq!(message.decode_warnings, ["extra_data"]);
+        assert_eq!(message.token_behavior.as_deref(), Some("fee_on_transfer"));
+        assert_eq!(message.received_value.as_deref(), Some("970"));
+        assert_eq!(message.sequence, None);
+        assert!(message.gap.is_none() && message.portfolio.is_none());
+        assert!(message.traceparent.unwrap().starts_with("00-4bf92f35"));
+        let payload: serde_json::Value = serde_json::from_str(&message.payload_json).unwrap();
+        assert_eq!(payload["received_value"], "970");
+    }
+
+    #[test]
+    fn test_filter_validation() {
+        let filter = subscription(EventFilter {
+            event_types: vec!["erc20_transfer".into()],
+            min_value: Some("1000".into()),
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(filter.event_types, [EventType::Erc20Transfer]);
+
+        for invalid in [
+            EventFilter {
+                event_types: vec!["nft_transfer".into()],
+                ..Default::default()
+            },
+            EventFilter {
+                min_value: Some("1.5".into()),
+                ..Default::default()
+            },
+        ] {
+            let err = subscription(invalid).unwrap_err();
+            assert_eq!(err.code(), Code::InvalidArgument);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_streams_matching_events() {
+        let bus = EventBus::default();
+        let svc = EventsService::new(state(Some("s3cret")), bus.clone());
+        let filter = EventFilter {
+            chains: vec!["solana".into()],
+            ..Default::default()
+        };
+        let err = svc
+            .subscribe_events(Request::new(filter.clone()))
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), Code::Unauthenticated);
+
+        let mut request = Request::new(filter);
+        request
+            .metadata_mut()
+            .insert("authorization", "Bearer s3cret".parse().unwrap());
+        let mut stream = svc.subscribe_events(request).await.unwrap().into_inner();
+        for (id, chain) in [("eth:0x1", "ethereum"), ("sol:abc", "solana")] {
+            bus.publish(published(crate::Event {
+                event_id: id.into(),
+                chain: chain.into(),
+                ..Default::default()
+            }));
+        }
+        let message = stream.next().await.unwrap().unwrap();
+        assert_eq!(message.event_id, "sol:abc");
+    }
+}
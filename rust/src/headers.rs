@@ -0,0 +1,318 @@
+//! Recent Ethereum block headers, for reorg detection and a light check of
+//! the RPC provider.
+//!
+//! The trackers record the number, hash and parent hash of every block they
+//! process. A block whose parent is the cached block below it extends the
+//! chain. One that doesn't means the chain reorganized: its ancestors are
+//! fetched by parent hash until one links to a cached block, the fork point,
+//! and the cached blocks above the fork are replaced by the new branch.
+//!
+//! A branch that forks more than [`MAX_REORG_DEPTH`] blocks below the
+//! highest block seen, or doesn't link to any cached block, is reported as
+//! inconsistent instead: mainnet doesn't reorganize that deep, so the
+//! provider is most likely serving blocks from backends that disagree.
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex as StdMutex};
+
+use ethers::providers::{JsonRpcClient, Middleware, Provider};
+use ethers::types::{Block, H256};
+use tracing::warn;
+
+/// Headers remembered per chain, and the longest branch fetched to find a
+/// fork point.
+pub const HEADERS_CAPACITY: usize = 128;
+
+/// Deepest fork below the highest block that is still reported as a reorg.
+pub const MAX_REORG_DEPTH: u64 = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlockHeader {
+    pub number: u64,
+    pub hash: H256,
+    pub parent_hash: H256,
+}
+
+impl BlockHeader {
+    /// The header of `block`; `None` for a pending block.
+    pub fn of<T>(block: &Block<T>) -> Option<Self> {
+        Some(BlockHeader {
+            number: block.number?.as_u64(),
+            hash: block.hash?,
+            parent_hash: block.parent_hash,
+        })
+    }
+}
+
+/// How a block relates to the cached headers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Linkage {
+    /// Its parent is the cached block below it.
+    Linked,
+    /// It was already recorded.
+    Known,
+    /// Nothing is cached to check it against, e.g. the first block after a
+    /// start, or one after a skipped block.
+    Unlinked,
+    /// It replaces the `depth` cached blocks above block `fork`.
+    Reorg { fork: u64, depth: u64 },
+    /// It forks too deep, or doesn't link to any cached block.
+    Inconsistent,
+}
+
+#[derive(Debug, Default)]
+struct Chain {
+    capacity: usize,
+    headers: BTreeMap<u64, BlockHeader>,
+}
+
+impl Chain {
+    fn hash_at(&self, number: u64) -> Option<H256> {
+        self.headers.get(&number).map(|h| h.hash)
+    }
+
+    fn lowest(&self) -> Option<u64> {
+        self.headers.keys().next().copied()
+    }
+
+    fn highest(&self) -> Option<u64> {
+        self.headers.keys().next_back().copied()
+    }
+
+    /// Whether `header`'s parent is cached, i.e. a fetched branch can stop.
+    fn has_parent(&self, header: &BlockHeader) -> bool {
+        header
+            .number
+            .checked_sub(1)
+            .is_some_and(|below| self.hash_at(below) == Some(header.parent_hash))
+    }
+
+    fn insert(&mut self, header: BlockHeader) {
+        self.headers.insert(header.number, header);
+        while self.headers.len() > self.capacity {
+            self.headers.pop_first();
+        }
+    }
+
+    /// Classify `header` without changing anything. `None` if it diverges
+    /// from the cached chain and needs a branch to place it.
+    fn check(&self, header: &BlockHeader) -> Option<Linkage> {
+        if self.hash_at(header.number) == Some(header.hash) {
+            return Some(Linkage::Known);
+        }
+        if self.lowest().is_some_and(|lowest| header.number < lowest) {
+            return Some(Linkage::Unlinked);
+        }
+        let above = self.highest().is_some_and(|h| h >= header.number);
+        if above {
+            return None;
+        }
+        match header.number.checked_sub(1).and_then(|n| self.hash_at(n)) {
+            Some(parent) if parent == header.parent_hash => Some(Linkage::Linked),
+            Some(_) => None,
+            None => Some(Linkage::Unlinked),
+        }
+    }
+
+    /// Replace the cached blocks above the fork with `branch`, newest first.
+    fn adopt(&mut self, branch: &[BlockHeader]) -> Linkage {
+        let Some(oldest) = branch.last() else {
+            return Linkage::Known;
+        };
+        let highest = self.highest().unwrap_or(0);
+        let fork = self.has_parent(oldest).then(|| oldest.number - 1);
+        match fork {
+            Some(fork) => self.headers.retain(|n, _| *n <= fork),
+            None => self.headers.clear(),
+        }
+        for header in branch.iter().rev() {
+            self.insert(*header);
+        }
+        match fork {
+            Some(fork) if highest.saturating_sub(fork) <= MAX_REORG_DEPTH => Linkage::Reorg {
+                fork,
+                depth: highest.saturating_sub(fork),
+            },
+            _ => Linkage::Inconsistent,
+        }
+    }
+}
+
+/// Cache of recent block headers. Cheap to clone; all clones share state.
+#[derive(Debug, Clone)]
+pub struct HeaderCache {
+    chain: Arc<StdMutex<Chain>>,
+}
+
+impl Default for HeaderCache {
+    fn default() -> Self {
+        HeaderCache::new(HEADERS_CAPACITY)
+    }
+}
+
+impl HeaderCache {
+    pub fn new(capacity: usize) -> Self {
+        HeaderCache {
+            chain: Arc::new(StdMutex::new(Chain {
+                capacity: capacity.max(1),
+                ..Default::default()
+            })),
+        }
+    }
+
+    /// Forget every header, e.g. after the chain was reset.
+    pub fn clear(&self) {
+        self.chain.lock().unwrap().headers.clear();
+    }
+
+    /// Record `header` and check how it links to the cached headers. If it
+    /// diverges, its ancestors are fetched from `provider` to find the fork
+    /// point; a failed fetch leaves the branch short, which reports it as
+    /// inconsistent.
+    pub async fn verify<P: JsonRpcClient>(
+        &self,
+        provider: &Provider<P>,
+        header: BlockHeader,
+    ) -> Linkage {
+        match self.check(&header) {
+            Some(Linkage::Known) => return Linkage::Known,
+            Some(linkage) => {
+                self.insert(header);
+                return linkage;
+            }
+            None => {}
+        }
+        let mut branch = vec![header];
+        loop {
+            let oldest = branch[branch.len() - 1];
+            let capacity = {
+                let chain = self.chain.lock().unwrap();
+                if chain.has_parent(&oldest) {
+                    break;
+                }
+                chain.capacity
+            };
+            if branch.len() >= capacity || oldest.number == 0 {
+                break;
+            }
+            let parent = match provider.get_block(oldest.parent_hash).await {
+                Ok(Some(block)) => BlockHeader::of(&block),
+                _ => None,
+            };
+            match parent {
+                Some(parent) if parent.hash == oldest.parent_hash => branch.push(parent),
+                _ => break,
+            }
+        }
+        let linkage = self.adopt(&branch);
+        match linkage {
+            Linkage::Reorg { fork, depth } => warn!(
+                "ETH reorg: block {} ({:?}) replaces {} blocks above block {}",
+                header.number, header.hash, depth, fork
+            ),
+            Linkage::Inconsistent => warn!(
+                "ETH block {} ({:?}) doesn't link to the cached chain within {} blocks; \
+                 the RPC provider's backends may disagree",
+                header.number,
+                header.hash,
+                branch.len()
+            ),
+            _ => {}
+        }
+        linkage
+    }
+
+    /// Place a fetched branch, newest first, its oldest block linking to the
+    /// fork point.
+    fn adopt(&self, branch: &[BlockHeader]) -> Linkage {
+        self.chain.lock().unwrap().adopt(branch)
+    }
+
+    fn check(&self, header: &BlockHeader) -> Option<Linkage> {
+        self.chain.lock().unwrap().check(header)
+    }
+
+    fn insert(&self, header: BlockHeader) {
+        self.chain.lock().unwrap().insert(header);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Block `number` of branch `branch`, whose parent is on `parent_branch`.
+    fn header(number: u64, branch: u64, parent_branch: u64) -> BlockHeader {
+        BlockHeader {
+            number,
+            hash: H256::from_low_u64_be((branch << 32) | number),
+            parent_hash: H256::from_low_u64_be((parent_branch << 32) | (number - 1)),
+        }
+    }
+
+    fn cache_with(numbers: std::ops::RangeInclusive<u64>) -> HeaderCache {
+        let cache = HeaderCache::default();
+        for number in numbers {
+            cache.insert(header(number, 0, 0));
+        }
+        cache
+    }
+
+    #[test]
+    fn test_extending_the_chain() {
+        let cache = cache_with(100..=105);
+        assert_eq!(cache.check(&header(106, 0, 0)), Some(Linkage::Linked));
+        assert_eq!(cache.check(&header(105, 0, 0)), Some(Linkage::Known));
+        assert_eq!(cache.check(&header(108, 0, 0)), Some(Linkage::Unlinked));
+        // The parent at 105 isn't the cached block 105.
+        assert_eq!(cache.check(&header(106, 1, 1)), None);
+        // A different block at a cached height.
+        assert_eq!(cache.check(&header(104, 1, 0)), None);
+    }
+
+    #[test]
+    fn test_reorg_replaces_blocks_above_the_fork() {
+        let cache = cache_with(100..=105);
+        // 104' and 105' fork off block 103; 106' arrives first.
+        let branch = [header(106, 1, 1), header(105, 1, 1), header(104, 1, 0)];
+        assert_eq!(
+            cache.adopt(&branch),
+            Linkage::Reorg {
+                fork: 103,
+                depth: 2
+            }
+        );
+        assert_eq!(cache.check(&header(107, 1, 1)), Some(Linkage::Linked));
+        assert_eq!(cache.check(&header(105, 1, 1)), Some(Linkage::Known));
+    }
+
+    #[test]
+    fn test_unlinked_branch_is_inconsistent() {
+        let cache = cache_with(100..=105);
+        assert_eq!(cache.adopt(&[header(106, 1, 1)]), Linkage::Inconsistent);
+        // The cache restarts from the new branch.
+        assert_eq!(cache.check(&header(107, 1, 1)), Some(Linkage::Linked));
+        assert_eq!(cache.check(&header(104, 0, 0)), Some(Linkage::Unlinked));
+    }
+
+    #[test]
+    fn test_deep_fork_is_inconsistent() {
+        let cache = cache_with(1..=100);
+        let depth = MAX_REORG_DEPTH + 1;
+        let fork = 100 - depth;
+        assert_eq!(
+            cache.adopt(&[header(fork + 1, 1, 0)]),
+            Linkage::Inconsistent
+        );
+    }
+
+    #[test]
+    fn test_capacity_keeps_the_newest() {
+        let cache = HeaderCache::new(3);
+        for number in 1..=5 {
+            cache.insert(header(number, 0, 0));
+        }
+        assert_eq!(cache.check(&header(3, 0, 0)), Some(Linkage::Known));
+        // Below the cache, so nothing to check against.
+        assert_eq!(cache.check(&header(2, 0, 0)), Some(Linkage::Unlinked));
+    }
+}
@@ -0,0 +1,330 @@
+//! Liveness and readiness probes (`HEALTH_BIND_ADDR`).
+//!
+//! A tracker stuck in a reconnect loop keeps the process alive while nothing
+//! is published. The probes let an orchestrator tell:
+//!
+//! - `GET /healthz` — 200 while every enabled chain that isn't paused is
+//!   connected and its checkpoint is at most `HEALTH_MAX_LAG_ETH` blocks or
+//!   `HEALTH_MAX_LAG_SOL` slots behind the node's head, 503 otherwise. For
+//!   a liveness probe: restarting is what helps.
+//! - `GET /readyz` — the same, and Redis answers a `PING`. For a readiness
+//!   probe, since restarting doesn't bring Redis back.
+//!
+//! The checks run in the `health_check` job (every 10s), so a probe answers
+//! at once from the last run and never waits on a node. Until the first run
+//! `/healthz` answers 200 and `/readyz` 503. The body is the last report.
+//!
+//! The Solana checkpoint only moves when a watched address transacts, so its
+//! lag is no sign of a stall and `HEALTH_MAX_LAG_SOL` is off (0) by default.
+//! A head the node doesn't report leaves the lag unchecked. The probes are
+//! served without `ADMIN_TOKEN`, on their own address so the admin API can
+//! stay private.
+use std::sync::{Arc, Mutex as StdMutex};
+
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::routing::get;
+use axum::{Json, Router};
+use chrono::Utc;
+use serde::Serialize;
+use tokio::time::{timeout, Duration};
+use tracing::{info, warn};
+
+use crate::admin::{AdminState, ChainStatus};
+use crate::config::Config;
+
+/// Default `HEALTH_MAX_LAG_ETH`: about ten minutes of blocks.
+pub const DEFAULT_MAX_LAG_ETH: u64 = 50;
+/// How long the Redis `PING` may take.
+const PING_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Probe settings.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HealthConfig {
+    /// `HEALTH_BIND_ADDR`; the probes are off when unset.
+    pub bind_addr: Option<String>,
+    /// Largest checkpoint lag that passes, in blocks (`HEALTH_MAX_LAG_ETH`)
+    /// and slots (`HEALTH_MAX_LAG_SOL`); 0 doesn't check it.
+    pub max_lag_eth: u64,
+    pub max_lag_sol: u64,
+}
+
+impl Default for HealthConfig {
+    fn default() -> Self {
+        HealthConfig {
+            bind_addr: None,
+            max_lag_eth: DEFAULT_MAX_LAG_ETH,
+            max_lag_sol: 0,
+        }
+    }
+}
+
+/// One chain's part of a report.
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct ChainHealth {
+    pub chain: &'static str,
+    pub healthy: bool,
+    /// Why the chain fails, if it does.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub problem: Option<String>,
+    pub paused: bool,
+    pub connected: bool,
+    pub checkpoint: Option<u64>,
+    pub head: Option<u64>,
+    pub lag: Option<u64>,
+}
+
+/// Outcome of a `health_check` run.
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct Report {
+    /// Every chain is healthy (`/healthz`).
+    pub live: bool,
+    /// Live, and Redis is reachable (`/readyz`).
+    pub ready: bool,
+    pub redis: bool,
+    pub chains: Vec<ChainHealth>,
+    /// RFC 3339.
+    pub checked_at: String,
+}
+
+/// Judge a chain from its status and the node's head.
+fn chain_health(status: ChainStatus, head: Option<u64>, max_lag: u64) -> ChainHealth {
+    let lag = head
+        .zip(status.checkpoint)
+        .map(|(head, checkpoint)| head.saturating_sub(checkpoint));
+    let problem = if status.paused {
+        None
+    } else if !status.connected {
+        Some("not connected".to_string())
+    } else {
+        lag.filter(|lag| max_lag > 0 && *lag > max_lag)
+            .map(|lag| format!("checkpoint {} behind the head (max {})", lag, max_lag))
+    };
+    ChainHealth {
+        chain: status.chain,
+        healthy: problem.is_none(),
+        problem,
+        paused: status.paused,
+        connected: status.connected,
+        checkpoint: status.checkpoint,
+        head,
+        lag,
+    }
+}
+
+/// The last report, shared by the job and the probes. Cheap to clone.
+#[derive(Debug, Clone, Default)]
+pub struct HealthReport(Arc<StdMutex<Option<Report>>>);
+
+impl HealthReport {
+    pub fn get(&self) -> Option<Report> {
+        self.0.lock().unwrap().clone()
+    }
+
+    fn set(&self, report: Report) {
+        *self.0.lock().unwrap() = Some(report);
+    }
+}
+
+/// What the `health_check` job checks.
+#[derive(Clone)]
+pub struct HealthCheck {
+    admin: AdminState,
+    redis: redis::Client,
+    eth_enabled: bool,
+    sol_enabled: bool,
+    cfg: HealthConfig,
+    report: HealthReport,
+}
+
+impl HealthCheck {
+    pub fn new(cfg: &Config, admin: AdminState) -> anyhow::Result<Self> {
+        Ok(HealthCheck {
+            admin,
+            redis: redis::Client::open(cfg.redis_url.as_str())?,
+            eth_enabled: cfg.eth_enabled,
+            sol_enabled: cfg.sol_enabled,
+            cfg: cfg.health.clone(),
+            report: HealthReport::default(),
+        })
+    }
+
+    pub fn report(&self) -> &HealthReport {
+        &self.report
+    }
+
+    /// Run the checks and keep the report, logging when liveness or
+    /// readiness changes.
+    pub async fn run(&self) -> anyhow::Result<()> {
+        let eth_head = async {
+            if self.eth_enabled {
+                self.admin.rpc.head().await
+            } else {
+                None
+            }
+        };
+        let (eth_head, sol_head, redis) =
+            tokio::join!(eth_head, self.admin.inspect.sol_head(), ping(&self.redis));
+        let mut chains = Vec::new();
+        for (enabled, control, head, max_lag) in [
+            (
+                self.eth_enabled,
+                &self.admin.controls.eth,
+                eth_head,
+                self.cfg.max_lag_eth,
+            ),
+            (
+                self.sol_enabled,
+                &self.admin.controls.sol,
+                sol_head,
+                self.cfg.max_lag_sol,
+            ),
+        ] {
+            if enabled {
+                let status = self.admin.status(control).await;
+                chains.push(chain_health(status, head, max_lag));
+            }
+        }
+        let live = chains.iter().all(|chain| chain.healthy);
+        let report = Report {
+            live,
+            ready: live && redis,
+            redis,
+            chains,
+            checked_at: Utc::now().to_rfc3339(),
+        };
+        let previous = self.report.get();
+        if previous.as_ref().map(|r| (r.live, r.ready)) != Some((report.live, report.ready)) {
+            let problems: Vec<String> = report
+                .chains
+                .iter()
+                .filter_map(|c| c.problem.as_ref().map(|p| format!("{}: {}", c.chain, p)))
+                .chain((!report.redis).then(|| "redis unreachable".to_string()))
+                .collect();
+            if problems.is_empty() {
+                info!("Health: live and ready");
+            } else {
+                warn!("Health: {}", problems.join("; "));
+            }
+        }
+        self.report.set(report);
+        Ok(())
+    }
+}
+
+async fn ping(client: &redis::Client) -> bool {
+    let ping = async {
+        let mut con = client.get_multiplexed_async_connection().await?;
+        let _: String = redis::cmd("PING").query_async(&mut con).await?;
+        Ok::<_, redis::RedisError>(())
+    };
+    matches!(timeout(PING_TIMEOUT, ping).await, Ok(Ok(())))
+}
+
+pub fn router(report: HealthReport) -> Router {
+    Router::new()
+        .route("/healthz", get(healthz))
+        .route("/readyz", get(readyz))
+        .with_state(report)
+}
+
+pub async fn serve(addr: &str, report: HealthReport) -> anyhow::Result<()> {
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+    info!("Health probes listening on {}", listener.local_addr()?);
+    axum::serve(listener, router(report)).await?;
+    Ok(())
+}
+
+type Probe = (StatusCode, Json<Option<Report>>);
+
+fn probe(report: Option<Report>, passes: impl Fn(&Report) -> bool, starting: bool) -> Probe {
+    let status = if report.as_ref().map_or(starting, passes) {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (status, Json(report))
+}
+
+async fn healthz(State(report): State<HealthReport>) -> Probe {
+    probe(report.get(), |r| r.live, true)
+}
+
+async fn readyz(State(report): State<HealthReport>) -> Probe {
+    probe(report.get(), |r| r.ready, false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::{to_bytes, Body};
+    use axum::http::Request;
+    use tower::ServiceExt;
+
+    fn status(paused: bool, connected: bool, checkpoint: Option<u64>) -> ChainStatus {
+        ChainStatus {
+            chain: "ethereum",
+            paused,
+            connected,
+            checkpoint,
+            region: None,
+            instance_id: "tracker".into(),
+        }
+    }
+
+    #[test]
+    fn test_chain_health() {
+        let healthy = chain_health(status(false, true, Some(100)), Some(110), 50);
+        assert!(healthy.healthy);
+        assert_eq!(healthy.lag, Some(10));
+
+        let behind = chain_health(status(false, true, Some(100)), Some(200), 50);
+        assert_eq!(
+            behind.problem.as_deref(),
+            Some("checkpoint 100 behind the head (max 50)")
+        );
+        // 0 doesn't check the lag, and an unknown head can't be checked.
+        assert!(chain_health(status(false, true, Some(100)), Some(200), 0).healthy);
+        assert!(chain_health(status(false, true, Some(100)), None, 50).healthy);
+
+        let disconnected = chain_health(status(false, false, Some(100)), Some(100), 50);
+        assert_eq!(disconnected.problem.as_deref(), Some("not connected"));
+        // A paused chain is disconnected on purpose.
+        assert!(chain_health(status(true, false, Some(100)), Some(200), 50).healthy);
+    }
+
+    async fn get(report: &HealthReport, uri: &str) -> (StatusCode, String) {
+        let req = Request::builder().uri(uri).body(Body::empty()).unwrap();
+        let res = router(report.clone()).oneshot(req).await.unwrap();
+        let status = res.status();
+        let body = to_bytes(res.into_body(), usize::MAX).await.unwrap();
+        (status, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_probes() {
+        let report = HealthReport::default();
+        assert_eq!(get(&report, "/healthz").await.0, StatusCode::OK);
+        assert_eq!(
+            get(&report, "/readyz").await.0,
+            StatusCode::SERVICE_UNAVAILABLE
+        );
+
+        let chain = chain_health(status(false, true, Some(100)), Some(101), 50);
+        report.set(Report {
+            live: true,
+            ready: false,
+            redis: false,
+            chains: vec![chain],
+            checked_at: "2024-05-01T12:00:00+00:00".into(),
+        });
+        let (status, body) = get(&report, "/healthz").await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains(r#""redis":false"#));
+        assert!(body.contains(r#""lag":1"#));
+        assert_eq!(
+            get(&report, "/readyz").await.0,
+            StatusCode::SERVICE_UNAVAILABLE
+        );
+    }
+}
@@ -0,0 +1,228 @@
+//! Periodic `address_heartbeat` events (`address_heartbeat` scheduler job).
+//!
+//! A quiet address and a broken tracker look the same downstream: no events.
+//! When the job is enabled (`SCHEDULE_ADDRESS_HEARTBEAT`, off by default),
+//! every watched address of every running chain gets an event saying it is
+//! still monitored, when the listener last saw activity for it, and
+//! the chain's current checkpoint. Paused chains are skipped, so a missing
+//! heartbeat always means "not being monitored".
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex as StdMutex};
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use tokio::sync::Mutex;
+use tracing::debug;
+
+use crate::config::Config;
+use crate::control::{ChainControl, Controls};
+use crate::event_type::EventType;
+use crate::publisher::Publisher;
+use crate::trace_context::TraceContext;
+use crate::Event;
+
+/// Payload of an `address_heartbeat` event.
+#[derive(Serialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct Heartbeat {
+    pub address: String,
+    /// When the listener last saw an event for the address (RFC 3339).
+    /// Absent if it hasn't since startup.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_activity_at: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_tx_hash: Option<String>,
+    /// Last processed block (Ethereum) or slot (Solana).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub checkpoint: Option<u64>,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct Activity {
+    pub at: DateTime<Utc>,
+    pub tx_hash: String,
+}
+
+/// Last activity per `(chain, watched address)`, recorded by the publisher.
+/// Cheap to clone; all clones share state.
+#[derive(Debug, Clone, Default)]
+pub struct ActivityLog {
+    last: Arc<StdMutex<HashMap<(String, String), Activity>>>,
+}
+
+impl ActivityLog {
+    /// Note activity for the event's watched address, if it has one.
+    pub fn record(&self, event: &Event) {
+        let Some(address) = &event.watched_address else {
+            return;
+        };
+        if event.event_type == EventType::AddressHeartbeat {
+            return;
+        }
+        self.last.lock().unwrap().insert(
+            (event.chain.clone(), address.clone()),
+            Activity {
+                at: Utc::now(),
+                tx_hash: event.tx_hash.clone(),
+            },
+        );
+    }
+
+    pub(crate) fn get(&self, chain: &str, address: &str) -> Option<Activity> {
+        self.last
+            .lock()
+            .unwrap()
+            .get(&(chain.to_string(), address.to_string()))
+            .cloned()
+    }
+}
+
+fn heartbeat_event(
+    chain: &str,
+    network: &str,
+    address: &str,
+    activity: Option<Activity>,
+    checkpoint: Option<u64>,
+    now: DateTime<Utc>,
+) -> Event {
+    Event {
+        event_id: format!("heartbeat:{}:{}:{}", chain, address, now.timestamp()),
+        chain: chain.to_string(),
+        network: network.to_string(),
+        timestamp: now.to_rfc3339(),
+        event_type: EventType::AddressHeartbeat,
+        heartbeat: Some(Heartbeat {
+            address: address.to_string(),
+            last_activity_at: activity.as_ref().map(|a| a.at.to_rfc3339()),
+            last_tx_hash: activity.map(|a| a.tx_hash),
+            checkpoint,
+        }),
+        watched_address: Some(address.to_string()),
+        ..Default::default()
+    }
+}
+
+/// Publish one heartbeat per watched address of each enabled, running chain.
+pub async fn emit(
+    cfg: &Config,
+    controls: &Controls,
+    last_eth_block: &Arc<Mutex<Option<u64>>>,
+    last_sol_slot: &Arc<Mutex<Option<u64>>>,
+    publisher: &Publisher,
+) -> anyhow::Result<()> {
+    let chains: [(
+        bool,
+        &ChainControl,
+        &str,
+        &[String],
+        &Arc<Mutex<Option<u64>>>,
+    ); 2] = [
+        (
+            cfg.eth_enabled,
+            &controls.eth,
+            &cfg.eth_network,
+            &cfg.watched_addresses_eth,
+            last_eth_block,
+        ),
+        (
+            cfg.sol_enabled,
+            &controls.sol,
+            &cfg.sol_network,
+            &cfg.watched_addresses_sol,
+            last_sol_slot,
+        ),
+    ];
+    for (enabled, control, network, addresses, checkpoint) in chains {
+        if !enabled || control.is_paused() {
+            debug!("Skipping {} heartbeats: not running", control.chain());
+            continue;
+        }
+        let checkpoint = *checkpoint.lock().await;
+        let now = Utc::now();
+        for address in addresses {
+            let activity = publisher.activity().get(control.chain(), address);
+            let event =
+                heartbeat_event(control.chain(), network, address, activity, checkpoint, now);
+            publisher.publish(&event, TraceContext::new_root()).await?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn transfer(address: &str) -> Event {
+        Event {
+            chain: "ethereum".into(),
+            tx_hash: "0xabc".into(),
+            event_type: EventType::Transfer,
+            watched_address: Some(address.into()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_activity_is_recorded_per_chain_and_address() {
+        let log = ActivityLog::default();
+        log.record(&transfer("0x01"));
+        log.record(&Event::default());
+
+        assert_eq!(log.get("ethereum", "0x01").unwrap().tx_hash, "0xabc");
+        assert!(log.get("solana", "0x01").is_none());
+        assert!(log.get("ethereum", "0x02").is_none());
+    }
+
+    #[test]
+    fn test_heartbeat_event_shape() {
+        let now = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let event = heartbeat_event("solana", "devnet", "Abc", None, Some(7), now);
+        let json = serde_json::to_value(&event).unwrap();
+        assert_eq!(json["event_id"], "heartbeat:solana:Abc:1700000000");
+        assert_eq!(json["event_type"], "address_heartbeat");
+        assert_eq!(
+            json["heartbeat"],
+            serde_json::json!({"address": "Abc", "checkpoint": 7})
+        );
+
+        // Heartbeats don't count as activity.
+        let log = ActivityLog::default();
+        log.record(&event);
+        assert!(log.get("solana", "Abc").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_emit_skips_paused_and_disabled_chains() {
+        let cfg = Config {
+            eth_enabled: true,
+            sol_enabled: false,
+            eth_network: "mainnet".into(),
+            watched_addresses_eth: vec!["0x01".into(), "0x02".into()],
+            watched_addresses_sol: vec!["Abc".into()],
+            ..Default::default()
+        };
+        let controls = Controls::default();
+        let (publisher, mut rx) = Publisher::in_memory();
+        publisher.activity().record(&transfer("0x01"));
+        let last_block = Arc::new(Mutex::new(Some(42)));
+        let last_slot = Arc::new(Mutex::new(None));
+
+        emit(&cfg, &controls, &last_block, &last_slot, &publisher)
+            .await
+            .unwrap();
+        let first: serde_json::Value =
+            serde_json::from_str(&rx.recv().await.unwrap().payload).unwrap();
+        assert_eq!(first["heartbeat"]["last_tx_hash"], "0xabc");
+        assert_eq!(first["heartbeat"]["checkpoint"], 42);
+        let second: serde_json::Value =
+            serde_json::from_str(&rx.recv().await.unwrap().payload).unwrap();
+        assert!(second["heartbeat"].get("last_activity_at").is_none());
+        assert!(rx.try_recv().is_err());
+
+        controls.eth.pause();
+        emit(&cfg, &controls, &last_block, &last_slot, &publisher)
+            .await
+            .unwrap();
+        assert!(rx.try_recv().is_err());
+    }
+}
@@ -0,0 +1,448 @@
+//! Helius enhanced-transactions API (`SOL_SOURCE=helius`).
+//!
+//! Helius returns each address's transactions already parsed, with native
+//! and token transfers broken out, so the listener neither fetches nor
+//! decodes raw transactions. With `SOL_SOURCE=helius` this replaces the RPC
+//! signature poller: every watched address is polled for its latest
+//! transactions, and the new ones are published oldest first as the same
+//! `solana_tx` events the RPC poller emits. Like those, they get what the
+//! watched address received: `to` is the address, `token` the first SPL mint
+//! it received and `value` its net balance change in that mint's base
+//! units, or in lamports from the native transfers of a transaction without
+//! token transfers. `value` and `to` stay empty if it received nothing.
+//! Unlike the RPC poller, `from` is set to the sender of that mint or of the
+//! first native transfer to the address.
+//!
+//! Memos aren't parsed from Helius responses, and token symbols aren't
+//! resolved (they are `UNKNOWN`); use the RPC source for memo matching of
+//! expectations.
+use std::sync::Arc;
+
+use chrono::DateTime;
+use serde::Deserialize;
+use tokio::sync::Mutex;
+use tokio::time::Duration;
+use tracing::{error, info, warn};
+
+use crate::confirmations::SolCommitment;
+use crate::control::ChainControl;
+use crate::dedup::{DedupKey, DedupSet};
+use crate::event_type::EventType;
+use crate::publisher::Publisher;
+use crate::spam::{SpamFilter, TransferSignals};
+use crate::token_filter::TokenFilter;
+use crate::token_metadata::UNKNOWN_SYMBOL;
+use crate::trace_context::TraceContext;
+use crate::{Event, Token};
+
+/// Most transactions Helius returns per request.
+pub const MAX_LIMIT: u64 = 100;
+
+/// The fields we use of an enhanced transaction.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HeliusTx {
+    signature: String,
+    slot: u64,
+    timestamp: i64,
+    #[serde(default)]
+    fee_payer: Option<String>,
+    #[serde(default)]
+    native_transfers: Vec<NativeTransfer>,
+    #[serde(default)]
+    token_transfers: Vec<TokenTransfer>,
+    #[serde(default)]
+    account_data: Vec<AccountData>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct NativeTransfer {
+    from_user_account: String,
+    to_user_account: String,
+    amount: u64,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TokenTransfer {
+    mint: String,
+    #[serde(default)]
+    from_user_account: String,
+    #[serde(default)]
+    to_user_account: String,
+}
+
+/// Balance changes of one account of the transaction.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct AccountData {
+    #[serde(default)]
+    token_balance_changes: Vec<TokenBalanceChange>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TokenBalanceChange {
+    user_account: String,
+    mint: String,
+    raw_token_amount: RawTokenAmount,
+}
+
+/// A signed amount in the mint's base units.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawTokenAmount {
+    token_amount: String,
+    decimals: u8,
+}
+
+/// What the watched address received in a transaction.
+struct Received {
+    from: String,
+    /// Net amount credited; `None` unless positive.
+    amount: Option<u128>,
+    token: Option<Token>,
+}
+
+impl HeliusTx {
+    /// What `watched` received: the first SPL mint transferred to it and its
+    /// net change in that mint, or with no token transfers, its net lamports
+    /// from native transfers. `None` if nothing was transferred to it.
+    fn received(&self, watched: &str) -> Option<Received> {
+        if let Some(transfer) = self
+            .token_transfers
+            .iter()
+            .find(|t| t.to_user_account == watched)
+        {
+            let changes: Vec<&TokenBalanceChange> = self
+                .account_data
+                .iter()
+                .flat_map(|account| &account.token_balance_changes)
+                .filter(|c| c.user_account == watched && c.mint == transfer.mint)
+                .collect();
+            let change: i128 = changes
+                .iter()
+                .filter_map(|c| c.raw_token_amount.token_amount.parse::<i128>().ok())
+                .sum();
+            return Some(Received {
+                from: transfer.from_user_account.clone(),
+                amount: (change > 0).then(|| change.unsigned_abs()),
+                token: Some(Token {
+                    address: transfer.mint.clone(),
+                    symbol: UNKNOWN_SYMBOL.to_string(),
+                    decimals: changes.first().map_or(0, |c| c.raw_token_amount.decimals),
+                }),
+            });
+        }
+        if !self.token_transfers.is_empty() {
+            return None;
+        }
+        let first = self
+            .native_transfers
+            .iter()
+            .find(|t| t.to_user_account == watched)?;
+        let change: i128 = self
+            .native_transfers
+            .iter()
+            .map(|t| {
+                let amount = i128::from(t.amount);
+                match (t.from_user_account == watched, t.to_user_account == watched) {
+                    (false, true) => amount,
+                    (true, false) => -amount,
+                    _ => 0,
+                }
+            })
+            .sum();
+        Some(Received {
+            from: first.from_user_account.clone(),
+            amount: (change > 0).then(|| change.unsigned_abs()),
+            token: None,
+        })
+    }
+}
+
+/// Client for one Helius endpoint and API key.
+#[derive(Debug, Clone)]
+pub struct Helius {
+    http: reqwest::Client,
+    url: String,
+    api_key: String,
+    /// Commitment asked for, or Helius's default.
+    commitment: Option<SolCommitment>,
+}
+
+impl Helius {
+    pub fn new(url: &str, api_key: &str) -> Self {
+        Helius {
+            http: reqwest::Client::new(),
+            url: url.trim_end_matches('/').to_string(),
+            api_key: api_key.to_string(),
+            commitment: None,
+        }
+    }
+
+    /// Only return transactions that reached `commitment`.
+    pub fn with_commitment(self, commitment: SolCommitment) -> Self {
+        Helius {
+            commitment: Some(commitment),
+            ..self
+        }
+    }
+
+    /// The `limit` (at most `MAX_LIMIT`) latest transactions of `address`,
+    /// newest first.
+    pub async fn transactions(&self, address: &str, limit: u64) -> anyhow::Result<Vec<HeliusTx>> {
+        let limit = limit.min(MAX_LIMIT).to_string();
+        let mut query = vec![
+            ("api-key", self.api_key.as_str()),
+            ("limit", limit.as_str()),
+        ];
+        if let Some(commitment) = self.commitment {
+            query.push(("commitment", commitment.as_str()));
+        }
+        Ok(self
+            .http
+            .get(format!(
+                "{}/v0/addresses/{}/transactions",
+                self.url, address
+            ))
+            .query(&query)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?)
+    }
+}
+
+/// The `solana_tx` event for an enhanced transaction of `watched`. `None` if
+/// the token or spam filter drops it.
+pub fn event(
+    tx: &HeliusTx,
+    watched: &str,
+    network: &str,
+    key: DedupKey,
+    tokens: &TokenFilter,
+    spam: &SpamFilter,
+) -> Option<Event> {
+    if !tokens.allows_any(tx.token_transfers.iter().map(|t| t.mint.as_str())) {
+        return None;
+    }
+    let received = tx.received(watched);
+    let amount = received.as_ref().and_then(|r| r.amount);
+    let mut event = Event {
+        event_id: key.event_id("sol", &tx.signature, None, watched),
+        chain: "solana".into(),
+        network: network.to_string(),
+        tx_hash: tx.signature.clone(),
+        timestamp: DateTime::from_timestamp(tx.timestamp, 0)?.to_rfc3339(),
+        from: received
+            .as_ref()
+            .map(|r| r.from.clone())
+            .unwrap_or_default(),
+        to: amount.map(|_| watched.to_string()).unwrap_or_default(),
+        value: amount.map(|v| v.to_string()).unwrap_or_default(),
+        event_type: EventType::SolanaTx,
+        slot: Some(tx.slot),
+        token: received.and_then(|r| r.token),
+        watched_address: Some(watched.to_string()),
+        signed_by_watched: tx.fee_payer.as_deref() == Some(watched),
+        ..Default::default()
+    };
+    let signals = TransferSignals {
+        unsolicited: !tx.token_transfers.is_empty() && tx.fee_payer.as_deref() != Some(watched),
+        zero_value: false,
+        symbol: None,
+        metadata_unverified: false,
+    };
+    spam.apply(&mut event, &signals).then_some(event)
+}
+
+/// Poll Helius for the watched addresses until the process exits. Stands in
+/// for the RPC poller; pausing works the same way.
+#[allow(clippy::too_many_arguments)]
+pub async fn track(
+    helius: Helius,
+    network: &str,
+    watched_addresses: &[String],
+    tokens: &TokenFilter,
+    spam: &SpamFilter,
+    processed_txs: Arc<Mutex<DedupSet>>,
+    last_slot: Arc<Mutex<Option<u64>>>,
+    control: ChainControl,
+    publisher: Publisher,
+) {
+    if watched_addresses.is_empty() {
+        info!("No Solana addresses to watch.");
+        return;
+    }
+    info!("Polling Helius enhanced transactions for Solana");
+    loop {
+        control.wait_resumed().await;
+        if control.is_shutting_down() {
+            return;
+        }
+        for address in watched_addresses {
+            if control.is_stopping() {
+                break;
+            }
+            let txs = match helius.transactions(address, MAX_LIMIT).await {
+                Ok(txs) => txs,
+                Err(e) => {
+                    control.set_connected(false);
+                    warn!(
+                        "Error fetching Helius transactions for {}: {:?}",
+                        address, e
+                    );
+                    continue;
+                }
+            };
+            control.set_connected(true);
+            let key = processed_txs.lock().await.key();
+            for tx in txs.iter().rev() {
+                if control.is_stopping() {
+                    break;
+                }
+                let Some(event) = event(tx, address, network, key, tokens, spam) else {
+                    continue;
+                };
+                if processed_txs.lock().await.contains(&event.event_id) {
+                    continue;
+                }
+                if let Err(e) = publisher.publish(&event, TraceContext::new_root()).await {
+                    error!("Failed to publish event: {:?}", e);
+                    continue;
+                }
+                processed_txs.lock().await.insert(event.event_id);
+                let mut last = last_slot.lock().await;
+                if !last.is_some_and(|slot| tx.slot <= slot) {
+                    *last = Some(tx.slot);
+                }
+            }
+        }
+        control.sleep(Duration::from_secs(5)).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spam::SpamMode;
+
+    fn tx(json: serde_json::Value) -> HeliusTx {
+        serde_json::from_value(json).unwrap()
+    }
+
+    #[test]
+    fn test_native_transfer_fills_from_to_value() {
+        let tx = tx(serde_json::json!({
+            "signature": "5sig",
+            "slot": 250000000,
+            "timestamp": 1700000000,
+            "feePayer": "Sender1",
+            "type": "TRANSFER",
+            "nativeTransfers": [
+                {"fromUserAccount": "Sender1", "toUserAccount": "Other", "amount": 5000},
+                {"fromUserAccount": "Sender1", "toUserAccount": "Watched1", "amount": 1000000},
+            ],
+            "tokenTransfers": [],
+        }));
+        let event = event(
+            &tx,
+            "Watched1",
+            "mainnet",
+            DedupKey::Log,
+            &TokenFilter::default(),
+            &SpamFilter::default(),
+        )
+        .unwrap();
+        assert_eq!(event.event_id, "sol:5sig");
+        assert_eq!(event.event_type, EventType::SolanaTx);
+        assert_eq!(event.slot, Some(250000000));
+        assert_eq!(event.timestamp, "2023-11-14T22:13:20+00:00");
+        assert_eq!(
+            (event.from.as_str(), event.to.as_str(), event.value.as_str()),
+            ("Sender1", "Watched1", "1000000")
+        );
+    }
+
+    #[test]
+    fn test_token_only_transaction_and_filters() {
+        let tx = tx(serde_json::json!({
+            "signature": "6sig",
+            "slot": 1,
+            "timestamp": 1700000000,
+            "feePayer": "Stranger",
+            "tokenTransfers": [{"mint": "Mint1", "tokenAmount": 1.5}],
+        }));
+        let tag = SpamFilter::default();
+        let event = event(
+            &tx,
+            "Watched1",
+            "mainnet",
+            DedupKey::Log,
+            &TokenFilter::default(),
+            &tag,
+        )
+        .unwrap();
+        assert_eq!(event.value, "");
+
+        let deny = TokenFilter::new(&[], &["Mint1".to_string()]);
+        assert!(event_for(&tx, &deny, &tag).is_none());
+        // Unsolicited, but no spam heuristic fires without an amount or symbol.
+        let drop = SpamFilter::new(SpamMode::Drop, &[]);
+        assert!(event_for(&tx, &TokenFilter::default(), &drop).is_some());
+    }
+
+    #[test]
+    fn test_received_token_fills_to_value_and_token() {
+        let tx = tx(serde_json::json!({
+            "signature": "7sig",
+            "slot": 1,
+            "timestamp": 1700000000,
+            "feePayer": "Sender1",
+            "nativeTransfers": [
+                {"fromUserAccount": "Watched1", "toUserAccount": "Sender1", "amount": 2039280},
+            ],
+            "tokenTransfers": [
+                {"fromUserAccount": "Watched1", "toUserAccount": "Other", "mint": "Mint0", "tokenAmount": 1},
+                {"fromUserAccount": "Sender1", "toUserAccount": "Watched1", "mint": "Mint1", "tokenAmount": 2.5},
+            ],
+            "accountData": [{
+                "account": "Ata1",
+                "nativeBalanceChange": 0,
+                "tokenBalanceChanges": [
+                    {"userAccount": "Watched1", "mint": "Mint0",
+                     "rawTokenAmount": {"tokenAmount": "-1000", "decimals": 3}},
+                    {"userAccount": "Watched1", "mint": "Mint1",
+                     "rawTokenAmount": {"tokenAmount": "2500000", "decimals": 6}},
+                ],
+            }],
+        }));
+        let event = event_for(&tx, &TokenFilter::default(), &SpamFilter::default()).unwrap();
+        assert_eq!(
+            (event.from.as_str(), event.to.as_str(), event.value.as_str()),
+            ("Sender1", "Watched1", "2500000")
+        );
+        let token = event.token.unwrap();
+        assert_eq!((token.address.as_str(), token.decimals), ("Mint1", 6));
+
+        // Sending only: nothing received, so no amount.
+        let sent = event(
+            &tx,
+            "Sender1",
+            "mainnet",
+            DedupKey::Log,
+            &TokenFilter::default(),
+            &SpamFilter::default(),
+        )
+        .unwrap();
+        assert_eq!((sent.to.as_str(), sent.value.as_str()), ("", ""));
+        assert!(sent.token.is_none());
+    }
+
+    fn event_for(tx: &HeliusTx, tokens: &TokenFilter, spam: &SpamFilter) -> Option<Event> {
+        event(tx, "Watched1", "mainnet", DedupKey::Log, tokens, spam)
+    }
+}
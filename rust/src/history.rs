@@ -0,0 +1,341 @@
+//! State of an address at a past block or slot (`GET /admin/history`).
+//!
+//! Auditors ask what an address held at a given height. On Ethereum that is
+//! read from the node: the native balance and the `balanceOf` of every token
+//! in the chain registry and `ETH_TOKEN_ALLOWLIST`, at the requested block.
+//! That needs an archive node for anything but recent blocks, so
+//! `ETH_ARCHIVE_RPC_URL` is used when set, otherwise `ETH_RPC_URL` if it is
+//! HTTP. Solana RPC has no historical balance call; the SOL balance is
+//! reconstructed from the address's last transaction at or before the slot,
+//! whose post-balances hold it. SPL holdings aren't reconstructed.
+use std::str::FromStr;
+use std::sync::Arc;
+
+use anyhow::{anyhow, bail, Context};
+use ethers::providers::{Http, Middleware, Provider};
+use ethers::types::transaction::eip2718::TypedTransaction;
+use ethers::types::{Address, BlockId, NameOrAddress, TransactionRequest};
+use serde::Serialize;
+use serde_json::Value;
+use solana_client::rpc_client::{GetConfirmedSignaturesForAddress2Config, RpcClient};
+use solana_client::rpc_config::RpcTransactionConfig;
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signature;
+use solana_transaction_status::UiTransactionEncoding;
+
+use crate::amount::Amount;
+use crate::assets::Assets;
+use crate::config::Config;
+use crate::erc20::{balance_of_call, TokenBehavior};
+use crate::token_metadata::TokenMetadata;
+
+/// Pages of 1000 signatures searched back for a Solana address's last
+/// transaction before giving up.
+const MAX_SIGNATURE_PAGES: usize = 20;
+
+/// A balance at the requested height.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct Holding {
+    /// Token contract; absent for the native currency.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+    pub symbol: String,
+    pub decimals: u8,
+    /// In the asset's smallest unit.
+    pub balance: String,
+    pub balance_formatted: String,
+}
+
+impl Holding {
+    fn new(token: Option<String>, symbol: String, amount: Amount, decimals: u8) -> Self {
+        Holding {
+            token,
+            symbol,
+            decimals,
+            balance: amount.to_string(),
+            balance_formatted: amount.formatted(),
+        }
+    }
+}
+
+/// `GET /admin/history/{chain}/{address}` response.
+#[derive(Serialize, Debug, Clone)]
+pub struct AddressState {
+    pub chain: String,
+    pub address: String,
+    /// Block (Ethereum) or slot (Solana) the state is for.
+    pub at: u64,
+    pub native: Holding,
+    /// Non-zero token balances.
+    pub tokens: Vec<Holding>,
+    /// Solana: the transaction the balance was read from, if the address had
+    /// any by then.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub as_of_tx: Option<String>,
+}
+
+/// Clients for historical queries. `None` endpoints make those queries fail.
+#[derive(Clone, Default)]
+pub struct History {
+    eth: Option<Arc<Provider<Http>>>,
+    sol: Option<Arc<RpcClient>>,
+    token_metadata: TokenMetadata,
+    eth_tokens: Vec<Address>,
+    assets: Assets,
+}
+
+impl History {
+    pub fn new(cfg: &Config) -> Self {
+        let eth_url = cfg
+            .eth_archive_rpc_url
+            .clone()
+            .or_else(|| Some(cfg.eth_rpc_url.clone()).filter(|url| url.starts_with("http")));
+        let eth = eth_url
+            .filter(|_| cfg.eth_enabled)
+            .and_then(|url| Provider::<Http>::try_from(url.as_str()).ok())
+            .map(Arc::new);
+        let sol = cfg
+            .sol_enabled
+            .then(|| Arc::new(RpcClient::new(http_url(&cfg.sol_rpc_url))));
+        let mut eth_tokens: Vec<Address> = cfg
+            .eth_chain
+            .iter()
+            .flat_map(|chain| chain.tokens.iter().map(|token| token.address))
+            .chain(cfg.eth_tokens.allowlist().filter_map(|t| t.parse().ok()))
+            .collect();
+        eth_tokens.sort();
+        eth_tokens.dedup();
+        History {
+            eth,
+            sol,
+            token_metadata: TokenMetadata::new(cfg.eth_chain.clone()),
+            eth_tokens,
+            assets: Assets::new(cfg),
+        }
+    }
+
+    /// ERC-20 tokens whose balances [`History::state`] reads on Ethereum.
+    pub(crate) fn eth_tokens(&self) -> &[Address] {
+        &self.eth_tokens
+    }
+
+    /// The registry's `behavior` of an Ethereum `token`, if it lists one.
+    pub(crate) fn token_behavior(&self, token: Address) -> Option<TokenBehavior> {
+        self.token_metadata.behavior(token)
+    }
+
+    /// State of `address` on `chain` at block or slot `at`. Errors with
+    /// [`BadRequest`] for an unknown chain or a malformed address.
+    pub async fn state(&self, chain: &str, address: &str, at: u64) -> anyhow::Result<AddressState> {
+        match chain {
+            "ethereum" => {
+                let address = Address::from_str(address).map_err(|_| BadRequest::address())?;
+                self.eth_state(address, at).await
+            }
+            "solana" => {
+                let address = Pubkey::from_str(address).map_err(|_| BadRequest::address())?;
+                self.sol_state(address, at).await
+            }
+            other => Err(BadRequest(format!("unknown chain '{}'", other)).into()),
+        }
+    }
+
+    async fn eth_state(&self, address: Address, block: u64) -> anyhow::Result<AddressState> {
+        let provider = self.eth.as_ref().ok_or_else(|| {
+            anyhow!("Ethereum history needs ETH_ARCHIVE_RPC_URL or an HTTP ETH_RPC_URL")
+        })?;
+        let at = BlockId::Number(block.into());
+        let (symbol, decimals) = self.assets.native("ethereum").unwrap_or_default();
+        let balance = provider
+            .get_balance(address, Some(at))
+            .await
+            .with_context(|| format!("eth_getBalance at block {}", block))?;
+        let native = Holding::new(None, symbol, Amount::new(balance, decimals), decimals);
+
+        let mut tokens = Vec::new();
+        for &token in &self.eth_tokens {
+            let data = balance_of_call(address);
+            let tx = TypedTransaction::Legacy(TransactionRequest {
+                to: Some(NameOrAddress::Address(token)),
+                data: Some(data.into()),
+                ..Default::default()
+            });
+            let bytes = provider
+                .call(&tx, Some(at))
+                .await
+                .with_context(|| format!("balanceOf on {:?} at block {}", token, block))?;
+            let (symbol, decimals) = self.token_metadata.get(provider.as_ref(), token).await;
+            let amount = Amount::from_abi_word(&bytes, decimals);
+            if !amount.is_zero() {
+                let token = Some(format!("{:?}", token));
+                tokens.push(Holding::new(token, symbol, amount, decimals));
+            }
+        }
+        Ok(AddressState {
+            chain: "ethereum".into(),
+            address: format!("{:?}", address),
+            at: block,
+            native,
+            tokens,
+            as_of_tx: None,
+        })
+    }
+
+    async fn sol_state(&self, address: Pubkey, slot: u64) -> anyhow::Result<AddressState> {
+        let rpc = Arc::clone(
+            self.sol
+                .as_ref()
+                .ok_or_else(|| anyhow!("Solana history needs SOL_ENABLED"))?,
+        );
+        let (lamports, as_of_tx) =
+            tokio::task::spawn_blocking(move || sol_balance_at(&rpc, &address, slot)).await??;
+        let (symbol, decimals) = self.assets.native("solana").unwrap_or_default();
+        Ok(AddressState {
+            chain: "solana".into(),
+            address: address.to_string(),
+            at: slot,
+            native: Holding::new(None, symbol, Amount::new(lamports, decimals), decimals),
+            tokens: Vec::new(),
+            as_of_tx,
+        })
+    }
+}
+
+/// A request the caller got wrong, as opposed to a failed RPC call.
+#[derive(Debug)]
+pub struct BadRequest(pub String);
+
+impl BadRequest {
+    fn address() -> Self {
+        BadRequest("malformed address".into())
+    }
+}
+
+impl std::fmt::Display for BadRequest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for BadRequest {}
+
+/// The JSON-RPC HTTP endpoint of a Solana URL, which may be a websocket one.
+pub(crate) fn http_url(url: &str) -> String {
+    if let Some(rest) = url.strip_prefix("wss://") {
+        format!("https://{}", rest)
+    } else if let Some(rest) = url.strip_prefix("ws://") {
+        format!("http://{}", rest)
+    } else {
+        url.to_string()
+    }
+}
+
+/// Lamports of `address` after its last transaction at or before `slot`, and
+/// that transaction's signature. An address without one had no balance.
+fn sol_balance_at(
+    rpc: &RpcClient,
+    address: &Pubkey,
+    slot: u64,
+) -> anyhow::Result<(u64, Option<String>)> {
+    let mut before = None;
+    for _ in 0..MAX_SIGNATURE_PAGES {
+        let page = rpc.get_signatures_for_address_with_config(
+            address,
+            GetConfirmedSignaturesForAddress2Config {
+                before,
+                until: None,
+                limit: None,
+                commitment: Some(CommitmentConfig::confirmed()),
+            },
+        )?;
+        let Some(last) = page.last() else {
+            return Ok((0, None));
+        };
+        // Newest first, so the first one at or before the slot is the last.
+        if let Some(status) = page.iter().find(|status| status.slot <= slot) {
+            let signature = Signature::from_str(&status.signature)?;
+            let tx = rpc.get_transaction_with_config(
+                &signature,
+                RpcTransactionConfig {
+                    encoding: Some(UiTransactionEncoding::JsonParsed),
+                    commitment: Some(CommitmentConfig::confirmed()),
+                    max_supported_transaction_version: Some(0),
+                },
+            )?;
+            let meta = tx
+                .transaction
+                .meta
+                .as_ref()
+                .ok_or_else(|| anyhow!("transaction {} has no status meta", signature))?;
+            let json = serde_json::to_value(&tx.transaction.transaction)?;
+            let lamports = post_balance(&json, &meta.post_balances, &address.to_string())
+                .ok_or_else(|| anyhow!("{} is not an account of {}", address, signature))?;
+            return Ok((lamports, Some(status.signature.clone())));
+        }
+        before = Some(Signature::from_str(&last.signature)?);
+    }
+    bail!(
+        "no transaction of {} at or before slot {} in its last {} pages of signatures",
+        address,
+        slot,
+        MAX_SIGNATURE_PAGES
+    )
+}
+
+/// The post-balance of `address` in a `jsonParsed` transaction, whose
+/// account keys include the ones loaded from lookup tables, in balance order.
+fn post_balance(tx: &Value, post_balances: &[u64], address: &str) -> Option<u64> {
+    let index = tx["message"]["accountKeys"]
+        .as_array()?
+        .iter()
+        .position(|key| key["pubkey"] == address)?;
+    post_balances.get(index).copied()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_post_balance_by_account_index() {
+        let tx = json!({
+            "message": {
+                "accountKeys": [
+                    {"pubkey": "Payer111", "signer": true},
+                    {"pubkey": "Watched111", "signer": false},
+                ]
+            }
+        });
+        assert_eq!(post_balance(&tx, &[5, 7], "Watched111"), Some(7));
+        assert_eq!(post_balance(&tx, &[5, 7], "Other111"), None);
+        assert_eq!(post_balance(&json!({}), &[5], "Payer111"), None);
+    }
+
+    #[test]
+    fn test_balance_of_calldata_and_urls() {
+        let owner = Address::from_low_u64_be(0xabcd);
+        let data = balance_of_call(owner);
+        assert_eq!(data.len(), 36);
+        assert_eq!(&data[..4], &[0x70, 0xa0, 0x82, 0x31]);
+        assert_eq!(&data[34..], &[0xab, 0xcd]);
+
+        assert_eq!(http_url("wss://rpc.example"), "https://rpc.example");
+        assert_eq!(http_url("http://127.0.0.1:8899"), "http://127.0.0.1:8899");
+    }
+
+    #[tokio::test]
+    async fn test_bad_requests_are_distinguished() {
+        let history = History::default();
+        for (chain, address) in [("bitcoin", "x"), ("ethereum", "0xzz"), ("solana", "0x01")] {
+            let err = history.state(chain, address, 1).await.unwrap_err();
+            assert!(err.is::<BadRequest>(), "{}", err);
+        }
+        let err = history
+            .state("ethereum", &format!("{:?}", Address::zero()), 1)
+            .await
+            .unwrap_err();
+        assert!(!err.is::<BadRequest>());
+    }
+}
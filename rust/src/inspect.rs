@@ -0,0 +1,393 @@
+//! Operational inspection endpoints of the admin API: recent events, chain
+//! status with lag, and the watched addresses.
+//!
+//! - `GET /admin/events?chain=&address=&since=&limit=`, also served as
+//!   `GET /events/recent`, answers from ring buffers of the last
+//!   `RECENT_EVENTS` published events of each chain (default 1000), filled
+//!   from the event bus, newest first. A busy chain doesn't push a quiet
+//!   one's events out. `address` matches `from`,
+//!   `to` and the watched address case-insensitively; `since` is Unix
+//!   seconds or RFC 3339 and matches the event `timestamp`. Events are
+//!   returned as published. The buffer restarts empty with the listener.
+//! - `GET /admin/status` adds to each chain's `GET /admin/chains` entry the
+//!   chain head, asked from the node on every request, the lag of the
+//!   checkpoint behind it, and when the chain's last event was published.
+//! - `GET /admin/watched` lists every watched address, including those
+//!   added at runtime (see `watchlist.rs`), with its address book label and
+//!   the last activity the publisher saw for it.
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex as StdMutex};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use solana_client::rpc_client::RpcClient;
+use tokio::time::{timeout, Duration};
+
+use crate::address_book::AddressBook;
+use crate::bus::{BusEvent, BusSubscriber};
+use crate::config::Config;
+use crate::heartbeat::ActivityLog;
+use crate::history::{http_url, BadRequest};
+use crate::watchlist::{WatchList, WatchLists};
+
+/// Default `RECENT_EVENTS`.
+pub const DEFAULT_RECENT_EVENTS: u64 = 1000;
+/// Events returned by `GET /admin/events` without a `limit`.
+const DEFAULT_LIMIT: usize = 100;
+/// How long `GET /admin/status` waits for a chain head.
+const HEAD_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// `GET /admin/events` query.
+#[derive(Debug, Default, Deserialize)]
+pub struct EventsQuery {
+    pub chain: Option<String>,
+    pub address: Option<String>,
+    pub since: Option<String>,
+    pub limit: Option<usize>,
+}
+
+/// An entry of `GET /admin/watched`.
+#[derive(Debug, Serialize, PartialEq, Eq)]
+pub struct WatchedAddress {
+    pub chain: &'static str,
+    pub address: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    /// When the publisher last saw an event for the address (RFC 3339).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_activity_at: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_tx_hash: Option<String>,
+}
+
+#[derive(Debug, Default)]
+struct Buffer {
+    /// Each chain's events, with the order they were published in.
+    events: HashMap<String, VecDeque<(u64, Arc<BusEvent>)>>,
+    published: u64,
+    /// When each chain last published, kept past the buffer's eviction.
+    last_published: HashMap<String, DateTime<Utc>>,
+}
+
+/// State of the inspection endpoints. Cheap to clone; all clones share the
+/// buffer.
+#[derive(Debug, Clone, Default)]
+pub struct Inspect {
+    capacity: usize,
+    buffer: Arc<StdMutex<Buffer>>,
+    /// Watch lists of the enabled chains.
+    watched: Vec<WatchList>,
+    /// Labels of the watched addresses.
+    address_book: AddressBook,
+    activity: ActivityLog,
+    /// Solana RPC asked for the head; `None` when Solana is disabled.
+    sol_rpc_url: Option<String>,
+}
+
+impl Inspect {
+    /// Buffer the events `events` receives from now on, and report the
+    /// activity `activity` records.
+    pub fn start(
+        cfg: &Config,
+        watched: &WatchLists,
+        mut events: BusSubscriber,
+        activity: ActivityLog,
+    ) -> Self {
+        let watched = [
+            (cfg.eth_enabled, &watched.eth),
+            (cfg.sol_enabled, &watched.sol),
+        ]
+        .into_iter()
+        .filter(|(enabled, _)| *enabled)
+        .map(|(_, list)| list.clone())
+        .collect();
+        let inspect = Inspect {
+            capacity: cfg.recent_events,
+            buffer: Arc::default(),
+            watched,
+            address_book: cfg.address_book.clone(),
+            activity,
+            sol_rpc_url: cfg.sol_enabled.then(|| http_url(&cfg.sol_rpc_url)),
+        };
+        let buffer = inspect.clone();
+        tokio::spawn(async move {
+            while let Some(published) = events.recv().await {
+                buffer.record(published, Utc::now());
+            }
+        });
+        inspect
+    }
+
+    fn record(&self, published: Arc<BusEvent>, now: DateTime<Utc>) {
+        let mut buffer = self.buffer.lock().unwrap();
+        buffer
+            .last_published
+            .insert(published.event.chain.clone(), now);
+        if self.capacity == 0 {
+            return;
+        }
+        buffer.published += 1;
+        let order = buffer.published;
+        let events = buffer
+            .events
+            .entry(published.event.chain.clone())
+            .or_default();
+        if events.len() == self.capacity {
+            events.pop_front();
+        }
+        events.push_back((order, published));
+    }
+
+    /// Buffered events matching `query`, newest first, as published.
+    pub fn events(&self, query: &EventsQuery) -> anyhow::Result<Vec<serde_json::Value>> {
+        let since = query.since.as_deref().map(parse_since).transpose()?;
+        let buffer = self.buffer.lock().unwrap();
+        let mut events: Vec<&(u64, Arc<BusEvent>)> = buffer
+            .events
+            .iter()
+            .filter(|(chain, _)| query.chain.as_ref().map_or(true, |c| c == *chain))
+            .flat_map(|(_, events)| events)
+            .collect();
+        events.sort_unstable_by_key(|(order, _)| std::cmp::Reverse(*order));
+        let matching = events
+            .into_iter()
+            .map(|(_, published)| published)
+            .filter(|published| {
+                let event = &published.event;
+                query.address.as_ref().map_or(true, |address| {
+                    address.eq_ignore_ascii_case(&event.from)
+                        || address.eq_ignore_ascii_case(&event.to)
+                        || event
+                            .watched_address
+                            .as_deref()
+                            .is_some_and(|watched| address.eq_ignore_ascii_case(watched))
+                }) && since.map_or(true, |since| {
+                    event_time(&event.timestamp).is_some_and(|at| at >= since)
+                })
+            });
+        Ok(matching
+            .take(query.limit.unwrap_or(DEFAULT_LIMIT))
+            .filter_map(|published| serde_json::from_str(&published.payload).ok())
+            .collect())
+    }
+
+    /// When `chain` last published an event.
+    pub fn last_published(&self, chain: &str) -> Option<DateTime<Utc>> {
+        self.buffer
+            .lock()
+            .unwrap()
+            .last_published
+            .get(chain)
+            .copied()
+    }
+
+    /// The current Solana slot, if Solana is enabled and the node answers.
+    pub async fn sol_head(&self) -> Option<u64> {
+        let client = RpcClient::new(self.sol_rpc_url.clone()?);
+        let slot = tokio::task::spawn_blocking(move || client.get_slot());
+        match timeout(HEAD_TIMEOUT, slot).await {
+            Ok(Ok(Ok(slot))) => Some(slot),
+            _ => None,
+        }
+    }
+
+    /// Every watched address with its last activity.
+    pub fn watched(&self) -> Vec<WatchedAddress> {
+        self.watched
+            .iter()
+            .flat_map(|list| {
+                let chain = list.chain();
+                list.addresses()
+                    .into_iter()
+                    .map(move |address| (chain, address))
+            })
+            .map(|(chain, address)| {
+                let activity = self.activity.get(chain, &address);
+                WatchedAddress {
+                    chain,
+                    label: self.address_book.label(chain, &address).map(str::to_string),
+                    address,
+                    last_activity_at: activity.as_ref().map(|a| a.at.to_rfc3339()),
+                    last_tx_hash: activity.map(|a| a.tx_hash),
+                }
+            })
+            .collect()
+    }
+}
+
+/// `since` as Unix seconds or RFC 3339.
+fn parse_since(since: &str) -> anyhow::Result<i64> {
+    if let Ok(seconds) = since.parse::<i64>() {
+        return Ok(seconds);
+    }
+    DateTime::parse_from_rfc3339(since)
+        .map(|at| at.timestamp())
+        .map_err(|_| {
+            BadRequest(format!(
+                "since must be Unix seconds or RFC 3339, got {:?}",
+                since
+            ))
+            .into()
+        })
+}
+
+/// An event `timestamp`, which is Unix seconds for chain events and RFC 3339
+/// for heartbeats.
+fn event_time(timestamp: &str) -> Option<i64> {
+    parse_since(timestamp).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::address_book::AddressEntry;
+    use crate::Event;
+    use tracing::Span;
+
+    fn published(chain: &str, from: &str, timestamp: &str) -> Arc<BusEvent> {
+        let event = Event {
+            event_id: format!("{}:{}", chain, timestamp),
+            chain: chain.into(),
+            from: from.into(),
+            timestamp: timestamp.into(),
+            ..Default::default()
+        };
+        Arc::new(BusEvent {
+            payload: serde_json::to_string(&event).unwrap(),
+            event,
+            span: Span::none(),
+        })
+    }
+
+    fn inspect(capacity: usize) -> Inspect {
+        Inspect {
+            capacity,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_query_filters_newest_first() {
+        let inspect = inspect(10);
+        let now = Utc::now();
+        inspect.record(published("ethereum", "0xAB", "1700000000"), now);
+        inspect.record(published("solana", "Sol1", "1700000060"), now);
+        inspect.record(published("ethereum", "0xcd", "1700000120"), now);
+
+        let ids = |query: EventsQuery| -> Vec<String> {
+            inspect
+                .events(&query)
+                .unwrap()
+                .iter()
+                .map(|e| e["event_id"].as_str().unwrap().to_string())
+                .collect()
+        };
+        assert_eq!(
+            ids(EventsQuery::default()),
+            [
+                "ethereum:1700000120",
+                "solana:1700000060",
+                "ethereum:1700000000"
+            ]
+        );
+        let ethereum = EventsQuery {
+            chain: Some("ethereum".into()),
+            ..Default::default()
+        };
+        assert_eq!(ids(ethereum).len(), 2);
+        let address = EventsQuery {
+            address: Some("0xab".into()),
+            ..Default::default()
+        };
+        assert_eq!(ids(address), ["ethereum:1700000000"]);
+        let since = EventsQuery {
+            since: Some("2023-11-14T22:14:20Z".into()),
+            limit: Some(1),
+            ..Default::default()
+        };
+        assert_eq!(ids(since), ["ethereum:1700000120"]);
+
+        let err = inspect
+            .events(&EventsQuery {
+                since: Some("yesterday".into()),
+                ..Default::default()
+            })
+            .unwrap_err();
+        assert!(err.is::<BadRequest>());
+    }
+
+    #[test]
+    fn test_buffer_is_bounded() {
+        let inspect = inspect(2);
+        let now = Utc::now();
+        for timestamp in ["1", "2", "3"] {
+            inspect.record(published("ethereum", "0x01", timestamp), now);
+        }
+        let events = inspect.events(&EventsQuery::default()).unwrap();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[1]["event_id"], "ethereum:2");
+        assert_eq!(inspect.last_published("ethereum"), Some(now));
+
+        // Without a buffer, the last publish time is still tracked.
+        let disabled = super::Inspect::default();
+        disabled.record(published("solana", "Sol1", "1"), now);
+        assert!(disabled.events(&EventsQuery::default()).unwrap().is_empty());
+        assert_eq!(disabled.last_published("solana"), Some(now));
+    }
+
+    #[test]
+    fn test_buffers_are_per_chain() {
+        let inspect = inspect(2);
+        let now = Utc::now();
+        inspect.record(published("solana", "Sol1", "1"), now);
+        for timestamp in ["2", "3", "4"] {
+            inspect.record(published("ethereum", "0x01", timestamp), now);
+        }
+        let ids: Vec<serde_json::Value> = inspect
+            .events(&EventsQuery::default())
+            .unwrap()
+            .into_iter()
+            .map(|e| e["event_id"].clone())
+            .collect();
+        assert_eq!(ids, ["ethereum:4", "ethereum:3", "solana:1"]);
+    }
+
+    #[test]
+    fn test_watched_addresses() {
+        let activity = ActivityLog::default();
+        activity.record(&Event {
+            chain: "ethereum".into(),
+            tx_hash: "0xabc".into(),
+            watched_address: Some("0x01".into()),
+            ..Default::default()
+        });
+        let inspect = Inspect {
+            watched: vec![
+                WatchList::new("ethereum", &["0x01".into()]),
+                WatchList::new("solana", &["Sol1".into()]),
+            ],
+            address_book: AddressBook::new(&[AddressEntry {
+                address: "ethereum:0x01".into(),
+                label: Some("Treasury".into()),
+                cold_storage: false,
+                severity: Default::default(),
+            }])
+            .unwrap(),
+            activity,
+            ..Default::default()
+        };
+        let watched = inspect.watched();
+        assert_eq!(watched[0].label.as_deref(), Some("Treasury"));
+        assert_eq!(watched[0].last_tx_hash.as_deref(), Some("0xabc"));
+        assert_eq!(
+            watched[1],
+            WatchedAddress {
+                chain: "solana",
+                address: "Sol1".into(),
+                label: None,
+                last_activity_at: None,
+                last_tx_hash: None,
+            }
+        );
+    }
+}
@@ -0,0 +1,81 @@
+//! Kafka destinations (`sink = "kafka"`, build with `--features kafka`).
+//!
+//! Each event is produced to `topic` on `brokers`, or to the topic it
+//! renders for the event if it is a template such as `events-{chain}`, keyed
+//! by the destination's `partition_key`, so one wallet's or one chain's
+//! events land in one partition and keep their order. The producer waits
+//! for the brokers' acknowledgement (`acks=all`, idempotent) before a
+//! delivery counts as published; a failed or timed-out produce is retried by
+//! the destination worker like any other sink error, without duplicates
+//! thanks to idempotence. Other librdkafka producer settings go in the
+//! destination's `kafka` table, e.g. `"compression.type" = "lz4"`.
+use std::time::Duration;
+
+use anyhow::anyhow;
+use async_trait::async_trait;
+use rdkafka::config::ClientConfig;
+use rdkafka::producer::{FutureProducer, FutureRecord};
+use rdkafka::util::Timeout;
+
+use crate::config::DestinationConfig;
+use crate::sink::EventSink;
+
+/// How long a produce may wait for room in librdkafka's queue.
+const QUEUE_TIMEOUT: Duration = Duration::from_secs(5);
+
+pub struct KafkaSink {
+    producer: FutureProducer,
+    brokers: String,
+    topic: String,
+}
+
+impl KafkaSink {
+    /// Create the producer of `dest`, a `kafka` destination.
+    pub fn open(dest: &DestinationConfig) -> anyhow::Result<Self> {
+        let mut config = ClientConfig::new();
+        config
+            .set("bootstrap.servers", &dest.brokers)
+            .set("acks", "all")
+            .set("enable.idempotence", "true");
+        for (key, value) in &dest.kafka {
+            config.set(key, value);
+        }
+        let producer = config
+            .create()
+            .map_err(|e| anyhow!("destination {}: invalid Kafka config: {}", dest.name, e))?;
+        Ok(KafkaSink {
+            producer,
+            brokers: dest.brokers.clone(),
+            topic: dest.topic.clone(),
+        })
+    }
+}
+
+#[async_trait]
+impl EventSink for KafkaSink {
+    async fn publish(&self, message: &[u8]) -> anyhow::Result<()> {
+        let record = FutureRecord::<(), [u8]>::to(&self.topic).payload(message);
+        self.producer
+            .send(record, Timeout::After(QUEUE_TIMEOUT))
+            .await
+            .map_err(|(e, _)| anyhow!("produce to {} failed: {}", self.topic, e))?;
+        Ok(())
+    }
+
+    async fn publish_keyed(&self, key: &str, message: &[u8]) -> anyhow::Result<()> {
+        self.publish_routed(&self.topic, key, message).await
+    }
+
+    async fn publish_routed(&self, topic: &str, key: &str, message: &[u8]) -> anyhow::Result<()> {
+        let record = FutureRecord::to(topic).key(key).payload(message);
+        self.producer
+            .send(record, Timeout::After(QUEUE_TIMEOUT))
+            .await
+            .map_err(|(e, _)| anyhow!("produce to {} failed: {}", topic, e))?;
+        Ok(())
+    }
+
+    fn describe(&self) -> String {
+        format!("kafka topic {} on {}", self.topic, self.brokers)
+    }
+}
@@ -0,0 +1,285 @@
+//! Emergency brake on publishing, driven by Redis keys (`KILL_SWITCH_KEY`).
+//!
+//! During a downstream incident operators need to stop the flow of events
+//! without access to the tracker host. The `kill_switch` job polls Redis
+//! every 2s; while `tracker:pause` exists nothing is published, and while
+//! `tracker:pause:<chain>` exists that chain's events are held, for any
+//! tracked chain (including `[[evm_networks]]`, Tron and Substrate chains):
+//!
+//! ```bash
+//! redis-cli SET tracker:pause:ethereum "downstream incident"
+//! redis-cli DEL tracker:pause:ethereum
+//! ```
+//!
+//! The trackers keep running. With `KILL_SWITCH_MODE=buffer` (the default)
+//! held events wait in memory, up to `KILL_SWITCH_BUFFER` events, and are
+//! published in order when the key is deleted; with `drop` they are
+//! discarded. Events derived from a held event, such as alerts, are held with
+//! it. If Redis can't be read, the last known state stays in effect.
+use std::collections::{HashSet, VecDeque};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex as StdMutex};
+
+use anyhow::anyhow;
+use tracing::warn;
+
+use crate::Event;
+
+/// What happens to events published while the switch is on.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum KillSwitchMode {
+    /// Keep them and publish them once released.
+    #[default]
+    Buffer,
+    Drop,
+}
+
+impl FromStr for KillSwitchMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "buffer" => Ok(KillSwitchMode::Buffer),
+            "drop" => Ok(KillSwitchMode::Drop),
+            other => Err(anyhow!(
+                "invalid KILL_SWITCH_MODE '{}' (expected buffer or drop)",
+                other
+            )),
+        }
+    }
+}
+
+/// Kill switch settings.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KillSwitchConfig {
+    /// Global key; per-chain keys append `:<chain>` (`KILL_SWITCH_KEY`).
+    pub key: String,
+    pub mode: KillSwitchMode,
+    /// Most events held in buffer mode (`KILL_SWITCH_BUFFER`).
+    pub max_buffered: usize,
+}
+
+impl Default for KillSwitchConfig {
+    fn default() -> Self {
+        KillSwitchConfig {
+            key: "tracker:pause".to_string(),
+            mode: KillSwitchMode::default(),
+            max_buffered: 10_000,
+        }
+    }
+}
+
+/// Scopes the switch is on for: `""` for all events, or a chain.
+type Scopes = HashSet<String>;
+
+#[derive(Debug, Default)]
+struct State {
+    halted: Scopes,
+    /// Chains with a per-chain key, from the tracked chains.
+    chains: Vec<String>,
+    buffer: VecDeque<Event>,
+    /// Events dropped since the last report.
+    dropped: u64,
+}
+
+impl State {
+    fn holds(&self, event: &Event) -> bool {
+        self.halted.contains("") || self.halted.contains(&event.chain)
+    }
+}
+
+/// Shared switch state; cheap to clone.
+#[derive(Clone, Debug, Default)]
+pub struct KillSwitch {
+    cfg: KillSwitchConfig,
+    /// `None` leaves the switch off for good (replay, tests).
+    client: Option<redis::Client>,
+    state: Arc<StdMutex<State>>,
+}
+
+impl KillSwitch {
+    pub fn new(cfg: KillSwitchConfig, client: redis::Client) -> Self {
+        KillSwitch {
+            cfg,
+            client: Some(client),
+            state: Arc::default(),
+        }
+    }
+
+    /// Read a per-chain key for each of `chains`, the event `chain` names
+    /// of the running trackers.
+    pub fn watch_chains<'a>(&self, chains: impl IntoIterator<Item = &'a str>) {
+        self.state.lock().unwrap().chains = chains.into_iter().map(str::to_string).collect();
+    }
+
+    /// Take `event` if the switch is on for it, buffering or dropping it.
+    /// Returns false if it should be published now.
+    pub fn hold(&self, event: &Event) -> bool {
+        let mut state = self.state.lock().unwrap();
+        if !state.holds(event) {
+            return false;
+        }
+        if self.cfg.mode == KillSwitchMode::Buffer && state.buffer.len() < self.cfg.max_buffered {
+            state.buffer.push_back(event.clone());
+        } else {
+            state.dropped += 1;
+        }
+        true
+    }
+
+    /// Scopes whose key currently exists.
+    pub async fn read(&self) -> anyhow::Result<Option<Scopes>> {
+        let Some(client) = &self.client else {
+            return Ok(None);
+        };
+        let chains = self.state.lock().unwrap().chains.clone();
+        let mut con = client.get_multiplexed_async_connection().await?;
+        let mut cmd = redis::cmd("MGET");
+        cmd.arg(&self.cfg.key);
+        for chain in &chains {
+            cmd.arg(format!("{}:{}", self.cfg.key, chain));
+        }
+        let values: Vec<Option<String>> = cmd.query_async(&mut con).await?;
+        let scopes = std::iter::once("")
+            .chain(chains.iter().map(String::as_str))
+            .zip(values)
+            .filter(|(_, value)| value.is_some())
+            .map(|(scope, _)| scope.to_string())
+            .collect();
+        Ok(Some(scopes))
+    }
+
+    /// Switch on every scope in `wanted` and take the next buffered event
+    /// that `wanted` no longer holds. Scopes being released stay on until
+    /// their buffered events are taken, so newer events can't overtake them;
+    /// once none are left `halted` becomes exactly `wanted`.
+    pub fn apply(&self, wanted: &Scopes) -> Option<Event> {
+        let mut state = self.state.lock().unwrap();
+        for scope in wanted.difference(&state.halted.clone()) {
+            warn!("Kill switch on for {}; holding events", describe(scope));
+            state.halted.insert(scope.clone());
+        }
+        if state.dropped > 0 {
+            warn!(
+                "Kill switch dropped {} events ({} buffered)",
+                state.dropped,
+                state.buffer.len()
+            );
+            state.dropped = 0;
+        }
+        let released = State {
+            halted: wanted.clone(),
+            ..Default::default()
+        };
+        if let Some(i) = state.buffer.iter().position(|e| !released.holds(e)) {
+            return state.buffer.remove(i);
+        }
+        for scope in state.halted.difference(wanted) {
+            warn!(
+                "Kill switch off for {}; publishing resumed",
+                describe(scope)
+            );
+        }
+        state.halted = wanted.clone();
+        None
+    }
+
+    /// Put back an event taken by [`KillSwitch::apply`] that couldn't be
+    /// published, ahead of the rest so it is taken again first.
+    pub fn requeue(&self, event: Event) {
+        self.state.lock().unwrap().buffer.push_front(event);
+    }
+
+    /// Events waiting to be published.
+    pub fn buffered(&self) -> usize {
+        self.state.lock().unwrap().buffer.len()
+    }
+}
+
+fn describe(scope: &str) -> &str {
+    if scope.is_empty() {
+        "all chains"
+    } else {
+        scope
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kill_switch(mode: KillSwitchMode, max_buffered: usize) -> KillSwitch {
+        KillSwitch {
+            cfg: KillSwitchConfig {
+                mode,
+                max_buffered,
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    fn event(chain: &str, id: &str) -> Event {
+        Event {
+            event_id: id.into(),
+            chain: chain.into(),
+            ..Default::default()
+        }
+    }
+
+    fn scopes(names: &[&str]) -> Scopes {
+        names.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse_mode() {
+        assert_eq!(
+            "Drop".parse::<KillSwitchMode>().unwrap(),
+            KillSwitchMode::Drop
+        );
+        assert!("pause".parse::<KillSwitchMode>().is_err());
+    }
+
+    #[test]
+    fn test_chain_switch_buffers_until_released() {
+        let switch = kill_switch(KillSwitchMode::Buffer, 10);
+        assert!(!switch.hold(&event("ethereum", "a")));
+
+        assert!(switch.apply(&scopes(&["ethereum"])).is_none());
+        assert!(switch.hold(&event("ethereum", "b")));
+        assert!(!switch.hold(&event("solana", "c")));
+        assert!(switch.hold(&event("ethereum", "d")));
+        assert_eq!(switch.buffered(), 2);
+
+        // Released: buffered events come back in order, and the chain stays
+        // held until they are all out.
+        let b = switch.apply(&Scopes::new()).unwrap();
+        assert_eq!(b.event_id, "b");
+        // Not published: taken again first.
+        switch.requeue(b);
+        assert_eq!(switch.apply(&Scopes::new()).unwrap().event_id, "b");
+        assert!(switch.hold(&event("ethereum", "e")));
+        assert_eq!(switch.apply(&Scopes::new()).unwrap().event_id, "d");
+        assert_eq!(switch.apply(&Scopes::new()).unwrap().event_id, "e");
+        assert!(switch.apply(&Scopes::new()).is_none());
+        assert!(!switch.hold(&event("ethereum", "f")));
+    }
+
+    #[test]
+    fn test_global_switch_and_drop_mode() {
+        let switch = kill_switch(KillSwitchMode::Drop, 10);
+        assert!(switch.apply(&scopes(&[""])).is_none());
+        assert!(switch.hold(&event("solana", "a")));
+        assert!(switch.hold(&event("", "portfolio")));
+        assert_eq!(switch.buffered(), 0);
+        assert!(switch.apply(&Scopes::new()).is_none());
+        assert!(!switch.hold(&event("solana", "b")));
+
+        // A full buffer drops instead.
+        let switch = kill_switch(KillSwitchMode::Buffer, 1);
+        switch.apply(&scopes(&[""]));
+        assert!(switch.hold(&event("solana", "a")));
+        assert!(switch.hold(&event("solana", "b")));
+        assert_eq!(switch.buffered(), 1);
+    }
+}
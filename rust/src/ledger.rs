@@ -0,0 +1,469 @@
+//! Double-entry ledger export of published events (`tracker ledger`).
+//!
+//! Reads events as JSON lines, e.g. from `tracker replay --out` or an archive
+//! of a destination channel, and writes each transfer as a balanced
+//! transaction with two postings: the amount debited to the receiving
+//! address and credited from the sending one. Every address gets an account
+//! `Assets:<Chain>:<Network>:<address>` so finance can map them onto its own
+//! chart of accounts. Amounts use the asset fields the publisher fills in
+//! (`asset_symbol`, `value_formatted`), with `logical_asset` taking the place
+//! of the symbol when set so bridged tokens roll up into one commodity;
+//! events without them, spam-tagged events and events without a parseable
+//! timestamp are skipped. Events don't carry transaction fees, so there are
+//! no fee postings.
+//!
+//! Transactions are dated in [`ReportOptions::timezone`], UTC by default,
+//! so a day's transfers add up to the organization's local business day.
+//! CSV amounts are written in [`ReportOptions::locale`]'s number format for
+//! spreadsheets that expect it; `raw_amount`, the published `timestamp` and
+//! Beancount, whose syntax is fixed, are not localized.
+use std::io::{BufRead, Write};
+
+use anyhow::Context;
+use chrono::DateTime;
+use chrono_tz::Tz;
+use clap::ValueEnum;
+use tracing::warn;
+use tracker_client::Event;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum LedgerFormat {
+    /// Beancount transactions, with `auto_accounts` opening the accounts.
+    #[default]
+    Beancount,
+    /// One row per posting.
+    Csv,
+}
+
+/// Number format of CSV amounts.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum NumberLocale {
+    /// As published: `-1234.5`.
+    #[default]
+    Plain,
+    /// `-1,234.5`
+    En,
+    /// `-1.234,5`
+    De,
+    /// `-1 234,5`, grouped with a narrow no-break space.
+    Fr,
+}
+
+impl NumberLocale {
+    /// `(group separator, decimal separator)`, `None` for [`Self::Plain`].
+    fn separators(self) -> Option<(&'static str, &'static str)> {
+        match self {
+            NumberLocale::Plain => None,
+            NumberLocale::En => Some((",", ".")),
+            NumberLocale::De => Some((".", ",")),
+            NumberLocale::Fr => Some(("\u{202f}", ",")),
+        }
+    }
+
+    /// `amount`, a decimal as published, in this format.
+    fn format(self, amount: &str) -> String {
+        let Some((group, decimal)) = self.separators() else {
+            return amount.to_string();
+        };
+        let (sign, digits) = match amount.strip_prefix('-') {
+            Some(digits) => ("-", digits),
+            None => ("", amount),
+        };
+        let (int, frac) = match digits.split_once('.') {
+            Some((int, frac)) => (int, Some(frac)),
+            None => (digits, None),
+        };
+        let mut grouped = String::new();
+        for (i, c) in int.chars().enumerate() {
+            if i > 0 && (int.len() - i) % 3 == 0 {
+                grouped.push_str(group);
+            }
+            grouped.push(c);
+        }
+        match frac {
+            Some(frac) => format!("{}{}{}{}", sign, grouped, decimal, frac),
+            None => format!("{}{}", sign, grouped),
+        }
+    }
+}
+
+/// How dates and numbers are written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReportOptions {
+    /// Time zone transactions are dated in.
+    pub timezone: Tz,
+    pub locale: NumberLocale,
+}
+
+impl Default for ReportOptions {
+    fn default() -> Self {
+        ReportOptions {
+            timezone: Tz::UTC,
+            locale: NumberLocale::Plain,
+        }
+    }
+}
+
+/// Parse an IANA time zone name such as `Europe/Berlin`, for the command
+/// line.
+pub fn parse_timezone(name: &str) -> Result<Tz, String> {
+    name.parse::<Tz>()
+        .map_err(|e| format!("{}: not an IANA time zone name", e))
+}
+
+const CSV_HEADER: &str = concat!(
+    "date,timestamp,chain,network,event_type,event_id,tx_hash,",
+    "account,side,asset,token,amount,raw_amount"
+);
+
+/// One side of a transfer.
+struct Posting {
+    account: String,
+    /// `debit` for the receiver, `credit` for the sender.
+    side: &'static str,
+    /// Signed amount in whole units.
+    amount: String,
+    /// Signed amount in the asset's smallest unit.
+    raw_amount: String,
+}
+
+/// A transfer as a balanced pair of postings.
+struct Entry<'a> {
+    event: &'a Event,
+    date: String,
+    asset: &'a str,
+    postings: [Posting; 2],
+}
+
+impl<'a> Entry<'a> {
+    fn new(event: &'a Event, options: &ReportOptions) -> Option<Self> {
+        if event.spam || event.from.is_empty() || event.to.is_empty() {
+            return None;
+        }
+        let asset = event
+            .logical_asset
+            .as_deref()
+            .or(event.asset_symbol.as_deref())?;
+        let amount = event.value_formatted.as_deref()?;
+        let date = DateTime::parse_from_rfc3339(&event.timestamp).ok()?;
+        let posting = |address: &str, side: &'static str, sign: &str, raw: &str| Posting {
+            account: account(event, address),
+            side,
+            amount: format!("{}{}", sign, amount),
+            raw_amount: format!("{}{}", sign, raw),
+        };
+        Some(Entry {
+            event,
+            date: date
+                .with_timezone(&options.timezone)
+                .format("%Y-%m-%d")
+                .to_string(),
+            asset,
+            postings: [
+                posting(&event.to, "debit", "", &event.value),
+                posting(&event.from, "credit", "-", &event.value),
+            ],
+        })
+    }
+
+    fn write_beancount(&self, out: &mut impl Write) -> std::io::Result<()> {
+        let event = self.event;
+        writeln!(
+            out,
+            "{} * \"{}\" \"{}\"",
+            self.date,
+            quoted(&event.chain),
+            quoted(&event.event_type)
+        )?;
+        writeln!(out, "  event_id: \"{}\"", quoted(&event.event_id))?;
+        writeln!(out, "  tx_hash: \"{}\"", quoted(&event.tx_hash))?;
+        let commodity = commodity(self.asset);
+        for posting in &self.postings {
+            writeln!(
+                out,
+                "  {}  {} {}",
+                posting.account, posting.amount, commodity
+            )?;
+        }
+        writeln!(out)
+    }
+
+    fn write_csv(&self, locale: NumberLocale, out: &mut impl Write) -> std::io::Result<()> {
+        let event = self.event;
+        let token = event
+            .token
+            .as_ref()
+            .map_or("", |token| token.address.as_str());
+        for posting in &self.postings {
+            let amount = locale.format(&posting.amount);
+            let fields: [&str; 13] = [
+                self.date.as_str(),
+                &event.timestamp,
+                &event.chain,
+                &event.network,
+                &event.event_type,
+                &event.event_id,
+                &event.tx_hash,
+                &posting.account,
+                posting.side,
+                self.asset,
+                token,
+                &amount,
+                &posting.raw_amount,
+            ];
+            let row: Vec<String> = fields.iter().map(|field| csv_field(field)).collect();
+            writeln!(out, "{}", row.join(","))?;
+        }
+        Ok(())
+    }
+}
+
+/// Convert the events in `input` and write them to `out`. Returns the number
+/// of transactions written.
+pub fn export(
+    input: impl BufRead,
+    format: LedgerFormat,
+    options: &ReportOptions,
+    out: &mut impl Write,
+) -> anyhow::Result<usize> {
+    match format {
+        LedgerFormat::Beancount => writeln!(out, "plugin \"beancount.plugins.auto_accounts\"\n")?,
+        LedgerFormat::Csv => writeln!(out, "{}", CSV_HEADER)?,
+    }
+    let mut written = 0;
+    let mut skipped = 0;
+    for (i, line) in input.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let event: Event = serde_json::from_str(&line)
+            .with_context(|| format!("line {} is not an event", i + 1))?;
+        let Some(entry) = Entry::new(&event, options) else {
+            skipped += 1;
+            continue;
+        };
+        match format {
+            LedgerFormat::Beancount => entry.write_beancount(out)?,
+            LedgerFormat::Csv => entry.write_csv(options.locale, out)?,
+        }
+        written += 1;
+    }
+    if skipped > 0 {
+        warn!(
+            "Skipped {} events without an amount, timestamp or counterparty, or tagged as spam",
+            skipped
+        );
+    }
+    Ok(written)
+}
+
+/// `Assets:<Chain>:<Network>:<address>`. Account name components are
+/// letters, digits and dashes starting with a capital letter or digit, so
+/// the chain and network are capitalized and an address that starts with a
+/// lowercase letter, as Solana ones may, is prefixed with `X`.
+fn account(event: &Event, address: &str) -> String {
+    let address = component(address);
+    let address = if address.starts_with(|c: char| c.is_ascii_uppercase() || c.is_ascii_digit()) {
+        address
+    } else {
+        format!("X{}", address)
+    };
+    format!(
+        "Assets:{}:{}:{}",
+        capitalized(&component(&event.chain)),
+        capitalized(&component(&event.network)),
+        address
+    )
+}
+
+fn component(name: &str) -> String {
+    name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+        .collect()
+}
+
+fn capitalized(name: &str) -> String {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(first) => first.to_ascii_uppercase().to_string() + chars.as_str(),
+        None => "Unknown".to_string(),
+    }
+}
+
+/// A Beancount commodity for a token symbol: 2 to 24 capital letters,
+/// digits and `'._-`, starting with a letter and ending with a letter or
+/// digit.
+fn commodity(symbol: &str) -> String {
+    let mut name: String = symbol
+        .to_ascii_uppercase()
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || "'._-".contains(*c))
+        .collect();
+    if !name.starts_with(|c: char| c.is_ascii_uppercase()) {
+        name.insert(0, 'X');
+    }
+    name.truncate(24);
+    while name.ends_with(|c: char| !c.is_ascii_alphanumeric()) {
+        name.pop();
+    }
+    match name.len() {
+        0 | 1 if name != "X" => format!("X{}", name),
+        0 | 1 => "UNKNOWN".to_string(),
+        _ => name,
+    }
+}
+
+fn quoted(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn usdc_transfer() -> String {
+        serde_json::json!({
+            "event_id": "eth:0xab:log3",
+            "chain": "ethereum",
+            "network": "mainnet",
+            "tx_hash": "0xab",
+            "timestamp": "2024-05-01T12:00:00+00:00",
+            "from": "0xf39fd6e51aad88f6f4ce6ab8827279cfffb92266",
+            "to": "0x70997970c51812dc3a010c7d01b50e0d17dc79c8",
+            "value": "1500000",
+            "asset_symbol": "USDC",
+            "asset_decimals": 6,
+            "value_formatted": "1.5",
+            "event_type": "erc20_transfer",
+            "token": {"address": "0xa0b8", "symbol": "USDC", "decimals": 6}
+        })
+        .to_string()
+    }
+
+    fn export_str(input: &str, format: LedgerFormat) -> (usize, String) {
+        export_with(input, format, &ReportOptions::default())
+    }
+
+    fn export_with(input: &str, format: LedgerFormat, options: &ReportOptions) -> (usize, String) {
+        let mut out = Vec::new();
+        let written = export(input.as_bytes(), format, options, &mut out).unwrap();
+        (written, String::from_utf8(out).unwrap())
+    }
+
+    #[test]
+    fn test_beancount_transactions_balance() {
+        let mut spam: serde_json::Value = serde_json::from_str(&usdc_transfer()).unwrap();
+        spam["spam"] = true.into();
+        let no_value = r#"{"event_id":"sol:1","chain":"solana","event_type":"solana_tx"}"#;
+        let input = format!("{}\n\n{}\n{}\n", usdc_transfer(), spam, no_value);
+        let (written, out) = export_str(&input, LedgerFormat::Beancount);
+        assert_eq!(written, 1);
+        assert!(out.starts_with("plugin \"beancount.plugins.auto_accounts\"\n\n"));
+        assert!(out.contains("2024-05-01 * \"ethereum\" \"erc20_transfer\"\n"));
+        assert!(out.contains("  event_id: \"eth:0xab:log3\"\n"));
+        assert!(out.contains(
+            "  Assets:Ethereum:Mainnet:0x70997970c51812dc3a010c7d01b50e0d17dc79c8  1.5 USDC\n"
+        ));
+        assert!(out.contains(
+            "  Assets:Ethereum:Mainnet:0xf39fd6e51aad88f6f4ce6ab8827279cfffb92266  -1.5 USDC\n"
+        ));
+    }
+
+    #[test]
+    fn test_csv_rows_per_posting() {
+        let (written, out) = export_str(&usdc_transfer(), LedgerFormat::Csv);
+        assert_eq!(written, 1);
+        let rows: Vec<&str> = out.lines().collect();
+        assert_eq!(rows[0], CSV_HEADER);
+        assert_eq!(rows.len(), 3);
+        assert!(rows[1].ends_with(",debit,USDC,0xa0b8,1.5,1500000"));
+        assert!(rows[2].ends_with(",credit,USDC,0xa0b8,-1.5,-1500000"));
+        assert_eq!(csv_field("a,\"b\""), "\"a,\"\"b\"\"\"");
+    }
+
+    #[test]
+    fn test_dates_in_report_timezone() {
+        let mut late: serde_json::Value = serde_json::from_str(&usdc_transfer()).unwrap();
+        late["timestamp"] = "2024-05-01T23:30:00+00:00".into();
+        let tokyo = ReportOptions {
+            timezone: parse_timezone("Asia/Tokyo").unwrap(),
+            ..Default::default()
+        };
+        let (_, out) = export_with(&late.to_string(), LedgerFormat::Beancount, &tokyo);
+        assert!(out.contains("2024-05-02 * "), "{}", out);
+        let (_, out) = export_str(&late.to_string(), LedgerFormat::Beancount);
+        assert!(out.contains("2024-05-01 * "), "{}", out);
+        assert!(parse_timezone("Mars/Olympus").is_err());
+    }
+
+    #[test]
+    fn test_csv_amounts_in_locale() {
+        assert_eq!(NumberLocale::Plain.format("-1234567.25"), "-1234567.25");
+        assert_eq!(NumberLocale::En.format("-1234567.25"), "-1,234,567.25");
+        assert_eq!(NumberLocale::De.format("1234567.25"), "1.234.567,25");
+        assert_eq!(NumberLocale::Fr.format("1234"), "1\u{202f}234");
+        assert_eq!(NumberLocale::De.format("123"), "123");
+
+        let german = ReportOptions {
+            locale: NumberLocale::De,
+            ..Default::default()
+        };
+        let (_, out) = export_with(&usdc_transfer(), LedgerFormat::Csv, &german);
+        let rows: Vec<&str> = out.lines().collect();
+        assert!(
+            rows[1].ends_with(",debit,USDC,0xa0b8,\"1,5\",1500000"),
+            "{}",
+            out
+        );
+    }
+
+    #[test]
+    fn test_logical_asset_is_the_commodity() {
+        let mut bridged: serde_json::Value = serde_json::from_str(&usdc_transfer()).unwrap();
+        bridged["asset_symbol"] = "USDC.e".into();
+        bridged["logical_asset"] = "USDC".into();
+        let (_, out) = export_str(&bridged.to_string(), LedgerFormat::Beancount);
+        assert!(out.contains("  -1.5 USDC\n"), "{}", out);
+    }
+
+    #[test]
+    fn test_names_are_valid_beancount() {
+        assert_eq!(commodity("USDC.e"), "USDC.E");
+        assert_eq!(commodity("$pepe!"), "PEPE");
+        assert_eq!(commodity("1INCH"), "X1INCH");
+        assert_eq!(commodity("T"), "XT");
+        assert_eq!(commodity("?"), "UNKNOWN");
+        let event = Event {
+            chain: "solana".into(),
+            network: "devnet".into(),
+            ..Default::default()
+        };
+        assert_eq!(
+            account(&event, "9xQeWvG816bU"),
+            "Assets:Solana:Devnet:9xQeWvG816bU"
+        );
+        assert_eq!(account(&event, "abc"), "Assets:Solana:Devnet:Xabc");
+    }
+
+    #[test]
+    fn test_invalid_line_is_an_error() {
+        let mut out = Vec::new();
+        let err = export(
+            "not json\n".as_bytes(),
+            LedgerFormat::Csv,
+            &ReportOptions::default(),
+            &mut out,
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("line 1"));
+    }
+}
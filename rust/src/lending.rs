@@ -0,0 +1,559 @@
+//! Lending positions of watched addresses (`lending_*` events).
+//!
+//! Deposits into and loans from a money market show up, if at all, as token
+//! transfers to and from a contract, and liquidations not even that. The
+//! Ethereum trackers also decode the logs of the markets below, and publish
+//! the ones whose account (the supplier, borrower or liquidated account) or
+//! counterparty (the sender, repayer or liquidator) is watched:
+//!
+//! | Event type | Aave v3 | Compound v2 |
+//! |---|---|---|
+//! | `lending_supply` | `Supply` | `Mint` |
+//! | `lending_borrow` | `Borrow` | `Borrow` |
+//! | `lending_repay` | `Repay` | `RepayBorrow` |
+//! | `lending_liquidation` | `LiquidationCall` | `LiquidateBorrow` |
+//!
+//! `value` and `token` are the amount supplied, borrowed or repaid, or the
+//! debt a liquidator covered; `token` is absent for ETH (Compound's cETH).
+//! `lending` holds the rest, including what tells how close the account is
+//! to liquidation: for Aave the pool's `getUserAccountData` as of the log's
+//! block, for Compound the account's debt in the market after the action.
+//!
+//! Only the Ethereum mainnet deployments are known.
+use ethers::providers::Middleware;
+use ethers::types::transaction::eip2718::TypedTransaction;
+use ethers::types::{Address, BlockId, Log, NameOrAddress, TransactionRequest, H256, U256};
+use ethers::utils::{id, keccak256};
+use serde::Serialize;
+
+use crate::event_type::EventType;
+
+const AAVE_SUPPLY: &str = "Supply(address,address,address,uint256,uint16)";
+const AAVE_BORROW: &str = "Borrow(address,address,address,uint256,uint8,uint256,uint16)";
+const AAVE_REPAY: &str = "Repay(address,address,address,uint256,bool)";
+const AAVE_LIQUIDATION: &str =
+    "LiquidationCall(address,address,address,uint256,uint256,address,bool)";
+const COMPOUND_MINT: &str = "Mint(address,uint256,uint256)";
+const COMPOUND_BORROW: &str = "Borrow(address,uint256,uint256,uint256)";
+const COMPOUND_REPAY: &str = "RepayBorrow(address,address,uint256,uint256,uint256)";
+const COMPOUND_LIQUIDATION: &str = "LiquidateBorrow(address,address,uint256,address,uint256)";
+
+/// Signatures of the decoded events, for log filters.
+pub const EVENTS: &[&str] = &[
+    AAVE_SUPPLY,
+    AAVE_BORROW,
+    AAVE_REPAY,
+    AAVE_LIQUIDATION,
+    COMPOUND_MINT,
+    COMPOUND_BORROW,
+    COMPOUND_REPAY,
+    COMPOUND_LIQUIDATION,
+];
+
+/// The network the markets below are deployed on.
+const NETWORK: &str = "mainnet";
+
+const AAVE_V3_POOL: &str = "0x87870bca3f3fd6335c3f4ce8392d69350b4fa4e2";
+
+/// Compound v2 cTokens and their underlying tokens; `None` is ETH.
+const COMPOUND_V2_MARKETS: &[(&str, Option<&str>)] = &[
+    ("0x4ddc2d193948926d02f9b1fe9e1daa0718270ed5", None),
+    (
+        "0x39aa39c021dfbae8fac545936693ac917d5e7563",
+        Some("0xa0b86991c6218b36c1d19d4a2e9eb0ce3606eb48"),
+    ),
+    (
+        "0x5d3a536e4d6dbd6114cc1ead35777bab948e3643",
+        Some("0x6b175474e89094c44da98b954eedeac495271d0f"),
+    ),
+    (
+        "0xf650c3d88d12db855b8bf7d11be6c55a4e07dcc9",
+        Some("0xdac17f958d2ee523a2206206994597c13d831ec7"),
+    ),
+    (
+        "0xccf4429db6322d5c611ee964527d42e5d685dd6a",
+        Some("0x2260fac5e5542a773aa44fbcfedf7c193bc2c599"),
+    ),
+];
+
+/// Whether `network` has known markets, so its log filters should include
+/// `EVENTS`.
+pub fn supports(network: &str) -> bool {
+    network == NETWORK
+}
+
+/// Payload of `lending_*` events.
+#[derive(Serialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct Lending {
+    /// `aave_v3` or `compound_v2`.
+    pub protocol: String,
+    /// The Aave pool or Compound cToken that emitted the log.
+    pub market: String,
+    /// Whose position changed: the supplier, borrower or liquidated account.
+    pub account: String,
+    /// Liquidations: the collateral seized, an Aave reserve or a Compound
+    /// cToken, and its amount in base units.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub collateral_asset: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub collateral_value: Option<String>,
+    /// Aave borrows: the rate of the loan, in ray (1e27 = 100% a year).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub borrow_rate: Option<String>,
+    /// Compound: the account's debt in the market after the action, in base
+    /// units of the borrowed token.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub account_debt: Option<String>,
+    /// Aave: the account's collateral and debt across the pool, in the
+    /// pool's base currency (USD, 8 decimals), as of the log's block.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total_collateral_base: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total_debt_base: Option<String>,
+    /// Aave: the account's health factor (18 decimals; liquidatable below
+    /// 1e18) as of the log's block; absent without debt.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub health_factor: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Protocol {
+    AaveV3,
+    CompoundV2,
+}
+
+/// A decoded lending log of a known market.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LendingLog {
+    pub event_type: EventType,
+    protocol: Protocol,
+    pub market: Address,
+    /// Whose position changed.
+    pub account: Address,
+    /// Who supplied or repaid, received the loan, or liquidated.
+    pub counterparty: Address,
+    /// The token supplied, borrowed or repaid; `None` is ETH.
+    pub asset: Option<Address>,
+    pub amount: U256,
+    collateral: Option<(Address, U256)>,
+    borrow_rate: Option<U256>,
+    account_debt: Option<U256>,
+}
+
+/// An Aave account's standing across the pool (`getUserAccountData`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AccountHealth {
+    /// In the pool's base currency (USD, 8 decimals).
+    pub total_collateral_base: U256,
+    pub total_debt_base: U256,
+    /// With 18 decimals; `U256::MAX` without debt.
+    pub health_factor: U256,
+}
+
+impl AccountHealth {
+    /// The `lending` payload of an Aave account's standing.
+    pub fn payload(self, account: Address) -> Lending {
+        self.fill(Lending {
+            protocol: "aave_v3".into(),
+            market: AAVE_V3_POOL.into(),
+            account: format!("{:?}", account),
+            ..Default::default()
+        })
+    }
+
+    fn fill(self, lending: Lending) -> Lending {
+        Lending {
+            total_collateral_base: Some(self.total_collateral_base.to_string()),
+            total_debt_base: Some(self.total_debt_base.to_string()),
+            health_factor: (!self.total_debt_base.is_zero())
+                .then(|| self.health_factor.to_string()),
+            ..lending
+        }
+    }
+}
+
+/// `account`'s standing in the Aave v3 pool as of `block`, or `None` if the
+/// call failed.
+pub async fn aave_health<M: Middleware>(
+    provider: &M,
+    account: Address,
+    block: u64,
+) -> Option<AccountHealth> {
+    let mut data = id("getUserAccountData(address)").to_vec();
+    data.extend_from_slice(H256::from(account).as_bytes());
+    let tx = TypedTransaction::Legacy(TransactionRequest {
+        to: Some(NameOrAddress::Address(AAVE_V3_POOL.parse().ok()?)),
+        data: Some(data.into()),
+        ..Default::default()
+    });
+    let bytes = provider
+        .call(&tx, Some(BlockId::Number(block.into())))
+        .await
+        .ok()?;
+    account_health(&bytes)
+}
+
+impl LendingLog {
+    /// Decode `log` if it is one of `EVENTS`, emitted by a known market on
+    /// `network`.
+    pub fn decode(log: &Log, network: &str) -> Option<Self> {
+        if !supports(network) {
+            return None;
+        }
+        let market = format!("{:?}", log.address);
+        if market == AAVE_V3_POOL {
+            return aave(log);
+        }
+        let (_, underlying) = COMPOUND_V2_MARKETS.iter().find(|(m, _)| *m == market)?;
+        let asset = match underlying {
+            Some(address) => Some(address.parse().ok()?),
+            None => None,
+        };
+        compound(log, asset)
+    }
+
+    /// The watched address of the log, if any: its account, else its
+    /// counterparty.
+    pub fn watched(&self, watched: &[Address]) -> Option<Address> {
+        [self.account, self.counterparty]
+            .into_iter()
+            .find(|address| watched.contains(address))
+    }
+
+    /// The event's `from` and `to`: funds go from the counterparty to the
+    /// market, except for a loan.
+    pub fn parties(&self) -> (Address, Address) {
+        match self.event_type {
+            EventType::LendingBorrow => (self.market, self.counterparty),
+            _ => (self.counterparty, self.market),
+        }
+    }
+
+    /// The account's standing as of `block`, for Aave logs; `None` for
+    /// Compound or if the call failed.
+    pub async fn health<M: Middleware>(&self, provider: &M, block: u64) -> Option<AccountHealth> {
+        if self.protocol != Protocol::AaveV3 {
+            return None;
+        }
+        aave_health(provider, self.account, block).await
+    }
+
+    /// The event's `lending` payload.
+    pub fn payload(&self, health: Option<AccountHealth>) -> Lending {
+        let lending = Lending {
+            protocol: match self.protocol {
+                Protocol::AaveV3 => "aave_v3",
+                Protocol::CompoundV2 => "compound_v2",
+            }
+            .into(),
+            market: format!("{:?}", self.market),
+            account: format!("{:?}", self.account),
+            collateral_asset: self.collateral.map(|(asset, _)| format!("{:?}", asset)),
+            collateral_value: self.collateral.map(|(_, value)| value.to_string()),
+            borrow_rate: self.borrow_rate.map(|rate| rate.to_string()),
+            account_debt: self.account_debt.map(|debt| debt.to_string()),
+            ..Default::default()
+        };
+        match health {
+            Some(health) => health.fill(lending),
+            None => lending,
+        }
+    }
+}
+
+fn aave(log: &Log) -> Option<LendingLog> {
+    if log.topics.len() != 4 {
+        return None;
+    }
+    let topic = log.topics[0];
+    let data = &log.data;
+    let position = |event_type, account, counterparty, asset, amount| LendingLog {
+        event_type,
+        protocol: Protocol::AaveV3,
+        market: log.address,
+        account,
+        counterparty,
+        asset: Some(asset),
+        amount,
+        collateral: None,
+        borrow_rate: None,
+        account_debt: None,
+    };
+    let [reserve, second, third] = [1, 2, 3].map(|i| Address::from(log.topics[i]));
+    if topic == signature(AAVE_SUPPLY) {
+        // reserve, onBehalfOf, referralCode indexed; user, amount.
+        Some(position(
+            EventType::LendingSupply,
+            second,
+            address_word(data, 0)?,
+            reserve,
+            word(data, 1)?,
+        ))
+    } else if topic == signature(AAVE_BORROW) {
+        // reserve, onBehalfOf, referralCode indexed; user, amount,
+        // interestRateMode, borrowRate.
+        Some(LendingLog {
+            borrow_rate: Some(word(data, 3)?),
+            ..position(
+                EventType::LendingBorrow,
+                second,
+                address_word(data, 0)?,
+                reserve,
+                word(data, 1)?,
+            )
+        })
+    } else if topic == signature(AAVE_REPAY) {
+        // reserve, user, repayer indexed; amount, useATokens.
+        Some(position(
+            EventType::LendingRepay,
+            second,
+            third,
+            reserve,
+            word(data, 0)?,
+        ))
+    } else if topic == signature(AAVE_LIQUIDATION) {
+        // collateralAsset, debtAsset, user indexed; debtToCover,
+        // liquidatedCollateralAmount, liquidator, receiveAToken.
+        Some(LendingLog {
+            collateral: Some((reserve, word(data, 1)?)),
+            ..position(
+                EventType::LendingLiquidation,
+                third,
+                address_word(data, 2)?,
+                second,
+                word(data, 0)?,
+            )
+        })
+    } else {
+        None
+    }
+}
+
+fn compound(log: &Log, asset: Option<Address>) -> Option<LendingLog> {
+    if log.topics.len() != 1 {
+        return None;
+    }
+    let topic = log.topics[0];
+    let data = &log.data;
+    let position = |event_type, account, counterparty, amount| LendingLog {
+        event_type,
+        protocol: Protocol::CompoundV2,
+        market: log.address,
+        account,
+        counterparty,
+        asset,
+        amount,
+        collateral: None,
+        borrow_rate: None,
+        account_debt: None,
+    };
+    if topic == signature(COMPOUND_MINT) {
+        // minter, mintAmount, mintTokens.
+        let minter = address_word(data, 0)?;
+        Some(position(
+            EventType::LendingSupply,
+            minter,
+            minter,
+            word(data, 1)?,
+        ))
+    } else if topic == signature(COMPOUND_BORROW) {
+        // borrower, borrowAmount, accountBorrows, totalBorrows.
+        let borrower = address_word(data, 0)?;
+        Some(LendingLog {
+            account_debt: Some(word(data, 2)?),
+            ..position(EventType::LendingBorrow, borrower, borrower, word(data, 1)?)
+        })
+    } else if topic == signature(COMPOUND_REPAY) {
+        // payer, borrower, repayAmount, accountBorrows, totalBorrows.
+        Some(LendingLog {
+            account_debt: Some(word(data, 3)?),
+            ..position(
+                EventType::LendingRepay,
+                address_word(data, 1)?,
+                address_word(data, 0)?,
+                word(data, 2)?,
+            )
+        })
+    } else if topic == signature(COMPOUND_LIQUIDATION) {
+        // liquidator, borrower, repayAmount, cTokenCollateral, seizeTokens.
+        Some(LendingLog {
+            collateral: Some((address_word(data, 3)?, word(data, 4)?)),
+            ..position(
+                EventType::LendingLiquidation,
+                address_word(data, 1)?,
+                address_word(data, 0)?,
+                word(data, 2)?,
+            )
+        })
+    } else {
+        None
+    }
+}
+
+fn signature(event: &str) -> H256 {
+    keccak256(event).into()
+}
+
+/// The `index`th 32-byte word of ABI-encoded `data`.
+fn word(data: &[u8], index: usize) -> Option<U256> {
+    data.get(index * 32..(index + 1) * 32)
+        .map(U256::from_big_endian)
+}
+
+/// The address in the `index`th word of `data`.
+fn address_word(data: &[u8], index: usize) -> Option<Address> {
+    data.get(index * 32 + 12..(index + 1) * 32)
+        .map(Address::from_slice)
+}
+
+/// Decode `getUserAccountData`'s totalCollateralBase, totalDebtBase,
+/// availableBorrowsBase, currentLiquidationThreshold, ltv and healthFactor.
+fn account_health(data: &[u8]) -> Option<AccountHealth> {
+    Some(AccountHealth {
+        total_collateral_base: word(data, 0)?,
+        total_debt_base: word(data, 1)?,
+        health_factor: word(data, 5)?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(words: &[U256]) -> Vec<u8> {
+        let mut data = vec![0u8; words.len() * 32];
+        for (i, word) in words.iter().enumerate() {
+            word.to_big_endian(&mut data[i * 32..(i + 1) * 32]);
+        }
+        data
+    }
+
+    fn address(byte: u8) -> U256 {
+        U256::from_big_endian(Address::repeat_byte(byte).as_bytes())
+    }
+
+    fn log(market: &str, event: &str, indexed: &[Address], data: Vec<u8>) -> Log {
+        let mut topics = vec![signature(event)];
+        topics.extend(indexed.iter().map(|address| H256::from(*address)));
+        Log {
+            address: market.parse().unwrap(),
+            topics,
+            data: data.into(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_aave_supply_and_liquidation() {
+        let [reserve, account, sender] = [1, 2, 3].map(Address::repeat_byte);
+        let supply = log(
+            AAVE_V3_POOL,
+            AAVE_SUPPLY,
+            &[reserve, account, Address::zero()],
+            words(&[address(3), U256::from(5_000_000)]),
+        );
+        let position = LendingLog::decode(&supply, "mainnet").unwrap();
+        assert_eq!(position.event_type, EventType::LendingSupply);
+        assert_eq!(position.account, account);
+        assert_eq!(position.asset, Some(reserve));
+        assert_eq!(position.amount, U256::from(5_000_000));
+        assert_eq!(position.parties(), (sender, position.market));
+        assert_eq!(position.watched(&[sender]), Some(sender));
+        assert_eq!(position.watched(&[Address::repeat_byte(9)]), None);
+        assert_eq!(LendingLog::decode(&supply, "sepolia"), None);
+
+        let debt = Address::repeat_byte(4);
+        let liquidation = log(
+            AAVE_V3_POOL,
+            AAVE_LIQUIDATION,
+            &[reserve, debt, account],
+            words(&[U256::from(700), U256::from(2), address(5), U256::zero()]),
+        );
+        let position = LendingLog::decode(&liquidation, "mainnet").unwrap();
+        assert_eq!(position.event_type, EventType::LendingLiquidation);
+        assert_eq!(position.asset, Some(debt));
+        assert_eq!(position.counterparty, Address::repeat_byte(5));
+        let lending = position.payload(None);
+        assert_eq!(lending.protocol, "aave_v3");
+        assert_eq!(
+            lending.collateral_asset,
+            Some(format!("{:?}", Address::repeat_byte(1)))
+        );
+        assert_eq!(lending.collateral_value.as_deref(), Some("2"));
+    }
+
+    #[test]
+    fn test_compound_repay() {
+        let cusdc = "0x39aa39c021dfbae8fac545936693ac917d5e7563";
+        let repay = log(
+            cusdc,
+            COMPOUND_REPAY,
+            &[],
+            words(&[
+                address(1),
+                address(2),
+                U256::from(250),
+                U256::from(750),
+                U256::from(1_000_000),
+            ]),
+        );
+        let position = LendingLog::decode(&repay, "mainnet").unwrap();
+        assert_eq!(position.event_type, EventType::LendingRepay);
+        assert_eq!(position.account, Address::repeat_byte(2));
+        assert_eq!(position.counterparty, Address::repeat_byte(1));
+        assert_eq!(
+            position.asset,
+            "0xa0b86991c6218b36c1d19d4a2e9eb0ce3606eb48".parse().ok()
+        );
+        let lending = position.payload(None);
+        assert_eq!(lending.protocol, "compound_v2");
+        assert_eq!(lending.account_debt.as_deref(), Some("750"));
+
+        // Other contracts emitting the same events are ignored.
+        let other = log(
+            "0x0000000000000000000000000000000000000001",
+            COMPOUND_REPAY,
+            &[],
+            repay.data.to_vec(),
+        );
+        assert_eq!(LendingLog::decode(&other, "mainnet"), None);
+    }
+
+    #[test]
+    fn test_health_factor_only_with_debt() {
+        let pool = log(
+            AAVE_V3_POOL,
+            AAVE_REPAY,
+            &[Address::zero(); 3],
+            words(&[U256::one()]),
+        );
+        let position = LendingLog::decode(&pool, "mainnet").unwrap();
+        let data = words(&[
+            U256::from(1_000_000_000_000u64),
+            U256::from(400_000_000_000u64),
+            U256::zero(),
+            U256::from(8_250),
+            U256::from(8_000),
+            U256::from(2_062_500_000_000_000_000u64),
+        ]);
+        let lending = position.payload(account_health(&data));
+        assert_eq!(lending.total_debt_base.as_deref(), Some("400000000000"));
+        assert_eq!(
+            lending.health_factor.as_deref(),
+            Some("2062500000000000000")
+        );
+
+        let no_debt = words(&[
+            U256::one(),
+            U256::zero(),
+            U256::zero(),
+            U256::zero(),
+            U256::zero(),
+            U256::MAX,
+        ]);
+        let lending = position.payload(account_health(&no_debt));
+        assert_eq!(lending.total_debt_base.as_deref(), Some("0"));
+        assert_eq!(lending.health_factor, None);
+        assert_eq!(account_health(&data[..64]), None);
+    }
+}
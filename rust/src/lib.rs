@@ -0,0 +1,3048 @@
+//! Cross-chain listener service
+//!
+//! The listener monitors Ethereum and Solana for transactions touching a set
+//! of watched addresses. Events are normalized to a chain-agnostic JSON schema
+//! and published to a Redis Pub/Sub channel (`cross_chain_events`) for the Go
+//! API to consume. The listener supports both websocket subscriptions
+//! (preferred in production) and HTTP polling (useful for local testing with
+//! Anvil/Devnet).
+//!
+//! The `tracker_rs` binary is a thin wrapper around `run`. Services that embed
+//! the listener instead build a `publisher::Publisher` and the trackers they
+//! need from `tracker`, and start them with `tracker::spawn_all`.
+use anyhow::{anyhow, Context};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use tokio::task::JoinSet;
+use tokio::time::{timeout_at, Duration, Instant};
+use tokio_stream::StreamExt;
+use tokio_util::sync::CancellationToken;
+
+use ethers::prelude::*;
+use ethers::providers::{Http, JsonRpcClient, Middleware, Provider, PubsubClient};
+use solana_client::rpc_client::{GetConfirmedSignaturesForAddress2Config, RpcClient};
+use solana_client::rpc_config::RpcTransactionConfig;
+use solana_client::rpc_response::RpcConfirmedTransactionStatusWithSignature;
+
+use solana_sdk::{pubkey::Pubkey, signature::Signature};
+use solana_transaction_status::option_serializer::OptionSerializer;
+use solana_transaction_status::{
+    EncodedConfirmedTransactionWithStatusMeta, UiTransactionEncoding, UiTransactionTokenBalance,
+};
+
+use tracing::{debug, error, info, warn};
+use tracing_subscriber::{fmt, EnvFilter};
+mod address_book;
+mod admin;
+mod amount;
+mod asset_map;
+mod assets;
+mod base58;
+mod block_times;
+mod bootstrap;
+mod bus;
+mod capture;
+mod chains;
+mod checkpoint;
+pub mod cli;
+pub mod config;
+mod confirmations;
+pub mod control;
+mod dashboard;
+mod dedup;
+mod dedup_store;
+mod distributed_dedup;
+mod doctor;
+mod erc20;
+mod eth_logs;
+pub mod event_type;
+mod evm_networks;
+mod expectations;
+mod explorer;
+mod fee_spike;
+mod finality;
+mod gaps;
+#[cfg(feature = "geyser")]
+mod geyser;
+mod graph;
+#[cfg(feature = "grpc")]
+mod grpc;
+mod headers;
+mod health;
+mod heartbeat;
+mod helius;
+mod history;
+mod inspect;
+#[cfg(feature = "kafka")]
+mod kafka;
+mod kill_switch;
+mod ledger;
+mod lending;
+mod liquid_staking;
+mod liquidation_risk;
+mod memory;
+#[cfg(feature = "nats")]
+mod nats;
+mod pending;
+mod portfolio;
+#[cfg(feature = "postgres")]
+mod postgres;
+mod profile;
+pub mod publisher;
+mod quarantine;
+mod reconcile;
+mod retention;
+mod retry;
+mod rpc_batch;
+mod rpc_guard;
+mod rpc_schema;
+mod runtimes;
+mod scheduler;
+mod schema;
+mod selectors;
+mod sequence;
+mod shutdown;
+mod sink;
+mod solana_parser;
+mod spam;
+mod streams;
+mod stuck;
+mod substrate;
+mod sweeps;
+mod token_filter;
+mod token_metadata;
+mod trace_context;
+pub mod tracker;
+mod tron;
+mod tui;
+mod validators;
+pub mod watchlist;
+mod webhook_sink;
+mod webhooks;
+
+use block_times::BlockTimes;
+use checkpoint::CheckpointStore;
+use confirmations::{PendingLogs, SolCommitment};
+use control::{ChainControl, Controls};
+use dedup::DedupSet;
+use erc20::TransferLog;
+use event_type::EventType;
+use fee_spike::GasPaid;
+use gaps::BlockLedger;
+use headers::{BlockHeader, HeaderCache, Linkage};
+use lending::LendingLog;
+use publisher::Publisher;
+use rpc_batch::{BatchedBlock, RpcBatch};
+use scheduler::{Schedule, Scheduler};
+use spam::{SpamFilter, TransferSignals};
+use token_filter::TokenFilter;
+use token_metadata::{SplMetadata, TokenMetadata, UNKNOWN_SYMBOL};
+use trace_context::TraceContext;
+use tracker::{ChainTracker, EthTracker, SolTracker};
+
+// Include the golden test modules
+#[cfg(test)]
+mod fixture_matrix;
+mod tests;
+
+#[allow(dead_code)]
+#[derive(Deserialize)]
+struct SystemTransfer {
+    source: String,
+    destination: String,
+    lamports: u64,
+}
+
+#[allow(dead_code)]
+#[derive(Deserialize)]
+struct TokenTransfer {
+    source: String,
+    destination: String,
+    amount: String,
+    decimals: Option<u8>,
+}
+
+/// Token of an ERC-20 or SPL transfer.
+#[derive(Serialize, Debug, Default, Clone)]
+pub struct Token {
+    pub address: String,
+    pub symbol: String,
+    pub decimals: u8,
+}
+
+/// A normalized event, as published (see `schema.rs` for the payload).
+#[derive(Serialize, Debug, Default, Clone)]
+pub struct Event {
+    pub event_id: String,
+    pub chain: String,
+    pub network: String,
+    /// Position of the event among its chain's published events, assigned
+    /// by the publisher; see [`sequence`].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sequence: Option<u64>,
+    /// `REGION` of the listener that published the event, when set.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub region: Option<String>,
+    /// `INSTANCE_ID` of the listener that published the event.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub instance_id: Option<String>,
+    pub tx_hash: String,
+    pub timestamp: String,
+    pub from: String,
+    pub to: String,
+    pub value: String,
+    /// Symbol of the asset `value` is denominated in, filled in by the
+    /// publisher with `asset_decimals` and `value_formatted`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub asset_symbol: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub asset_decimals: Option<u8>,
+    /// `value` scaled by `asset_decimals`, e.g. `1.5`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value_formatted: Option<String>,
+    /// Cross-chain asset the token or native currency is mapped to in the
+    /// `[[assets]]` config, e.g. `USDC` for USDC.e on Avalanche.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub logical_asset: Option<String>,
+    pub event_type: EventType,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub slot: Option<u64>,
+    /// Block of an EVM transfer, ERC-20 or lending event, or of a Tron or
+    /// Substrate event.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub block_number: Option<u64>,
+    /// Index of the log within its block (ERC-20 and lending events), or of
+    /// the event among its block's events on Substrate chains.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub log_index: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token: Option<Token>,
+    /// SPL Memo text of a Solana transaction, if it has one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memo: Option<String>,
+    /// 4-byte function selector of an Ethereum transaction's calldata.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub selector: Option<String>,
+    /// Signature for `selector`, when it is a well-known method.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub method: Option<String>,
+    /// Set by the spam heuristics (`SPAM_FILTER=tag`); omitted when false.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub spam: bool,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub spam_reasons: Vec<String>,
+    /// What was non-standard about an ERC-20 `Transfer` log (see
+    /// [`erc20`]); omitted when nothing was.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub decode_warnings: Vec<erc20::DecodeWarning>,
+    /// How the token's balances move, when it is a fee-on-transfer or
+    /// rebasing token (see [`erc20`]).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token_behavior: Option<erc20::TokenBehavior>,
+    /// Raw amount the recipient of a fee-on-transfer token received, when
+    /// its balance change shows it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub received_value: Option<String>,
+    /// Blocks reprocessed by the gap auditor (`gap_repaired` events only).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gap: Option<gaps::GapRange>,
+    /// Monitoring status (`address_heartbeat` events only).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub heartbeat: Option<heartbeat::Heartbeat>,
+    /// The expectation resolved (`expected_transfer_*` events only).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expectation: Option<expectations::Expectation>,
+    /// Portfolio valuation (`portfolio_snapshot` events only).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub portfolio: Option<portfolio::Snapshot>,
+    /// The rule that fired (`unexpected_activity`, `liquidation_risk`,
+    /// `stuck_transaction` and `fee_spike` events only).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub alert: Option<address_book::Alert>,
+    /// The transfers aggregated (`sweep_detected` events only).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sweep: Option<sweeps::Sweep>,
+    /// What changed (`validator_event` events only).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub validator: Option<validators::Validator>,
+    /// The underlying asset (`lst_mint` and `lst_redeem` events only).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub liquid_staking: Option<liquid_staking::LiquidStaking>,
+    /// The position change (`lending_*` events), or the account's standing
+    /// (`liquidation_risk` events).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lending: Option<lending::Lending>,
+    /// Predicted outcome (`pending_transfer` events with
+    /// `ETH_PENDING_SIMULATION`).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub simulation: Option<pending::Simulation>,
+    /// Why the transaction is stuck (`stuck_transaction` events only).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stuck: Option<stuck::Stuck>,
+    /// What the transaction paid (`fee_spike` events only).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fee_spike: Option<fee_spike::FeeSpike>,
+    /// Block and event position (`[[substrate_networks]]` chains only).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub substrate: Option<substrate::SubstrateBlock>,
+    /// Set on the history the cold start publishes from an indexer (see
+    /// `bootstrap.rs`); omitted when false.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub backfill: bool,
+    /// Block explorer links, filled in by the publisher.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub explorer_url: Option<explorer::ExplorerUrls>,
+    /// Watched address that matched this event. Used as the per-address
+    /// ordering key when publishing; never serialized.
+    #[serde(skip)]
+    pub watched_address: Option<String>,
+    /// Whether the watched address signed the transaction (Solana). Never
+    /// serialized.
+    #[serde(skip)]
+    pub signed_by_watched: bool,
+    /// What a transaction of a watched sender paid for gas, for the fee
+    /// spike check. Never serialized.
+    #[serde(skip)]
+    pub gas: Option<fee_spike::GasPaid>,
+}
+
+/// Run the `tracker` command line: the listener, or the subcommand `cli`
+/// selects.
+pub async fn run(cli: cli::Cli) -> anyhow::Result<()> {
+    // The dashboard owns the terminal, so it runs before logging is set up.
+    if let Some(cli::Command::Tui {
+        redis_url,
+        channel,
+        admin_url,
+        admin_token,
+    }) = &cli.command
+    {
+        return tui::run(redis_url, channel, admin_url, admin_token.clone()).await;
+    }
+
+    // Initialize logging
+    fmt().with_env_filter(EnvFilter::from_default_env()).init();
+
+    // Code generation needs no configuration.
+    if let Some(cli::Command::Typescript { out, no_zod }) = &cli.command {
+        let ts = schema::typescript(!no_zod);
+        match out {
+            Some(path) => std::fs::write(path, ts)?,
+            None => print!("{}", ts),
+        }
+        return Ok(());
+    }
+    if let Some(cli::Command::Ledger {
+        input,
+        format,
+        timezone,
+        locale,
+        out,
+    }) = &cli.command
+    {
+        let options = ledger::ReportOptions {
+            timezone: *timezone,
+            locale: *locale,
+        };
+        let input: Box<dyn std::io::BufRead> = if input.as_os_str() == "-" {
+            Box::new(std::io::stdin().lock())
+        } else {
+            Box::new(std::io::BufReader::new(std::fs::File::open(input)?))
+        };
+        let written = match out {
+            Some(path) => {
+                let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+                let written = ledger::export(input, *format, &options, &mut file)?;
+                std::io::Write::flush(&mut file)?;
+                written
+            }
+            None => ledger::export(input, *format, &options, &mut std::io::stdout().lock())?,
+        };
+        info!("Wrote {} ledger transactions", written);
+        return Ok(());
+    }
+    if let Some(cli::Command::Graph { input, format, out }) = &cli.command {
+        let input: Box<dyn std::io::BufRead> = if input.as_os_str() == "-" {
+            Box::new(std::io::stdin().lock())
+        } else {
+            Box::new(std::io::BufReader::new(std::fs::File::open(input)?))
+        };
+        let added = match out {
+            Some(path) => {
+                let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+                let added = graph::export(input, *format, &mut file)?;
+                std::io::Write::flush(&mut file)?;
+                added
+            }
+            None => graph::export(input, *format, &mut std::io::stdout().lock())?,
+        };
+        info!("Wrote the graph of {} transfers", added);
+        return Ok(());
+    }
+
+    if let Some(profile) = &cli.profile {
+        let set = profile::apply(profile)?;
+        info!("Profile {} set {} variables", profile, set);
+    }
+
+    // Load config
+    let cfg = match config::Config::from_env() {
+        Ok(cfg) => cfg,
+        Err(e) => {
+            error!("{:#}", e);
+            std::process::exit(1);
+        }
+    };
+
+    match &cli.command {
+        Some(cli::Command::Doctor) => {
+            let report = doctor::run(&cfg).await;
+            println!("{}", report);
+            std::process::exit(if report.passed() { 0 } else { 1 });
+        }
+        Some(cli::Command::Replay { capture, out }) => {
+            let events = capture::replay(&cfg, capture).await?;
+            let lines: String = events.iter().map(|e| format!("{}\n", e)).collect();
+            match out {
+                Some(path) => std::fs::write(path, lines)?,
+                None => print!("{}", lines),
+            }
+            return Ok(());
+        }
+        Some(cli::Command::Record { out, duration_secs }) => {
+            let publisher = Publisher::new(&cfg)?;
+            return capture::record(&cfg, publisher, out, Duration::from_secs(*duration_secs))
+                .await;
+        }
+        Some(cli::Command::Typescript { .. })
+        | Some(cli::Command::Tui { .. })
+        | Some(cli::Command::Ledger { .. })
+        | Some(cli::Command::Graph { .. })
+        | None => {}
+    }
+
+    let publisher = Publisher::new(&cfg)?;
+
+    let processed_txs: Arc<Mutex<DedupSet>> = Arc::new(Mutex::new(DedupSet::new(cfg.dedup_key)));
+    let shutdown = CancellationToken::new();
+    let mut controls = Controls::new(&shutdown);
+    let watch_lists = watchlist::WatchLists::new(&cfg);
+    let eth_tracker = Arc::new(
+        EthTracker::new(
+            cfg.clone(),
+            Arc::clone(&processed_txs),
+            controls.eth.clone(),
+            publisher.clone(),
+        )
+        .with_watch_list(watch_lists.eth.clone()),
+    );
+    let sol_tracker = Arc::new(
+        SolTracker::new(
+            cfg.clone(),
+            Arc::clone(&processed_txs),
+            controls.sol.clone(),
+            publisher.clone(),
+        )
+        .with_watch_list(watch_lists.sol.clone()),
+    );
+    // One more ETH tracker per `[[evm_networks]]` chain, sharing the dedup
+    // state. Chain names are `&'static str`; the configured ones are leaked
+    // once per process.
+    let evm_trackers: Vec<Arc<EthTracker>> = cfg
+        .evm_networks
+        .iter()
+        .map(|network| {
+            let chain: &'static str = Box::leak(network.chain.clone().into_boxed_str());
+            Arc::new(EthTracker::new(
+                network.config(&cfg),
+                Arc::clone(&processed_txs),
+                controls.add_evm(chain, &shutdown),
+                publisher.clone(),
+            ))
+        })
+        .collect();
+    let tron_tracker = cfg.tron.as_ref().map(|_| {
+        Arc::new(tron::TronTracker::new(
+            &cfg,
+            Arc::clone(&processed_txs),
+            controls.add_tron(&shutdown),
+            publisher.clone(),
+        ))
+    });
+    let substrate_trackers: Vec<Arc<substrate::SubstrateTracker>> = cfg
+        .substrate_networks
+        .iter()
+        .map(|network| {
+            let chain: &'static str = Box::leak(network.chain.clone().into_boxed_str());
+            Arc::new(substrate::SubstrateTracker::new(
+                &cfg,
+                network.clone(),
+                Arc::clone(&processed_txs),
+                controls.add_substrate(chain, &shutdown),
+                publisher.clone(),
+            ))
+        })
+        .collect();
+    publisher.watch_kill_switch_chains(controls.all().map(ChainControl::chain));
+    let mut evm_checkpoints: Vec<checkpoint::EvmCheckpoint> = cfg
+        .evm_networks
+        .iter()
+        .zip(&evm_trackers)
+        .map(|(network, tracker)| (network.key(), Arc::clone(tracker.last_block())))
+        .collect();
+    if let (Some(tron), Some(tracker)) = (&cfg.tron, &tron_tracker) {
+        evm_checkpoints.push((tron.key(), Arc::clone(tracker.last_timestamp())));
+    }
+    evm_checkpoints.extend(
+        cfg.substrate_networks
+            .iter()
+            .zip(&substrate_trackers)
+            .map(|(network, tracker)| (network.key(), Arc::clone(tracker.last_block()))),
+    );
+    let last_eth_block = Arc::clone(eth_tracker.last_block());
+    let last_sol_slot = Arc::clone(sol_tracker.last_slot());
+    let eth_ledger = Arc::clone(eth_tracker.ledger());
+    let eth_rpc = eth_tracker.rpc().clone();
+    checkpoint::restore(
+        &cfg,
+        &last_eth_block,
+        &last_sol_slot,
+        &evm_checkpoints,
+        publisher.sequences(),
+    )
+    .await
+    .context("failed to restore checkpoints")?;
+
+    let mut scheduler = Scheduler::new(cfg.schedules.clone());
+    if cfg.checkpoints.store != CheckpointStore::None {
+        let cfg = Arc::new(cfg.clone());
+        let last_eth_block = Arc::clone(&last_eth_block);
+        let last_sol_slot = Arc::clone(&last_sol_slot);
+        let evm_checkpoints = Arc::new(evm_checkpoints.clone());
+        let sequences = publisher.sequences().clone();
+        scheduler.register(
+            "checkpoint_save",
+            Schedule::Every(Duration::from_secs(10)),
+            move || {
+                let cfg = Arc::clone(&cfg);
+                let last_eth_block = Arc::clone(&last_eth_block);
+                let last_sol_slot = Arc::clone(&last_sol_slot);
+                let evm_checkpoints = Arc::clone(&evm_checkpoints);
+                let sequences = sequences.clone();
+                async move {
+                    checkpoint::save(
+                        &cfg,
+                        &last_eth_block,
+                        &last_sol_slot,
+                        &evm_checkpoints,
+                        &sequences,
+                    )
+                    .await
+                }
+            },
+        )?;
+    }
+    {
+        let processed_txs = Arc::clone(&processed_txs);
+        let retention = Duration::from_secs(cfg.dedup_retention_secs);
+        scheduler.register(
+            "dedup_compaction",
+            Schedule::Every(Duration::from_secs(600)),
+            move || {
+                let processed_txs = Arc::clone(&processed_txs);
+                async move {
+                    let mut set = processed_txs.lock().await;
+                    let removed = set.compact(retention);
+                    info!(
+                        "Dedup compaction removed {} entries, {} remaining",
+                        removed,
+                        set.len()
+                    );
+                    Ok(())
+                }
+            },
+        )?;
+    }
+    let archives = publisher.archives();
+    if !archives.is_empty() {
+        let archives = Arc::new(archives);
+        scheduler.register(
+            "archive_flush",
+            Schedule::Every(Duration::from_secs(30)),
+            move || {
+                let archives = Arc::clone(&archives);
+                async move {
+                    for (name, sink) in archives.iter() {
+                        sink.flush()
+                            .await
+                            .with_context(|| format!("destination {}", name))?;
+                    }
+                    Ok(())
+                }
+            },
+        )?;
+    }
+    let caches: Vec<_> = std::iter::once(&eth_tracker)
+        .filter(|_| cfg.eth_enabled)
+        .chain(&evm_trackers)
+        .map(|tracker| (tracker.token_metadata().clone(), tracker.rpc().clone()))
+        .collect();
+    if !caches.is_empty() {
+        let caches = Arc::new(caches);
+        scheduler.register(
+            "token_metadata_refresh",
+            Schedule::Every(Duration::from_secs(86_400)),
+            move || {
+                let caches = Arc::clone(&caches);
+                async move {
+                    for (metadata, rpc) in caches.iter() {
+                        let url = history::http_url(&rpc.active_url());
+                        let provider = match Provider::<Http>::try_from(url.as_str()) {
+                            Ok(provider) if !url.starts_with("ipc://") => provider,
+                            _ => {
+                                debug!("Token metadata refresh skipped for {}: not HTTP", url);
+                                continue;
+                            }
+                        };
+                        let changed = metadata.refresh(&provider).await;
+                        if changed > 0 {
+                            info!("Token metadata refresh updated {} tokens", changed);
+                        }
+                    }
+                    Ok(())
+                }
+            },
+        )?;
+    }
+    if cfg.eth_enabled && !cfg.watched_addresses_eth.is_empty() {
+        let reconciler = reconcile::Reconciler::start(
+            history::History::new(&cfg),
+            &cfg.watched_addresses_eth,
+            publisher.bus().subscribe("reconcile"),
+        );
+        let last_eth_block = Arc::clone(&last_eth_block);
+        scheduler.register(
+            "balance_reconciliation",
+            Schedule::Every(Duration::from_secs(600)),
+            move || {
+                let reconciler = reconciler.clone();
+                let last_eth_block = Arc::clone(&last_eth_block);
+                async move { reconciler.run(&last_eth_block).await }
+            },
+        )?;
+    }
+    let retention = retention::Retention::new(&cfg, &publisher);
+    if retention.enabled() {
+        scheduler.register(
+            "retention",
+            Schedule::Every(Duration::from_secs(3600)),
+            move || {
+                let retention = retention.clone();
+                async move { retention.run().await }
+            },
+        )?;
+    }
+    {
+        let webhooks = publisher.webhooks().clone();
+        scheduler.register(
+            "webhook_acks",
+            Schedule::Every(Duration::from_secs(10)),
+            move || {
+                let webhooks = webhooks.clone();
+                async move {
+                    webhooks.check_acks(chrono::Utc::now());
+                    Ok(())
+                }
+            },
+        )?;
+    }
+    {
+        let publisher = publisher.clone();
+        scheduler.register(
+            "kill_switch",
+            Schedule::Every(Duration::from_secs(2)),
+            move || {
+                let publisher = publisher.clone();
+                async move { publisher.refresh_kill_switch().await }
+            },
+        )?;
+    }
+    {
+        let budget = cfg.memory;
+        let processed_txs = Arc::clone(&processed_txs);
+        let publisher = publisher.clone();
+        scheduler.register(
+            "memory_report",
+            Schedule::Every(Duration::from_secs(60)),
+            move || {
+                let processed_txs = Arc::clone(&processed_txs);
+                let publisher = publisher.clone();
+                async move {
+                    memory::report(&budget, &processed_txs, &publisher).await?;
+                    Ok(())
+                }
+            },
+        )?;
+    }
+    {
+        let cfg = Arc::new(cfg.clone());
+        let controls = controls.clone();
+        let last_eth_block = Arc::clone(&last_eth_block);
+        let last_sol_slot = Arc::clone(&last_sol_slot);
+        let publisher = publisher.clone();
+        scheduler.register("address_heartbeat", Schedule::Disabled, move || {
+            let cfg = Arc::clone(&cfg);
+            let controls = controls.clone();
+            let last_eth_block = Arc::clone(&last_eth_block);
+            let last_sol_slot = Arc::clone(&last_sol_slot);
+            let publisher = publisher.clone();
+            async move {
+                heartbeat::emit(&cfg, &controls, &last_eth_block, &last_sol_slot, &publisher).await
+            }
+        })?;
+    }
+    if !cfg.portfolios.is_empty() {
+        let cfg = Arc::new(cfg.clone());
+        let history = history::History::new(&cfg);
+        let last_eth_block = Arc::clone(&last_eth_block);
+        let last_sol_slot = Arc::clone(&last_sol_slot);
+        let publisher = publisher.clone();
+        scheduler.register(
+            "portfolio_snapshot",
+            Schedule::Every(Duration::from_secs(3600)),
+            move || {
+                let cfg = Arc::clone(&cfg);
+                let history = history.clone();
+                let last_eth_block = Arc::clone(&last_eth_block);
+                let last_sol_slot = Arc::clone(&last_sol_slot);
+                let publisher = publisher.clone();
+                async move {
+                    portfolio::emit(&cfg, &history, &last_eth_block, &last_sol_slot, &publisher)
+                        .await
+                }
+            },
+        )?;
+    }
+    if cfg.eth_enabled || cfg.sol_enabled {
+        let publisher = publisher.clone();
+        scheduler.register(
+            "expectation_deadlines",
+            Schedule::Every(Duration::from_secs(30)),
+            move || {
+                let publisher = publisher.clone();
+                async move { expectations::expire(&publisher).await }
+            },
+        )?;
+    }
+    if cfg.sol_enabled {
+        let rpc = Arc::new(RpcClient::new(history::http_url(&cfg.sol_rpc_url)));
+        let publisher = publisher.clone();
+        scheduler.register(
+            "solana_finality",
+            Schedule::Every(Duration::from_secs(30)),
+            move || {
+                let rpc = Arc::clone(&rpc);
+                let publisher = publisher.clone();
+                async move { finality::check(&rpc, &publisher).await }
+            },
+        )?;
+    }
+    if let (true, Some(validators)) = (cfg.sol_enabled, &cfg.validator_monitoring) {
+        let rpc = Arc::new(RpcClient::new(history::http_url(&cfg.sol_rpc_url)));
+        let monitor = validators::ValidatorMonitor::new(validators, &cfg.sol_network);
+        let publisher = publisher.clone();
+        scheduler.register(
+            "validator_monitor",
+            Schedule::Every(Duration::from_secs(60)),
+            move || {
+                let rpc = Arc::clone(&rpc);
+                let monitor = monitor.clone();
+                let publisher = publisher.clone();
+                async move { validators::poll(&rpc, &monitor, &publisher).await }
+            },
+        )?;
+    }
+    if let (true, Some(risk)) = (cfg.eth_enabled, &cfg.liquidation_risk) {
+        let url = history::http_url(&cfg.eth_rpc_url);
+        if !lending::supports(&cfg.eth_network) {
+            warn!(
+                "[liquidation_risk] ignored: no lending markets known on {}",
+                cfg.eth_network
+            );
+        } else if let Ok(provider) = Provider::<Http>::try_from(url.as_str()) {
+            let provider = Arc::new(provider);
+            let monitor = liquidation_risk::RiskMonitor::new(risk, &cfg);
+            let publisher = publisher.clone();
+            scheduler.register(
+                "liquidation_risk",
+                Schedule::Every(Duration::from_secs(60)),
+                move || {
+                    let provider = Arc::clone(&provider);
+                    let monitor = monitor.clone();
+                    let publisher = publisher.clone();
+                    async move { liquidation_risk::poll(&provider, &monitor, &publisher).await }
+                },
+            )?;
+        } else {
+            warn!(
+                "[liquidation_risk] ignored: ETH_RPC_URL {} is not an HTTP URL",
+                url
+            );
+        }
+    }
+    if let (true, Some(stuck)) = (cfg.eth_enabled, &cfg.stuck_transactions) {
+        let url = history::http_url(&cfg.eth_rpc_url);
+        if let Ok(provider) = Provider::<Http>::try_from(url.as_str()) {
+            let provider = Arc::new(provider);
+            let monitor = stuck::StuckMonitor::new(stuck, &cfg);
+            let publisher = publisher.clone();
+            scheduler.register(
+                "stuck_transactions",
+                Schedule::Every(Duration::from_secs(30)),
+                move || {
+                    let provider = Arc::clone(&provider);
+                    let monitor = monitor.clone();
+                    let publisher = publisher.clone();
+                    async move { stuck::poll(&provider, &monitor, &publisher).await }
+                },
+            )?;
+        } else {
+            warn!(
+                "[stuck_transactions] ignored: ETH_RPC_URL {} is not an HTTP URL",
+                url
+            );
+        }
+    }
+    if cfg.eth_enabled {
+        let cfg = Arc::new(cfg.clone());
+        let eth_ledger = Arc::clone(&eth_ledger);
+        let control = controls.eth.clone();
+        let processed_txs = Arc::clone(&processed_txs);
+        let publisher = publisher.clone();
+        scheduler.register(
+            "gap_audit",
+            Schedule::Every(Duration::from_secs(300)),
+            move || {
+                let cfg = Arc::clone(&cfg);
+                let eth_ledger = Arc::clone(&eth_ledger);
+                let control = control.clone();
+                let processed_txs = Arc::clone(&processed_txs);
+                let publisher = publisher.clone();
+                async move {
+                    gaps::audit(&cfg, &eth_ledger, &control, &processed_txs, &publisher).await
+                }
+            },
+        )?;
+    }
+    if cfg.eth_enabled && eth_rpc.guards() {
+        let eth_rpc = eth_rpc.clone();
+        scheduler.register(
+            "rpc_consistency",
+            Schedule::Every(Duration::from_secs(30)),
+            move || {
+                let eth_rpc = eth_rpc.clone();
+                async move { eth_rpc.check().await }
+            },
+        )?;
+    }
+
+    if let Some(dir) = cfg.graph_export_dir.clone() {
+        let graph =
+            graph::LiveGraph::start(publisher.bus().subscribe("graph"), cfg.address_book.clone());
+        scheduler.register(
+            "graph_export",
+            Schedule::Every(Duration::from_secs(3600)),
+            move || {
+                let graph = graph.clone();
+                let dir = dir.clone();
+                async move { graph.write(&dir) }
+            },
+        )?;
+    }
+
+    let admin_state = admin::AdminState {
+        controls: controls.clone(),
+        last_eth_block: Arc::clone(&last_eth_block),
+        last_sol_slot: Arc::clone(&last_sol_slot),
+        last_evm_blocks: controls
+            .evm
+            .iter()
+            .zip(&evm_trackers)
+            .map(|(control, tracker)| (control.chain(), Arc::clone(tracker.last_block())))
+            .chain(
+                tron_tracker
+                    .iter()
+                    .map(|tracker| (tracker.chain(), Arc::clone(tracker.last_timestamp()))),
+            )
+            .chain(
+                substrate_trackers
+                    .iter()
+                    .map(|tracker| (tracker.chain(), Arc::clone(tracker.last_block()))),
+            )
+            .collect(),
+        token: cfg.admin_token.clone(),
+        expectations: publisher.expectations().clone(),
+        webhooks: publisher.webhooks().clone(),
+        history: history::History::new(&cfg),
+        streams: streams::Streams::new(&cfg)?,
+        region: cfg.region.clone(),
+        instance_id: cfg.instance_id.clone(),
+        rpc: eth_rpc,
+        dashboard: dashboard::Dashboard::start(publisher.bus().subscribe("dashboard")),
+        inspect: inspect::Inspect::start(
+            &cfg,
+            &watch_lists,
+            publisher.bus().subscribe("inspect"),
+            publisher.activity().clone(),
+        ),
+        publisher: publisher.clone(),
+        watched: watch_lists,
+        backfill: bootstrap::Backfill::new(&cfg, &processed_txs),
+    };
+    if let Some(addr) = cfg.health.bind_addr.clone() {
+        let check = health::HealthCheck::new(&cfg, admin_state.clone())?;
+        let report = check.report().clone();
+        scheduler.register(
+            "health_check",
+            Schedule::Every(Duration::from_secs(10)),
+            move || {
+                let check = check.clone();
+                async move { check.run().await }
+            },
+        )?;
+        tokio::spawn(async move {
+            if let Err(e) = health::serve(&addr, report).await {
+                error!("Health probes failed: {:?}", e);
+            }
+        });
+    }
+    let jobs = scheduler.spawn(&shutdown);
+    if let Some(addr) = cfg.admin_bind_addr.clone() {
+        let state = admin_state.clone();
+        tokio::spawn(async move {
+            if let Err(e) = admin::serve(&addr, state).await {
+                error!("Admin API failed: {:?}", e);
+            }
+        });
+    }
+    if let Some(addr) = cfg.grpc_bind_addr.clone() {
+        #[cfg(feature = "grpc")]
+        {
+            let bus = publisher.bus().clone();
+            tokio::spawn(async move {
+                if let Err(e) = grpc::serve(&addr, admin_state, bus).await {
+                    error!("gRPC services failed: {:?}", e);
+                }
+            });
+        }
+        #[cfg(not(feature = "grpc"))]
+        warn!(
+            "GRPC_BIND_ADDR={} ignored: built without the grpc feature",
+            addr
+        );
+    }
+
+    if cfg.bootstrap_etherscan_api_key.is_some() || cfg.bootstrap_helius_api_key.is_some() {
+        if let Err(e) = bootstrap::run(&cfg, &processed_txs, &publisher).await {
+            warn!("Bootstrap failed, starting live tracking: {:?}", e);
+        }
+    }
+
+    let mut trackers: Vec<Arc<dyn ChainTracker>> = vec![eth_tracker, sol_tracker];
+    trackers.extend(
+        evm_trackers
+            .into_iter()
+            .map(|tracker| tracker as Arc<dyn ChainTracker>),
+    );
+    trackers.extend(
+        tron_tracker
+            .into_iter()
+            .map(|tracker| tracker as Arc<dyn ChainTracker>),
+    );
+    trackers.extend(
+        substrate_trackers
+            .into_iter()
+            .map(|tracker| tracker as Arc<dyn ChainTracker>),
+    );
+    shutdown::listen(shutdown.clone());
+    let tracking = tracker::spawn_all(trackers, cfg.chain_runtime_threads);
+    tokio::pin!(tracking);
+    let grace = Duration::from_secs(cfg.shutdown_timeout_secs);
+    let (deadline, stopped) = tokio::select! {
+        res = &mut tracking => {
+            res?;
+            shutdown.cancel();
+            (Instant::now() + grace, true)
+        }
+        _ = shutdown.cancelled() => {
+            let deadline = Instant::now() + grace;
+            match timeout_at(deadline, &mut tracking).await {
+                Ok(res) => {
+                    res?;
+                    (deadline, true)
+                }
+                Err(_) => {
+                    warn!("Shutdown timeout reached with trackers still running");
+                    (deadline, false)
+                }
+            }
+        }
+    };
+    shutdown::flush(
+        &cfg,
+        deadline,
+        jobs,
+        &publisher,
+        &last_eth_block,
+        &last_sol_slot,
+        &evm_checkpoints,
+    )
+    .await?;
+    if !stopped {
+        // A tracker stuck in a blocking call would keep the runtime alive.
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+/// Run the subscription-based native and ERC‑20 trackers, and the pending
+/// transaction tracker when enabled, on a connected pubsub transport
+/// (websocket or IPC) until one of the first two stops or the chain is
+/// paused.
+#[allow(clippy::too_many_arguments)]
+async fn run_eth_subscriptions<P: PubsubClient + 'static>(
+    provider: Arc<Provider<P>>,
+    cfg: &config::Config,
+    archive: Option<Arc<Provider<Http>>>,
+    token_metadata: &TokenMetadata,
+    block_times: &BlockTimes,
+    headers: &HeaderCache,
+    processed_txs: &Arc<Mutex<DedupSet>>,
+    last_eth_block: &Arc<Mutex<Option<u64>>>,
+    ledger: &Arc<Mutex<BlockLedger>>,
+    control: &ChainControl,
+    publisher: &Publisher,
+) {
+    let watched_addresses: Vec<Address> = cfg
+        .watched_addresses_eth
+        .iter()
+        .map(|s| s.parse().expect("Invalid ETH address"))
+        .collect();
+    if cfg.token_warmup {
+        token_metadata
+            .warm_up(provider.as_ref(), cfg.eth_tokens.allowlist())
+            .await;
+    }
+
+    let native_tracker = async {
+        // Mined transactions arrive at the tip, so confirmations need the
+        // newHeads tracker.
+        if cfg.eth_alchemy_mined_transactions
+            && cfg.eth_confirmations == 0
+            && !watched_addresses.is_empty()
+        {
+            track_mined_transactions(
+                Arc::clone(&provider),
+                watched_addresses.clone(),
+                cfg.eth_network.clone(),
+                block_times.clone(),
+                headers.clone(),
+                Arc::clone(processed_txs),
+                Arc::clone(last_eth_block),
+                Arc::clone(ledger),
+                control.clone(),
+                publisher.clone(),
+            )
+            .await
+        } else {
+            track_native_transfers(
+                Arc::clone(&provider),
+                watched_addresses.clone(),
+                cfg.eth_network.clone(),
+                block_times.clone(),
+                headers.clone(),
+                Arc::clone(processed_txs),
+                Arc::clone(last_eth_block),
+                Arc::clone(ledger),
+                cfg.eth_confirmations,
+                control.clone(),
+                publisher.clone(),
+            )
+            .await
+        }
+    };
+
+    let pending_tracker = async {
+        if cfg.eth_pending_transfers && !watched_addresses.is_empty() {
+            if let Err(e) = pending::track(
+                Arc::clone(&provider),
+                watched_addresses.clone(),
+                cfg.eth_network.clone(),
+                cfg.eth_alchemy_mined_transactions,
+                cfg.eth_pending_simulation,
+                Arc::clone(processed_txs),
+                control.clone(),
+                publisher.clone(),
+            )
+            .await
+            {
+                warn!("Pending transaction tracker failed: {}.", e);
+            }
+        }
+        // Mined transactions still matter; wait for the other trackers.
+        std::future::pending::<()>().await
+    };
+
+    let trackers = async {
+        if watched_addresses.is_empty() {
+            warn!("No watched ETH addresses for ERC-20 transfers. Tracking native transfers only.");
+            if let Err(e) = native_tracker.await {
+                warn!("Native ETH transfer tracker failed: {}.", e);
+            }
+        } else {
+            let erc20_tracker = track_erc20_transfers(
+                Arc::clone(&provider),
+                watched_addresses.clone(),
+                cfg.eth_network.clone(),
+                token_metadata.clone(),
+                block_times.clone(),
+                Arc::clone(processed_txs),
+                Arc::clone(last_eth_block),
+                archive,
+                cfg.eth_logs_chunk_size,
+                cfg.checkpoints.max_catchup_blocks,
+                cfg.eth_confirmations,
+                cfg.eth_tokens.clone(),
+                cfg.spam.clone(),
+                control.clone(),
+                publisher.clone(),
+            );
+
+            tokio::pin!(erc20_tracker);
+            tokio::pin!(native_tracker);
+            let erc20_finished = tokio::select! {
+                res = &mut erc20_tracker => {
+                    if let Err(e) = res {
+                        warn!("ERC-20 tracker failed: {}.", e);
+                    }
+                    true
+                },
+                res = &mut native_tracker => {
+                    if let Err(e) = res {
+                        warn!("Native ETH transfer tracker failed: {}.", e);
+                    }
+                    false
+                },
+            };
+            if control.is_stopping() {
+                // Let the other tracker finish the block or log it is handling.
+                let res = if erc20_finished {
+                    native_tracker.await
+                } else {
+                    erc20_tracker.await
+                };
+                if let Err(e) = res {
+                    warn!("ETH tracker failed while pausing: {}.", e);
+                }
+            }
+        }
+    };
+    tokio::select! {
+        () = trackers => {}
+        () = pending_tracker => {}
+    }
+}
+
+/// Track ERC‑20 Transfer events via log subscriptions and publish matching events.
+///
+/// Filters to events where either the `from` or `to` matches the watched set.
+/// After a reconnect, logs emitted while disconnected are backfilled with
+/// chunked `eth_getLogs` from the last processed block, falling back to the
+/// archive endpoint if the primary node has pruned that range. On networks
+/// with known lending markets their logs are subscribed to as well (see
+/// `lending.rs`). With `confirmations`, logs are held until their block is
+/// deep enough (see `confirmations.rs`).
+#[allow(clippy::too_many_arguments)]
+async fn track_erc20_transfers<P: PubsubClient>(
+    provider: Arc<Provider<P>>,
+    watched_addresses: Vec<Address>,
+    network: String,
+    token_metadata: TokenMetadata,
+    block_times: BlockTimes,
+    processed_txs: Arc<Mutex<DedupSet>>,
+    last_block: Arc<Mutex<Option<u64>>>,
+    archive: Option<Arc<Provider<Http>>>,
+    logs_chunk_size: u64,
+    max_catchup_blocks: u64,
+    confirmations: u64,
+    tokens: TokenFilter,
+    spam: SpamFilter,
+    control: ChainControl,
+    publisher: Publisher,
+) -> anyhow::Result<()> {
+    let mut events = vec!["Transfer(address,address,uint256)"];
+    if control.chain() == "ethereum" && lending::supports(&network) {
+        events.extend(lending::EVENTS);
+    }
+    let filter = Filter::new().events(events);
+    let mut stream = provider.subscribe_logs(&filter).await?;
+    info!("Subscribed to all ERC-20 Transfer and lending market logs");
+
+    let handle = |log| {
+        handle_erc20_log(
+            &provider,
+            log,
+            &watched_addresses,
+            control.chain(),
+            &network,
+            &token_metadata,
+            &block_times,
+            &processed_txs,
+            &last_block,
+            &tokens,
+            &spam,
+            &publisher,
+        )
+    };
+    let mut pending = PendingLogs::new(confirmations);
+
+    // Subscribe first so nothing is missed between the backfill and the
+    // stream; overlap is removed by dedup.
+    let resume_from = *last_block.lock().await;
+    if let Some(prev) = resume_from {
+        let current = provider.get_block_number().await?.as_u64();
+        let prev = checkpoint::catch_up_from(prev, current, max_catchup_blocks);
+        if current > prev {
+            info!(
+                "Backfilling ERC-20 Transfer logs for blocks {} to {}",
+                prev + 1,
+                current
+            );
+            let logs = eth_logs::backfill_logs(
+                provider.as_ref(),
+                archive.as_deref(),
+                &filter,
+                prev + 1,
+                current,
+                logs_chunk_size,
+            )
+            .await?;
+            let deep = current.saturating_sub(confirmations);
+            for log in logs {
+                if control.is_stopping() {
+                    return Ok(());
+                }
+                let log = match log.block_number {
+                    Some(block) if block.as_u64() > deep => pending.hold(log),
+                    _ => Some(log),
+                };
+                if let Some(log) = log {
+                    handle(log).await;
+                }
+            }
+        }
+    }
+
+    let mut recheck =
+        tokio::time::interval(Duration::from_secs(confirmations::RECHECK_INTERVAL_SECS));
+    loop {
+        let log = tokio::select! {
+            log = stream.next() => log,
+            _ = recheck.tick(), if !pending.is_empty() => {
+                for log in confirmations::release(provider.as_ref(), &mut pending).await {
+                    handle(log).await;
+                }
+                continue;
+            }
+            _ = control.stopping() => return Ok(()),
+        };
+        let Some(log) = log else { break };
+        if let Some(log) = pending.hold(log) {
+            handle(log).await;
+        }
+    }
+    warn!("ERC-20 log stream ended.");
+    Err(anyhow!("ERC-20 log stream ended"))
+}
+
+/// Normalize and publish a single ERC‑20 Transfer log, or lending log, if it
+/// touches a watched address.
+#[allow(clippy::too_many_arguments)]
+async fn handle_erc20_log<P: JsonRpcClient>(
+    provider: &Provider<P>,
+    log: Log,
+    watched_addresses: &[Address],
+    chain: &str,
+    network: &str,
+    token_metadata: &TokenMetadata,
+    block_times: &BlockTimes,
+    processed_txs: &Arc<Mutex<DedupSet>>,
+    last_block: &Arc<Mutex<Option<u64>>>,
+    tokens: &TokenFilter,
+    spam: &SpamFilter,
+    publisher: &Publisher,
+) {
+    let trace = TraceContext::new_root();
+    // Only the Ethereum markets are known.
+    let lending = LendingLog::decode(&log, network).filter(|_| chain == "ethereum");
+    if let Some(position) = lending {
+        let Some(watched) = position.watched(watched_addresses) else {
+            return;
+        };
+        if rpc_schema::eth_log(&log).is_err() {
+            return;
+        }
+        let timestamp = log_timestamp(provider, block_times, &log).await;
+        handle_lending_log(
+            provider,
+            &log,
+            position,
+            watched,
+            timestamp,
+            network,
+            token_metadata,
+            processed_txs,
+            publisher,
+            trace,
+        )
+        .await;
+        return;
+    }
+    let Some(transfer) = TransferLog::decode(&log) else {
+        return;
+    };
+    let (from, to) = (transfer.from, transfer.to);
+    if !watched_addresses.contains(&from) && !watched_addresses.contains(&to) {
+        return;
+    }
+    if !tokens.allows(&format!("{:?}", log.address)) {
+        debug!("Skipping filtered token {:?}", log.address);
+        return;
+    }
+    if rpc_schema::eth_log(&log).is_err() {
+        return;
+    }
+
+    let tx_hash = log.transaction_hash.unwrap_or_default();
+    let watched = if watched_addresses.contains(&from) {
+        from
+    } else {
+        to
+    };
+    // Same id as the block poller, so both paths dedup against each other.
+    let event_id = processed_txs.lock().await.key().event_id(
+        dedup::evm_prefix(chain),
+        &format!("{:?}", tx_hash),
+        Some(log.log_index.unwrap_or_default().low_u64()),
+        &format!("{:?}", watched),
+    );
+
+    if processed_txs.lock().await.contains(&event_id) {
+        info!("Duplicate event skipped: {}", event_id);
+        return;
+    }
+
+    let block_number = log.block_number;
+    let timestamp = log_timestamp(provider, block_times, &log).await;
+
+    // Fetch token metadata
+    let (symbol, decimals) = token_metadata.get(provider, log.address).await;
+    let amount = transfer.amount(decimals);
+    let decode_warnings = transfer.warnings(decimals);
+    if !decode_warnings.is_empty() {
+        warn!(
+            "Non-standard Transfer log of token {:?} in tx {:?}: {:?}",
+            log.address, tx_hash, decode_warnings
+        );
+    }
+    let token_behavior = token_metadata.behavior(log.address);
+    let received_value = transfer
+        .received(
+            provider,
+            log.address,
+            token_behavior,
+            block_number.map(|bn| bn.as_u64()),
+        )
+        .await
+        .map(|received| received.to_string());
+    let signals = TransferSignals {
+        unsolicited: !watched_addresses.contains(&from),
+        zero_value: amount.is_zero(),
+        symbol: Some(&symbol),
+        metadata_unverified: symbol == UNKNOWN_SYMBOL,
+    };
+
+    let mut event = Event {
+        event_id: event_id.clone(),
+        chain: chain.into(),
+        network: network.to_string(),
+        sequence: None,
+        region: None,
+        instance_id: None,
+        tx_hash: format!("{:?}", tx_hash),
+        timestamp,
+        from: format!("{:?}", from),
+        to: format!("{:?}", to),
+        value: amount.to_string(),
+        asset_symbol: None,
+        asset_decimals: None,
+        value_formatted: None,
+        logical_asset: None,
+        event_type: EventType::Erc20Transfer,
+        slot: None,
+        block_number: block_number.map(|bn| bn.as_u64()),
+        log_index: log.log_index.map(|index| index.low_u64()),
+        token: Some(Token {
+            address: format!("{:?}", log.address),
+            symbol: symbol.clone(),
+            decimals,
+        }),
+        memo: None,
+        selector: None,
+        method: None,
+        spam: false,
+        spam_reasons: Vec::new(),
+        decode_warnings,
+        token_behavior,
+        received_value,
+        gap: None,
+        heartbeat: None,
+        expectation: None,
+        portfolio: None,
+        alert: None,
+        sweep: None,
+        validator: None,
+        liquid_staking: None,
+        lending: None,
+        simulation: None,
+        stuck: None,
+        fee_spike: None,
+        substrate: None,
+        backfill: false,
+        explorer_url: None,
+        watched_address: Some(format!("{:?}", watched)),
+        signed_by_watched: false,
+        gas: None,
+    };
+
+    if !spam.apply(&mut event, &signals) {
+        processed_txs.lock().await.insert(event_id);
+    } else if let Err(e) = publisher.publish(&event, trace).await {
+        // Only mark as processed if publish succeeds
+        error!("Failed to publish event: {:?}", e);
+        // Don't mark as processed so it can be retried later
+    } else {
+        processed_txs.lock().await.insert(event_id);
+    }
+
+    if let Some(bn) = block_number {
+        let mut last = last_block.lock().await;
+        let current_bn = bn.as_u64();
+        if last.is_none() || current_bn > last.unwrap() {
+            *last = Some(current_bn);
+            info!("Updated last processed ETH block to: {}", current_bn);
+        }
+    }
+}
+
+/// Timestamp of the block of a subscribed or backfilled log, or `""` if it
+/// can't be fetched.
+async fn log_timestamp<P: JsonRpcClient>(
+    provider: &Provider<P>,
+    block_times: &BlockTimes,
+    log: &Log,
+) -> String {
+    let Some(hash) = log.block_hash else {
+        return "".to_string();
+    };
+    match block_times.timestamp(provider, hash).await {
+        Some(timestamp) => timestamp.to_string(),
+        None => {
+            warn!(
+                "Could not get block for log in tx {:?}",
+                log.transaction_hash.unwrap_or_default()
+            );
+            "".to_string()
+        }
+    }
+}
+
+/// Publish a decoded Aave or Compound log whose account or counterparty is
+/// `watched`, with the account's health where the protocol reports it.
+#[allow(clippy::too_many_arguments)]
+async fn handle_lending_log<P: JsonRpcClient>(
+    provider: &Provider<P>,
+    log: &Log,
+    position: LendingLog,
+    watched: Address,
+    timestamp: String,
+    network: &str,
+    token_metadata: &TokenMetadata,
+    processed_txs: &Arc<Mutex<DedupSet>>,
+    publisher: &Publisher,
+    trace: TraceContext,
+) {
+    let tx_hash = log.transaction_hash.unwrap_or_default();
+    // Same id in both paths, like ERC-20 logs.
+    let event_id = processed_txs.lock().await.key().event_id(
+        "eth",
+        &format!("{:?}", tx_hash),
+        Some(log.log_index.unwrap_or_default().low_u64()),
+        &format!("{:?}", watched),
+    );
+    if processed_txs.lock().await.contains(&event_id) {
+        info!("Duplicate event skipped: {}", event_id);
+        return;
+    }
+
+    let token = match position.asset {
+        Some(asset) => {
+            let (symbol, decimals) = token_metadata.get(provider, asset).await;
+            Some(Token {
+                address: format!("{:?}", asset),
+                symbol,
+                decimals,
+            })
+        }
+        None => None,
+    };
+    let health = match log.block_number {
+        Some(block) => position.health(provider, block.as_u64()).await,
+        None => None,
+    };
+    let (from, to) = position.parties();
+    let event = Event {
+        event_id: event_id.clone(),
+        chain: "ethereum".into(),
+        network: network.to_string(),
+        sequence: None,
+        region: None,
+        instance_id: None,
+        tx_hash: format!("{:?}", tx_hash),
+        timestamp,
+        from: format!("{:?}", from),
+        to: format!("{:?}", to),
+        value: position.amount.to_string(),
+        asset_symbol: None,
+        asset_decimals: None,
+        value_formatted: None,
+        logical_asset: None,
+        event_type: position.event_type,
+        slot: None,
+        block_number: log.block_number.map(|bn| bn.as_u64()),
+        log_index: log.log_index.map(|index| index.low_u64()),
+        token,
+        memo: None,
+        selector: None,
+        method: None,
+        spam: false,
+        spam_reasons: Vec::new(),
+        decode_warnings: Vec::new(),
+        token_behavior: None,
+        received_value: None,
+        gap: None,
+        heartbeat: None,
+        expectation: None,
+        portfolio: None,
+        alert: None,
+        sweep: None,
+        validator: None,
+        liquid_staking: None,
+        lending: Some(position.payload(health)),
+        simulation: None,
+        stuck: None,
+        fee_spike: None,
+        substrate: None,
+        backfill: false,
+        explorer_url: None,
+        watched_address: Some(format!("{:?}", watched)),
+        signed_by_watched: false,
+        gas: None,
+    };
+    if let Err(e) = publisher.publish(&event, trace).await {
+        error!("Failed to publish event: {:?}", e);
+    } else {
+        processed_txs.lock().await.insert(event_id);
+    }
+}
+
+/// Track native ETH transfers by subscribing to new blocks and scanning txs.
+///
+/// This is a pragmatic approach that works across providers with websocket
+/// support and provides consistent timestamps from the block header. With
+/// `confirmations`, each new head instead has the blocks that many below it
+/// handled, fetched by number.
+#[allow(clippy::too_many_arguments)]
+async fn track_native_transfers<P: PubsubClient>(
+    provider: Arc<Provider<P>>,
+    watched_addresses: Vec<Address>,
+    network: String,
+    block_times: BlockTimes,
+    headers: HeaderCache,
+    processed_txs: Arc<Mutex<DedupSet>>,
+    last_block: Arc<Mutex<Option<u64>>>,
+    ledger: Arc<Mutex<BlockLedger>>,
+    confirmations: u64,
+    control: ChainControl,
+    publisher: Publisher,
+) -> anyhow::Result<()> {
+    let mut stream = provider.subscribe_blocks().await?;
+    info!("Subscribed to new blocks for native transfers");
+
+    // First block not handled yet, with confirmations.
+    let mut next: Option<u64> = None;
+    loop {
+        let block_sub = tokio::select! {
+            block_sub = stream.next() => block_sub,
+            _ = control.stopping() => return Ok(()),
+        };
+        let Some(block_sub) = block_sub else { break };
+        let ids: Vec<BlockId> = if confirmations == 0 {
+            block_sub.hash.map(BlockId::from).into_iter().collect()
+        } else {
+            let Some(head) = block_sub.number else {
+                continue;
+            };
+            let blocks = confirmations::confirmed_blocks(next, head.as_u64(), confirmations);
+            if blocks.is_empty() {
+                continue;
+            }
+            next = Some(blocks.end() + 1);
+            blocks.map(BlockId::from).collect()
+        };
+        for id in ids {
+            match provider.get_block_with_txs(id).await {
+                Ok(Some(block)) => {
+                    let trace = TraceContext::new_root();
+                    let block_number = block.number.unwrap_or_default();
+                    let block_hash = block.hash.unwrap_or_default();
+                    if let Some(header) = BlockHeader::of(&block) {
+                        headers.verify(&provider, header).await;
+                    }
+                    block_times.insert(block_hash, block.timestamp.as_u64());
+                    for tx in block.transactions {
+                        let from_watched =
+                            tx.from != Address::zero() && watched_addresses.contains(&tx.from);
+                        let to_watched =
+                            tx.to.is_some() && watched_addresses.contains(&tx.to.unwrap());
+
+                        if from_watched || to_watched {
+                            let watched = if from_watched {
+                                tx.from
+                            } else {
+                                tx.to.unwrap_or_default()
+                            };
+                            let event_id = processed_txs.lock().await.key().event_id(
+                                dedup::evm_prefix(control.chain()),
+                                &format!("{:?}", tx.hash),
+                                None,
+                                &format!("{:?}", watched),
+                            );
+
+                            if processed_txs.lock().await.contains(&event_id) {
+                                info!("Duplicate event skipped: {}", event_id);
+                                continue;
+                            }
+
+                            let gas = if from_watched && publisher.checks_fees() {
+                                fee_spike::fetch(&*provider, &tx, block.base_fee_per_gas).await
+                            } else {
+                                None
+                            };
+                            let selector = selectors::selector(&tx.input);
+                            let event = Event {
+                                event_id: event_id.clone(),
+                                chain: control.chain().into(),
+                                network: network.clone(),
+                                sequence: None,
+                                region: None,
+                                instance_id: None,
+                                tx_hash: format!("{:?}", tx.hash),
+                                timestamp: block.timestamp.to_string(),
+                                from: format!("{:?}", tx.from),
+                                to: format!("{:?}", tx.to.unwrap_or_default()),
+                                value: tx.value.to_string(),
+                                asset_symbol: None,
+                                asset_decimals: None,
+                                value_formatted: None,
+                                logical_asset: None,
+                                event_type: EventType::Transfer,
+                                slot: None,
+                                block_number: Some(block_number.as_u64()),
+                                log_index: None,
+                                token: None,
+                                memo: None,
+                                selector: selector.clone(),
+                                method: selector
+                                    .as_deref()
+                                    .and_then(selectors::method)
+                                    .map(str::to_string),
+                                spam: false,
+                                spam_reasons: Vec::new(),
+                                decode_warnings: Vec::new(),
+                                token_behavior: None,
+                                received_value: None,
+                                gap: None,
+                                heartbeat: None,
+                                expectation: None,
+                                portfolio: None,
+                                alert: None,
+                                sweep: None,
+                                validator: None,
+                                liquid_staking: None,
+                                lending: None,
+                                simulation: None,
+                                stuck: None,
+                                fee_spike: None,
+                                substrate: None,
+                                backfill: false,
+                                explorer_url: None,
+                                watched_address: Some(format!("{:?}", watched)),
+                                signed_by_watched: false,
+                                gas,
+                            };
+                            // Only mark as processed if publish succeeds
+                            if let Err(e) = publisher.publish(&event, trace).await {
+                                error!("Failed to publish event: {:?}", e);
+                                // Don't mark as processed so it can be retried later
+                            } else {
+                                processed_txs.lock().await.insert(event_id);
+                            }
+                        }
+                    }
+                    let mut last = last_block.lock().await;
+                    let current_bn = block_number.as_u64();
+                    if last.is_none() || current_bn > last.unwrap() {
+                        *last = Some(current_bn);
+                        info!("Updated last processed block to: {}", current_bn);
+                    }
+                    ledger.lock().await.record(current_bn);
+                }
+                Ok(None) => {
+                    warn!(
+                        "Block {:?} not found after receiving it from subscription.",
+                        id
+                    );
+                }
+                Err(e) => {
+                    error!("Error getting block with transactions: {:?}", e);
+                }
+            }
+        }
+    }
+    warn!("Native transfer block stream ended.");
+    Err(anyhow!("Native transfer block stream ended"))
+}
+
+/// An `alchemy_minedTransactions` notification.
+#[derive(Debug, Deserialize)]
+struct MinedTransaction {
+    #[serde(default)]
+    removed: bool,
+    transaction: Transaction,
+}
+
+/// Track native ETH transfers through Alchemy's `alchemy_minedTransactions`
+/// subscription (`ETH_ALCHEMY_MINED_TRANSACTIONS`), which sends only the
+/// transactions from or to watched addresses instead of every full block.
+///
+/// Block headers (`newHeads`) still drive the checkpoint and the gap ledger,
+/// and seed the block timestamp cache the events take their timestamp from.
+#[allow(clippy::too_many_arguments)]
+async fn track_mined_transactions<P: PubsubClient>(
+    provider: Arc<Provider<P>>,
+    watched_addresses: Vec<Address>,
+    network: String,
+    block_times: BlockTimes,
+    headers: HeaderCache,
+    processed_txs: Arc<Mutex<DedupSet>>,
+    last_block: Arc<Mutex<Option<u64>>>,
+    ledger: Arc<Mutex<BlockLedger>>,
+    control: ChainControl,
+    publisher: Publisher,
+) -> anyhow::Result<()> {
+    let addresses: Vec<serde_json::Value> = watched_addresses
+        .iter()
+        .flat_map(|a| {
+            [
+                serde_json::json!({ "from": a }),
+                serde_json::json!({ "to": a }),
+            ]
+        })
+        .collect();
+    let filter = serde_json::json!({
+        "addresses": addresses,
+        "includeRemoved": false,
+        "hashesOnly": false,
+    });
+    let mut heads = provider.subscribe_blocks().await?;
+    let mut mined = provider
+        .subscribe::<_, MinedTransaction>(("alchemy_minedTransactions", filter))
+        .await?;
+    info!("Subscribed to alchemy_minedTransactions for native transfers");
+
+    loop {
+        tokio::select! {
+            head = heads.next() => {
+                let Some(head) = head else { break };
+                let (Some(hash), Some(number)) = (head.hash, head.number) else {
+                    continue;
+                };
+                if let Some(header) = BlockHeader::of(&head) {
+                    headers.verify(&provider, header).await;
+                }
+                block_times.insert(hash, head.timestamp.as_u64());
+                let current_bn = number.as_u64();
+                let mut last = last_block.lock().await;
+                if last.is_none() || current_bn > last.unwrap() {
+                    *last = Some(current_bn);
+                    info!("Updated last processed block to: {}", current_bn);
+                }
+                ledger.lock().await.record(current_bn);
+            }
+            notification = mined.next() => {
+                let Some(MinedTransaction { removed, transaction: tx }) = notification else {
+                    break;
+                };
+                if removed {
+                    continue;
+                }
+                let trace = TraceContext::new_root();
+                let watched = if watched_addresses.contains(&tx.from) {
+                    tx.from
+                } else {
+                    tx.to.unwrap_or_default()
+                };
+                let event_id = processed_txs.lock().await.key().event_id(
+                    dedup::evm_prefix(control.chain()),
+                    &format!("{:?}", tx.hash),
+                    None,
+                    &format!("{:?}", watched),
+                );
+                if processed_txs.lock().await.contains(&event_id) {
+                    info!("Duplicate event skipped: {}", event_id);
+                    continue;
+                }
+                let timestamp = match tx.block_hash {
+                    Some(hash) => block_times.timestamp(&provider, hash).await,
+                    None => None,
+                };
+                let gas = if watched_addresses.contains(&tx.from) && publisher.checks_fees() {
+                    fee_spike::fetch(&*provider, &tx, None).await
+                } else {
+                    None
+                };
+                let selector = selectors::selector(&tx.input);
+                let event = Event {
+                    event_id: event_id.clone(),
+                    chain: control.chain().into(),
+                    network: network.clone(),
+                    sequence: None,
+                    region: None,
+                    instance_id: None,
+                    tx_hash: format!("{:?}", tx.hash),
+                    timestamp: timestamp.map(|t| t.to_string()).unwrap_or_default(),
+                    from: format!("{:?}", tx.from),
+                    to: format!("{:?}", tx.to.unwrap_or_default()),
+                    value: tx.value.to_string(),
+                    asset_symbol: None,
+                    asset_decimals: None,
+                    value_formatted: None,
+                    logical_asset: None,
+                    event_type: EventType::Transfer,
+                    slot: None,
+                    block_number: tx.block_number.map(|bn| bn.as_u64()),
+                    log_index: None,
+                    token: None,
+                    memo: None,
+                    selector: selector.clone(),
+                    method: selector
+                        .as_deref()
+                        .and_then(selectors::method)
+                        .map(str::to_string),
+                    spam: false,
+                    spam_reasons: Vec::new(),
+                    decode_warnings: Vec::new(),
+                    token_behavior: None,
+                    received_value: None,
+                    gap: None,
+                    heartbeat: None,
+                    expectation: None,
+                    portfolio: None,
+                    alert: None,
+                    sweep: None,
+                    validator: None,
+                    liquid_staking: None,
+                    lending: None,
+                    simulation: None,
+                    stuck: None,
+                    fee_spike: None,
+                    substrate: None,
+                    backfill: false,
+                    explorer_url: None,
+                    watched_address: Some(format!("{:?}", watched)),
+                    signed_by_watched: false,
+                    gas,
+                };
+                if let Err(e) = publisher.publish(&event, trace).await {
+                    error!("Failed to publish event: {:?}", e);
+                } else {
+                    processed_txs.lock().await.insert(event_id);
+                }
+            }
+            _ = control.stopping() => return Ok(()),
+        }
+    }
+    warn!("alchemy_minedTransactions stream ended.");
+    Err(anyhow!("alchemy_minedTransactions stream ended"))
+}
+
+/// HTTP polling mode for Ethereum (e.g., local Anvil). Processes new blocks
+/// since the last seen height and handles chain resets with a small lookback.
+/// While paused, no RPC calls are made; a pause mid-range stops after the
+/// current block and checkpoints the last one processed. With
+/// `confirmations`, polling stops that many blocks short of the head.
+///
+/// Generic over the JSON-RPC transport so record/replay runs can wrap it.
+/// With `batch`, blocks and their receipts are fetched in JSON-RPC batches
+/// ahead of processing; a failed batch falls back to single calls.
+#[allow(clippy::too_many_arguments)]
+async fn poll_eth_blocks<P: JsonRpcClient + 'static>(
+    provider: Arc<Provider<P>>,
+    batch: Option<RpcBatch>,
+    watched_addresses_str: Vec<String>,
+    network: String,
+    token_metadata: TokenMetadata,
+    tokens: TokenFilter,
+    spam: SpamFilter,
+    processed_txs: Arc<Mutex<DedupSet>>,
+    last_block: Arc<Mutex<Option<u64>>>,
+    ledger: Arc<Mutex<BlockLedger>>,
+    headers: HeaderCache,
+    max_catchup_blocks: u64,
+    confirmations: u64,
+    control: ChainControl,
+    publisher: Publisher,
+) {
+    info!("Starting ETH HTTP polling mode");
+    let watched_addresses: Vec<Address> = watched_addresses_str
+        .iter()
+        .filter_map(|s| s.parse().ok())
+        .collect();
+
+    loop {
+        control.wait_resumed().await;
+        if control.is_shutting_down() {
+            return;
+        }
+        match provider.get_block_number().await {
+            Ok(current_block) => {
+                let current = current_block.as_u64().saturating_sub(confirmations);
+                control.set_connected(true);
+                let start = {
+                    let mut last = last_block.lock().await;
+                    match *last {
+                        Some(prev) => {
+                            if current < prev {
+                                // Chain likely restarted (e.g., Anvil reset). Reset window with a small lookback
+                                // to ensure we pick up immediate post-restart transactions.
+                                let lookback = 10u64;
+                                let new_start = current.saturating_sub(lookback);
+                                *last = Some(new_start);
+                                headers.clear();
+                                info!(
+                                    "ETH poller detected block regression (prev={}, current={}); resetting start to {}",
+                                    prev, current, new_start
+                                );
+                                new_start
+                            } else {
+                                // No regression if current == prev; just continue next loop
+                                checkpoint::catch_up_from(prev, current, max_catchup_blocks)
+                            }
+                        }
+                        None => {
+                            // Initial state: start from block 0 if chain has any blocks
+                            if current > 0 {
+                                0
+                            } else {
+                                current
+                            }
+                        }
+                    }
+                };
+
+                // Process blocks even when current == start (to catch block 1 on fresh chains)
+                if current >= start {
+                    let range_start = if current == start { start } else { start + 1 };
+                    let mut checkpoint = Some(current);
+                    if range_start <= current {
+                        info!("Polling blocks {} to {}", range_start, current);
+                        let mut prefetched = HashMap::new();
+                        let mut prefetched_to = None;
+                        for block_num in range_start..=current {
+                            if control.is_stopping() {
+                                checkpoint = if block_num > range_start {
+                                    Some(block_num - 1)
+                                } else {
+                                    None
+                                };
+                                info!("ETH poller paused before block {}", block_num);
+                                break;
+                            }
+                            if let Some(batch) = &batch {
+                                if !prefetched_to.is_some_and(|to| block_num <= to) {
+                                    let end = current
+                                        .min(block_num + batch.blocks_per_request() as u64 - 1);
+                                    prefetch_eth_blocks(batch, block_num, end, &mut prefetched)
+                                        .await;
+                                    prefetched_to = Some(end);
+                                }
+                            }
+                            let result = match prefetched.remove(&block_num) {
+                                Some(Some(BatchedBlock { block, receipts })) => {
+                                    let header = BlockHeader::of(&block);
+                                    handle_eth_block(
+                                        &provider,
+                                        block,
+                                        receipts,
+                                        &watched_addresses,
+                                        control.chain(),
+                                        &network,
+                                        &token_metadata,
+                                        &tokens,
+                                        &spam,
+                                        &processed_txs,
+                                        &publisher,
+                                    )
+                                    .await
+                                    .map(|()| header)
+                                }
+                                Some(None) => Ok(None),
+                                None => {
+                                    process_eth_block(
+                                        &provider,
+                                        block_num,
+                                        &watched_addresses,
+                                        control.chain(),
+                                        &network,
+                                        &token_metadata,
+                                        &tokens,
+                                        &spam,
+                                        &processed_txs,
+                                        &publisher,
+                                    )
+                                    .await
+                                }
+                            };
+                            match result {
+                                Ok(header) => {
+                                    ledger.lock().await.record(block_num);
+                                    let linkage = match header {
+                                        Some(header) => headers.verify(&provider, header).await,
+                                        None => Linkage::Unlinked,
+                                    };
+                                    if let Linkage::Reorg { fork, .. } = linkage {
+                                        // Blocks above the fork were replaced; the
+                                        // new ones are fetched again by number.
+                                        info!("Reprocessing ETH blocks after {}", fork);
+                                        checkpoint = Some(fork);
+                                        break;
+                                    }
+                                }
+                                Err(e) => {
+                                    warn!("Error processing block {}: {:?}", block_num, e)
+                                }
+                            }
+                        }
+                    }
+                    if let Some(checkpoint) = checkpoint {
+                        *last_block.lock().await = Some(checkpoint);
+                    }
+                }
+            }
+            Err(e) => {
+                error!("Failed to get block number: {:?}", e);
+                control.set_connected(false);
+            }
+        }
+        control.sleep(Duration::from_secs(2)).await;
+    }
+}
+
+/// Batch-fetch blocks `from..=to` with their receipts into `prefetched`. If
+/// the batch fails they are left out, to be fetched singly.
+async fn prefetch_eth_blocks(
+    batch: &RpcBatch,
+    from: u64,
+    to: u64,
+    prefetched: &mut HashMap<u64, Option<BatchedBlock>>,
+) {
+    let numbers: Vec<u64> = (from..=to).collect();
+    match batch.blocks(&numbers).await {
+        Ok(blocks) => prefetched.extend(numbers.into_iter().zip(blocks)),
+        Err(e) => warn!(
+            "Batch fetch of blocks {} to {} failed, fetching singly: {:?}",
+            from, to, e
+        ),
+    }
+}
+
+/// Receipts fetched concurrently per block by `process_eth_block`.
+const RECEIPT_CONCURRENCY: usize = 16;
+
+/// Fetch the receipts for `hashes`, at most `RECEIPT_CONCURRENCY` at a time,
+/// in the order of `hashes`. A receipt that can't be fetched is `None`, like
+/// one the node doesn't have.
+async fn fetch_receipts<P: JsonRpcClient + 'static>(
+    provider: &Arc<Provider<P>>,
+    hashes: &[TxHash],
+) -> Vec<Option<TransactionReceipt>> {
+    let mut receipts = vec![None; hashes.len()];
+    let mut queued = hashes.iter().copied().enumerate();
+    let mut in_flight = JoinSet::new();
+    loop {
+        while in_flight.len() < RECEIPT_CONCURRENCY {
+            let Some((i, hash)) = queued.next() else {
+                break;
+            };
+            let provider = Arc::clone(provider);
+            in_flight.spawn(async move { (i, provider.get_transaction_receipt(hash).await) });
+        }
+        let Some(joined) = in_flight.join_next().await else {
+            break;
+        };
+        match joined {
+            Ok((i, Ok(receipt))) => receipts[i] = receipt,
+            Ok((i, Err(e))) => debug!("Failed to fetch receipt for {:?}: {:?}", hashes[i], e),
+            Err(e) => warn!("Receipt fetch task failed: {:?}", e),
+        }
+    }
+    receipts
+}
+
+/// Fetch and process a single Ethereum block (native transfers and ERC‑20
+/// logs).
+///
+/// Receipts are fetched up front, concurrently; events are still published
+/// in transaction order. Returns the block's header, or `None` if the node
+/// doesn't have the block.
+#[allow(clippy::too_many_arguments)]
+async fn process_eth_block<P: JsonRpcClient + 'static>(
+    provider: &Arc<Provider<P>>,
+    block_num: u64,
+    watched_addresses: &[Address],
+    chain: &str,
+    network: &str,
+    token_metadata: &TokenMetadata,
+    tokens: &TokenFilter,
+    spam: &SpamFilter,
+    processed_txs: &Arc<Mutex<DedupSet>>,
+    publisher: &Publisher,
+) -> anyhow::Result<Option<BlockHeader>> {
+    use ethers::types::BlockNumber;
+
+    let block = match provider
+        .get_block_with_txs(BlockNumber::Number(block_num.into()))
+        .await?
+    {
+        Some(b) => b,
+        None => return Ok(None),
+    };
+    let header = BlockHeader::of(&block);
+    let hashes: Vec<TxHash> = block.transactions.iter().map(|tx| tx.hash).collect();
+    let receipts = fetch_receipts(provider, &hashes).await;
+    handle_eth_block(
+        provider,
+        block,
+        receipts,
+        watched_addresses,
+        chain,
+        network,
+        token_metadata,
+        tokens,
+        spam,
+        processed_txs,
+        publisher,
+    )
+    .await?;
+    Ok(header)
+}
+
+/// Publish the native transfers, ERC‑20 logs and lending logs of a fetched
+/// block, with `receipts` in transaction order, and update the in‑memory
+/// deduplication state. Fails without publishing anything if the block or a
+/// receipt is malformed.
+#[allow(clippy::too_many_arguments)]
+async fn handle_eth_block<P: JsonRpcClient>(
+    provider: &Provider<P>,
+    block: Block<Transaction>,
+    receipts: Vec<Option<TransactionReceipt>>,
+    watched_addresses: &[Address],
+    chain: &str,
+    network: &str,
+    token_metadata: &TokenMetadata,
+    tokens: &TokenFilter,
+    spam: &SpamFilter,
+    processed_txs: &Arc<Mutex<DedupSet>>,
+    publisher: &Publisher,
+) -> anyhow::Result<()> {
+    rpc_schema::eth_block(&block, &receipts)?;
+    let trace = TraceContext::new_root();
+    for (tx, receipt) in block.transactions.into_iter().zip(receipts) {
+        // Check native transfers
+        // If watched_addresses is empty, track ALL transactions (useful for testing)
+        let track_all = watched_addresses.is_empty();
+        let from_watched = track_all || watched_addresses.contains(&tx.from);
+        let to_watched = track_all
+            || tx
+                .to
+                .map(|to| watched_addresses.contains(&to))
+                .unwrap_or(false);
+
+        if from_watched || to_watched {
+            let watched = if from_watched {
+                tx.from
+            } else {
+                tx.to.unwrap_or_default()
+            };
+            let event_id = processed_txs.lock().await.key().event_id(
+                dedup::evm_prefix(chain),
+                &format!("{:?}", tx.hash),
+                None,
+                &format!("{:?}", watched),
+            );
+            // Check if already processed before creating the event
+            let already_processed = {
+                let processed = processed_txs.lock().await;
+                processed.contains(&event_id)
+            };
+
+            if !already_processed {
+                let selector = selectors::selector(&tx.input);
+                let event = Event {
+                    event_id: event_id.clone(),
+                    chain: chain.into(),
+                    network: network.to_string(),
+                    sequence: None,
+                    region: None,
+                    instance_id: None,
+                    tx_hash: format!("{:?}", tx.hash),
+                    timestamp: block.timestamp.to_string(),
+                    from: format!("{:?}", tx.from),
+                    to: format!("{:?}", tx.to.unwrap_or_default()),
+                    value: tx.value.to_string(),
+                    asset_symbol: None,
+                    asset_decimals: None,
+                    value_formatted: None,
+                    logical_asset: None,
+                    event_type: EventType::Transfer,
+                    slot: None,
+                    block_number: block.number.map(|bn| bn.as_u64()),
+                    log_index: None,
+                    token: None,
+                    memo: None,
+                    selector: selector.clone(),
+                    method: selector
+                        .as_deref()
+                        .and_then(selectors::method)
+                        .map(str::to_string),
+                    spam: false,
+                    spam_reasons: Vec::new(),
+                    decode_warnings: Vec::new(),
+                    token_behavior: None,
+                    received_value: None,
+                    gap: None,
+                    heartbeat: None,
+                    expectation: None,
+                    portfolio: None,
+                    alert: None,
+                    sweep: None,
+                    validator: None,
+                    liquid_staking: None,
+                    lending: None,
+                    simulation: None,
+                    stuck: None,
+                    fee_spike: None,
+                    substrate: None,
+                    backfill: false,
+                    explorer_url: None,
+                    watched_address: Some(format!("{:?}", watched)),
+                    signed_by_watched: false,
+                    gas: receipt
+                        .as_ref()
+                        .filter(|_| watched_addresses.contains(&tx.from))
+                        .zip(block.base_fee_per_gas)
+                        .and_then(|(receipt, base_fee)| GasPaid::of(&tx, receipt, base_fee)),
+                };
+                // Only mark as processed if publish succeeds
+                if let Err(e) = publisher.publish(&event, trace).await {
+                    error!("Failed to publish event: {:?}", e);
+                    // Don't mark as processed so it can be retried later
+                } else {
+                    processed_txs.lock().await.insert(event_id);
+                }
+            }
+        }
+
+        // Check for ERC20 Transfer logs in transaction receipt
+        // Always check receipts (either for specific addresses or all if list is empty)
+        if let Some(receipt) = receipt {
+            for log in receipt.logs {
+                let lending = LendingLog::decode(&log, network).filter(|_| chain == "ethereum");
+                if let Some(position) = lending {
+                    let watched = if watched_addresses.is_empty() {
+                        Some(position.account)
+                    } else {
+                        position.watched(watched_addresses)
+                    };
+                    if let Some(watched) = watched {
+                        handle_lending_log(
+                            provider,
+                            &log,
+                            position,
+                            watched,
+                            block.timestamp.to_string(),
+                            network,
+                            token_metadata,
+                            processed_txs,
+                            publisher,
+                            trace,
+                        )
+                        .await;
+                    }
+                    continue;
+                }
+                let transfer_topic: H256 =
+                    ethers::core::utils::keccak256("Transfer(address,address,uint256)").into();
+                let transfer = match log.topics.first() {
+                    Some(topic) if *topic == transfer_topic => TransferLog::decode(&log),
+                    _ => None,
+                };
+                if let Some(transfer) = transfer {
+                    let (from, to) = (transfer.from, transfer.to);
+
+                    // Track all ERC20 transfers if watched_addresses is empty
+                    let track_all = watched_addresses.is_empty();
+                    if (track_all
+                        || watched_addresses.contains(&from)
+                        || watched_addresses.contains(&to))
+                        && tokens.allows(&format!("{:?}", log.address))
+                    {
+                        let watched = if track_all || watched_addresses.contains(&from) {
+                            from
+                        } else {
+                            to
+                        };
+                        let event_id = processed_txs.lock().await.key().event_id(
+                            dedup::evm_prefix(chain),
+                            &format!("{:?}", tx.hash),
+                            Some(log.log_index.unwrap_or_default().low_u64()),
+                            &format!("{:?}", watched),
+                        );
+
+                        // Check if already processed before creating the event
+                        let already_processed = {
+                            let processed = processed_txs.lock().await;
+                            processed.contains(&event_id)
+                        };
+
+                        if !already_processed {
+                            // Fetch token metadata
+                            let (symbol, decimals) =
+                                token_metadata.get(provider, log.address).await;
+                            let amount = transfer.amount(decimals);
+                            let decode_warnings = transfer.warnings(decimals);
+                            if !decode_warnings.is_empty() {
+                                warn!(
+                                    "Non-standard Transfer log of token {:?} in tx {:?}: {:?}",
+                                    log.address, tx.hash, decode_warnings
+                                );
+                            }
+                            let token_behavior = token_metadata.behavior(log.address);
+                            let received_value = transfer
+                                .received(
+                                    provider,
+                                    log.address,
+                                    token_behavior,
+                                    block.number.map(|bn| bn.as_u64()),
+                                )
+                                .await
+                                .map(|received| received.to_string());
+                            let signals = TransferSignals {
+                                unsolicited: !track_all && !watched_addresses.contains(&from),
+                                zero_value: amount.is_zero(),
+                                symbol: Some(&symbol),
+                                metadata_unverified: symbol == UNKNOWN_SYMBOL,
+                            };
+
+                            let mut event = Event {
+                                event_id: event_id.clone(),
+                                chain: chain.into(),
+                                network: network.to_string(),
+                                sequence: None,
+                                region: None,
+                                instance_id: None,
+                                tx_hash: format!("{:?}", tx.hash),
+                                timestamp: block.timestamp.to_string(),
+                                from: format!("{:?}", from),
+                                to: format!("{:?}", to),
+                                value: amount.to_string(),
+                                asset_symbol: None,
+                                asset_decimals: None,
+                                value_formatted: None,
+                                logical_asset: None,
+                                event_type: EventType::Erc20Transfer,
+                                slot: None,
+                                block_number: block.number.map(|bn| bn.as_u64()),
+                                log_index: log.log_index.map(|index| index.low_u64()),
+                                token: Some(Token {
+                                    address: format!("{:?}", log.address),
+                                    symbol: symbol.clone(),
+                                    decimals,
+                                }),
+                                memo: None,
+                                selector: None,
+                                method: None,
+                                spam: false,
+                                spam_reasons: Vec::new(),
+                                decode_warnings,
+                                token_behavior,
+                                received_value,
+                                gap: None,
+                                heartbeat: None,
+                                expectation: None,
+                                portfolio: None,
+                                alert: None,
+                                sweep: None,
+                                validator: None,
+                                liquid_staking: None,
+                                lending: None,
+                                simulation: None,
+                                stuck: None,
+                                fee_spike: None,
+                                substrate: None,
+                                backfill: false,
+                                explorer_url: None,
+                                watched_address: Some(format!("{:?}", watched)),
+                                signed_by_watched: false,
+                                gas: None,
+                            };
+                            if !spam.apply(&mut event, &signals) {
+                                processed_txs.lock().await.insert(event_id);
+                            } else if let Err(e) = publisher.publish(&event, trace).await {
+                                // Only mark as processed if publish succeeds
+                                error!("Failed to publish event: {:?}", e);
+                                // Don't mark as processed so it can be retried later
+                            } else {
+                                processed_txs.lock().await.insert(event_id);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Subscribe to (or rather, poll for) Solana transactions touching watched
+/// addresses and publish normalized events. Uses RPC polling to avoid
+/// compatibility issues across pubsub client versions.
+#[allow(clippy::too_many_arguments)]
+async fn subscribe_to_solana_transfers(
+    ws_url: &str,
+    network: &str,
+    watched_addresses: &[Pubkey],
+    tokens: &TokenFilter,
+    spam: &SpamFilter,
+    spl_metadata: &SplMetadata,
+    commitment: SolCommitment,
+    processed_txs: Arc<Mutex<DedupSet>>,
+    last_slot: Arc<Mutex<Option<u64>>>,
+    control: ChainControl,
+    publisher: Publisher,
+) -> anyhow::Result<()> {
+    // The solana `PubsubClient` / logs_subscribe API surface has changed across
+    // versions. To avoid depending on the websocket pubsub API and the
+    // unresolved types, poll the RPC for recent signatures for each watched
+    // address and process any new transactions.
+    let rpc_url = ws_url.replace("ws:", "http:").replace("wss:", "https:");
+    let rpc_client = Arc::new(RpcClient::new_with_commitment(rpc_url, commitment.config()));
+
+    info!("Polling Solana RPC for transfers (no websocket pubsub used)");
+
+    let mut poll_loops = JoinSet::new();
+    for address in watched_addresses {
+        let pubkey = *address;
+        let network = network.to_string();
+        let rpc_client = rpc_client.clone();
+        let processed_txs = Arc::clone(&processed_txs);
+        let last_slot = Arc::clone(&last_slot);
+        let publisher = publisher.clone();
+        let tokens = tokens.clone();
+        let spam = spam.clone();
+        let spl_metadata = spl_metadata.clone();
+        let control = control.clone();
+
+        poll_loops.spawn(async move {
+            info!("Starting poll loop for {}", pubkey);
+            loop {
+                control.wait_resumed().await;
+                if control.is_shutting_down() {
+                    return;
+                }
+                // Use the synchronous RpcClient method inside a blocking task
+                // so we don't block the async runtime's reactor.
+                let signatures_res = tokio::task::spawn_blocking({
+                    let rpc_client = rpc_client.clone();
+                    let pubkey = pubkey;
+                    move || signatures_for_address(&rpc_client, &pubkey)
+                })
+                .await;
+
+                match signatures_res {
+                    Ok(Ok(signatures)) => {
+                        control.set_connected(true);
+                        for sig_info in signatures.iter() {
+                            if control.is_stopping() {
+                                break;
+                            }
+                            // ConfirmedSignatureInfo.signature is a String
+                            let signature = sig_info.signature.clone();
+                            if let Err(e) = process_solana_transaction(
+                                &rpc_client,
+                                &network,
+                                signature,
+                                &pubkey,
+                                &tokens,
+                                &spam,
+                                &spl_metadata,
+                                Arc::clone(&processed_txs),
+                                Arc::clone(&last_slot),
+                                &publisher,
+                            )
+                            .await
+                            {
+                                warn!(
+                                    "Failed to process solana tx {}: {:?}",
+                                    sig_info.signature, e
+                                );
+                            }
+                        }
+                    }
+                    Ok(Err(e)) => {
+                        control.set_connected(false);
+                        warn!("Error fetching signatures for {}: {:?}", pubkey, e);
+                    }
+                    Err(e) => {
+                        warn!(
+                            "Task panicked while fetching signatures for {}: {:?}",
+                            pubkey, e
+                        );
+                    }
+                }
+                control.sleep(Duration::from_secs(5)).await;
+            }
+        });
+    }
+    // The loops only stop when the listener shuts down.
+    while poll_loops.join_next().await.is_some() {}
+    Ok(())
+}
+
+/// Recent signatures of `address` at the client's commitment.
+fn signatures_for_address(
+    rpc_client: &RpcClient,
+    address: &Pubkey,
+) -> solana_client::client_error::Result<Vec<RpcConfirmedTransactionStatusWithSignature>> {
+    rpc_client.get_signatures_for_address_with_config(
+        address,
+        GetConfirmedSignaturesForAddress2Config {
+            commitment: Some(rpc_client.commitment()),
+            ..Default::default()
+        },
+    )
+}
+
+/// Process a single Solana transaction by signature, emitting a normalized
+/// placeholder event when the watched address is involved (native or token).
+#[allow(clippy::too_many_arguments)]
+async fn process_solana_transaction(
+    rpc_client: &RpcClient,
+    network: &str,
+    signature: String,
+    watched_address: &Pubkey,
+    tokens: &TokenFilter,
+    spam: &SpamFilter,
+    spl_metadata: &SplMetadata,
+    processed_txs: Arc<Mutex<DedupSet>>,
+    last_slot: Arc<Mutex<Option<u64>>>,
+    publisher: &Publisher,
+) -> anyhow::Result<()> {
+    let trace = TraceContext::new_root();
+    let event_id = processed_txs.lock().await.key().event_id(
+        "sol",
+        &signature,
+        None,
+        &watched_address.to_string(),
+    );
+    if processed_txs.lock().await.contains(&event_id) {
+        info!("Duplicate event skipped: {}", event_id);
+        return Ok(());
+    }
+
+    let sig = Signature::from_str(&signature)?;
+    let tx_with_meta = rpc_client.get_transaction_with_config(
+        &sig,
+        RpcTransactionConfig {
+            encoding: Some(UiTransactionEncoding::JsonParsed),
+            commitment: Some(rpc_client.commitment()),
+            max_supported_transaction_version: Some(0),
+        },
+    )?;
+    rpc_schema::solana_tx(&signature, &tx_with_meta)?;
+
+    let slot = tx_with_meta.slot;
+    let block_time = tx_with_meta.block_time.unwrap_or(0);
+    let timestamp = chrono::DateTime::from_timestamp(block_time, 0)
+        .unwrap()
+        .to_rfc3339();
+
+    // We only check whether the watched address appears among the
+    // transaction's account keys. This is a simpler, reliable signal that the
+    // transaction touched the watched address (covers native and token
+    // transfers). `jsonParsed` transactions can't be decoded into a compiled
+    // message, so the keys are read from the JSON.
+    let mints = spl_mints(&tx_with_meta);
+    let token_allowed = tokens.allows_any(mints.iter().map(String::as_str));
+    if !token_allowed {
+        debug!("Skipping {}: no allowed SPL mint in {:?}", event_id, mints);
+    }
+    let tx_json = serde_json::to_value(&tx_with_meta.transaction.transaction)?;
+    let watched = watched_address.to_string();
+    if let Some(account_keys) = solana_parser::account_keys(&tx_json).filter(|_| token_allowed) {
+        if let Some(&(_, signed)) = account_keys.iter().find(|(key, _)| *key == watched) {
+            let token = spl_transfer_mint(&tx_with_meta, &watched).map(|(mint, reported)| {
+                let (symbol, decimals) = spl_metadata.get(rpc_client, &mint, reported);
+                Token {
+                    address: mint,
+                    symbol,
+                    decimals,
+                }
+            });
+            let received = solana_received(
+                &tx_with_meta,
+                &account_keys,
+                &watched,
+                token.as_ref().map(|t| t.address.as_str()),
+                mints.is_empty(),
+            );
+            let signals = TransferSignals {
+                unsolicited: !mints.is_empty() && !signed,
+                zero_value: spl_balance_unchanged(&tx_with_meta, &watched),
+                symbol: token.as_ref().map(|t| t.symbol.as_str()),
+                metadata_unverified: token.as_ref().is_some_and(|t| t.symbol == UNKNOWN_SYMBOL),
+            };
+            let mut event = Event {
+                event_id: event_id.clone(),
+                chain: "solana".into(),
+                network: network.to_string(),
+                sequence: None,
+                region: None,
+                instance_id: None,
+                tx_hash: signature.clone(),
+                timestamp: timestamp.clone(),
+                from: "".into(),
+                to: received.map(|_| watched.clone()).unwrap_or_default(),
+                value: received.map(|v| v.to_string()).unwrap_or_default(),
+                asset_symbol: None,
+                asset_decimals: None,
+                value_formatted: None,
+                logical_asset: None,
+                event_type: EventType::SolanaTx,
+                slot: Some(slot),
+                block_number: None,
+                log_index: None,
+                token: token.clone(),
+                memo: solana_parser::parse_memo(&tx_json),
+                selector: None,
+                method: None,
+                spam: false,
+                spam_reasons: Vec::new(),
+                decode_warnings: Vec::new(),
+                token_behavior: None,
+                received_value: None,
+                gap: None,
+                heartbeat: None,
+                expectation: None,
+                portfolio: None,
+                alert: None,
+                sweep: None,
+                validator: None,
+                liquid_staking: None,
+                lending: None,
+                simulation: None,
+                stuck: None,
+                fee_spike: None,
+                substrate: None,
+                backfill: false,
+                explorer_url: None,
+                watched_address: Some(watched_address.to_string()),
+                signed_by_watched: signed,
+                gas: None,
+            };
+            if !spam.apply(&mut event, &signals) {
+                processed_txs.lock().await.insert(event_id.clone());
+            } else if let Err(e) =
+                publish_solana_event(publisher, &event, &tx_with_meta, &account_keys, trace).await
+            {
+                // Only mark as processed if publish succeeds
+                error!("Failed to publish event: {:?}", e);
+                // Don't mark as processed so it can be retried later
+            } else {
+                processed_txs.lock().await.insert(event_id.clone());
+            }
+        }
+    }
+
+    let mut last = last_slot.lock().await;
+    let current_slot = tx_with_meta.slot;
+    if last.is_none() || current_slot > last.unwrap() {
+        *last = Some(current_slot);
+        info!("Updated last processed SOL slot to: {}", current_slot);
+    }
+
+    Ok(())
+}
+
+/// Publish a `solana_tx` event, followed by the liquid staking flows of its
+/// transaction.
+async fn publish_solana_event(
+    publisher: &Publisher,
+    event: &Event,
+    tx: &EncodedConfirmedTransactionWithStatusMeta,
+    account_keys: &[(String, bool)],
+    trace: TraceContext,
+) -> anyhow::Result<()> {
+    publisher.publish(event, trace).await?;
+    for flow in liquid_staking::solana_flows(event, tx, account_keys) {
+        publisher.publish(&flow, trace).await?;
+    }
+    Ok(())
+}
+
+/// SPL token mints whose balances a transaction changed, from the pre/post
+/// token balances in its metadata. Empty for native-only transactions.
+fn spl_mints(tx: &EncodedConfirmedTransactionWithStatusMeta) -> Vec<String> {
+    let mut mints: Vec<String> = Vec::new();
+    if let Some(meta) = &tx.transaction.meta {
+        for balances in [&meta.pre_token_balances, &meta.post_token_balances] {
+            if let OptionSerializer::Some(balances) = balances {
+                for b in balances {
+                    if !mints.contains(&b.mint) {
+                        mints.push(b.mint.clone());
+                    }
+                }
+            }
+        }
+    }
+    mints
+}
+
+/// The mint of the SPL transfer `owner` made or received in this
+/// transaction, with the decimals its token balances report: the one mint
+/// whose balance of `owner` changed. `None` if none or several did.
+fn spl_transfer_mint(
+    tx: &EncodedConfirmedTransactionWithStatusMeta,
+    owner: &str,
+) -> Option<(String, u8)> {
+    let meta = tx.transaction.meta.as_ref()?;
+    let totals = |balances: &OptionSerializer<Vec<UiTransactionTokenBalance>>| {
+        let mut totals: Vec<(String, u8, u128)> = Vec::new();
+        if let OptionSerializer::Some(balances) = balances {
+            for b in balances {
+                if !matches!(&b.owner, OptionSerializer::Some(o) if o == owner) {
+                    continue;
+                }
+                let amount = b.ui_token_amount.amount.parse::<u128>().unwrap_or(0);
+                match totals.iter_mut().find(|(mint, _, _)| *mint == b.mint) {
+                    Some((_, _, total)) => *total += amount,
+                    None => totals.push((b.mint.clone(), b.ui_token_amount.decimals, amount)),
+                }
+            }
+        }
+        totals
+    };
+    let pre = totals(&meta.pre_token_balances);
+    let post = totals(&meta.post_token_balances);
+    let amount = |totals: &[(String, u8, u128)], mint: &str| {
+        totals
+            .iter()
+            .find(|(m, _, _)| m == mint)
+            .map_or(0, |(_, _, a)| *a)
+    };
+    let mut changed = pre
+        .iter()
+        .chain(&post)
+        .filter(|(mint, _, _)| amount(&pre, mint) != amount(&post, mint))
+        .map(|(mint, decimals, _)| (mint.clone(), *decimals));
+    let first = changed.next()?;
+    changed.all(|(mint, _)| mint == first.0).then_some(first)
+}
+
+/// What `owner` received in this transaction: the growth of its balance of
+/// `mint` in base units or, for a `native` transaction (one that moved no SPL
+/// tokens), of its lamports. `None` if it didn't grow, including when several
+/// mints moved and `mint` is unknown.
+fn solana_received(
+    tx: &EncodedConfirmedTransactionWithStatusMeta,
+    account_keys: &[(String, bool)],
+    owner: &str,
+    mint: Option<&str>,
+    native: bool,
+) -> Option<u128> {
+    let meta = tx.transaction.meta.as_ref()?;
+    let change = match mint {
+        Some(mint) => liquid_staking::token_change(meta, owner, mint),
+        None if native => liquid_staking::lamport_change(meta, account_keys, owner),
+        None => 0,
+    };
+    (change > 0).then(|| change.unsigned_abs())
+}
+
+/// Whether `owner` holds SPL balances in this transaction and none of them
+/// changed, i.e. a zero-amount token transfer. False if `owner` has no token
+/// balances recorded.
+fn spl_balance_unchanged(tx: &EncodedConfirmedTransactionWithStatusMeta, owner: &str) -> bool {
+    let Some(meta) = &tx.transaction.meta else {
+        return false;
+    };
+    let totals = |balances: &OptionSerializer<Vec<UiTransactionTokenBalance>>| {
+        let mut totals: Vec<(String, u128)> = Vec::new();
+        if let OptionSerializer::Some(balances) = balances {
+            for b in balances {
+                if !matches!(&b.owner, OptionSerializer::Some(o) if o == owner) {
+                    continue;
+                }
+                let amount = b.ui_token_amount.amount.parse::<u128>().unwrap_or(0);
+                match totals.iter_mut().find(|(mint, _)| *mint == b.mint) {
+                    Some((_, total)) => *total += amount,
+                    None => totals.push((b.mint.clone(), amount)),
+                }
+            }
+        }
+        totals
+    };
+    // A newly created token account has no pre balance, which counts as zero.
+    let pre = totals(&meta.pre_token_balances);
+    let post = totals(&meta.post_token_balances);
+    !post.is_empty()
+        && post.iter().all(|(mint, amount)| {
+            let before = pre.iter().find(|(m, _)| m == mint).map_or(0, |(_, a)| *a);
+            before == *amount
+        })
+}
+
+/// Entry point for Solana tracking. Supports websocket URLs but falls back to
+/// HTTP polling mode when necessary. Restarts on failure with a short delay.
+#[allow(clippy::too_many_arguments)]
+async fn track_solana_transfers(
+    ws_url: &str,
+    network: &str,
+    watched_addresses_str: &[String],
+    tokens: &TokenFilter,
+    spam: &SpamFilter,
+    commitment: SolCommitment,
+    processed_txs: Arc<Mutex<DedupSet>>,
+    last_slot: Arc<Mutex<Option<u64>>>,
+    control: ChainControl,
+    publisher: Publisher,
+) {
+    if watched_addresses_str.is_empty() {
+        info!("No Solana addresses to watch.");
+        return;
+    }
+
+    // Support both WebSocket and HTTP URLs
+    let use_websocket = ws_url.starts_with("ws");
+    // Kept across reconnects.
+    let spl_metadata = SplMetadata::default();
+
+    if !use_websocket {
+        info!("Using HTTP polling mode for Solana at {}", ws_url);
+        // For HTTP mode, convert URL and use polling
+        let rpc_client = Arc::new(RpcClient::new_with_commitment(
+            ws_url.to_string(),
+            commitment.config(),
+        ));
+        poll_solana_transfers(
+            rpc_client,
+            network,
+            watched_addresses_str,
+            tokens.clone(),
+            spam.clone(),
+            spl_metadata,
+            processed_txs,
+            last_slot,
+            control,
+            publisher,
+        )
+        .await;
+        return;
+    }
+
+    let watched_addresses: Vec<Pubkey> = watched_addresses_str
+        .iter()
+        .map(|s| Pubkey::from_str(s).expect("Invalid Solana address"))
+        .collect();
+
+    loop {
+        match subscribe_to_solana_transfers(
+            ws_url,
+            network,
+            &watched_addresses,
+            tokens,
+            spam,
+            &spl_metadata,
+            commitment,
+            Arc::clone(&processed_txs),
+            Arc::clone(&last_slot),
+            control.clone(),
+            publisher.clone(),
+        )
+        .await
+        {
+            Ok(_) if control.is_shutting_down() => return,
+            Ok(_) => info!("Solana subscription stream ended. This should not happen."),
+            Err(e) => error!("Solana subscription failed: {:?}. Reconnecting...", e),
+        }
+        control.sleep(Duration::from_secs(5)).await;
+    }
+}
+
+/// HTTP polling loop for Solana. Iterates per‑address to keep logic simple and
+/// robust across RPC versions, publishing new events as they appear.
+#[allow(clippy::too_many_arguments)]
+async fn poll_solana_transfers(
+    rpc_client: Arc<RpcClient>,
+    network: &str,
+    watched_addresses_str: &[String],
+    tokens: TokenFilter,
+    spam: SpamFilter,
+    spl_metadata: SplMetadata,
+    processed_txs: Arc<Mutex<DedupSet>>,
+    last_slot: Arc<Mutex<Option<u64>>>,
+    control: ChainControl,
+    publisher: Publisher,
+) {
+    info!("Starting Solana HTTP polling mode");
+    let watched_addresses: Vec<Pubkey> = watched_addresses_str
+        .iter()
+        .filter_map(|s| Pubkey::from_str(s).ok())
+        .collect();
+
+    // Signatures up to a restored checkpoint were published before the
+    // restart. A chain behind the checkpoint was reset (e.g. a restarted test
+    // validator), so nothing is skipped then.
+    let resume_slot = match *last_slot.lock().await {
+        Some(saved) => {
+            let current = tokio::task::spawn_blocking({
+                let rpc_client = rpc_client.clone();
+                move || rpc_client.get_slot()
+            })
+            .await;
+            match current {
+                Ok(Ok(current)) if current < saved => {
+                    info!(
+                        "Solana slot {} is behind checkpoint {}; not skipping signatures",
+                        current, saved
+                    );
+                    None
+                }
+                _ => Some(saved),
+            }
+        }
+        None => None,
+    };
+
+    let mut poll_loops = JoinSet::new();
+    for address in watched_addresses {
+        let pubkey = address;
+        let network = network.to_string();
+        let rpc_client = rpc_client.clone();
+        let processed_txs = Arc::clone(&processed_txs);
+        let last_slot = Arc::clone(&last_slot);
+        let publisher = publisher.clone();
+        let tokens = tokens.clone();
+        let spam = spam.clone();
+        let spl_metadata = spl_metadata.clone();
+        let control = control.clone();
+
+        poll_loops.spawn(async move {
+            info!("Starting poll loop for Solana address {}", pubkey);
+            loop {
+                control.wait_resumed().await;
+                if control.is_shutting_down() {
+                    return;
+                }
+                let signatures_res = tokio::task::spawn_blocking({
+                    let rpc_client = rpc_client.clone();
+                    let pubkey = pubkey;
+                    move || signatures_for_address(&rpc_client, &pubkey)
+                })
+                .await;
+
+                match signatures_res {
+                    Ok(Ok(signatures)) => {
+                        control.set_connected(true);
+                        for sig_info in signatures.iter() {
+                            if control.is_stopping() {
+                                break;
+                            }
+                            if resume_slot.is_some_and(|slot| sig_info.slot <= slot) {
+                                continue;
+                            }
+                            let signature = sig_info.signature.clone();
+                            if let Err(e) = process_solana_transaction(
+                                &rpc_client,
+                                &network,
+                                signature,
+                                &pubkey,
+                                &tokens,
+                                &spam,
+                                &spl_metadata,
+                                Arc::clone(&processed_txs),
+                                Arc::clone(&last_slot),
+                                &publisher,
+                            )
+                            .await
+                            {
+                                warn!(
+                                    "Failed to process solana tx {}: {:?}",
+                                    sig_info.signature, e
+                                );
+                            }
+                        }
+                    }
+                    Ok(Err(e)) => {
+                        control.set_connected(false);
+                        warn!("Error fetching signatures for {}: {:?}", pubkey, e);
+                    }
+                    Err(e) => {
+                        warn!(
+                            "Task panicked while fetching signatures for {}: {:?}",
+                            pubkey, e
+                        );
+                    }
+                }
+                control.sleep(Duration::from_secs(5)).await;
+            }
+        });
+    }
+
+    // The loops only stop when the listener shuts down.
+    while poll_loops.join_next().await.is_some() {}
+}
@@ -0,0 +1,303 @@
+//! Liquid staking flows of watched addresses (`lst_mint` and `lst_redeem`
+//! events).
+//!
+//! Staking into and out of a liquid staking token (LST) shows up as an
+//! ordinary token transfer, or as an unexplained Solana transaction, and the
+//! amount of ETH or SOL behind it is lost. For the protocols below, a watched
+//! address's mint or redemption is followed by an `lst_mint` or `lst_redeem`
+//! copy of its event with the LST amount in `value` and `token`, and the
+//! underlying asset in `liquid_staking`:
+//!
+//! - Lido stETH (Ethereum mainnet): a `Transfer` of stETH from the zero
+//!   address to a watched address is a mint; one from a watched address to
+//!   the Lido withdrawal queue is a redemption request. stETH balances are
+//!   denominated in ETH, so the underlying amount is the transferred amount.
+//! - Marinade mSOL and Jito jitoSOL (Solana): a transaction that calls the
+//!   protocol's program and changes a watched address's LST balance. The
+//!   underlying amount is the opposite change of the address's SOL balance,
+//!   net of the fee if the address paid it, so it includes any account rent
+//!   the transaction paid. A redemption paid out later (Marinade delayed
+//!   unstake) or in a stake account has no `underlying_value`.
+//!
+//! Solana flows are recognized by the RPC trackers, which read the
+//! transaction's balances; with `SOL_SOURCE=helius` or `geyser` only the
+//! `solana_tx` event is published.
+use serde::Serialize;
+use solana_transaction_status::option_serializer::OptionSerializer;
+use solana_transaction_status::{
+    EncodedConfirmedTransactionWithStatusMeta, UiTransactionStatusMeta, UiTransactionTokenBalance,
+};
+
+use crate::assets::SOL_DECIMALS;
+use crate::event_type::EventType;
+use crate::{Event, Token};
+
+const ETH_DECIMALS: u8 = 18;
+
+const ZERO_ADDRESS: &str = "0x0000000000000000000000000000000000000000";
+
+/// An LST on Ethereum.
+struct EthLst {
+    protocol: &'static str,
+    network: &'static str,
+    token: &'static str,
+    /// Where holders send the LST to redeem it.
+    withdrawal_queue: &'static str,
+}
+
+const ETHEREUM: &[EthLst] = &[EthLst {
+    protocol: "lido",
+    network: "mainnet",
+    token: "0xae7ab96520de3a18e5e111b5eaab095312d7fe84",
+    withdrawal_queue: "0x889edc2edab5f40e902b864ad4d7ade8e412f9b1",
+}];
+
+/// An LST on Solana.
+struct SolLst {
+    protocol: &'static str,
+    /// Program whose instructions mint and burn the LST.
+    program: &'static str,
+    mint: &'static str,
+    symbol: &'static str,
+    decimals: u8,
+}
+
+const SOLANA: &[SolLst] = &[
+    SolLst {
+        protocol: "marinade",
+        program: "MarBmsSgKXdrN1egZf5sqe1TMai9K1rChYNDJgjq7aD",
+        mint: "mSoLzYCxHdYgdzU16g5QSh3i5K3z3KZK7ytfqcJm7So",
+        symbol: "mSOL",
+        decimals: 9,
+    },
+    SolLst {
+        protocol: "jito",
+        program: "SPoo1Ku8WFXoNDMHPsrGSTSG1Y47rzgn41SLUNakuHy",
+        mint: "J1toso1uCk3RLmjorhTtrVwY9HJ7X8V9yYKVYPr7UhG",
+        symbol: "jitoSOL",
+        decimals: 9,
+    },
+];
+
+/// Payload of `lst_mint` and `lst_redeem` events.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct LiquidStaking {
+    /// `lido`, `marinade` or `jito`.
+    pub protocol: String,
+    /// The staked asset, `ETH` or `SOL`.
+    pub underlying_symbol: String,
+    pub underlying_decimals: u8,
+    /// Amount of the staked asset paid in or out, in its smallest unit;
+    /// absent when the transaction doesn't pay it out.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub underlying_value: Option<String>,
+}
+
+/// The `lst_mint` or `lst_redeem` event an Ethereum `erc20_transfer` event
+/// is, if any.
+pub fn ethereum_flow(event: &Event) -> Option<Event> {
+    if event.chain != "ethereum" || event.event_type != EventType::Erc20Transfer {
+        return None;
+    }
+    let token = event.token.as_ref()?;
+    let lst = ETHEREUM.iter().find(|lst| {
+        lst.network == event.network && lst.token.eq_ignore_ascii_case(&token.address)
+    })?;
+    let watched = event.watched_address.as_deref()?;
+    let event_type = if event.from == ZERO_ADDRESS && watched.eq_ignore_ascii_case(&event.to) {
+        EventType::LstMint
+    } else if lst.withdrawal_queue.eq_ignore_ascii_case(&event.to)
+        && watched.eq_ignore_ascii_case(&event.from)
+    {
+        EventType::LstRedeem
+    } else {
+        return None;
+    };
+    Some(flow(
+        event.clone(),
+        event_type,
+        LiquidStaking {
+            protocol: lst.protocol.into(),
+            underlying_symbol: "ETH".into(),
+            underlying_decimals: ETH_DECIMALS,
+            underlying_value: Some(event.value.clone()),
+        },
+    ))
+}
+
+/// The `lst_mint` and `lst_redeem` events of the watched address of
+/// `event`, a `solana_tx` event for `tx` with the transaction's
+/// `account_keys`.
+pub fn solana_flows(
+    event: &Event,
+    tx: &EncodedConfirmedTransactionWithStatusMeta,
+    account_keys: &[(String, bool)],
+) -> Vec<Event> {
+    let (Some(meta), Some(owner)) = (&tx.transaction.meta, event.watched_address.as_deref()) else {
+        return Vec::new();
+    };
+    let lamports = lamport_change(meta, account_keys, owner);
+    SOLANA
+        .iter()
+        .filter(|lst| account_keys.iter().any(|(key, _)| key == lst.program))
+        .filter_map(|lst| {
+            let (event_type, amount, underlying) =
+                solana_flow(token_change(meta, owner, lst.mint), lamports)?;
+            let (from, to) = match event_type {
+                EventType::LstMint => (String::new(), owner.to_string()),
+                _ => (owner.to_string(), String::new()),
+            };
+            Some(flow(
+                Event {
+                    from,
+                    to,
+                    value: amount.to_string(),
+                    token: Some(Token {
+                        address: lst.mint.into(),
+                        symbol: lst.symbol.into(),
+                        decimals: lst.decimals,
+                    }),
+                    ..event.clone()
+                },
+                event_type,
+                LiquidStaking {
+                    protocol: lst.protocol.into(),
+                    underlying_symbol: "SOL".into(),
+                    underlying_decimals: SOL_DECIMALS,
+                    underlying_value: underlying.map(|lamports| lamports.to_string()),
+                },
+            ))
+        })
+        .collect()
+}
+
+/// Type, LST amount and underlying amount of a flow, from the owner's change
+/// of LST and of lamports (net of the fee). The underlying amount is the
+/// lamports that moved the other way, if any did.
+fn solana_flow(lst_change: i128, lamport_change: i128) -> Option<(EventType, u128, Option<u128>)> {
+    match lst_change {
+        0 => None,
+        minted if minted > 0 => Some((
+            EventType::LstMint,
+            minted.unsigned_abs(),
+            (lamport_change < 0).then(|| lamport_change.unsigned_abs()),
+        )),
+        burned => Some((
+            EventType::LstRedeem,
+            burned.unsigned_abs(),
+            (lamport_change > 0).then(|| lamport_change.unsigned_abs()),
+        )),
+    }
+}
+
+fn flow(event: Event, event_type: EventType, staking: LiquidStaking) -> Event {
+    Event {
+        event_id: format!("{}:{}", event.event_id, event_type),
+        event_type,
+        liquid_staking: Some(staking),
+        ..event
+    }
+}
+
+/// Change of `owner`'s SOL balance, with the fee added back if it paid it.
+pub(crate) fn lamport_change(
+    meta: &UiTransactionStatusMeta,
+    account_keys: &[(String, bool)],
+    owner: &str,
+) -> i128 {
+    let Some(index) = account_keys.iter().position(|(key, _)| key == owner) else {
+        return 0;
+    };
+    let (Some(pre), Some(post)) = (meta.pre_balances.get(index), meta.post_balances.get(index))
+    else {
+        return 0;
+    };
+    let fee = if index == 0 { meta.fee } else { 0 };
+    *post as i128 - *pre as i128 + fee as i128
+}
+
+/// Change of `owner`'s balance of `mint`, in base units.
+pub(crate) fn token_change(meta: &UiTransactionStatusMeta, owner: &str, mint: &str) -> i128 {
+    let total = |balances: &OptionSerializer<Vec<UiTransactionTokenBalance>>| -> i128 {
+        let OptionSerializer::Some(balances) = balances else {
+            return 0;
+        };
+        balances
+            .iter()
+            .filter(|b| {
+                b.mint == mint && matches!(&b.owner, OptionSerializer::Some(o) if o == owner)
+            })
+            .map(|b| b.ui_token_amount.amount.parse::<i128>().unwrap_or(0))
+            .sum()
+    };
+    total(&meta.post_token_balances) - total(&meta.pre_token_balances)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WATCHED: &str = "0x70997970c51812dc3a010c7d01b50e0d17dc79c8";
+
+    fn steth_transfer(from: &str, to: &str) -> Event {
+        Event {
+            event_id: "eth:0xabc:7".into(),
+            chain: "ethereum".into(),
+            network: "mainnet".into(),
+            from: from.into(),
+            to: to.into(),
+            value: "1000000000000000000".into(),
+            event_type: EventType::Erc20Transfer,
+            token: Some(Token {
+                address: "0xae7aB96520DE3A18E5e111B5EaAb095312D7fE84".into(),
+                symbol: "stETH".into(),
+                decimals: 18,
+            }),
+            watched_address: Some(WATCHED.into()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_steth_mint_and_redeem() {
+        let mint = ethereum_flow(&steth_transfer(ZERO_ADDRESS, WATCHED)).unwrap();
+        assert_eq!(mint.event_type, EventType::LstMint);
+        assert_eq!(mint.event_id, "eth:0xabc:7:lst_mint");
+        let staking = mint.liquid_staking.unwrap();
+        assert_eq!(staking.protocol, "lido");
+        assert_eq!(
+            staking.underlying_value.as_deref(),
+            Some("1000000000000000000")
+        );
+
+        let queue = "0x889edC2eDab5f40e902b864aD4d7AdE8E412F9B1";
+        let redeem = ethereum_flow(&steth_transfer(WATCHED, queue)).unwrap();
+        assert_eq!(redeem.event_type, EventType::LstRedeem);
+
+        // Ordinary stETH transfers, and other networks, are no flows.
+        assert!(ethereum_flow(&steth_transfer(WATCHED, "0x01")).is_none());
+        let mut testnet = steth_transfer(ZERO_ADDRESS, WATCHED);
+        testnet.network = "sepolia".into();
+        assert!(ethereum_flow(&testnet).is_none());
+    }
+
+    #[test]
+    fn test_solana_flow() {
+        // Staked 2 SOL (and paid 0.002 SOL of rent) for 1.9 mSOL.
+        assert_eq!(
+            solana_flow(1_900_000_000, -2_002_039_280),
+            Some((EventType::LstMint, 1_900_000_000, Some(2_002_039_280)))
+        );
+        // Liquid unstake pays out at once.
+        assert_eq!(
+            solana_flow(-1_000_000_000, 1_050_000_000),
+            Some((EventType::LstRedeem, 1_000_000_000, Some(1_050_000_000)))
+        );
+        // Delayed unstake pays out later.
+        assert_eq!(
+            solana_flow(-1_000_000_000, -2_000_000),
+            Some((EventType::LstRedeem, 1_000_000_000, None))
+        );
+        assert_eq!(solana_flow(0, -5_000), None);
+    }
+}
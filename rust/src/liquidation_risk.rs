@@ -0,0 +1,297 @@
+//! Liquidation-risk alerts for watched borrowers (`[liquidation_risk]` in
+//! `CONFIG_FILE`, `liquidation_risk` job).
+//!
+//! `lending_*` events say when a position changes, but a position mostly
+//! gets riskier without any transaction of its owner: the collateral's price
+//! falls, or the debt's rises. Once a minute the job reads the Aave v3
+//! health factor of every watched Ethereum address at the head block, and
+//! publishes a `liquidation_risk` event when an address with debt falls
+//! below `threshold`:
+//!
+//! ```toml
+//! [liquidation_risk]
+//! threshold = 1.1   # health factor, default
+//! ```
+//!
+//! The event's `alert` has the rule `liquidation_risk`, severity `high`, or
+//! `critical` once the health factor is below 1 and the position can be
+//! liquidated, and the address book label of the address. `lending` holds
+//! the account's collateral, debt and health factor. An address is alerted
+//! again only when it gets worse (from `high` to `critical`), or after it
+//! recovered above the threshold or repaid its debt. That state is kept in
+//! memory, so after a restart a position still at risk is alerted once
+//! more. Compound v2 has no health factor and isn't monitored; like the
+//! lending events, only Ethereum mainnet is.
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex as StdMutex};
+
+use anyhow::bail;
+use chrono::Utc;
+use ethers::providers::{Http, Middleware, Provider};
+use ethers::types::{Address, U256};
+use serde::Deserialize;
+use tracing::{info, warn};
+
+use crate::address_book::{Alert, Severity};
+use crate::config::Config;
+use crate::event_type::EventType;
+use crate::lending::{self, AccountHealth};
+use crate::publisher::Publisher;
+use crate::trace_context::TraceContext;
+use crate::Event;
+
+/// Rule name of `liquidation_risk` alerts.
+pub const LIQUIDATION_RISK: &str = "liquidation_risk";
+
+fn default_threshold() -> f64 {
+    1.1
+}
+
+/// The `[liquidation_risk]` section of the config file.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LiquidationRiskConfig {
+    /// Health factor below which a position is alerted.
+    #[serde(default = "default_threshold")]
+    pub threshold: f64,
+}
+
+impl LiquidationRiskConfig {
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if !self.threshold.is_finite() || self.threshold < 1.0 {
+            bail!("threshold must be at least 1, the health factor positions are liquidated at");
+        }
+        Ok(())
+    }
+}
+
+/// How close a position is to liquidation, in increasing order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Risk {
+    /// Below the threshold.
+    AtRisk,
+    /// Below 1: anyone can liquidate it.
+    Liquidatable,
+}
+
+impl Risk {
+    fn severity(self) -> Severity {
+        match self {
+            Risk::AtRisk => Severity::High,
+            Risk::Liquidatable => Severity::Critical,
+        }
+    }
+}
+
+/// Health factor 1, with 18 decimals.
+fn one() -> U256 {
+    U256::exp10(18)
+}
+
+/// The watched addresses and the risk they were last alerted at. Cheap to
+/// clone; all clones share the state.
+#[derive(Debug, Clone, Default)]
+pub struct RiskMonitor {
+    /// Watched addresses and their address book labels.
+    accounts: Arc<Vec<(Address, Option<String>)>>,
+    /// `threshold` with 18 decimals.
+    threshold: U256,
+    network: String,
+    alerted: Arc<StdMutex<HashMap<Address, Risk>>>,
+}
+
+impl RiskMonitor {
+    pub fn new(risk: &LiquidationRiskConfig, cfg: &Config) -> Self {
+        let accounts = cfg
+            .watched_addresses_eth
+            .iter()
+            .filter_map(|address| {
+                let label = cfg.address_book.label("ethereum", address);
+                Some((address.parse().ok()?, label.map(str::to_string)))
+            })
+            .collect();
+        RiskMonitor {
+            accounts: Arc::new(accounts),
+            // Four decimals are plenty for a threshold.
+            threshold: U256::from((risk.threshold * 10_000.0).round() as u64) * U256::exp10(14),
+            network: cfg.eth_network.clone(),
+            alerted: Arc::default(),
+        }
+    }
+
+    fn risk(&self, health: &AccountHealth) -> Option<Risk> {
+        if health.total_debt_base.is_zero() {
+            None
+        } else if health.health_factor < one() {
+            Some(Risk::Liquidatable)
+        } else if health.health_factor < self.threshold {
+            Some(Risk::AtRisk)
+        } else {
+            None
+        }
+    }
+
+    /// `account`'s current risk, and the alert it calls for if it got worse
+    /// since the last one.
+    fn check(
+        &self,
+        account: Address,
+        label: Option<&str>,
+        health: AccountHealth,
+        block: u64,
+        now: i64,
+    ) -> (Option<Risk>, Option<Event>) {
+        let risk = self.risk(&health);
+        let previous = self.alerted.lock().unwrap().get(&account).copied();
+        let Some(current) = risk.filter(|current| Some(*current) > previous) else {
+            return (risk, None);
+        };
+        let address = format!("{:?}", account);
+        let lending = health.payload(account);
+        let event = Event {
+            event_id: format!("liquidation_risk:ethereum:{}:{}", address, block),
+            chain: "ethereum".into(),
+            network: self.network.clone(),
+            timestamp: now.to_string(),
+            from: address.clone(),
+            to: lending.market.clone(),
+            event_type: EventType::LiquidationRisk,
+            alert: Some(Alert {
+                rule: LIQUIDATION_RISK.to_string(),
+                severity: current.severity(),
+                address: address.clone(),
+                label: label.map(str::to_string),
+            }),
+            lending: Some(lending),
+            watched_address: Some(address),
+            ..Default::default()
+        };
+        (risk, Some(event))
+    }
+
+    fn record(&self, account: Address, risk: Option<Risk>) {
+        let mut alerted = self.alerted.lock().unwrap();
+        match risk {
+            Some(risk) => alerted.insert(account, risk),
+            None => alerted.remove(&account),
+        };
+    }
+}
+
+/// Read the watched addresses' health factors at the head and publish the
+/// alerts they call for. An address whose alert can't be published is
+/// checked against its old state again on the next run.
+pub async fn poll(
+    provider: &Provider<Http>,
+    monitor: &RiskMonitor,
+    publisher: &Publisher,
+) -> anyhow::Result<()> {
+    let block = provider.get_block_number().await?.as_u64();
+    let now = Utc::now().timestamp();
+    let mut failed = 0;
+    for (account, label) in monitor.accounts.iter() {
+        let Some(health) = lending::aave_health(provider, *account, block).await else {
+            failed += 1;
+            continue;
+        };
+        let (risk, event) = monitor.check(*account, label.as_deref(), health, block, now);
+        if let Some(event) = event {
+            info!("Liquidation risk of {:?}: {}", account, event.event_id);
+            publisher.publish(&event, TraceContext::new_root()).await?;
+        }
+        monitor.record(*account, risk);
+    }
+    if failed > 0 {
+        warn!(
+            "Could not read the Aave health factor of {} of {} watched addresses at block {}",
+            failed,
+            monitor.accounts.len(),
+            block
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn monitor(threshold: f64) -> RiskMonitor {
+        let cfg = Config {
+            watched_addresses_eth: vec!["0x70997970c51812dc3a010c7d01b50e0d17dc79c8".into()],
+            eth_network: "mainnet".into(),
+            ..Default::default()
+        };
+        RiskMonitor::new(&LiquidationRiskConfig { threshold }, &cfg)
+    }
+
+    fn health(debt: u64, health_factor: U256) -> AccountHealth {
+        AccountHealth {
+            total_collateral_base: U256::from(1_000_000_000_000u64),
+            total_debt_base: U256::from(debt),
+            health_factor,
+        }
+    }
+
+    /// Health factor `percent` / 100.
+    fn factor(percent: u64) -> U256 {
+        U256::from(percent) * U256::exp10(16)
+    }
+
+    #[test]
+    fn test_alerts_when_risk_grows() {
+        let monitor = monitor(1.2);
+        let (account, _) = monitor.accounts[0].clone();
+        let check = |health, block| {
+            let (risk, event) = monitor.check(account, Some("Vault"), health, block, 0);
+            monitor.record(account, risk);
+            event
+        };
+
+        assert!(check(health(1, factor(150)), 1).is_none());
+        let alert = check(health(1, factor(115)), 2).unwrap();
+        assert_eq!(alert.event_type, EventType::LiquidationRisk);
+        assert_eq!(
+            alert.event_id,
+            "liquidation_risk:ethereum:0x70997970c51812dc3a010c7d01b50e0d17dc79c8:2"
+        );
+        let rule = alert.alert.unwrap();
+        assert_eq!(rule.severity, Severity::High);
+        assert_eq!(rule.label.as_deref(), Some("Vault"));
+        let lending = alert.lending.unwrap();
+        assert_eq!(lending.health_factor, Some(factor(115).to_string()));
+
+        // Still at risk: no new alert, until it can be liquidated.
+        assert!(check(health(1, factor(110)), 3).is_none());
+        let critical = check(health(1, factor(99)), 4).unwrap();
+        assert_eq!(critical.alert.unwrap().severity, Severity::Critical);
+
+        // Recovered, then at risk again.
+        assert!(check(health(1, factor(130)), 5).is_none());
+        assert!(check(health(1, factor(119)), 6).is_some());
+        // Repaying everything resets it too.
+        assert!(check(health(0, U256::MAX), 7).is_none());
+        assert!(check(health(1, factor(119)), 8).is_some());
+    }
+
+    #[test]
+    fn test_unpublished_alert_is_retried() {
+        let monitor = monitor(1.1);
+        let (account, _) = monitor.accounts[0].clone();
+        let (_, event) = monitor.check(account, None, health(1, factor(105)), 1, 0);
+        assert!(event.is_some());
+        // Not recorded, as when publishing failed.
+        let (_, event) = monitor.check(account, None, health(1, factor(105)), 2, 0);
+        assert!(event.is_some());
+    }
+
+    #[test]
+    fn test_config_validation() {
+        assert!(LiquidationRiskConfig { threshold: 1.5 }.validate().is_ok());
+        assert!(LiquidationRiskConfig { threshold: 0.9 }.validate().is_err());
+        let nan = LiquidationRiskConfig {
+            threshold: f64::NAN,
+        };
+        assert!(nan.validate().is_err());
+    }
+}
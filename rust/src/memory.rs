@@ -0,0 +1,130 @@
+//! Memory budgets and usage reports (`memory_report` scheduler job).
+//!
+//! The dedup set and the webhook delivery logs, with the webhook events held
+//! or waiting for an acknowledgement, grow with traffic, so a burst or a long
+//! retention window can take the process past its container limit.
+//! The job logs their estimated size along with the number of events waiting
+//! in the destination queues, and when a budget is set (`DEDUP_MEMORY_MB`,
+//! `WEBHOOK_LOG_MEMORY_MB`) it shrinks the structure back under it, oldest
+//! entries first, instead of letting it grow until the process is killed.
+//! The destination queues are bounded by their `queue_size` and the event bus
+//! by its capacity, so they need no budget of their own.
+use std::sync::Arc;
+
+use tokio::sync::Mutex;
+use tracing::{info, warn};
+
+use crate::dedup::DedupSet;
+use crate::publisher::Publisher;
+
+/// Byte budgets; `None` is unlimited.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MemoryBudget {
+    pub dedup_bytes: Option<usize>,
+    pub webhook_log_bytes: Option<usize>,
+}
+
+/// Estimated usage after a run of the job.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Usage {
+    pub dedup_entries: usize,
+    pub dedup_bytes: usize,
+    pub webhook_log_records: usize,
+    pub webhook_log_bytes: usize,
+    pub queued_events: usize,
+}
+
+fn mb(bytes: usize) -> f64 {
+    bytes as f64 / (1024.0 * 1024.0)
+}
+
+/// Shrink whatever is over its budget, then log the estimated usage.
+pub async fn report(
+    budget: &MemoryBudget,
+    processed_txs: &Arc<Mutex<DedupSet>>,
+    publisher: &Publisher,
+) -> anyhow::Result<Usage> {
+    let (dedup_entries, dedup_bytes) = {
+        let mut set = processed_txs.lock().await;
+        if let Some(max) = budget.dedup_bytes {
+            let evicted = set.evict_to(max);
+            if evicted > 0 {
+                warn!(
+                    "Dedup set over its {:.1} MB budget, evicted the {} oldest ids",
+                    mb(max),
+                    evicted
+                );
+            }
+        }
+        (set.len(), set.estimated_bytes())
+    };
+    let webhooks = publisher.webhooks();
+    if let Some(max) = budget.webhook_log_bytes {
+        let trimmed = webhooks.trim_log(max);
+        if trimmed > 0 {
+            warn!(
+                "Webhook delivery logs over their {:.1} MB budget, dropped {} records",
+                mb(max),
+                trimmed
+            );
+        }
+    }
+    let (webhook_log_records, webhook_log_bytes) = webhooks.log_usage();
+    let usage = Usage {
+        dedup_entries,
+        dedup_bytes,
+        webhook_log_records,
+        webhook_log_bytes,
+        queued_events: publisher.queued(),
+    };
+    info!(
+        "Memory: dedup set {:.1} MB ({} ids), webhook logs {:.1} MB ({} records), \
+         {} events queued",
+        mb(usage.dedup_bytes),
+        usage.dedup_entries,
+        mb(usage.webhook_log_bytes),
+        usage.webhook_log_records,
+        usage.queued_events
+    );
+    Ok(usage)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::trace_context::TraceContext;
+    use crate::Event;
+
+    #[tokio::test]
+    async fn test_report_enforces_dedup_budget() {
+        let processed_txs = Arc::new(Mutex::new(DedupSet::default()));
+        for i in 0..10 {
+            processed_txs.lock().await.insert(format!("eth:0x{:02}", i));
+        }
+        let (publisher, _rx) = Publisher::in_memory();
+        publisher
+            .publish(
+                &Event {
+                    event_id: "eth:0xff".into(),
+                    ..Default::default()
+                },
+                TraceContext::new_root(),
+            )
+            .await
+            .unwrap();
+
+        let unlimited = report(&MemoryBudget::default(), &processed_txs, &publisher)
+            .await
+            .unwrap();
+        assert_eq!(unlimited.dedup_entries, 10);
+        assert_eq!(unlimited.queued_events, 1);
+
+        let budget = MemoryBudget {
+            dedup_bytes: Some(unlimited.dedup_bytes / 2),
+            ..Default::default()
+        };
+        let usage = report(&budget, &processed_txs, &publisher).await.unwrap();
+        assert_eq!(usage.dedup_entries, 5);
+        assert!(usage.dedup_bytes <= unlimited.dedup_bytes / 2);
+    }
+}
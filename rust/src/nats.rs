@@ -0,0 +1,99 @@
+//! NATS destinations (`sink = "nats"`, build with `--features nats`).
+//!
+//! Each event is published to the subject its destination's `subject`
+//! template gives it, e.g. `events.ethereum.erc20_transfer` for
+//! `events.{chain}.{event_type}` (see [`crate::sink::route`]), so
+//! subscribers pick events with subject wildcards such as `events.*.transfer`.
+//!
+//! Core NATS is fire-and-forget: a publish counts once the server has
+//! received it, and subscribers that are offline miss it. With
+//! `jetstream = true` the publisher waits for the JetStream acknowledgement
+//! instead, so an event only counts as published once a stream has stored
+//! it. The stream has to exist and cover the subjects; the sink doesn't
+//! create it.
+use anyhow::anyhow;
+use async_nats::jetstream;
+use async_trait::async_trait;
+use tokio::sync::Mutex;
+
+use crate::config::DestinationConfig;
+use crate::sink::EventSink;
+
+pub struct NatsSink {
+    url: String,
+    subject: String,
+    jetstream: bool,
+    /// Connected on first use; the client reconnects by itself afterwards.
+    client: Mutex<Option<async_nats::Client>>,
+}
+
+impl NatsSink {
+    /// The sink of `dest`, a `nats` destination. Connects on the first
+    /// publish, so a server that is down at startup delays events instead of
+    /// failing the listener.
+    pub fn open(dest: &DestinationConfig) -> anyhow::Result<Self> {
+        let url = dest
+            .nats_url
+            .clone()
+            .ok_or_else(|| anyhow!("destination {}: nats sink needs a nats_url", dest.name))?;
+        Ok(NatsSink {
+            url,
+            subject: dest.subject.clone(),
+            jetstream: dest.jetstream,
+            client: Mutex::new(None),
+        })
+    }
+
+    async fn client(&self) -> anyhow::Result<async_nats::Client> {
+        let mut slot = self.client.lock().await;
+        if let Some(client) = slot.as_ref() {
+            return Ok(client.clone());
+        }
+        let client = async_nats::connect(&self.url)
+            .await
+            .map_err(|e| anyhow!("failed to connect to NATS at {}: {}", self.url, e))?;
+        *slot = Some(client.clone());
+        Ok(client)
+    }
+}
+
+#[async_trait]
+impl EventSink for NatsSink {
+    async fn publish(&self, _message: &[u8]) -> anyhow::Result<()> {
+        // Batches would span subjects; the config rejects batch_max_events.
+        Err(anyhow!("nats sink publishes one event per subject"))
+    }
+
+    async fn publish_routed(
+        &self,
+        subject: &str,
+        _key: &str,
+        message: &[u8],
+    ) -> anyhow::Result<()> {
+        let client = self.client().await?;
+        let subject = subject.to_string();
+        if self.jetstream {
+            jetstream::new(client)
+                .publish(subject.clone(), message.to_vec().into())
+                .await
+                .map_err(|e| anyhow!("JetStream publish to {} failed: {}", subject, e))?
+                .await
+                .map_err(|e| anyhow!("JetStream didn't store {}: {}", subject, e))?;
+        } else {
+            client
+                .publish(subject.clone(), message.to_vec().into())
+                .await
+                .map_err(|e| anyhow!("NATS publish to {} failed: {}", subject, e))?;
+            client
+                .flush()
+                .await
+                .map_err(|e| anyhow!("NATS flush failed: {}", e))?;
+        }
+        Ok(())
+    }
+
+    fn describe(&self) -> String {
+        let kind = if self.jetstream { "jetstream" } else { "nats" };
+        format!("{} subjects {} on {}", kind, self.subject, self.url)
+    }
+}
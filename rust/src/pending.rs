@@ -0,0 +1,437 @@
+//! Pending transactions of watched Ethereum addresses
+//! (`ETH_PENDING_TRANSFERS`), with an optional simulation preview
+//! (`ETH_PENDING_SIMULATION`).
+//!
+//! A transaction from or to a watched address is published as a
+//! `pending_transfer` event when it reaches the node's mempool, with event id
+//! `<mined event id>:pending` and the time it was seen as `timestamp`. Once
+//! mined it is published again as usual; a transaction that is dropped or
+//! replaced gets no further event. With `ETH_ALCHEMY_MINED_TRANSACTIONS` on,
+//! the node filters the mempool with `alchemy_pendingTransactions`;
+//! otherwise the full transactions come from `newPendingTransactions`
+//! (geth, erigon, reth), which means reading the whole mempool. Only the
+//! subscription trackers watch the mempool: HTTP polling has no pending
+//! events.
+//!
+//! With simulation, each transaction is first executed against the pending
+//! state with `debug_traceCall` and the call tracer, and `simulation` says
+//! whether it would succeed, why not, the gas it would use and the ERC-20
+//! transfers and approvals it would emit, in any contract it calls. On a
+//! node without the debug namespace it is an `eth_call` instead, which only
+//! tells success or the revert reason. The preview is as good as the
+//! pending state: a transaction mined first can change the outcome.
+use std::sync::Arc;
+
+use anyhow::anyhow;
+use chrono::Utc;
+use ethers::abi::{self, ParamType, Token};
+use ethers::providers::{JsonRpcClient, Middleware, Provider, PubsubClient, RpcError};
+use ethers::types::{Address, Bytes, Transaction, H256, U256};
+use ethers::utils::keccak256;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tokio::sync::Mutex;
+use tokio_stream::StreamExt;
+use tracing::{debug, error, info, warn};
+
+use crate::control::ChainControl;
+use crate::dedup::DedupSet;
+use crate::event_type::EventType;
+use crate::publisher::Publisher;
+use crate::trace_context::TraceContext;
+use crate::{selectors, Event};
+
+/// Selector of Solidity's `Error(string)` revert data.
+const ERROR_SELECTOR: [u8; 4] = [0x08, 0xc3, 0x79, 0xa0];
+
+/// How the simulation ran.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SimulationMethod {
+    /// `debug_traceCall` with the call tracer.
+    Trace,
+    /// `eth_call`: no gas or effects.
+    Call,
+}
+
+/// What a token effect is.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum EffectKind {
+    Transfer,
+    Approval,
+}
+
+/// An ERC-20 `Transfer` or `Approval` log the transaction would emit.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Effect {
+    pub kind: EffectKind,
+    /// The token contract.
+    pub token: String,
+    /// Sender, or owner of an approval.
+    pub from: String,
+    /// Recipient, or spender of an approval.
+    pub to: String,
+    /// Raw amount, or allowance.
+    pub value: String,
+}
+
+/// Predicted outcome of a pending transaction (`pending_transfer` events
+/// with `ETH_PENDING_SIMULATION`).
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Simulation {
+    pub method: SimulationMethod,
+    pub success: bool,
+    /// Why it would fail, e.g. `execution reverted` or `out of gas`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// The `Error(string)` message of a revert.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub revert_reason: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gas_used: Option<u64>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub effects: Vec<Effect>,
+}
+
+/// A frame of the call tracer's output.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+struct CallFrame {
+    gas_used: Option<U256>,
+    error: Option<String>,
+    revert_reason: Option<String>,
+    logs: Vec<TraceLog>,
+    calls: Vec<CallFrame>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TraceLog {
+    address: Address,
+    #[serde(default)]
+    topics: Vec<H256>,
+    #[serde(default)]
+    data: Bytes,
+}
+
+fn topic(signature: &str) -> H256 {
+    H256::from(keccak256(signature))
+}
+
+/// The transfer or approval `log` records, if it is an ERC-20 one.
+fn effect(log: &TraceLog) -> Option<Effect> {
+    if log.topics.len() != 3 || log.data.len() < 32 {
+        return None;
+    }
+    let kind = if log.topics[0] == topic("Transfer(address,address,uint256)") {
+        EffectKind::Transfer
+    } else if log.topics[0] == topic("Approval(address,address,uint256)") {
+        EffectKind::Approval
+    } else {
+        return None;
+    };
+    Some(Effect {
+        kind,
+        token: format!("{:?}", log.address),
+        from: format!("{:?}", Address::from(log.topics[1])),
+        to: format!("{:?}", Address::from(log.topics[2])),
+        value: U256::from_big_endian(&log.data[..32]).to_string(),
+    })
+}
+
+/// Effects of `frame` and the calls it made. A failed call's logs are
+/// discarded with its state changes.
+fn collect_effects(frame: &CallFrame, effects: &mut Vec<Effect>) {
+    if frame.error.is_some() {
+        return;
+    }
+    effects.extend(frame.logs.iter().filter_map(effect));
+    for call in &frame.calls {
+        collect_effects(call, effects);
+    }
+}
+
+fn from_trace(frame: CallFrame) -> Simulation {
+    let mut effects = Vec::new();
+    collect_effects(&frame, &mut effects);
+    Simulation {
+        method: SimulationMethod::Trace,
+        success: frame.error.is_none(),
+        error: frame.error,
+        revert_reason: frame.revert_reason,
+        gas_used: frame.gas_used.map(|gas| gas.low_u64()),
+        effects,
+    }
+}
+
+/// The message of `Error(string)` revert data.
+fn revert_reason(data: &[u8]) -> Option<String> {
+    let payload = data.strip_prefix(&ERROR_SELECTOR)?;
+    match abi::decode(&[ParamType::String], payload).ok()?.pop()? {
+        Token::String(reason) => Some(reason),
+        _ => None,
+    }
+}
+
+/// Run `tx` against the pending state. `None` if the node can do neither a
+/// trace nor a call.
+pub async fn simulate<P: JsonRpcClient>(
+    provider: &Provider<P>,
+    tx: &Transaction,
+) -> Option<Simulation> {
+    let call = json!({
+        "from": tx.from,
+        "to": tx.to,
+        "gas": tx.gas,
+        "value": tx.value,
+        "data": tx.input,
+    });
+    let tracer = json!({ "tracer": "callTracer", "tracerConfig": { "withLog": true } });
+    let trace = provider
+        .request::<_, CallFrame>("debug_traceCall", (&call, "pending", tracer))
+        .await;
+    let e = match trace {
+        Ok(frame) => return Some(from_trace(frame)),
+        Err(e) => e,
+    };
+    debug!("debug_traceCall of {:?} failed: {}", tx.hash, e);
+    match provider
+        .request::<_, Bytes>("eth_call", (&call, "pending"))
+        .await
+    {
+        Ok(_) => Some(Simulation {
+            method: SimulationMethod::Call,
+            success: true,
+            error: None,
+            revert_reason: None,
+            gas_used: None,
+            effects: Vec::new(),
+        }),
+        Err(e) => {
+            // A revert is an error response; anything else is a failed call.
+            let response = e.as_error_response()?;
+            Some(Simulation {
+                method: SimulationMethod::Call,
+                success: false,
+                error: Some(response.message.clone()),
+                revert_reason: response
+                    .as_revert_data()
+                    .and_then(|data| revert_reason(&data)),
+                gas_used: None,
+                effects: Vec::new(),
+            })
+        }
+    }
+}
+
+/// The watched party of `tx`, the sender first.
+fn watched_party(tx: &Transaction, watched_addresses: &[Address]) -> Option<Address> {
+    if watched_addresses.contains(&tx.from) {
+        return Some(tx.from);
+    }
+    tx.to.filter(|to| watched_addresses.contains(to))
+}
+
+fn pending_event(
+    tx: &Transaction,
+    watched: Address,
+    network: &str,
+    event_id: String,
+    simulation: Option<Simulation>,
+) -> Event {
+    let selector = selectors::selector(&tx.input);
+    Event {
+        event_id,
+        chain: "ethereum".into(),
+        network: network.to_string(),
+        tx_hash: format!("{:?}", tx.hash),
+        timestamp: Utc::now().timestamp().to_string(),
+        from: format!("{:?}", tx.from),
+        to: format!("{:?}", tx.to.unwrap_or_default()),
+        value: tx.value.to_string(),
+        event_type: EventType::PendingTransfer,
+        method: selector
+            .as_deref()
+            .and_then(selectors::method)
+            .map(str::to_string),
+        selector,
+        simulation,
+        watched_address: Some(format!("{:?}", watched)),
+        ..Default::default()
+    }
+}
+
+/// Publish a `pending_transfer` event for every pending transaction of a
+/// watched address until the subscription ends or the chain is paused.
+#[allow(clippy::too_many_arguments)]
+pub async fn track<P: PubsubClient>(
+    provider: Arc<Provider<P>>,
+    watched_addresses: Vec<Address>,
+    network: String,
+    alchemy: bool,
+    simulation: bool,
+    processed_txs: Arc<Mutex<DedupSet>>,
+    control: ChainControl,
+    publisher: Publisher,
+) -> anyhow::Result<()> {
+    let mut stream = if alchemy {
+        let filter = json!({
+            "fromAddress": watched_addresses,
+            "toAddress": watched_addresses,
+            "hashesOnly": false,
+        });
+        let stream = provider
+            .subscribe::<_, Transaction>(("alchemy_pendingTransactions", filter))
+            .await?;
+        info!("Subscribed to alchemy_pendingTransactions");
+        stream
+    } else {
+        let stream = provider.subscribe_full_pending_txs().await?;
+        info!("Subscribed to full pending transactions");
+        stream
+    };
+
+    loop {
+        let tx = tokio::select! {
+            tx = stream.next() => tx,
+            _ = control.stopping() => return Ok(()),
+        };
+        let Some(tx) = tx else { break };
+        let Some(watched) = watched_party(&tx, &watched_addresses) else {
+            continue;
+        };
+        let trace = TraceContext::new_root();
+        let event_id = format!(
+            "{}:pending",
+            processed_txs.lock().await.key().event_id(
+                "eth",
+                &format!("{:?}", tx.hash),
+                None,
+                &format!("{:?}", watched),
+            )
+        );
+        if processed_txs.lock().await.contains(&event_id) {
+            debug!("Pending transaction already published: {}", event_id);
+            continue;
+        }
+        let simulation = if simulation {
+            simulate(&provider, &tx).await
+        } else {
+            None
+        };
+        let event = pending_event(&tx, watched, &network, event_id.clone(), simulation);
+        if let Err(e) = publisher.publish(&event, trace).await {
+            error!("Failed to publish event: {:?}", e);
+        } else {
+            processed_txs.lock().await.insert(event_id);
+        }
+    }
+    warn!("Pending transaction stream ended.");
+    Err(anyhow!("Pending transaction stream ended"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn padded(address: Address) -> H256 {
+        H256::from(address)
+    }
+
+    fn log(signature: &str, value: u64) -> TraceLog {
+        let mut data = [0u8; 32];
+        U256::from(value).to_big_endian(&mut data);
+        TraceLog {
+            address: Address::from_low_u64_be(0xaa),
+            topics: vec![
+                topic(signature),
+                padded(Address::from_low_u64_be(1)),
+                padded(Address::from_low_u64_be(2)),
+            ],
+            data: Bytes::from(data.to_vec()),
+        }
+    }
+
+    #[test]
+    fn test_trace_effects() {
+        let frame = CallFrame {
+            gas_used: Some(U256::from(51_000)),
+            logs: vec![log("Approval(address,address,uint256)", 5)],
+            calls: vec![
+                CallFrame {
+                    logs: vec![log("Transfer(address,address,uint256)", 7)],
+                    ..Default::default()
+                },
+                // Reverted inner call: its transfer doesn't happen.
+                CallFrame {
+                    error: Some("execution reverted".into()),
+                    logs: vec![log("Transfer(address,address,uint256)", 9)],
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+        let simulation = from_trace(frame);
+        assert!(simulation.success);
+        assert_eq!(simulation.gas_used, Some(51_000));
+        assert_eq!(
+            simulation
+                .effects
+                .iter()
+                .map(|e| (e.kind, e.value.as_str()))
+                .collect::<Vec<_>>(),
+            [(EffectKind::Approval, "5"), (EffectKind::Transfer, "7")]
+        );
+        assert_eq!(
+            simulation.effects[1].to,
+            format!("{:?}", Address::from_low_u64_be(2))
+        );
+    }
+
+    #[test]
+    fn test_trace_revert() {
+        let frame: CallFrame = serde_json::from_value(json!({
+            "type": "CALL",
+            "gasUsed": "0x5208",
+            "error": "execution reverted",
+            "revertReason": "ERC20: transfer amount exceeds balance",
+            "logs": [],
+        }))
+        .unwrap();
+        let simulation = from_trace(frame);
+        assert!(!simulation.success);
+        assert_eq!(
+            simulation.revert_reason.as_deref(),
+            Some("ERC20: transfer amount exceeds balance")
+        );
+        assert!(simulation.effects.is_empty());
+    }
+
+    #[test]
+    fn test_revert_reason() {
+        let mut data = ERROR_SELECTOR.to_vec();
+        data.extend(abi::encode(&[Token::String("nope".into())]));
+        assert_eq!(revert_reason(&data).as_deref(), Some("nope"));
+        assert_eq!(revert_reason(&[0xde, 0xad, 0xbe, 0xef]), None);
+    }
+
+    #[test]
+    fn test_pending_event() {
+        let watched = Address::from_low_u64_be(1);
+        let tx = Transaction {
+            from: Address::from_low_u64_be(9),
+            to: Some(watched),
+            value: U256::from(3),
+            input: Bytes::from(vec![0xa9, 0x05, 0x9c, 0xbb]),
+            ..Default::default()
+        };
+        assert_eq!(watched_party(&tx, &[watched]), Some(watched));
+        assert_eq!(watched_party(&tx, &[]), None);
+
+        let event = pending_event(&tx, watched, "mainnet", "eth:0x1:pending".into(), None);
+        let payload = serde_json::to_value(&event).unwrap();
+        assert_eq!(payload["event_type"], "pending_transfer");
+        assert_eq!(payload["method"], "transfer(address,uint256)");
+        assert!(payload.get("simulation").is_none());
+    }
+}
@@ -0,0 +1,403 @@
+//! Valuation snapshots of watched portfolios (`portfolio_snapshot` job).
+//!
+//! Treasury dashboards want what a group of addresses is worth, not a stream
+//! of transfers to sum up. A `[[portfolios]]` entry in `CONFIG_FILE` names a
+//! set of addresses across chains, and the job publishes one
+//! `portfolio_snapshot` event per portfolio with the balance of every asset
+//! they hold and its value at the `[prices]` configured for it:
+//!
+//! ```toml
+//! [[portfolios]]
+//! name = "treasury"
+//! addresses = ["ethereum:0x7099...", "solana:9xQe..."]
+//!
+//! [prices]
+//! ETH = 2500.0
+//! USDC = 1.0
+//! SOL = 150.0
+//! ```
+//!
+//! Balances are read through [`History`] at each chain's checkpoint, so a
+//! snapshot agrees with the events published so far. Holdings are grouped by
+//! logical asset (`[[assets]]`) where one is mapped, otherwise by symbol,
+//! which is also the key into `[prices]`. Assets without a price are listed
+//! without a value and left out of the total. Solana contributes SOL only,
+//! since SPL balances aren't reconstructed.
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::Arc;
+
+use anyhow::{anyhow, bail};
+use chrono::{DateTime, Utc};
+use ethers::types::U256;
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+use tracing::warn;
+
+use crate::amount::Amount;
+use crate::asset_map::AssetMap;
+use crate::config::Config;
+use crate::event_type::EventType;
+use crate::history::{AddressState, History};
+use crate::publisher::Publisher;
+use crate::trace_context::TraceContext;
+use crate::Event;
+
+/// One `[[portfolios]]` entry in the config file.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PortfolioConfig {
+    pub name: String,
+    /// `<chain>:<address>`, e.g. `ethereum:0x...`.
+    pub addresses: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Portfolio {
+    name: String,
+    /// `(chain, address)` pairs.
+    addresses: Vec<(String, String)>,
+}
+
+/// The configured portfolios and asset prices.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Portfolios {
+    portfolios: Vec<Portfolio>,
+    prices: HashMap<String, f64>,
+}
+
+impl Portfolios {
+    /// Validate the entries: unique non-empty names and addresses on a
+    /// supported chain. Prices must be finite and non-negative.
+    pub fn new(configs: &[PortfolioConfig], prices: HashMap<String, f64>) -> anyhow::Result<Self> {
+        let mut names = HashSet::new();
+        let mut portfolios = Vec::new();
+        for config in configs {
+            if config.name.trim().is_empty() {
+                bail!("portfolio with an empty name");
+            }
+            if !names.insert(config.name.as_str()) {
+                bail!("portfolio {} is defined twice", config.name);
+            }
+            let mut addresses = Vec::new();
+            for entry in &config.addresses {
+                let (chain, address) = entry
+                    .split_once(':')
+                    .filter(|(chain, _)| matches!(*chain, "ethereum" | "solana"))
+                    .ok_or_else(|| {
+                        anyhow!(
+                            "portfolio {}: address '{}' must be ethereum:<address> or \
+                             solana:<address>",
+                            config.name,
+                            entry
+                        )
+                    })?;
+                addresses.push((chain.to_string(), address.trim().to_string()));
+            }
+            portfolios.push(Portfolio {
+                name: config.name.clone(),
+                addresses,
+            });
+        }
+        if let Some((asset, price)) = prices.iter().find(|(_, p)| !p.is_finite() || **p < 0.0) {
+            bail!(
+                "price of {} must be a non-negative number, got {}",
+                asset,
+                price
+            );
+        }
+        Ok(Portfolios { portfolios, prices })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.portfolios.is_empty()
+    }
+}
+
+/// Payload of a `portfolio_snapshot` event.
+#[derive(Serialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct Snapshot {
+    pub name: String,
+    /// Ethereum block the balances were read at, if the portfolio has
+    /// Ethereum addresses.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub block: Option<u64>,
+    /// Solana slot, likewise.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub slot: Option<u64>,
+    /// Sum of the priced positions' values, to two decimals.
+    pub total_value: String,
+    /// Non-zero balances per asset, by asset name.
+    pub positions: Vec<Position>,
+}
+
+/// Balance of one asset across a portfolio's addresses.
+#[derive(Serialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct Position {
+    /// Logical asset, or the symbol if the asset isn't mapped.
+    pub asset: String,
+    /// In whole units, e.g. `1.5`.
+    pub balance: String,
+    /// Configured price per whole unit; absent if there is none.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub price: Option<String>,
+    /// `balance` × `price`, to two decimals.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value: Option<String>,
+}
+
+/// Value the balances of `states` (each with the network of its chain).
+fn snapshot(
+    name: &str,
+    states: &[(&str, AddressState)],
+    asset_map: &AssetMap,
+    prices: &HashMap<String, f64>,
+) -> Snapshot {
+    // Amounts of one asset may differ in decimals across chains (USDT has 6
+    // on Ethereum and 18 on BSC), so they are summed at the largest.
+    let mut totals: BTreeMap<String, (U256, u8)> = BTreeMap::new();
+    let mut snapshot = Snapshot {
+        name: name.to_string(),
+        ..Default::default()
+    };
+    for (network, state) in states {
+        match state.chain.as_str() {
+            "ethereum" => snapshot.block = Some(state.at),
+            _ => snapshot.slot = Some(state.at),
+        }
+        for holding in std::iter::once(&state.native).chain(&state.tokens) {
+            let Some(amount) = Amount::parse(&holding.balance, holding.decimals) else {
+                continue;
+            };
+            if amount.is_zero() {
+                continue;
+            }
+            let asset = asset_map
+                .logical_asset(&state.chain, network, holding.token.as_deref())
+                .unwrap_or(holding.symbol.as_str());
+            let (sum, decimals) = totals.entry(asset.to_string()).or_default();
+            let scale = holding.decimals.max(*decimals);
+            *sum = rescale(*sum, *decimals, scale) + rescale(amount.raw(), holding.decimals, scale);
+            *decimals = scale;
+        }
+    }
+    let mut total = 0.0;
+    for (asset, (sum, decimals)) in totals {
+        let balance = Amount::new(sum, decimals).formatted();
+        let price = prices.get(&asset).copied();
+        let value = price.map(|price| price * balance.parse::<f64>().unwrap_or_default());
+        total += value.unwrap_or_default();
+        snapshot.positions.push(Position {
+            asset,
+            balance,
+            price: price.map(|price| price.to_string()),
+            value: value.map(|value| format!("{:.2}", value)),
+        });
+    }
+    snapshot.total_value = format!("{:.2}", total);
+    snapshot
+}
+
+fn rescale(raw: U256, from: u8, to: u8) -> U256 {
+    raw * U256::exp10((to - from) as usize)
+}
+
+fn snapshot_event(snapshot: Snapshot, now: DateTime<Utc>) -> Event {
+    Event {
+        event_id: format!("portfolio:{}:{}", snapshot.name, now.timestamp()),
+        timestamp: now.to_rfc3339(),
+        event_type: EventType::PortfolioSnapshot,
+        portfolio: Some(snapshot),
+        ..Default::default()
+    }
+}
+
+/// Publish a snapshot of every configured portfolio. A portfolio whose
+/// balances can't all be read is skipped rather than published short.
+pub async fn emit(
+    cfg: &Config,
+    history: &History,
+    last_eth_block: &Arc<Mutex<Option<u64>>>,
+    last_sol_slot: &Arc<Mutex<Option<u64>>>,
+    publisher: &Publisher,
+) -> anyhow::Result<()> {
+    let block = *last_eth_block.lock().await;
+    let slot = *last_sol_slot.lock().await;
+    let now = Utc::now();
+    let mut failed = 0;
+    for portfolio in &cfg.portfolios.portfolios {
+        match read_states(cfg, history, portfolio, block, slot).await {
+            Ok(states) => {
+                let snapshot = snapshot(
+                    &portfolio.name,
+                    &states,
+                    &cfg.asset_map,
+                    &cfg.portfolios.prices,
+                );
+                publisher
+                    .publish(&snapshot_event(snapshot, now), TraceContext::new_root())
+                    .await?;
+            }
+            Err(e) => {
+                warn!("Skipping snapshot of portfolio {}: {:#}", portfolio.name, e);
+                failed += 1;
+            }
+        }
+    }
+    if failed > 0 {
+        bail!(
+            "{} of {} portfolio snapshots failed",
+            failed,
+            cfg.portfolios.portfolios.len()
+        );
+    }
+    Ok(())
+}
+
+async fn read_states<'a>(
+    cfg: &'a Config,
+    history: &History,
+    portfolio: &Portfolio,
+    block: Option<u64>,
+    slot: Option<u64>,
+) -> anyhow::Result<Vec<(&'a str, AddressState)>> {
+    let mut states = Vec::new();
+    for (chain, address) in &portfolio.addresses {
+        let (network, at) = match chain.as_str() {
+            "ethereum" => (cfg.eth_network.as_str(), block),
+            _ => (cfg.sol_network.as_str(), slot),
+        };
+        let at = at.ok_or_else(|| anyhow!("{} has no checkpoint yet", chain))?;
+        states.push((network, history.state(chain, address, at).await?));
+    }
+    Ok(states)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asset_map::AssetMapping;
+    use crate::history::Holding;
+
+    fn holding(token: Option<&str>, symbol: &str, balance: u64, decimals: u8) -> Holding {
+        let amount = Amount::new(balance, decimals);
+        Holding {
+            token: token.map(str::to_string),
+            symbol: symbol.into(),
+            decimals,
+            balance: amount.to_string(),
+            balance_formatted: amount.formatted(),
+        }
+    }
+
+    fn state(chain: &str, at: u64, native: Holding, tokens: Vec<Holding>) -> AddressState {
+        AddressState {
+            chain: chain.into(),
+            address: "0x01".into(),
+            at,
+            native,
+            tokens,
+            as_of_tx: None,
+        }
+    }
+
+    #[test]
+    fn test_portfolios_config_validation() {
+        let config = |name: &str, addresses: &[&str]| PortfolioConfig {
+            name: name.into(),
+            addresses: addresses.iter().map(|a| a.to_string()).collect(),
+        };
+        let ok = Portfolios::new(
+            &[config("treasury", &["ethereum:0x01", "solana:Abc"])],
+            HashMap::from([("ETH".to_string(), 2500.0)]),
+        )
+        .unwrap();
+        assert_eq!(
+            ok.portfolios[0].addresses[1],
+            ("solana".to_string(), "Abc".to_string())
+        );
+
+        let err = Portfolios::new(&[config("t", &["0x01"])], HashMap::new()).unwrap_err();
+        assert!(err.to_string().contains("'0x01'"), "{}", err);
+        let twice = [config("t", &[]), config("t", &[])];
+        assert!(Portfolios::new(&twice, HashMap::new()).is_err());
+        let negative = HashMap::from([("ETH".to_string(), -1.0)]);
+        assert!(Portfolios::new(&[], negative).is_err());
+    }
+
+    #[test]
+    fn test_snapshot_groups_by_asset_and_values_priced_ones() {
+        let usdc_eth = "0xa0b86991c6218b36c1d19d4a2e9eb0ce3606eb48";
+        let asset_map = AssetMap::new(&[AssetMapping {
+            name: "USDC".into(),
+            tokens: vec![format!("mainnet:{}", usdc_eth), "bsc:0x02".into()],
+        }])
+        .unwrap();
+        let prices = HashMap::from([("ETH".to_string(), 2000.0), ("USDC".to_string(), 1.0)]);
+        let states = [
+            (
+                "mainnet",
+                state(
+                    "ethereum",
+                    100,
+                    holding(None, "ETH", 1_500_000_000_000_000_000, 18),
+                    vec![
+                        holding(Some(usdc_eth), "USDC", 2_500_000, 6),
+                        holding(Some("0x03"), "PEPE", 7, 0),
+                    ],
+                ),
+            ),
+            (
+                "bsc",
+                state(
+                    "ethereum",
+                    100,
+                    holding(None, "ETH", 0, 18),
+                    vec![holding(Some("0x02"), "USDC", 500_000_000_000_000_000, 18)],
+                ),
+            ),
+            (
+                "mainnet",
+                state("solana", 7, holding(None, "SOL", 1_000_000_000, 9), vec![]),
+            ),
+        ];
+
+        let snapshot = snapshot("treasury", &states, &asset_map, &prices);
+        assert_eq!(snapshot.block, Some(100));
+        assert_eq!(snapshot.slot, Some(7));
+        assert_eq!(snapshot.total_value, "3003.00");
+        let positions: Vec<_> = snapshot
+            .positions
+            .iter()
+            .map(|p| (p.asset.as_str(), p.balance.as_str(), p.value.as_deref()))
+            .collect();
+        assert_eq!(
+            positions,
+            [
+                ("ETH", "1.5", Some("3000.00")),
+                ("PEPE", "7", None),
+                ("SOL", "1", None),
+                ("USDC", "3", Some("3.00")),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_snapshot_event_shape() {
+        let now = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let event = snapshot_event(
+            Snapshot {
+                name: "treasury".into(),
+                total_value: "0.00".into(),
+                ..Default::default()
+            },
+            now,
+        );
+        let json = serde_json::to_value(&event).unwrap();
+        assert_eq!(json["event_id"], "portfolio:treasury:1700000000");
+        assert_eq!(json["event_type"], "portfolio_snapshot");
+        assert_eq!(
+            json["portfolio"],
+            serde_json::json!({"name": "treasury", "total_value": "0.00", "positions": []})
+        );
+    }
+}
@@ -0,0 +1,356 @@
+//! PostgreSQL destinations (`sink = "postgres"`, build with `--features
+//! postgres`).
+//!
+//! Each event is upserted into `table` keyed by `event_id`, so the database
+//! holds a queryable history without a separate consumer, and a retried or
+//! republished event replaces its row instead of adding one. The columns
+//! most queries filter on are broken out; `token` is kept as JSONB, and
+//! `payload` holds the whole event for everything else. The sink creates the
+//! table and its indexes if they don't exist, on first use:
+//!
+//! ```sql
+//! CREATE TABLE tracker_events (
+//!     event_id TEXT PRIMARY KEY,
+//!     chain TEXT NOT NULL,
+//!     network TEXT NOT NULL,
+//!     event_type TEXT NOT NULL,
+//!     tx_hash TEXT NOT NULL,
+//!     block_time TIMESTAMPTZ,
+//!     from_address TEXT NOT NULL,
+//!     to_address TEXT NOT NULL,
+//!     value NUMERIC,
+//!     slot BIGINT,
+//!     sequence BIGINT,
+//!     token JSONB,
+//!     payload JSONB NOT NULL,
+//!     updated_at TIMESTAMPTZ NOT NULL DEFAULT now()
+//! );
+//! ```
+//!
+//! The default table is `tracker_events`, so the sink doesn't write into the
+//! Go API's `events` table in a shared database. If `table` already exists
+//! without these columns, such as a table of another application, the
+//! sink refuses to use it: the first connection fails with the columns it
+//! lacks, and so does every delivery until `table` is changed.
+//!
+//! A batch (`batch_max_events`) is upserted in one transaction.
+//!
+//! With `retention_days`, the `retention` job deletes the rows whose
+//! `block_time`, or `updated_at` for events without one, is older than that.
+use anyhow::{anyhow, Context};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde_json::Value;
+use sqlx::postgres::{PgPool, PgPoolOptions};
+use tokio::sync::OnceCell;
+use tracing::error;
+
+use crate::config::DestinationConfig;
+use crate::sink::{check_table, EventSink};
+
+pub struct PostgresSink {
+    pool: PgPool,
+    table: String,
+    /// Set once the table exists.
+    ready: OnceCell<()>,
+}
+
+impl PostgresSink {
+    /// The sink of `dest`, a `postgres` destination. Connects on first use,
+    /// so a database that is down at startup delays events instead of
+    /// failing the listener.
+    pub fn open(dest: &DestinationConfig) -> anyhow::Result<Self> {
+        let url = dest.postgres_url.as_deref().ok_or_else(|| {
+            anyhow!(
+                "destination {}: postgres sink needs a postgres_url",
+                dest.name
+            )
+        })?;
+        check_table(&dest.table).map_err(|e| anyhow!("destination {}: {}", dest.name, e))?;
+        let pool = PgPoolOptions::new()
+            .max_connections(dest.workers.max(1) as u32)
+            .connect_lazy(url)
+            .map_err(|e| anyhow!("destination {}: invalid postgres_url: {}", dest.name, e))?;
+        Ok(PostgresSink {
+            pool,
+            table: dest.table.clone(),
+            ready: OnceCell::new(),
+        })
+    }
+
+    async fn create_table(&self) -> anyhow::Result<()> {
+        self.ready
+            .get_or_try_init(|| async {
+                self.check_columns().await?;
+                for statement in create_statements(&self.table) {
+                    sqlx::query(&statement)
+                        .execute(&self.pool)
+                        .await
+                        .with_context(|| format!("failed to create table {}", self.table))?;
+                }
+                anyhow::Ok(())
+            })
+            .await?;
+        Ok(())
+    }
+}
+
+impl PostgresSink {
+    /// Fail if `table` exists without the sink's columns.
+    async fn check_columns(&self) -> anyhow::Result<()> {
+        let (schema, name) = match self.table.split_once('.') {
+            Some((schema, name)) => (Some(schema), name),
+            None => (None, self.table.as_str()),
+        };
+        let found: Vec<String> = sqlx::query_scalar(
+            "SELECT column_name::text FROM information_schema.columns \
+             WHERE table_schema = COALESCE($1, current_schema()) AND table_name = $2",
+        )
+        .bind(schema)
+        .bind(name)
+        .fetch_all(&self.pool)
+        .await
+        .with_context(|| format!("failed to read the columns of {}", self.table))?;
+        match missing_columns(&found) {
+            missing if found.is_empty() || missing.is_empty() => Ok(()),
+            missing => {
+                let e = anyhow!(
+                    "table {} exists but lacks the columns {}; it isn't a table of this sink, \
+                     set the destination's table to another name",
+                    self.table,
+                    missing.join(", ")
+                );
+                error!("{}", e);
+                Err(e)
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl EventSink for PostgresSink {
+    async fn publish(&self, message: &[u8]) -> anyhow::Result<()> {
+        let rows = message
+            .split(|b| *b == b'\n')
+            .filter(|line| !line.is_empty())
+            .map(Row::parse)
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        self.create_table().await?;
+        let upsert = upsert_statement(&self.table);
+        let mut tx = self.pool.begin().await?;
+        for row in rows {
+            sqlx::query(&upsert)
+                .bind(row.event_id)
+                .bind(row.chain)
+                .bind(row.network)
+                .bind(row.event_type)
+                .bind(row.tx_hash)
+                .bind(row.block_time)
+                .bind(row.from)
+                .bind(row.to)
+                .bind(row.value)
+                .bind(row.slot)
+                .bind(row.sequence)
+                .bind(row.token)
+                .bind(row.payload)
+                .execute(&mut *tx)
+                .await
+                .with_context(|| format!("failed to upsert into {}", self.table))?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn expire(&self, cutoff: DateTime<Utc>) -> anyhow::Result<u64> {
+        self.create_table().await?;
+        let deleted = sqlx::query(&delete_statement(&self.table))
+            .bind(cutoff.timestamp() as f64)
+            .execute(&self.pool)
+            .await
+            .with_context(|| format!("failed to expire rows of {}", self.table))?;
+        Ok(deleted.rows_affected())
+    }
+
+    fn describe(&self) -> String {
+        format!("postgres table {}", self.table)
+    }
+}
+
+/// Columns the sink writes.
+const COLUMNS: &[&str] = &[
+    "event_id",
+    "chain",
+    "network",
+    "event_type",
+    "tx_hash",
+    "block_time",
+    "from_address",
+    "to_address",
+    "value",
+    "slot",
+    "sequence",
+    "token",
+    "payload",
+    "updated_at",
+];
+
+/// The sink's columns not among `found`.
+fn missing_columns(found: &[String]) -> Vec<&'static str> {
+    COLUMNS
+        .iter()
+        .copied()
+        .filter(|column| !found.iter().any(|f| f == column))
+        .collect()
+}
+
+fn create_statements(table: &str) -> Vec<String> {
+    let index = table.replace('.', "_");
+    vec![
+        format!(
+            "CREATE TABLE IF NOT EXISTS {table} (
+                event_id TEXT PRIMARY KEY,
+                chain TEXT NOT NULL,
+                network TEXT NOT NULL,
+                event_type TEXT NOT NULL,
+                tx_hash TEXT NOT NULL,
+                block_time TIMESTAMPTZ,
+                from_address TEXT NOT NULL,
+                to_address TEXT NOT NULL,
+                value NUMERIC,
+                slot BIGINT,
+                sequence BIGINT,
+                token JSONB,
+                payload JSONB NOT NULL,
+                updated_at TIMESTAMPTZ NOT NULL DEFAULT now()
+            )"
+        ),
+        format!("CREATE INDEX IF NOT EXISTS {index}_tx_hash ON {table} (tx_hash)"),
+        format!(
+            "CREATE INDEX IF NOT EXISTS {index}_chain_time ON {table} (chain, network, block_time)"
+        ),
+    ]
+}
+
+fn upsert_statement(table: &str) -> String {
+    format!(
+        "INSERT INTO {table} (event_id, chain, network, event_type, tx_hash, block_time, \
+         from_address, to_address, value, slot, sequence, token, payload) \
+         VALUES ($1, $2, $3, $4, $5, to_timestamp($6), $7, $8, $9::numeric, $10, $11, $12, $13) \
+         ON CONFLICT (event_id) DO UPDATE SET chain = EXCLUDED.chain, \
+         network = EXCLUDED.network, event_type = EXCLUDED.event_type, \
+         tx_hash = EXCLUDED.tx_hash, block_time = EXCLUDED.block_time, \
+         from_address = EXCLUDED.from_address, to_address = EXCLUDED.to_address, \
+         value = EXCLUDED.value, slot = EXCLUDED.slot, sequence = EXCLUDED.sequence, \
+         token = EXCLUDED.token, payload = EXCLUDED.payload, updated_at = now()"
+    )
+}
+
+fn delete_statement(table: &str) -> String {
+    format!("DELETE FROM {table} WHERE COALESCE(block_time, updated_at) < to_timestamp($1)")
+}
+
+/// The columns of one event payload.
+#[derive(Debug, PartialEq)]
+struct Row {
+    event_id: String,
+    chain: String,
+    network: String,
+    event_type: String,
+    tx_hash: String,
+    /// Unix seconds; `None` for events without a timestamp.
+    block_time: Option<f64>,
+    from: String,
+    to: String,
+    /// Raw integer amount, cast to NUMERIC; `None` if `value` isn't one.
+    value: Option<String>,
+    slot: Option<i64>,
+    sequence: Option<i64>,
+    token: Option<Value>,
+    payload: Value,
+}
+
+impl Row {
+    fn parse(line: &[u8]) -> anyhow::Result<Self> {
+        let payload: Value =
+            serde_json::from_slice(line).context("postgres sink needs JSON event payloads")?;
+        let text = |key: &str| {
+            payload
+                .get(key)
+                .and_then(Value::as_str)
+                .unwrap_or_default()
+                .to_string()
+        };
+        let event_id = text("event_id");
+        if event_id.is_empty() {
+            return Err(anyhow!("event payload without event_id"));
+        }
+        let value = text("value");
+        Ok(Row {
+            event_id,
+            chain: text("chain"),
+            network: text("network"),
+            event_type: text("event_type"),
+            tx_hash: text("tx_hash"),
+            block_time: text("timestamp").parse::<i64>().ok().map(|t| t as f64),
+            from: text("from"),
+            to: text("to"),
+            value: (!value.is_empty() && value.bytes().all(|b| b.is_ascii_digit()))
+                .then_some(value),
+            slot: payload.get("slot").and_then(Value::as_i64),
+            sequence: payload.get("sequence").and_then(Value::as_i64),
+            token: payload.get("token").cloned(),
+            payload,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_row_from_payload() {
+        let line = br#"{"event_id":"eth:0xabc:3","chain":"ethereum","network":"mainnet",
+            "sequence":7,"tx_hash":"0xabc","timestamp":"1700000000","from":"0x1","to":"0x2",
+            "value":"1500000","event_type":"erc20_transfer",
+            "token":{"address":"0xusdc","symbol":"USDC","decimals":6}}"#;
+        let row = Row::parse(line).unwrap();
+        assert_eq!(row.event_id, "eth:0xabc:3");
+        assert_eq!(row.event_type, "erc20_transfer");
+        assert_eq!(row.block_time, Some(1_700_000_000.0));
+        assert_eq!(row.value.as_deref(), Some("1500000"));
+        assert_eq!(row.slot, None);
+        assert_eq!(row.sequence, Some(7));
+        assert_eq!(row.token.unwrap()["symbol"], "USDC");
+        assert_eq!(row.payload["from"], "0x1");
+    }
+
+    #[test]
+    fn test_row_tolerates_missing_fields() {
+        let row = Row::parse(br#"{"event_id":"heartbeat:1","value":"","timestamp":""}"#).unwrap();
+        assert_eq!(row.block_time, None);
+        assert_eq!(row.value, None);
+        assert_eq!(row.token, None);
+        assert!(Row::parse(br#"{"chain":"ethereum"}"#).is_err());
+        assert!(Row::parse(b"\x28\xb5\x2f\xfd").is_err());
+    }
+
+    #[test]
+    fn test_missing_columns() {
+        let ours: Vec<String> = COLUMNS.iter().map(|c| c.to_string()).collect();
+        assert!(missing_columns(&ours).is_empty());
+        // The Go API's events table.
+        let theirs: Vec<String> = ["seq", "event_id", "chain", "tx_hash", "payload"]
+            .iter()
+            .map(|c| c.to_string())
+            .collect();
+        let missing = missing_columns(&theirs);
+        assert!(missing.contains(&"from_address"));
+        assert!(!missing.contains(&"event_id"));
+    }
+
+    #[test]
+    fn test_index_names() {
+        assert!(create_statements("tracker.events")[1].contains("tracker_events_tx_hash"));
+    }
+}
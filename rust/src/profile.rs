@@ -0,0 +1,158 @@
+//! Configuration profiles (`--profile`, `TRACKER_PROFILE`).
+//!
+//! A profile is a TOML file of environment variables:
+//!
+//! ```toml
+//! ETH_RPC_URL = "http://localhost:8545"
+//! POLL_INTERVAL_SECS = 2
+//! WATCHED_ADDRESSES_ETH = ["0x...", "0x..."]   # joined with commas
+//! ```
+//!
+//! `--profile dev`, `staging` or `prod` selects one of the profiles in
+//! `rust/profiles/`, built into the binary; anything else is read as a path.
+//! A profile only fills in variables nothing else sets, so the layers are,
+//! from lowest to highest: built-in defaults, the profile, `.env`, and the
+//! environment. Since a profile sets variables, its values also override
+//! those of `CONFIG_FILE`, which a profile can name too.
+use std::collections::BTreeMap;
+
+use anyhow::{bail, Context};
+use dotenvy::dotenv;
+
+/// The profiles built into the binary.
+pub const BUILTIN: [(&str, &str); 3] = [
+    ("dev", include_str!("../profiles/dev.toml")),
+    ("staging", include_str!("../profiles/staging.toml")),
+    ("prod", include_str!("../profiles/prod.toml")),
+];
+
+/// Variables of the built-in profile `name`, or of the file at that path.
+pub fn load(name: &str) -> anyhow::Result<Vec<(String, String)>> {
+    match BUILTIN.iter().find(|(builtin, _)| *builtin == name) {
+        Some((_, raw)) => parse(raw).with_context(|| format!("invalid built-in profile {}", name)),
+        None => {
+            let raw = std::fs::read_to_string(name).with_context(|| {
+                format!(
+                    "no profile {} (expected dev, staging, prod or a file)",
+                    name
+                )
+            })?;
+            parse(&raw).with_context(|| format!("invalid profile {}", name))
+        }
+    }
+}
+
+/// A profile's variables, with lists joined by commas.
+fn parse(raw: &str) -> anyhow::Result<Vec<(String, String)>> {
+    let table: BTreeMap<String, toml::Value> = toml::from_str(raw)?;
+    table
+        .into_iter()
+        .map(|(name, value)| {
+            if name.is_empty()
+                || !name
+                    .chars()
+                    .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '_')
+            {
+                bail!("{} is not an environment variable name", name);
+            }
+            let value = match value {
+                toml::Value::Array(items) => items
+                    .into_iter()
+                    .map(|item| scalar(&name, item))
+                    .collect::<anyhow::Result<Vec<_>>>()?
+                    .join(","),
+                value => scalar(&name, value)?,
+            };
+            Ok((name, value))
+        })
+        .collect()
+}
+
+fn scalar(name: &str, value: toml::Value) -> anyhow::Result<String> {
+    match value {
+        toml::Value::String(s) => Ok(s),
+        toml::Value::Integer(i) => Ok(i.to_string()),
+        toml::Value::Float(f) => Ok(f.to_string()),
+        toml::Value::Boolean(b) => Ok(b.to_string()),
+        other => bail!(
+            "{} must be a string, number, boolean or list, got {}",
+            name,
+            other
+        ),
+    }
+}
+
+/// Set the variables of profile `name` that neither the environment nor
+/// `.env` sets. Returns how many it set.
+pub fn apply(name: &str) -> anyhow::Result<usize> {
+    let vars = load(name)?;
+    // Loaded first so that its values rank above the profile's.
+    dotenv().ok();
+    let mut set = 0;
+    for (var, value) in vars {
+        if std::env::var_os(&var).is_none() {
+            std::env::set_var(&var, value);
+            set += 1;
+        }
+    }
+    Ok(set)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serial_test::serial;
+
+    #[test]
+    fn test_parse_values() {
+        let vars = parse(
+            r#"
+            ETH_RPC_URL = "http://localhost:8545"
+            POLL_INTERVAL_SECS = 2
+            TOKEN_WARMUP = true
+            WATCHED_ADDRESSES_ETH = ["0xa", "0xb"]
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            vars,
+            [
+                (
+                    "ETH_RPC_URL".to_string(),
+                    "http://localhost:8545".to_string()
+                ),
+                ("POLL_INTERVAL_SECS".to_string(), "2".to_string()),
+                ("TOKEN_WARMUP".to_string(), "true".to_string()),
+                ("WATCHED_ADDRESSES_ETH".to_string(), "0xa,0xb".to_string()),
+            ]
+        );
+        assert!(parse("eth_rpc_url = \"x\"").is_err());
+        assert!(parse("[ethereum]\nrpc_url = \"x\"").is_err());
+    }
+
+    #[test]
+    fn test_builtin_profiles_parse() {
+        for (name, _) in BUILTIN {
+            let vars = load(name).unwrap();
+            assert!(vars.iter().any(|(var, _)| var == "ETH_NETWORK"), "{}", name);
+        }
+        assert!(load("qa").is_err());
+    }
+
+    #[test]
+    #[serial]
+    fn test_environment_overrides_profile() {
+        std::env::set_var("POLL_INTERVAL_SECS", "7");
+        std::env::remove_var("SOL_NETWORK");
+        let res = apply("dev");
+        let poll = std::env::var("POLL_INTERVAL_SECS");
+        let network = std::env::var("SOL_NETWORK");
+        for (var, _) in load("dev").unwrap() {
+            std::env::remove_var(var);
+        }
+
+        assert!(res.unwrap() > 0);
+        assert_eq!(poll.unwrap(), "7");
+        assert_eq!(network.unwrap(), "localnet");
+    }
+}
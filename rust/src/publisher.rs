@@ -0,0 +1,1443 @@
+//! Fan-out of normalized events to every configured destination.
+//!
+//! Each destination owns one or more bounded queues, each drained by a worker
+//! task with the destination's retry policy, so a slow or unreachable
+//! destination only delays its own queues. With `ordering = "per_address"`
+//! events are sharded by watched address, which keeps delivery in publish
+//! order per wallet while still spreading different wallets across workers.
+//! Quarantining a delivery breaks that order: the wallet's later events go
+//! out without it, and a requeue delivers it after them. Each quarantined
+//! delivery counts how many of its wallet's events overtook it.
+//! An event counts as published once every matching destination has accepted
+//! it into a queue; a delivery that fails every retry is quarantined for its
+//! destination, and so is an event that keeps failing to serialize (see
+//! [`crate::quarantine`]).
+//!
+//! Each published event gets a W3C `traceparent` (see
+//! [`crate::trace_context`]) naming its `publish` span, a child of the trace
+//! the caller started for the block or transaction it came from; the span
+//! follows the event into the delivery workers so retries and failures are
+//! logged with the same trace id consumers see.
+//!
+//! On shutdown, `drain` waits for the queues to empty, so events already
+//! counted as published reach their destinations before the process exits.
+use anyhow::anyhow;
+use serde::Serialize;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use tokio::sync::mpsc::{self, error::TrySendError};
+use tokio::time::{sleep, timeout_at, Duration, Instant};
+use tracing::{debug, error, info, info_span, warn, Instrument, Span};
+
+use crate::address_book::AddressBook;
+use crate::assets::Assets;
+use crate::bus::{BusEvent, EventBus};
+use crate::config::{Compression, Config, DestinationConfig, OrderingMode, Overflow};
+use crate::dedup_store::{self, DedupStore};
+use crate::event_type::EventType;
+use crate::expectations::Expectations;
+use crate::explorer::Explorers;
+use crate::fee_spike::FeeGuard;
+use crate::finality::Finality;
+use crate::heartbeat::ActivityLog;
+use crate::kill_switch::KillSwitch;
+use crate::liquid_staking;
+use crate::quarantine::{Quarantine, QuarantinedEvent, Retry, Stage};
+use crate::retry::retry_with_backoff;
+use crate::sequence::Sequences;
+use crate::sink::{self, EventSink, PartitionKey, SinkKind};
+use crate::sweeps::SweepDetector;
+use crate::trace_context::TraceContext;
+use crate::webhooks::Webhooks;
+use crate::Event;
+
+/// Top-level keys of the published payload, for `fields` projection.
+pub const PAYLOAD_FIELDS: &[&str] = &[
+    "event_id",
+    "chain",
+    "network",
+    "sequence",
+    "region",
+    "instance_id",
+    "tx_hash",
+    "timestamp",
+    "from",
+    "to",
+    "value",
+    "asset_symbol",
+    "asset_decimals",
+    "value_formatted",
+    "logical_asset",
+    "event_type",
+    "slot",
+    "block_number",
+    "log_index",
+    "token",
+    "memo",
+    "selector",
+    "method",
+    "spam",
+    "spam_reasons",
+    "decode_warnings",
+    "token_behavior",
+    "received_value",
+    "gap",
+    "heartbeat",
+    "expectation",
+    "portfolio",
+    "alert",
+    "sweep",
+    "validator",
+    "liquid_staking",
+    "lending",
+    "simulation",
+    "stuck",
+    "fee_spike",
+    "substrate",
+    "backfill",
+    "explorer_url",
+    "traceparent",
+];
+
+/// Which events a destination receives. Empty lists match everything.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct EventFilter {
+    pub chains: Vec<String>,
+    pub event_types: Vec<EventType>,
+}
+
+impl EventFilter {
+    /// Whether the filter lets every event through.
+    pub fn is_empty(&self) -> bool {
+        self.chains.is_empty() && self.event_types.is_empty()
+    }
+
+    pub fn matches(&self, event: &Event) -> bool {
+        (self.chains.is_empty() || self.chains.iter().any(|c| *c == event.chain))
+            && (self.event_types.is_empty()
+                || self.event_types.iter().any(|t| *t == event.event_type))
+    }
+}
+
+pub(crate) struct Delivery {
+    pub(crate) event_id: String,
+    /// Message key, for sinks with keys.
+    pub(crate) key: String,
+    /// The watched address (or event id) the delivery is ordered by, with
+    /// `ordering = "per_address"`.
+    pub(crate) order_key: Option<String>,
+    /// Subject, channel or topic the destination's template routes the event
+    /// to; `None` for a fixed one.
+    pub(crate) route: Option<String>,
+    pub(crate) payload: String,
+    /// The event's `publish` span, carrying its trace context.
+    pub(crate) span: Span,
+    /// Attempts spent before a requeue from the quarantine.
+    pub(crate) attempts: u64,
+    /// Counts the delivery in its destination's `pending` until dropped.
+    pub(crate) _pending: PendingGuard,
+}
+
+/// Deliveries of a destination from enqueueing until its worker is done with
+/// them, delivered or given up on.
+#[derive(Debug, Clone, Default)]
+struct Pending(Arc<AtomicUsize>);
+
+impl Pending {
+    fn add(&self) -> PendingGuard {
+        self.0.fetch_add(1, Ordering::SeqCst);
+        PendingGuard(Arc::clone(&self.0))
+    }
+
+    fn get(&self) -> usize {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+pub(crate) struct PendingGuard(Arc<AtomicUsize>);
+
+impl Drop for PendingGuard {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// How often `drain` checks the queues.
+const DRAIN_POLL: Duration = Duration::from_millis(50);
+
+/// Wire format: the event's fields followed by its `traceparent`.
+#[derive(Serialize)]
+struct Envelope<'a> {
+    #[serde(flatten)]
+    event: &'a Event,
+    traceparent: String,
+}
+
+struct Destination {
+    name: String,
+    filter: EventFilter,
+    /// Payload projection; empty sends the full event.
+    fields: Vec<String>,
+    overflow: Overflow,
+    ordering: OrderingMode,
+    partition_key: PartitionKey,
+    /// Routing template: the subject of a NATS destination, or a channel or
+    /// topic with placeholders.
+    route: Option<String>,
+    shards: Vec<mpsc::Sender<Delivery>>,
+    next_shard: AtomicUsize,
+    pending: Pending,
+    /// `retention_days` and the sink of a destination whose stored events
+    /// expire.
+    retention: Option<(u64, Arc<dyn EventSink>)>,
+    /// The sink of a destination writing local files (`file`, or `stream`
+    /// with an `archive`), for the `archive_flush` job.
+    archive: Option<Arc<dyn EventSink>>,
+}
+
+impl Destination {
+    /// What an event's deliveries are kept in order by: its watched
+    /// address, or its event id if it has none. `None` when unordered.
+    fn order_key<'a>(&self, event: &'a Event) -> Option<&'a str> {
+        match self.ordering {
+            OrderingMode::PerAddress => {
+                Some(event.watched_address.as_deref().unwrap_or(&event.event_id))
+            }
+            OrderingMode::Unordered => None,
+        }
+    }
+
+    /// Pick the worker queue for an event according to the ordering mode.
+    fn shard(&self, event: &Event) -> &mpsc::Sender<Delivery> {
+        let idx = match self.order_key(event) {
+            Some(key) => {
+                let mut hasher = DefaultHasher::new();
+                key.hash(&mut hasher);
+                (hasher.finish() % self.shards.len() as u64) as usize
+            }
+            None => self.next_shard.fetch_add(1, Ordering::Relaxed),
+        };
+        &self.shards[idx % self.shards.len()]
+    }
+}
+
+/// Cheaply cloneable handle used by the trackers to publish events.
+#[derive(Clone)]
+pub struct Publisher {
+    destinations: Arc<Vec<Destination>>,
+    /// Durable claims on event ids, unless `DEDUP_STORE=memory`.
+    dedup_store: Option<Arc<dyn DedupStore>>,
+    /// Numbers each chain's published events.
+    sequences: Sequences,
+    /// `REGION` and `INSTANCE_ID`, tagged on every published event.
+    region: Option<String>,
+    instance_id: Option<String>,
+    /// Last activity per watched address, for heartbeats.
+    activity: ActivityLog,
+    /// Solana events to check at finalized commitment.
+    finality: Finality,
+    /// Pending expected transfers, resolved as matching events are published.
+    expectations: Expectations,
+    /// Cold-storage addresses whose outgoing transactions raise alerts.
+    address_book: AddressBook,
+    /// Groups outgoing transfers into `sweep_detected` events.
+    sweeps: SweepDetector,
+    /// Raises `fee_spike` alerts for transactions that overpaid for gas.
+    fee_guard: FeeGuard,
+    /// Holds events while an operator has the kill switch on.
+    kill_switch: KillSwitch,
+    /// Builds the events' `explorer_url` links.
+    explorers: Explorers,
+    /// Native currency per chain, for the asset fields.
+    assets: Assets,
+    /// Runtime webhook subscriptions, fed from the bus.
+    webhooks: Webhooks,
+    /// Every published event, for in-process consumers.
+    bus: EventBus,
+    /// Events that failed every attempt, to inspect and requeue.
+    quarantine: Quarantine,
+}
+
+impl Publisher {
+    /// Open the sink of each destination and spawn its delivery workers.
+    pub fn new(cfg: &Config) -> anyhow::Result<Self> {
+        let quarantine = Quarantine::new(cfg.quarantine.clone())?;
+        let mut destinations = Vec::with_capacity(cfg.destinations.len());
+        for dest in &cfg.destinations {
+            let sink = sink::open(dest, &cfg.redis_url)?;
+            let mut shards = Vec::with_capacity(dest.workers.max(1));
+            for shard in 0..dest.workers.max(1) {
+                let (tx, rx) = mpsc::channel(dest.queue_size.max(1));
+                let worker = Worker {
+                    dest: dest.clone(),
+                    shard,
+                    sink: Arc::clone(&sink),
+                    quarantine: quarantine.clone(),
+                };
+                if dest.batch_max_events > 0 {
+                    tokio::spawn(run_batch_destination(worker, rx));
+                } else {
+                    tokio::spawn(run_destination(worker, rx));
+                }
+                shards.push(tx);
+            }
+            info!(
+                "Publishing to destination {} ({}, {} workers, {:?})",
+                dest.name,
+                sink.describe(),
+                shards.len(),
+                dest.ordering
+            );
+            destinations.push(Destination {
+                name: dest.name.clone(),
+                filter: EventFilter {
+                    chains: dest.chains.clone(),
+                    event_types: dest.event_types.clone(),
+                },
+                fields: dest.fields.clone(),
+                overflow: dest.overflow,
+                ordering: dest.ordering,
+                partition_key: dest.partition_key,
+                route: route_template(dest),
+                shards,
+                next_shard: AtomicUsize::new(0),
+                pending: Pending::default(),
+                archive: (dest.sink == SinkKind::File
+                    || (dest.sink == SinkKind::Stream && dest.archive.is_some()))
+                .then(|| Arc::clone(&sink)),
+                retention: (dest.retention_days > 0).then(|| (dest.retention_days, sink)),
+            });
+        }
+        let dedup_store = dedup_store::open(cfg)?;
+        let bus = EventBus::default();
+        Ok(Publisher {
+            destinations: Arc::new(destinations),
+            dedup_store,
+            sequences: Sequences::default(),
+            region: cfg.region.clone(),
+            instance_id: Some(cfg.instance_id.clone()),
+            activity: ActivityLog::default(),
+            finality: Finality::default(),
+            expectations: Expectations::new(cfg),
+            address_book: cfg.address_book.clone(),
+            sweeps: SweepDetector::new(cfg),
+            fee_guard: FeeGuard::new(cfg),
+            kill_switch: KillSwitch::new(
+                cfg.kill_switch.clone(),
+                redis::Client::open(cfg.redis_url.as_str())?,
+            ),
+            explorers: Explorers::new(cfg),
+            assets: Assets::new(cfg),
+            webhooks: Webhooks::start(cfg, bus.subscribe("webhooks"))?,
+            bus,
+            quarantine,
+        })
+    }
+
+    /// A single unfiltered destination whose queue is handed back to the
+    /// caller instead of a Redis worker. Used by replay to collect events.
+    pub(crate) fn in_memory() -> (Self, mpsc::Receiver<Delivery>) {
+        let (tx, rx) = mpsc::channel(1024);
+        let dest = Destination {
+            name: "in_memory".into(),
+            filter: EventFilter::default(),
+            fields: Vec::new(),
+            overflow: Overflow::Block,
+            ordering: OrderingMode::Unordered,
+            partition_key: PartitionKey::default(),
+            route: None,
+            shards: vec![tx],
+            next_shard: AtomicUsize::new(0),
+            pending: Pending::default(),
+            retention: None,
+            archive: None,
+        };
+        (
+            Publisher {
+                destinations: Arc::new(vec![dest]),
+                dedup_store: None,
+                sequences: Sequences::default(),
+                region: None,
+                instance_id: None,
+                activity: ActivityLog::default(),
+                finality: Finality::default(),
+                expectations: Expectations::default(),
+                address_book: AddressBook::default(),
+                sweeps: SweepDetector::default(),
+                fee_guard: FeeGuard::default(),
+                kill_switch: KillSwitch::default(),
+                explorers: Explorers::default(),
+                assets: Assets::default(),
+                webhooks: Webhooks::default(),
+                bus: EventBus::default(),
+                quarantine: Quarantine::default(),
+            },
+            rx,
+        )
+    }
+
+    /// A publisher over `destinations`, otherwise like
+    /// [`Publisher::in_memory`].
+    #[cfg(test)]
+    pub(crate) fn with_destinations(destinations: Vec<Destination>) -> Self {
+        let (mut publisher, _) = Publisher::in_memory();
+        publisher.destinations = Arc::new(destinations);
+        publisher
+    }
+
+    /// Queue an event for every destination whose filter matches, followed
+    /// by an `unexpected_activity` alert if it is an outgoing transaction of
+    /// a cold-storage address, a `fee_spike` alert if it paid too much over
+    /// the base fee, a `sweep_detected` event if it completes a
+    /// sweep, an `lst_mint` or `lst_redeem` event if it is a stETH mint or
+    /// redemption, and an `expected_transfer_received` event if it resolves
+    /// an expectation. A `backfill` event is queued alone.
+    ///
+    /// Returns an error only if a destination's worker has stopped; a full
+    /// queue either waits (`overflow = "block"`) or drops the event for that
+    /// destination (`overflow = "drop"`). With a dedup store, an event already
+    /// claimed there, by another replica or before a restart, is skipped and
+    /// reported as published.
+    ///
+    /// `trace` is the context of the block or transaction the event comes
+    /// from; every event published here gets a child span of it.
+    pub async fn publish(&self, event: &Event, trace: TraceContext) -> anyhow::Result<()> {
+        self.publish_one(event, trace).await?;
+        if event.backfill {
+            // Derived events describe live activity, not history.
+            return Ok(());
+        }
+        if let Some(alert) = self.address_book.unexpected_activity(event) {
+            warn!(
+                "Outgoing transaction {} from cold-storage address {}",
+                event.tx_hash,
+                alert.alert.as_ref().map_or("", |a| a.address.as_str())
+            );
+            self.publish_one(&alert, trace).await?;
+        }
+        if let Some(spike) = self.fee_guard.check(event, &self.address_book) {
+            warn!(
+                "Transaction {} from {} paid {} wei over the base fee",
+                event.tx_hash,
+                event.from,
+                spike
+                    .fee_spike
+                    .as_ref()
+                    .map_or("", |f| f.overpayment.as_str())
+            );
+            self.publish_one(&spike, trace).await?;
+        }
+        if let Some(sweep) = self.sweeps.observe(event) {
+            info!(
+                "Sweep of {} transfers to {}",
+                sweep.sweep.as_ref().map_or(0, |s| s.tx_hashes.len()),
+                sweep.to
+            );
+            self.publish_one(&sweep, trace).await?;
+        }
+        if let Some(flow) = liquid_staking::ethereum_flow(event) {
+            self.publish_one(&flow, trace).await?;
+        }
+        if let Some(mut received) = self.expectations.resolve(event) {
+            if let Err(e) = self.publish_one(&received, trace).await {
+                if let Some(expectation) = received.expectation.take() {
+                    self.expectations.restore(expectation);
+                }
+                return Err(e);
+            }
+        }
+        Ok(())
+    }
+
+    async fn publish_one(&self, event: &Event, trace: TraceContext) -> anyhow::Result<()> {
+        if self.kill_switch.hold(event) {
+            return Ok(());
+        }
+        self.deliver(event, trace).await
+    }
+
+    /// Watch a per-chain kill switch key for each of `chains`.
+    pub fn watch_kill_switch_chains<'a>(&self, chains: impl IntoIterator<Item = &'a str>) {
+        self.kill_switch.watch_chains(chains);
+    }
+
+    /// Read the kill switch keys, then publish the events a released switch
+    /// was holding. Run by the `kill_switch` job; an event that fails to
+    /// publish goes back to the front of the buffer for the next run.
+    pub async fn refresh_kill_switch(&self) -> anyhow::Result<()> {
+        let Some(wanted) = self.kill_switch.read().await? else {
+            return Ok(());
+        };
+        while let Some(event) = self.kill_switch.apply(&wanted) {
+            if let Err(e) = self.deliver(&event, TraceContext::new_root()).await {
+                self.kill_switch.requeue(event);
+                return Err(e);
+            }
+        }
+        Ok(())
+    }
+
+    async fn deliver(&self, event: &Event, parent: TraceContext) -> anyhow::Result<()> {
+        let trace = parent.child();
+        let span = info_span!(
+            "publish",
+            event_id = %event.event_id,
+            trace_id = %trace.trace_id_hex(),
+            span_id = %trace.span_id_hex(),
+            parent_span_id = %parent.span_id_hex(),
+        );
+        self.activity.record(event);
+        async {
+            if !self.claim(event).await {
+                return Ok(());
+            }
+            let mut event = event.clone();
+            self.sequences.assign(&mut event);
+            let res = self.enqueue(&event, trace, span.clone()).await;
+            match &res {
+                Ok(()) => self.finality.record(&event),
+                Err(_) => {
+                    self.sequences.release(&event);
+                    self.release(&event).await
+                }
+            }
+            res
+        }
+        .instrument(span.clone())
+        .await
+    }
+
+    /// Last activity seen per watched address.
+    pub fn activity(&self) -> &ActivityLog {
+        &self.activity
+    }
+
+    /// Solana events awaiting finality.
+    pub fn finality(&self) -> &Finality {
+        &self.finality
+    }
+
+    /// Per-chain sequence numbers, saved with the checkpoints.
+    pub fn sequences(&self) -> &Sequences {
+        &self.sequences
+    }
+
+    /// Pending expected transfers, shared with the admin API.
+    pub fn expectations(&self) -> &Expectations {
+        &self.expectations
+    }
+
+    /// Webhook subscriptions, shared with the admin API.
+    pub fn webhooks(&self) -> &Webhooks {
+        &self.webhooks
+    }
+
+    /// Bus of published events, for in-process consumers.
+    pub fn bus(&self) -> &EventBus {
+        &self.bus
+    }
+
+    /// Destinations whose stored events expire, with their `retention_days`
+    /// and sink, for the `retention` job.
+    pub fn retained(&self) -> Vec<(String, u64, Arc<dyn EventSink>)> {
+        self.destinations
+            .iter()
+            .filter_map(|dest| {
+                let (days, sink) = dest.retention.as_ref()?;
+                Some((dest.name.clone(), *days, Arc::clone(sink)))
+            })
+            .collect()
+    }
+
+    /// Destinations writing local files, with their sink, for the
+    /// `archive_flush` job.
+    pub fn archives(&self) -> Vec<(String, Arc<dyn EventSink>)> {
+        self.destinations
+            .iter()
+            .filter_map(|dest| Some((dest.name.clone(), Arc::clone(dest.archive.as_ref()?))))
+            .collect()
+    }
+
+    /// Quarantined events, shared with the admin API.
+    pub fn quarantine(&self) -> &Quarantine {
+        &self.quarantine
+    }
+
+    /// Send a quarantined event through the stage it failed again. Returns
+    /// `false` if there is no such event; on an error it stays quarantined.
+    pub async fn requeue(&self, id: u64) -> anyhow::Result<bool> {
+        let Some(quarantined) = self.quarantine.take(id) else {
+            return Ok(false);
+        };
+        let res = match &quarantined.retry {
+            Retry::Publish(event) => {
+                // The failed publish kept its claim in the dedup store.
+                self.release(event).await;
+                self.publish_one(event, TraceContext::new_root()).await
+            }
+            Retry::Deliver {
+                shard,
+                key,
+                order_key,
+                route,
+            } => {
+                let (key, order_key) = (key.clone(), order_key.clone());
+                self.redeliver(&quarantined, *shard, key, order_key, route.clone())
+                    .await
+            }
+            Retry::Unsaved => Err(anyhow!(
+                "event {} failed to serialize before a restart and wasn't saved",
+                quarantined.event_id
+            )),
+        };
+        match res {
+            Ok(()) => {
+                info!(
+                    "Requeued quarantined event {} (id {})",
+                    quarantined.event_id, id
+                );
+                Ok(true)
+            }
+            Err(e) => {
+                self.quarantine.restore(quarantined);
+                Err(e)
+            }
+        }
+    }
+
+    async fn redeliver(
+        &self,
+        quarantined: &QuarantinedEvent,
+        shard: usize,
+        key: String,
+        order_key: Option<String>,
+        route: Option<String>,
+    ) -> anyhow::Result<()> {
+        let name = quarantined.destination.as_deref().unwrap_or_default();
+        let dest = self
+            .destinations
+            .iter()
+            .find(|d| d.name == name)
+            .ok_or_else(|| anyhow!("destination {} no longer exists", name))?;
+        let delivery = Delivery {
+            event_id: quarantined.event_id.clone(),
+            key,
+            order_key,
+            route,
+            payload: quarantined.payload.clone(),
+            span: info_span!("requeue", event_id = %quarantined.event_id),
+            attempts: quarantined.attempts,
+            _pending: dest.pending.add(),
+        };
+        dest.shards[shard % dest.shards.len()]
+            .try_send(delivery)
+            .map_err(|e| anyhow!("destination {} queue: {}", dest.name, e))
+    }
+
+    /// Wait until the destination workers are done with every queued event,
+    /// or until `deadline`. Returns how many events were still pending, per
+    /// destination name, if any were.
+    pub async fn drain(&self, deadline: Instant) -> Vec<(String, usize)> {
+        loop {
+            let pending: Vec<(String, usize)> = self
+                .destinations
+                .iter()
+                .map(|dest| (dest.name.clone(), dest.pending.get()))
+                .filter(|(_, pending)| *pending > 0)
+                .collect();
+            if pending.is_empty() || Instant::now() >= deadline {
+                return pending;
+            }
+            sleep(DRAIN_POLL).await;
+        }
+    }
+
+    /// Events the kill switch is holding, which are lost on shutdown.
+    pub fn held(&self) -> usize {
+        self.kill_switch.buffered()
+    }
+
+    /// Events waiting in the destination queues.
+    pub fn queued(&self) -> usize {
+        self.destinations
+            .iter()
+            .flat_map(|dest| &dest.shards)
+            .map(|tx| tx.max_capacity() - tx.capacity())
+            .sum()
+    }
+
+    /// Whether transactions of watched senders are checked for fee spikes,
+    /// so the trackers fetch what they paid.
+    pub fn checks_fees(&self) -> bool {
+        self.fee_guard.enabled()
+    }
+
+    #[cfg(test)]
+    pub(crate) fn with_expectations(mut self, expectations: Expectations) -> Self {
+        self.expectations = expectations;
+        self
+    }
+
+    #[cfg(test)]
+    pub(crate) fn with_sweeps(mut self, sweeps: SweepDetector) -> Self {
+        self.sweeps = sweeps;
+        self
+    }
+
+    #[cfg(test)]
+    pub(crate) fn with_address_book(mut self, address_book: AddressBook) -> Self {
+        self.address_book = address_book;
+        self
+    }
+
+    /// Whether `event` should be published, i.e. it isn't claimed in the
+    /// dedup store yet. Fails open when the store is unavailable.
+    async fn claim(&self, event: &Event) -> bool {
+        let Some(dedup) = &self.dedup_store else {
+            return true;
+        };
+        match dedup.claim(&event.event_id).await {
+            Ok(true) => true,
+            Ok(false) => {
+                debug!(
+                    "Event {} already claimed in the dedup store",
+                    event.event_id
+                );
+                false
+            }
+            Err(e) => {
+                warn!(
+                    "Dedup store unavailable, publishing {} unclaimed: {:?}",
+                    event.event_id, e
+                );
+                true
+            }
+        }
+    }
+
+    async fn release(&self, event: &Event) {
+        if let Some(dedup) = &self.dedup_store {
+            if let Err(e) = dedup.release(&event.event_id).await {
+                warn!(
+                    "Failed to release dedup claim for {}: {:?}",
+                    event.event_id, e
+                );
+            }
+        }
+    }
+
+    /// `event` as published, with explorer links and asset fields filled in.
+    fn enrich(&self, event: &Event) -> Event {
+        let mut event = event.clone();
+        if event.explorer_url.is_none() {
+            event.explorer_url = self.explorers.links(&event);
+        }
+        if event.asset_symbol.is_none() {
+            self.assets.fill(&mut event);
+        }
+        if event.region.is_none() {
+            event.region = self.region.clone();
+        }
+        if event.instance_id.is_none() {
+            event.instance_id = self.instance_id.clone();
+        }
+        event
+    }
+
+    /// The payload, and its JSON value if some destination projects fields.
+    fn serialize(
+        &self,
+        envelope: &Envelope,
+    ) -> Result<(String, serde_json::Value), serde_json::Error> {
+        let payload = serde_json::to_string(envelope)?;
+        // Only built if some destination projects fields.
+        let value = if self.destinations.iter().any(|d| !d.fields.is_empty()) {
+            serde_json::to_value(envelope)?
+        } else {
+            serde_json::Value::Null
+        };
+        Ok((payload, value))
+    }
+
+    /// The payload of an event for `dest`: projected to its `fields`, and
+    /// numbered in the destination's own sequence if it filters events, so
+    /// that its consumers see no gaps for the events it leaves out.
+    fn payload_for(
+        &self,
+        dest: &Destination,
+        envelope: &Envelope,
+        payload: &str,
+        value: &serde_json::Value,
+    ) -> serde_json::Result<String> {
+        let sequence = match dest.filter.is_empty() {
+            true => None,
+            false => self.sequences.next_for(&dest.name, envelope.event),
+        };
+        match (sequence, dest.fields.is_empty()) {
+            (None, true) => Ok(payload.to_string()),
+            (None, false) => Ok(project(value, &dest.fields)),
+            (Some(sequence), true) => {
+                let event = Event {
+                    sequence: Some(sequence),
+                    ..envelope.event.clone()
+                };
+                serde_json::to_string(&Envelope {
+                    event: &event,
+                    traceparent: envelope.traceparent.clone(),
+                })
+            }
+            (Some(sequence), false) => {
+                let mut value = value.clone();
+                value["sequence"] = sequence.into();
+                Ok(project(&value, &dest.fields))
+            }
+        }
+    }
+
+    async fn enqueue(&self, event: &Event, trace: TraceContext, span: Span) -> anyhow::Result<()> {
+        let event = &self.enrich(event);
+        let envelope = Envelope {
+            event,
+            traceparent: trace.to_string(),
+        };
+        let (payload, value) = match self.serialize(&envelope) {
+            Ok(serialized) => serialized,
+            Err(e) => {
+                return match self.quarantine.unserializable(event, e) {
+                    Some(e) => Err(e),
+                    None => Ok(()),
+                }
+            }
+        };
+        for dest in self.destinations.iter().filter(|d| d.filter.matches(event)) {
+            let payload = self.payload_for(dest, &envelope, &payload, &value)?;
+            let delivery = Delivery {
+                event_id: event.event_id.clone(),
+                key: dest.partition_key.key(event),
+                order_key: dest.order_key(event).map(str::to_string),
+                route: dest.route.as_ref().map(|t| sink::route(t, event)),
+                payload,
+                span: span.clone(),
+                attempts: 0,
+                _pending: dest.pending.add(),
+            };
+            let tx = dest.shard(event);
+            match dest.overflow {
+                Overflow::Block => tx
+                    .send(delivery)
+                    .await
+                    .map_err(|_| anyhow!("destination {} worker stopped", dest.name))?,
+                Overflow::Drop => match tx.try_send(delivery) {
+                    Ok(()) => {}
+                    Err(TrySendError::Full(d)) => warn!(
+                        "Destination {} queue full, dropping event {}",
+                        dest.name, d.event_id
+                    ),
+                    Err(TrySendError::Closed(_)) => {
+                        return Err(anyhow!("destination {} worker stopped", dest.name))
+                    }
+                },
+            }
+        }
+        self.bus.publish(BusEvent {
+            event: event.clone(),
+            payload,
+            span,
+        });
+        Ok(())
+    }
+}
+
+/// The routing template of `dest`: a NATS subject, or a Redis channel or
+/// Kafka topic with placeholders.
+fn route_template(dest: &DestinationConfig) -> Option<String> {
+    match dest.sink {
+        SinkKind::Nats => Some(dest.subject.clone()),
+        SinkKind::Redis if sink::is_template(&dest.channel) => Some(dest.channel.clone()),
+        SinkKind::Kafka if sink::is_template(&dest.topic) => Some(dest.topic.clone()),
+        _ => None,
+    }
+}
+
+/// Serialize only `fields` of a payload, in the configured order. Fields the
+/// event doesn't have (e.g. `token` on a native transfer) are left out.
+fn project(payload: &serde_json::Value, fields: &[String]) -> String {
+    let parts: Vec<String> = fields
+        .iter()
+        .filter_map(|f| {
+            payload
+                .get(f)
+                .map(|v| format!("{}:{}", serde_json::Value::from(f.as_str()), v))
+        })
+        .collect();
+    format!("{{{}}}", parts.join(","))
+}
+
+/// A delivery worker: one queue of a destination.
+struct Worker {
+    dest: DestinationConfig,
+    /// Index of the worker's queue, for requeues.
+    shard: usize,
+    sink: Arc<dyn EventSink>,
+    quarantine: Quarantine,
+}
+
+impl Worker {
+    /// Quarantine a delivery that failed `attempts` more times.
+    fn give_up(&self, delivery: &Delivery, attempts: usize, e: &anyhow::Error) {
+        self.quarantine.add(
+            &delivery.event_id,
+            Stage::Deliver,
+            Some(&self.dest.name),
+            delivery.attempts + attempts as u64,
+            format!("{:#}", e),
+            delivery.payload.clone(),
+            Retry::Deliver {
+                shard: self.shard,
+                key: delivery.key.clone(),
+                order_key: delivery.order_key.clone(),
+                route: delivery.route.clone(),
+            },
+        );
+    }
+
+    /// Count `delivery` against the quarantined deliveries of its order key
+    /// it went out ahead of. Requeued deliveries aren't counted: unlike a
+    /// fresh one, they aren't known to be later than the others.
+    fn delivered(&self, delivery: &Delivery) {
+        match &delivery.order_key {
+            Some(key) if delivery.attempts == 0 => self.quarantine.overtaken(&self.dest.name, key),
+            _ => {}
+        }
+    }
+}
+
+/// Deliver queued events to the destination's sink with retry and
+/// exponential backoff to survive short outages.
+async fn run_destination(worker: Worker, mut rx: mpsc::Receiver<Delivery>) {
+    let Worker { dest, sink, .. } = &worker;
+    let base = Duration::from_millis(dest.retry_base_ms);
+    while let Some(delivery) = rx.recv().await {
+        let span = delivery.span.clone();
+        async {
+            let res: anyhow::Result<()> =
+                retry_with_backoff(dest.retry_attempts, base, 2.0, || {
+                    let payload = delivery.payload.as_bytes();
+                    match &delivery.route {
+                        Some(route) => sink.publish_routed(route, &delivery.key, payload),
+                        None => sink.publish_keyed(&delivery.key, payload),
+                    }
+                })
+                .await;
+
+            match res {
+                Ok(_) => {
+                    info!(
+                        "Published event {} (destination {})",
+                        delivery.event_id, dest.name
+                    );
+                    worker.delivered(&delivery);
+                }
+                Err(e) => worker.give_up(&delivery, dest.retry_attempts.max(1), &e),
+            }
+        }
+        .instrument(span)
+        .await;
+    }
+}
+
+/// Encode a batch as newline-delimited JSON, optionally zstd-compressed.
+fn encode_batch(batch: &[Delivery], compression: Compression) -> anyhow::Result<Vec<u8>> {
+    let mut body = Vec::new();
+    for delivery in batch {
+        body.extend_from_slice(delivery.payload.as_bytes());
+        body.push(b'\n');
+    }
+    match compression {
+        Compression::None => Ok(body),
+        Compression::Zstd => Ok(zstd::encode_all(body.as_slice(), ZSTD_LEVEL)?),
+    }
+}
+
+/// zstd's default level; a good ratio at a few hundred MB/s.
+const ZSTD_LEVEL: i32 = 3;
+
+/// Collect up to `batch_max_events` deliveries, or whatever arrived within
+/// `batch_max_delay_ms` of the first, and publish them as one message.
+async fn run_batch_destination(worker: Worker, mut rx: mpsc::Receiver<Delivery>) {
+    let Worker { dest, sink, .. } = &worker;
+    let base = Duration::from_millis(dest.retry_base_ms);
+    let max_delay = Duration::from_millis(dest.batch_max_delay_ms);
+    let mut batch: Vec<Delivery> = Vec::with_capacity(dest.batch_max_events);
+    while let Some(first) = rx.recv().await {
+        batch.push(first);
+        let deadline = Instant::now() + max_delay;
+        while batch.len() < dest.batch_max_events {
+            match timeout_at(deadline, rx.recv()).await {
+                Ok(Some(delivery)) => batch.push(delivery),
+                Ok(None) | Err(_) => break,
+            }
+        }
+
+        let message = match encode_batch(&batch, dest.compression) {
+            Ok(message) => message,
+            Err(e) => {
+                error!(
+                    "Failed to encode batch of {} events for destination {}: {:?}",
+                    batch.len(),
+                    dest.name,
+                    e
+                );
+                for delivery in batch.drain(..) {
+                    worker.give_up(&delivery, 1, &e);
+                }
+                continue;
+            }
+        };
+        let res: anyhow::Result<()> =
+            retry_with_backoff(dest.retry_attempts, base, 2.0, || sink.publish(&message)).await;
+
+        match res {
+            Ok(_) => {
+                info!(
+                    "Published batch of {} events ({} bytes) to destination {}",
+                    batch.len(),
+                    message.len(),
+                    dest.name
+                );
+                for delivery in &batch {
+                    worker.delivered(delivery);
+                }
+            }
+            Err(e) => {
+                error!(
+                    "Failed to publish batch of {} events (first {}) to destination {} after retries: {:?}",
+                    batch.len(),
+                    batch[0].event_id,
+                    dest.name,
+                    e
+                );
+                for delivery in &batch {
+                    worker.give_up(delivery, dest.retry_attempts.max(1), &e);
+                }
+            }
+        }
+        batch.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(chain: &str, event_type: EventType) -> Event {
+        Event {
+            event_id: format!("{}:1", chain),
+            chain: chain.into(),
+            event_type,
+            ..Default::default()
+        }
+    }
+
+    fn destination(
+        name: &str,
+        filter: EventFilter,
+        overflow: Overflow,
+        size: usize,
+    ) -> (Destination, mpsc::Receiver<Delivery>) {
+        let (dest, mut rxs) = sharded(name, filter, overflow, OrderingMode::Unordered, 1, size);
+        (dest, rxs.remove(0))
+    }
+
+    fn sharded(
+        name: &str,
+        filter: EventFilter,
+        overflow: Overflow,
+        ordering: OrderingMode,
+        workers: usize,
+        size: usize,
+    ) -> (Destination, Vec<mpsc::Receiver<Delivery>>) {
+        let (shards, rxs) = (0..workers).map(|_| mpsc::channel(size)).unzip();
+        (
+            Destination {
+                name: name.into(),
+                filter,
+                fields: Vec::new(),
+                overflow,
+                ordering,
+                partition_key: PartitionKey::default(),
+                route: None,
+                shards,
+                next_shard: AtomicUsize::new(0),
+                pending: Pending::default(),
+                retention: None,
+                archive: None,
+            },
+            rxs,
+        )
+    }
+
+    #[test]
+    fn test_filter_matching() {
+        let all = EventFilter::default();
+        assert!(all.matches(&event("solana", EventType::SolanaTx)));
+
+        let eth_erc20 = EventFilter {
+            chains: vec!["ethereum".into()],
+            event_types: vec![EventType::Erc20Transfer],
+        };
+        assert!(eth_erc20.matches(&event("ethereum", EventType::Erc20Transfer)));
+        assert!(!eth_erc20.matches(&event("ethereum", EventType::Transfer)));
+        assert!(!eth_erc20.matches(&event("solana", EventType::Erc20Transfer)));
+    }
+
+    #[tokio::test]
+    async fn test_publish_fans_out_by_filter() {
+        let (all, mut all_rx) = destination("all", EventFilter::default(), Overflow::Block, 4);
+        let (sol, mut sol_rx) = destination(
+            "sol",
+            EventFilter {
+                chains: vec!["solana".into()],
+                event_types: vec![],
+            },
+            Overflow::Block,
+            4,
+        );
+        let publisher = Publisher::with_destinations(vec![all, sol]);
+
+        let block = TraceContext::new_root();
+        publisher
+            .publish(&event("ethereum", EventType::Transfer), block)
+            .await
+            .unwrap();
+        publisher
+            .publish(
+                &event("solana", EventType::SolanaTx),
+                TraceContext::new_root(),
+            )
+            .await
+            .unwrap();
+
+        let first = all_rx.recv().await.unwrap();
+        assert_eq!(first.event_id, "ethereum:1");
+        let payload: serde_json::Value = serde_json::from_str(&first.payload).unwrap();
+        let trace = TraceContext::parse(payload["traceparent"].as_str().unwrap()).unwrap();
+        // A child of the caller's span in the caller's trace.
+        assert_eq!(trace.trace_id, block.trace_id);
+        assert_ne!(trace.span_id, block.span_id);
+        assert_eq!(all_rx.recv().await.unwrap().event_id, "solana:1");
+        assert_eq!(sol_rx.recv().await.unwrap().event_id, "solana:1");
+        assert!(sol_rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_filtered_destination_numbers_its_own_events() {
+        let transfers = EventFilter {
+            chains: vec![],
+            event_types: vec![EventType::Transfer],
+        };
+        let (all, mut all_rx) = destination("all", EventFilter::default(), Overflow::Block, 4);
+        let (full, mut full_rx) = destination("full", transfers.clone(), Overflow::Block, 4);
+        let (mut minimal, mut minimal_rx) = destination("minimal", transfers, Overflow::Block, 4);
+        minimal.fields = vec!["sequence".into()];
+        let publisher = Publisher::with_destinations(vec![all, full, minimal]);
+
+        for event_type in [
+            EventType::Transfer,
+            EventType::Erc20Transfer,
+            EventType::Transfer,
+        ] {
+            publisher
+                .publish(&event("ethereum", event_type), TraceContext::new_root())
+                .await
+                .unwrap();
+        }
+
+        let sequence = |delivery: Delivery| {
+            let payload: serde_json::Value = serde_json::from_str(&delivery.payload).unwrap();
+            payload["sequence"].as_u64().unwrap()
+        };
+        for expected in [1, 2, 3] {
+            assert_eq!(sequence(all_rx.recv().await.unwrap()), expected);
+        }
+        for expected in [1, 2] {
+            assert_eq!(sequence(full_rx.recv().await.unwrap()), expected);
+            assert_eq!(sequence(minimal_rx.recv().await.unwrap()), expected);
+        }
+        assert!(full_rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_fields_projection_per_destination() {
+        let (full, mut full_rx) = destination("full", EventFilter::default(), Overflow::Block, 4);
+        let (mut minimal, mut minimal_rx) =
+            destination("minimal", EventFilter::default(), Overflow::Block, 4);
+        minimal.fields = ["value", "event_id", "token"]
+            .iter()
+            .map(|f| f.to_string())
+            .collect();
+        let publisher = Publisher::with_destinations(vec![full, minimal]);
+
+        let mut event = event("ethereum", EventType::Transfer);
+        event.value = "42".into();
+        publisher
+            .publish(&event, TraceContext::new_root())
+            .await
+            .unwrap();
+
+        let full: serde_json::Value =
+            serde_json::from_str(&full_rx.recv().await.unwrap().payload).unwrap();
+        assert_eq!(full["chain"], "ethereum");
+        assert_eq!(
+            minimal_rx.recv().await.unwrap().payload,
+            r#"{"value":"42","event_id":"ethereum:1"}"#
+        );
+    }
+
+    #[test]
+    fn test_encode_batch_zstd_round_trip() {
+        let batch: Vec<Delivery> = (0..3)
+            .map(|i| Delivery {
+                event_id: format!("eth:{}", i),
+                key: String::new(),
+                order_key: None,
+                route: None,
+                payload: format!(r#"{{"event_id":"eth:{}"}}"#, i),
+                span: Span::none(),
+                attempts: 0,
+                _pending: Pending::default().add(),
+            })
+            .collect();
+
+        let plain = encode_batch(&batch, Compression::None).unwrap();
+        assert_eq!(
+            String::from_utf8(plain.clone()).unwrap(),
+            "{\"event_id\":\"eth:0\"}\n{\"event_id\":\"eth:1\"}\n{\"event_id\":\"eth:2\"}\n"
+        );
+
+        let compressed = encode_batch(&batch, Compression::Zstd).unwrap();
+        assert_eq!(zstd::decode_all(compressed.as_slice()).unwrap(), plain);
+    }
+
+    #[tokio::test]
+    async fn test_full_drop_queue_does_not_block_others() {
+        let (slow, _slow_rx) = destination("slow", EventFilter::default(), Overflow::Drop, 1);
+        let (fast, mut fast_rx) = destination("fast", EventFilter::default(), Overflow::Block, 4);
+        let publisher = Publisher::with_destinations(vec![slow, fast]);
+
+        for _ in 0..3 {
+            publisher
+                .publish(
+                    &event("ethereum", EventType::Transfer),
+                    TraceContext::new_root(),
+                )
+                .await
+                .unwrap();
+        }
+
+        for _ in 0..3 {
+            assert!(fast_rx.recv().await.is_some());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_drain_waits_for_workers() {
+        let (publisher, mut rx) = Publisher::in_memory();
+        for _ in 0..2 {
+            publisher
+                .publish(
+                    &event("ethereum", EventType::Transfer),
+                    TraceContext::new_root(),
+                )
+                .await
+                .unwrap();
+        }
+        assert_eq!(
+            publisher.drain(Instant::now()).await,
+            [("in_memory".to_string(), 2)]
+        );
+
+        // A delivery stays pending until the worker is done with it.
+        let first = rx.recv().await.unwrap();
+        drop(rx.recv().await.unwrap());
+        let draining = tokio::spawn({
+            let publisher = publisher.clone();
+            async move {
+                publisher
+                    .drain(Instant::now() + Duration::from_secs(5))
+                    .await
+            }
+        });
+        drop(first);
+        assert!(draining.await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_per_address_ordering_keeps_wallet_on_one_worker() {
+        let (dest, mut rxs) = sharded(
+            "ordered",
+            EventFilter::default(),
+            Overflow::Block,
+            OrderingMode::PerAddress,
+            4,
+            16,
+        );
+        let publisher = Publisher::with_destinations(vec![dest]);
+
+        for i in 0..5 {
+            let event = Event {
+                event_id: format!("eth:{}", i),
+                chain: "ethereum".into(),
+                watched_address: Some("0xabc".into()),
+                ..Default::default()
+            };
+            publisher
+                .publish(&event, TraceContext::new_root())
+                .await
+                .unwrap();
+        }
+
+        let mut received = Vec::new();
+        for rx in rxs.iter_mut() {
+            while let Ok(d) = rx.try_recv() {
+                received.push(d.event_id);
+            }
+            if !received.is_empty() {
+                break;
+            }
+        }
+        let expected: Vec<String> = (0..5).map(|i| format!("eth:{}", i)).collect();
+        assert_eq!(received, expected);
+        assert!(rxs.iter_mut().all(|rx| rx.try_recv().is_err()));
+    }
+
+    struct FailingSink;
+
+    #[async_trait::async_trait]
+    impl EventSink for FailingSink {
+        async fn publish(&self, _message: &[u8]) -> anyhow::Result<()> {
+            Err(anyhow!("connection refused"))
+        }
+
+        async fn publish_routed(
+            &self,
+            _route: &str,
+            _key: &str,
+            message: &[u8],
+        ) -> anyhow::Result<()> {
+            self.publish(message).await
+        }
+
+        fn describe(&self) -> String {
+            "failing".into()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_failed_delivery_is_quarantined_and_requeued() {
+        let quarantine = Quarantine::default();
+        let worker = Worker {
+            dest: DestinationConfig {
+                name: "flaky".into(),
+                retry_attempts: 2,
+                retry_base_ms: 1,
+                ..Default::default()
+            },
+            shard: 0,
+            sink: Arc::new(FailingSink),
+            quarantine: quarantine.clone(),
+        };
+        let (tx, rx) = mpsc::channel(1);
+        tx.send(Delivery {
+            event_id: "eth:1".into(),
+            key: "0xabc".into(),
+            order_key: None,
+            route: Some("events.ethereum.transfer".into()),
+            payload: r#"{"event_id":"eth:1"}"#.into(),
+            span: Span::none(),
+            attempts: 0,
+            _pending: Pending::default().add(),
+        })
+        .await
+        .unwrap();
+        drop(tx);
+        run_destination(worker, rx).await;
+
+        let quarantined = quarantine.list();
+        assert_eq!(quarantined.len(), 1);
+        assert_eq!(quarantined[0].destination.as_deref(), Some("flaky"));
+        assert_eq!(quarantined[0].attempts, 2);
+        assert_eq!(quarantined[0].error, "connection refused");
+
+        let (dest, mut rx) = destination("flaky", EventFilter::default(), Overflow::Block, 4);
+        let mut publisher = Publisher::with_destinations(vec![dest]);
+        publisher.quarantine = quarantine.clone();
+        let id = quarantined[0].id;
+        assert!(publisher.requeue(id).await.unwrap());
+        let requeued = rx.recv().await.unwrap();
+        assert_eq!(requeued.key, "0xabc");
+        assert_eq!(requeued.route.as_deref(), Some("events.ethereum.transfer"));
+        assert_eq!(requeued.payload, r#"{"event_id":"eth:1"}"#);
+        assert_eq!(requeued.attempts, 2);
+        assert!(quarantine.list().is_empty());
+        assert!(!publisher.requeue(id).await.unwrap());
+    }
+
+    /// Refuses the payloads of event `eth:1`.
+    struct PoisonSink;
+
+    #[async_trait::async_trait]
+    impl EventSink for PoisonSink {
+        async fn publish(&self, message: &[u8]) -> anyhow::Result<()> {
+            match String::from_utf8_lossy(message).contains("eth:1") {
+                true => Err(anyhow!("rejected")),
+                false => Ok(()),
+            }
+        }
+
+        fn describe(&self) -> String {
+            "poison".into()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_quarantined_delivery_counts_the_events_overtaking_it() {
+        let quarantine = Quarantine::default();
+        let worker = Worker {
+            dest: DestinationConfig {
+                name: "ordered".into(),
+                ordering: OrderingMode::PerAddress,
+                retry_attempts: 1,
+                retry_base_ms: 1,
+                ..Default::default()
+            },
+            shard: 0,
+            sink: Arc::new(PoisonSink),
+            quarantine: quarantine.clone(),
+        };
+        let (tx, rx) = mpsc::channel(4);
+        for (id, address) in [("eth:1", "0xabc"), ("eth:2", "0xabc"), ("eth:3", "0xdef")] {
+            tx.send(Delivery {
+                event_id: id.into(),
+                key: address.into(),
+                order_key: Some(address.into()),
+                route: None,
+                payload: format!(r#"{{"event_id":"{}"}}"#, id),
+                span: Span::none(),
+                attempts: 0,
+                _pending: Pending::default().add(),
+            })
+            .await
+            .unwrap();
+        }
+        drop(tx);
+        run_destination(worker, rx).await;
+
+        let quarantined = quarantine.list();
+        assert_eq!(quarantined.len(), 1);
+        assert_eq!(quarantined[0].event_id, "eth:1");
+        assert_eq!(quarantined[0].overtaken, 1);
+    }
+}
@@ -0,0 +1,532 @@
+//! Quarantine of poison events (`EVENT_RETRY_BUDGET`, `QUARANTINE_MAX`,
+//! `QUARANTINE_FILE`).
+//!
+//! An event that can't be published no longer blocks the trackers or
+//! disappears into the log:
+//!
+//! - an event that fails to serialize fails its `publish`, so the tracker
+//!   retries it later. After `EVENT_RETRY_BUDGET` failures (default 3) it is
+//!   quarantined instead and the publish succeeds, letting the tracker move
+//!   on;
+//! - a delivery that fails every one of its destination's `retry_attempts`
+//!   is quarantined for that destination instead of dropped, and so is every
+//!   event of a batch that does.
+//!
+//! The quarantine keeps the last `QUARANTINE_MAX` events (default 1000; 0
+//! keeps none) in memory, with the failing stage, destination, attempts and
+//! last error, and the payload as it would have been delivered.
+//! `GET /admin/quarantine` lists them, newest first, and
+//! `POST /admin/quarantine/{id}/requeue` sends one through the failed stage
+//! again: back into its destination's queue, or through `publish`. A
+//! requeued event that fails again is quarantined again with its attempts
+//! added up. Every quarantined event is also logged as an error.
+//!
+//! With `QUARANTINE_FILE` set, the quarantine is saved to it on every
+//! change, and on shutdown for the `overtaken` counts, and loaded back at
+//! startup, so entries can still be inspected and requeued after a restart.
+//! An event that failed to serialize can't be saved itself: after a restart
+//! its entry remains for inspection, but requeueing it fails. Without it the
+//! quarantine restarts empty with the listener.
+//!
+//! With `ordering = "per_address"`, a quarantined delivery doesn't hold
+//! back its wallet's later events: they are delivered without it, and a
+//! requeue delivers it after them, so consumers see the wallet's events out
+//! of order. Each entry's `overtaken` counts the later events of its wallet
+//! delivered to its destination since it was quarantined, and the first one
+//! is logged as a warning.
+use std::collections::{HashMap, VecDeque};
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex as StdMutex};
+
+use anyhow::{anyhow, Context};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use tracing::{error, info, warn};
+
+use crate::Event;
+
+/// Default `EVENT_RETRY_BUDGET`.
+pub const DEFAULT_RETRY_BUDGET: u64 = 3;
+/// Default `QUARANTINE_MAX`.
+pub const DEFAULT_MAX_EVENTS: u64 = 1000;
+/// Events with failed publishes counted at once; past it the counts restart.
+const MAX_COUNTED: usize = 10_000;
+
+/// Quarantine settings.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QuarantineConfig {
+    /// Failed publishes of an event before it is quarantined
+    /// (`EVENT_RETRY_BUDGET`).
+    pub retry_budget: u64,
+    /// Events kept (`QUARANTINE_MAX`).
+    pub max_events: usize,
+    /// Where the quarantine is saved (`QUARANTINE_FILE`); in memory only
+    /// when unset.
+    pub file: Option<String>,
+}
+
+impl Default for QuarantineConfig {
+    fn default() -> Self {
+        QuarantineConfig {
+            retry_budget: DEFAULT_RETRY_BUDGET,
+            max_events: DEFAULT_MAX_EVENTS as usize,
+            file: None,
+        }
+    }
+}
+
+/// Where an event failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Stage {
+    /// Serializing the event, before any destination.
+    Serialize,
+    /// Delivering it to a destination's sink.
+    Deliver,
+}
+
+/// How a requeued event is sent again.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub(crate) enum Retry {
+    /// Through `publish`. Never saved, as the event can't be serialized.
+    #[serde(skip)]
+    Publish(Event),
+    /// Into the queue of a destination's worker.
+    Deliver {
+        shard: usize,
+        key: String,
+        /// See `Delivery::order_key`.
+        order_key: Option<String>,
+        route: Option<String>,
+    },
+    /// Nothing: the event failed to serialize before a restart.
+    #[default]
+    Unsaved,
+}
+
+/// An entry of `GET /admin/quarantine`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QuarantinedEvent {
+    pub id: u64,
+    pub event_id: String,
+    pub stage: Stage,
+    /// The destination a delivery failed for.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub destination: Option<String>,
+    /// Attempts made, over every requeue.
+    pub attempts: u64,
+    pub error: String,
+    /// The payload as delivered, or the event's debug representation if it
+    /// couldn't be serialized.
+    pub payload: String,
+    /// When it was quarantined (RFC 3339).
+    pub quarantined_at: String,
+    /// Later events of the same watched address delivered since, with
+    /// `ordering = "per_address"`; consumers saw them out of order.
+    pub overtaken: u64,
+    #[serde(skip)]
+    pub(crate) retry: Retry,
+}
+
+#[derive(Debug, Default)]
+struct Store {
+    next_id: u64,
+    events: VecDeque<QuarantinedEvent>,
+    /// Failed publishes per event id, until it is quarantined.
+    failures: HashMap<String, u64>,
+}
+
+/// A quarantined event as saved to `QUARANTINE_FILE`, with how to send it
+/// again.
+#[derive(Serialize, Deserialize)]
+struct SavedEvent {
+    #[serde(flatten)]
+    event: QuarantinedEvent,
+    retry: Retry,
+}
+
+/// The contents of `QUARANTINE_FILE`.
+#[derive(Serialize, Deserialize)]
+struct Saved {
+    next_id: u64,
+    events: Vec<SavedEvent>,
+}
+
+/// The quarantined events. Cheap to clone; all clones share the store.
+#[derive(Debug, Clone, Default)]
+pub struct Quarantine {
+    cfg: QuarantineConfig,
+    store: Arc<StdMutex<Store>>,
+    /// `QUARANTINE_FILE`.
+    path: Option<Arc<PathBuf>>,
+    /// Whether the store changed since it was last saved.
+    changed: Arc<AtomicBool>,
+}
+
+impl Quarantine {
+    /// Load the events saved in `QUARANTINE_FILE`, if any.
+    pub fn new(cfg: QuarantineConfig) -> anyhow::Result<Self> {
+        let path = cfg.file.as_ref().map(PathBuf::from);
+        let mut store = match &path {
+            Some(path) if path.exists() => load(path)?,
+            _ => Store::default(),
+        };
+        while store.events.len() > cfg.max_events {
+            store.events.pop_front();
+        }
+        if !store.events.is_empty() {
+            info!("Loaded {} quarantined events", store.events.len());
+        }
+        Ok(Quarantine {
+            cfg,
+            store: Arc::new(StdMutex::new(store)),
+            path: path.map(Arc::new),
+            changed: Arc::default(),
+        })
+    }
+
+    /// Count a failed serialization of `event`. Returns the error for
+    /// `publish` to fail with while the event has retries left, and `None`
+    /// once it has been quarantined instead.
+    pub(crate) fn unserializable(&self, event: &Event, e: impl Display) -> Option<anyhow::Error> {
+        let attempts = {
+            let mut store = self.store.lock().unwrap();
+            if store.failures.len() >= MAX_COUNTED && !store.failures.contains_key(&event.event_id)
+            {
+                store.failures.clear();
+            }
+            let failures = store.failures.entry(event.event_id.clone()).or_default();
+            *failures += 1;
+            if *failures < self.cfg.retry_budget {
+                return Some(anyhow!(
+                    "failed to serialize event {}: {}",
+                    event.event_id,
+                    e
+                ));
+            }
+            let attempts = *failures;
+            store.failures.remove(&event.event_id);
+            attempts
+        };
+        self.add(
+            &event.event_id,
+            Stage::Serialize,
+            None,
+            attempts,
+            e,
+            format!("{:?}", event),
+            Retry::Publish(event.clone()),
+        );
+        None
+    }
+
+    /// Keep an event that failed `stage`, evicting the oldest one past
+    /// `QUARANTINE_MAX`. Returns its id.
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn add(
+        &self,
+        event_id: &str,
+        stage: Stage,
+        destination: Option<&str>,
+        attempts: u64,
+        e: impl Display,
+        payload: String,
+        retry: Retry,
+    ) -> u64 {
+        error!(
+            "Quarantined event {} after {} failed attempts ({:?}, destination {}): {}",
+            event_id,
+            attempts,
+            stage,
+            destination.unwrap_or("-"),
+            e
+        );
+        let mut store = self.store.lock().unwrap();
+        store.next_id += 1;
+        let id = store.next_id;
+        self.changed.store(true, Ordering::Relaxed);
+        if self.cfg.max_events == 0 {
+            drop(store);
+            self.save();
+            return id;
+        }
+        if store.events.len() == self.cfg.max_events {
+            if let Some(evicted) = store.events.pop_front() {
+                warn!(
+                    "Quarantine full, evicting event {} (id {})",
+                    evicted.event_id, evicted.id
+                );
+            }
+        }
+        store.events.push_back(QuarantinedEvent {
+            id,
+            event_id: event_id.to_string(),
+            stage,
+            destination: destination.map(str::to_string),
+            attempts,
+            error: e.to_string(),
+            payload,
+            quarantined_at: Utc::now().to_rfc3339(),
+            overtaken: 0,
+            retry,
+        });
+        drop(store);
+        self.save();
+        id
+    }
+
+    /// Count a delivery to `destination` ordered by `order_key` against the
+    /// quarantined deliveries it overtook, warning the first time each one
+    /// is overtaken.
+    pub(crate) fn overtaken(&self, destination: &str, order_key: &str) {
+        let mut store = self.store.lock().unwrap();
+        for quarantined in store.events.iter_mut() {
+            let Retry::Deliver {
+                order_key: Some(key),
+                ..
+            } = &quarantined.retry
+            else {
+                continue;
+            };
+            if key != order_key || quarantined.destination.as_deref() != Some(destination) {
+                continue;
+            }
+            if quarantined.overtaken == 0 {
+                warn!(
+                    "Destination {} delivered a later event of {} ahead of quarantined event {} (id {})",
+                    destination, order_key, quarantined.event_id, quarantined.id
+                );
+            }
+            quarantined.overtaken += 1;
+            self.changed.store(true, Ordering::Relaxed);
+        }
+    }
+
+    /// The quarantined events, newest first.
+    pub fn list(&self) -> Vec<QuarantinedEvent> {
+        let store = self.store.lock().unwrap();
+        store.events.iter().rev().cloned().collect()
+    }
+
+    /// Remove an event to requeue it.
+    pub(crate) fn take(&self, id: u64) -> Option<QuarantinedEvent> {
+        let taken = {
+            let mut store = self.store.lock().unwrap();
+            let idx = store.events.iter().position(|q| q.id == id)?;
+            store.events.remove(idx)
+        };
+        self.changed.store(true, Ordering::Relaxed);
+        self.save();
+        taken
+    }
+
+    /// Put back an event whose requeue failed, in its old place.
+    pub(crate) fn restore(&self, quarantined: QuarantinedEvent) {
+        {
+            let mut store = self.store.lock().unwrap();
+            let idx = store.events.partition_point(|q| q.id < quarantined.id);
+            store.events.insert(idx, quarantined);
+        }
+        self.changed.store(true, Ordering::Relaxed);
+        self.save();
+    }
+
+    /// Save the quarantine to `QUARANTINE_FILE`, if it is set and the
+    /// quarantine changed since the last save. A failure is logged, and the
+    /// next change or shutdown tries again.
+    pub fn save(&self) {
+        let Some(path) = &self.path else {
+            return;
+        };
+        if !self.changed.swap(false, Ordering::Relaxed) {
+            return;
+        }
+        let json = {
+            let store = self.store.lock().unwrap();
+            let events = store
+                .events
+                .iter()
+                .map(|quarantined| SavedEvent {
+                    event: quarantined.clone(),
+                    retry: match &quarantined.retry {
+                        Retry::Publish(_) => Retry::Unsaved,
+                        retry => retry.clone(),
+                    },
+                })
+                .collect();
+            serde_json::to_string(&Saved {
+                next_id: store.next_id,
+                events,
+            })
+        };
+        if let Err(e) = write(path, json) {
+            self.changed.store(true, Ordering::Relaxed);
+            error!(
+                "Failed to save the quarantine to {}: {:#}",
+                path.display(),
+                e
+            );
+        }
+    }
+}
+
+/// Write the serialized quarantine to `path` through a temporary file, so a
+/// crash never leaves it half-written.
+fn write(path: &Path, json: serde_json::Result<String>) -> anyhow::Result<()> {
+    let tmp = path.with_extension("tmp");
+    std::fs::write(&tmp, json?)?;
+    std::fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+fn load(path: &Path) -> anyhow::Result<Store> {
+    let raw = std::fs::read_to_string(path)
+        .with_context(|| format!("failed to read quarantine {}", path.display()))?;
+    let saved: Saved = serde_json::from_str(&raw)
+        .with_context(|| format!("failed to parse quarantine {}", path.display()))?;
+    let events = saved
+        .events
+        .into_iter()
+        .map(|SavedEvent { mut event, retry }| {
+            event.retry = retry;
+            event
+        })
+        .collect();
+    Ok(Store {
+        next_id: saved.next_id,
+        events,
+        failures: HashMap::new(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(id: &str) -> Event {
+        Event {
+            event_id: id.into(),
+            chain: "ethereum".into(),
+            ..Default::default()
+        }
+    }
+
+    fn quarantine(max_events: usize) -> Quarantine {
+        Quarantine::new(QuarantineConfig {
+            retry_budget: 2,
+            max_events,
+            file: None,
+        })
+        .unwrap()
+    }
+
+    fn deliver(quarantine: &Quarantine, event_id: &str) -> u64 {
+        let retry = Retry::Deliver {
+            shard: 0,
+            key: String::new(),
+            order_key: Some("0xabc".into()),
+            route: None,
+        };
+        quarantine.add(
+            event_id,
+            Stage::Deliver,
+            Some("main"),
+            8,
+            "refused",
+            "{}".into(),
+            retry,
+        )
+    }
+
+    #[test]
+    fn test_unserializable_event_spends_its_budget() {
+        let quarantine = quarantine(10);
+        let error = quarantine.unserializable(&event("eth:1"), "bad");
+        assert!(error.unwrap().to_string().contains("eth:1"));
+        assert!(quarantine.list().is_empty());
+
+        assert!(quarantine.unserializable(&event("eth:1"), "bad").is_none());
+        let list = quarantine.list();
+        assert_eq!(list.len(), 1);
+        assert_eq!(list[0].stage, Stage::Serialize);
+        assert_eq!(list[0].attempts, 2);
+        assert!(list[0].payload.contains("eth:1"));
+
+        // A requeued event starts with a fresh budget.
+        assert!(quarantine.unserializable(&event("eth:1"), "bad").is_some());
+    }
+
+    #[test]
+    fn test_bounded_newest_first() {
+        let quarantine = quarantine(2);
+        deliver(&quarantine, "eth:1");
+        deliver(&quarantine, "eth:2");
+        deliver(&quarantine, "eth:3");
+        let ids: Vec<String> = quarantine.list().into_iter().map(|q| q.event_id).collect();
+        assert_eq!(ids, vec!["eth:3", "eth:2"]);
+        let json = serde_json::to_string(&quarantine.list()[0]).unwrap();
+        assert!(json.starts_with(concat!(
+            r#"{"id":3,"event_id":"eth:3","stage":"deliver","destination":"main","#,
+            r#""attempts":8,"error":"refused","payload":"{}","quarantined_at":"#
+        )));
+    }
+
+    #[test]
+    fn test_overtaken_counts_later_deliveries_of_the_key() {
+        let quarantine = quarantine(10);
+        deliver(&quarantine, "eth:1");
+        quarantine.overtaken("main", "0xabc");
+        quarantine.overtaken("main", "0xabc");
+        quarantine.overtaken("main", "0xdef");
+        quarantine.overtaken("backup", "0xabc");
+        assert_eq!(quarantine.list()[0].overtaken, 2);
+    }
+
+    #[test]
+    fn test_take_and_restore() {
+        let quarantine = quarantine(10);
+        let first = deliver(&quarantine, "eth:1");
+        deliver(&quarantine, "eth:2");
+        let taken = quarantine.take(first).unwrap();
+        assert_eq!(taken.event_id, "eth:1");
+        assert!(quarantine.take(first).is_none());
+
+        quarantine.restore(taken);
+        let ids: Vec<u64> = quarantine.list().into_iter().map(|q| q.id).collect();
+        assert_eq!(ids, vec![2, 1]);
+    }
+
+    #[test]
+    fn test_saved_across_restarts() {
+        let path = std::env::temp_dir().join("tracker_quarantine_test.json");
+        let _ = std::fs::remove_file(&path);
+        let cfg = QuarantineConfig {
+            retry_budget: 1,
+            max_events: 10,
+            file: Some(path.to_string_lossy().into_owned()),
+        };
+        let quarantine = Quarantine::new(cfg.clone()).unwrap();
+        deliver(&quarantine, "eth:1");
+        assert!(quarantine.unserializable(&event("eth:2"), "bad").is_none());
+        // Overtaken counts are saved on shutdown.
+        quarantine.overtaken("main", "0xabc");
+        quarantine.save();
+
+        let restarted = Quarantine::new(cfg).unwrap();
+        let _ = std::fs::remove_file(&path);
+        let list = restarted.list();
+        assert_eq!(list.len(), 2);
+        assert_eq!(list[0].event_id, "eth:2");
+        assert!(matches!(list[0].retry, Retry::Unsaved));
+        assert_eq!(list[1].overtaken, 1);
+        assert!(matches!(
+            &list[1].retry,
+            Retry::Deliver { order_key: Some(key), .. } if key == "0xabc"
+        ));
+        // Ids carry on.
+        assert_eq!(deliver(&restarted, "eth:3"), 3);
+    }
+}
@@ -0,0 +1,298 @@
+//! Token balance reconciliation of watched Ethereum addresses
+//! (`balance_reconciliation` job).
+//!
+//! The listener only knows the transfers it decodes. A missed log, a token
+//! with unusual mechanics or a decoding bug shows up as a balance that moved
+//! without a matching event. The job reads the token balances of every
+//! watched Ethereum address at the checkpoint through [`History`] (the chain
+//! registry's tokens and `ETH_TOKEN_ALLOWLIST`), and checks that the
+//! `erc20_transfer` events published since the previous agreeing run
+//! explain how far each balance moved. A fee-on-transfer token is credited
+//! with the recipient's `received_value`; rebasing tokens are skipped.
+//!
+//! Events and the checkpoint don't move in lockstep, so a single run can
+//! see a transfer's balance change before its event, or the other way
+//! round. A drift is therefore only reported, as a warning, once two runs in
+//! a row find the same amount unexplained; the balance then becomes the new
+//! baseline. With `SPAM_FILTER=drop`, dropped spam transfers are reported
+//! as drift too. Native balances aren't reconciled: gas isn't reported on
+//! every path.
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex as StdMutex};
+
+use ethers::types::{Address, U256};
+use tokio::sync::Mutex;
+use tracing::{debug, warn};
+
+use crate::bus::BusSubscriber;
+use crate::erc20::TokenBehavior;
+use crate::event_type::EventType;
+use crate::history::History;
+use crate::Event;
+
+/// An address's balance of a token, keyed `(address, token)`, both
+/// lowercase.
+type Key = (String, String);
+
+/// How far a balance is from what the events explain.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Drift {
+    /// The balance holds more than the events explain.
+    Over(U256),
+    /// The balance holds less than the events explain.
+    Under(U256),
+}
+
+/// One balance since its baseline.
+#[derive(Debug, Clone, Default)]
+struct Account {
+    /// Balance at the last run that agreed with the events; `None` until
+    /// the first run.
+    baseline: Option<U256>,
+    inflow: U256,
+    outflow: U256,
+    /// Drift the previous run found, if it found one.
+    drift: Option<Drift>,
+}
+
+impl Account {
+    fn unexplained(&self, balance: U256, baseline: U256) -> Option<Drift> {
+        let have = balance.saturating_add(self.outflow);
+        let explained = baseline.saturating_add(self.inflow);
+        match have.cmp(&explained) {
+            std::cmp::Ordering::Equal => None,
+            std::cmp::Ordering::Greater => Some(Drift::Over(have - explained)),
+            std::cmp::Ordering::Less => Some(Drift::Under(explained - have)),
+        }
+    }
+
+    /// Start over from `balance`.
+    fn rebase(&mut self, balance: U256) {
+        *self = Account {
+            baseline: Some(balance),
+            ..Default::default()
+        };
+    }
+
+    /// Compare `balance` with the events so far. Returns the drift to
+    /// report, if the previous run found the same one.
+    fn check(&mut self, balance: U256) -> Option<Drift> {
+        let Some(baseline) = self.baseline else {
+            self.rebase(balance);
+            return None;
+        };
+        match self.unexplained(balance, baseline) {
+            None => {
+                self.rebase(balance);
+                None
+            }
+            Some(drift) if self.drift == Some(drift) => {
+                self.rebase(balance);
+                Some(drift)
+            }
+            Some(drift) => {
+                self.drift = Some(drift);
+                None
+            }
+        }
+    }
+}
+
+/// Balances and the transfers seen since their baselines. Cheap to clone;
+/// all clones share state.
+#[derive(Clone)]
+pub struct Reconciler {
+    history: History,
+    /// Watched Ethereum addresses, lowercase.
+    addresses: Arc<Vec<String>>,
+    accounts: Arc<StdMutex<HashMap<Key, Account>>>,
+}
+
+impl Reconciler {
+    /// Count the transfers `events` receives from now on.
+    pub fn start(history: History, addresses: &[String], mut events: BusSubscriber) -> Self {
+        let reconciler = Reconciler {
+            history,
+            addresses: Arc::new(addresses.iter().map(|a| a.to_lowercase()).collect()),
+            accounts: Arc::default(),
+        };
+        let counting = reconciler.clone();
+        tokio::spawn(async move {
+            while let Some(published) = events.recv().await {
+                counting.record(&published.event);
+            }
+        });
+        reconciler
+    }
+
+    /// Add an `erc20_transfer` event to the flows of the watched addresses
+    /// it moves tokens of. Backfilled history predates every baseline.
+    fn record(&self, event: &Event) {
+        if event.chain != "ethereum"
+            || event.event_type != EventType::Erc20Transfer
+            || event.backfill
+        {
+            return;
+        }
+        let (Some(token), Ok(value)) = (&event.token, U256::from_dec_str(&event.value)) else {
+            return;
+        };
+        let token = token.address.to_lowercase();
+        let received = event
+            .received_value
+            .as_deref()
+            .and_then(|v| U256::from_dec_str(v).ok())
+            .unwrap_or(value);
+        let mut accounts = self.accounts.lock().unwrap();
+        let from = event.from.to_lowercase();
+        if self.addresses.contains(&from) {
+            let account = accounts.entry((from, token.clone())).or_default();
+            account.outflow = account.outflow.saturating_add(value);
+        }
+        let to = event.to.to_lowercase();
+        if self.addresses.contains(&to) {
+            let account = accounts.entry((to, token)).or_default();
+            account.inflow = account.inflow.saturating_add(received);
+        }
+    }
+
+    /// Check every watched address's token balances at `last_block`. Fails
+    /// if a balance couldn't be read; the others are still checked.
+    pub async fn run(&self, last_block: &Mutex<Option<u64>>) -> anyhow::Result<()> {
+        let Some(block) = *last_block.lock().await else {
+            debug!("Balance reconciliation skipped: no Ethereum checkpoint yet");
+            return Ok(());
+        };
+        let tokens: Vec<Address> = self
+            .history
+            .eth_tokens()
+            .iter()
+            .copied()
+            .filter(|&token| self.history.token_behavior(token) != Some(TokenBehavior::Rebasing))
+            .collect();
+        let mut failed = 0;
+        for address in self.addresses.iter() {
+            let state = match self.history.state("ethereum", address, block).await {
+                Ok(state) => state,
+                Err(e) => {
+                    warn!("Balance reconciliation of {} failed: {:?}", address, e);
+                    failed += 1;
+                    continue;
+                }
+            };
+            // Only non-zero balances are listed.
+            let held: HashMap<String, U256> = state
+                .tokens
+                .iter()
+                .filter_map(|holding| {
+                    let token = holding.token.as_deref()?.to_lowercase();
+                    Some((token, U256::from_dec_str(&holding.balance).ok()?))
+                })
+                .collect();
+            let mut accounts = self.accounts.lock().unwrap();
+            for token in &tokens {
+                let token = format!("{:?}", token);
+                let balance = held.get(&token).copied().unwrap_or_default();
+                let account = accounts
+                    .entry((address.clone(), token.clone()))
+                    .or_default();
+                match account.check(balance) {
+                    Some(Drift::Over(amount)) => warn!(
+                        "Balance of {} in token {} at block {} is {} more than its transfers explain",
+                        address, token, block, amount
+                    ),
+                    Some(Drift::Under(amount)) => warn!(
+                        "Balance of {} in token {} at block {} is {} less than its transfers explain",
+                        address, token, block, amount
+                    ),
+                    None => {}
+                }
+            }
+        }
+        match failed {
+            0 => Ok(()),
+            n => Err(anyhow::anyhow!(
+                "balance reconciliation failed for {} addresses",
+                n
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bus::EventBus;
+    use crate::Token;
+
+    const WATCHED: &str = "0x70997970c51812dc3a010c7d01b50e0d17dc79c8";
+    const USDC: &str = "0xa0b86991c6218b36c1d19d4a2e9eb0ce3606eb48";
+
+    fn units(n: u64) -> U256 {
+        U256::from(n)
+    }
+
+    #[test]
+    fn test_drift_is_reported_once_it_persists() {
+        let mut account = Account::default();
+        // The first run only sets the baseline.
+        assert_eq!(account.check(units(100)), None);
+
+        // Explained by the events.
+        account.inflow = units(50);
+        assert_eq!(account.check(units(150)), None);
+        assert_eq!(account.baseline, Some(units(150)));
+
+        // A transfer whose event is still on its way: not reported...
+        assert_eq!(account.check(units(170)), None);
+        // ...and explained once the event arrives.
+        account.inflow = units(20);
+        assert_eq!(account.check(units(170)), None);
+        assert_eq!(account.baseline, Some(units(170)));
+
+        // A transfer that never gets an event is reported on the second run,
+        // then becomes the baseline.
+        assert_eq!(account.check(units(160)), None);
+        assert_eq!(account.check(units(160)), Some(Drift::Under(units(10))));
+        assert_eq!(account.check(units(160)), None);
+    }
+
+    #[tokio::test]
+    async fn test_transfers_count_towards_watched_balances() {
+        let reconciler = Reconciler::start(
+            History::default(),
+            &[WATCHED.to_uppercase().replace("0X", "0x")],
+            EventBus::default().subscribe("reconcile"),
+        );
+        let transfer = |from: &str, to: &str, value: &str, received: Option<&str>| Event {
+            chain: "ethereum".into(),
+            event_type: EventType::Erc20Transfer,
+            from: from.into(),
+            to: to.into(),
+            value: value.into(),
+            received_value: received.map(str::to_string),
+            token: Some(Token {
+                address: USDC.to_uppercase().replace("0X", "0x"),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        reconciler.record(&transfer("0x01", WATCHED, "100", Some("98")));
+        reconciler.record(&transfer(WATCHED, "0x02", "30", None));
+        reconciler.record(&transfer("0x01", "0x02", "500", None));
+        reconciler.record(&Event {
+            chain: "polygon".into(),
+            ..transfer("0x01", WATCHED, "7", None)
+        });
+        reconciler.record(&Event {
+            backfill: true,
+            ..transfer("0x01", WATCHED, "9", None)
+        });
+
+        let accounts = reconciler.accounts.lock().unwrap();
+        let account = &accounts[&(WATCHED.to_string(), USDC.to_string())];
+        assert_eq!(account.inflow, units(98));
+        assert_eq!(account.outflow, units(30));
+        assert_eq!(accounts.len(), 1);
+    }
+}
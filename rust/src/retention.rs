@@ -0,0 +1,208 @@
+//! Retention of stored events and checkpoints.
+//!
+//! The `file`, `stream` and `postgres` sinks keep everything they deliver,
+//! and the checkpoint store keeps a position for every network any
+//! deployment ever tracked. To bound them, set how long to keep:
+//!
+//! ```toml
+//! [[destinations]]
+//! name = "history"
+//! sink = "postgres"
+//! retention_days = 90
+//!
+//! [[destinations]]
+//! name = "ledger"
+//! sink = "file"
+//! path = "/var/lib/tracker/events.jsonl"
+//! retention_days = 365
+//! ```
+//!
+//! and `CHECKPOINT_RETENTION_DAYS` for checkpoints. The `retention` job
+//! (hourly; `SCHEDULE_RETENTION`) then removes events older than that by
+//! their `timestamp` (see [`EventSink::expire`]) and positions no deployment
+//! has saved for that long (see [`checkpoint::expire`]). It is only
+//! registered when some retention is configured.
+//!
+//! Every run is recorded per target, served by `GET /admin/retention`: the
+//! configured days, runs since startup, the time and outcome of the last
+//! run, and how many events or positions were removed in it and in total.
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex as StdMutex};
+
+use chrono::{DateTime, Duration, SecondsFormat, Utc};
+use serde::Serialize;
+use tracing::{info, warn};
+
+use crate::checkpoint::{self, CheckpointStore};
+use crate::config::Config;
+use crate::publisher::Publisher;
+use crate::sink::EventSink;
+
+/// Target name of the checkpoint store.
+const CHECKPOINTS: &str = "checkpoints";
+
+static RUNS: StdMutex<BTreeMap<String, TargetStats>> = StdMutex::new(BTreeMap::new());
+
+/// Retention runs of one target since startup.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct TargetStats {
+    /// `destination:<name>`, or `checkpoints`.
+    pub target: String,
+    pub retention_days: u64,
+    pub runs: u64,
+    /// RFC 3339 time of the last run.
+    pub last_run: Option<String>,
+    /// Events or positions removed by the last run.
+    pub last_removed: u64,
+    pub removed_total: u64,
+    /// Why the last run failed; `None` if it succeeded.
+    pub last_error: Option<String>,
+}
+
+/// Runs of every target since startup, by target name.
+pub fn stats() -> Vec<TargetStats> {
+    RUNS.lock().unwrap().values().cloned().collect()
+}
+
+fn record(target: &str, retention_days: u64, now: DateTime<Utc>, result: &anyhow::Result<u64>) {
+    let mut runs = RUNS.lock().unwrap();
+    let stats = runs
+        .entry(target.to_string())
+        .or_insert_with(|| TargetStats {
+            target: target.to_string(),
+            ..Default::default()
+        });
+    stats.retention_days = retention_days;
+    stats.runs += 1;
+    stats.last_run = Some(now.to_rfc3339_opts(SecondsFormat::Secs, true));
+    match result {
+        Ok(removed) => {
+            stats.last_removed = *removed;
+            stats.removed_total += removed;
+            stats.last_error = None;
+        }
+        Err(e) => {
+            stats.last_removed = 0;
+            stats.last_error = Some(format!("{:#}", e));
+        }
+    }
+}
+
+/// What the `retention` job expires; cheap to clone.
+#[derive(Clone, Default)]
+pub struct Retention {
+    cfg: Arc<Config>,
+    sinks: Arc<Vec<(String, u64, Arc<dyn EventSink>)>>,
+}
+
+impl Retention {
+    pub fn new(cfg: &Config, publisher: &Publisher) -> Self {
+        Retention {
+            cfg: Arc::new(cfg.clone()),
+            sinks: Arc::new(publisher.retained()),
+        }
+    }
+
+    /// Whether anything has a retention period, so the job is worth
+    /// registering.
+    pub fn enabled(&self) -> bool {
+        !self.sinks.is_empty() || self.checkpoint_days() > 0
+    }
+
+    fn checkpoint_days(&self) -> u64 {
+        match self.cfg.checkpoints.store {
+            CheckpointStore::None => 0,
+            _ => self.cfg.checkpoints.retention_days,
+        }
+    }
+
+    /// Expire every target once. A failing target is logged and recorded
+    /// without stopping the others; the run fails if any did.
+    pub async fn run(&self) -> anyhow::Result<()> {
+        let mut failed = 0;
+        for (name, days, sink) in self.sinks.iter() {
+            let now = Utc::now();
+            let cutoff = now - Duration::days(*days as i64);
+            let result = sink.expire(cutoff).await;
+            failed += report(&format!("destination:{}", name), *days, now, result);
+        }
+        let days = self.checkpoint_days();
+        if days > 0 {
+            let now = Utc::now();
+            let result = checkpoint::expire(&self.cfg, now.timestamp().max(0) as u64).await;
+            failed += report(CHECKPOINTS, days, now, result);
+        }
+        match failed {
+            0 => Ok(()),
+            n => Err(anyhow::anyhow!("retention failed for {} targets", n)),
+        }
+    }
+}
+
+/// Log and record one target's result; 1 if it failed.
+fn report(target: &str, days: u64, now: DateTime<Utc>, result: anyhow::Result<u64>) -> usize {
+    record(target, days, now, &result);
+    match result {
+        Ok(removed) => {
+            info!(
+                "Retention removed {} entries older than {} days from {}",
+                removed, days, target
+            );
+            0
+        }
+        Err(e) => {
+            warn!("Retention of {} failed: {:#}", target, e);
+            1
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_runs_are_recorded_per_target() {
+        let now = DateTime::from_timestamp(1_760_000_000, 0).unwrap();
+        record("destination:test_ledger", 365, now, &Ok(4));
+        record("destination:test_ledger", 365, now, &Ok(1));
+        record(
+            "destination:test_history",
+            90,
+            now,
+            &Err(anyhow::anyhow!("db down")),
+        );
+
+        let stats = stats();
+        let ledger = stats
+            .iter()
+            .find(|s| s.target == "destination:test_ledger")
+            .unwrap();
+        assert_eq!(ledger.runs, 2);
+        assert_eq!(ledger.last_removed, 1);
+        assert_eq!(ledger.removed_total, 5);
+        assert_eq!(ledger.last_run.as_deref(), Some("2025-10-09T08:53:20Z"));
+        let history = stats
+            .iter()
+            .find(|s| s.target == "destination:test_history")
+            .unwrap();
+        assert_eq!(history.retention_days, 90);
+        assert_eq!(history.last_error.as_deref(), Some("db down"));
+    }
+
+    #[test]
+    fn test_disabled_without_retention() {
+        assert!(!Retention::default().enabled());
+        let checkpoints = Retention {
+            cfg: Arc::new(Config {
+                checkpoints: checkpoint::CheckpointConfig {
+                    retention_days: 30,
+                    ..Default::default()
+                },
+                ..Default::default()
+            }),
+            sinks: Arc::default(),
+        };
+        assert!(checkpoints.enabled());
+    }
+}
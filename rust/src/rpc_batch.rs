@@ -0,0 +1,262 @@
+//! JSON-RPC batch requests for the Ethereum HTTP poller
+//! (`ETH_RPC_BATCH_SIZE`).
+//!
+//! Polling a block one call at a time costs a block request plus one receipt
+//! request per transaction. With batching on, the poller asks for a run of
+//! blocks together with their receipts (`eth_getBlockReceipts`) as a single
+//! JSON array, so a block range takes one round-trip per
+//! `ETH_RPC_BATCH_SIZE` calls. Nodes without `eth_getBlockReceipts` get a
+//! second batch with `eth_getTransactionReceipt` per transaction.
+//!
+//! Batches go straight over HTTP, bypassing the ethers transport, so they
+//! aren't recorded by `tracker record`; capture runs poll without them.
+use std::collections::HashMap;
+
+use anyhow::{anyhow, bail, Context};
+use ethers::types::{Block, Transaction, TransactionReceipt, TxHash};
+use serde_json::{json, Value};
+use tracing::debug;
+
+/// A fetched block and its receipts, in transaction order. A receipt the
+/// node couldn't return is `None`.
+#[derive(Debug)]
+pub struct BatchedBlock {
+    pub block: Block<Transaction>,
+    pub receipts: Vec<Option<TransactionReceipt>>,
+}
+
+/// Sends batch requests to one HTTP endpoint.
+#[derive(Debug, Clone)]
+pub struct RpcBatch {
+    client: reqwest::Client,
+    url: String,
+    /// Most calls per request.
+    size: usize,
+}
+
+impl RpcBatch {
+    pub fn new(url: &str, size: usize) -> Self {
+        RpcBatch {
+            client: reqwest::Client::new(),
+            url: url.to_string(),
+            size: size.max(1),
+        }
+    }
+
+    /// Blocks fetched per round-trip: each needs a block and a receipts call.
+    pub fn blocks_per_request(&self) -> usize {
+        (self.size / 2).max(1)
+    }
+
+    /// Run `calls` (method, params), at most `size` per request. Results are
+    /// in call order; a call the node answered with an error is `Err` with
+    /// its message. Fails as a whole if a request doesn't get a valid batch
+    /// response.
+    pub async fn call(
+        &self,
+        calls: &[(&str, Value)],
+    ) -> anyhow::Result<Vec<Result<Value, String>>> {
+        let mut results = Vec::with_capacity(calls.len());
+        for chunk in calls.chunks(self.size) {
+            let body: Vec<Value> = chunk
+                .iter()
+                .enumerate()
+                .map(|(id, (method, params))| {
+                    json!({"jsonrpc": "2.0", "id": id, "method": method, "params": params})
+                })
+                .collect();
+            let response: Value = self
+                .client
+                .post(&self.url)
+                .json(&body)
+                .send()
+                .await?
+                .error_for_status()?
+                .json()
+                .await?;
+            results.extend(parse_response(response, chunk.len())?);
+        }
+        Ok(results)
+    }
+
+    /// Fetch `numbers` with full transactions and their receipts. A block the
+    /// node doesn't have yet is `None`.
+    pub async fn blocks(&self, numbers: &[u64]) -> anyhow::Result<Vec<Option<BatchedBlock>>> {
+        let calls: Vec<(&str, Value)> = numbers
+            .iter()
+            .flat_map(|n| {
+                let tag = format!("0x{:x}", n);
+                [
+                    ("eth_getBlockByNumber", json!([tag, true])),
+                    ("eth_getBlockReceipts", json!([tag])),
+                ]
+            })
+            .collect();
+        let mut results = self.call(&calls).await?.into_iter();
+
+        let mut blocks = Vec::with_capacity(numbers.len());
+        let mut missing: Vec<TxHash> = Vec::new();
+        while let (Some(block), Some(receipts)) = (results.next(), results.next()) {
+            let block = block.map_err(|e| anyhow!("eth_getBlockByNumber failed: {}", e))?;
+            if block.is_null() {
+                blocks.push(None);
+                continue;
+            }
+            let block: Block<Transaction> =
+                serde_json::from_value(block).context("invalid block in batch response")?;
+            let receipts = match receipts {
+                Ok(receipts) if !receipts.is_null() => {
+                    let receipts: Vec<TransactionReceipt> = serde_json::from_value(receipts)
+                        .context("invalid receipts in batch response")?;
+                    in_tx_order(&block, receipts)
+                }
+                other => {
+                    if let Err(e) = other {
+                        debug!("eth_getBlockReceipts unavailable: {}", e);
+                    }
+                    missing.extend(block.transactions.iter().map(|tx| tx.hash));
+                    vec![None; block.transactions.len()]
+                }
+            };
+            blocks.push(Some(BatchedBlock { block, receipts }));
+        }
+
+        if !missing.is_empty() {
+            let fetched = self.transaction_receipts(&missing).await?;
+            for batched in blocks.iter_mut().flatten() {
+                for (tx, receipt) in batched.block.transactions.iter().zip(&mut batched.receipts) {
+                    if receipt.is_none() {
+                        *receipt = fetched.get(&tx.hash).cloned();
+                    }
+                }
+            }
+        }
+        Ok(blocks)
+    }
+
+    async fn transaction_receipts(
+        &self,
+        hashes: &[TxHash],
+    ) -> anyhow::Result<HashMap<TxHash, TransactionReceipt>> {
+        let calls: Vec<(&str, Value)> = hashes
+            .iter()
+            .map(|hash| ("eth_getTransactionReceipt", json!([hash])))
+            .collect();
+        let mut receipts = HashMap::new();
+        for (hash, result) in hashes.iter().zip(self.call(&calls).await?) {
+            match result {
+                Ok(Value::Null) => {}
+                Ok(receipt) => {
+                    let receipt = serde_json::from_value(receipt)
+                        .context("invalid receipt in batch response")?;
+                    receipts.insert(*hash, receipt);
+                }
+                Err(e) => debug!("Failed to fetch receipt for {:?}: {}", hash, e),
+            }
+        }
+        Ok(receipts)
+    }
+}
+
+/// Match receipts to the block's transactions by hash.
+fn in_tx_order(
+    block: &Block<Transaction>,
+    receipts: Vec<TransactionReceipt>,
+) -> Vec<Option<TransactionReceipt>> {
+    let mut by_hash: HashMap<TxHash, TransactionReceipt> = receipts
+        .into_iter()
+        .map(|r| (r.transaction_hash, r))
+        .collect();
+    block
+        .transactions
+        .iter()
+        .map(|tx| by_hash.remove(&tx.hash))
+        .collect()
+}
+
+/// Results of a batch response with ids `0..calls`, in id order. Servers may
+/// answer in any order.
+fn parse_response(response: Value, calls: usize) -> anyhow::Result<Vec<Result<Value, String>>> {
+    let Value::Array(items) = response else {
+        // Servers that don't support batches reply with a single error.
+        bail!("not a batch response: {}", response);
+    };
+    let mut results: Vec<Option<Result<Value, String>>> = vec![None; calls];
+    for mut item in items {
+        let id = item["id"]
+            .as_u64()
+            .map(|id| id as usize)
+            .filter(|id| *id < calls)
+            .ok_or_else(|| anyhow!("unexpected id in batch response: {}", item["id"]))?;
+        let error = item.get_mut("error").map(Value::take);
+        results[id] = Some(match error {
+            Some(error) => Err(error["message"]
+                .as_str()
+                .map(str::to_string)
+                .unwrap_or_else(|| error.to_string())),
+            None => Ok(item["result"].take()),
+        });
+    }
+    results
+        .into_iter()
+        .enumerate()
+        .map(|(id, result)| result.ok_or_else(|| anyhow!("no response for call {} in batch", id)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_responses_are_matched_by_id() {
+        let response = json!([
+            {"jsonrpc": "2.0", "id": 1, "error": {"code": -32601, "message": "method not found"}},
+            {"jsonrpc": "2.0", "id": 0, "result": "0x10"},
+            {"jsonrpc": "2.0", "id": 2, "result": null},
+        ]);
+        let results = parse_response(response, 3).unwrap();
+        assert_eq!(results[0], Ok(json!("0x10")));
+        assert_eq!(results[1], Err("method not found".to_string()));
+        assert_eq!(results[2], Ok(Value::Null));
+    }
+
+    #[test]
+    fn test_incomplete_or_unbatched_responses_fail() {
+        let missing = json!([{"jsonrpc": "2.0", "id": 0, "result": "0x1"}]);
+        assert!(parse_response(missing, 2).is_err());
+
+        let unbatched =
+            json!({"jsonrpc": "2.0", "id": null, "error": {"message": "batch not supported"}});
+        assert!(parse_response(unbatched, 1).is_err());
+    }
+
+    #[test]
+    fn test_receipts_follow_transaction_order() {
+        let tx = |n: u64| Transaction {
+            hash: TxHash::from_low_u64_be(n),
+            ..Default::default()
+        };
+        let receipt = |n: u64| TransactionReceipt {
+            transaction_hash: TxHash::from_low_u64_be(n),
+            ..Default::default()
+        };
+        let block = Block {
+            transactions: vec![tx(1), tx(2), tx(3)],
+            ..Default::default()
+        };
+        let receipts = in_tx_order(&block, vec![receipt(3), receipt(1)]);
+        let hashes: Vec<_> = receipts
+            .iter()
+            .map(|r| r.as_ref().map(|r| r.transaction_hash))
+            .collect();
+        assert_eq!(
+            hashes,
+            vec![
+                Some(TxHash::from_low_u64_be(1)),
+                None,
+                Some(TxHash::from_low_u64_be(3))
+            ]
+        );
+    }
+}
@@ -0,0 +1,405 @@
+//! Consistency guard over several Ethereum endpoints (`ETH_RPC_BACKUP_URLS`).
+//!
+//! Providers behind a load balancer, or simply different providers, don't
+//! always agree: one backend falls behind, another follows a fork the rest
+//! have abandoned. The `rpc_consistency` job asks every endpoint for its
+//! head, then for its block hash at the lowest head among the endpoints that
+//! keep up, and sorts them out:
+//!
+//! - `unreachable`: the endpoint didn't answer.
+//! - `lagging`: its head is more than `ETH_RPC_MAX_HEAD_LAG` blocks behind
+//!   the highest head.
+//! - `diverged`: it returns a different hash at that height than most
+//!   endpoints (the active endpoint decides a tie).
+//! - `healthy`: none of the above.
+//!
+//! Problems are logged as warnings, and `GET /admin/rpc` shows the last
+//! result. Tracking stays on its endpoint while that is healthy; otherwise
+//! the guard pins it to the healthy endpoint with the highest head, and the
+//! tracker reconnects there and resumes from its checkpoint.
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex as StdMutex};
+
+use anyhow::anyhow;
+use ethers::providers::{Http, Ipc, Middleware, Provider, Ws};
+use ethers::types::H256;
+use serde::Serialize;
+use tokio::sync::watch;
+use tokio::time::{timeout, Duration};
+use tracing::{info, warn};
+
+use crate::config::Config;
+
+/// Time allowed for each endpoint's connect and each call.
+const PROBE_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EndpointStatus {
+    /// Not checked yet.
+    Unchecked,
+    Healthy,
+    Unreachable,
+    Lagging,
+    Diverged,
+}
+
+/// Last check of one endpoint, for `GET /admin/rpc`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct EndpointHealth {
+    pub url: String,
+    /// Whether tracking is pinned to this endpoint.
+    pub active: bool,
+    pub status: EndpointStatus,
+    pub head: Option<u64>,
+}
+
+/// What one endpoint answered.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct Probe {
+    head: Option<u64>,
+    /// Block hash at the reference height.
+    hash: Option<H256>,
+}
+
+/// The endpoints and which one tracking is pinned to. Cheap to clone; all
+/// clones share state.
+#[derive(Debug, Clone)]
+pub struct RpcGuard {
+    endpoints: Arc<Vec<String>>,
+    max_head_lag: u64,
+    /// Index of the pinned endpoint.
+    active: Arc<watch::Sender<usize>>,
+    health: Arc<StdMutex<Vec<EndpointHealth>>>,
+}
+
+impl Default for RpcGuard {
+    fn default() -> Self {
+        RpcGuard::new(&Config::default())
+    }
+}
+
+impl RpcGuard {
+    /// `ETH_RPC_URL` followed by `ETH_RPC_BACKUP_URLS`; tracking starts on
+    /// `ETH_RPC_URL`.
+    pub fn new(cfg: &Config) -> Self {
+        let mut endpoints = vec![cfg.eth_rpc_url.clone()];
+        endpoints.extend(
+            cfg.eth_rpc_backup_urls
+                .iter()
+                .filter(|url| **url != cfg.eth_rpc_url)
+                .cloned(),
+        );
+        let health = endpoints
+            .iter()
+            .enumerate()
+            .map(|(i, url)| EndpointHealth {
+                url: url.clone(),
+                active: i == 0,
+                status: EndpointStatus::Unchecked,
+                head: None,
+            })
+            .collect();
+        let (active, _) = watch::channel(0);
+        RpcGuard {
+            endpoints: Arc::new(endpoints),
+            max_head_lag: cfg.eth_rpc_max_head_lag,
+            active: Arc::new(active),
+            health: Arc::new(StdMutex::new(health)),
+        }
+    }
+
+    /// Whether there is more than one endpoint to compare.
+    pub fn guards(&self) -> bool {
+        self.endpoints.len() > 1
+    }
+
+    /// The endpoint tracking is pinned to.
+    pub fn active_url(&self) -> String {
+        self.endpoints[*self.active.borrow()].clone()
+    }
+
+    /// Changes when tracking is pinned to another endpoint.
+    pub fn subscribe(&self) -> watch::Receiver<usize> {
+        self.active.subscribe()
+    }
+
+    /// Result of the last check.
+    pub fn health(&self) -> Vec<EndpointHealth> {
+        self.health.lock().unwrap().clone()
+    }
+
+    /// Head block of the endpoint tracking is pinned to, or `None` if it
+    /// doesn't answer.
+    pub async fn head(&self) -> Option<u64> {
+        let probe = async {
+            let client = Client::connect(&self.active_url()).await?;
+            client.head().await
+        };
+        timeout(PROBE_TIMEOUT, probe).await.ok()?.ok()
+    }
+
+    /// Probe every endpoint, record their health and move tracking off an
+    /// unhealthy endpoint. Run by the `rpc_consistency` job.
+    pub async fn check(&self) -> anyhow::Result<()> {
+        let mut clients = Vec::with_capacity(self.endpoints.len());
+        let mut probes = vec![Probe::default(); self.endpoints.len()];
+        for (i, url) in self.endpoints.iter().enumerate() {
+            let client = match timeout(PROBE_TIMEOUT, Client::connect(url)).await {
+                Ok(Ok(client)) => client,
+                Ok(Err(e)) => {
+                    warn!("ETH endpoint {} is unreachable: {:?}", url, e);
+                    clients.push(None);
+                    continue;
+                }
+                Err(_) => {
+                    warn!("ETH endpoint {} is unreachable: connect timed out", url);
+                    clients.push(None);
+                    continue;
+                }
+            };
+            probes[i].head = match timeout(PROBE_TIMEOUT, client.head()).await {
+                Ok(Ok(head)) => Some(head),
+                _ => None,
+            };
+            clients.push(Some(client));
+        }
+        if let Some(height) = reference_height(&probes, self.max_head_lag) {
+            for (probe, client) in probes.iter_mut().zip(&clients) {
+                let (Some(client), Some(_)) = (client, probe.head) else {
+                    continue;
+                };
+                if let Ok(Ok(hash)) = timeout(PROBE_TIMEOUT, client.hash_at(height)).await {
+                    probe.hash = hash;
+                }
+            }
+        }
+
+        let active = *self.active.borrow();
+        let statuses = assess(&probes, self.max_head_lag, active);
+        let highest = probes.iter().filter_map(|p| p.head).max();
+        for (i, status) in statuses.iter().enumerate() {
+            let url = &self.endpoints[i];
+            match status {
+                EndpointStatus::Lagging => warn!(
+                    "ETH endpoint {} is at block {:?}, more than {} blocks behind the highest \
+                     head {:?}",
+                    url, probes[i].head, self.max_head_lag, highest
+                ),
+                EndpointStatus::Diverged => warn!(
+                    "ETH endpoint {} returns block hash {:?}, unlike the other endpoints",
+                    url, probes[i].hash
+                ),
+                EndpointStatus::Unreachable if clients[i].is_some() => {
+                    warn!("ETH endpoint {} didn't answer the consistency check", url)
+                }
+                _ => {}
+            }
+        }
+        let pinned = pick(&statuses, &probes, active);
+        if pinned != active {
+            warn!(
+                "Pinning ETH tracking to {} ({} is {:?})",
+                self.endpoints[pinned], self.endpoints[active], statuses[active]
+            );
+            self.active.send_replace(pinned);
+        } else if statuses[active] != EndpointStatus::Healthy {
+            warn!(
+                "ETH endpoint {} is {:?}, and no other endpoint is healthy",
+                self.endpoints[active], statuses[active]
+            );
+        }
+        *self.health.lock().unwrap() = statuses
+            .iter()
+            .enumerate()
+            .map(|(i, status)| EndpointHealth {
+                url: self.endpoints[i].clone(),
+                active: i == pinned,
+                status: *status,
+                head: probes[i].head,
+            })
+            .collect();
+        info!(
+            "ETH endpoints checked: {} of {} healthy",
+            statuses
+                .iter()
+                .filter(|s| **s == EndpointStatus::Healthy)
+                .count(),
+            statuses.len()
+        );
+        Ok(())
+    }
+}
+
+/// The lowest head among endpoints within `max_head_lag` of the highest, a
+/// height they all have.
+fn reference_height(probes: &[Probe], max_head_lag: u64) -> Option<u64> {
+    let highest = probes.iter().filter_map(|p| p.head).max()?;
+    probes
+        .iter()
+        .filter_map(|p| p.head)
+        .filter(|head| highest - head <= max_head_lag)
+        .min()
+}
+
+/// Status of each endpoint from the probes; `active` decides a tie between
+/// hashes.
+fn assess(probes: &[Probe], max_head_lag: u64, active: usize) -> Vec<EndpointStatus> {
+    let highest = probes.iter().filter_map(|p| p.head).max();
+    let mut votes: HashMap<H256, usize> = HashMap::new();
+    for hash in probes.iter().filter_map(|p| p.hash) {
+        *votes.entry(hash).or_default() += 1;
+    }
+    let most = votes.values().copied().max().unwrap_or(0);
+    let majority = match probes.get(active).and_then(|p| p.hash) {
+        Some(hash) if votes[&hash] == most => Some(hash),
+        _ => probes
+            .iter()
+            .filter_map(|p| p.hash)
+            .find(|hash| votes[hash] == most),
+    };
+    probes
+        .iter()
+        .map(|probe| match (probe.head, highest) {
+            (Some(head), Some(highest)) if highest - head > max_head_lag => EndpointStatus::Lagging,
+            (Some(_), _) => match probe.hash {
+                Some(hash) if Some(hash) == majority => EndpointStatus::Healthy,
+                Some(_) => EndpointStatus::Diverged,
+                None => EndpointStatus::Unreachable,
+            },
+            (None, _) => EndpointStatus::Unreachable,
+        })
+        .collect()
+}
+
+/// The endpoint to track: the active one while it is healthy, else the
+/// healthy one with the highest head, else still the active one.
+fn pick(statuses: &[EndpointStatus], probes: &[Probe], active: usize) -> usize {
+    if statuses[active] == EndpointStatus::Healthy {
+        return active;
+    }
+    statuses
+        .iter()
+        .enumerate()
+        .filter(|(_, status)| **status == EndpointStatus::Healthy)
+        .max_by_key(|(i, _)| (probes[*i].head, std::cmp::Reverse(*i)))
+        .map(|(i, _)| i)
+        .unwrap_or(active)
+}
+
+/// A connection to one endpoint, by URL scheme like the trackers.
+enum Client {
+    Http(Provider<Http>),
+    Ws(Provider<Ws>),
+    Ipc(Provider<Ipc>),
+}
+
+impl Client {
+    async fn connect(url: &str) -> anyhow::Result<Self> {
+        if let Some(path) = url.strip_prefix("ipc://") {
+            Ok(Client::Ipc(Provider::new(Ipc::connect(path).await?)))
+        } else if url.starts_with("ws") {
+            Ok(Client::Ws(Provider::new(Ws::connect(url).await?)))
+        } else {
+            Ok(Client::Http(
+                Provider::<Http>::try_from(url)
+                    .map_err(|e| anyhow!("invalid endpoint {}: {}", url, e))?,
+            ))
+        }
+    }
+
+    async fn head(&self) -> anyhow::Result<u64> {
+        let head = match self {
+            Client::Http(p) => p.get_block_number().await?,
+            Client::Ws(p) => p.get_block_number().await?,
+            Client::Ipc(p) => p.get_block_number().await?,
+        };
+        Ok(head.as_u64())
+    }
+
+    async fn hash_at(&self, height: u64) -> anyhow::Result<Option<H256>> {
+        let block = match self {
+            Client::Http(p) => p.get_block(height).await?,
+            Client::Ws(p) => p.get_block(height).await?,
+            Client::Ipc(p) => p.get_block(height).await?,
+        };
+        Ok(block.and_then(|b| b.hash))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use EndpointStatus::*;
+
+    fn probe(head: Option<u64>, hash: Option<u64>) -> Probe {
+        Probe {
+            head,
+            hash: hash.map(H256::from_low_u64_be),
+        }
+    }
+
+    #[test]
+    fn test_reference_height_ignores_laggards() {
+        let probes = [
+            probe(Some(100), None),
+            probe(Some(98), None),
+            probe(Some(80), None),
+            probe(None, None),
+        ];
+        assert_eq!(reference_height(&probes, 5), Some(98));
+        assert_eq!(reference_height(&[probe(None, None)], 5), None);
+    }
+
+    #[test]
+    fn test_assess() {
+        let probes = [
+            probe(Some(100), Some(1)),
+            probe(Some(99), Some(1)),
+            probe(Some(100), Some(2)),
+            probe(Some(80), None),
+            probe(None, None),
+        ];
+        assert_eq!(
+            assess(&probes, 5, 0),
+            [Healthy, Healthy, Diverged, Lagging, Unreachable]
+        );
+    }
+
+    #[test]
+    fn test_active_endpoint_breaks_ties() {
+        let probes = [probe(Some(100), Some(1)), probe(Some(100), Some(2))];
+        assert_eq!(assess(&probes, 5, 0), [Healthy, Diverged]);
+        assert_eq!(assess(&probes, 5, 1), [Diverged, Healthy]);
+    }
+
+    #[test]
+    fn test_pick_stays_while_healthy() {
+        let probes = [
+            probe(Some(100), None),
+            probe(Some(104), None),
+            probe(Some(103), None),
+        ];
+        assert_eq!(pick(&[Healthy, Healthy, Healthy], &probes, 0), 0);
+        assert_eq!(pick(&[Lagging, Healthy, Healthy], &probes, 0), 1);
+        assert_eq!(pick(&[Diverged, Unreachable, Healthy], &probes, 0), 2);
+        assert_eq!(pick(&[Diverged, Unreachable, Lagging], &probes, 0), 0);
+    }
+
+    #[test]
+    fn test_new_lists_the_primary_first() {
+        let cfg = Config {
+            eth_rpc_url: "wss://primary".into(),
+            eth_rpc_backup_urls: vec!["https://backup".into(), "wss://primary".into()],
+            ..Default::default()
+        };
+        let guard = RpcGuard::new(&cfg);
+        assert!(guard.guards());
+        assert_eq!(guard.active_url(), "wss://primary");
+        let health = guard.health();
+        assert_eq!(health.len(), 2);
+        assert!(health[0].active);
+        assert_eq!(health[1].url, "https://backup");
+        assert_eq!(health[1].status, Unchecked);
+    }
+}
@@ -0,0 +1,206 @@
+//! Validation of RPC responses before they are normalized.
+//!
+//! Nodes and hosted providers sometimes return responses with fields missing
+//! (a log without its transaction hash, a block short of receipts, a Solana
+//! transaction without `meta.postTokenBalances`). Read as they are, these
+//! turn into zero hashes, 1970 timestamps or silently dropped token
+//! transfers. The trackers check each response here first and fail with an
+//! error naming the missing field instead, so the block or transaction is
+//! retried or left for the gap audit. Every failure is also counted per chain
+//! and kind, for `GET /admin/rpc-errors`.
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::Mutex as StdMutex;
+
+use ethers::types::{Block, Log, Transaction, TransactionReceipt};
+use serde::Serialize;
+use solana_transaction_status::option_serializer::OptionSerializer;
+use solana_transaction_status::EncodedConfirmedTransactionWithStatusMeta;
+use tracing::warn;
+
+use crate::solana_parser::account_keys;
+
+/// Failures since startup by `(chain, kind)`.
+static FAILURES: StdMutex<BTreeMap<(&str, &str), u64>> = StdMutex::new(BTreeMap::new());
+
+/// A response missing data the normalizer needs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SchemaError {
+    pub chain: &'static str,
+    /// What is wrong, e.g. `missing meta.postTokenBalances`.
+    pub kind: &'static str,
+    /// Which response, e.g. `tx 5Vf…`.
+    pub context: String,
+}
+
+impl fmt::Display for SchemaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "malformed {} RPC response for {}: {}",
+            self.chain, self.context, self.kind
+        )
+    }
+}
+
+impl std::error::Error for SchemaError {}
+
+/// Count and log a failure.
+fn fail(chain: &'static str, kind: &'static str, context: String) -> SchemaError {
+    *FAILURES.lock().unwrap().entry((chain, kind)).or_default() += 1;
+    let error = SchemaError {
+        chain,
+        kind,
+        context,
+    };
+    warn!("{}", error);
+    error
+}
+
+/// Failure count of one kind on one chain.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct FailureCount {
+    pub chain: &'static str,
+    pub kind: &'static str,
+    pub count: u64,
+}
+
+/// Failures since startup, by chain and kind.
+pub fn failures() -> Vec<FailureCount> {
+    FAILURES
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|(&(chain, kind), &count)| FailureCount { chain, kind, count })
+        .collect()
+}
+
+/// A mined log, as delivered by a log subscription or `eth_getLogs`.
+pub fn eth_log(log: &Log) -> Result<(), SchemaError> {
+    let context = || format!("log of {:?}", log.address);
+    let missing = [
+        (log.transaction_hash.is_none(), "missing transactionHash"),
+        (log.log_index.is_none(), "missing logIndex"),
+        (log.block_number.is_none(), "missing blockNumber"),
+        (log.block_hash.is_none(), "missing blockHash"),
+    ];
+    match missing.iter().find(|(missing, _)| *missing) {
+        Some((_, kind)) => Err(fail("ethereum", *kind, context())),
+        None => Ok(()),
+    }
+}
+
+/// A block with its transactions and their receipts, in transaction order.
+pub fn eth_block(
+    block: &Block<Transaction>,
+    receipts: &[Option<TransactionReceipt>],
+) -> Result<(), SchemaError> {
+    let context = format!("block {:?}", block.hash.unwrap_or_default());
+    let Some(number) = block.number else {
+        return Err(fail("ethereum", "missing number", context));
+    };
+    let context = format!("block {}", number);
+    if receipts.len() != block.transactions.len() {
+        return Err(fail("ethereum", "receipt count mismatch", context));
+    }
+    for (tx, receipt) in block.transactions.iter().zip(receipts) {
+        let context = || format!("{} tx {:?}", context, tx.hash);
+        match receipt {
+            None => return Err(fail("ethereum", "missing receipt", context())),
+            Some(receipt) if receipt.transaction_hash != tx.hash => {
+                return Err(fail("ethereum", "receipt out of order", context()));
+            }
+            Some(_) => {}
+        }
+    }
+    Ok(())
+}
+
+/// A transaction from `getTransaction` with `jsonParsed` encoding.
+pub fn solana_tx(
+    signature: &str,
+    tx: &EncodedConfirmedTransactionWithStatusMeta,
+) -> Result<(), SchemaError> {
+    let context = || format!("tx {}", signature);
+    if tx.block_time.is_none() {
+        return Err(fail("solana", "missing blockTime", context()));
+    }
+    let Some(meta) = &tx.transaction.meta else {
+        return Err(fail("solana", "missing meta", context()));
+    };
+    if !matches!(meta.pre_token_balances, OptionSerializer::Some(_)) {
+        return Err(fail("solana", "missing meta.preTokenBalances", context()));
+    }
+    if !matches!(meta.post_token_balances, OptionSerializer::Some(_)) {
+        return Err(fail("solana", "missing meta.postTokenBalances", context()));
+    }
+    let message = serde_json::to_value(&tx.transaction.transaction).unwrap_or_default();
+    if account_keys(&message).is_none() {
+        return Err(fail("solana", "missing message.accountKeys", context()));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::types::{H256, U256, U64};
+
+    fn count(chain: &str, kind: &str) -> u64 {
+        failures()
+            .into_iter()
+            .find(|f| f.chain == chain && f.kind == kind)
+            .map_or(0, |f| f.count)
+    }
+
+    #[test]
+    fn test_eth_log_names_the_missing_field() {
+        let mut log = Log {
+            transaction_hash: Some(H256::repeat_byte(1)),
+            log_index: Some(U256::from(3)),
+            block_number: Some(U64::from(10)),
+            block_hash: Some(H256::repeat_byte(2)),
+            ..Default::default()
+        };
+        assert!(eth_log(&log).is_ok());
+
+        let before = count("ethereum", "missing logIndex");
+        log.log_index = None;
+        let err = eth_log(&log).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "malformed ethereum RPC response for log of {:?}: missing logIndex",
+                log.address
+            )
+        );
+        assert!(count("ethereum", "missing logIndex") > before);
+    }
+
+    #[test]
+    fn test_eth_block_needs_every_receipt() {
+        let tx = Transaction {
+            hash: H256::repeat_byte(5),
+            ..Default::default()
+        };
+        let mut block = Block {
+            transactions: vec![tx.clone()],
+            ..Default::default()
+        };
+        assert_eq!(eth_block(&block, &[]).unwrap_err().kind, "missing number");
+        block.number = Some(U64::from(7));
+        assert_eq!(
+            eth_block(&block, &[]).unwrap_err().kind,
+            "receipt count mismatch"
+        );
+        let err = eth_block(&block, &[None]).unwrap_err();
+        assert_eq!(err.kind, "missing receipt");
+        assert!(err.context.starts_with("block 7 tx "), "{}", err.context);
+
+        let receipt = TransactionReceipt {
+            transaction_hash: tx.hash,
+            ..Default::default()
+        };
+        assert!(eth_block(&block, &[Some(receipt)]).is_ok());
+    }
+}
@@ -0,0 +1,91 @@
+//! Dedicated tokio runtimes per chain (`CHAIN_RUNTIME_THREADS`).
+//!
+//! By default the trackers of every chain share the main runtime, so a chain
+//! doing heavy work, such as catching up tens of thousands of blocks after a
+//! restart, competes for the same worker threads as the other chain's live
+//! subscriptions. With `CHAIN_RUNTIME_THREADS` set, each chain's trackers run
+//! on their own multi-threaded runtime with that many workers, on threads
+//! named `<chain>-tracker`. The publisher's delivery workers, the admin API
+//! and the maintenance jobs stay on the main runtime; events cross over
+//! through the publisher's queues.
+use std::future::Future;
+
+use tokio::runtime::{Builder, Handle, Runtime};
+use tokio::task::JoinHandle;
+use tracing::{error, info};
+
+/// Run a chain's tracker future on a runtime of its own with `threads`
+/// workers, or on the current runtime if `threads` is `None`. If the
+/// dedicated runtime can't be built the tracker falls back to the current
+/// one.
+pub fn spawn_chain<F>(chain: &'static str, threads: Option<usize>, tracker: F) -> JoinHandle<()>
+where
+    F: Future<Output = ()> + Send + 'static,
+{
+    let Some(threads) = threads else {
+        return tokio::spawn(tracker);
+    };
+    let main = Handle::current();
+    // The runtime's block_on occupies this thread for the tracker's lifetime.
+    tokio::task::spawn_blocking(move || match runtime(chain, threads) {
+        Ok(runtime) => {
+            info!(
+                "Running {} trackers on a dedicated runtime with {} threads",
+                chain, threads
+            );
+            runtime.block_on(tracker)
+        }
+        Err(e) => {
+            error!(
+                "Failed to start the {} runtime, using the shared one: {:?}",
+                chain, e
+            );
+            main.block_on(tracker)
+        }
+    })
+}
+
+fn runtime(chain: &str, threads: usize) -> std::io::Result<Runtime> {
+    Builder::new_multi_thread()
+        .worker_threads(threads.max(1))
+        .thread_name(format!("{}-tracker", chain))
+        .enable_all()
+        .build()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn thread_name() -> String {
+        std::thread::current()
+            .name()
+            .unwrap_or_default()
+            .to_string()
+    }
+
+    #[tokio::test]
+    async fn test_dedicated_runtime_threads() {
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        spawn_chain("ethereum", Some(1), async move {
+            // Timers and spawned tasks work on the dedicated runtime.
+            tokio::time::sleep(std::time::Duration::from_millis(1)).await;
+            let name = tokio::spawn(async { thread_name() }).await.unwrap();
+            tx.send(name).unwrap();
+        })
+        .await
+        .unwrap();
+        assert_eq!(rx.await.unwrap(), "ethereum-tracker");
+    }
+
+    #[tokio::test]
+    async fn test_shared_runtime_by_default() {
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        spawn_chain("solana", None, async move {
+            tx.send(thread_name()).unwrap();
+        })
+        .await
+        .unwrap();
+        assert_eq!(rx.await.unwrap(), thread_name());
+    }
+}
@@ -0,0 +1,319 @@
+//! Embedded scheduler for periodic maintenance jobs.
+//!
+//! Subsystems register named jobs with a default schedule; operators can
+//! override or disable any job through `SCHEDULE_<JOB_NAME>` environment
+//! variables (e.g. `SCHEDULE_DEDUP_COMPACTION="@every 5m"` or `off`). Each job
+//! runs in its own task and never overlaps with itself. On shutdown no new
+//! run starts; a run in progress finishes. A `SCHEDULE_` variable naming no
+//! job in [`JOBS`], or with a malformed schedule, fails the config load.
+//!
+//! Only maintenance runs here. The trackers' own waits, the HTTP poll
+//! interval and reconnect backoff, stay in the trackers: they follow each
+//! chain's pause and resume (see `control.rs`) rather than a schedule.
+use anyhow::{anyhow, Context, Result};
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::str::FromStr;
+use std::sync::Arc;
+use tokio::task::JoinHandle;
+use tokio::time::{interval_at, Duration, Instant, MissedTickBehavior};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info, warn};
+
+/// Every job the listener can register. `SCHEDULE_<JOB>` must name one of
+/// them, so a typo fails at startup instead of leaving the default in place.
+pub const JOBS: &[&str] = &[
+    "address_heartbeat",
+    "archive_flush",
+    "balance_reconciliation",
+    "checkpoint_save",
+    "dedup_compaction",
+    "expectation_deadlines",
+    "gap_audit",
+    "graph_export",
+    "health_check",
+    "kill_switch",
+    "liquidation_risk",
+    "memory_report",
+    "portfolio_snapshot",
+    "retention",
+    "rpc_consistency",
+    "solana_finality",
+    "stuck_transactions",
+    "token_metadata_refresh",
+    "validator_monitor",
+    "webhook_acks",
+];
+
+/// Check `SCHEDULE_<JOB>` overrides, keyed by lowercase job name: each must
+/// name a job in [`JOBS`] and parse as a [`Schedule`].
+pub fn check_overrides(overrides: &HashMap<String, String>) -> Vec<anyhow::Error> {
+    let mut names: Vec<&String> = overrides.keys().collect();
+    names.sort();
+    names
+        .into_iter()
+        .filter_map(|name| {
+            if !JOBS.contains(&name.as_str()) {
+                return Some(anyhow!(
+                    "SCHEDULE_{}: unknown maintenance job {} (expected one of {})",
+                    name.to_ascii_uppercase(),
+                    name,
+                    JOBS.join(", ")
+                ));
+            }
+            overrides[name]
+                .parse::<Schedule>()
+                .with_context(|| format!("SCHEDULE_{}", name.to_ascii_uppercase()))
+                .err()
+        })
+        .collect()
+}
+
+type JobFuture = Pin<Box<dyn Future<Output = Result<()>> + Send>>;
+type JobFn = Arc<dyn Fn() -> JobFuture + Send + Sync>;
+
+/// When a job runs.
+///
+/// Accepted forms: `@every <n><s|m|h|d>`, a bare duration like `30s`,
+/// `@hourly`, `@daily`, and `off`/`disabled`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Schedule {
+    Every(Duration),
+    Disabled,
+}
+
+/// Longest accepted period: 366 days.
+const MAX_PERIOD_SECS: u64 = 366 * 86_400;
+
+fn parse_duration(s: &str) -> Result<Duration> {
+    let s = s.trim();
+    let split = s
+        .find(|c: char| !c.is_ascii_digit())
+        .ok_or_else(|| anyhow!("missing unit in duration '{}'", s))?;
+    let (num, unit) = s.split_at(split);
+    let n: u64 = num
+        .parse()
+        .with_context(|| format!("invalid number in duration '{}'", s))?;
+    let scale = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 3600,
+        "d" => 86_400,
+        other => return Err(anyhow!("unknown duration unit '{}'", other)),
+    };
+    let secs = n
+        .checked_mul(scale)
+        .ok_or_else(|| anyhow!("duration '{}' is too long", s))?;
+    if secs == 0 {
+        return Err(anyhow!("duration must be greater than zero"));
+    }
+    // Far enough for any maintenance job, and keeps `now + period` from
+    // overflowing the clock.
+    if secs > MAX_PERIOD_SECS {
+        return Err(anyhow!("duration '{}' is too long (at most 366d)", s));
+    }
+    Ok(Duration::from_secs(secs))
+}
+
+impl FromStr for Schedule {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+        match s {
+            "off" | "disabled" => Ok(Schedule::Disabled),
+            "@hourly" => Ok(Schedule::Every(Duration::from_secs(3600))),
+            "@daily" => Ok(Schedule::Every(Duration::from_secs(86_400))),
+            _ => match s.strip_prefix("@every") {
+                Some(rest) => parse_duration(rest).map(Schedule::Every),
+                None => parse_duration(s).map(Schedule::Every),
+            },
+        }
+    }
+}
+
+struct Job {
+    name: String,
+    period: Duration,
+    run: JobFn,
+}
+
+/// Collects maintenance jobs and runs them on their schedules.
+pub struct Scheduler {
+    overrides: HashMap<String, String>,
+    jobs: Vec<Job>,
+}
+
+impl Scheduler {
+    /// Create a scheduler with per-job schedule overrides keyed by lowercase
+    /// job name (see `Config::schedules`).
+    pub fn new(overrides: HashMap<String, String>) -> Self {
+        Scheduler {
+            overrides,
+            jobs: Vec::new(),
+        }
+    }
+
+    /// Register a job under `name`, using `default` unless overridden.
+    pub fn register<F, Fut>(&mut self, name: &str, default: Schedule, job: F) -> Result<()>
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<()>> + Send + 'static,
+    {
+        let schedule = match self.overrides.get(name) {
+            Some(spec) => spec
+                .parse()
+                .with_context(|| format!("invalid schedule for job {}", name))?,
+            None => default,
+        };
+        match schedule {
+            Schedule::Disabled => info!("Maintenance job {} disabled", name),
+            Schedule::Every(period) => {
+                info!("Scheduled maintenance job {} every {:?}", name, period);
+                self.jobs.push(Job {
+                    name: name.to_string(),
+                    period,
+                    run: Arc::new(move || Box::pin(job()) as JobFuture),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Spawn one task per registered job. The first run happens one period
+    /// after startup; missed ticks are skipped rather than bunched up. A
+    /// task ends once `shutdown` is cancelled and its run in progress, if
+    /// any, is done.
+    pub fn spawn(self, shutdown: &CancellationToken) -> Vec<JoinHandle<()>> {
+        self.jobs
+            .into_iter()
+            .map(|job| {
+                let shutdown = shutdown.clone();
+                tokio::spawn(async move {
+                    let mut ticker = interval_at(Instant::now() + job.period, job.period);
+                    ticker.set_missed_tick_behavior(MissedTickBehavior::Skip);
+                    loop {
+                        tokio::select! {
+                            _ = ticker.tick() => {}
+                            _ = shutdown.cancelled() => return,
+                        }
+                        let started = Instant::now();
+                        match (job.run)().await {
+                            Ok(()) => debug!(
+                                "Maintenance job {} finished in {:?}",
+                                job.name,
+                                started.elapsed()
+                            ),
+                            Err(e) => warn!("Maintenance job {} failed: {:?}", job.name, e),
+                        }
+                    }
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn test_parse_schedules() {
+        assert_eq!(
+            "@every 30s".parse::<Schedule>().unwrap(),
+            Schedule::Every(Duration::from_secs(30))
+        );
+        assert_eq!(
+            "5m".parse::<Schedule>().unwrap(),
+            Schedule::Every(Duration::from_secs(300))
+        );
+        assert_eq!(
+            "@hourly".parse::<Schedule>().unwrap(),
+            Schedule::Every(Duration::from_secs(3600))
+        );
+        assert_eq!("off".parse::<Schedule>().unwrap(), Schedule::Disabled);
+    }
+
+    #[test]
+    fn test_parse_schedule_errors() {
+        assert!("@every".parse::<Schedule>().is_err());
+        assert!("10".parse::<Schedule>().is_err());
+        assert!("10w".parse::<Schedule>().is_err());
+        assert!("0s".parse::<Schedule>().is_err());
+        let err = "@every 18446744073709551615d"
+            .parse::<Schedule>()
+            .unwrap_err();
+        assert!(err.to_string().contains("too long"), "{}", err);
+        assert!("@every 400d".parse::<Schedule>().is_err());
+    }
+
+    #[test]
+    fn test_check_overrides() {
+        let mut overrides = HashMap::new();
+        overrides.insert("dedup_compaction".to_string(), "@every 5m".to_string());
+        assert!(check_overrides(&overrides).is_empty());
+
+        overrides.insert("dedup_compation".to_string(), "@every 5m".to_string());
+        overrides.insert("gap_audit".to_string(), "sometimes".to_string());
+        let errors: Vec<String> = check_overrides(&overrides)
+            .iter()
+            .map(|e| format!("{:#}", e))
+            .collect();
+        assert_eq!(errors.len(), 2, "{:?}", errors);
+        assert!(errors[0].contains("unknown maintenance job dedup_compation"));
+        assert!(errors[1].starts_with("SCHEDULE_GAP_AUDIT"));
+    }
+
+    #[test]
+    fn test_override_disables_job() {
+        let mut overrides = HashMap::new();
+        overrides.insert("compaction".to_string(), "off".to_string());
+        let mut scheduler = Scheduler::new(overrides);
+        scheduler
+            .register(
+                "compaction",
+                Schedule::Every(Duration::from_secs(1)),
+                || async { Ok(()) },
+            )
+            .unwrap();
+        assert!(scheduler.jobs.is_empty());
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn test_job_runs_on_schedule() {
+        tokio::time::pause();
+
+        let runs = Arc::new(AtomicUsize::new(0));
+        let mut scheduler = Scheduler::new(HashMap::new());
+        {
+            let runs = Arc::clone(&runs);
+            scheduler
+                .register(
+                    "tick",
+                    Schedule::Every(Duration::from_secs(60)),
+                    move || {
+                        let runs = Arc::clone(&runs);
+                        async move {
+                            runs.fetch_add(1, Ordering::SeqCst);
+                            Ok(())
+                        }
+                    },
+                )
+                .unwrap();
+        }
+        let shutdown = CancellationToken::new();
+        let handles = scheduler.spawn(&shutdown);
+
+        tokio::time::sleep(Duration::from_secs(150)).await;
+        assert_eq!(runs.load(Ordering::SeqCst), 2);
+
+        // Shutting down ends the tasks before the next run.
+        shutdown.cancel();
+        for h in handles {
+            h.await.unwrap();
+        }
+        assert_eq!(runs.load(Ordering::SeqCst), 2);
+    }
+}
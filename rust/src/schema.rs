@@ -0,0 +1,1040 @@
+//! Machine-readable description of the published event payload, and the
+//! TypeScript/zod generator built on it (`tracker typescript`).
+//!
+//! The description is kept next to the serde structs rather than derived
+//! from them; the tests below serialize a fully populated event and fail if
+//! the two ever disagree, so a schema change can't ship without updating the
+//! generated types.
+use std::fmt::Write;
+
+#[derive(Debug, Clone, Copy)]
+enum Ty {
+    String,
+    Integer,
+    Bool,
+    StringArray,
+    Object(&'static str, &'static [Field]),
+    ObjectArray(&'static str, &'static [Field]),
+}
+
+#[derive(Debug)]
+struct Field {
+    name: &'static str,
+    ty: Ty,
+    /// Omitted from the payload when empty/absent.
+    optional: bool,
+    doc: &'static str,
+}
+
+const fn field(name: &'static str, ty: Ty, optional: bool, doc: &'static str) -> Field {
+    Field {
+        name,
+        ty,
+        optional,
+        doc,
+    }
+}
+
+const TOKEN: &[Field] = &[
+    field(
+        "address",
+        Ty::String,
+        false,
+        "Contract address or SPL mint.",
+    ),
+    field("symbol", Ty::String, false, "`UNKNOWN` if unreadable."),
+    field("decimals", Ty::Integer, false, ""),
+];
+
+const GAP: &[Field] = &[
+    field("from_block", Ty::Integer, false, ""),
+    field("to_block", Ty::Integer, false, ""),
+];
+
+const HEARTBEAT: &[Field] = &[
+    field("address", Ty::String, false, "Watched address."),
+    field(
+        "last_activity_at",
+        Ty::String,
+        true,
+        "RFC 3339; absent if no activity since the listener started.",
+    ),
+    field("last_tx_hash", Ty::String, true, ""),
+    field(
+        "checkpoint",
+        Ty::Integer,
+        true,
+        "Last processed block (Ethereum) or slot (Solana).",
+    ),
+];
+
+const POSITION: &[Field] = &[
+    field(
+        "asset",
+        Ty::String,
+        false,
+        "Logical asset, or the symbol if it isn't mapped.",
+    ),
+    field("balance", Ty::String, false, "In whole units, e.g. `1.5`."),
+    field(
+        "price",
+        Ty::String,
+        true,
+        "Configured price per whole unit.",
+    ),
+    field(
+        "value",
+        Ty::String,
+        true,
+        "`balance` × `price`, to two decimals.",
+    ),
+];
+
+const PORTFOLIO: &[Field] = &[
+    field("name", Ty::String, false, ""),
+    field(
+        "block",
+        Ty::Integer,
+        true,
+        "Ethereum block the balances were read at.",
+    ),
+    field(
+        "slot",
+        Ty::Integer,
+        true,
+        "Solana slot the balances were read at.",
+    ),
+    field(
+        "total_value",
+        Ty::String,
+        false,
+        "Sum of the priced positions' values.",
+    ),
+    field(
+        "positions",
+        Ty::ObjectArray("Position", POSITION),
+        false,
+        "",
+    ),
+];
+
+const ALERT: &[Field] = &[
+    field(
+        "rule",
+        Ty::String,
+        false,
+        "`cold_storage_outgoing`, `liquidation_risk`, `stuck_transaction` or `fee_spike`.",
+    ),
+    field(
+        "severity",
+        Ty::String,
+        false,
+        "`low`, `medium`, `high` or `critical`.",
+    ),
+    field("address", Ty::String, false, "Address the rule fired for."),
+    field("label", Ty::String, true, "Its address book label."),
+];
+
+const SWEEP: &[Field] = &[
+    field(
+        "destination",
+        Ty::String,
+        false,
+        "Address the transfers went to.",
+    ),
+    field(
+        "sources",
+        Ty::StringArray,
+        false,
+        "Watched addresses that sent, in order of their first transfer.",
+    ),
+    field(
+        "tx_hashes",
+        Ty::StringArray,
+        false,
+        "Transactions of the sweep.",
+    ),
+    field("window_secs", Ty::Integer, false, "Detection window."),
+];
+
+const VALIDATOR: &[Field] = &[
+    field(
+        "change",
+        Ty::String,
+        false,
+        "`delinquent`, `recovered`, `commission_changed` or `stake_changed`.",
+    ),
+    field("vote_account", Ty::String, false, ""),
+    field(
+        "identity",
+        Ty::String,
+        false,
+        "Validator identity (node) address.",
+    ),
+    field(
+        "delinquent",
+        Ty::Bool,
+        false,
+        "Whether the validator is delinquent now.",
+    ),
+    field("commission", Ty::Integer, false, "Commission in percent."),
+    field(
+        "previous_commission",
+        Ty::Integer,
+        true,
+        "`commission_changed` only.",
+    ),
+    field("activated_stake", Ty::String, false, "In lamports."),
+    field(
+        "previous_activated_stake",
+        Ty::String,
+        true,
+        "`stake_changed` only.",
+    ),
+    field(
+        "last_vote",
+        Ty::Integer,
+        false,
+        "Last slot the validator voted on.",
+    ),
+];
+
+const LIQUID_STAKING: &[Field] = &[
+    field(
+        "protocol",
+        Ty::String,
+        false,
+        "`lido`, `marinade` or `jito`.",
+    ),
+    field(
+        "underlying_symbol",
+        Ty::String,
+        false,
+        "The staked asset, `ETH` or `SOL`.",
+    ),
+    field("underlying_decimals", Ty::Integer, false, ""),
+    field(
+        "underlying_value",
+        Ty::String,
+        true,
+        "Amount staked or paid out, in base units; absent when paid out later.",
+    ),
+];
+
+const LENDING: &[Field] = &[
+    field("protocol", Ty::String, false, "`aave_v3` or `compound_v2`."),
+    field(
+        "market",
+        Ty::String,
+        false,
+        "The Aave pool or Compound cToken that emitted the log.",
+    ),
+    field(
+        "account",
+        Ty::String,
+        false,
+        "Whose position changed: the supplier, borrower or liquidated account.",
+    ),
+    field(
+        "collateral_asset",
+        Ty::String,
+        true,
+        "Liquidations: the collateral seized, an Aave reserve or a Compound cToken.",
+    ),
+    field(
+        "collateral_value",
+        Ty::String,
+        true,
+        "Liquidations: the collateral seized, in base units.",
+    ),
+    field(
+        "borrow_rate",
+        Ty::String,
+        true,
+        "Aave borrows: the loan's rate, in ray (1e27 = 100% a year).",
+    ),
+    field(
+        "account_debt",
+        Ty::String,
+        true,
+        "Compound: the account's debt in the market after the action, in base units.",
+    ),
+    field(
+        "total_collateral_base",
+        Ty::String,
+        true,
+        "Aave: the account's collateral across the pool, in USD with 8 decimals.",
+    ),
+    field(
+        "total_debt_base",
+        Ty::String,
+        true,
+        "Aave: the account's debt across the pool, in USD with 8 decimals.",
+    ),
+    field(
+        "health_factor",
+        Ty::String,
+        true,
+        "Aave: the account's health factor with 18 decimals, liquidatable below 1; absent \
+         without debt.",
+    ),
+];
+
+const EFFECT: &[Field] = &[
+    field("kind", Ty::String, false, "`transfer` or `approval`."),
+    field("token", Ty::String, false, "The token contract."),
+    field(
+        "from",
+        Ty::String,
+        false,
+        "Sender, or owner of an approval.",
+    ),
+    field(
+        "to",
+        Ty::String,
+        false,
+        "Recipient, or spender of an approval.",
+    ),
+    field("value", Ty::String, false, "Raw amount, or allowance."),
+];
+
+const SIMULATION: &[Field] = &[
+    field(
+        "method",
+        Ty::String,
+        false,
+        "`trace` (`debug_traceCall`) or `call` (`eth_call`, without gas or effects).",
+    ),
+    field("success", Ty::Bool, false, ""),
+    field(
+        "error",
+        Ty::String,
+        true,
+        "Why it would fail, e.g. `execution reverted`.",
+    ),
+    field(
+        "revert_reason",
+        Ty::String,
+        true,
+        "The `Error(string)` message of a revert.",
+    ),
+    field("gas_used", Ty::Integer, true, ""),
+    field(
+        "effects",
+        Ty::ObjectArray("Effect", EFFECT),
+        true,
+        "ERC-20 transfers and approvals the transaction would emit.",
+    ),
+];
+
+const STUCK: &[Field] = &[
+    field("reason", Ty::String, false, "`pending` or `nonce_gap`."),
+    field(
+        "nonce",
+        Ty::Integer,
+        false,
+        "The nonce that isn't getting mined, or the first missing one.",
+    ),
+    field("confirmed_nonce", Ty::Integer, false, ""),
+    field("pending_nonce", Ty::Integer, false, ""),
+    field(
+        "pending_secs",
+        Ty::Integer,
+        true,
+        "`pending` only: how long transactions have waited at `confirmed_nonce`.",
+    ),
+    field(
+        "queued_nonce",
+        Ty::Integer,
+        true,
+        "`nonce_gap` only: the lowest nonce waiting for the gap.",
+    ),
+];
+
+const FEE_SPIKE: &[Field] = &[
+    field("gas_used", Ty::Integer, false, ""),
+    field(
+        "gas_price",
+        Ty::String,
+        false,
+        "Effective price per gas, in wei.",
+    ),
+    field(
+        "base_fee",
+        Ty::String,
+        false,
+        "Base fee per gas of the block, in wei.",
+    ),
+    field("fee", Ty::String, false, "`gas_used` times `gas_price`."),
+    field(
+        "overpayment",
+        Ty::String,
+        false,
+        "What was paid above the base fee, in wei.",
+    ),
+];
+
+const SUBSTRATE: &[Field] = &[
+    field("block_number", Ty::Integer, false, ""),
+    field("block_hash", Ty::String, false, ""),
+    field(
+        "event_index",
+        Ty::Integer,
+        false,
+        "Position of the event among its block's events.",
+    ),
+    field(
+        "extrinsic_index",
+        Ty::Integer,
+        true,
+        "Position of the emitting extrinsic in its block; absent for events outside one.",
+    ),
+    field(
+        "era",
+        Ty::Integer,
+        true,
+        "Active staking era, on chains with staking.",
+    ),
+];
+
+const EXPLORER_URLS: &[Field] = &[
+    field("tx", Ty::String, true, ""),
+    field("from", Ty::String, true, ""),
+    field("to", Ty::String, true, ""),
+];
+
+const EXPECTATION: &[Field] = &[
+    field("id", Ty::String, false, ""),
+    field("chain", Ty::String, false, ""),
+    field("address", Ty::String, false, "Receiving address."),
+    field(
+        "token",
+        Ty::String,
+        true,
+        "Token contract; absent for native.",
+    ),
+    field(
+        "min_amount",
+        Ty::String,
+        true,
+        "Smallest unit, as a decimal string.",
+    ),
+    field(
+        "amount",
+        Ty::String,
+        true,
+        "Exact amount, after fingerprinting; set instead of `min_amount`.",
+    ),
+    field(
+        "memo",
+        Ty::String,
+        true,
+        "Solana expectations: the memo the payment must carry.",
+    ),
+    field("deadline", Ty::String, false, "RFC 3339."),
+];
+
+const EVENT: &[Field] = &[
+    field(
+        "event_id",
+        Ty::String,
+        false,
+        "Stable id, also the dedup key.",
+    ),
+    field(
+        "chain",
+        Ty::String,
+        false,
+        "`ethereum`, `solana`, an `[[evm_networks]]` chain such as `polygon`, `tron`, or a \
+         `[[substrate_networks]]` chain such as `polkadot`.",
+    ),
+    field(
+        "network",
+        Ty::String,
+        false,
+        "e.g. `mainnet`, `sepolia`, `devnet`.",
+    ),
+    field(
+        "sequence",
+        Ty::Integer,
+        true,
+        "1, 2, 3, … per chain and network, across restarts; a jump means missed events.",
+    ),
+    field(
+        "region",
+        Ty::String,
+        true,
+        "`REGION` of the publishing listener, when set.",
+    ),
+    field(
+        "instance_id",
+        Ty::String,
+        true,
+        "`INSTANCE_ID` of the publishing listener.",
+    ),
+    field(
+        "tx_hash",
+        Ty::String,
+        false,
+        "Transaction hash, or signature on Solana.",
+    ),
+    field(
+        "timestamp",
+        Ty::String,
+        false,
+        "Unix seconds on Ethereum, RFC 3339 on Solana.",
+    ),
+    field("from", Ty::String, false, ""),
+    field("to", Ty::String, false, ""),
+    field(
+        "value",
+        Ty::String,
+        false,
+        "Amount in the smallest unit, as a decimal string.",
+    ),
+    field(
+        "asset_symbol",
+        Ty::String,
+        true,
+        "Asset `value` is in: the token's symbol, or the chain's native currency.",
+    ),
+    field("asset_decimals", Ty::Integer, true, ""),
+    field(
+        "value_formatted",
+        Ty::String,
+        true,
+        "`value` scaled by `asset_decimals`, e.g. `1.5`.",
+    ),
+    field(
+        "logical_asset",
+        Ty::String,
+        true,
+        "Cross-chain asset the token or native currency is mapped to in `[[assets]]`.",
+    ),
+    field(
+        "event_type",
+        Ty::String,
+        false,
+        "`transfer`, `erc20_transfer`, `solana_tx`, `gap_repaired`, `address_heartbeat`, \
+         `expected_transfer_received`, `expected_transfer_missed`, `transaction_retracted`, \
+         `portfolio_snapshot`, `unexpected_activity`, `sweep_detected`, `validator_event`, \
+         `lst_mint`, `lst_redeem`, `lending_supply`, `lending_borrow`, `lending_repay`, \
+         `lending_liquidation`, `liquidation_risk`, `pending_transfer`, `stuck_transaction` or \
+         `fee_spike`.",
+    ),
+    field("slot", Ty::Integer, true, "Solana slot."),
+    field(
+        "block_number",
+        Ty::Integer,
+        true,
+        "Block of an EVM transfer, ERC-20 or lending event, or of a Tron or Substrate \
+         event.",
+    ),
+    field(
+        "log_index",
+        Ty::Integer,
+        true,
+        "Index of the log within its block (ERC-20 and lending events), or of the event among \
+         its block's events on Substrate chains.",
+    ),
+    field(
+        "token",
+        Ty::Object("Token", TOKEN),
+        true,
+        "ERC-20 / SPL token transfers only.",
+    ),
+    field(
+        "memo",
+        Ty::String,
+        true,
+        "SPL Memo text of a Solana transaction.",
+    ),
+    field(
+        "selector",
+        Ty::String,
+        true,
+        "4-byte function selector of an Ethereum transaction's calldata.",
+    ),
+    field(
+        "method",
+        Ty::String,
+        true,
+        "Signature for `selector`, e.g. `transfer(address,uint256)`, if well known.",
+    ),
+    field(
+        "spam",
+        Ty::Bool,
+        true,
+        "Present (true) when the spam heuristics flagged the event.",
+    ),
+    field("spam_reasons", Ty::StringArray, true, ""),
+    field(
+        "decode_warnings",
+        Ty::StringArray,
+        true,
+        "What was non-standard about an ERC-20 `Transfer` log: `missing_data`, `short_data`, \
+         `extra_data`, `dirty_address_topic`, `decimals_overflow`.",
+    ),
+    field(
+        "token_behavior",
+        Ty::String,
+        true,
+        "`fee_on_transfer` or `rebasing`, for tokens the chain registry lists as such.",
+    ),
+    field(
+        "received_value",
+        Ty::String,
+        true,
+        "Raw amount the recipient of a fee-on-transfer token received, when known.",
+    ),
+    field(
+        "gap",
+        Ty::Object("GapRange", GAP),
+        true,
+        "`gap_repaired` events only.",
+    ),
+    field(
+        "heartbeat",
+        Ty::Object("Heartbeat", HEARTBEAT),
+        true,
+        "`address_heartbeat` events only.",
+    ),
+    field(
+        "expectation",
+        Ty::Object("Expectation", EXPECTATION),
+        true,
+        "`expected_transfer_*` events only.",
+    ),
+    field(
+        "portfolio",
+        Ty::Object("Portfolio", PORTFOLIO),
+        true,
+        "`portfolio_snapshot` events only.",
+    ),
+    field(
+        "alert",
+        Ty::Object("Alert", ALERT),
+        true,
+        "`unexpected_activity` and `liquidation_risk` events only.",
+    ),
+    field(
+        "sweep",
+        Ty::Object("Sweep", SWEEP),
+        true,
+        "`sweep_detected` events only.",
+    ),
+    field(
+        "validator",
+        Ty::Object("Validator", VALIDATOR),
+        true,
+        "`validator_event` events only.",
+    ),
+    field(
+        "liquid_staking",
+        Ty::Object("LiquidStaking", LIQUID_STAKING),
+        true,
+        "`lst_mint` and `lst_redeem` events only.",
+    ),
+    field(
+        "lending",
+        Ty::Object("Lending", LENDING),
+        true,
+        "`lending_*` and `liquidation_risk` events only.",
+    ),
+    field(
+        "simulation",
+        Ty::Object("Simulation", SIMULATION),
+        true,
+        "`pending_transfer` events with `ETH_PENDING_SIMULATION` only.",
+    ),
+    field(
+        "stuck",
+        Ty::Object("Stuck", STUCK),
+        true,
+        "`stuck_transaction` events only.",
+    ),
+    field(
+        "fee_spike",
+        Ty::Object("FeeSpike", FEE_SPIKE),
+        true,
+        "`fee_spike` events only.",
+    ),
+    field(
+        "substrate",
+        Ty::Object("SubstrateBlock", SUBSTRATE),
+        true,
+        "`[[substrate_networks]]` chains only.",
+    ),
+    field(
+        "backfill",
+        Ty::Bool,
+        true,
+        "Present (true) on history published by the cold start bootstrap.",
+    ),
+    field(
+        "explorer_url",
+        Ty::Object("ExplorerUrls", EXPLORER_URLS),
+        true,
+        "Block explorer links, when the chain's explorer is known.",
+    ),
+    field(
+        "traceparent",
+        Ty::String,
+        false,
+        "W3C trace context of the listener's publish span.",
+    ),
+];
+
+/// Top-level payload keys, in wire order.
+#[cfg(test)]
+pub fn payload_fields() -> Vec<&'static str> {
+    EVENT.iter().map(|f| f.name).collect()
+}
+
+fn ts_type(ty: Ty) -> String {
+    match ty {
+        Ty::String => "string".into(),
+        Ty::Integer => "number".into(),
+        Ty::Bool => "boolean".into(),
+        Ty::StringArray => "string[]".into(),
+        Ty::Object(name, _) => name.into(),
+        Ty::ObjectArray(name, _) => format!("{}[]", name),
+    }
+}
+
+fn zod_type(ty: Ty) -> String {
+    match ty {
+        Ty::String => "z.string()".into(),
+        Ty::Integer => "z.number().int().nonnegative()".into(),
+        Ty::Bool => "z.boolean()".into(),
+        Ty::StringArray => "z.array(z.string())".into(),
+        Ty::Object(name, _) => format!("{}Schema", name),
+        Ty::ObjectArray(name, _) => format!("z.array({}Schema)", name),
+    }
+}
+
+fn write_interface(out: &mut String, name: &str, fields: &[Field]) {
+    writeln!(out, "export interface {} {{", name).unwrap();
+    for f in fields {
+        if !f.doc.is_empty() {
+            writeln!(out, "  /** {} */", f.doc).unwrap();
+        }
+        let opt = if f.optional { "?" } else { "" };
+        writeln!(out, "  {}{}: {};", f.name, opt, ts_type(f.ty)).unwrap();
+    }
+    out.push_str("}\n\n");
+}
+
+fn write_zod(out: &mut String, name: &str, fields: &[Field]) {
+    writeln!(out, "export const {}Schema = z.object({{", name).unwrap();
+    for f in fields {
+        let opt = if f.optional { ".optional()" } else { "" };
+        writeln!(out, "  {}: {}{},", f.name, zod_type(f.ty), opt).unwrap();
+    }
+    // Consumers must tolerate fields added in later versions.
+    out.push_str("}).passthrough();\n\n");
+}
+
+/// Render TypeScript interfaces for the event payload and, with `zod`, zod
+/// validators for them.
+pub fn typescript(zod: bool) -> String {
+    let mut out = String::from(
+        "// Generated by `tracker typescript` from rust/src/schema.rs. Do not edit.\n\n",
+    );
+    if zod {
+        out.push_str("import { z } from \"zod\";\n\n");
+    }
+    let types: [(&str, &[Field]); 18] = [
+        ("Token", TOKEN),
+        ("GapRange", GAP),
+        ("Heartbeat", HEARTBEAT),
+        ("Expectation", EXPECTATION),
+        ("Position", POSITION),
+        ("Portfolio", PORTFOLIO),
+        ("Alert", ALERT),
+        ("Sweep", SWEEP),
+        ("Validator", VALIDATOR),
+        ("LiquidStaking", LIQUID_STAKING),
+        ("Lending", LENDING),
+        ("Effect", EFFECT),
+        ("Simulation", SIMULATION),
+        ("Stuck", STUCK),
+        ("FeeSpike", FEE_SPIKE),
+        ("SubstrateBlock", SUBSTRATE),
+        ("ExplorerUrls", EXPLORER_URLS),
+        ("Event", EVENT),
+    ];
+    for (name, fields) in types {
+        write_interface(&mut out, name, fields);
+    }
+    if zod {
+        // Dependencies first: EventSchema references the nested schemas.
+        for (name, fields) in types {
+            write_zod(&mut out, name, fields);
+        }
+    }
+    out.truncate(out.trim_end().len());
+    out.push('\n');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::address_book::{Alert, Severity};
+    use crate::expectations::Expectation;
+    use crate::explorer::ExplorerUrls;
+    use crate::fee_spike::FeeSpike;
+    use crate::gaps::GapRange;
+    use crate::heartbeat::Heartbeat;
+    use crate::lending::Lending;
+    use crate::liquid_staking::LiquidStaking;
+    use crate::pending::{Effect, EffectKind, Simulation, SimulationMethod};
+    use crate::portfolio::{Position, Snapshot};
+    use crate::publisher::PAYLOAD_FIELDS;
+    use crate::stuck::{Stuck, StuckReason};
+    use crate::substrate::SubstrateBlock;
+    use crate::sweeps::Sweep;
+    use crate::validators::{Validator, ValidatorChange};
+    use crate::{Event, Token};
+    use serde_json::Value;
+
+    fn full_event() -> Value {
+        let event = Event {
+            sequence: Some(1),
+            region: Some("eu-west".into()),
+            instance_id: Some("tracker-eu-1".into()),
+            slot: Some(1),
+            block_number: Some(19000000),
+            log_index: Some(3),
+            asset_symbol: Some("USDC".into()),
+            asset_decimals: Some(6),
+            value_formatted: Some("1.5".into()),
+            logical_asset: Some("USDC".into()),
+            token: Some(Token::default()),
+            memo: Some("deposit 1042".into()),
+            selector: Some("0xa9059cbb".into()),
+            method: Some("transfer(address,uint256)".into()),
+            spam: true,
+            spam_reasons: vec!["zero_value".into()],
+            decode_warnings: vec![crate::erc20::DecodeWarning::ExtraData],
+            token_behavior: Some(crate::erc20::TokenBehavior::FeeOnTransfer),
+            received_value: Some("970".into()),
+            gap: Some(GapRange {
+                from_block: 1,
+                to_block: 2,
+            }),
+            heartbeat: Some(Heartbeat {
+                address: "0x01".into(),
+                last_activity_at: Some("2025-01-01T00:00:00+00:00".into()),
+                last_tx_hash: Some("0xabc".into()),
+                checkpoint: Some(1),
+            }),
+            expectation: Some(Expectation {
+                id: "order-1".into(),
+                chain: "ethereum".into(),
+                address: "0x01".into(),
+                token: Some("0x02".into()),
+                min_amount: Some("1".into()),
+                amount: Some("2".into()),
+                memo: Some("deposit 1042".into()),
+                deadline: chrono::DateTime::from_timestamp(0, 0).unwrap(),
+            }),
+            portfolio: Some(Snapshot {
+                name: "treasury".into(),
+                block: Some(1),
+                slot: Some(2),
+                total_value: "3.00".into(),
+                positions: vec![Position {
+                    asset: "USDC".into(),
+                    balance: "3".into(),
+                    price: Some("1".into()),
+                    value: Some("3.00".into()),
+                }],
+            }),
+            alert: Some(Alert {
+                rule: "cold_storage_outgoing".into(),
+                severity: Severity::Critical,
+                address: "0x01".into(),
+                label: Some("Treasury cold wallet".into()),
+            }),
+            sweep: Some(Sweep {
+                destination: "0x02".into(),
+                sources: vec!["0x01".into()],
+                tx_hashes: vec!["0xabc".into()],
+                window_secs: 600,
+            }),
+            validator: Some(Validator {
+                change: ValidatorChange::CommissionChanged,
+                vote_account: "Vote1".into(),
+                identity: "Node1".into(),
+                delinquent: false,
+                commission: 10,
+                previous_commission: Some(5),
+                activated_stake: "1000000000000".into(),
+                previous_activated_stake: Some("900000000000".into()),
+                last_vote: 250_000_000,
+            }),
+            liquid_staking: Some(LiquidStaking {
+                protocol: "lido".into(),
+                underlying_symbol: "ETH".into(),
+                underlying_decimals: 18,
+                underlying_value: Some("1000000000000000000".into()),
+            }),
+            lending: Some(Lending {
+                protocol: "aave_v3".into(),
+                market: "0x87870bca3f3fd6335c3f4ce8392d69350b4fa4e2".into(),
+                account: "0x01".into(),
+                collateral_asset: Some("0x03".into()),
+                collateral_value: Some("2".into()),
+                borrow_rate: Some("50000000000000000000000000".into()),
+                account_debt: Some("750".into()),
+                total_collateral_base: Some("1000000000000".into()),
+                total_debt_base: Some("400000000000".into()),
+                health_factor: Some("2062500000000000000".into()),
+            }),
+            simulation: Some(Simulation {
+                method: SimulationMethod::Trace,
+                success: false,
+                error: Some("execution reverted".into()),
+                revert_reason: Some("insufficient balance".into()),
+                gas_used: Some(21_000),
+                effects: vec![Effect {
+                    kind: EffectKind::Approval,
+                    token: "0x03".into(),
+                    from: "0x01".into(),
+                    to: "0x02".into(),
+                    value: "5".into(),
+                }],
+            }),
+            stuck: Some(Stuck {
+                reason: StuckReason::Pending,
+                nonce: 5,
+                confirmed_nonce: 5,
+                pending_nonce: 7,
+                pending_secs: Some(600),
+                queued_nonce: Some(9),
+            }),
+            fee_spike: Some(FeeSpike {
+                gas_used: 21_000,
+                gas_price: "400000000000".into(),
+                base_fee: "20000000000".into(),
+                fee: "8400000000000000".into(),
+                overpayment: "7980000000000000".into(),
+            }),
+            substrate: Some(SubstrateBlock {
+                block_number: 18_000_000,
+                block_hash: "0xabc".into(),
+                event_index: 7,
+                extrinsic_index: Some(2),
+                era: Some(1_234),
+            }),
+            backfill: true,
+            explorer_url: Some(ExplorerUrls {
+                tx: Some("https://etherscan.io/tx/0xabc".into()),
+                from: Some("https://etherscan.io/address/0x01".into()),
+                to: Some("https://etherscan.io/address/0x02".into()),
+            }),
+            ..Default::default()
+        };
+        let mut value = serde_json::to_value(&event).unwrap();
+        value["traceparent"] = Value::from("00-…-01");
+        value
+    }
+
+    fn keys(value: &Value) -> Vec<String> {
+        let mut keys: Vec<String> = value.as_object().unwrap().keys().cloned().collect();
+        keys.sort();
+        keys
+    }
+
+    fn names(fields: &[Field]) -> Vec<String> {
+        let mut names: Vec<String> = fields.iter().map(|f| f.name.to_string()).collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn test_schema_matches_serialized_event() {
+        let event = full_event();
+        assert_eq!(keys(&event), names(EVENT));
+        assert_eq!(keys(&event["token"]), names(TOKEN));
+        assert_eq!(keys(&event["gap"]), names(GAP));
+        assert_eq!(keys(&event["heartbeat"]), names(HEARTBEAT));
+        assert_eq!(keys(&event["expectation"]), names(EXPECTATION));
+        assert_eq!(keys(&event["portfolio"]), names(PORTFOLIO));
+        assert_eq!(keys(&event["portfolio"]["positions"][0]), names(POSITION));
+        assert_eq!(keys(&event["alert"]), names(ALERT));
+        assert_eq!(keys(&event["sweep"]), names(SWEEP));
+        assert_eq!(keys(&event["validator"]), names(VALIDATOR));
+        assert_eq!(keys(&event["liquid_staking"]), names(LIQUID_STAKING));
+        assert_eq!(keys(&event["lending"]), names(LENDING));
+        assert_eq!(keys(&event["simulation"]), names(SIMULATION));
+        assert_eq!(keys(&event["simulation"]["effects"][0]), names(EFFECT));
+        assert_eq!(keys(&event["stuck"]), names(STUCK));
+        assert_eq!(keys(&event["fee_spike"]), names(FEE_SPIKE));
+        assert_eq!(keys(&event["substrate"]), names(SUBSTRATE));
+        assert_eq!(keys(&event["explorer_url"]), names(EXPLORER_URLS));
+        assert_eq!(payload_fields(), PAYLOAD_FIELDS);
+    }
+
+    #[test]
+    fn test_optional_fields_are_the_omittable_ones() {
+        let minimal = serde_json::to_value(Event::default()).unwrap();
+        for f in EVENT.iter().filter(|f| f.name != "traceparent") {
+            assert_eq!(
+                minimal.get(f.name).is_none(),
+                f.optional,
+                "optional flag wrong for {}",
+                f.name
+            );
+        }
+    }
+
+    #[test]
+    fn test_event_type_doc_lists_every_type() {
+        let doc = EVENT.iter().find(|f| f.name == "event_type").unwrap().doc;
+        for event_type in crate::event_type::EventType::ALL {
+            assert!(
+                doc.contains(&format!("`{}`", event_type)),
+                "event_type doc is missing {}",
+                event_type
+            );
+        }
+    }
+
+    #[test]
+    fn test_proto_event_has_every_field() {
+        // proto/events.proto is compiled only with the grpc feature, so check
+        // its Event message against the schema here.
+        let proto = include_str!("../proto/events.proto");
+        let start = proto.find("message Event {").unwrap();
+        let message = &proto[start..start + proto[start..].find('}').unwrap()];
+        for f in EVENT {
+            assert!(
+                message
+                    .lines()
+                    .any(|line| line.split_whitespace().any(|word| word == f.name)),
+                "events.proto Event is missing {}",
+                f.name
+            );
+        }
+    }
+
+    #[test]
+    fn test_typescript_output() {
+        let ts = typescript(true);
+        assert!(ts.contains("import { z } from \"zod\";"));
+        assert!(ts.contains("export interface Event {"));
+        assert!(ts.contains("  token?: Token;\n"));
+        assert!(ts.contains("  spam_reasons: z.array(z.string()).optional(),\n"));
+        assert!(ts.contains("  positions: Position[];\n"));
+        assert!(ts.contains("  positions: z.array(PositionSchema),\n"));
+        assert!(ts.find("export const PositionSchema") < ts.find("export const PortfolioSchema"));
+        assert!(ts.find("export const TokenSchema") < ts.find("export const EventSchema"));
+
+        let plain = typescript(false);
+        assert!(!plain.contains("zod"));
+        assert!(plain.ends_with("}\n"));
+    }
+}
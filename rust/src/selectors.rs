@@ -0,0 +1,92 @@
+//! Function selectors of Ethereum contract calls (`selector` / `method`).
+//!
+//! Every transaction event whose calldata holds at least four bytes carries
+//! its selector. The signature is filled in from a built-in table of common
+//! token, NFT, WETH and DEX router methods; other selectors are published
+//! without one, and consumers can resolve them against their own ABIs.
+
+/// Selector and signature of well-known methods.
+const KNOWN_METHODS: &[(&str, &str)] = &[
+    ("0xa9059cbb", "transfer(address,uint256)"),
+    ("0x095ea7b3", "approve(address,uint256)"),
+    ("0x23b872dd", "transferFrom(address,address,uint256)"),
+    ("0x39509351", "increaseAllowance(address,uint256)"),
+    ("0xa457c2d7", "decreaseAllowance(address,uint256)"),
+    (
+        "0xd505accf",
+        "permit(address,address,uint256,uint256,uint8,bytes32,bytes32)",
+    ),
+    ("0x42842e0e", "safeTransferFrom(address,address,uint256)"),
+    (
+        "0xb88d4fde",
+        "safeTransferFrom(address,address,uint256,bytes)",
+    ),
+    (
+        "0xf242432a",
+        "safeTransferFrom(address,address,uint256,uint256,bytes)",
+    ),
+    ("0xa22cb465", "setApprovalForAll(address,bool)"),
+    ("0xd0e30db0", "deposit()"),
+    ("0x2e1a7d4d", "withdraw(uint256)"),
+    ("0xac9650d8", "multicall(bytes[])"),
+    ("0x5ae401dc", "multicall(uint256,bytes[])"),
+    ("0x3593564c", "execute(bytes,bytes[],uint256)"),
+    (
+        "0x38ed1739",
+        "swapExactTokensForTokens(uint256,uint256,address[],address,uint256)",
+    ),
+    (
+        "0x7ff36ab5",
+        "swapExactETHForTokens(uint256,address[],address,uint256)",
+    ),
+    (
+        "0x18cbafe5",
+        "swapExactTokensForETH(uint256,uint256,address[],address,uint256)",
+    ),
+];
+
+/// The selector of `input` as `0x` + 8 hex digits; `None` for plain
+/// transfers without calldata.
+pub fn selector(input: &[u8]) -> Option<String> {
+    let selector = input.get(..4)?;
+    Some(format!(
+        "0x{}",
+        selector
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect::<String>()
+    ))
+}
+
+/// The signature for a selector, if it is in the built-in table.
+pub fn method(selector: &str) -> Option<&'static str> {
+    KNOWN_METHODS
+        .iter()
+        .find(|(s, _)| *s == selector)
+        .map(|(_, signature)| *signature)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_known_selectors_match_their_signatures() {
+        for (sel, signature) in KNOWN_METHODS {
+            let id = ethers::utils::id(signature);
+            assert_eq!(selector(&id).as_deref(), Some(*sel), "{}", signature);
+        }
+    }
+
+    #[test]
+    fn test_selector_and_method() {
+        let calldata = [0xa9, 0x05, 0x9c, 0xbb, 0x00, 0x01];
+        let sel = selector(&calldata).unwrap();
+        assert_eq!(sel, "0xa9059cbb");
+        assert_eq!(method(&sel), Some("transfer(address,uint256)"));
+
+        assert_eq!(method("0xdeadbeef"), None);
+        assert_eq!(selector(&[]), None);
+        assert_eq!(selector(&[0xa9, 0x05, 0x9c]), None);
+    }
+}
@@ -0,0 +1,206 @@
+//! Per-chain event sequence numbers (`sequence` in the payload).
+//!
+//! Redis Pub/Sub drops messages for a subscriber that is disconnected or too
+//! slow, and nothing tells the subscriber. The publisher numbers each chain's
+//! events 1, 2, 3, … as it publishes them, so a consumer that sees 41 after
+//! 39 knows it missed one.
+//!
+//! Numbers are counted per `<chain>:<network>` and saved with the checkpoints
+//! (`CHECKPOINT_STORE`), so they carry on across restarts. Each periodic save
+//! reserves [`RESERVE`] numbers ahead of the last one handed out, and a
+//! restart continues after the reservation, so a crash never hands out a
+//! number twice: the sequence jumps ahead instead. A graceful shutdown saves
+//! the exact positions, so the sequence carries on without a gap. Events
+//! without a chain, such as portfolio snapshots, aren't numbered, and a
+//! number whose event fails to publish is given back unless a later one was
+//! handed out meanwhile.
+//!
+//! A destination with `chains` or `event_types` filters would see the
+//! numbers of the events it filters out as gaps. Its events are numbered in
+//! a sequence of its own instead, per `<chain>:<network>@<destination>`,
+//! counting only the events it receives.
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex as StdMutex};
+
+use crate::Event;
+
+/// Prefix of sequence entries among the checkpoint positions.
+pub const KEY_PREFIX: &str = "sequence:";
+
+/// Numbers reserved per sequence by each save: more than a chain publishes
+/// between two saves (10s).
+pub const RESERVE: u64 = 10_000;
+
+/// Last number handed out per `<chain>:<network>`; cheap to clone.
+#[derive(Clone, Debug, Default)]
+pub struct Sequences {
+    last: Arc<StdMutex<BTreeMap<String, u64>>>,
+    /// Set once nothing is numbered any more, so the last save needs no
+    /// reservation.
+    settled: Arc<AtomicBool>,
+}
+
+impl Sequences {
+    /// Number `event` in its chain's sequence.
+    pub fn assign(&self, event: &mut Event) {
+        if event.chain.is_empty() {
+            return;
+        }
+        let key = format!("{}:{}", event.chain, event.network);
+        let mut last = self.last.lock().unwrap();
+        let next = last.entry(key).or_insert(0);
+        *next += 1;
+        event.sequence = Some(*next);
+    }
+
+    /// Give back the number of `event`, which wasn't published, if it is
+    /// still the last one of its chain.
+    pub fn release(&self, event: &Event) {
+        let Some(sequence) = event.sequence else {
+            return;
+        };
+        let key = format!("{}:{}", event.chain, event.network);
+        let mut last = self.last.lock().unwrap();
+        if let Some(last) = last.get_mut(&key).filter(|last| **last == sequence) {
+            *last -= 1;
+        }
+    }
+
+    /// Next number of `event`'s chain in the sequence of `destination`, a
+    /// destination that filters events.
+    pub fn next_for(&self, destination: &str, event: &Event) -> Option<u64> {
+        if event.chain.is_empty() {
+            return None;
+        }
+        let key = format!("{}:{}@{}", event.chain, event.network, destination);
+        let mut last = self.last.lock().unwrap();
+        let next = last.entry(key).or_insert(0);
+        *next += 1;
+        Some(*next)
+    }
+
+    /// Continue from saved positions; entries without [`KEY_PREFIX`] are
+    /// ignored.
+    pub fn restore(&self, positions: &BTreeMap<String, u64>) {
+        let mut last = self.last.lock().unwrap();
+        for (key, seq) in positions {
+            if let Some(key) = key.strip_prefix(KEY_PREFIX) {
+                let last = last.entry(key.to_string()).or_insert(0);
+                *last = (*last).max(*seq);
+            }
+        }
+    }
+
+    /// Current positions, keyed with [`KEY_PREFIX`].
+    pub fn positions(&self) -> BTreeMap<String, u64> {
+        self.last
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(key, seq)| (format!("{}{}", KEY_PREFIX, key), *seq))
+            .collect()
+    }
+
+    /// Positions to save: the current ones plus [`RESERVE`], or exactly the
+    /// current ones once settled.
+    pub fn reserved(&self) -> BTreeMap<String, u64> {
+        let reserve = if self.settled.load(Ordering::Relaxed) {
+            0
+        } else {
+            RESERVE
+        };
+        let mut positions = self.positions();
+        positions.values_mut().for_each(|seq| *seq += reserve);
+        positions
+    }
+
+    /// Save exact positions from now on. Called on shutdown, once the
+    /// trackers have stopped publishing.
+    pub fn settle(&self) {
+        self.settled.store(true, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(chain: &str, network: &str) -> Event {
+        Event {
+            chain: chain.into(),
+            network: network.into(),
+            ..Default::default()
+        }
+    }
+
+    fn assign(sequences: &Sequences, chain: &str, network: &str) -> Option<u64> {
+        let mut event = event(chain, network);
+        sequences.assign(&mut event);
+        event.sequence
+    }
+
+    #[test]
+    fn test_sequences_per_chain() {
+        let sequences = Sequences::default();
+        assert_eq!(assign(&sequences, "ethereum", "mainnet"), Some(1));
+        assert_eq!(assign(&sequences, "ethereum", "mainnet"), Some(2));
+        assert_eq!(assign(&sequences, "solana", "mainnet"), Some(1));
+        assert_eq!(assign(&sequences, "ethereum", "sepolia"), Some(1));
+        assert_eq!(assign(&sequences, "", ""), None);
+    }
+
+    #[test]
+    fn test_destination_sequences() {
+        let sequences = Sequences::default();
+        assert_eq!(assign(&sequences, "ethereum", "mainnet"), Some(1));
+        let event = event("ethereum", "mainnet");
+        assert_eq!(sequences.next_for("alerts", &event), Some(1));
+        assert_eq!(assign(&sequences, "ethereum", "mainnet"), Some(2));
+        assert_eq!(sequences.next_for("alerts", &event), Some(2));
+        assert_eq!(sequences.next_for("alerts", &Event::default()), None);
+        assert_eq!(
+            sequences
+                .positions()
+                .get("sequence:ethereum:mainnet@alerts"),
+            Some(&2)
+        );
+    }
+
+    #[test]
+    fn test_sequences_restore() {
+        let before = Sequences::default();
+        for _ in 0..3 {
+            assign(&before, "ethereum", "mainnet");
+        }
+        let mut positions = before.positions();
+        assert_eq!(positions.get("sequence:ethereum:mainnet"), Some(&3));
+        positions.insert("ethereum:mainnet".into(), 19_000_000);
+
+        let after = Sequences::default();
+        after.restore(&positions);
+        assert_eq!(assign(&after, "ethereum", "mainnet"), Some(4));
+        assert_eq!(assign(&after, "solana", "mainnet"), Some(1));
+
+        // Saved positions reserve numbers until settled.
+        assert_eq!(
+            after.reserved().get("sequence:ethereum:mainnet"),
+            Some(&(4 + RESERVE))
+        );
+        after.settle();
+        assert_eq!(after.reserved().get("sequence:ethereum:mainnet"), Some(&4));
+    }
+
+    #[test]
+    fn test_release_gives_back_the_last_number() {
+        let sequences = Sequences::default();
+        let mut first = event("ethereum", "mainnet");
+        sequences.assign(&mut first);
+        sequences.release(&first);
+        assert_eq!(assign(&sequences, "ethereum", "mainnet"), Some(1));
+
+        // A later number was handed out: the released one stays a gap.
+        sequences.release(&first);
+        assert_eq!(assign(&sequences, "ethereum", "mainnet"), Some(2));
+    }
+}
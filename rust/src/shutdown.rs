@@ -0,0 +1,225 @@
+//! Graceful shutdown on SIGTERM and SIGINT (`SHUTDOWN_TIMEOUT_SECS`).
+//!
+//! A signal cancels the shutdown token the chain controls and the scheduler
+//! share. Then, within `SHUTDOWN_TIMEOUT_SECS` (default 25s, inside the 30s
+//! Docker and Kubernetes allow before killing the process):
+//!
+//! 1. the trackers finish the block, log or transaction in flight and
+//!    return, like they do on a pause; the maintenance jobs finish the run in
+//!    progress and start no new one;
+//! 2. the destination workers deliver the events already queued, and the
+//!    webhook acknowledgement state and the quarantine are saved;
+//! 3. the checkpoints are saved a last time, so a restart resumes where
+//!    tracking stopped instead of up to 10s (one `checkpoint_save` period)
+//!    earlier.
+//!
+//! Sinks open their Redis connections per delivery, so once the queues are
+//! empty none is left mid-command. At the deadline whatever is still running
+//! is abandoned with a warning. If events are still undelivered then, or
+//! held by the [kill switch](crate::kill_switch), which can't keep them
+//! across a restart, the last checkpoint save is skipped: the restart
+//! resumes from the previous one and publishes those events again, along
+//! with some that were delivered already. Events from before that save are
+//! lost. A second signal exits at once.
+use std::sync::Arc;
+
+use anyhow::Context;
+use tokio::sync::Mutex;
+use tokio::task::JoinHandle;
+use tokio::time::{timeout_at, Instant};
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info, warn};
+
+use crate::checkpoint;
+use crate::config::Config;
+use crate::publisher::Publisher;
+
+/// Default `SHUTDOWN_TIMEOUT_SECS`.
+pub const DEFAULT_TIMEOUT_SECS: u64 = 25;
+
+/// Wait for SIGTERM or SIGINT, and name it.
+async fn signal() -> std::io::Result<&'static str> {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        let mut terminate = signal(SignalKind::terminate())?;
+        tokio::select! {
+            _ = terminate.recv() => Ok("SIGTERM"),
+            res = tokio::signal::ctrl_c() => res.map(|()| "SIGINT"),
+        }
+    }
+    #[cfg(not(unix))]
+    tokio::signal::ctrl_c().await.map(|()| "Ctrl-C")
+}
+
+/// Cancel `shutdown` on the first signal, and exit on the second.
+pub fn listen(shutdown: CancellationToken) {
+    tokio::spawn(async move {
+        match signal().await {
+            Ok(name) => info!("{} received, shutting down", name),
+            Err(e) => {
+                error!("Can't listen for shutdown signals: {:?}", e);
+                return;
+            }
+        }
+        shutdown.cancel();
+        if let Ok(name) = signal().await {
+            warn!("{} received again, exiting without flushing", name);
+            std::process::exit(130);
+        }
+    });
+}
+
+/// Once the trackers have stopped: wait until `deadline` for the jobs' runs
+/// in progress and the destination queues, then save the checkpoints unless
+/// events are left undelivered.
+pub async fn flush(
+    cfg: &Config,
+    deadline: Instant,
+    jobs: Vec<JoinHandle<()>>,
+    publisher: &Publisher,
+    last_eth_block: &Arc<Mutex<Option<u64>>>,
+    last_sol_slot: &Arc<Mutex<Option<u64>>>,
+    evm: &[checkpoint::EvmCheckpoint],
+) -> anyhow::Result<()> {
+    for job in jobs {
+        if timeout_at(deadline, job).await.is_err() {
+            warn!("Shutdown timeout reached with maintenance jobs still running");
+            break;
+        }
+    }
+    let mut undelivered = 0;
+    for (destination, pending) in publisher.drain(deadline).await {
+        warn!(
+            "Shutdown timeout reached with {} events undelivered to destination {}",
+            pending, destination
+        );
+        undelivered += pending;
+    }
+    let held = publisher.held();
+    if held > 0 {
+        warn!("Shutting down with {} events held by the kill switch", held);
+        undelivered += held;
+    }
+    publisher.webhooks().save_acks();
+    publisher.quarantine().save();
+    publisher.sequences().settle();
+    if undelivered > 0 {
+        warn!(
+            "Not saving checkpoints, so the {} undelivered events are tracked again after a restart",
+            undelivered
+        );
+        return Ok(());
+    }
+    checkpoint::save(
+        cfg,
+        last_eth_block,
+        last_sol_slot,
+        evm,
+        publisher.sequences(),
+    )
+    .await
+    .context("failed to save checkpoints on shutdown")?;
+    info!(
+        "Shutdown complete at ETH block {:?}, SOL slot {:?}",
+        *last_eth_block.lock().await,
+        *last_sol_slot.lock().await
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::checkpoint::{CheckpointConfig, CheckpointStore};
+    use crate::trace_context::TraceContext;
+    use crate::Event;
+    use tokio::time::Duration;
+
+    #[tokio::test]
+    async fn test_flush_waits_for_jobs_and_queues() {
+        let cfg = Config {
+            checkpoints: CheckpointConfig {
+                store: CheckpointStore::None,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let (publisher, mut rx) = Publisher::in_memory();
+        publisher
+            .publish(
+                &Event {
+                    event_id: "eth:0xabc".into(),
+                    chain: "ethereum".into(),
+                    ..Default::default()
+                },
+                TraceContext::new_root(),
+            )
+            .await
+            .unwrap();
+        // A job run in progress, and a worker delivering the queued event.
+        let job = tokio::spawn(tokio::time::sleep(Duration::from_millis(20)));
+        let worker = tokio::spawn(async move { drop(rx.recv().await) });
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        flush(
+            &cfg,
+            deadline,
+            vec![job],
+            &publisher,
+            &Arc::default(),
+            &Arc::default(),
+            &[],
+        )
+        .await
+        .unwrap();
+        assert!(publisher.drain(Instant::now()).await.is_empty());
+        worker.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_flush_keeps_checkpoints_of_undelivered_events() {
+        let file = std::env::temp_dir().join(format!("shutdown-{}.json", std::process::id()));
+        let cfg = Config {
+            checkpoints: CheckpointConfig {
+                store: CheckpointStore::File,
+                file: file.clone(),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let (publisher, mut rx) = Publisher::in_memory();
+        publisher
+            .publish(
+                &Event {
+                    event_id: "eth:0xabc".into(),
+                    chain: "ethereum".into(),
+                    ..Default::default()
+                },
+                TraceContext::new_root(),
+            )
+            .await
+            .unwrap();
+        let last_eth_block = Arc::new(Mutex::new(Some(42)));
+
+        // Nothing takes the queued event before the deadline.
+        let flush_now = || {
+            flush(
+                &cfg,
+                Instant::now(),
+                vec![],
+                &publisher,
+                &last_eth_block,
+                &Arc::default(),
+                &[],
+            )
+        };
+        flush_now().await.unwrap();
+        assert!(!file.exists());
+
+        drop(rx.recv().await);
+        flush_now().await.unwrap();
+        assert!(file.exists());
+        std::fs::remove_file(&file).unwrap();
+    }
+}
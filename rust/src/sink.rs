@@ -0,0 +1,579 @@
+//! Where a destination's messages go (`sink` of a `[[destinations]]` entry).
+//!
+//! The publisher does everything transport-independent: filtering,
+//! projection, per-address ordering, batching and compression. What is left
+//! for a sink is moving the finished message, a single event payload or an
+//! encoded batch, so a new output only implements [`EventSink`] and gets a
+//! [`SinkKind`] variant; neither the trackers nor the delivery workers change.
+//!
+//! - `redis` (default): Pub/Sub `channel` on `redis_url`, or `REDIS_URL`.
+//! - `file`: appends each message to `path`, one event per line, e.g. to
+//!   archive events for `tracker ledger`. Uncompressed only.
+//! - `stream`: `XADD` to the Redis stream `stream`, with replays from an
+//!   optional `archive` file (see [`crate::streams`]).
+//! - `kafka`: produces to `topic` on `brokers`, keyed by `partition_key`;
+//!   needs the `kafka` feature (see `kafka.rs`).
+//! - `nats`: publishes to the subject `subject` renders for each event,
+//!   optionally through JetStream; needs the `nats` feature (see `nats.rs`).
+//! - `postgres`: upserts each event into `table` by `event_id`; needs the
+//!   `postgres` feature (see `postgres.rs`).
+//! - `webhook`: POSTs each message to `urls`, signed with `secret` (see
+//!   `webhook_sink.rs`).
+//!
+//! A `redis` channel, `kafka` topic or `nats` subject may be a template such
+//! as `events.{chain}.{event_type}`, rendered per event by [`route`], so
+//! consumers subscribe to just the chains and event types they want.
+//!
+//! The sinks that keep what they deliver (`file`, `stream` and `postgres`)
+//! also implement [`EventSink::expire`], which the `retention` job calls for
+//! destinations with `retention_days` (see `retention.rs`). Those writing
+//! local files (`file`, and `stream` with an `archive`) implement
+//! [`EventSink::flush`] for the `archive_flush` job, which syncs them to disk.
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use anyhow::{anyhow, Context};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use redis::AsyncCommands;
+use serde::Deserialize;
+use tokio::fs::File;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::Mutex;
+
+use crate::config::DestinationConfig;
+use crate::streams::StreamSink;
+use crate::Event;
+
+/// Transport of a destination.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SinkKind {
+    #[default]
+    Redis,
+    File,
+    Stream,
+    Kafka,
+    Nats,
+    Postgres,
+    Webhook,
+}
+
+/// What a destination's messages are keyed by, for sinks with keys.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PartitionKey {
+    /// The watched address the event matched, as for `ordering =
+    /// "per_address"`; the event id for events without one.
+    #[default]
+    WatchedAddress,
+    Chain,
+    EventId,
+}
+
+impl PartitionKey {
+    pub fn key(self, event: &Event) -> String {
+        match self {
+            PartitionKey::WatchedAddress => event
+                .watched_address
+                .clone()
+                .unwrap_or_else(|| event.event_id.clone()),
+            PartitionKey::Chain => event.chain.clone(),
+            PartitionKey::EventId => event.event_id.clone(),
+        }
+    }
+}
+
+/// Placeholders of a routing template: a `subject`, or a `channel` or
+/// `topic` with braces.
+const ROUTE_PLACEHOLDERS: &[&str] = &["chain", "network", "event_type"];
+
+/// Whether a `channel` or `topic` is a template rendered per event rather
+/// than a fixed name.
+pub fn is_template(name: &str) -> bool {
+    name.contains('{')
+}
+
+/// Check the placeholders of a routing template: closed, and only from
+/// [`ROUTE_PLACEHOLDERS`].
+pub fn check_template(template: &str) -> anyhow::Result<()> {
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        let end = rest[start..]
+            .find('}')
+            .ok_or_else(|| anyhow!("unclosed placeholder in {}", template))?;
+        let name = &rest[start + 1..start + end];
+        if !ROUTE_PLACEHOLDERS.contains(&name) {
+            return Err(anyhow!(
+                "unknown placeholder {{{}}} in {} (expected one of {})",
+                name,
+                template,
+                ROUTE_PLACEHOLDERS.join(", ")
+            ));
+        }
+        rest = &rest[start + end + 1..];
+    }
+    Ok(())
+}
+
+/// Check a `subject` template: dot-separated tokens, valid placeholders
+/// (see [`check_template`]), and no wildcards.
+pub fn check_subject(template: &str) -> anyhow::Result<()> {
+    if template.is_empty() {
+        return Err(anyhow!("empty subject"));
+    }
+    for token in template.split('.') {
+        if token.is_empty() || token.contains(['*', '>']) || token.contains(char::is_whitespace) {
+            return Err(anyhow!("invalid subject token '{}' in {}", token, template));
+        }
+    }
+    check_template(template)
+}
+
+/// Check a Kafka `topic`, fixed or a template: at most 249 characters from
+/// `[a-zA-Z0-9._-]` besides its placeholders.
+pub fn check_topic(topic: &str) -> anyhow::Result<()> {
+    check_template(topic)?;
+    let mut fixed = String::new();
+    let mut rest = topic;
+    while let Some(start) = rest.find('{') {
+        fixed.push_str(&rest[..start]);
+        let end = start + rest[start..].find('}').unwrap_or(0);
+        rest = &rest[end + 1..];
+    }
+    fixed.push_str(rest);
+    if topic.len() > 249
+        || !fixed
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-'))
+    {
+        return Err(anyhow!(
+            "invalid topic '{}' (expected up to 249 of a-z, A-Z, 0-9, '.', '_' and '-')",
+            topic
+        ));
+    }
+    Ok(())
+}
+
+/// The name `template` routes `event` to: a NATS subject, Redis channel or
+/// Kafka topic. Values are made valid in all three: `.`, `*`, `>` and
+/// whitespace become `_`, and an empty value is `_`.
+pub fn route(template: &str, event: &Event) -> String {
+    let token = |value: &str| {
+        if value.is_empty() {
+            return "_".to_string();
+        }
+        value
+            .chars()
+            .map(|c| match c {
+                '.' | '*' | '>' => '_',
+                c if c.is_whitespace() => '_',
+                c => c,
+            })
+            .collect::<String>()
+    };
+    template
+        .replace("{chain}", &token(&event.chain))
+        .replace("{network}", &token(&event.network))
+        .replace("{event_type}", &token(event.event_type.as_str()))
+}
+
+/// Check a `table` name: an identifier, optionally schema-qualified, that
+/// can go into SQL unquoted.
+pub fn check_table(table: &str) -> anyhow::Result<()> {
+    let valid = |part: &str| {
+        part.starts_with(|c: char| c.is_ascii_lowercase() || c == '_')
+            && part
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+    };
+    let parts: Vec<&str> = table.split('.').collect();
+    if parts.len() > 2 || !parts.iter().all(|part| valid(part)) {
+        return Err(anyhow!(
+            "invalid table '{}' (expected a lowercase name, optionally schema.name)",
+            table
+        ));
+    }
+    Ok(())
+}
+
+/// Delivers a destination's messages. Shared by its workers, so a sink must
+/// allow concurrent `publish` calls. Errors are retried with backoff by the
+/// worker.
+#[async_trait]
+pub trait EventSink: Send + Sync + 'static {
+    /// Deliver one message: an event payload, or a newline-delimited batch.
+    async fn publish(&self, message: &[u8]) -> anyhow::Result<()>;
+
+    /// Deliver one event payload with its key. Sinks without keys ignore it.
+    async fn publish_keyed(&self, _key: &str, message: &[u8]) -> anyhow::Result<()> {
+        self.publish(message).await
+    }
+
+    /// Deliver one event payload with its key to `route`, the subject,
+    /// channel or topic the destination's template gives the event. Only
+    /// sinks whose config accepts templates override it.
+    async fn publish_routed(&self, route: &str, _key: &str, _message: &[u8]) -> anyhow::Result<()> {
+        Err(anyhow!("{} can't publish to {}", self.describe(), route))
+    }
+
+    /// Remove stored events older than `cutoff` and return how many were
+    /// removed. Sinks that keep nothing have nothing to expire.
+    async fn expire(&self, _cutoff: DateTime<Utc>) -> anyhow::Result<u64> {
+        Ok(0)
+    }
+
+    /// Flush what the sink has written to local files to disk, so a crash
+    /// or power loss doesn't lose archived events. The `archive_flush` job
+    /// calls it; sinks without local files have nothing to flush.
+    async fn flush(&self) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    /// Where messages go, for logs.
+    fn describe(&self) -> String;
+}
+
+/// Open the sink `dest` selects. `redis_url` is the default Redis instance.
+pub fn open(dest: &DestinationConfig, redis_url: &str) -> anyhow::Result<Arc<dyn EventSink>> {
+    match dest.sink {
+        SinkKind::Redis => {
+            let url = dest.redis_url.as_deref().unwrap_or(redis_url);
+            Ok(Arc::new(RedisSink {
+                client: redis::Client::open(url)?,
+                channel: dest.channel.clone(),
+            }))
+        }
+        SinkKind::File => {
+            let path = dest
+                .path
+                .clone()
+                .with_context(|| format!("destination {}: file sink needs a path", dest.name))?;
+            Ok(Arc::new(FileSink::open(path)?))
+        }
+        SinkKind::Stream => {
+            let url = dest.redis_url.as_deref().unwrap_or(redis_url);
+            Ok(Arc::new(StreamSink::open(redis::Client::open(url)?, dest)?))
+        }
+        #[cfg(feature = "kafka")]
+        SinkKind::Kafka => Ok(Arc::new(crate::kafka::KafkaSink::open(dest)?)),
+        #[cfg(not(feature = "kafka"))]
+        SinkKind::Kafka => Err(anyhow!(
+            "destination {}: kafka sink needs a build with the kafka feature",
+            dest.name
+        )),
+        #[cfg(feature = "nats")]
+        SinkKind::Nats => Ok(Arc::new(crate::nats::NatsSink::open(dest)?)),
+        #[cfg(not(feature = "nats"))]
+        SinkKind::Nats => Err(anyhow!(
+            "destination {}: nats sink needs a build with the nats feature",
+            dest.name
+        )),
+        #[cfg(feature = "postgres")]
+        SinkKind::Postgres => Ok(Arc::new(crate::postgres::PostgresSink::open(dest)?)),
+        #[cfg(not(feature = "postgres"))]
+        SinkKind::Postgres => Err(anyhow!(
+            "destination {}: postgres sink needs a build with the postgres feature",
+            dest.name
+        )),
+        SinkKind::Webhook => Ok(Arc::new(crate::webhook_sink::WebhookSink::open(dest)?)),
+    }
+}
+
+/// Redis Pub/Sub channel.
+pub struct RedisSink {
+    client: redis::Client,
+    channel: String,
+}
+
+#[async_trait]
+impl EventSink for RedisSink {
+    async fn publish(&self, message: &[u8]) -> anyhow::Result<()> {
+        let mut con = self.client.get_multiplexed_async_connection().await?;
+        con.publish::<_, _, ()>(&self.channel, message).await?;
+        Ok(())
+    }
+
+    async fn publish_routed(
+        &self,
+        channel: &str,
+        _key: &str,
+        message: &[u8],
+    ) -> anyhow::Result<()> {
+        let mut con = self.client.get_multiplexed_async_connection().await?;
+        con.publish::<_, _, ()>(channel, message).await?;
+        Ok(())
+    }
+
+    fn describe(&self) -> String {
+        format!("channel {}", self.channel)
+    }
+}
+
+/// Append-only file of newline-delimited messages.
+pub struct FileSink {
+    path: PathBuf,
+    file: Mutex<File>,
+}
+
+impl FileSink {
+    /// Open `path` for appending, creating it if needed.
+    pub fn open(path: PathBuf) -> anyhow::Result<Self> {
+        let file = append(&path)?;
+        Ok(FileSink {
+            path,
+            file: Mutex::new(File::from_std(file)),
+        })
+    }
+}
+
+#[async_trait]
+impl EventSink for FileSink {
+    async fn publish(&self, message: &[u8]) -> anyhow::Result<()> {
+        let mut line = message.to_vec();
+        if !line.ends_with(b"\n") {
+            line.push(b'\n');
+        }
+        // One write per message under the lock, so concurrent workers never
+        // interleave lines.
+        let mut file = self.file.lock().await;
+        file.write_all(&line).await?;
+        file.flush().await?;
+        Ok(())
+    }
+
+    async fn expire(&self, cutoff: DateTime<Utc>) -> anyhow::Result<u64> {
+        let cutoff = cutoff.timestamp();
+        // Under the lock, so no line is appended to the file being replaced.
+        let mut file = self.file.lock().await;
+        let removed = drop_lines(&self.path, |line| {
+            serde_json::from_str::<serde_json::Value>(line)
+                .ok()
+                .and_then(|event| event_time(event.get("timestamp")?.as_str()?))
+                .is_some_and(|t| t < cutoff)
+        })?;
+        if removed > 0 {
+            *file = File::from_std(append(&self.path)?);
+        }
+        Ok(removed)
+    }
+
+    async fn flush(&self) -> anyhow::Result<()> {
+        let file = self.file.lock().await;
+        file.sync_data()
+            .await
+            .with_context(|| format!("failed to sync {}", self.path.display()))
+    }
+
+    fn describe(&self) -> String {
+        format!("file {}", self.path.display())
+    }
+}
+
+fn append(path: &Path) -> anyhow::Result<std::fs::File> {
+    std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("failed to open {}", path.display()))
+}
+
+/// An event `timestamp` in Unix seconds: chain events carry seconds,
+/// heartbeats RFC 3339.
+fn event_time(timestamp: &str) -> Option<i64> {
+    match timestamp.parse::<i64>() {
+        Ok(secs) => Some(secs),
+        Err(_) => DateTime::parse_from_rfc3339(timestamp)
+            .ok()
+            .map(|t| t.timestamp()),
+    }
+}
+
+/// Rewrite the line-per-entry file `path` without the lines `expired`
+/// picks, and return how many it dropped. The copy is renamed over `path`,
+/// so a crash leaves one version or the other; a caller appending to `path`
+/// must reopen it afterwards. A missing file has nothing to drop.
+pub(crate) fn drop_lines(path: &Path, expired: impl Fn(&str) -> bool) -> anyhow::Result<u64> {
+    let file = match std::fs::File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+        Err(e) => return Err(e).with_context(|| format!("failed to read {}", path.display())),
+    };
+    let tmp = path.with_extension("expire.tmp");
+    let mut out = std::io::BufWriter::new(
+        std::fs::File::create(&tmp)
+            .with_context(|| format!("failed to create {}", tmp.display()))?,
+    );
+    let mut removed = 0;
+    for line in BufReader::new(file).lines() {
+        let line = line.with_context(|| format!("failed to read {}", path.display()))?;
+        if expired(&line) {
+            removed += 1;
+        } else {
+            writeln!(out, "{}", line)?;
+        }
+    }
+    if removed == 0 {
+        drop(out);
+        std::fs::remove_file(&tmp).ok();
+        return Ok(0);
+    }
+    out.into_inner()
+        .map_err(|e| e.into_error())
+        .and_then(|file| file.sync_all())
+        .and_then(|()| std::fs::rename(&tmp, path))
+        .with_context(|| format!("failed to write {}", path.display()))?;
+    Ok(removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event_type::EventType;
+
+    #[tokio::test]
+    async fn test_file_sink_appends_lines() {
+        let path = std::env::temp_dir().join("tracker_file_sink_test.jsonl");
+        std::fs::remove_file(&path).ok();
+        let dest = DestinationConfig {
+            name: "archive".into(),
+            sink: SinkKind::File,
+            path: Some(path.clone()),
+            ..Default::default()
+        };
+
+        let sink = open(&dest, "redis://localhost").unwrap();
+        sink.publish(br#"{"event_id":"a"}"#).await.unwrap();
+        sink.publish(b"{\"event_id\":\"b\"}\n{\"event_id\":\"c\"}\n")
+            .await
+            .unwrap();
+        drop(sink);
+        // Reopening appends.
+        let sink = open(&dest, "redis://localhost").unwrap();
+        sink.publish(br#"{"event_id":"d"}"#).await.unwrap();
+        sink.flush().await.unwrap();
+        assert!(sink.describe().starts_with("file "));
+
+        let written = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).ok();
+        let ids: Vec<serde_json::Value> = written
+            .lines()
+            .map(|line| {
+                serde_json::from_str::<serde_json::Value>(line).unwrap()["event_id"].clone()
+            })
+            .collect();
+        assert_eq!(ids, ["a", "b", "c", "d"]);
+    }
+
+    #[tokio::test]
+    async fn test_file_sink_expires_old_events() {
+        let path = std::env::temp_dir().join("tracker_file_sink_expire_test.jsonl");
+        std::fs::write(
+            &path,
+            concat!(
+                r#"{"event_id":"a","timestamp":"1700000000"}"#,
+                "\n",
+                r#"{"event_id":"b","timestamp":"2023-11-20T00:00:00Z"}"#,
+                "\n",
+                r#"{"event_id":"c","timestamp":"1800000000"}"#,
+                "\n",
+                r#"{"event_id":"d","#,
+                "\n",
+            ),
+        )
+        .unwrap();
+        let sink = FileSink::open(path.clone()).unwrap();
+
+        let cutoff = DateTime::from_timestamp(1_750_000_000, 0).unwrap();
+        let removed = sink.expire(cutoff).await.unwrap();
+        // Appends go to the rewritten file.
+        sink.publish(br#"{"event_id":"e","timestamp":"1800000001"}"#)
+            .await
+            .unwrap();
+        let again = sink.expire(cutoff).await.unwrap();
+        let written = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).ok();
+        assert_eq!(removed, 2);
+        assert_eq!(again, 0);
+        // Lines without a readable timestamp are kept.
+        assert_eq!(
+            written.lines().collect::<Vec<_>>(),
+            [
+                r#"{"event_id":"c","timestamp":"1800000000"}"#,
+                r#"{"event_id":"d","#,
+                r#"{"event_id":"e","timestamp":"1800000001"}"#,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_partition_keys() {
+        let mut event = Event {
+            event_id: "eth:0xabc".into(),
+            chain: "ethereum".into(),
+            ..Default::default()
+        };
+        assert_eq!(PartitionKey::WatchedAddress.key(&event), "eth:0xabc");
+        event.watched_address = Some("0x01".into());
+        assert_eq!(PartitionKey::WatchedAddress.key(&event), "0x01");
+        assert_eq!(PartitionKey::Chain.key(&event), "ethereum");
+        assert_eq!(PartitionKey::EventId.key(&event), "eth:0xabc");
+    }
+
+    #[test]
+    fn test_routes() {
+        let event = Event {
+            chain: "ethereum".into(),
+            network: "mainnet".into(),
+            event_type: EventType::Erc20Transfer,
+            ..Default::default()
+        };
+        assert_eq!(
+            route("events.{chain}.{event_type}", &event),
+            "events.ethereum.erc20_transfer"
+        );
+        let event = Event {
+            network: "local.anvil".into(),
+            ..event
+        };
+        assert_eq!(
+            route("events.{chain}-{network}", &event),
+            "events.ethereum-local_anvil"
+        );
+        assert!(check_subject("events.{chain}.{event_type}").is_ok());
+        assert!(check_subject("events.{chain}-{network}").is_ok());
+        assert!(check_subject("events.{token}").is_err());
+        assert!(check_subject("events.*").is_err());
+        assert!(check_subject("events..{chain}").is_err());
+        assert!(check_subject("events.{chain").is_err());
+        assert!(check_subject("").is_err());
+        assert!(check_topic("chain-events").is_ok());
+        assert!(check_topic("events-{chain}-{event_type}").is_ok());
+        assert!(check_topic("events {chain}").is_err());
+        assert!(check_topic("events-{token}").is_err());
+        assert!(is_template("events:{chain}"));
+        assert!(!is_template("cross_chain_events"));
+    }
+
+    #[test]
+    fn test_table_names() {
+        assert!(check_table("events").is_ok());
+        assert!(check_table("tracker.events_v2").is_ok());
+        assert!(check_table("Events").is_err());
+        assert!(check_table("events; drop table x").is_err());
+        assert!(check_table("a.b.c").is_err());
+        assert!(check_table("").is_err());
+    }
+
+    #[test]
+    fn test_file_sink_needs_a_path() {
+        let dest = DestinationConfig {
+            name: "archive".into(),
+            sink: SinkKind::File,
+            ..Default::default()
+        };
+        let err = open(&dest, "redis://localhost").err().unwrap();
+        assert!(err.to_string().contains("needs a path"), "{}", err);
+    }
+}
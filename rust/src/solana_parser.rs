@@ -52,6 +52,58 @@ pub fn parse_spl_transfer(tx: &Value) -> Option<ParsedTransfer> {
     None
 }
 
+/// SPL Memo program ids (v2 and the legacy v1).
+pub(crate) const MEMO_PROGRAM_IDS: &[&str] = &[
+    "MemoSq4gqABAXKb96qnH8TysNcWxMyWCqXgDLGmfcHr",
+    "Memo1UhkJRfHyvLMcVucJwxXeuD728EqVDDwQDxFMNo",
+];
+
+/// Extract the memo text from a `jsonParsed` transaction: the `parsed` string
+/// of its top-level Memo program instructions, joined with newlines if there
+/// are several. Memos written by inner (CPI) instructions are not included.
+pub fn parse_memo(tx: &Value) -> Option<String> {
+    let instructions = tx.get("message")?.get("instructions")?.as_array()?;
+    let memos: Vec<&str> = instructions
+        .iter()
+        .filter(|ix| {
+            ix.get("programId")
+                .and_then(Value::as_str)
+                .is_some_and(|id| MEMO_PROGRAM_IDS.contains(&id))
+        })
+        .filter_map(|ix| ix.get("parsed")?.as_str())
+        .collect();
+    if memos.is_empty() {
+        None
+    } else {
+        Some(memos.join("\n"))
+    }
+}
+
+/// Account keys of a transaction's message, each with whether it signed.
+/// `jsonParsed` messages list `{pubkey, signer}` objects, including the keys
+/// loaded from lookup tables; `json` ones list plain keys, of which the first
+/// `header.numRequiredSignatures` signed.
+pub fn account_keys(tx: &Value) -> Option<Vec<(String, bool)>> {
+    let message = tx.get("message")?;
+    let signers = message
+        .pointer("/header/numRequiredSignatures")
+        .and_then(Value::as_u64)
+        .unwrap_or(0) as usize;
+    message
+        .get("accountKeys")?
+        .as_array()?
+        .iter()
+        .enumerate()
+        .map(|(i, key)| match key {
+            Value::String(key) => Some((key.clone(), i < signers)),
+            key => Some((
+                key.get("pubkey")?.as_str()?.to_string(),
+                key.get("signer")?.as_bool()?,
+            )),
+        })
+        .collect()
+}
+
 /// Validate a transaction has all required fields and decode it
 #[allow(dead_code)]
 pub fn validate_and_decode_tx(tx: &Value) -> Result<Value> {
@@ -132,6 +184,33 @@ mod tests {
         assert!(!parsed_tx_touches_watched(&parsed, &watched));
     }
 
+    #[test]
+    fn test_account_keys_parsed_and_raw() {
+        let parsed = json!({
+            "message": {
+                "accountKeys": [
+                    {"pubkey": "Payer", "signer": true, "writable": true, "source": "transaction"},
+                    {"pubkey": "Dest", "signer": false, "writable": true, "source": "lookupTable"}
+                ]
+            }
+        });
+        assert_eq!(
+            account_keys(&parsed),
+            Some(vec![
+                ("Payer".to_string(), true),
+                ("Dest".to_string(), false)
+            ])
+        );
+        let raw = json!({
+            "message": {
+                "header": {"numRequiredSignatures": 1},
+                "accountKeys": ["Payer", "Dest"]
+            }
+        });
+        assert_eq!(account_keys(&parsed), account_keys(&raw));
+        assert_eq!(account_keys(&json!(["base64blob", "base64"])), None);
+    }
+
     #[test]
     fn test_parse_spl_transfer_valid() {
         let from = Pubkey::new_unique();
@@ -291,6 +370,41 @@ mod tests {
         assert!(parse_spl_transfer(&tx).is_none());
     }
 
+    #[test]
+    fn test_parse_memo() {
+        let tx = json!({
+            "message": {
+                "instructions": [
+                    {
+                        "program": "spl-memo",
+                        "programId": "MemoSq4gqABAXKb96qnH8TysNcWxMyWCqXgDLGmfcHr",
+                        "parsed": "deposit 1042",
+                        "stackHeight": null
+                    },
+                    {
+                        "programId": TOKEN_PROGRAM_ID,
+                        "parsed": {"type": "transfer"}
+                    }
+                ]
+            }
+        });
+        assert_eq!(parse_memo(&tx).as_deref(), Some("deposit 1042"));
+    }
+
+    #[test]
+    fn test_parse_memo_absent() {
+        let tx = json!({
+            "message": {
+                "instructions": [{
+                    "programId": TOKEN_PROGRAM_ID,
+                    "parsed": {"type": "transfer"}
+                }]
+            }
+        });
+        assert!(parse_memo(&tx).is_none());
+        assert!(parse_memo(&json!({"signatures": []})).is_none());
+    }
+
     #[test]
     fn test_parse_spl_transfer_malformed_pubkey() {
         let tx = json!({
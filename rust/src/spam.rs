@@ -0,0 +1,208 @@
+//! Heuristic spam detection for incoming token transfers.
+//!
+//! Targets the common wallet-spam patterns: zero-value "address poisoning"
+//! transfers, fake airdrops whose symbol is a URL or call to action, and
+//! look-alike symbols using non-ASCII characters (e.g. a Cyrillic `Ѕ` in
+//! "UЅDT"). Only unsolicited transfers — a watched address receiving from an
+//! unwatched sender — are checked; anything a watched wallet sends is assumed
+//! intentional. Depending on `SPAM_FILTER` matching events are tagged
+//! `"spam": true` (default), dropped, or passed through untouched.
+use anyhow::anyhow;
+use std::str::FromStr;
+use tracing::info;
+
+use crate::Event;
+
+/// Built-in symbol fragments seen in fake-airdrop tokens.
+const SCAM_SYMBOL_PATTERNS: &[&str] = &[
+    "http", "www.", ".com", ".io", ".xyz", ".org", ".net", ".app", "t.me/", "claim", "visit",
+    "reward", "airdrop", "voucher",
+];
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SpamMode {
+    /// No heuristics are evaluated.
+    Off,
+    /// Publish with `spam: true` and the matching reasons.
+    #[default]
+    Tag,
+    /// Do not publish.
+    Drop,
+}
+
+impl FromStr for SpamMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "off" => Ok(SpamMode::Off),
+            "tag" => Ok(SpamMode::Tag),
+            "drop" => Ok(SpamMode::Drop),
+            other => Err(anyhow!(
+                "SPAM_FILTER must be off, tag or drop, got '{}'",
+                other
+            )),
+        }
+    }
+}
+
+/// What the heuristics know about a token transfer.
+#[derive(Debug, Default)]
+pub struct TransferSignals<'a> {
+    /// A watched address received it from a sender that isn't watched.
+    pub unsolicited: bool,
+    /// The amount credited to the watched address is zero.
+    pub zero_value: bool,
+    /// Token symbol, if known.
+    pub symbol: Option<&'a str>,
+    /// Token metadata could not be read from the contract.
+    pub metadata_unverified: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpamFilter {
+    mode: SpamMode,
+    patterns: Vec<String>,
+}
+
+impl Default for SpamFilter {
+    fn default() -> Self {
+        SpamFilter::new(SpamMode::default(), &[])
+    }
+}
+
+impl SpamFilter {
+    /// `extra_patterns` extend the built-in scam symbol fragments
+    /// (`SPAM_SYMBOL_PATTERNS`); matching is case-insensitive.
+    pub fn new(mode: SpamMode, extra_patterns: &[String]) -> Self {
+        let patterns = SCAM_SYMBOL_PATTERNS
+            .iter()
+            .map(|p| p.to_string())
+            .chain(extra_patterns.iter().map(|p| p.to_lowercase()))
+            .collect();
+        SpamFilter { mode, patterns }
+    }
+
+    /// Reasons a transfer looks like spam; empty if it doesn't.
+    ///
+    /// Unreadable metadata alone is common for legitimate older tokens (e.g.
+    /// bytes32 symbols), so it is only reported alongside a stronger signal.
+    pub fn reasons(&self, signals: &TransferSignals) -> Vec<String> {
+        if self.mode == SpamMode::Off || !signals.unsolicited {
+            return Vec::new();
+        }
+        let mut reasons = Vec::new();
+        if signals.zero_value {
+            reasons.push("zero_value".to_string());
+        }
+        if let Some(symbol) = signals.symbol {
+            let lower = symbol.to_lowercase();
+            if self.patterns.iter().any(|p| lower.contains(p.as_str())) {
+                reasons.push("scam_symbol".to_string());
+            }
+            if !symbol.is_ascii() {
+                reasons.push("lookalike_symbol".to_string());
+            }
+        }
+        if !reasons.is_empty() && signals.metadata_unverified {
+            reasons.push("unverified_metadata".to_string());
+        }
+        reasons
+    }
+
+    /// Tag the event according to the configured mode. Returns false if it
+    /// should be dropped instead of published.
+    pub fn apply(&self, event: &mut Event, signals: &TransferSignals) -> bool {
+        let reasons = self.reasons(signals);
+        if reasons.is_empty() {
+            return true;
+        }
+        match self.mode {
+            SpamMode::Off => true,
+            SpamMode::Tag => {
+                event.spam = true;
+                event.spam_reasons = reasons;
+                true
+            }
+            SpamMode::Drop => {
+                info!("Dropping spam event {} ({:?})", event.event_id, reasons);
+                false
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn incoming(symbol: &str, zero_value: bool) -> TransferSignals<'_> {
+        TransferSignals {
+            unsolicited: true,
+            zero_value,
+            symbol: Some(symbol),
+            metadata_unverified: false,
+        }
+    }
+
+    #[test]
+    fn test_parse_mode() {
+        assert_eq!("Drop".parse::<SpamMode>().unwrap(), SpamMode::Drop);
+        assert!("maybe".parse::<SpamMode>().is_err());
+    }
+
+    #[test]
+    fn test_heuristics() {
+        let filter = SpamFilter::new(SpamMode::Tag, &[]);
+        assert!(filter.reasons(&incoming("USDC", false)).is_empty());
+        assert_eq!(filter.reasons(&incoming("USDC", true)), vec!["zero_value"]);
+        assert_eq!(
+            filter.reasons(&incoming("Visit usdc-bonus.com", false)),
+            vec!["scam_symbol"]
+        );
+        assert_eq!(
+            filter.reasons(&incoming("UЅDT", false)),
+            vec!["lookalike_symbol"]
+        );
+    }
+
+    #[test]
+    fn test_unverified_metadata_alone_is_not_spam() {
+        let filter = SpamFilter::new(SpamMode::Tag, &[]);
+        let mut signals = incoming("UNKNOWN", false);
+        signals.metadata_unverified = true;
+        assert!(filter.reasons(&signals).is_empty());
+
+        signals.zero_value = true;
+        assert_eq!(
+            filter.reasons(&signals),
+            vec!["zero_value", "unverified_metadata"]
+        );
+    }
+
+    #[test]
+    fn test_outgoing_transfers_are_not_checked() {
+        let filter = SpamFilter::new(SpamMode::Tag, &["pepe".to_string()]);
+        let mut signals = incoming("PEPE", true);
+        assert_eq!(filter.reasons(&signals).len(), 2);
+        signals.unsolicited = false;
+        assert!(filter.reasons(&signals).is_empty());
+    }
+
+    #[test]
+    fn test_apply_modes() {
+        let signals = incoming("USDC", true);
+
+        let mut event = Event::default();
+        assert!(SpamFilter::new(SpamMode::Tag, &[]).apply(&mut event, &signals));
+        assert!(event.spam);
+        assert_eq!(event.spam_reasons, vec!["zero_value"]);
+
+        let mut event = Event::default();
+        assert!(!SpamFilter::new(SpamMode::Drop, &[]).apply(&mut event, &signals));
+
+        let mut event = Event::default();
+        assert!(SpamFilter::new(SpamMode::Off, &[]).apply(&mut event, &signals));
+        assert!(!event.spam);
+    }
+}
@@ -0,0 +1,515 @@
+//! Redis Streams destinations (`sink = "stream"`) and their replay protocol.
+//!
+//! A stream sink appends each message to the stream `stream` with
+//! `XADD <stream> MAXLEN ~ <max_len> * payload <message>`. Unlike Pub/Sub,
+//! entries stay after delivery, so a consumer that remembers the last entry
+//! id it processed can continue from there after a disconnect with `XREAD`
+//! or a consumer group. Without `[[destinations]]`, `REDIS_OUTPUT=stream` or
+//! `both` sets one up from `REDIS_STREAM` and `REDIS_STREAM_MAXLEN`.
+//!
+//! Once the stream has been trimmed, the entries a slow consumer still needs
+//! may be gone. With `archive` set, the sink also appends every entry it adds
+//! to that file, and `POST /admin/streams/{destination}/replay` serves the
+//! trimmed part from it:
+//!
+//! ```json
+//! {"from": "1760445296000-0", "consumer": "ledger"}
+//! ```
+//!
+//! The tracker copies every entry after `from`, first from the archive and
+//! then from the stream, to `<stream>:replay:<consumer>` as `id <original id>
+//! payload <message>`, and answers with the replay stream and the last id
+//! copied. The consumer reads the replay stream to its end, then continues
+//! the live stream after that id. Replay streams expire after a day.
+//!
+//! With `retention_days`, the `retention` job also drops entries older than
+//! that from the stream (`XTRIM MINID ~`) and from the archive, by the time
+//! in their ids, whatever `max_len` still allows.
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex as StdMutex};
+
+use anyhow::{anyhow, Context};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::config::{Config, DestinationConfig};
+use crate::history::BadRequest;
+use crate::sink::{drop_lines, EventSink, SinkKind};
+
+/// Entries copied per `XRANGE` page.
+const PAGE: usize = 500;
+
+/// Lifetime of a replay stream.
+const REPLAY_TTL_SECS: u64 = 86_400;
+
+/// Id of a stream entry, `<milliseconds>-<sequence>`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct StreamId {
+    ms: u64,
+    seq: u64,
+}
+
+impl StreamId {
+    /// The smallest id after this one.
+    fn next(self) -> Self {
+        match self.seq.checked_add(1) {
+            Some(seq) => StreamId { ms: self.ms, seq },
+            None => StreamId {
+                ms: self.ms + 1,
+                seq: 0,
+            },
+        }
+    }
+}
+
+impl FromStr for StreamId {
+    type Err = anyhow::Error;
+
+    /// `<ms>-<seq>`, or `<ms>` for `<ms>-0`.
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        let (ms, seq) = s.split_once('-').unwrap_or((s, "0"));
+        match (ms.parse(), seq.parse()) {
+            (Ok(ms), Ok(seq)) => Ok(StreamId { ms, seq }),
+            _ => Err(anyhow!("malformed stream id '{}'", s)),
+        }
+    }
+}
+
+impl fmt::Display for StreamId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-{}", self.ms, self.seq)
+    }
+}
+
+/// A line of the archive file.
+#[derive(Debug, Serialize, Deserialize)]
+struct Archived {
+    id: String,
+    payload: String,
+}
+
+/// Stream sink: `XADD` to `key`, and to the archive file if there is one.
+pub struct StreamSink {
+    client: redis::Client,
+    key: String,
+    /// Approximate trim length; 0 never trims.
+    max_len: u64,
+    archive: Option<(PathBuf, StdMutex<std::fs::File>)>,
+}
+
+impl StreamSink {
+    /// Open the sink of `dest`, a `stream` destination.
+    pub fn open(client: redis::Client, dest: &DestinationConfig) -> anyhow::Result<Self> {
+        let archive = match &dest.archive {
+            Some(path) => Some((path.clone(), StdMutex::new(open_archive(path)?))),
+            None => None,
+        };
+        Ok(StreamSink {
+            client,
+            key: dest.stream.clone(),
+            max_len: dest.max_len,
+            archive,
+        })
+    }
+}
+
+fn open_archive(path: &Path) -> anyhow::Result<std::fs::File> {
+    std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("failed to open {}", path.display()))
+}
+
+/// Whether the archive `line` holds an entry added before `cutoff`, in
+/// milliseconds. Lines cut short by a crash go too.
+fn archived_before(line: &str, cutoff: u64) -> bool {
+    serde_json::from_str::<Archived>(line)
+        .ok()
+        .and_then(|archived| archived.id.parse::<StreamId>().ok())
+        .map_or(true, |id| id.ms < cutoff)
+}
+
+#[async_trait]
+impl EventSink for StreamSink {
+    async fn publish(&self, message: &[u8]) -> anyhow::Result<()> {
+        let mut con = self.client.get_multiplexed_async_connection().await?;
+        let mut cmd = redis::cmd("XADD");
+        cmd.arg(&self.key);
+        if self.max_len > 0 {
+            cmd.arg("MAXLEN").arg("~").arg(self.max_len);
+        }
+        let id: String = cmd
+            .arg("*")
+            .arg("payload")
+            .arg(message)
+            .query_async(&mut con)
+            .await?;
+        if let Some((_, archive)) = &self.archive {
+            let line = serde_json::to_string(&Archived {
+                id,
+                payload: String::from_utf8_lossy(message).into_owned(),
+            })?;
+            // The entry is in the stream already; a retry would add it twice.
+            if let Err(e) = writeln!(archive.lock().unwrap(), "{}", line) {
+                warn!("Failed to archive an entry of stream {}: {:?}", self.key, e);
+            }
+        }
+        Ok(())
+    }
+
+    /// Trims the stream and the archive; returns the entries trimmed from
+    /// both together.
+    async fn expire(&self, cutoff: DateTime<Utc>) -> anyhow::Result<u64> {
+        let cutoff = cutoff.timestamp_millis().max(0) as u64;
+        let mut con = self.client.get_multiplexed_async_connection().await?;
+        let mut removed: u64 = redis::cmd("XTRIM")
+            .arg(&self.key)
+            .arg("MINID")
+            .arg("~")
+            .arg(cutoff)
+            .query_async(&mut con)
+            .await?;
+        if let Some((path, archive)) = &self.archive {
+            let mut file = archive.lock().unwrap();
+            let dropped = drop_lines(path, |line| archived_before(line, cutoff))?;
+            if dropped > 0 {
+                *file = open_archive(path)?;
+            }
+            removed += dropped;
+        }
+        Ok(removed)
+    }
+
+    async fn flush(&self) -> anyhow::Result<()> {
+        if let Some((path, archive)) = &self.archive {
+            archive
+                .lock()
+                .unwrap()
+                .sync_data()
+                .with_context(|| format!("failed to sync {}", path.display()))?;
+        }
+        Ok(())
+    }
+
+    fn describe(&self) -> String {
+        match &self.archive {
+            Some(_) => format!("stream {} (max {}), archived", self.key, self.max_len),
+            None => format!("stream {} (max {})", self.key, self.max_len),
+        }
+    }
+}
+
+/// Request body of `POST /admin/streams/{destination}/replay`.
+#[derive(Debug, Deserialize)]
+pub struct ReplayRequest {
+    /// Last entry id the consumer processed; replay starts after it.
+    pub from: String,
+    /// Names the replay stream, so consumers don't read each other's.
+    pub consumer: String,
+}
+
+/// What a replay copied.
+#[derive(Debug, Serialize, PartialEq, Eq)]
+pub struct Replay {
+    /// Stream holding the copied entries.
+    pub stream: String,
+    /// Entries copied from the archive, i.e. trimmed from the stream.
+    pub archived: usize,
+    /// Entries copied from the stream.
+    pub streamed: usize,
+    /// Id to continue the live stream after; `from` if nothing was copied.
+    pub last_id: String,
+}
+
+#[derive(Debug, Clone)]
+struct Source {
+    client: redis::Client,
+    key: String,
+    archive: Option<PathBuf>,
+}
+
+/// Stream destinations by name, for replays; cheap to clone.
+#[derive(Clone, Debug, Default)]
+pub struct Streams {
+    sources: Arc<HashMap<String, Source>>,
+}
+
+impl Streams {
+    pub fn new(cfg: &Config) -> anyhow::Result<Self> {
+        let mut sources = HashMap::new();
+        for dest in cfg
+            .destinations
+            .iter()
+            .filter(|d| d.sink == SinkKind::Stream)
+        {
+            let url = dest.redis_url.as_deref().unwrap_or(&cfg.redis_url);
+            sources.insert(
+                dest.name.clone(),
+                Source {
+                    client: redis::Client::open(url)?,
+                    key: dest.stream.clone(),
+                    archive: dest.archive.clone(),
+                },
+            );
+        }
+        Ok(Streams {
+            sources: Arc::new(sources),
+        })
+    }
+
+    /// Copy the entries of `destination` after `req.from` to the consumer's
+    /// replay stream. `None` if `destination` isn't a stream destination;
+    /// [`BadRequest`] for a malformed request.
+    pub async fn replay(
+        &self,
+        destination: &str,
+        req: &ReplayRequest,
+    ) -> anyhow::Result<Option<Replay>> {
+        let Some(source) = self.sources.get(destination) else {
+            return Ok(None);
+        };
+        let from: StreamId = req
+            .from
+            .parse()
+            .map_err(|e: anyhow::Error| BadRequest(e.to_string()))?;
+        if req.consumer.is_empty() || req.consumer.contains(char::is_whitespace) {
+            return Err(BadRequest("consumer must be a non-empty name".into()).into());
+        }
+        let target = format!("{}:replay:{}", source.key, req.consumer);
+        let mut con = source.client.get_multiplexed_async_connection().await?;
+
+        let first = xrange(&mut con, &source.key, "-", 1).await?;
+        let first = first.first().map(|(id, _)| *id);
+        let archived = match &source.archive {
+            Some(path) => read_archive(path, from, first)?,
+            None => Vec::new(),
+        };
+        redis::cmd("DEL")
+            .arg(&target)
+            .query_async::<_, ()>(&mut con)
+            .await?;
+        let mut last = from;
+        copy(&mut con, &target, &archived).await?;
+        if let Some((id, _)) = archived.last() {
+            last = *id;
+        }
+        let mut streamed = 0;
+        loop {
+            let page = xrange(&mut con, &source.key, &last.next().to_string(), PAGE).await?;
+            copy(&mut con, &target, &page).await?;
+            streamed += page.len();
+            match page.last() {
+                Some((id, _)) => last = *id,
+                None => break,
+            }
+            if page.len() < PAGE {
+                break;
+            }
+        }
+        if last != from {
+            redis::cmd("EXPIRE")
+                .arg(&target)
+                .arg(REPLAY_TTL_SECS)
+                .query_async::<_, ()>(&mut con)
+                .await?;
+        }
+        Ok(Some(Replay {
+            stream: target,
+            archived: archived.len(),
+            streamed,
+            last_id: last.to_string(),
+        }))
+    }
+}
+
+type Entry = (StreamId, Vec<u8>);
+
+/// Up to `count` entries of `key` from `start` on.
+async fn xrange(
+    con: &mut redis::aio::MultiplexedConnection,
+    key: &str,
+    start: &str,
+    count: usize,
+) -> anyhow::Result<Vec<Entry>> {
+    let reply: redis::Value = redis::cmd("XRANGE")
+        .arg(key)
+        .arg(start)
+        .arg("+")
+        .arg("COUNT")
+        .arg(count)
+        .query_async(con)
+        .await?;
+    parse_entries(reply)
+}
+
+/// Entries of an `XRANGE` reply, with their `payload` field.
+fn parse_entries(reply: redis::Value) -> anyhow::Result<Vec<Entry>> {
+    use redis::Value;
+    let Value::Bulk(entries) = reply else {
+        return Err(anyhow!("unexpected XRANGE reply {:?}", reply));
+    };
+    let mut out = Vec::with_capacity(entries.len());
+    for entry in entries {
+        let Value::Bulk(parts) = entry else {
+            return Err(anyhow!("unexpected stream entry {:?}", entry));
+        };
+        let [Value::Data(id), Value::Bulk(fields)] = parts.as_slice() else {
+            return Err(anyhow!("unexpected stream entry {:?}", parts));
+        };
+        let id: StreamId = String::from_utf8_lossy(id).parse()?;
+        let payload = fields
+            .chunks(2)
+            .find_map(|pair| match pair {
+                [Value::Data(name), Value::Data(value)] if name == b"payload" => {
+                    Some(value.clone())
+                }
+                _ => None,
+            })
+            .unwrap_or_default();
+        out.push((id, payload));
+    }
+    Ok(out)
+}
+
+/// Append `entries` to the replay stream `target`.
+async fn copy(
+    con: &mut redis::aio::MultiplexedConnection,
+    target: &str,
+    entries: &[Entry],
+) -> anyhow::Result<()> {
+    if entries.is_empty() {
+        return Ok(());
+    }
+    let mut pipe = redis::pipe();
+    for (id, payload) in entries {
+        pipe.cmd("XADD")
+            .arg(target)
+            .arg("*")
+            .arg("id")
+            .arg(id.to_string())
+            .arg("payload")
+            .arg(payload.as_slice())
+            .ignore();
+    }
+    pipe.query_async::<_, ()>(con).await?;
+    Ok(())
+}
+
+/// Archived entries after `from` and before `before`, the stream's first
+/// entry, in id order. A missing archive has none.
+fn read_archive(
+    path: &Path,
+    from: StreamId,
+    before: Option<StreamId>,
+) -> anyhow::Result<Vec<Entry>> {
+    let file = match std::fs::File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e).with_context(|| format!("failed to read {}", path.display())),
+    };
+    let mut entries = Vec::new();
+    for line in BufReader::new(file).lines() {
+        let line = line.with_context(|| format!("failed to read {}", path.display()))?;
+        let Ok(archived) = serde_json::from_str::<Archived>(&line) else {
+            // A line cut short by a crash.
+            continue;
+        };
+        let id: StreamId = archived.id.parse()?;
+        if id > from && before.map_or(true, |before| id < before) {
+            entries.push((id, archived.payload.into_bytes()));
+        }
+    }
+    entries.sort_by_key(|(id, _)| *id);
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use redis::Value;
+
+    fn id(s: &str) -> StreamId {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_stream_ids() {
+        assert_eq!(id("1760445296000-3").to_string(), "1760445296000-3");
+        assert_eq!(id("1760445296000"), id("1760445296000-0"));
+        assert!(id("5-10") > id("5-9"));
+        assert!(id("6-0") > id("5-99"));
+        assert_eq!(id("5-1").next(), id("5-2"));
+        assert_eq!(
+            StreamId {
+                ms: 5,
+                seq: u64::MAX
+            }
+            .next(),
+            id("6-0")
+        );
+        assert!("$".parse::<StreamId>().is_err());
+        assert!("5-x".parse::<StreamId>().is_err());
+    }
+
+    #[test]
+    fn test_parse_xrange_reply() {
+        let data = |s: &str| Value::Data(s.as_bytes().to_vec());
+        let reply = Value::Bulk(vec![
+            Value::Bulk(vec![
+                data("1-0"),
+                Value::Bulk(vec![data("payload"), data(r#"{"event_id":"a"}"#)]),
+            ]),
+            Value::Bulk(vec![data("2-0"), Value::Bulk(vec![])]),
+        ]);
+        let entries = parse_entries(reply).unwrap();
+        assert_eq!(entries[0], (id("1-0"), br#"{"event_id":"a"}"#.to_vec()));
+        assert_eq!(entries[1], (id("2-0"), Vec::new()));
+        assert!(parse_entries(Value::Nil).is_err());
+    }
+
+    #[test]
+    fn test_archive_lines_expire_by_id() {
+        assert!(archived_before(
+            r#"{"id":"1700000000000-0","payload":"a"}"#,
+            1_750_000_000_000
+        ));
+        assert!(!archived_before(
+            r#"{"id":"1750000000000-0","payload":"a"}"#,
+            1_750_000_000_000
+        ));
+        assert!(archived_before(r#"{"id":"17500"#, 1_750_000_000_000));
+    }
+
+    #[test]
+    fn test_archive_serves_trimmed_range() {
+        let path = std::env::temp_dir().join("tracker_stream_archive_test.jsonl");
+        let lines = [
+            r#"{"id":"1-0","payload":"a"}"#,
+            r#"{"id":"3-0","payload":"c"}"#,
+            r#"{"id":"2-0","payload":"b"}"#,
+            r#"{"id":"4-0","payload":"d"}"#,
+            r#"{"id":"5-0","pay"#,
+        ];
+        std::fs::write(&path, lines.join("\n")).unwrap();
+
+        let trimmed = read_archive(&path, id("1-0"), Some(id("4-0"))).unwrap();
+        let everything = read_archive(&path, id("0-0"), None).unwrap();
+        std::fs::remove_file(&path).ok();
+        let payloads = |entries: &[Entry]| -> Vec<String> {
+            entries
+                .iter()
+                .map(|(_, p)| String::from_utf8(p.clone()).unwrap())
+                .collect()
+        };
+        assert_eq!(payloads(&trimmed), ["b", "c"]);
+        assert_eq!(payloads(&everything), ["a", "b", "c", "d"]);
+        assert!(read_archive(&path, id("0-0"), None).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,473 @@
+//! Stuck transactions of watched Ethereum addresses (`[stuck_transactions]`
+//! in `CONFIG_FILE`, `stuck_transactions` job).
+//!
+//! A transaction sent with too low a fee can sit in the mempool for hours,
+//! and every later transaction of the account waits behind it; one sent
+//! with a skipped nonce is never mined at all. Every 30 seconds the job
+//! reads the confirmed (`latest`) and pending nonce of every watched
+//! address, and the address's transactions in the node's pool
+//! (`txpool_contentFrom`), and publishes a `stuck_transaction` event when:
+//!
+//! - `pending`: transactions have been pending above the confirmed nonce
+//!   for `pending_secs` without it moving, so the one with that nonce isn't
+//!   getting mined.
+//! - `nonce_gap`: the pool holds a transaction of the address that can't be
+//!   mined until a lower nonce is sent.
+//!
+//! ```toml
+//! [stuck_transactions]
+//! pending_secs = 600   # default
+//! ```
+//!
+//! `tx_hash` is the stuck transaction, or the first one held back by the
+//! gap, when the pool has it. `alert` has the rule `stuck_transaction`,
+//! severity `high`, and the address book label of the address. Each reason
+//! is reported once per confirmed nonce: an address that moves on but gets
+//! stuck again is reported again. Nodes without the `txpool` namespace
+//! (most hosted RPC providers) only get `pending` reports, without a
+//! `tx_hash`. The state is kept in memory, so after a restart the wait
+//! starts over.
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex as StdMutex};
+
+use anyhow::bail;
+use chrono::Utc;
+use ethers::providers::{Http, Middleware, Provider};
+use ethers::types::{Address, BlockId, BlockNumber, H256};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, info, warn};
+
+use crate::address_book::{Alert, Severity};
+use crate::config::Config;
+use crate::event_type::EventType;
+use crate::publisher::Publisher;
+use crate::trace_context::TraceContext;
+use crate::Event;
+
+/// Rule name of `stuck_transaction` alerts.
+pub const STUCK_TRANSACTION: &str = "stuck_transaction";
+
+fn default_pending_secs() -> u64 {
+    600
+}
+
+/// The `[stuck_transactions]` section of the config file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct StuckConfig {
+    /// How long transactions may be pending above an unchanged confirmed
+    /// nonce.
+    #[serde(default = "default_pending_secs")]
+    pub pending_secs: u64,
+}
+
+impl StuckConfig {
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if self.pending_secs == 0 {
+            bail!("pending_secs must be positive");
+        }
+        Ok(())
+    }
+}
+
+/// Why a transaction is stuck.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum StuckReason {
+    Pending,
+    NonceGap,
+}
+
+impl StuckReason {
+    fn as_str(self) -> &'static str {
+        match self {
+            StuckReason::Pending => "pending",
+            StuckReason::NonceGap => "nonce_gap",
+        }
+    }
+}
+
+/// Payload of a `stuck_transaction` event.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct Stuck {
+    pub reason: StuckReason,
+    /// `pending`: the nonce that isn't getting mined. `nonce_gap`: the first
+    /// missing nonce.
+    pub nonce: u64,
+    /// Transactions of the address mined so far.
+    pub confirmed_nonce: u64,
+    /// Next nonce after the pending transactions.
+    pub pending_nonce: u64,
+    /// `pending`: seconds since transactions were first seen pending at
+    /// `confirmed_nonce`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pending_secs: Option<u64>,
+    /// `nonce_gap`: the lowest nonce waiting for the gap.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub queued_nonce: Option<u64>,
+}
+
+/// A transaction in the node's pool.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct PoolTx {
+    pub hash: H256,
+    #[serde(default)]
+    pub to: Option<Address>,
+}
+
+/// An address's transactions in the node's pool, by nonce: `pending` ones
+/// can be mined next, `queued` ones wait for a lower nonce.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Pool {
+    pub pending: BTreeMap<u64, PoolTx>,
+    pub queued: BTreeMap<u64, PoolTx>,
+}
+
+impl Pool {
+    fn parse(raw: RawPool) -> Self {
+        let by_nonce = |txs: HashMap<String, PoolTx>| {
+            txs.into_iter()
+                .filter_map(|(nonce, tx)| Some((nonce.parse().ok()?, tx)))
+                .collect()
+        };
+        Pool {
+            pending: by_nonce(raw.pending),
+            queued: by_nonce(raw.queued),
+        }
+    }
+}
+
+/// `txpool_contentFrom` result.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct RawPool {
+    pending: HashMap<String, PoolTx>,
+    queued: HashMap<String, PoolTx>,
+}
+
+/// What an address is waiting on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Waiting {
+    /// The confirmed nonce, and when transactions were first seen waiting
+    /// at it.
+    nonce: u64,
+    since: i64,
+    /// Reasons reported at this nonce.
+    reported: Vec<StuckReason>,
+}
+
+/// The watched addresses and what they wait on. Cheap to clone; all clones
+/// share the state.
+#[derive(Debug, Clone, Default)]
+pub struct StuckMonitor {
+    /// Watched addresses and their address book labels.
+    accounts: Arc<Vec<(Address, Option<String>)>>,
+    pending_secs: u64,
+    network: String,
+    waiting: Arc<StdMutex<HashMap<Address, Waiting>>>,
+}
+
+impl StuckMonitor {
+    pub fn new(stuck: &StuckConfig, cfg: &Config) -> Self {
+        let accounts = cfg
+            .watched_addresses_eth
+            .iter()
+            .filter_map(|address| {
+                let label = cfg.address_book.label("ethereum", address);
+                Some((address.parse().ok()?, label.map(str::to_string)))
+            })
+            .collect();
+        StuckMonitor {
+            accounts: Arc::new(accounts),
+            pending_secs: stuck.pending_secs,
+            network: cfg.eth_network.clone(),
+            waiting: Arc::default(),
+        }
+    }
+
+    /// What `account` waits on given its nonces and pool (if the node
+    /// serves it), and the events it calls for.
+    fn check(
+        &self,
+        account: Address,
+        label: Option<&str>,
+        confirmed: u64,
+        pending: u64,
+        pool: Option<&Pool>,
+        now: i64,
+    ) -> (Option<Waiting>, Vec<Event>) {
+        let queued = pool.and_then(|pool| pool.queued.iter().find(|(nonce, _)| **nonce > pending));
+        if pending <= confirmed && queued.is_none() {
+            return (None, Vec::new());
+        }
+        let mut waiting = match self.waiting.lock().unwrap().get(&account) {
+            Some(previous) if previous.nonce == confirmed => previous.clone(),
+            _ => Waiting {
+                nonce: confirmed,
+                since: now,
+                reported: Vec::new(),
+            },
+        };
+        let mut events = Vec::new();
+        let waited = now.saturating_sub(waiting.since).max(0) as u64;
+        if pending > confirmed
+            && waited >= self.pending_secs
+            && !waiting.reported.contains(&StuckReason::Pending)
+        {
+            let tx = pool.and_then(|pool| pool.pending.get(&confirmed));
+            events.push(self.event(
+                account,
+                label,
+                tx,
+                Stuck {
+                    reason: StuckReason::Pending,
+                    nonce: confirmed,
+                    confirmed_nonce: confirmed,
+                    pending_nonce: pending,
+                    pending_secs: Some(waited),
+                    queued_nonce: None,
+                },
+                now,
+            ));
+            waiting.reported.push(StuckReason::Pending);
+        }
+        if let Some((queued_nonce, tx)) = queued {
+            if !waiting.reported.contains(&StuckReason::NonceGap) {
+                events.push(self.event(
+                    account,
+                    label,
+                    Some(tx),
+                    Stuck {
+                        reason: StuckReason::NonceGap,
+                        nonce: pending,
+                        confirmed_nonce: confirmed,
+                        pending_nonce: pending,
+                        pending_secs: None,
+                        queued_nonce: Some(*queued_nonce),
+                    },
+                    now,
+                ));
+                waiting.reported.push(StuckReason::NonceGap);
+            }
+        }
+        (Some(waiting), events)
+    }
+
+    fn event(
+        &self,
+        account: Address,
+        label: Option<&str>,
+        tx: Option<&PoolTx>,
+        stuck: Stuck,
+        now: i64,
+    ) -> Event {
+        let address = format!("{:?}", account);
+        Event {
+            event_id: format!(
+                "stuck:ethereum:{}:{}:{}",
+                address,
+                stuck.nonce,
+                stuck.reason.as_str()
+            ),
+            chain: "ethereum".into(),
+            network: self.network.clone(),
+            tx_hash: tx.map(|tx| format!("{:?}", tx.hash)).unwrap_or_default(),
+            timestamp: now.to_string(),
+            from: address.clone(),
+            to: tx
+                .and_then(|tx| tx.to)
+                .map(|to| format!("{:?}", to))
+                .unwrap_or_default(),
+            event_type: EventType::StuckTransaction,
+            alert: Some(Alert {
+                rule: STUCK_TRANSACTION.to_string(),
+                severity: Severity::High,
+                address: address.clone(),
+                label: label.map(str::to_string),
+            }),
+            stuck: Some(stuck),
+            watched_address: Some(address),
+            ..Default::default()
+        }
+    }
+
+    fn record(&self, account: Address, waiting: Option<Waiting>) {
+        let mut all = self.waiting.lock().unwrap();
+        match waiting {
+            Some(waiting) => all.insert(account, waiting),
+            None => all.remove(&account),
+        };
+    }
+}
+
+/// `address`'s transactions in the node's pool, or `None` if the node
+/// doesn't serve `txpool_contentFrom`.
+async fn pool(provider: &Provider<Http>, address: Address) -> Option<Pool> {
+    match provider
+        .request::<_, RawPool>("txpool_contentFrom", [address])
+        .await
+    {
+        Ok(raw) => Some(Pool::parse(raw)),
+        Err(e) => {
+            debug!("txpool_contentFrom unavailable: {}", e);
+            None
+        }
+    }
+}
+
+/// Read the watched addresses' nonces and pools and publish the reports
+/// they call for. An address whose report can't be published is checked
+/// against its old state again on the next run.
+pub async fn poll(
+    provider: &Provider<Http>,
+    monitor: &StuckMonitor,
+    publisher: &Publisher,
+) -> anyhow::Result<()> {
+    let now = Utc::now().timestamp();
+    let mut failed = 0;
+    for (account, label) in monitor.accounts.iter() {
+        let nonces = tokio::try_join!(
+            provider.get_transaction_count(*account, Some(BlockId::Number(BlockNumber::Latest))),
+            provider.get_transaction_count(*account, Some(BlockId::Number(BlockNumber::Pending))),
+        );
+        let (confirmed, pending) = match nonces {
+            Ok((confirmed, pending)) => (confirmed.as_u64(), pending.as_u64()),
+            Err(e) => {
+                debug!("Failed to read the nonces of {:?}: {}", account, e);
+                failed += 1;
+                continue;
+            }
+        };
+        let pool = pool(provider, *account).await;
+        let (waiting, events) = monitor.check(
+            *account,
+            label.as_deref(),
+            confirmed,
+            pending,
+            pool.as_ref(),
+            now,
+        );
+        for event in &events {
+            info!("Stuck transaction of {:?}: {}", account, event.event_id);
+            publisher.publish(event, TraceContext::new_root()).await?;
+        }
+        monitor.record(*account, waiting);
+    }
+    if failed > 0 {
+        warn!(
+            "Could not read the nonces of {} of {} watched addresses",
+            failed,
+            monitor.accounts.len()
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn monitor() -> StuckMonitor {
+        let cfg = Config {
+            watched_addresses_eth: vec!["0x70997970c51812dc3a010c7d01b50e0d17dc79c8".into()],
+            eth_network: "mainnet".into(),
+            ..Default::default()
+        };
+        StuckMonitor::new(&StuckConfig { pending_secs: 600 }, &cfg)
+    }
+
+    fn tx(n: u64) -> PoolTx {
+        PoolTx {
+            hash: H256::from_low_u64_be(n),
+            to: Some(Address::from_low_u64_be(0xbeef)),
+        }
+    }
+
+    #[test]
+    fn test_pending_reported_once_after_wait() {
+        let monitor = monitor();
+        let (account, _) = monitor.accounts[0].clone();
+        let check = |confirmed, pending, now| {
+            let (waiting, events) =
+                monitor.check(account, Some("Treasury"), confirmed, pending, None, now);
+            monitor.record(account, waiting);
+            events
+        };
+
+        assert!(check(5, 5, 0).is_empty());
+        assert!(check(5, 7, 100).is_empty());
+        let events = check(5, 7, 700);
+        assert_eq!(events.len(), 1);
+        let event = &events[0];
+        assert_eq!(event.event_type, EventType::StuckTransaction);
+        assert_eq!(
+            event.event_id,
+            "stuck:ethereum:0x70997970c51812dc3a010c7d01b50e0d17dc79c8:5:pending"
+        );
+        assert_eq!(event.tx_hash, "");
+        let stuck = event.stuck.as_ref().unwrap();
+        assert_eq!(stuck.pending_secs, Some(600));
+        assert_eq!(stuck.pending_nonce, 7);
+        assert_eq!(
+            event.alert.as_ref().unwrap().label.as_deref(),
+            Some("Treasury")
+        );
+        // Reported once, until the account moves on and gets stuck again.
+        assert!(check(5, 7, 800).is_empty());
+        assert!(check(6, 7, 900).is_empty());
+        assert_eq!(check(6, 7, 1500).len(), 1);
+        // Everything mined.
+        assert!(check(7, 7, 1600).is_empty());
+        assert!(monitor.waiting.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_nonce_gap_reported_at_once() {
+        let monitor = monitor();
+        let (account, _) = monitor.accounts[0].clone();
+        let pool = Pool {
+            pending: BTreeMap::from([(5, tx(5))]),
+            queued: BTreeMap::from([(8, tx(8)), (9, tx(9))]),
+        };
+        let (waiting, events) = monitor.check(account, None, 5, 6, Some(&pool), 0);
+        assert_eq!(events.len(), 1);
+        let stuck = events[0].stuck.as_ref().unwrap();
+        assert_eq!(stuck.reason, StuckReason::NonceGap);
+        assert_eq!((stuck.nonce, stuck.queued_nonce), (6, Some(8)));
+        assert_eq!(events[0].tx_hash, format!("{:?}", H256::from_low_u64_be(8)));
+        monitor.record(account, waiting);
+
+        // The pending one is reported with its hash once it has waited.
+        let (_, events) = monitor.check(account, None, 5, 6, Some(&pool), 600);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].tx_hash, format!("{:?}", H256::from_low_u64_be(5)));
+    }
+
+    #[test]
+    fn test_unpublished_report_is_retried() {
+        let monitor = monitor();
+        let (account, _) = monitor.accounts[0].clone();
+        let (waiting, _) = monitor.check(account, None, 1, 2, None, 0);
+        monitor.record(account, waiting);
+        let (_, events) = monitor.check(account, None, 1, 2, None, 600);
+        assert_eq!(events.len(), 1);
+        // Not recorded, as when publishing failed.
+        let (_, events) = monitor.check(account, None, 1, 2, None, 630);
+        assert_eq!(events.len(), 1);
+    }
+
+    #[test]
+    fn test_parse_pool_and_config() {
+        let raw: RawPool = serde_json::from_value(serde_json::json!({
+            "pending": {"5": {"hash": format!("{:?}", H256::from_low_u64_be(5)), "to": null, "nonce": "0x5"}},
+            "queued": {"x": {"hash": format!("{:?}", H256::from_low_u64_be(9))}},
+        }))
+        .unwrap();
+        let pool = Pool::parse(raw);
+        assert_eq!(pool.pending[&5].to, None);
+        assert!(pool.queued.is_empty());
+
+        assert!(StuckConfig { pending_secs: 60 }.validate().is_ok());
+        assert!(StuckConfig { pending_secs: 0 }.validate().is_err());
+    }
+}
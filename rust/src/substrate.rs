@@ -0,0 +1,741 @@
+//! Substrate chains (`[[substrate_networks]]` in `CONFIG_FILE`, built with
+//! the `substrate` feature).
+//!
+//! Each entry follows one Polkadot SDK chain (the Polkadot and Kusama relay
+//! chains, or a parachain) over its WebSocket RPC endpoint with subxt, and
+//! publishes the `balances.Transfer` events from or to its watched SS58
+//! addresses as `transfer` events with the entry's `chain` and `network`:
+//!
+//! ```toml
+//! [[substrate_networks]]
+//! chain = "polkadot"
+//! rpc_url = "wss://rpc.polkadot.io"
+//! watched_addresses = ["15oF4uVJwmo4TdGW7VfQxNLavjCXviqxT9S1MgbjMNHr6Sp5"]
+//!
+//! [[substrate_networks]]
+//! chain = "astar"
+//! rpc_url = "wss://rpc.astar.network"
+//! ss58_prefix = 5
+//! symbol = "ASTR"
+//! decimals = 18
+//! watched_addresses = ["..."]
+//! ```
+//!
+//! `network` defaults to the chain name. `polkadot`, `kusama` and `westend`
+//! know their SS58 prefix, native currency and decimals; other chains set
+//! `symbol` and `decimals`, and `ss58_prefix` unless it is the generic 42.
+//! Watched addresses may be written with any prefix; events carry every
+//! address in the chain's own. `explorer_url` defaults to the chain's
+//! Subscan site.
+//!
+//! Only finalized blocks are read, so events are never retracted. Event ids
+//! are `<chain>:<block>-<event index>`, following `DEDUP_KEY`; `tx_hash` is
+//! the extrinsic id (`<block>-<extrinsic index>`), empty for transfers made
+//! outside an extrinsic. `value` is in the chain's smallest unit (planck),
+//! and `substrate` carries the block number and hash, the event and
+//! extrinsic indexes and, on chains with staking, the active era.
+//!
+//! The checkpoint (`<chain>:<network>`) is the last finalized block read;
+//! after a restart the blocks since are read again, at most
+//! `MAX_CATCHUP_BLOCKS` of them. Each chain pauses and resumes at
+//! `/admin/chains/<chain>`. Without the `substrate` feature the entries are
+//! validated but not tracked.
+use std::sync::Arc;
+
+use anyhow::{bail, Context};
+use async_trait::async_trait;
+use blake2::{Blake2b512, Digest};
+use chrono::DateTime;
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+use tracing::info;
+
+use crate::base58;
+use crate::config::Config;
+use crate::control::ChainControl;
+use crate::dedup::{DedupKey, DedupSet};
+use crate::event_type::EventType;
+use crate::evm_networks::EvmNetwork;
+use crate::publisher::Publisher;
+use crate::trace_context::TraceContext;
+use crate::tracker::ChainTracker;
+use crate::Event;
+
+/// SS58 prefix, native symbol and decimals of the chains that need no
+/// settings.
+const KNOWN_CHAINS: &[(&str, u16, &str, u8)] = &[
+    ("polkadot", 0, "DOT", 10),
+    ("kusama", 2, "KSM", 12),
+    ("westend", 42, "WND", 12),
+];
+
+/// Prefix of addresses that name no particular chain.
+const GENERIC_PREFIX: u16 = 42;
+
+/// One `[[substrate_networks]]` entry as written.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SubstrateNetworkConfig {
+    /// Event `chain` name, e.g. `polkadot`.
+    pub chain: String,
+    /// Event `network` name; defaults to `chain`.
+    #[serde(default)]
+    pub network: Option<String>,
+    /// `ws://` or `wss://` RPC endpoint.
+    pub rpc_url: String,
+    #[serde(default)]
+    pub ss58_prefix: Option<u16>,
+    /// Native currency symbol, e.g. `DOT`.
+    #[serde(default)]
+    pub symbol: Option<String>,
+    #[serde(default)]
+    pub decimals: Option<u8>,
+    /// Base of the `explorer_url` links; defaults to Subscan.
+    #[serde(default)]
+    pub explorer_url: Option<String>,
+    #[serde(default)]
+    pub watched_addresses: Vec<String>,
+}
+
+/// A validated entry.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SubstrateNetwork {
+    pub chain: String,
+    pub network: String,
+    pub rpc_url: String,
+    pub ss58_prefix: u16,
+    pub symbol: String,
+    pub decimals: u8,
+    pub explorer_url: String,
+    /// Account ids of the watched addresses.
+    pub watched: Vec<[u8; 32]>,
+}
+
+impl SubstrateNetwork {
+    /// Validate `entries`, none of which may reuse an `[[evm_networks]]`
+    /// chain name.
+    pub fn resolve(
+        entries: &[SubstrateNetworkConfig],
+        evm_networks: &[EvmNetwork],
+    ) -> anyhow::Result<Vec<SubstrateNetwork>> {
+        let mut networks: Vec<SubstrateNetwork> = Vec::new();
+        for entry in entries {
+            let network = SubstrateNetwork::new(entry)
+                .with_context(|| format!("substrate network {:?}", entry.chain))?;
+            if networks.iter().any(|n| n.chain == network.chain) {
+                bail!("substrate network {:?} is listed twice", network.chain);
+            }
+            if evm_networks.iter().any(|n| n.chain == network.chain) {
+                bail!(
+                    "substrate network {:?} is also an [[evm_networks]] chain",
+                    network.chain
+                );
+            }
+            networks.push(network);
+        }
+        Ok(networks)
+    }
+
+    fn new(entry: &SubstrateNetworkConfig) -> anyhow::Result<SubstrateNetwork> {
+        let chain = entry.chain.trim().to_ascii_lowercase();
+        if chain.is_empty()
+            || !chain
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        {
+            bail!("chain must be a name of letters, digits, '-' and '_'");
+        }
+        if matches!(chain.as_str(), "ethereum" | "solana" | "tron") {
+            bail!("chain {} is configured with its own settings", chain);
+        }
+        let network = match &entry.network {
+            Some(network) if network.trim().is_empty() => bail!("network must not be empty"),
+            Some(network) => network.trim().to_string(),
+            None => chain.clone(),
+        };
+        let rpc_url = entry.rpc_url.trim().to_string();
+        if !rpc_url.starts_with("ws://") && !rpc_url.starts_with("wss://") {
+            bail!("rpc_url must be a ws(s):// URL");
+        }
+        let known = KNOWN_CHAINS.iter().find(|(name, ..)| *name == chain);
+        let ss58_prefix = entry
+            .ss58_prefix
+            .or(known.map(|&(_, prefix, ..)| prefix))
+            .unwrap_or(GENERIC_PREFIX);
+        if ss58_prefix > 16383 {
+            bail!("ss58_prefix must be at most 16383");
+        }
+        let symbol = match (&entry.symbol, known) {
+            (Some(symbol), _) => symbol.trim().to_string(),
+            (None, Some(&(_, _, symbol, _))) => symbol.to_string(),
+            (None, None) => bail!("symbol must be set for chain {}", chain),
+        };
+        let decimals = match (entry.decimals, known) {
+            (Some(decimals), _) => decimals,
+            (None, Some(&(.., decimals))) => decimals,
+            (None, None) => bail!("decimals must be set for chain {}", chain),
+        };
+        let explorer_url = entry
+            .explorer_url
+            .clone()
+            .unwrap_or_else(|| format!("https://{}.subscan.io", chain))
+            .trim_end_matches('/')
+            .to_string();
+        let watched = entry
+            .watched_addresses
+            .iter()
+            .map(|address| {
+                decode_address(address)
+                    .map(|(_, account)| account)
+                    .with_context(|| format!("invalid SS58 address {:?}", address))
+            })
+            .collect::<anyhow::Result<_>>()?;
+        Ok(SubstrateNetwork {
+            chain,
+            network,
+            rpc_url,
+            ss58_prefix,
+            symbol,
+            decimals,
+            explorer_url,
+            watched,
+        })
+    }
+
+    /// Checkpoint key of the network's tracker.
+    pub fn key(&self) -> String {
+        format!("{}:{}", self.chain, self.network)
+    }
+
+    /// `account` as an address of this chain.
+    pub fn address(&self, account: &[u8; 32]) -> String {
+        encode_address(account, self.ss58_prefix)
+    }
+
+    /// The watched account `transfer` is from or to, the sender first.
+    pub fn watched_party(&self, transfer: &Transfer) -> Option<[u8; 32]> {
+        [transfer.from, transfer.to]
+            .into_iter()
+            .find(|account| self.watched.contains(account))
+    }
+
+    /// The `transfer` event of `transfer`, the `event_index`th event of
+    /// `block`, made at `timestamp_ms`. `None` unless it is from or to a
+    /// watched account.
+    pub fn transfer_event(
+        &self,
+        transfer: &Transfer,
+        block: &SubstrateBlock,
+        timestamp_ms: u64,
+        key: DedupKey,
+    ) -> Option<Event> {
+        let watched = self.address(&self.watched_party(transfer)?);
+        let event_id = key.event_id(
+            &self.chain,
+            &format!("{}-{}", block.block_number, block.event_index),
+            None,
+            &watched,
+        );
+        Some(Event {
+            event_id,
+            chain: self.chain.clone(),
+            network: self.network.clone(),
+            tx_hash: block
+                .extrinsic_index
+                .map(|index| format!("{}-{}", block.block_number, index))
+                .unwrap_or_default(),
+            timestamp: DateTime::from_timestamp_millis(timestamp_ms as i64)?.to_rfc3339(),
+            block_number: Some(block.block_number),
+            log_index: Some(block.event_index as u64),
+            from: self.address(&transfer.from),
+            to: self.address(&transfer.to),
+            value: transfer.amount.to_string(),
+            event_type: EventType::Transfer,
+            substrate: Some(block.clone()),
+            signed_by_watched: self.watched.contains(&transfer.from),
+            watched_address: Some(watched),
+            ..Default::default()
+        })
+    }
+}
+
+fn ss58_checksum(payload: &[u8]) -> [u8; 2] {
+    let digest = Blake2b512::new()
+        .chain_update(b"SS58PRE")
+        .chain_update(payload)
+        .finalize();
+    [digest[0], digest[1]]
+}
+
+/// The SS58 address of `account` with `prefix`.
+pub fn encode_address(account: &[u8; 32], prefix: u16) -> String {
+    let mut bytes = match prefix {
+        0..=63 => vec![prefix as u8],
+        _ => vec![
+            ((prefix & 0b1111_1100) >> 2) as u8 | 0b0100_0000,
+            (prefix >> 8) as u8 | ((prefix & 0b11) << 6) as u8,
+        ],
+    };
+    bytes.extend_from_slice(account);
+    let check = ss58_checksum(&bytes);
+    bytes.extend_from_slice(&check);
+    base58::encode(&bytes)
+}
+
+/// The prefix and account id of an SS58 address, `None` unless it is a
+/// valid address of a 32-byte account.
+pub fn decode_address(address: &str) -> Option<(u16, [u8; 32])> {
+    let bytes = base58::decode(address.trim())?;
+    let (prefix, prefix_len) = match *bytes.first()? {
+        first @ 0..=63 => (first as u16, 1),
+        first @ 64..=127 => {
+            let second = *bytes.get(1)?;
+            let lower = (first << 2) | (second >> 6);
+            let upper = second & 0b0011_1111;
+            (lower as u16 | (upper as u16) << 8, 2)
+        }
+        _ => return None,
+    };
+    if bytes.len() != prefix_len + 32 + 2 {
+        return None;
+    }
+    let (payload, check) = bytes.split_at(prefix_len + 32);
+    if check != ss58_checksum(payload) {
+        return None;
+    }
+    Some((prefix, payload[prefix_len..].try_into().ok()?))
+}
+
+/// A `balances.Transfer` event, as decoded from a block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Transfer {
+    pub from: [u8; 32],
+    pub to: [u8; 32],
+    pub amount: u128,
+}
+
+/// Where a Substrate event sits in its chain (`substrate` in the payload).
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct SubstrateBlock {
+    pub block_number: u64,
+    pub block_hash: String,
+    /// Position of the event among its block's events.
+    pub event_index: u32,
+    /// Position of the extrinsic that emitted the event in its block;
+    /// absent for events of block initialization or finalization.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub extrinsic_index: Option<u32>,
+    /// Active staking era at the block, on chains with staking.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub era: Option<u32>,
+}
+
+/// `balances.Transfer` events of one `[[substrate_networks]]` chain.
+pub struct SubstrateTracker {
+    chain: &'static str,
+    network: SubstrateNetwork,
+    #[cfg_attr(not(feature = "substrate"), allow(dead_code))]
+    max_catchup_blocks: u64,
+    processed_txs: Arc<Mutex<DedupSet>>,
+    last_block: Arc<Mutex<Option<u64>>>,
+    control: ChainControl,
+    publisher: Publisher,
+}
+
+impl SubstrateTracker {
+    /// `processed_txs` may be shared with other trackers; `control` names
+    /// the chain.
+    pub fn new(
+        cfg: &Config,
+        network: SubstrateNetwork,
+        processed_txs: Arc<Mutex<DedupSet>>,
+        control: ChainControl,
+        publisher: Publisher,
+    ) -> Self {
+        SubstrateTracker {
+            chain: control.chain(),
+            network,
+            max_catchup_blocks: cfg.checkpoints.max_catchup_blocks,
+            processed_txs,
+            last_block: Arc::default(),
+            control,
+            publisher,
+        }
+    }
+
+    /// The checkpoint, shared with the admin API.
+    pub(crate) fn last_block(&self) -> &Arc<Mutex<Option<u64>>> {
+        &self.last_block
+    }
+
+    /// Publish the events of one block's transfers, in order, and move the
+    /// checkpoint past it. `transfers` are the block's `balances.Transfer`
+    /// events with their event and extrinsic indexes.
+    #[cfg_attr(not(feature = "substrate"), allow(dead_code))]
+    async fn publish_block(
+        &self,
+        block_number: u64,
+        block_hash: String,
+        transfers: &[(u32, Option<u32>, Transfer)],
+        timestamp_ms: u64,
+        era: Option<u32>,
+    ) -> anyhow::Result<()> {
+        let trace = TraceContext::new_root();
+        let key = self.processed_txs.lock().await.key();
+        for &(event_index, extrinsic_index, transfer) in transfers {
+            let block = SubstrateBlock {
+                block_number,
+                block_hash: block_hash.clone(),
+                event_index,
+                extrinsic_index,
+                era,
+            };
+            let Some(event) = self
+                .network
+                .transfer_event(&transfer, &block, timestamp_ms, key)
+            else {
+                continue;
+            };
+            if self.processed_txs.lock().await.contains(&event.event_id) {
+                continue;
+            }
+            self.publisher.publish(&event, trace).await?;
+            self.processed_txs.lock().await.insert(event.event_id);
+        }
+        let mut last = self.last_block.lock().await;
+        if !last.is_some_and(|n| block_number <= n) {
+            *last = Some(block_number);
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl ChainTracker for SubstrateTracker {
+    fn chain(&self) -> &'static str {
+        self.chain
+    }
+
+    fn enabled(&self) -> bool {
+        true
+    }
+
+    async fn track(&self) {
+        if self.network.watched.is_empty() {
+            info!("No {} addresses to watch.", self.chain);
+            return;
+        }
+        #[cfg(feature = "substrate")]
+        {
+            info!(
+                "Following finalized {} blocks at {}",
+                self.chain, self.network.rpc_url
+            );
+            loop {
+                self.control.wait_resumed().await;
+                if self.control.is_shutting_down() {
+                    return;
+                }
+                if let Err(e) = client::follow(self).await {
+                    self.control.set_connected(false);
+                    tracing::warn!("{} subscription failed: {:?}", self.chain, e);
+                }
+                if self.control.is_shutting_down() {
+                    return;
+                }
+                self.control
+                    .sleep(tokio::time::Duration::from_secs(5))
+                    .await;
+            }
+        }
+        #[cfg(not(feature = "substrate"))]
+        tracing::error!(
+            "[[substrate_networks]] chain {} ({}) needs a build with the substrate feature",
+            self.chain,
+            self.network.rpc_url
+        );
+    }
+
+    async fn checkpoint(&self) -> Option<u64> {
+        *self.last_block.lock().await
+    }
+
+    fn control(&self) -> &ChainControl {
+        &self.control
+    }
+
+    fn publisher(&self) -> &Publisher {
+        &self.publisher
+    }
+}
+
+/// Finalized blocks over subxt.
+#[cfg(feature = "substrate")]
+mod client {
+    use subxt::backend::legacy::LegacyRpcMethods;
+    use subxt::backend::rpc::RpcClient;
+    use subxt::blocks::Block;
+    use subxt::events::{Phase, StaticEvent};
+    use subxt::ext::scale_value::At;
+    use subxt::utils::AccountId32;
+    use subxt::{OnlineClient, PolkadotConfig};
+    use tokio_stream::StreamExt;
+
+    use super::*;
+
+    type Api = OnlineClient<PolkadotConfig>;
+
+    /// `Balances.Transfer`, the same on every chain with the balances
+    /// pallet.
+    #[derive(Debug, subxt::ext::scale_decode::DecodeAsType)]
+    #[decode_as_type(crate_path = "subxt::ext::scale_decode")]
+    struct BalancesTransfer {
+        from: AccountId32,
+        to: AccountId32,
+        amount: u128,
+    }
+
+    impl StaticEvent for BalancesTransfer {
+        const PALLET: &'static str = "Balances";
+        const EVENT: &'static str = "Transfer";
+    }
+
+    /// Read the blocks finalized since the checkpoint, then each newly
+    /// finalized block, until the connection fails or the chain stops.
+    pub(super) async fn follow(tracker: &SubstrateTracker) -> anyhow::Result<()> {
+        let url = &tracker.network.rpc_url;
+        let rpc = if url.starts_with("ws://") {
+            RpcClient::from_insecure_url(url).await?
+        } else {
+            RpcClient::from_url(url).await?
+        };
+        let legacy = LegacyRpcMethods::<PolkadotConfig>::new(rpc.clone());
+        let api = Api::from_rpc_client(rpc).await?;
+        let mut blocks = api.blocks().subscribe_finalized().await?;
+        tracker.control.set_connected(true);
+        let mut caught_up = false;
+        while let Some(block) = blocks.next().await {
+            if tracker.control.is_stopping() {
+                return Ok(());
+            }
+            let block = block?;
+            let number = u64::from(block.number());
+            if !caught_up {
+                caught_up = true;
+                let prev = *tracker.last_block.lock().await;
+                if let Some(prev) = prev {
+                    let from = crate::checkpoint::catch_up_from(
+                        prev,
+                        number.saturating_sub(1),
+                        tracker.max_catchup_blocks,
+                    );
+                    for missed in from + 1..number {
+                        if tracker.control.is_stopping() {
+                            return Ok(());
+                        }
+                        let hash = legacy
+                            .chain_get_block_hash(Some(missed.into()))
+                            .await?
+                            .with_context(|| format!("no hash for block {}", missed))?;
+                        process(tracker, &api, api.blocks().at(hash).await?).await?;
+                    }
+                }
+            }
+            process(tracker, &api, block).await?;
+        }
+        bail!("finalized block subscription ended")
+    }
+
+    /// Publish the watched transfers of `block`.
+    async fn process(
+        tracker: &SubstrateTracker,
+        api: &Api,
+        block: Block<PolkadotConfig, Api>,
+    ) -> anyhow::Result<()> {
+        let number = u64::from(block.number());
+        let mut transfers = Vec::new();
+        for details in block.events().await?.iter() {
+            let details = details?;
+            let Some(transfer) = details.as_event::<BalancesTransfer>()? else {
+                continue;
+            };
+            let extrinsic_index = match details.phase() {
+                Phase::ApplyExtrinsic(index) => Some(index),
+                _ => None,
+            };
+            transfers.push((
+                details.index(),
+                extrinsic_index,
+                Transfer {
+                    from: transfer.from.0,
+                    to: transfer.to.0,
+                    amount: transfer.amount,
+                },
+            ));
+        }
+        transfers.retain(|(_, _, transfer)| tracker.network.watched_party(transfer).is_some());
+        if transfers.is_empty() {
+            return tracker
+                .publish_block(number, String::new(), &[], 0, None)
+                .await;
+        }
+
+        let storage = api.storage().at(block.reference());
+        let timestamp_ms = storage
+            .fetch(&subxt::dynamic::storage("Timestamp", "Now", ()))
+            .await?
+            .context("block has no timestamp")?
+            .to_value()?
+            .as_u128()
+            .context("block timestamp is not a number")? as u64;
+        // Parachains have no staking pallet.
+        let era = match storage
+            .fetch(&subxt::dynamic::storage("Staking", "ActiveEra", ()))
+            .await
+        {
+            Ok(Some(active)) => active
+                .to_value()
+                .ok()
+                .and_then(|value| value.at("index").and_then(|index| index.as_u128()))
+                .map(|index| index as u32),
+            _ => None,
+        };
+        tracker
+            .publish_block(
+                number,
+                format!("{:?}", block.hash()),
+                &transfers,
+                timestamp_ms,
+                era,
+            )
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALICE: [u8; 32] = [
+        0xd4, 0x35, 0x93, 0xc7, 0x15, 0xfd, 0xd3, 0x1c, 0x61, 0x14, 0x1a, 0xbd, 0x04, 0xa9, 0x9f,
+        0xd6, 0x82, 0x2c, 0x85, 0x58, 0x85, 0x4c, 0xcd, 0xe3, 0x9a, 0x56, 0x84, 0xe7, 0xa5, 0x6d,
+        0xa2, 0x7d,
+    ];
+    const ALICE_POLKADOT: &str = "15oF4uVJwmo4TdGW7VfQxNLavjCXviqxT9S1MgbjMNHr6Sp5";
+    const ALICE_GENERIC: &str = "5GrwvaEF5zXb26Fz9rcQpDWS57CtERHpNehXCPcNoHGKutQY";
+    const BOB_POLKADOT: &str = "14E5nqKAp3oAJcmzgZhUD2RcptBeUBScxKHgJKU4HPNcKVf3";
+
+    fn entry(chain: &str) -> SubstrateNetworkConfig {
+        SubstrateNetworkConfig {
+            chain: chain.to_string(),
+            network: None,
+            rpc_url: "wss://rpc.polkadot.io".to_string(),
+            ss58_prefix: None,
+            symbol: None,
+            decimals: None,
+            backfill: false,
+            explorer_url: None,
+            watched_addresses: vec![ALICE_GENERIC.to_string()],
+        }
+    }
+
+    #[test]
+    fn test_ss58_addresses() {
+        assert_eq!(encode_address(&ALICE, 0), ALICE_POLKADOT);
+        assert_eq!(encode_address(&ALICE, 42), ALICE_GENERIC);
+        assert_eq!(
+            encode_address(&ALICE, 2),
+            "HNZata7iMYWmk5RvZRTiAsSDhV8366zq2YGb3tLH5Upf74F"
+        );
+        // Two-byte prefix (Moonbeam).
+        let moonbeam = encode_address(&ALICE, 1284);
+        assert_eq!(
+            moonbeam,
+            "VdvKmYJfD4VXA9fzz1SbmCo2eYHSzUFbaDCZSuaNKJAe8YNg6"
+        );
+        assert_eq!(decode_address(&moonbeam), Some((1284, ALICE)));
+        assert_eq!(decode_address(ALICE_POLKADOT), Some((0, ALICE)));
+        // Checksum and length are checked.
+        assert!(decode_address("15oF4uVJwmo4TdGW7VfQxNLavjCXviqxT9S1MgbjMNHr6Sp6").is_none());
+        assert!(decode_address("T9yD14Nj9j7xAB4dbGeiX9h8unkKHxuWwb").is_none());
+    }
+
+    #[test]
+    fn test_resolve() {
+        let astar = SubstrateNetworkConfig {
+            ss58_prefix: Some(5),
+            symbol: Some("ASTR".to_string()),
+            decimals: Some(18),
+            ..entry("astar")
+        };
+        let networks = SubstrateNetwork::resolve(&[entry("Polkadot"), astar], &[]).unwrap();
+        let polkadot = &networks[0];
+        assert_eq!(polkadot.key(), "polkadot:polkadot");
+        assert_eq!((polkadot.ss58_prefix, polkadot.decimals), (0, 10));
+        assert_eq!(polkadot.explorer_url, "https://polkadot.subscan.io");
+        assert_eq!(polkadot.watched, vec![ALICE]);
+        assert_eq!(networks[1].symbol, "ASTR");
+
+        let resolve = |entries: &[SubstrateNetworkConfig]| {
+            format!("{:#}", SubstrateNetwork::resolve(entries, &[]).unwrap_err())
+        };
+        assert!(resolve(&[entry("kusama"), entry("kusama")]).contains("listed twice"));
+        assert!(resolve(&[entry("tron")]).contains("its own settings"));
+        assert!(resolve(&[entry("astar")]).contains("symbol must be set"));
+        let http = SubstrateNetworkConfig {
+            rpc_url: "https://rpc.polkadot.io".to_string(),
+            ..entry("polkadot")
+        };
+        assert!(resolve(&[http]).contains("rpc_url"));
+        let bad_address = SubstrateNetworkConfig {
+            watched_addresses: vec!["0x742d35Cc6634C0532925a3b844Bc454e4438f44e".to_string()],
+            ..entry("polkadot")
+        };
+        assert!(resolve(&[bad_address]).contains("invalid SS58 address"));
+    }
+
+    #[test]
+    fn test_transfer_event() {
+        let polkadot = SubstrateNetwork::new(&entry("polkadot")).unwrap();
+        let bob = decode_address(BOB_POLKADOT).unwrap().1;
+        let block = SubstrateBlock {
+            block_number: 18_000_000,
+            block_hash: "0xabc".to_string(),
+            event_index: 7,
+            extrinsic_index: Some(2),
+            era: Some(1_234),
+        };
+        let transfer = Transfer {
+            from: bob,
+            to: ALICE,
+            amount: 25_000_000_000,
+        };
+        let event = polkadot
+            .transfer_event(&transfer, &block, 1_700_000_000_000, DedupKey::Log)
+            .unwrap();
+        assert_eq!(event.event_id, "polkadot:18000000-7");
+        assert_eq!(event.tx_hash, "18000000-2");
+        assert_eq!(
+            (event.block_number, event.log_index),
+            (Some(18_000_000), Some(7))
+        );
+        assert_eq!(event.timestamp, "2023-11-14T22:13:20+00:00");
+        assert_eq!(
+            (event.from.as_str(), event.to.as_str()),
+            (BOB_POLKADOT, ALICE_POLKADOT)
+        );
+        assert_eq!(event.value, "25000000000");
+        assert_eq!(event.watched_address.as_deref(), Some(ALICE_POLKADOT));
+        assert!(!event.signed_by_watched);
+        assert_eq!(event.substrate, Some(block.clone()));
+
+        let unwatched = Transfer {
+            from: bob,
+            to: bob,
+            amount: 1,
+        };
+        assert!(polkadot
+            .transfer_event(&unwatched, &block, 1_700_000_000_000, DedupKey::Log)
+            .is_none());
+    }
+}
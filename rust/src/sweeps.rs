@@ -0,0 +1,337 @@
+//! Deposit sweep detection (`[sweep_detection]` in `CONFIG_FILE`).
+//!
+//! Exchanges give every customer a deposit address and periodically sweep
+//! them into a hot wallet. Seen one transfer at a time a sweep is just a lot
+//! of small outgoing transfers; what operations want to know is that one
+//! happened, and from where. When at least `min_sources` watched addresses
+//! send the same asset to one destination within `window_secs`, a
+//! `sweep_detected` event is published with the constituent transfers:
+//!
+//! ```toml
+//! [sweep_detection]
+//! min_sources = 5
+//! window_secs = 600
+//! ```
+//!
+//! The event's `to`, `token` and `value` are the destination, the asset and
+//! the total swept, so it gets asset fields and explorer links like a
+//! transfer. Transfers are grouped as they are published, so a sweep spread
+//! over a longer outage or backfill may be reported late or in parts. Solana
+//! events don't carry a sender, so only Ethereum is checked.
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex as StdMutex};
+use std::time::{Duration, Instant};
+
+use anyhow::bail;
+use ethers::types::U256;
+use serde::{Deserialize, Serialize};
+
+use crate::config::Config;
+use crate::event_type::EventType;
+use crate::token_filter::normalize;
+use crate::Event;
+
+/// Transfers that can be part of a sweep.
+const TRANSFER_TYPES: &[EventType] = &[EventType::Transfer, EventType::Erc20Transfer];
+
+fn default_window_secs() -> u64 {
+    600
+}
+
+/// The `[sweep_detection]` section of the config file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SweepConfig {
+    /// Distinct watched senders that make a sweep.
+    pub min_sources: usize,
+    #[serde(default = "default_window_secs")]
+    pub window_secs: u64,
+}
+
+impl SweepConfig {
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if self.min_sources < 2 {
+            bail!("min_sources must be at least 2");
+        }
+        if self.window_secs == 0 {
+            bail!("window_secs must be positive");
+        }
+        Ok(())
+    }
+}
+
+/// Payload of a `sweep_detected` event.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct Sweep {
+    pub destination: String,
+    /// Watched addresses that sent, in order of their first transfer.
+    pub sources: Vec<String>,
+    /// Transactions of the sweep, in the order they were seen.
+    pub tx_hashes: Vec<String>,
+    pub window_secs: u64,
+}
+
+/// One transfer towards a destination.
+#[derive(Debug)]
+struct Leg {
+    source: String,
+    tx_hash: String,
+    value: U256,
+    seen: Instant,
+}
+
+/// Destination and asset of a group: `(chain, to, token or "")`.
+type GroupKey = (String, String, String);
+
+/// Recent outgoing transfers of watched addresses, grouped by destination.
+#[derive(Clone, Debug, Default)]
+pub struct SweepDetector {
+    cfg: Option<SweepConfig>,
+    watched: Arc<HashSet<String>>,
+    groups: Arc<StdMutex<HashMap<GroupKey, Vec<Leg>>>>,
+}
+
+impl SweepDetector {
+    /// Disabled unless `cfg` has a `[sweep_detection]` section.
+    pub fn new(cfg: &Config) -> Self {
+        SweepDetector {
+            cfg: cfg.sweep_detection,
+            watched: Arc::new(
+                cfg.watched_addresses_eth
+                    .iter()
+                    .map(String::as_str)
+                    .map(normalize)
+                    .collect(),
+            ),
+            groups: Arc::default(),
+        }
+    }
+
+    /// The `sweep_detected` event `event` completes, if any.
+    pub fn observe(&self, event: &Event) -> Option<Event> {
+        self.observe_at(event, Instant::now())
+    }
+
+    fn observe_at(&self, event: &Event, now: Instant) -> Option<Event> {
+        let cfg = self.cfg?;
+        if event.chain != "ethereum"
+            || event.spam
+            || !TRANSFER_TYPES.contains(&event.event_type)
+            || event.to.is_empty()
+        {
+            return None;
+        }
+        let source = normalize(&event.from);
+        if !self.watched.contains(&source) {
+            return None;
+        }
+        let destination = normalize(&event.to);
+        let token = event
+            .token
+            .as_ref()
+            .map(|t| normalize(&t.address))
+            .unwrap_or_default();
+        let key = (event.chain.clone(), destination.clone(), token);
+        let window = Duration::from_secs(cfg.window_secs);
+
+        let mut groups = self.groups.lock().unwrap();
+        groups.retain(|_, legs| {
+            legs.retain(|leg| now.duration_since(leg.seen) <= window);
+            !legs.is_empty()
+        });
+        let legs = groups.entry(key.clone()).or_default();
+        // The same transfer can be published once per watched address.
+        if legs.iter().any(|leg| leg.tx_hash == event.tx_hash) {
+            return None;
+        }
+        legs.push(Leg {
+            source,
+            tx_hash: event.tx_hash.clone(),
+            value: U256::from_dec_str(&event.value).unwrap_or_default(),
+            seen: now,
+        });
+        let mut sources: Vec<String> = Vec::new();
+        for leg in legs.iter() {
+            if !sources.contains(&leg.source) {
+                sources.push(leg.source.clone());
+            }
+        }
+        if sources.len() < cfg.min_sources {
+            return None;
+        }
+
+        let legs = groups.remove(&key).unwrap_or_default();
+        let total = legs
+            .iter()
+            .fold(U256::zero(), |sum, leg| sum.saturating_add(leg.value));
+        Some(Event {
+            event_id: format!("sweep:{}:{}:{}", event.chain, destination, event.tx_hash),
+            chain: event.chain.clone(),
+            network: event.network.clone(),
+            timestamp: event.timestamp.clone(),
+            to: destination.clone(),
+            value: total.to_string(),
+            event_type: EventType::SweepDetected,
+            token: event.token.clone(),
+            sweep: Some(Sweep {
+                destination,
+                sources,
+                tx_hashes: legs.into_iter().map(|leg| leg.tx_hash).collect(),
+                window_secs: cfg.window_secs,
+            }),
+            ..Default::default()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::publisher::Publisher;
+    use crate::trace_context::TraceContext;
+    use crate::Token;
+
+    const HOT: &str = "0x00000000000000000000000000000000000000ff";
+
+    fn detector(min_sources: usize) -> SweepDetector {
+        SweepDetector::new(&Config {
+            watched_addresses_eth: (1..=5).map(deposit).collect(),
+            sweep_detection: Some(SweepConfig {
+                min_sources,
+                window_secs: 60,
+            }),
+            ..Default::default()
+        })
+    }
+
+    fn deposit(i: u8) -> String {
+        format!("0x{:040x}", i)
+    }
+
+    fn transfer(from: u8, value: &str) -> Event {
+        Event {
+            event_id: format!("eth:0x{:02x}", from),
+            chain: "ethereum".into(),
+            network: "mainnet".into(),
+            tx_hash: format!("0x{:064x}", from),
+            from: deposit(from),
+            to: HOT.to_ascii_uppercase().replace("0X", "0x"),
+            value: value.into(),
+            event_type: EventType::Erc20Transfer,
+            token: Some(Token {
+                address: "0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48".into(),
+                symbol: "USDC".into(),
+                decimals: 6,
+            }),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_sweep_config_validation() {
+        let cfg = |min_sources, window_secs| SweepConfig {
+            min_sources,
+            window_secs,
+        };
+        assert!(cfg(3, 600).validate().is_ok());
+        assert!(cfg(1, 600).validate().is_err());
+        assert!(cfg(3, 0).validate().is_err());
+    }
+
+    #[test]
+    fn test_sweep_detected_from_distinct_sources() {
+        let detector = detector(3);
+        let start = Instant::now();
+        assert!(detector.observe_at(&transfer(1, "100"), start).is_none());
+        // A second transfer from the same deposit address isn't a new source.
+        let mut again = transfer(1, "5");
+        again.tx_hash = "0xrepeat".into();
+        assert!(detector.observe_at(&again, start).is_none());
+        assert!(detector.observe_at(&transfer(2, "200"), start).is_none());
+        let sweep = detector.observe_at(&transfer(3, "300"), start).unwrap();
+
+        assert_eq!(sweep.event_type, EventType::SweepDetected);
+        assert_eq!(sweep.to, HOT);
+        assert_eq!(sweep.value, "605");
+        assert_eq!(sweep.token.as_ref().unwrap().symbol, "USDC");
+        let payload = sweep.sweep.unwrap();
+        assert_eq!(payload.sources, [deposit(1), deposit(2), deposit(3)]);
+        assert_eq!(payload.tx_hashes.len(), 4);
+        assert_eq!(payload.tx_hashes[1], "0xrepeat");
+
+        // The group starts over after a sweep.
+        assert!(detector.observe_at(&transfer(4, "1"), start).is_none());
+    }
+
+    #[test]
+    fn test_sweep_window_and_grouping() {
+        let detector = detector(2);
+        let start = Instant::now();
+        assert!(detector.observe_at(&transfer(1, "1"), start).is_none());
+        // Too late to join the first transfer.
+        let late = start + Duration::from_secs(61);
+        assert!(detector.observe_at(&transfer(2, "1"), late).is_none());
+
+        // Another token or an unwatched sender doesn't count.
+        let mut native = transfer(3, "1");
+        native.token = None;
+        native.event_type = EventType::Transfer;
+        assert!(detector.observe_at(&native, late).is_none());
+        assert!(detector.observe_at(&transfer(9, "1"), late).is_none());
+
+        let sweep = detector.observe_at(&transfer(4, "1"), late).unwrap();
+        assert_eq!(sweep.sweep.unwrap().sources, [deposit(2), deposit(4)]);
+        assert!(SweepDetector::default()
+            .observe_at(&transfer(1, "1"), start)
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn test_publisher_publishes_sweep() {
+        let (publisher, mut rx) = Publisher::in_memory();
+        let publisher = publisher.with_sweeps(detector(2));
+        publisher
+            .publish(&transfer(1, "1500000"), TraceContext::new_root())
+            .await
+            .unwrap();
+        publisher
+            .publish(&transfer(2, "2500000"), TraceContext::new_root())
+            .await
+            .unwrap();
+
+        assert_eq!(rx.recv().await.unwrap().event_id, "eth:0x01");
+        assert_eq!(rx.recv().await.unwrap().event_id, "eth:0x02");
+        let sweep: serde_json::Value =
+            serde_json::from_str(&rx.recv().await.unwrap().payload).unwrap();
+        assert_eq!(sweep["event_type"], "sweep_detected");
+        assert_eq!(sweep["value_formatted"], "4");
+        assert_eq!(sweep["sweep"]["tx_hashes"].as_array().unwrap().len(), 2);
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_backfill_publishes_no_sweep() {
+        let (publisher, mut rx) = Publisher::in_memory();
+        let publisher = publisher.with_sweeps(detector(2));
+        for (i, value) in [(1, "1500000"), (2, "2500000")] {
+            let event = Event {
+                backfill: true,
+                ..transfer(i, value)
+            };
+            publisher
+                .publish(&event, TraceContext::new_root())
+                .await
+                .unwrap();
+        }
+
+        for id in ["eth:0x01", "eth:0x02"] {
+            let payload: serde_json::Value =
+                serde_json::from_str(&rx.recv().await.unwrap().payload).unwrap();
+            assert_eq!(
+                (payload["event_id"].as_str(), &payload["backfill"]),
+                (Some(id), &serde_json::json!(true))
+            );
+        }
+        assert!(rx.try_recv().is_err());
+    }
+}
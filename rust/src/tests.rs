@@ -21,12 +21,12 @@ mod unit_tests {
         #[serde(rename = "type")]
         tx_type: String,
         hash: String,
-        block_number: i64,
-        timestamp: Option<i64>,
+        block_number: u64,
+        timestamp: Option<u64>,
         from: String,
         to: String,
         value: String,
-        decimals: i32,
+        decimals: u8,
         status: String,
         #[serde(skip_serializing_if = "Option::is_none")]
         token_address: Option<String>,
@@ -68,7 +68,7 @@ mod unit_tests {
         let json: serde_json::Value = serde_json::from_str(json_str).expect("Failed to parse JSON");
 
         let block_number = if let Some(block_hex) = json["blockNumber"].as_str() {
-            i64::from_str_radix(&block_hex[2..], 16).unwrap_or(0)
+            u64::from_str_radix(&block_hex[2..], 16).unwrap_or(0)
         } else {
             0
         };
@@ -122,12 +122,12 @@ mod unit_tests {
             }
         }
 
-        if let Some(slot) = json["slot"].as_f64() {
-            normalized.block_number = slot as i64;
+        if let Some(slot) = json["slot"].as_u64() {
+            normalized.block_number = slot;
         }
 
-        if let Some(block_time) = json["blockTime"].as_f64() {
-            normalized.timestamp = Some(block_time as i64);
+        if let Some(block_time) = json["blockTime"].as_u64() {
+            normalized.timestamp = Some(block_time);
         }
 
         if let Some(message) = json["transaction"]["message"].as_object() {
@@ -247,8 +247,19 @@ mod unit_tests {
         assert_eq!(from, from_addr);
         assert_eq!(to, to_addr);
 
-        let value = U256::from_big_endian(&log.data.0);
-        assert_eq!(value.as_u64(), 42);
+        let value = crate::amount::Amount::from_abi_word(&log.data, 18);
+        assert_eq!(value.to_string(), "42");
+    }
+
+    #[test]
+    fn test_erc20_amount_above_u64_is_not_truncated() {
+        // 2^64 * 1000 + 1: wraps to 1001 if squeezed through a u64.
+        let raw = (U256::from(u64::MAX) + 1) * 1000 + 1;
+        let mut data = [0u8; 32];
+        raw.to_big_endian(&mut data);
+        let value = crate::amount::Amount::from_abi_word(&data, 18);
+        assert_eq!(value.to_string(), "18446744073709551616001");
+        assert_eq!(value.formatted(), "18446.744073709551616001");
     }
 
     #[test]
@@ -0,0 +1,82 @@
+//! Token allow/deny lists for ERC-20 and SPL events.
+//!
+//! Checked before token metadata lookups so filtered tokens cost no extra RPC
+//! calls. EVM contract addresses are compared case-insensitively; Solana mints
+//! are base58 and compared exactly.
+use std::collections::HashSet;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TokenFilter {
+    allow: HashSet<String>,
+    deny: HashSet<String>,
+}
+
+pub(crate) fn normalize(token: &str) -> String {
+    let token = token.trim();
+    if token.starts_with("0x") || token.starts_with("0X") {
+        token.to_ascii_lowercase()
+    } else {
+        token.to_string()
+    }
+}
+
+impl TokenFilter {
+    pub fn new(allow: &[String], deny: &[String]) -> Self {
+        TokenFilter {
+            allow: allow.iter().map(|t| normalize(t)).collect(),
+            deny: deny.iter().map(|t| normalize(t)).collect(),
+        }
+    }
+
+    /// A token passes if it is not denylisted and either no allowlist is
+    /// configured or it is on the allowlist.
+    pub fn allows(&self, token: &str) -> bool {
+        let token = normalize(token);
+        !self.deny.contains(&token) && (self.allow.is_empty() || self.allow.contains(&token))
+    }
+
+    /// The allowlisted tokens, normalized.
+    pub fn allowlist(&self) -> impl Iterator<Item = &str> {
+        self.allow.iter().map(String::as_str)
+    }
+
+    /// For transactions touching several tokens: passes if any token does.
+    /// A transaction with no tokens (e.g. a native transfer) always passes.
+    pub fn allows_any<'a>(&self, tokens: impl IntoIterator<Item = &'a str>) -> bool {
+        let mut tokens = tokens.into_iter().peekable();
+        tokens.peek().is_none() || tokens.any(|t| self.allows(t))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const USDC: &str = "0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48";
+
+    #[test]
+    fn test_empty_filter_allows_everything() {
+        let filter = TokenFilter::default();
+        assert!(filter.allows(USDC));
+        assert!(filter.allows_any([]));
+    }
+
+    #[test]
+    fn test_allowlist_is_case_insensitive_for_evm() {
+        let filter = TokenFilter::new(&[USDC.to_lowercase()], &[]);
+        assert!(filter.allows(USDC));
+        assert!(!filter.allows("0x0000000000000000000000000000000000000001"));
+    }
+
+    #[test]
+    fn test_denylist_wins_and_mints_are_exact() {
+        let mint = "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v".to_string();
+        let filter = TokenFilter::new(&[mint.clone()], &[mint.clone()]);
+        assert!(!filter.allows(&mint));
+
+        let filter = TokenFilter::new(&[mint.clone()], &[]);
+        assert!(!filter.allows(&mint.to_lowercase()));
+        assert!(filter.allows_any(["spam", mint.as_str()]));
+        assert!(!filter.allows_any(["spam"]));
+    }
+}
@@ -0,0 +1,445 @@
+//! Token metadata (symbol and decimals) of ERC-20 contracts and SPL mints.
+//!
+//! ERC-20 tokens listed for the chain in the registry are answered from it.
+//! Others are resolved with `symbol()` and `decimals()` calls on the contract
+//! and remembered, so a busy token costs two calls once rather than on every
+//! transfer. With `TOKEN_WARMUP` the allowlisted tokens are resolved before
+//! the trackers start, so the first minutes after a deploy don't spike RPC
+//! usage and latency.
+//!
+//! SPL mints are resolved with one `getMultipleAccounts` call: the decimals
+//! come from the mint account and the symbol from its Metaplex metadata
+//! account, if it has one.
+//!
+//! Both caches hold the `TOKEN_CACHE_CAPACITY` most recently seen tokens.
+//! The `token_metadata_refresh` job resolves the cached ERC-20 tokens again
+//! once a day, so answers don't go stale for the life of the process.
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex as StdMutex};
+use std::time::Instant;
+
+use ethers::providers::Middleware;
+use ethers::types::transaction::eip2718::TypedTransaction;
+use ethers::types::{Address, NameOrAddress, TransactionRequest, U256};
+use solana_client::rpc_client::RpcClient;
+use solana_sdk::pubkey::Pubkey;
+use tracing::{debug, info};
+
+use crate::amount::Amount;
+use crate::chains::ChainInfo;
+use crate::erc20::TokenBehavior;
+
+/// Tokens remembered. Once full, the least recently seen one is forgotten.
+pub const TOKEN_CACHE_CAPACITY: usize = 10_000;
+
+/// Symbol of a token whose `symbol()` call failed or returned garbage, or of
+/// a mint without Metaplex metadata.
+pub const UNKNOWN_SYMBOL: &str = "UNKNOWN";
+
+/// Metaplex token metadata program.
+const METAPLEX_PROGRAM_ID: &str = "metaqbxxUerdq28cj1RbAWkYQm3ybzjb6a8bt518x1s";
+
+/// A map that forgets its least recently used entry once full.
+#[derive(Debug)]
+struct Lru<K, V> {
+    capacity: usize,
+    tick: u64,
+    entries: HashMap<K, (V, u64)>,
+}
+
+impl<K, V> Default for Lru<K, V> {
+    fn default() -> Self {
+        Lru {
+            capacity: TOKEN_CACHE_CAPACITY,
+            tick: 0,
+            entries: HashMap::new(),
+        }
+    }
+}
+
+impl<K: Eq + Hash + Clone, V: Clone> Lru<K, V> {
+    fn get(&mut self, key: &K) -> Option<V> {
+        self.tick += 1;
+        let (value, used) = self.entries.get_mut(key)?;
+        *used = self.tick;
+        Some(value.clone())
+    }
+
+    fn insert(&mut self, key: K, value: V) {
+        self.tick += 1;
+        if self.entries.len() >= self.capacity && !self.entries.contains_key(&key) {
+            // A linear scan, but only when a new token arrives at capacity.
+            let oldest = self
+                .entries
+                .iter()
+                .min_by_key(|(_, (_, used))| *used)
+                .map(|(key, _)| key.clone());
+            if let Some(oldest) = oldest {
+                self.entries.remove(&oldest);
+            }
+        }
+        self.entries.insert(key, (value, self.tick));
+    }
+
+    fn keys(&self) -> Vec<K> {
+        self.entries.keys().cloned().collect()
+    }
+
+    /// Replace the value of `key` if it is still present, without counting
+    /// as a use. Returns whether the value changed.
+    fn refresh(&mut self, key: &K, value: V) -> bool
+    where
+        V: PartialEq,
+    {
+        match self.entries.get_mut(key) {
+            Some((old, _)) if *old != value => {
+                *old = value;
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Token metadata of one chain. Cheap to clone; all clones share the cache.
+#[derive(Debug, Clone, Default)]
+pub struct TokenMetadata {
+    chain: Option<ChainInfo>,
+    cache: Arc<StdMutex<Lru<Address, (String, u8)>>>,
+}
+
+impl TokenMetadata {
+    pub fn new(chain: Option<ChainInfo>) -> Self {
+        TokenMetadata {
+            chain,
+            ..Default::default()
+        }
+    }
+
+    fn cached(&self, token: Address) -> Option<(String, u8)> {
+        if let Some(known) = self.chain.as_ref().and_then(|chain| chain.token(token)) {
+            return Some((known.symbol.clone(), known.decimals));
+        }
+        self.cache.lock().unwrap().get(&token)
+    }
+
+    /// Symbol and decimals of `token`. Non-standard contracts get sensible
+    /// defaults; those aren't cached, so a failed call is retried next time.
+    pub async fn get<M: Middleware>(&self, provider: &M, token: Address) -> (String, u8) {
+        if let Some(metadata) = self.cached(token) {
+            return metadata;
+        }
+        let (symbol, decimals) = fetch(provider, token).await;
+        if symbol != UNKNOWN_SYMBOL {
+            let mut cache = self.cache.lock().unwrap();
+            cache.insert(token, (symbol.clone(), decimals));
+        }
+        (symbol, decimals)
+    }
+
+    /// The registry's `behavior` of `token`, if it lists one.
+    pub fn behavior(&self, token: Address) -> Option<TokenBehavior> {
+        self.chain.as_ref()?.token(token)?.behavior
+    }
+
+    /// Resolve every token in `tokens` that isn't known yet. Entries that
+    /// aren't contract addresses (e.g. Solana mints) are skipped.
+    pub async fn warm_up<'a, M: Middleware>(
+        &self,
+        provider: &M,
+        tokens: impl IntoIterator<Item = &'a str>,
+    ) {
+        let started = Instant::now();
+        let mut resolved = 0;
+        for token in tokens {
+            let Ok(token) = token.parse::<Address>() else {
+                continue;
+            };
+            if self.cached(token).is_none() {
+                self.get(provider, token).await;
+                resolved += 1;
+            }
+        }
+        if resolved > 0 {
+            info!(
+                "Warmed up metadata of {} tokens in {:?}",
+                resolved,
+                started.elapsed()
+            );
+        }
+    }
+
+    /// Resolve every cached token again, for the `token_metadata_refresh`
+    /// job, so a proxy upgrade that changed a symbol or decimals is picked
+    /// up. A failed lookup keeps the cached answer. Returns how many tokens
+    /// changed.
+    pub async fn refresh<M: Middleware>(&self, provider: &M) -> usize {
+        let tokens = self.cache.lock().unwrap().keys();
+        let mut changed = 0;
+        for token in tokens {
+            let (symbol, decimals) = fetch(provider, token).await;
+            if symbol == UNKNOWN_SYMBOL {
+                continue;
+            }
+            if self
+                .cache
+                .lock()
+                .unwrap()
+                .refresh(&token, (symbol, decimals))
+            {
+                changed += 1;
+            }
+        }
+        changed
+    }
+}
+
+/// `eth_call` of a no-argument function of `token` by its selector.
+async fn call<M: Middleware>(provider: &M, token: Address, selector: &str) -> Option<Vec<u8>> {
+    let tx = TypedTransaction::Legacy(TransactionRequest {
+        to: Some(NameOrAddress::Address(token)),
+        data: Some(ethers::core::utils::hex::decode(selector).unwrap().into()),
+        ..Default::default()
+    });
+    provider
+        .call(&tx, None)
+        .await
+        .ok()
+        .map(|bytes| bytes.to_vec())
+}
+
+/// Call `symbol()` and `decimals()` on the contract, falling back to
+/// `UNKNOWN` and 18 for non-standard contracts.
+async fn fetch<M: Middleware>(provider: &M, token: Address) -> (String, u8) {
+    // symbol() - function selector 0x95d89b41
+    let symbol = match call(provider, token, "95d89b41").await {
+        // Decode as string (ABI encoded string starts with offset, length, then data)
+        Some(bytes) if bytes.len() >= 64 => {
+            // Skip offset (32 bytes), read length (32 bytes)
+            // A length beyond usize can't be in the response either.
+            let len = usize::try_from(U256::from_big_endian(&bytes[32..64])).unwrap_or(usize::MAX);
+            if bytes.len() - 64 >= len {
+                String::from_utf8(bytes[64..64 + len].to_vec())
+                    .unwrap_or_else(|_| UNKNOWN_SYMBOL.to_string())
+            } else {
+                UNKNOWN_SYMBOL.to_string()
+            }
+        }
+        _ => UNKNOWN_SYMBOL.to_string(),
+    };
+
+    // decimals() - function selector 0x313ce567
+    let decimals = match call(provider, token, "313ce567").await {
+        // A value that doesn't fit is as unusable as a failed call.
+        Some(bytes) if bytes.len() >= 32 => {
+            u8::try_from(Amount::from_abi_word(&bytes, 0).raw()).unwrap_or(18)
+        }
+        _ => 18,
+    };
+
+    (symbol, decimals)
+}
+
+/// Metadata of SPL mints. Cheap to clone; all clones share the cache.
+#[derive(Debug, Clone, Default)]
+pub struct SplMetadata {
+    cache: Arc<StdMutex<Lru<String, (String, u8)>>>,
+}
+
+impl SplMetadata {
+    /// Symbol and decimals of `mint`. `reported_decimals`, the decimals of
+    /// the transaction's token balances, stand in if the mint account can't
+    /// be read; that answer isn't cached, so the read is retried next time.
+    pub fn get(&self, rpc_client: &RpcClient, mint: &str, reported_decimals: u8) -> (String, u8) {
+        if let Some(metadata) = self.cache.lock().unwrap().get(&mint.to_string()) {
+            return metadata;
+        }
+        match fetch_mint(rpc_client, mint) {
+            Ok(metadata) => {
+                self.cache
+                    .lock()
+                    .unwrap()
+                    .insert(mint.to_string(), metadata.clone());
+                metadata
+            }
+            Err(e) => {
+                debug!("Failed to read mint {}: {:#}", mint, e);
+                (UNKNOWN_SYMBOL.to_string(), reported_decimals)
+            }
+        }
+    }
+}
+
+/// Read the mint account of `mint` and its Metaplex metadata account.
+fn fetch_mint(rpc_client: &RpcClient, mint: &str) -> anyhow::Result<(String, u8)> {
+    let mint = Pubkey::from_str(mint)?;
+    let program = Pubkey::from_str(METAPLEX_PROGRAM_ID)?;
+    let (metadata, _) =
+        Pubkey::find_program_address(&[b"metadata", program.as_ref(), mint.as_ref()], &program);
+    let accounts = rpc_client.get_multiple_accounts(&[mint, metadata])?;
+    let decimals = accounts
+        .first()
+        .and_then(Option::as_ref)
+        .and_then(|account| mint_decimals(&account.data))
+        .ok_or_else(|| anyhow::anyhow!("{} is not an initialized mint", mint))?;
+    let symbol = accounts
+        .get(1)
+        .and_then(Option::as_ref)
+        .and_then(|account| metaplex_symbol(&account.data))
+        .unwrap_or_else(|| UNKNOWN_SYMBOL.to_string());
+    Ok((symbol, decimals))
+}
+
+/// Decimals of an initialized mint, from its account data. The base layout
+/// is shared by SPL Token and Token-2022 mints: mint authority (36 bytes),
+/// supply (8), decimals (1), is_initialized (1), freeze authority (36).
+fn mint_decimals(data: &[u8]) -> Option<u8> {
+    if data.len() < 82 || data[45] != 1 {
+        return None;
+    }
+    Some(data[44])
+}
+
+/// Symbol of a Metaplex metadata account: key (1 byte), update authority
+/// (32), mint (32), then the name and symbol as Borsh strings, padded with
+/// NULs.
+fn metaplex_symbol(data: &[u8]) -> Option<String> {
+    let borsh_string = |at: usize| -> Option<(String, usize)> {
+        let len = u32::from_le_bytes(data.get(at..at + 4)?.try_into().ok()?) as usize;
+        let bytes = data.get(at + 4..(at + 4).checked_add(len)?)?;
+        let s = String::from_utf8(bytes.to_vec()).ok()?;
+        Some((s.trim_end_matches('\0').trim().to_string(), at + 4 + len))
+    };
+    let (_, after_name) = borsh_string(65)?;
+    let (symbol, _) = borsh_string(after_name)?;
+    (!symbol.is_empty()).then_some(symbol)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chains::ChainRegistry;
+    use ethers::providers::Provider;
+
+    const USDC: &str = "0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48";
+
+    #[tokio::test]
+    async fn test_registry_and_cache_hits_make_no_calls() {
+        // The mock has no responses queued, so any call fails.
+        let (provider, _mock) = Provider::mocked();
+        let metadata = TokenMetadata::new(ChainRegistry::default().get(1).cloned());
+        let usdc: Address = USDC.parse().unwrap();
+        assert_eq!(metadata.get(&provider, usdc).await, ("USDC".to_string(), 6));
+
+        let token = Address::from_low_u64_be(7);
+        metadata
+            .cache
+            .lock()
+            .unwrap()
+            .insert(token, ("TKN".into(), 8));
+        assert_eq!(metadata.get(&provider, token).await, ("TKN".to_string(), 8));
+        assert_eq!(metadata.clone().cached(token), Some(("TKN".into(), 8)));
+    }
+
+    #[tokio::test]
+    async fn test_failed_lookups_are_not_cached() {
+        let (provider, _mock) = Provider::mocked();
+        let metadata = TokenMetadata::default();
+        let token = Address::from_low_u64_be(9);
+        metadata
+            .warm_up(&provider, [format!("{:?}", token).as_str(), "MintA"])
+            .await;
+        assert_eq!(
+            metadata.get(&provider, token).await,
+            (UNKNOWN_SYMBOL.to_string(), 18)
+        );
+        assert!(metadata.cached(token).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_refresh_keeps_entries_on_failed_lookups() {
+        let (provider, _mock) = Provider::mocked();
+        let metadata = TokenMetadata::default();
+        let token = Address::from_low_u64_be(7);
+        metadata
+            .cache
+            .lock()
+            .unwrap()
+            .insert(token, ("TKN".into(), 8));
+        assert_eq!(metadata.refresh(&provider).await, 0);
+        assert_eq!(metadata.cached(token), Some(("TKN".into(), 8)));
+    }
+
+    #[test]
+    fn test_lru_refresh_doesnt_count_as_use() {
+        let mut lru = Lru {
+            capacity: 2,
+            ..Default::default()
+        };
+        lru.insert("a", 1);
+        lru.insert("b", 2);
+        assert!(lru.refresh(&"a", 10));
+        assert!(!lru.refresh(&"a", 10));
+        assert!(!lru.refresh(&"z", 1));
+        // "a" is still the least recently used.
+        lru.insert("c", 3);
+        assert_eq!(lru.get(&"a"), None);
+        assert_eq!(lru.get(&"b"), Some(2));
+    }
+
+    #[test]
+    fn test_lru_forgets_least_recently_used() {
+        let mut lru = Lru {
+            capacity: 2,
+            ..Default::default()
+        };
+        lru.insert("a", 1);
+        lru.insert("b", 2);
+        assert_eq!(lru.get(&"a"), Some(1));
+        lru.insert("c", 3);
+        assert_eq!(lru.get(&"b"), None);
+        assert_eq!(lru.get(&"a"), Some(1));
+        assert_eq!(lru.get(&"c"), Some(3));
+        // Replacing an entry doesn't evict another.
+        lru.insert("c", 4);
+        assert_eq!(lru.entries.len(), 2);
+        assert_eq!(lru.get(&"c"), Some(4));
+    }
+
+    #[test]
+    fn test_mint_account_layouts() {
+        let mut mint = vec![0u8; 82];
+        mint[44] = 6;
+        assert_eq!(mint_decimals(&mint), None);
+        mint[45] = 1;
+        assert_eq!(mint_decimals(&mint), Some(6));
+        // Token-2022 mints carry extensions after the base layout.
+        mint.extend([0u8; 90]);
+        assert_eq!(mint_decimals(&mint), Some(6));
+        assert_eq!(mint_decimals(&mint[..60]), None);
+
+        let mut metadata = vec![4u8];
+        metadata.extend([0u8; 64]);
+        for field in ["USD Coin\0\0", "USDC\0\0\0\0\0\0"] {
+            metadata.extend((field.len() as u32).to_le_bytes());
+            metadata.extend(field.as_bytes());
+        }
+        assert_eq!(metaplex_symbol(&metadata), Some("USDC".to_string()));
+        assert_eq!(metaplex_symbol(&metadata[..80]), None);
+    }
+
+    #[test]
+    fn test_unreadable_mint_uses_reported_decimals() {
+        // Nothing listens on port 1, so the account read fails.
+        let rpc_client = RpcClient::new("http://127.0.0.1:1".to_string());
+        let spl = SplMetadata::default();
+        let mint = "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v";
+        assert_eq!(
+            spl.get(&rpc_client, mint, 6),
+            (UNKNOWN_SYMBOL.to_string(), 6)
+        );
+        assert!(spl.cache.lock().unwrap().get(&mint.to_string()).is_none());
+    }
+}
@@ -0,0 +1,143 @@
+//! W3C Trace Context (`traceparent`) for published events.
+//!
+//! A chain tracker starts a trace when it begins processing a block, log or
+//! transaction and hands it to `Publisher::publish`, which gives each event
+//! published from it a child span: same trace id, its own span id. The
+//! event's `traceparent` names that span as parent, so a consumer reading
+//! from Redis can start its own spans as children of it, and the events of
+//! one block or transaction (with their alerts) share a trace. The same ids
+//! are attached to the tracker's log lines for the event (`trace_id`,
+//! `span_id`, `parent_span_id`), which lets the two sides be joined without
+//! a tracing backend. Events not read from a chain, such as heartbeats,
+//! start a trace of their own.
+//!
+//! Format: `00-<32 hex trace id>-<16 hex parent id>-<2 hex flags>`, see
+//! <https://www.w3.org/TR/trace-context/#traceparent-header>.
+use std::fmt;
+
+use rand::Rng;
+
+const VERSION: &str = "00";
+const FLAG_SAMPLED: u8 = 0x01;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TraceContext {
+    pub trace_id: u128,
+    pub span_id: u64,
+    pub flags: u8,
+}
+
+impl TraceContext {
+    /// Start a new sampled trace.
+    pub fn new_root() -> Self {
+        let mut rng = rand::thread_rng();
+        TraceContext {
+            // All-zero ids are invalid per the spec.
+            trace_id: rng.gen_range(1..=u128::MAX),
+            span_id: rng.gen_range(1..=u64::MAX),
+            flags: FLAG_SAMPLED,
+        }
+    }
+
+    /// A span in the same trace, as a child of this one.
+    pub fn child(&self) -> Self {
+        TraceContext {
+            span_id: rand::thread_rng().gen_range(1..=u64::MAX),
+            ..*self
+        }
+    }
+
+    /// Parse a `traceparent` header value. Returns `None` for malformed or
+    /// all-zero ids. Used to validate what consumers receive.
+    #[allow(dead_code)]
+    pub fn parse(traceparent: &str) -> Option<Self> {
+        let mut parts = traceparent.trim().split('-');
+        let (version, trace_id, span_id, flags) =
+            (parts.next()?, parts.next()?, parts.next()?, parts.next()?);
+        if version.len() != 2 || version == "ff" || trace_id.len() != 32 || span_id.len() != 16 {
+            return None;
+        }
+        // Version 00 has exactly four fields; later versions may append more.
+        if version == VERSION && parts.next().is_some() {
+            return None;
+        }
+        let ctx = TraceContext {
+            trace_id: u128::from_str_radix(trace_id, 16).ok()?,
+            span_id: u64::from_str_radix(span_id, 16).ok()?,
+            flags: u8::from_str_radix(flags, 16).ok()?,
+        };
+        (ctx.trace_id != 0 && ctx.span_id != 0).then_some(ctx)
+    }
+
+    pub fn trace_id_hex(&self) -> String {
+        format!("{:032x}", self.trace_id)
+    }
+
+    pub fn span_id_hex(&self) -> String {
+        format!("{:016x}", self.span_id)
+    }
+}
+
+impl fmt::Display for TraceContext {
+    /// Formats as a `traceparent` value.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}-{:032x}-{:016x}-{:02x}",
+            VERSION, self.trace_id, self.span_id, self.flags
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_and_parse_round_trip() {
+        let ctx = TraceContext::new_root();
+        let header = ctx.to_string();
+        assert_eq!(header.len(), 55);
+        assert!(header.starts_with("00-") && header.ends_with("-01"));
+        assert_eq!(TraceContext::parse(&header), Some(ctx));
+    }
+
+    #[test]
+    fn test_child_keeps_the_trace() {
+        let root = TraceContext::new_root();
+        let child = root.child();
+        assert_eq!(child.trace_id, root.trace_id);
+        assert_eq!(child.flags, root.flags);
+        assert_ne!(child.span_id, root.span_id);
+    }
+
+    #[test]
+    fn test_parse_spec_example() {
+        let ctx =
+            TraceContext::parse("00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01").unwrap();
+        assert_eq!(ctx.trace_id_hex(), "4bf92f3577b34da6a3ce929d0e0e4736");
+        assert_eq!(ctx.span_id_hex(), "00f067aa0ba902b7");
+        assert_eq!(ctx.flags, FLAG_SAMPLED);
+    }
+
+    #[test]
+    fn test_parse_rejects_invalid() {
+        assert!(TraceContext::parse("").is_none());
+        assert!(
+            TraceContext::parse("00-00000000000000000000000000000000-00f067aa0ba902b7-01")
+                .is_none()
+        );
+        assert!(
+            TraceContext::parse("00-4bf92f3577b34da6a3ce929d0e0e4736-0000000000000000-01")
+                .is_none()
+        );
+        assert!(
+            TraceContext::parse("ff-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01")
+                .is_none()
+        );
+        assert!(
+            TraceContext::parse("00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01-x")
+                .is_none()
+        );
+    }
+}
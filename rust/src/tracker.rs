@@ -0,0 +1,618 @@
+//! Chain trackers behind a common interface.
+//!
+//! A `ChainTracker` follows one chain: it subscribes to or polls the chain's
+//! node, publishes the events it finds through the shared `Publisher` and
+//! keeps a checkpoint of the last block or slot it processed. `run` starts
+//! the built-in Ethereum and Solana trackers with `spawn_all`; a service
+//! embedding the listener can do the same with its own set, and a new chain
+//! only needs an implementation of the trait.
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use ethers::providers::{Http, Ipc, Provider, Ws};
+use tokio::sync::Mutex;
+use tokio::task::JoinSet;
+use tokio::time::Duration;
+use tracing::{error, info, warn};
+
+use crate::block_times::BlockTimes;
+use crate::config::{self, Config};
+use crate::control::ChainControl;
+use crate::dedup::DedupSet;
+use crate::gaps::BlockLedger;
+use crate::headers::HeaderCache;
+use crate::publisher::Publisher;
+use crate::rpc_batch::RpcBatch;
+use crate::rpc_guard::RpcGuard;
+use crate::token_metadata::TokenMetadata;
+use crate::trace_context::TraceContext;
+use crate::watchlist::WatchList;
+use crate::{helius, Event};
+
+#[async_trait]
+pub trait ChainTracker: Send + Sync + 'static {
+    /// The event `chain` name, e.g. `ethereum`.
+    fn chain(&self) -> &'static str;
+
+    /// Whether the configuration enables this chain.
+    fn enabled(&self) -> bool;
+
+    /// Follow the chain, over a subscription or by polling depending on the
+    /// configured endpoint, and publish its events. Reconnects after
+    /// failures and waits while the chain is paused; returns once the
+    /// control's shutdown token is cancelled and the unit of work in flight
+    /// is done, or if the tracker can't run at all.
+    async fn track(&self);
+
+    /// The last block or slot fully processed, where tracking resumes after
+    /// a reconnect.
+    async fn checkpoint(&self) -> Option<u64>;
+
+    /// Pause and resume handle, shared with the admin API.
+    fn control(&self) -> &ChainControl;
+
+    /// Where the tracker's events go.
+    fn publisher(&self) -> &Publisher;
+
+    /// Publish an event found by this tracker, under a trace of its own.
+    async fn emit(&self, event: &Event) -> anyhow::Result<()> {
+        self.publisher()
+            .publish(event, TraceContext::new_root())
+            .await
+    }
+}
+
+/// Run each enabled tracker on its own runtime (see `runtimes.rs`) until
+/// they all return, which they do once the listener shuts down. Fails if a
+/// tracker panics.
+pub async fn spawn_all(
+    trackers: Vec<Arc<dyn ChainTracker>>,
+    threads: Option<usize>,
+) -> anyhow::Result<()> {
+    let mut running = JoinSet::new();
+    for tracker in trackers {
+        if !tracker.enabled() {
+            info!("{} tracking disabled", tracker.chain());
+            continue;
+        }
+        let handle = crate::runtimes::spawn_chain(tracker.chain(), threads, async move {
+            tracker.track().await;
+            info!(
+                "{} tracking stopped at {:?}",
+                tracker.chain(),
+                tracker.checkpoint().await
+            );
+        });
+        running.spawn(handle);
+    }
+    while let Some(joined) = running.join_next().await {
+        joined??;
+    }
+    Ok(())
+}
+
+/// Native transfers and ERC-20 logs of watched Ethereum addresses, over IPC,
+/// WebSocket subscriptions or HTTP polling depending on `ETH_RPC_URL`, or
+/// on the backup endpoint the consistency guard pins. An `[[evm_networks]]`
+/// chain gets its own tracker, built from that network's config (see
+/// `evm_networks.rs`) and tagging events with its control's chain name.
+pub struct EthTracker {
+    cfg: Config,
+    /// Replaces `cfg.watched_addresses_eth`; tracking restarts when it
+    /// changes.
+    watched: WatchList,
+    processed_txs: Arc<Mutex<DedupSet>>,
+    last_block: Arc<Mutex<Option<u64>>>,
+    ledger: Arc<Mutex<BlockLedger>>,
+    /// Outlives reconnects, like the dedup state.
+    token_metadata: TokenMetadata,
+    rpc: RpcGuard,
+    control: ChainControl,
+    publisher: Publisher,
+}
+
+impl EthTracker {
+    /// `processed_txs` may be shared with other trackers.
+    pub fn new(
+        cfg: Config,
+        processed_txs: Arc<Mutex<DedupSet>>,
+        control: ChainControl,
+        publisher: Publisher,
+    ) -> Self {
+        EthTracker {
+            rpc: RpcGuard::new(&cfg),
+            token_metadata: TokenMetadata::new(cfg.eth_chain.clone()),
+            watched: WatchList::new(control.chain(), &cfg.watched_addresses_eth),
+            cfg,
+            processed_txs,
+            last_block: Arc::default(),
+            ledger: Arc::default(),
+            control,
+            publisher,
+        }
+    }
+
+    /// Follow `watched` instead of the configured addresses, e.g. a list
+    /// the admin API changes.
+    pub fn with_watch_list(mut self, watched: WatchList) -> Self {
+        self.watched = watched;
+        self
+    }
+
+    /// The checkpoint, shared with the heartbeat job and the admin API.
+    pub(crate) fn last_block(&self) -> &Arc<Mutex<Option<u64>>> {
+        &self.last_block
+    }
+
+    /// Blocks processed so far, shared with the gap auditor.
+    pub(crate) fn ledger(&self) -> &Arc<Mutex<BlockLedger>> {
+        &self.ledger
+    }
+
+    /// Token metadata cache, shared with the `token_metadata_refresh` job.
+    pub(crate) fn token_metadata(&self) -> &TokenMetadata {
+        &self.token_metadata
+    }
+
+    /// The endpoints and the consistency guard over them.
+    pub(crate) fn rpc(&self) -> &RpcGuard {
+        &self.rpc
+    }
+
+    /// Track the addresses of `cfg` over `url` until it fails for good.
+    async fn follow(
+        &self,
+        cfg: &Config,
+        url: &str,
+        archive: Option<Arc<Provider<Http>>>,
+        token_metadata: &TokenMetadata,
+        block_times: &BlockTimes,
+        headers: &HeaderCache,
+    ) {
+        let control = &self.control;
+        // Support IPC (co-located node), WebSocket (for production) and
+        // HTTP (for Anvil testing)
+        let ipc_path = url.strip_prefix("ipc://");
+        let use_websocket = url.starts_with("ws");
+
+        if let Some(path) = ipc_path {
+            loop {
+                control.wait_resumed().await;
+                if control.is_shutting_down() {
+                    return;
+                }
+                info!("Connecting to ETH IPC provider at {}", path);
+                let ipc = match Ipc::connect(path).await {
+                    Ok(ipc) => ipc,
+                    Err(e) => {
+                        error!("Failed to connect ETH IPC: {:?}. Retrying in 10s.", e);
+                        control.set_connected(false);
+                        control.sleep(Duration::from_secs(10)).await;
+                        continue;
+                    }
+                };
+                info!("Successfully connected to ETH IPC provider.");
+                control.set_connected(true);
+                crate::run_eth_subscriptions(
+                    Arc::new(Provider::new(ipc)),
+                    cfg,
+                    archive.clone(),
+                    token_metadata,
+                    block_times,
+                    headers,
+                    &self.processed_txs,
+                    &self.last_block,
+                    &self.ledger,
+                    control,
+                    &self.publisher,
+                )
+                .await;
+                control.set_connected(false);
+                if control.is_shutting_down() {
+                    return;
+                }
+                if control.is_paused() {
+                    info!(
+                        "ETH trackers disconnected while paused at block {:?}",
+                        self.checkpoint().await
+                    );
+                    continue;
+                }
+                warn!("An ETH IPC tracker task has finished. Restarting trackers after 5s delay.");
+                control.sleep(Duration::from_secs(5)).await;
+            }
+        } else if use_websocket {
+            loop {
+                control.wait_resumed().await;
+                if control.is_shutting_down() {
+                    return;
+                }
+                info!("Connecting to ETH WebSocket provider at {}", url);
+                let ws = match Ws::connect(url).await {
+                    Ok(ws) => ws,
+                    Err(e) => {
+                        error!("Failed to connect ETH WebSocket: {:?}. Retrying in 10s.", e);
+                        control.set_connected(false);
+                        control.sleep(Duration::from_secs(10)).await;
+                        continue;
+                    }
+                };
+                info!("Successfully connected to ETH WebSocket provider.");
+                control.set_connected(true);
+                crate::run_eth_subscriptions(
+                    Arc::new(Provider::new(ws)),
+                    cfg,
+                    archive.clone(),
+                    token_metadata,
+                    block_times,
+                    headers,
+                    &self.processed_txs,
+                    &self.last_block,
+                    &self.ledger,
+                    control,
+                    &self.publisher,
+                )
+                .await;
+                control.set_connected(false);
+                if control.is_shutting_down() {
+                    return;
+                }
+                if control.is_paused() {
+                    info!(
+                        "ETH trackers disconnected while paused at block {:?}",
+                        self.checkpoint().await
+                    );
+                    continue;
+                }
+                warn!(
+                    "An ETH WebSocket tracker task has finished. Restarting trackers after 5s delay."
+                );
+                control.sleep(Duration::from_secs(5)).await;
+            }
+        } else {
+            // HTTP polling mode for Anvil testing
+            info!("Using HTTP polling mode for ETH at {}", url);
+            let provider = match Provider::<Http>::try_from(url) {
+                Ok(p) => Arc::new(p),
+                Err(e) => {
+                    error!("Failed to create HTTP provider: {:?}", e);
+                    return;
+                }
+            };
+            if cfg.token_warmup {
+                token_metadata
+                    .warm_up(provider.as_ref(), cfg.eth_tokens.allowlist())
+                    .await;
+            }
+            let batch = (cfg.eth_rpc_batch_size > 0)
+                .then(|| RpcBatch::new(url, cfg.eth_rpc_batch_size as usize));
+            crate::poll_eth_blocks(
+                provider,
+                batch,
+                cfg.watched_addresses_eth.clone(),
+                cfg.eth_network.clone(),
+                token_metadata.clone(),
+                cfg.eth_tokens.clone(),
+                cfg.spam.clone(),
+                Arc::clone(&self.processed_txs),
+                Arc::clone(&self.last_block),
+                Arc::clone(&self.ledger),
+                headers.clone(),
+                cfg.checkpoints.max_catchup_blocks,
+                cfg.eth_confirmations,
+                control.clone(),
+                self.publisher.clone(),
+            )
+            .await;
+        }
+    }
+}
+
+#[async_trait]
+impl ChainTracker for EthTracker {
+    fn chain(&self) -> &'static str {
+        // `ethereum`, or an `[[evm_networks]]` chain.
+        self.control.chain()
+    }
+
+    fn enabled(&self) -> bool {
+        self.cfg.eth_enabled
+    }
+
+    async fn track(&self) {
+        let cfg = &self.cfg;
+        match &cfg.eth_chain {
+            Some(chain) => info!(
+                "Ethereum network {} is {} (chain id {})",
+                cfg.eth_network, chain.name, chain.chain_id
+            ),
+            None => info!(
+                "Ethereum network {} is not in the chain registry; set ETH_CHAIN_ID \
+                 or add it with CHAIN_REGISTRY_FILE",
+                cfg.eth_network
+            ),
+        }
+
+        let archive = cfg.eth_archive_rpc_url.as_ref().and_then(|url| {
+            match Provider::<Http>::try_from(url.as_str()) {
+                Ok(p) => Some(Arc::new(p)),
+                Err(e) => {
+                    warn!("Ignoring invalid ETH_ARCHIVE_RPC_URL: {:?}", e);
+                    None
+                }
+            }
+        });
+
+        // Outlive reconnects, like the dedup state.
+        let block_times = BlockTimes::default();
+        let headers = HeaderCache::default();
+
+        // Follow the endpoint the consistency guard pins, moving when it
+        // pins another one, and start over when the watch list changes.
+        loop {
+            let mut pinned = self.rpc.subscribe();
+            let mut watched = self.watched.subscribe();
+            let cfg = Config {
+                watched_addresses_eth: self.watched.addresses(),
+                ..cfg.clone()
+            };
+            let url = self.rpc.active_url();
+            let following = self.follow(
+                &cfg,
+                &url,
+                archive.clone(),
+                &self.token_metadata,
+                &block_times,
+                &headers,
+            );
+            tokio::select! {
+                _ = following => return,
+                _ = pinned.changed() => {
+                    warn!(
+                        "Moving ETH tracking from {} to {}",
+                        url,
+                        self.rpc.active_url()
+                    );
+                    self.control.set_connected(false);
+                }
+                _ = watched.changed() => {
+                    info!(
+                        "Restarting {} tracking for {} watched addresses",
+                        self.chain(),
+                        self.watched.addresses().len()
+                    );
+                    self.control.set_connected(false);
+                }
+            }
+        }
+    }
+
+    async fn checkpoint(&self) -> Option<u64> {
+        *self.last_block.lock().await
+    }
+
+    fn control(&self) -> &ChainControl {
+        &self.control
+    }
+
+    fn publisher(&self) -> &Publisher {
+        &self.publisher
+    }
+}
+
+/// Transactions touching watched Solana addresses, from RPC polling, Helius
+/// or Geyser depending on `SOL_SOURCE`.
+pub struct SolTracker {
+    cfg: Config,
+    /// Replaces `cfg.watched_addresses_sol`; tracking restarts when it
+    /// changes.
+    watched: WatchList,
+    processed_txs: Arc<Mutex<DedupSet>>,
+    last_slot: Arc<Mutex<Option<u64>>>,
+    control: ChainControl,
+    publisher: Publisher,
+}
+
+impl SolTracker {
+    /// `processed_txs` may be shared with other trackers.
+    pub fn new(
+        cfg: Config,
+        processed_txs: Arc<Mutex<DedupSet>>,
+        control: ChainControl,
+        publisher: Publisher,
+    ) -> Self {
+        SolTracker {
+            watched: WatchList::new("solana", &cfg.watched_addresses_sol),
+            cfg,
+            processed_txs,
+            last_slot: Arc::default(),
+            control,
+            publisher,
+        }
+    }
+
+    /// Follow `watched` instead of the configured addresses, e.g. a list
+    /// the admin API changes.
+    pub fn with_watch_list(mut self, watched: WatchList) -> Self {
+        self.watched = watched;
+        self
+    }
+
+    /// The checkpoint, shared with the heartbeat job and the admin API.
+    pub(crate) fn last_slot(&self) -> &Arc<Mutex<Option<u64>>> {
+        &self.last_slot
+    }
+
+    /// Track the addresses of `cfg` from the configured source until it
+    /// fails for good.
+    async fn follow(&self, cfg: &Config) {
+        let control = self.control.clone();
+        let publisher = self.publisher.clone();
+        if let (config::SolSource::Helius, Some(api_key)) = (cfg.sol_source, &cfg.helius_api_key) {
+            return helius::track(
+                helius::Helius::new(&cfg.helius_url, api_key).with_commitment(cfg.sol_commitment),
+                &cfg.sol_network,
+                &cfg.watched_addresses_sol,
+                &cfg.sol_tokens,
+                &cfg.spam,
+                Arc::clone(&self.processed_txs),
+                Arc::clone(&self.last_slot),
+                control,
+                publisher,
+            )
+            .await;
+        }
+        if let (config::SolSource::Geyser, Some(endpoint)) = (cfg.sol_source, &cfg.geyser_endpoint)
+        {
+            #[cfg(feature = "geyser")]
+            return crate::geyser::track(
+                endpoint,
+                cfg.geyser_x_token.clone(),
+                &cfg.sol_network,
+                &cfg.watched_addresses_sol,
+                &cfg.sol_tokens,
+                &cfg.spam,
+                cfg.sol_commitment,
+                Arc::clone(&self.processed_txs),
+                Arc::clone(&self.last_slot),
+                control,
+                publisher,
+            )
+            .await;
+            #[cfg(not(feature = "geyser"))]
+            {
+                error!(
+                    "SOL_SOURCE=geyser ({}) needs a build with the geyser feature",
+                    endpoint
+                );
+                return;
+            }
+        }
+        crate::track_solana_transfers(
+            &cfg.sol_rpc_url,
+            &cfg.sol_network,
+            &cfg.watched_addresses_sol,
+            &cfg.sol_tokens,
+            &cfg.spam,
+            cfg.sol_commitment,
+            Arc::clone(&self.processed_txs),
+            Arc::clone(&self.last_slot),
+            control,
+            publisher,
+        )
+        .await
+    }
+}
+
+#[async_trait]
+impl ChainTracker for SolTracker {
+    fn chain(&self) -> &'static str {
+        "solana"
+    }
+
+    fn enabled(&self) -> bool {
+        self.cfg.sol_enabled
+    }
+
+    async fn track(&self) {
+        // Start over whenever the watch list changes.
+        loop {
+            let mut watched = self.watched.subscribe();
+            let cfg = Config {
+                watched_addresses_sol: self.watched.addresses(),
+                ..self.cfg.clone()
+            };
+            tokio::select! {
+                _ = self.follow(&cfg) => {
+                    // The trackers return straight away with nothing to
+                    // watch; wait for an address then.
+                    if !cfg.watched_addresses_sol.is_empty() || self.control.is_shutting_down() {
+                        return;
+                    }
+                    tokio::select! {
+                        _ = watched.changed() => {}
+                        _ = self.control.shutting_down() => return,
+                    }
+                }
+                _ = watched.changed() => {
+                    info!(
+                        "Restarting Solana tracking for {} watched addresses",
+                        self.watched.addresses().len()
+                    );
+                    self.control.set_connected(false);
+                }
+            }
+        }
+    }
+
+    async fn checkpoint(&self) -> Option<u64> {
+        *self.last_slot.lock().await
+    }
+
+    fn control(&self) -> &ChainControl {
+        &self.control
+    }
+
+    fn publisher(&self) -> &Publisher {
+        &self.publisher
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event_type::EventType;
+
+    #[tokio::test]
+    async fn test_eth_tracker_checkpoint_and_emit() {
+        let (publisher, mut rx) = Publisher::in_memory();
+        let tracker = EthTracker::new(
+            Config {
+                eth_enabled: true,
+                ..Default::default()
+            },
+            Arc::default(),
+            ChainControl::new("ethereum"),
+            publisher,
+        );
+        assert!(tracker.enabled());
+        assert_eq!(tracker.checkpoint().await, None);
+        *tracker.last_block().lock().await = Some(42);
+        assert_eq!(tracker.checkpoint().await, Some(42));
+
+        let event = Event {
+            event_id: "eth:0xabc".into(),
+            chain: "ethereum".into(),
+            event_type: EventType::Transfer,
+            ..Default::default()
+        };
+        tracker.emit(&event).await.unwrap();
+        assert!(rx
+            .recv()
+            .await
+            .unwrap()
+            .payload
+            .contains(r#""event_id":"eth:0xabc""#));
+    }
+
+    #[tokio::test]
+    async fn test_disabled_trackers_are_not_started() {
+        let (publisher, _rx) = Publisher::in_memory();
+        let trackers: Vec<Arc<dyn ChainTracker>> = vec![
+            Arc::new(EthTracker::new(
+                Config::default(),
+                Arc::default(),
+                ChainControl::new("ethereum"),
+                publisher.clone(),
+            )),
+            Arc::new(SolTracker::new(
+                Config::default(),
+                Arc::default(),
+                ChainControl::new("solana"),
+                publisher,
+            )),
+        ];
+        assert!(trackers.iter().all(|t| !t.enabled()));
+        spawn_all(trackers, None).await.unwrap();
+    }
+}
@@ -0,0 +1,785 @@
+//! Tron tracking (`[tron]` in `CONFIG_FILE`).
+//!
+//! Tron has no JSON-RPC subscriptions the listener could share with the EVM
+//! trackers, so this tracker polls the TronGrid REST API (or a full node
+//! serving the same `/v1` endpoints) for every watched address: native TRX
+//! transfers (`TransferContract` transactions) are published as `transfer`
+//! events and TRC-20 transfers, USDT among them, as `erc20_transfer` events,
+//! both with `chain` `tron`:
+//!
+//! ```toml
+//! [tron]
+//! api_url = "https://api.trongrid.io"   # default
+//! api_key = "<TRON-PRO-API-KEY>"        # optional, raises TronGrid's rate limit
+//! network = "mainnet"                   # default
+//! watched_addresses = ["T..."]
+//! token_allowlist = ["TR7NHqjeKQxGTCi8q8ZY4pL8otSzgjLj6t"]  # optional
+//! poll_interval_secs = 5                # default
+//! ```
+//!
+//! Only confirmed (solidified) transactions are asked for, so events lag the
+//! head by about a minute and are never retracted. Addresses are base58
+//! (`T...`) throughout. Event ids are `tron:<txid>` for TRX and
+//! `tron:<txid>:log<n>` for the `n`th TRC-20 transfer of the address in the
+//! transaction, following `DEDUP_KEY`. `value` is in sun for TRX (6
+//! decimals) and in token base units for TRC-20 transfers; the spam
+//! heuristics apply to TRC-20 transfers like to ERC-20 ones. Events carry
+//! their `block_number`; the TRC-20 listing doesn't have it, so it is looked
+//! up per new transfer with `/wallet/gettransactioninfobyid`.
+//!
+//! TronGrid is queried by block timestamp, so the checkpoint (`tron:<network>`)
+//! is the block timestamp in milliseconds up to which every watched address
+//! has been read, not a block number. The tracker pauses and resumes at
+//! `/admin/chains/tron`. The mempool, fee, lending and scheduled jobs are
+//! Ethereum-only.
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use anyhow::{anyhow, bail};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use tokio::sync::Mutex;
+use tokio::time::Duration;
+use tracing::{error, info, warn};
+
+use crate::base58;
+use crate::config::Config;
+use crate::control::ChainControl;
+use crate::dedup::{DedupKey, DedupSet};
+use crate::event_type::EventType;
+use crate::publisher::Publisher;
+use crate::spam::{SpamFilter, TransferSignals};
+use crate::token_filter::TokenFilter;
+use crate::trace_context::TraceContext;
+use crate::tracker::ChainTracker;
+use crate::{Event, Token};
+
+/// Decimals of TRX (sun per TRX is 10^6).
+pub const TRX_DECIMALS: u8 = 6;
+
+/// Most results TronGrid returns per request.
+const PAGE_LIMIT: usize = 200;
+
+/// How far behind the time of a poll confirmed transactions may still
+/// appear. Blocks solidify after 19 confirmations, about a minute.
+const SOLIDITY_LAG_MS: u64 = 120_000;
+
+/// First byte of every Tron address.
+const ADDRESS_PREFIX: u8 = 0x41;
+
+fn default_api_url() -> String {
+    "https://api.trongrid.io".to_string()
+}
+
+fn default_network() -> String {
+    "mainnet".to_string()
+}
+
+fn default_explorer_url() -> String {
+    "https://tronscan.org/#".to_string()
+}
+
+fn default_poll_interval_secs() -> u64 {
+    5
+}
+
+/// The `[tron]` section of the config file.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TronConfig {
+    /// TronGrid or full node base URL.
+    #[serde(default = "default_api_url")]
+    pub api_url: String,
+    /// Sent as `TRON-PRO-API-KEY`.
+    #[serde(default)]
+    pub api_key: Option<String>,
+    /// Event `network` name, e.g. `mainnet`, `shasta` or `nile`.
+    #[serde(default = "default_network")]
+    pub network: String,
+    #[serde(default)]
+    pub watched_addresses: Vec<String>,
+    /// TRC-20 contracts to publish; all when empty.
+    #[serde(default)]
+    pub token_allowlist: Vec<String>,
+    #[serde(default)]
+    pub token_denylist: Vec<String>,
+    /// Base of the `explorer_url` links.
+    #[serde(default = "default_explorer_url")]
+    pub explorer_url: String,
+    #[serde(default = "default_poll_interval_secs")]
+    pub poll_interval_secs: u64,
+}
+
+impl TronConfig {
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if !self.api_url.starts_with("http://") && !self.api_url.starts_with("https://") {
+            bail!("api_url must be an http(s):// URL");
+        }
+        if self.network.trim().is_empty() {
+            bail!("network must not be empty");
+        }
+        if self.poll_interval_secs == 0 {
+            bail!("poll_interval_secs must be positive");
+        }
+        for address in self
+            .watched_addresses
+            .iter()
+            .chain(&self.token_allowlist)
+            .chain(&self.token_denylist)
+        {
+            if decode_address(address).is_none() {
+                bail!("invalid Tron address {:?}", address);
+            }
+        }
+        Ok(())
+    }
+
+    /// Checkpoint key of the tracker.
+    pub fn key(&self) -> String {
+        format!("tron:{}", self.network)
+    }
+}
+
+fn checksum(payload: &[u8]) -> [u8; 4] {
+    let digest = Sha256::digest(Sha256::digest(payload));
+    [digest[0], digest[1], digest[2], digest[3]]
+}
+
+/// The 21 bytes of a base58check address, `None` unless it is a valid Tron
+/// address.
+pub fn decode_address(address: &str) -> Option<[u8; 21]> {
+    let bytes = base58::decode(address.trim())?;
+    if bytes.len() != 25 {
+        return None;
+    }
+    let (payload, check) = bytes.split_at(21);
+    if payload[0] != ADDRESS_PREFIX || check != checksum(payload) {
+        return None;
+    }
+    payload.try_into().ok()
+}
+
+/// The base58check form of a hex address (`41...`), as the node API
+/// returns them.
+pub fn hex_to_base58(hex: &str) -> Option<String> {
+    let hex = hex.strip_prefix("0x").unwrap_or(hex);
+    if hex.len() != 42 || !hex.is_ascii() {
+        return None;
+    }
+    let payload = (0..42)
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).ok())
+        .collect::<Option<Vec<u8>>>()?;
+    if payload[0] != ADDRESS_PREFIX {
+        return None;
+    }
+    let mut bytes = payload;
+    let check = checksum(&bytes);
+    bytes.extend_from_slice(&check);
+    Some(base58::encode(&bytes))
+}
+
+#[derive(Debug, Deserialize)]
+struct Page {
+    #[serde(default)]
+    data: Vec<serde_json::Value>,
+}
+
+/// A transaction of `/v1/accounts/{address}/transactions`, as far as a TRX
+/// transfer needs it.
+#[derive(Debug, Deserialize)]
+struct TronTx {
+    #[serde(rename = "txID")]
+    tx_id: String,
+    #[serde(rename = "blockNumber", default)]
+    block_number: Option<u64>,
+    block_timestamp: u64,
+    raw_data: RawData,
+    #[serde(default)]
+    ret: Vec<Ret>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RawData {
+    #[serde(default)]
+    contract: Vec<Contract>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Contract {
+    #[serde(rename = "type")]
+    kind: String,
+    parameter: Parameter,
+}
+
+#[derive(Debug, Deserialize)]
+struct Parameter {
+    value: serde_json::Value,
+}
+
+#[derive(Debug, Deserialize)]
+struct Ret {
+    #[serde(rename = "contractRet", default)]
+    contract_ret: Option<String>,
+}
+
+/// `parameter.value` of a `TransferContract`.
+#[derive(Debug, Deserialize)]
+struct TransferValue {
+    amount: u64,
+    owner_address: String,
+    to_address: String,
+}
+
+/// An entry of `/v1/accounts/{address}/transactions/trc20`.
+#[derive(Debug, Deserialize)]
+struct Trc20Transfer {
+    transaction_id: String,
+    token_info: TokenInfo,
+    block_timestamp: u64,
+    from: String,
+    to: String,
+    #[serde(rename = "type", default)]
+    kind: String,
+    value: String,
+}
+
+/// `/wallet/gettransactioninfobyid`, as far as the block is concerned. Empty
+/// for a transaction the node doesn't know.
+#[derive(Debug, Deserialize)]
+struct TransactionInfo {
+    #[serde(rename = "blockNumber", default)]
+    block_number: Option<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenInfo {
+    address: String,
+    #[serde(default)]
+    symbol: String,
+    #[serde(default)]
+    decimals: u8,
+}
+
+/// Client for one TronGrid endpoint.
+#[derive(Debug, Clone)]
+pub struct TronGrid {
+    http: reqwest::Client,
+    url: String,
+    api_key: Option<String>,
+}
+
+impl TronGrid {
+    pub fn new(cfg: &TronConfig) -> Self {
+        TronGrid {
+            http: reqwest::Client::new(),
+            url: cfg.api_url.trim_end_matches('/').to_string(),
+            api_key: cfg.api_key.clone(),
+        }
+    }
+
+    /// Confirmed results of `path` from block timestamp `since` (ms) on,
+    /// oldest first.
+    async fn page(
+        &self,
+        path: &str,
+        since: u64,
+        extra: &[(&str, &str)],
+    ) -> anyhow::Result<Vec<serde_json::Value>> {
+        let limit = PAGE_LIMIT.to_string();
+        let since = since.to_string();
+        let mut query = vec![
+            ("only_confirmed", "true"),
+            ("limit", limit.as_str()),
+            ("order_by", "block_timestamp,asc"),
+            ("min_timestamp", since.as_str()),
+        ];
+        query.extend_from_slice(extra);
+        let mut request = self.http.get(format!("{}{}", self.url, path)).query(&query);
+        if let Some(key) = &self.api_key {
+            request = request.header("TRON-PRO-API-KEY", key);
+        }
+        let page: Page = request.send().await?.error_for_status()?.json().await?;
+        Ok(page.data)
+    }
+
+    /// Transactions of `address` from `since` on, internal ones left out.
+    pub async fn transactions(
+        &self,
+        address: &str,
+        since: u64,
+    ) -> anyhow::Result<Vec<serde_json::Value>> {
+        self.page(
+            &format!("/v1/accounts/{}/transactions", address),
+            since,
+            &[("search_internal", "false")],
+        )
+        .await
+    }
+
+    /// Block of transaction `tx_id`, which the TRC-20 transfer listing
+    /// leaves out.
+    pub async fn block_number(&self, tx_id: &str) -> anyhow::Result<u64> {
+        let mut request = self
+            .http
+            .post(format!("{}/wallet/gettransactioninfobyid", self.url))
+            .json(&serde_json::json!({ "value": tx_id }));
+        if let Some(key) = &self.api_key {
+            request = request.header("TRON-PRO-API-KEY", key);
+        }
+        let info: TransactionInfo = request.send().await?.error_for_status()?.json().await?;
+        info.block_number
+            .ok_or_else(|| anyhow!("no block for Tron transaction {}", tx_id))
+    }
+
+    /// TRC-20 transfers from or to `address` from `since` on.
+    pub async fn trc20_transfers(
+        &self,
+        address: &str,
+        since: u64,
+    ) -> anyhow::Result<Vec<serde_json::Value>> {
+        self.page(
+            &format!("/v1/accounts/{}/transactions/trc20", address),
+            since,
+            &[],
+        )
+        .await
+    }
+}
+
+fn timestamp(ms: u64) -> Option<String> {
+    Some(DateTime::from_timestamp_millis(ms as i64)?.to_rfc3339())
+}
+
+/// The `transfer` event of a successful TRX transfer of `watched`, and its
+/// block timestamp. `None` for any other transaction.
+fn trx_event(
+    tx: &serde_json::Value,
+    watched: &str,
+    network: &str,
+    key: DedupKey,
+) -> Option<(Event, u64)> {
+    let tx: TronTx = serde_json::from_value(tx.clone()).ok()?;
+    if tx
+        .ret
+        .iter()
+        .any(|ret| ret.contract_ret.as_deref().is_some_and(|r| r != "SUCCESS"))
+    {
+        return None;
+    }
+    let contract = tx.raw_data.contract.first()?;
+    if contract.kind != "TransferContract" {
+        return None;
+    }
+    let transfer: TransferValue = serde_json::from_value(contract.parameter.value.clone()).ok()?;
+    let from = hex_to_base58(&transfer.owner_address)?;
+    let to = hex_to_base58(&transfer.to_address)?;
+    if from != watched && to != watched {
+        return None;
+    }
+    let event = Event {
+        event_id: key.event_id("tron", &tx.tx_id, None, watched),
+        chain: "tron".into(),
+        network: network.to_string(),
+        tx_hash: tx.tx_id,
+        timestamp: timestamp(tx.block_timestamp)?,
+        block_number: tx.block_number,
+        signed_by_watched: from == watched,
+        from,
+        to,
+        value: transfer.amount.to_string(),
+        event_type: EventType::Transfer,
+        watched_address: Some(watched.to_string()),
+        ..Default::default()
+    };
+    Some((event, tx.block_timestamp))
+}
+
+/// The `erc20_transfer` events of `watched`'s TRC-20 transfers, with their
+/// block timestamps. Transfers the token or spam filter drops are left out.
+fn trc20_events(
+    transfers: &[serde_json::Value],
+    watched: &str,
+    network: &str,
+    key: DedupKey,
+    tokens: &TokenFilter,
+    spam: &SpamFilter,
+) -> Vec<(Event, u64)> {
+    let transfers: Vec<Trc20Transfer> = transfers
+        .iter()
+        .filter_map(|t| serde_json::from_value(t.clone()).ok())
+        .collect();
+    let mut events = Vec::new();
+    for (i, transfer) in transfers.iter().enumerate() {
+        // Position among the address's transfers in the same transaction.
+        let index = transfers[..i]
+            .iter()
+            .filter(|t| t.transaction_id == transfer.transaction_id)
+            .count() as u64;
+        if transfer.kind != "Transfer" || !tokens.allows(&transfer.token_info.address) {
+            continue;
+        }
+        let Some(timestamp) = timestamp(transfer.block_timestamp) else {
+            continue;
+        };
+        let info = &transfer.token_info;
+        let mut event = Event {
+            event_id: key.event_id("tron", &transfer.transaction_id, Some(index), watched),
+            chain: "tron".into(),
+            network: network.to_string(),
+            tx_hash: transfer.transaction_id.clone(),
+            timestamp,
+            from: transfer.from.clone(),
+            to: transfer.to.clone(),
+            value: transfer.value.clone(),
+            event_type: EventType::Erc20Transfer,
+            token: Some(Token {
+                address: info.address.clone(),
+                symbol: info.symbol.clone(),
+                decimals: info.decimals,
+            }),
+            watched_address: Some(watched.to_string()),
+            signed_by_watched: transfer.from == watched,
+            ..Default::default()
+        };
+        let signals = TransferSignals {
+            unsolicited: transfer.from != watched,
+            zero_value: transfer.value.trim_start_matches('0').is_empty(),
+            symbol: (!info.symbol.is_empty()).then_some(info.symbol.as_str()),
+            metadata_unverified: info.symbol.is_empty(),
+        };
+        if spam.apply(&mut event, &signals) {
+            events.push((event, transfer.block_timestamp));
+        }
+    }
+    events
+}
+
+fn now_ms() -> u64 {
+    Utc::now().timestamp_millis() as u64
+}
+
+/// TRX and TRC-20 transfers of the watched Tron addresses, polled from
+/// TronGrid.
+pub struct TronTracker {
+    cfg: Option<TronConfig>,
+    tokens: TokenFilter,
+    spam: SpamFilter,
+    processed_txs: Arc<Mutex<DedupSet>>,
+    last_timestamp: Arc<Mutex<Option<u64>>>,
+    control: ChainControl,
+    publisher: Publisher,
+}
+
+impl TronTracker {
+    /// `processed_txs` may be shared with other trackers.
+    pub fn new(
+        cfg: &Config,
+        processed_txs: Arc<Mutex<DedupSet>>,
+        control: ChainControl,
+        publisher: Publisher,
+    ) -> Self {
+        let tokens = cfg.tron.as_ref().map_or_else(TokenFilter::default, |tron| {
+            TokenFilter::new(&tron.token_allowlist, &tron.token_denylist)
+        });
+        TronTracker {
+            cfg: cfg.tron.clone(),
+            tokens,
+            spam: cfg.spam.clone(),
+            processed_txs,
+            last_timestamp: Arc::default(),
+            control,
+            publisher,
+        }
+    }
+
+    /// The checkpoint, shared with the admin API.
+    pub(crate) fn last_timestamp(&self) -> &Arc<Mutex<Option<u64>>> {
+        &self.last_timestamp
+    }
+
+    /// Publish the transfers of `address` from `since` on. Returns where
+    /// the address's next poll starts.
+    async fn poll(
+        &self,
+        cfg: &TronConfig,
+        client: &TronGrid,
+        address: &str,
+        since: u64,
+        polled_at: u64,
+    ) -> anyhow::Result<u64> {
+        let txs = client.transactions(address, since).await?;
+        let transfers = client.trc20_transfers(address, since).await?;
+        let key = self.processed_txs.lock().await.key();
+        let mut events: Vec<(Event, u64)> = txs
+            .iter()
+            .filter_map(|tx| trx_event(tx, address, &cfg.network, key))
+            .collect();
+        events.extend(trc20_events(
+            &transfers,
+            address,
+            &cfg.network,
+            key,
+            &self.tokens,
+            &self.spam,
+        ));
+        events.sort_by_key(|(_, at)| *at);
+
+        // A full page may have more after it: resume from its last entry.
+        // Otherwise everything confirmed up to the poll has been read.
+        let last_at = |page: &[serde_json::Value]| {
+            page.last()
+                .and_then(|entry| entry.get("block_timestamp"))
+                .and_then(|at| at.as_u64())
+        };
+        let page_end = [&txs, &transfers]
+            .into_iter()
+            .filter(|page| page.len() >= PAGE_LIMIT)
+            .filter_map(|page| last_at(page))
+            .min();
+        let mut next = match page_end {
+            Some(at) => at,
+            None => polled_at.saturating_sub(SOLIDITY_LAG_MS),
+        };
+        for (mut event, at) in events {
+            if page_end.is_some_and(|end| at > end) {
+                // Read again with the next page.
+                break;
+            }
+            if self.control.is_stopping() {
+                return Ok(since.max(at));
+            }
+            if self.processed_txs.lock().await.contains(&event.event_id) {
+                continue;
+            }
+            if event.block_number.is_none() {
+                match client.block_number(&event.tx_hash).await {
+                    Ok(block) => event.block_number = Some(block),
+                    Err(e) => {
+                        error!("Failed to look up Tron transaction block: {:?}", e);
+                        return Ok(since.max(at));
+                    }
+                }
+            }
+            if let Err(e) = self
+                .publisher
+                .publish(&event, TraceContext::new_root())
+                .await
+            {
+                error!("Failed to publish event: {:?}", e);
+                return Ok(since.max(at));
+            }
+            self.processed_txs.lock().await.insert(event.event_id);
+            next = next.max(at);
+        }
+        Ok(next.max(since))
+    }
+}
+
+#[async_trait]
+impl ChainTracker for TronTracker {
+    fn chain(&self) -> &'static str {
+        "tron"
+    }
+
+    fn enabled(&self) -> bool {
+        self.cfg.is_some()
+    }
+
+    async fn track(&self) {
+        let Some(cfg) = &self.cfg else {
+            return;
+        };
+        if cfg.watched_addresses.is_empty() {
+            info!("No Tron addresses to watch.");
+            return;
+        }
+        let client = TronGrid::new(cfg);
+        let start = self.last_timestamp.lock().await.unwrap_or_else(now_ms);
+        let mut cursors: HashMap<&str, u64> = cfg
+            .watched_addresses
+            .iter()
+            .map(|address| (address.as_str(), start))
+            .collect();
+        info!("Polling {} for Tron transfers", cfg.api_url);
+        loop {
+            self.control.wait_resumed().await;
+            if self.control.is_shutting_down() {
+                return;
+            }
+            let polled_at = now_ms();
+            for address in &cfg.watched_addresses {
+                if self.control.is_stopping() {
+                    break;
+                }
+                let since = cursors[address.as_str()];
+                match self.poll(cfg, &client, address, since, polled_at).await {
+                    Ok(next) => {
+                        self.control.set_connected(true);
+                        cursors.insert(address.as_str(), next);
+                    }
+                    Err(e) => {
+                        self.control.set_connected(false);
+                        warn!("Error polling TronGrid for {}: {:?}", address, e);
+                    }
+                }
+            }
+            if let Some(&read) = cursors.values().min() {
+                let mut last = self.last_timestamp.lock().await;
+                if !last.is_some_and(|at| read <= at) {
+                    *last = Some(read);
+                }
+            }
+            self.control
+                .sleep(Duration::from_secs(cfg.poll_interval_secs))
+                .await;
+        }
+    }
+
+    async fn checkpoint(&self) -> Option<u64> {
+        *self.last_timestamp.lock().await
+    }
+
+    fn control(&self) -> &ChainControl {
+        &self.control
+    }
+
+    fn publisher(&self) -> &Publisher {
+        &self.publisher
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spam::SpamMode;
+
+    const USDT: &str = "TR7NHqjeKQxGTCi8q8ZY4pL8otSzgjLj6t";
+    const WATCHED: &str = "T9yD14Nj9j7xAB4dbGeiX9h8unkKHxuWwb";
+
+    #[test]
+    fn test_addresses() {
+        let usdt = decode_address(USDT).unwrap();
+        assert_eq!(usdt[0], ADDRESS_PREFIX);
+        assert_eq!(
+            hex_to_base58("41a614f803b6fd780986a42c78ec9c7f77e6ded13c").as_deref(),
+            Some(USDT)
+        );
+        assert_eq!(
+            hex_to_base58(&format!("41{}", "00".repeat(20))).as_deref(),
+            Some(WATCHED)
+        );
+        // Checksum, alphabet and prefix are checked.
+        assert!(decode_address("TR7NHqjeKQxGTCi8q8ZY4pL8otSzgjLj6u").is_none());
+        assert!(decode_address("0x742d35Cc6634C0532925a3b844Bc454e4438f44e").is_none());
+        assert!(hex_to_base58(&format!("42{}", "00".repeat(20))).is_none());
+    }
+
+    #[test]
+    fn test_trx_transfer_event() {
+        let tx = serde_json::json!({
+            "txID": "c0ffee",
+            "blockNumber": 60000000,
+            "block_timestamp": 1700000000000u64,
+            "ret": [{"contractRet": "SUCCESS"}],
+            "raw_data": {"contract": [{
+                "type": "TransferContract",
+                "parameter": {"value": {
+                    "amount": 2500000,
+                    "owner_address": "41a614f803b6fd780986a42c78ec9c7f77e6ded13c",
+                    "to_address": format!("41{}", "00".repeat(20)),
+                }},
+            }]},
+        });
+        let (event, at) = trx_event(&tx, WATCHED, "mainnet", DedupKey::Log).unwrap();
+        assert_eq!(at, 1700000000000);
+        assert_eq!(event.event_id, "tron:c0ffee");
+        assert_eq!(event.chain, "tron");
+        assert_eq!(event.event_type, EventType::Transfer);
+        assert_eq!(event.timestamp, "2023-11-14T22:13:20+00:00");
+        assert_eq!((event.from.as_str(), event.to.as_str()), (USDT, WATCHED));
+        assert_eq!(event.value, "2500000");
+        assert_eq!(event.block_number, Some(60000000));
+        assert!(!event.signed_by_watched);
+
+        let mut failed = tx.clone();
+        failed["ret"][0]["contractRet"] = "REVERT".into();
+        assert!(trx_event(&failed, WATCHED, "mainnet", DedupKey::Log).is_none());
+        let mut call = tx;
+        call["raw_data"]["contract"][0]["type"] = "TriggerSmartContract".into();
+        assert!(trx_event(&call, WATCHED, "mainnet", DedupKey::Log).is_none());
+    }
+
+    #[test]
+    fn test_trc20_transfer_events() {
+        let transfer = |value: &str, symbol: &str| {
+            serde_json::json!({
+                "transaction_id": "beef",
+                "token_info": {"symbol": symbol, "address": USDT, "decimals": 6, "name": "Tether USD"},
+                "block_timestamp": 1700000003000u64,
+                "from": "TNPeeaaFB7K9cmo4uQpcU32zGK8G1NYqeL",
+                "to": WATCHED,
+                "type": "Transfer",
+                "value": value,
+            })
+        };
+        let transfers = [transfer("1500000", "USDT"), transfer("0", "USDT")];
+        let events = trc20_events(
+            &transfers,
+            WATCHED,
+            "mainnet",
+            DedupKey::Log,
+            &TokenFilter::default(),
+            &SpamFilter::default(),
+        );
+        assert_eq!(events.len(), 2);
+        let (event, _) = &events[0];
+        assert_eq!(event.event_id, "tron:beef:log0");
+        assert_eq!(event.event_type, EventType::Erc20Transfer);
+        assert_eq!(event.token.as_ref().unwrap().decimals, 6);
+        assert_eq!(events[1].0.event_id, "tron:beef:log1");
+        assert!(events[1].0.spam);
+
+        let drop = SpamFilter::new(SpamMode::Drop, &[]);
+        let kept = trc20_events(
+            &transfers,
+            WATCHED,
+            "mainnet",
+            DedupKey::Log,
+            &TokenFilter::default(),
+            &drop,
+        );
+        assert_eq!(kept.len(), 1);
+        let deny = TokenFilter::new(&[], &[USDT.to_string()]);
+        let denied = trc20_events(
+            &transfers,
+            WATCHED,
+            "mainnet",
+            DedupKey::Log,
+            &deny,
+            &SpamFilter::default(),
+        );
+        assert!(denied.is_empty());
+    }
+
+    #[test]
+    fn test_config_validation() {
+        let cfg: TronConfig =
+            toml::from_str(&format!("watched_addresses = [\"{}\"]", WATCHED)).unwrap();
+        assert_eq!(cfg.api_url, "https://api.trongrid.io");
+        assert_eq!(cfg.key(), "tron:mainnet");
+        cfg.validate().unwrap();
+        let bad = TronConfig {
+            watched_addresses: vec!["TXYZ".to_string()],
+            ..cfg.clone()
+        };
+        assert!(bad.validate().is_err());
+        let bad_url = TronConfig {
+            api_url: "api.trongrid.io".to_string(),
+            ..cfg
+        };
+        assert!(bad_url.validate().is_err());
+    }
+}
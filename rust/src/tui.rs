@@ -0,0 +1,425 @@
+//! `tracker tui`: a terminal dashboard for a running listener.
+//!
+//! The dashboard attaches from the outside like any other consumer. Chain
+//! status (paused, RPC connected, checkpoint) is polled from the admin API's
+//! `GET /admin/chains`, and the event feed and rates come from a Redis
+//! destination channel. It never talks to the RPC providers itself, so it can
+//! run on any host that reaches the listener's admin port and Redis.
+use std::collections::{BTreeMap, VecDeque};
+use std::io;
+use std::time::{Duration, Instant};
+
+use crossterm::event::{self as term, Event as TermEvent, KeyCode, KeyEventKind, KeyModifiers};
+use crossterm::execute;
+use crossterm::terminal::{
+    disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen,
+};
+use ratatui::backend::CrosstermBackend;
+use ratatui::layout::{Constraint, Direction, Layout};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Borders, Cell, List, ListItem, Paragraph, Row, Table};
+use ratatui::{Frame, Terminal};
+use serde::Deserialize;
+use tokio::sync::mpsc;
+use tracker_client::Subscriber;
+
+/// Events kept in the recent-events pane.
+const RECENT_EVENTS: usize = 200;
+/// Events/sec is averaged over this window.
+const RATE_WINDOW: Duration = Duration::from_secs(10);
+const STATUS_INTERVAL: Duration = Duration::from_secs(2);
+const REDRAW_INTERVAL: Duration = Duration::from_millis(250);
+
+/// One entry of `GET /admin/chains`.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+struct ChainStatus {
+    chain: String,
+    paused: bool,
+    /// Missing from listeners that predate the field.
+    #[serde(default)]
+    connected: bool,
+    checkpoint: Option<u64>,
+}
+
+#[derive(Debug, Default)]
+struct Dashboard {
+    admin_url: String,
+    chains: Vec<ChainStatus>,
+    /// Set while the admin API is unreachable; `chains` keeps the last
+    /// answer so the table doesn't flicker empty.
+    admin_error: Option<String>,
+    /// Arrival times within `RATE_WINDOW`, per chain.
+    arrivals: BTreeMap<String, VecDeque<Instant>>,
+    recent: VecDeque<tracker_client::Event>,
+    total: u64,
+}
+
+impl Dashboard {
+    fn new(admin_url: &str) -> Self {
+        Dashboard {
+            admin_url: admin_url.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn record(&mut self, event: tracker_client::Event, now: Instant) {
+        self.arrivals
+            .entry(event.chain.clone())
+            .or_default()
+            .push_back(now);
+        self.recent.push_front(event);
+        self.recent.truncate(RECENT_EVENTS);
+        self.total += 1;
+    }
+
+    fn set_status(&mut self, status: Result<Vec<ChainStatus>, String>) {
+        match status {
+            Ok(chains) => {
+                self.chains = chains;
+                self.admin_error = None;
+            }
+            Err(e) => self.admin_error = Some(e),
+        }
+    }
+
+    /// Forget arrivals that fell out of the rate window.
+    fn expire(&mut self, now: Instant) {
+        for arrivals in self.arrivals.values_mut() {
+            while arrivals
+                .front()
+                .is_some_and(|t| now.duration_since(*t) > RATE_WINDOW)
+            {
+                arrivals.pop_front();
+            }
+        }
+    }
+
+    fn rate(&self, chain: &str) -> f64 {
+        let count = self.arrivals.get(chain).map_or(0, VecDeque::len);
+        count as f64 / RATE_WINDOW.as_secs_f64()
+    }
+
+    /// Chains reported by the admin API, plus any only seen in the feed
+    /// (e.g. before the first status poll succeeds).
+    fn chain_names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.chains.iter().map(|c| c.chain.as_str()).collect();
+        for chain in self.arrivals.keys() {
+            if !names.contains(&chain.as_str()) {
+                names.push(chain);
+            }
+        }
+        names
+    }
+}
+
+/// Run the dashboard until the user quits with `q`, `Esc` or Ctrl-C.
+pub async fn run(
+    redis_url: &str,
+    channel: &str,
+    admin_url: &str,
+    admin_token: Option<String>,
+) -> anyhow::Result<()> {
+    let mut events = Subscriber::new(redis_url, channel)?.spawn();
+    let mut status = spawn_status_poller(admin_url.to_string(), admin_token)?;
+    let mut keys = spawn_key_reader();
+
+    enable_raw_mode()?;
+    execute!(io::stdout(), EnterAlternateScreen)?;
+    let mut terminal = Terminal::new(CrosstermBackend::new(io::stdout()))?;
+
+    let mut dashboard = Dashboard::new(admin_url);
+    let mut redraw = tokio::time::interval(REDRAW_INTERVAL);
+    let result: anyhow::Result<()> = async {
+        loop {
+            tokio::select! {
+                Some(event) = events.recv() => dashboard.record(event, Instant::now()),
+                Some(s) = status.recv() => dashboard.set_status(s),
+                key = keys.recv() => match key {
+                    Some(Ok(key)) if !is_quit(&key) => {}
+                    Some(Err(e)) => return Err(e.into()),
+                    _ => return Ok(()),
+                },
+                _ = redraw.tick() => {
+                    dashboard.expire(Instant::now());
+                    terminal.draw(|frame| render(frame, &dashboard))?;
+                }
+            }
+        }
+    }
+    .await;
+
+    // Restore the terminal even if drawing failed.
+    disable_raw_mode()?;
+    execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
+    terminal.show_cursor()?;
+    result
+}
+
+fn is_quit(key: &term::KeyEvent) -> bool {
+    key.kind == KeyEventKind::Press
+        && match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => true,
+            // Raw mode swallows SIGINT.
+            KeyCode::Char('c') => key.modifiers.contains(KeyModifiers::CONTROL),
+            _ => false,
+        }
+}
+
+/// Poll `GET /admin/chains` in the background so a slow or unreachable
+/// admin API never stalls redraws.
+fn spawn_status_poller(
+    admin_url: String,
+    admin_token: Option<String>,
+) -> anyhow::Result<mpsc::Receiver<Result<Vec<ChainStatus>, String>>> {
+    let http = reqwest::Client::builder()
+        .timeout(STATUS_INTERVAL)
+        .build()?;
+    let url = format!("{}/admin/chains", admin_url.trim_end_matches('/'));
+    let (tx, rx) = mpsc::channel(1);
+    tokio::spawn(async move {
+        let mut tick = tokio::time::interval(STATUS_INTERVAL);
+        loop {
+            tick.tick().await;
+            let mut req = http.get(&url);
+            if let Some(token) = &admin_token {
+                req = req.bearer_auth(token);
+            }
+            let status = match req.send().await.and_then(|r| r.error_for_status()) {
+                Ok(res) => res.json().await.map_err(|e| e.to_string()),
+                Err(e) => Err(e.to_string()),
+            };
+            if tx.send(status).await.is_err() {
+                return;
+            }
+        }
+    });
+    Ok(rx)
+}
+
+/// crossterm's reader blocks, so keys are read on a plain thread.
+fn spawn_key_reader() -> mpsc::Receiver<io::Result<term::KeyEvent>> {
+    let (tx, rx) = mpsc::channel(16);
+    std::thread::spawn(move || {
+        while !tx.is_closed() {
+            let key = match term::poll(Duration::from_millis(200)) {
+                Ok(false) => continue,
+                Ok(true) => match term::read() {
+                    Ok(TermEvent::Key(key)) => Ok(key),
+                    Ok(_) => continue,
+                    Err(e) => Err(e),
+                },
+                Err(e) => Err(e),
+            };
+            let failed = key.is_err();
+            if tx.blocking_send(key).is_err() || failed {
+                return;
+            }
+        }
+    });
+    rx
+}
+
+fn render(frame: &mut Frame, dashboard: &Dashboard) {
+    let names = dashboard.chain_names();
+    let areas = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(names.len().max(1) as u16 + 3),
+            Constraint::Min(3),
+            Constraint::Length(1),
+        ])
+        .split(frame.size());
+    let (chains_area, events_area, help_area) = (areas[0], areas[1], areas[2]);
+
+    let rows = names.iter().map(|name| {
+        let status = dashboard.chains.iter().find(|c| c.chain == *name);
+        let (state, rpc, checkpoint) = match status {
+            Some(s) => (
+                if s.paused {
+                    Cell::from("paused").style(Style::default().fg(Color::Yellow))
+                } else {
+                    Cell::from("running").style(Style::default().fg(Color::Green))
+                },
+                if s.connected {
+                    Cell::from("connected").style(Style::default().fg(Color::Green))
+                } else {
+                    Cell::from("disconnected").style(Style::default().fg(Color::Red))
+                },
+                s.checkpoint.map_or("-".to_string(), |c| c.to_string()),
+            ),
+            None => (Cell::from("?"), Cell::from("?"), "-".to_string()),
+        };
+        Row::new(vec![
+            Cell::from(name.to_string()),
+            state,
+            rpc,
+            Cell::from(checkpoint),
+            Cell::from(format!("{:.1}", dashboard.rate(name))),
+        ])
+    });
+    let title = match &dashboard.admin_error {
+        None => Line::from(format!(" Chains ({}) ", dashboard.admin_url)),
+        Some(e) => Line::from(vec![
+            Span::raw(" Chains "),
+            Span::styled(
+                format!("(admin API unreachable: {}) ", e),
+                Style::default().fg(Color::Red),
+            ),
+        ]),
+    };
+    let table = Table::new(
+        rows,
+        [
+            Constraint::Length(12),
+            Constraint::Length(9),
+            Constraint::Length(14),
+            Constraint::Length(16),
+            Constraint::Length(10),
+        ],
+    )
+    .header(
+        Row::new(vec!["Chain", "State", "RPC", "Block/slot", "Events/s"])
+            .style(Style::default().add_modifier(Modifier::BOLD)),
+    )
+    .block(Block::default().borders(Borders::ALL).title(title));
+    frame.render_widget(table, chains_area);
+
+    let items: Vec<ListItem> = dashboard
+        .recent
+        .iter()
+        .take(events_area.height as usize)
+        .map(|e| {
+            let style = if e.spam {
+                Style::default().fg(Color::DarkGray)
+            } else {
+                Style::default()
+            };
+            let symbol = e.token.as_ref().map_or("", |t| t.symbol.as_str());
+            ListItem::new(format!(
+                "{:<9} {:<15} {:<20} {} -> {} {} {}",
+                e.chain,
+                e.event_type,
+                abbreviate(&e.tx_hash),
+                abbreviate(&e.from),
+                abbreviate(&e.to),
+                e.value,
+                symbol,
+            ))
+            .style(style)
+        })
+        .collect();
+    let list = List::new(items).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title(format!(" Recent events ({} received) ", dashboard.total)),
+    );
+    frame.render_widget(list, events_area);
+
+    frame.render_widget(Paragraph::new(" q: quit"), help_area);
+}
+
+/// `0x1234…cdef`-style shortening for hashes and addresses.
+fn abbreviate(s: &str) -> String {
+    let chars: Vec<char> = s.chars().collect();
+    if chars.len() <= 14 {
+        return s.to_string();
+    }
+    let head: String = chars[..6].iter().collect();
+    let tail: String = chars[chars.len() - 4..].iter().collect();
+    format!("{}…{}", head, tail)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ratatui::backend::TestBackend;
+
+    fn event(chain: &str) -> tracker_client::Event {
+        tracker_client::Event {
+            chain: chain.to_string(),
+            event_type: "transfer".to_string(),
+            tx_hash: "0xabcdef0123456789abcdef".to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_rate_window_and_recent_cap() {
+        let mut dashboard = Dashboard::new("http://127.0.0.1:9090");
+        let start = Instant::now();
+        for _ in 0..RECENT_EVENTS + 5 {
+            dashboard.record(event("ethereum"), start);
+        }
+        dashboard.record(event("solana"), start + Duration::from_secs(8));
+        assert_eq!(dashboard.recent.len(), RECENT_EVENTS);
+        assert_eq!(dashboard.recent[0].chain, "solana");
+        assert_eq!(
+            dashboard.rate("ethereum"),
+            (RECENT_EVENTS + 5) as f64 / 10.0
+        );
+
+        dashboard.expire(start + Duration::from_secs(15));
+        assert_eq!(dashboard.rate("ethereum"), 0.0);
+        assert_eq!(dashboard.rate("solana"), 0.1);
+        assert_eq!(dashboard.total, RECENT_EVENTS as u64 + 6);
+    }
+
+    #[test]
+    fn test_status_keeps_last_answer_on_error() {
+        let mut dashboard = Dashboard::new("http://127.0.0.1:9090");
+        let chains: Vec<ChainStatus> = serde_json::from_str(
+            r#"[{"chain":"ethereum","paused":true,"connected":true,"checkpoint":42},
+                {"chain":"solana","paused":false,"checkpoint":null}]"#,
+        )
+        .unwrap();
+        assert!(!chains[1].connected);
+        dashboard.set_status(Ok(chains.clone()));
+        dashboard.set_status(Err("connection refused".into()));
+        assert_eq!(dashboard.chains, chains);
+        assert_eq!(dashboard.admin_error.as_deref(), Some("connection refused"));
+
+        dashboard.record(event("base"), Instant::now());
+        assert_eq!(dashboard.chain_names(), ["ethereum", "solana", "base"]);
+    }
+
+    #[test]
+    fn test_render() {
+        let mut dashboard = Dashboard::new("http://127.0.0.1:9090");
+        dashboard.set_status(Ok(vec![ChainStatus {
+            chain: "ethereum".into(),
+            paused: true,
+            connected: false,
+            checkpoint: Some(19_000_000),
+        }]));
+        dashboard.record(event("ethereum"), Instant::now());
+
+        let mut terminal = Terminal::new(TestBackend::new(100, 12)).unwrap();
+        terminal.draw(|f| render(f, &dashboard)).unwrap();
+        let screen: String = terminal
+            .backend()
+            .buffer()
+            .content()
+            .iter()
+            .map(|c| c.symbol())
+            .collect();
+        for expected in [
+            "paused",
+            "disconnected",
+            "19000000",
+            "0xabcd…cdef",
+            "1 received",
+        ] {
+            assert!(screen.contains(expected), "missing {:?}", expected);
+        }
+    }
+
+    #[test]
+    fn test_abbreviate() {
+        assert_eq!(abbreviate("0x1234"), "0x1234");
+        assert_eq!(
+            abbreviate("0x70997970C51812dc3A010C7d01b50e0d17dc79C8"),
+            "0x7099…79C8"
+        );
+    }
+}
@@ -0,0 +1,493 @@
+//! Validator monitoring on Solana (`[validator_monitoring]` in `CONFIG_FILE`,
+//! `validator_monitor` job).
+//!
+//! Staking providers want to hear about the validators they run or delegate
+//! to in the same stream as their transfers. Every configured vote account
+//! or validator identity is looked up in `getVoteAccounts` once a minute, and
+//! a `validator_event` is published when it
+//!
+//! - `delinquent`: stops voting, i.e. the cluster lists it as delinquent;
+//! - `recovered`: votes again after being delinquent;
+//! - `commission_changed`: changes its commission;
+//! - `stake_changed`: gains or loses at least `min_stake_change` SOL of
+//!   activated stake, which only moves at epoch boundaries.
+//!
+//! ```toml
+//! [validator_monitoring]
+//! validators = ["<vote account or identity>"]
+//! min_stake_change = 10000   # SOL, default
+//! ```
+//!
+//! The first poll after a start only records each validator's state, except
+//! that a validator already delinquent is reported. The event ids end in the
+//! validator's last voted slot, so a restart doesn't report a delinquency
+//! twice. Solana doesn't slash stake yet; delinquency is what costs
+//! delegators their rewards. A configured validator that isn't among the
+//! vote accounts (closed, or on another cluster) is logged, not published.
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex as StdMutex};
+
+use anyhow::{anyhow, bail};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use solana_client::rpc_client::RpcClient;
+use solana_client::rpc_response::{RpcVoteAccountInfo, RpcVoteAccountStatus};
+use solana_sdk::native_token::LAMPORTS_PER_SOL;
+use solana_sdk::pubkey::Pubkey;
+use tracing::{info, warn};
+
+use crate::event_type::EventType;
+use crate::publisher::Publisher;
+use crate::trace_context::TraceContext;
+use crate::Event;
+
+fn default_min_stake_change() -> u64 {
+    10_000
+}
+
+/// The `[validator_monitoring]` section of the config file.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ValidatorConfig {
+    /// Vote accounts or validator identities.
+    pub validators: Vec<String>,
+    /// Smallest change of activated stake reported, in whole SOL.
+    #[serde(default = "default_min_stake_change")]
+    pub min_stake_change: u64,
+}
+
+impl ValidatorConfig {
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if self.validators.is_empty() {
+            bail!("validators must not be empty");
+        }
+        let mut seen = HashSet::new();
+        for validator in &self.validators {
+            Pubkey::from_str(validator)
+                .map_err(|_| anyhow!("validator '{}' is not a Solana address", validator))?;
+            if !seen.insert(validator) {
+                bail!("validator {} is listed twice", validator);
+            }
+        }
+        if self.min_stake_change == 0 {
+            bail!("min_stake_change must be positive");
+        }
+        Ok(())
+    }
+}
+
+/// What happened to a validator.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ValidatorChange {
+    Delinquent,
+    Recovered,
+    CommissionChanged,
+    StakeChanged,
+}
+
+impl ValidatorChange {
+    fn as_str(self) -> &'static str {
+        match self {
+            ValidatorChange::Delinquent => "delinquent",
+            ValidatorChange::Recovered => "recovered",
+            ValidatorChange::CommissionChanged => "commission_changed",
+            ValidatorChange::StakeChanged => "stake_changed",
+        }
+    }
+}
+
+/// Payload of a `validator_event` event.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct Validator {
+    pub change: ValidatorChange,
+    pub vote_account: String,
+    pub identity: String,
+    pub delinquent: bool,
+    /// Percentage of rewards the validator keeps.
+    pub commission: u8,
+    /// Commission before a `commission_changed`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub previous_commission: Option<u8>,
+    /// In lamports.
+    pub activated_stake: String,
+    /// Activated stake before a `stake_changed`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub previous_activated_stake: Option<String>,
+    /// Most recent slot the validator voted on.
+    pub last_vote: u64,
+}
+
+/// The state of a validator the changes are computed from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Observed {
+    delinquent: bool,
+    commission: u8,
+    activated_stake: u64,
+}
+
+/// Changes from `previous` to `current`; on first sight only a delinquency.
+fn changes(
+    previous: Option<Observed>,
+    current: Observed,
+    min_stake_change: u64,
+) -> Vec<ValidatorChange> {
+    let Some(previous) = previous else {
+        return current
+            .delinquent
+            .then_some(ValidatorChange::Delinquent)
+            .into_iter()
+            .collect();
+    };
+    let mut changes = Vec::new();
+    match (previous.delinquent, current.delinquent) {
+        (false, true) => changes.push(ValidatorChange::Delinquent),
+        (true, false) => changes.push(ValidatorChange::Recovered),
+        _ => {}
+    }
+    if previous.commission != current.commission {
+        changes.push(ValidatorChange::CommissionChanged);
+    }
+    if previous.activated_stake.abs_diff(current.activated_stake) >= min_stake_change {
+        changes.push(ValidatorChange::StakeChanged);
+    }
+    changes
+}
+
+/// A validator's new state and the events it takes to report it.
+#[derive(Debug)]
+struct Update {
+    validator: String,
+    observed: Observed,
+    events: Vec<Event>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    /// By configured address.
+    observed: HashMap<String, Observed>,
+    /// Configured addresses already logged as missing.
+    missing: HashSet<String>,
+}
+
+/// The configured validators and their last polled state. Cheap to clone;
+/// all clones share the state.
+#[derive(Debug, Clone, Default)]
+pub struct ValidatorMonitor {
+    validators: Arc<Vec<String>>,
+    /// In lamports.
+    min_stake_change: u64,
+    network: String,
+    state: Arc<StdMutex<State>>,
+}
+
+impl ValidatorMonitor {
+    pub fn new(cfg: &ValidatorConfig, network: &str) -> Self {
+        ValidatorMonitor {
+            validators: Arc::new(cfg.validators.clone()),
+            min_stake_change: cfg.min_stake_change.saturating_mul(LAMPORTS_PER_SOL),
+            network: network.to_string(),
+            state: Arc::default(),
+        }
+    }
+
+    /// The configured validators' entries in `status`, with the events their
+    /// changes since the last poll call for. Missing validators are logged
+    /// once each time they disappear.
+    fn updates(&self, status: &RpcVoteAccountStatus, now: i64) -> Vec<Update> {
+        let accounts = status
+            .current
+            .iter()
+            .map(|account| (account, false))
+            .chain(status.delinquent.iter().map(|account| (account, true)));
+        let mut found: HashMap<&str, (&RpcVoteAccountInfo, bool)> = HashMap::new();
+        for (account, delinquent) in accounts {
+            found.insert(account.vote_pubkey.as_str(), (account, delinquent));
+            found.insert(account.node_pubkey.as_str(), (account, delinquent));
+        }
+        let mut state = self.state.lock().unwrap();
+        let mut updates = Vec::new();
+        for validator in self.validators.iter() {
+            let Some((account, delinquent)) = found.get(validator.as_str()) else {
+                if state.missing.insert(validator.clone()) {
+                    warn!("Validator {} is not among the vote accounts", validator);
+                }
+                continue;
+            };
+            state.missing.remove(validator);
+            let observed = Observed {
+                delinquent: *delinquent,
+                commission: account.commission,
+                activated_stake: account.activated_stake,
+            };
+            let previous = state.observed.get(validator).copied();
+            let events = changes(previous, observed, self.min_stake_change)
+                .into_iter()
+                .map(|change| self.event(validator, account, change, previous, observed, now))
+                .collect();
+            updates.push(Update {
+                validator: validator.clone(),
+                observed,
+                events,
+            });
+        }
+        updates
+    }
+
+    fn record(&self, update: &Update) {
+        self.state
+            .lock()
+            .unwrap()
+            .observed
+            .insert(update.validator.clone(), update.observed);
+    }
+
+    fn event(
+        &self,
+        validator: &str,
+        account: &RpcVoteAccountInfo,
+        change: ValidatorChange,
+        previous: Option<Observed>,
+        current: Observed,
+        now: i64,
+    ) -> Event {
+        let (previous_commission, previous_activated_stake, value) = match (change, previous) {
+            (ValidatorChange::CommissionChanged, Some(previous)) => {
+                (Some(previous.commission), None, String::new())
+            }
+            (ValidatorChange::StakeChanged, Some(previous)) => (
+                None,
+                Some(previous.activated_stake.to_string()),
+                previous
+                    .activated_stake
+                    .abs_diff(account.activated_stake)
+                    .to_string(),
+            ),
+            _ => (None, None, String::new()),
+        };
+        Event {
+            event_id: format!(
+                "validator:{}:{}:{}",
+                account.vote_pubkey,
+                change.as_str(),
+                account.last_vote
+            ),
+            chain: "solana".into(),
+            network: self.network.clone(),
+            timestamp: now.to_string(),
+            from: account.node_pubkey.clone(),
+            to: account.vote_pubkey.clone(),
+            value,
+            event_type: EventType::ValidatorEvent,
+            validator: Some(Validator {
+                change,
+                vote_account: account.vote_pubkey.clone(),
+                identity: account.node_pubkey.clone(),
+                delinquent: current.delinquent,
+                commission: account.commission,
+                previous_commission,
+                activated_stake: account.activated_stake.to_string(),
+                previous_activated_stake,
+                last_vote: account.last_vote,
+            }),
+            watched_address: Some(validator.to_string()),
+            ..Default::default()
+        }
+    }
+}
+
+/// Poll the vote accounts and publish the configured validators' changes. A
+/// validator whose events can't all be published is compared against its
+/// old state again on the next poll.
+pub async fn poll(
+    rpc: &Arc<RpcClient>,
+    monitor: &ValidatorMonitor,
+    publisher: &Publisher,
+) -> anyhow::Result<()> {
+    let status = tokio::task::spawn_blocking({
+        let rpc = Arc::clone(rpc);
+        move || rpc.get_vote_accounts()
+    })
+    .await??;
+    let mut published = 0;
+    for update in monitor.updates(&status, Utc::now().timestamp()) {
+        for event in &update.events {
+            info!("Validator {}: {}", update.validator, event.event_id);
+            publisher.publish(event, TraceContext::new_root()).await?;
+            published += 1;
+        }
+        monitor.record(&update);
+    }
+    if published > 0 {
+        info!("Published {} validator events", published);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VOTE: &str = "Vote111111111111111111111111111111111111111";
+    const IDENTITY: &str = "9xQeWvG816bUx9EPjHmaT23yvVM2ZWbrrpZb9PusVFin";
+
+    fn account(commission: u8, activated_stake: u64, last_vote: u64) -> RpcVoteAccountInfo {
+        RpcVoteAccountInfo {
+            vote_pubkey: VOTE.into(),
+            node_pubkey: IDENTITY.into(),
+            activated_stake,
+            commission,
+            epoch_vote_account: true,
+            epoch_credits: Vec::new(),
+            last_vote,
+            root_slot: last_vote.saturating_sub(32),
+        }
+    }
+
+    fn status(account: RpcVoteAccountInfo, delinquent: bool) -> RpcVoteAccountStatus {
+        let (current, delinquent) = if delinquent {
+            (Vec::new(), vec![account])
+        } else {
+            (vec![account], Vec::new())
+        };
+        RpcVoteAccountStatus {
+            current,
+            delinquent,
+        }
+    }
+
+    fn monitor(validator: &str) -> ValidatorMonitor {
+        ValidatorMonitor::new(
+            &ValidatorConfig {
+                validators: vec![validator.into()],
+                min_stake_change: 100,
+            },
+            "mainnet",
+        )
+    }
+
+    /// Events of a poll, recording the new state like `poll` does.
+    fn poll_once(monitor: &ValidatorMonitor, status: &RpcVoteAccountStatus) -> Vec<Event> {
+        let mut events = Vec::new();
+        for update in monitor.updates(status, 1_700_000_000) {
+            monitor.record(&update);
+            events.extend(update.events);
+        }
+        events
+    }
+
+    #[test]
+    fn test_changes() {
+        let healthy = Observed {
+            delinquent: false,
+            commission: 5,
+            activated_stake: 1_000,
+        };
+        assert!(changes(None, healthy, 100).is_empty());
+        let delinquent = Observed {
+            delinquent: true,
+            ..healthy
+        };
+        assert_eq!(
+            changes(None, delinquent, 100),
+            [ValidatorChange::Delinquent]
+        );
+        assert_eq!(
+            changes(Some(delinquent), healthy, 100),
+            [ValidatorChange::Recovered]
+        );
+        let moved = Observed {
+            commission: 10,
+            activated_stake: 1_100,
+            ..healthy
+        };
+        assert_eq!(
+            changes(Some(healthy), moved, 100),
+            [
+                ValidatorChange::CommissionChanged,
+                ValidatorChange::StakeChanged
+            ]
+        );
+        // Smaller stake movements aren't reported.
+        assert!(changes(
+            Some(moved),
+            Observed {
+                activated_stake: 1_001,
+                ..moved
+            },
+            100
+        )
+        .is_empty());
+    }
+
+    #[test]
+    fn test_poll_reports_changes() {
+        let lamports = LAMPORTS_PER_SOL;
+        // Matched by identity as well as by vote account.
+        let monitor = monitor(IDENTITY);
+        assert!(poll_once(&monitor, &status(account(5, 1_000 * lamports, 10), false)).is_empty());
+
+        let events = poll_once(&monitor, &status(account(8, 1_200 * lamports, 20), false));
+        assert_eq!(events.len(), 2);
+        let commission = events[0].validator.as_ref().unwrap();
+        assert_eq!(commission.change, ValidatorChange::CommissionChanged);
+        assert_eq!(commission.previous_commission, Some(5));
+        assert_eq!(
+            events[0].event_id,
+            format!("validator:{}:commission_changed:20", VOTE)
+        );
+        assert_eq!(events[0].event_type, EventType::ValidatorEvent);
+        assert_eq!(
+            (events[0].from.as_str(), events[0].to.as_str()),
+            (IDENTITY, VOTE)
+        );
+        assert_eq!(events[1].value, (200 * lamports).to_string());
+        assert_eq!(
+            events[1]
+                .validator
+                .as_ref()
+                .unwrap()
+                .previous_activated_stake,
+            Some((1_000 * lamports).to_string())
+        );
+
+        let events = poll_once(&monitor, &status(account(8, 1_200 * lamports, 20), true));
+        assert_eq!(
+            events[0].event_id,
+            format!("validator:{}:delinquent:20", VOTE)
+        );
+        assert!(events[0].validator.as_ref().unwrap().delinquent);
+        let events = poll_once(&monitor, &status(account(8, 1_200 * lamports, 90), false));
+        let recovered = events[0].validator.as_ref().unwrap();
+        assert_eq!(recovered.change, ValidatorChange::Recovered);
+        assert!(!recovered.delinquent);
+    }
+
+    #[test]
+    fn test_unrecorded_update_is_reported_again() {
+        let monitor = monitor(VOTE);
+        let delinquent = status(account(5, 1_000, 10), true);
+        assert_eq!(monitor.updates(&delinquent, 0)[0].events.len(), 1);
+        // Not recorded, e.g. because publishing failed.
+        assert_eq!(monitor.updates(&delinquent, 0)[0].events.len(), 1);
+        let gone = RpcVoteAccountStatus {
+            current: Vec::new(),
+            delinquent: Vec::new(),
+        };
+        assert!(monitor.updates(&gone, 0).is_empty());
+    }
+
+    #[test]
+    fn test_config_validation() {
+        let cfg = |validators: &[&str], min_stake_change| ValidatorConfig {
+            validators: validators.iter().map(|v| v.to_string()).collect(),
+            min_stake_change,
+        };
+        assert!(cfg(&[VOTE, IDENTITY], 1).validate().is_ok());
+        assert!(cfg(&[], 1).validate().is_err());
+        assert!(cfg(&["0x01"], 1).validate().is_err());
+        assert!(cfg(&[VOTE, VOTE], 1).validate().is_err());
+        assert!(cfg(&[VOTE], 0).validate().is_err());
+    }
+}
@@ -0,0 +1,196 @@
+//! Watched addresses that can change while the listener runs
+//! (`POST`/`DELETE /admin/watched/{chain}/{address}`).
+//!
+//! The Ethereum and Solana trackers start from `WATCHED_ADDRESSES_ETH` and
+//! `WATCHED_ADDRESSES_SOL`, and restart their subscriptions or polls with
+//! the new list whenever an address is added or removed. Like an endpoint
+//! switch, a restart resumes from the checkpoint and the dedup set keeps
+//! events from being published twice. Changes are kept in memory only: the
+//! configured addresses are watched again after a restart.
+use std::str::FromStr;
+
+use ethers::types::Address;
+use serde::Serialize;
+use solana_sdk::pubkey::Pubkey;
+use tokio::sync::watch;
+use tracing::info;
+
+use crate::config::Config;
+use crate::history::BadRequest;
+
+/// The watched addresses of one chain. Cheap to clone; all clones share
+/// state.
+#[derive(Debug, Clone)]
+pub struct WatchList {
+    chain: &'static str,
+    addresses: watch::Sender<Vec<String>>,
+}
+
+/// A chain's watched addresses, as answered by the admin API.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct WatchedAddresses {
+    pub chain: &'static str,
+    pub addresses: Vec<String>,
+}
+
+impl WatchList {
+    pub fn new(chain: &'static str, addresses: &[String]) -> Self {
+        let (addresses, _) = watch::channel(addresses.to_vec());
+        WatchList { chain, addresses }
+    }
+
+    pub fn chain(&self) -> &'static str {
+        self.chain
+    }
+
+    pub fn addresses(&self) -> Vec<String> {
+        self.addresses.borrow().clone()
+    }
+
+    pub fn snapshot(&self) -> WatchedAddresses {
+        WatchedAddresses {
+            chain: self.chain,
+            addresses: self.addresses(),
+        }
+    }
+
+    /// Notified on every change; trackers restart when it fires.
+    pub fn subscribe(&self) -> watch::Receiver<Vec<String>> {
+        self.addresses.subscribe()
+    }
+
+    pub fn contains(&self, address: &str) -> bool {
+        self.addresses
+            .borrow()
+            .iter()
+            .any(|watched| self.same(watched, address))
+    }
+
+    /// Start watching `address`. Returns false if it already was; errors
+    /// with [`BadRequest`] if it is malformed.
+    pub fn add(&self, address: &str) -> anyhow::Result<bool> {
+        let valid = match self.chain {
+            "ethereum" => Address::from_str(address).is_ok(),
+            "solana" => Pubkey::from_str(address).is_ok(),
+            _ => true,
+        };
+        if !valid {
+            return Err(
+                BadRequest(format!("malformed {} address {:?}", self.chain, address)).into(),
+            );
+        }
+        let added = self.addresses.send_if_modified(|addresses| {
+            if addresses.iter().any(|watched| self.same(watched, address)) {
+                return false;
+            }
+            addresses.push(address.to_string());
+            true
+        });
+        if added {
+            info!("Watching {} address {}", self.chain, address);
+        }
+        Ok(added)
+    }
+
+    /// Stop watching `address`. Returns false if it wasn't watched.
+    pub fn remove(&self, address: &str) -> bool {
+        let removed = self.addresses.send_if_modified(|addresses| {
+            let before = addresses.len();
+            addresses.retain(|watched| !self.same(watched, address));
+            addresses.len() != before
+        });
+        if removed {
+            info!("No longer watching {} address {}", self.chain, address);
+        }
+        removed
+    }
+
+    /// Ethereum addresses are compared ignoring their checksum case; Solana
+    /// keys are case-sensitive base58.
+    fn same(&self, a: &str, b: &str) -> bool {
+        match self.chain {
+            "ethereum" => a.eq_ignore_ascii_case(b),
+            _ => a == b,
+        }
+    }
+}
+
+/// The watch lists of the chains whose addresses can change at runtime,
+/// shared between the trackers and the admin API.
+#[derive(Debug, Clone)]
+pub struct WatchLists {
+    pub eth: WatchList,
+    pub sol: WatchList,
+}
+
+impl Default for WatchLists {
+    fn default() -> Self {
+        WatchLists::new(&Config::default())
+    }
+}
+
+impl WatchLists {
+    /// Lists seeded with the configured addresses.
+    pub fn new(cfg: &Config) -> Self {
+        WatchLists {
+            eth: WatchList::new("ethereum", &cfg.watched_addresses_eth),
+            sol: WatchList::new("solana", &cfg.watched_addresses_sol),
+        }
+    }
+
+    /// Look up a chain by its event `chain` name.
+    pub fn get(&self, chain: &str) -> Option<&WatchList> {
+        [&self.eth, &self.sol]
+            .into_iter()
+            .find(|list| list.chain == chain)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WATCHED: &str = "0x70997970C51812dc3A010C7d01b50e0d17dc79C8";
+    const SOL_WATCHED: &str = "7xkZG8s8pJ1kG9gA4q3j5Rm4PpG7mVq79k6h4n8P1yqT";
+
+    #[test]
+    fn test_add_and_remove() {
+        let lists = WatchLists::new(&Config {
+            watched_addresses_eth: vec![WATCHED.into()],
+            ..Default::default()
+        });
+        let eth = lists.get("ethereum").unwrap();
+        let mut changes = eth.subscribe();
+
+        // Already watched, in another case.
+        assert!(!eth.add(&WATCHED.to_lowercase()).unwrap());
+        assert!(!changes.has_changed().unwrap());
+        assert!(eth
+            .add("0x00000000000000000000000000000000000000ff")
+            .unwrap());
+        assert!(changes.has_changed().unwrap());
+        assert_eq!(changes.borrow_and_update().len(), 2);
+
+        let err = eth.add("0xnope").unwrap_err();
+        assert!(err.is::<BadRequest>());
+        assert!(eth.remove(&WATCHED.to_lowercase()));
+        assert!(!eth.remove(WATCHED));
+        assert_eq!(
+            eth.snapshot(),
+            WatchedAddresses {
+                chain: "ethereum",
+                addresses: vec!["0x00000000000000000000000000000000000000ff".into()],
+            }
+        );
+        assert!(lists.get("polygon").is_none());
+    }
+
+    #[test]
+    fn test_solana_keys_are_case_sensitive() {
+        let sol = WatchLists::default().sol;
+        assert!(sol.add(SOL_WATCHED).unwrap());
+        assert!(sol.contains(SOL_WATCHED));
+        assert!(!sol.contains(&SOL_WATCHED.to_lowercase()));
+        assert!(sol.add("not base58!").is_err());
+    }
+}
@@ -0,0 +1,265 @@
+//! Webhook destinations (`sink = "webhook"`).
+//!
+//! Each message, an event payload or a newline-delimited batch, is POSTed to
+//! every URL in `urls`. Unlike the runtime subscriptions of `webhooks.rs`,
+//! these endpoints are fixed in the config and receive everything the
+//! destination's filters let through.
+//!
+//! Requests are signed so a receiver can check they came from the listener:
+//! `X-Tracker-Timestamp` is the Unix time of the request, and
+//! `X-Tracker-Signature` is `sha256=` followed by the hex HMAC-SHA256, keyed
+//! with the destination's `secret`, of `<timestamp>.<body>`. A receiver
+//! recomputes it over the raw body and rejects stale timestamps to stop
+//! replays.
+//!
+//! Each URL is retried on its own with the destination's `retry_attempts`
+//! and `retry_base_ms`, so one slow endpoint doesn't hold up or duplicate
+//! deliveries to the others. A delivery that still fails is appended to the
+//! `dead_letter` file as a JSON line with the URL, the error and the
+//! payload, and logged, instead of being retried again.
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use anyhow::anyhow;
+use async_trait::async_trait;
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use serde::Serialize;
+use sha2::Sha256;
+use tokio::task::JoinSet;
+use tokio::time::Duration;
+use tracing::error;
+
+use crate::config::DestinationConfig;
+use crate::retry::retry_with_backoff;
+use crate::sink::{EventSink, FileSink};
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+pub struct WebhookSink {
+    http: reqwest::Client,
+    name: String,
+    urls: Vec<String>,
+    secret: String,
+    retry_attempts: usize,
+    retry_base: Duration,
+    dead_letter: Option<Arc<FileSink>>,
+    dead_letter_path: Option<PathBuf>,
+}
+
+/// A line of the dead-letter file.
+#[derive(Debug, Serialize)]
+struct DeadLetter<'a> {
+    failed_at: String,
+    destination: &'a str,
+    url: &'a str,
+    attempts: usize,
+    error: String,
+    payload: &'a str,
+}
+
+impl WebhookSink {
+    /// The sink of `dest`, a `webhook` destination.
+    pub fn open(dest: &DestinationConfig) -> anyhow::Result<Self> {
+        let secret = dest
+            .secret
+            .clone()
+            .ok_or_else(|| anyhow!("destination {}: webhook sink needs a secret", dest.name))?;
+        let dead_letter = match &dest.dead_letter {
+            Some(path) => Some(Arc::new(FileSink::open(path.clone())?)),
+            None => None,
+        };
+        Ok(WebhookSink {
+            http: reqwest::Client::builder()
+                .timeout(REQUEST_TIMEOUT)
+                .build()?,
+            name: dest.name.clone(),
+            urls: dest.urls.clone(),
+            secret,
+            retry_attempts: dest.retry_attempts,
+            retry_base: Duration::from_millis(dest.retry_base_ms),
+            dead_letter,
+            dead_letter_path: dest.dead_letter.clone(),
+        })
+    }
+
+    /// POST `body` to `url`, retrying with backoff. Returns the attempts
+    /// made and the last error.
+    async fn deliver(
+        http: reqwest::Client,
+        url: String,
+        body: Arc<Vec<u8>>,
+        headers: [(&'static str, String); 2],
+        attempts: usize,
+        base: Duration,
+    ) -> (String, usize, anyhow::Result<()>) {
+        let mut made = 0;
+        let res = retry_with_backoff(attempts, base, 2.0, || {
+            made += 1;
+            let request = http
+                .post(&url)
+                .header(reqwest::header::CONTENT_TYPE, content_type(&body))
+                .header(headers[0].0, &headers[0].1)
+                .header(headers[1].0, &headers[1].1)
+                .body(body.to_vec());
+            async move {
+                request.send().await?.error_for_status()?;
+                Ok::<(), anyhow::Error>(())
+            }
+        })
+        .await;
+        (url, made, res)
+    }
+}
+
+#[async_trait]
+impl EventSink for WebhookSink {
+    async fn publish(&self, message: &[u8]) -> anyhow::Result<()> {
+        let timestamp = Utc::now().timestamp().to_string();
+        let signature = sign(self.secret.as_bytes(), &timestamp, message);
+        let body = Arc::new(message.to_vec());
+        let mut deliveries = JoinSet::new();
+        for url in &self.urls {
+            deliveries.spawn(Self::deliver(
+                self.http.clone(),
+                url.clone(),
+                Arc::clone(&body),
+                [
+                    ("X-Tracker-Timestamp", timestamp.clone()),
+                    ("X-Tracker-Signature", signature.clone()),
+                ],
+                self.retry_attempts,
+                self.retry_base,
+            ));
+        }
+        while let Some(joined) = deliveries.join_next().await {
+            let (url, attempts, res) = joined?;
+            let Err(e) = res else { continue };
+            error!(
+                "Webhook {} of destination {} failed after {} attempts: {:?}",
+                url, self.name, attempts, e
+            );
+            if let Some(dead_letter) = &self.dead_letter {
+                let line = serde_json::to_vec(&DeadLetter {
+                    failed_at: Utc::now().to_rfc3339(),
+                    destination: &self.name,
+                    url: &url,
+                    attempts,
+                    error: format!("{:#}", e),
+                    payload: &String::from_utf8_lossy(message),
+                })?;
+                dead_letter.publish(&line).await?;
+            }
+        }
+        Ok(())
+    }
+
+    fn describe(&self) -> String {
+        let dead_letter = match &self.dead_letter_path {
+            Some(path) => format!(", dead letters to {}", path.display()),
+            None => String::new(),
+        };
+        format!("webhooks {}{}", self.urls.join(", "), dead_letter)
+    }
+}
+
+/// `X-Tracker-Signature` of `body` sent at `timestamp`.
+pub fn sign(secret: &[u8], timestamp: &str, body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret).expect("HMAC takes keys of any length");
+    mac.update(timestamp.as_bytes());
+    mac.update(b".");
+    mac.update(body);
+    format!(
+        "sha256={}",
+        ethers::utils::hex::encode(mac.finalize().into_bytes())
+    )
+}
+
+/// A batch is newline-delimited JSON; a single event is JSON.
+fn content_type(body: &[u8]) -> &'static str {
+    let body = body.strip_suffix(b"\n").unwrap_or(body);
+    if body.contains(&b'\n') {
+        "application/x-ndjson"
+    } else {
+        "application/json"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderMap;
+    use axum::routing::post;
+    use axum::Router;
+    use tokio::sync::mpsc;
+
+    #[test]
+    fn test_signature() {
+        // HMAC-SHA256 of `1700000000.{"event_id":"a"}` keyed with `secret`.
+        assert_eq!(
+            sign(b"secret", "1700000000", br#"{"event_id":"a"}"#),
+            "sha256=009d43b0b1f14ca2a8c691ea9db076ebf76cef2570181e0fe9aa661aab36ec77"
+        );
+        assert_ne!(
+            sign(b"secret", "1700000001", br#"{"event_id":"a"}"#),
+            sign(b"secret", "1700000000", br#"{"event_id":"a"}"#)
+        );
+    }
+
+    #[test]
+    fn test_content_type() {
+        assert_eq!(content_type(b"{\"event_id\":\"a\"}"), "application/json");
+        assert_eq!(content_type(b"{\"event_id\":\"a\"}\n"), "application/json");
+        assert_eq!(content_type(b"{}\n{}\n"), "application/x-ndjson");
+    }
+
+    #[tokio::test]
+    async fn test_delivers_signed_and_dead_letters_failures() {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let app = Router::new().route(
+            "/hook",
+            post(move |headers: HeaderMap, body: String| {
+                let tx = tx.clone();
+                async move {
+                    let header = |name: &str| headers[name].to_str().unwrap().to_string();
+                    tx.send((
+                        header("X-Tracker-Timestamp"),
+                        header("X-Tracker-Signature"),
+                        body,
+                    ))
+                    .unwrap();
+                }
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let dead_letter = std::env::temp_dir().join("tracker_webhook_dead_letter_test.jsonl");
+        std::fs::remove_file(&dead_letter).ok();
+        let sink = WebhookSink::open(&DestinationConfig {
+            name: "hooks".into(),
+            urls: vec![
+                format!("http://{}/hook", addr),
+                format!("http://{}/missing", addr),
+            ],
+            secret: Some("secret".into()),
+            dead_letter: Some(dead_letter.clone()),
+            retry_attempts: 2,
+            retry_base_ms: 1,
+            ..Default::default()
+        })
+        .unwrap();
+        sink.publish(br#"{"event_id":"a"}"#).await.unwrap();
+
+        let (timestamp, signature, body) = rx.recv().await.unwrap();
+        assert_eq!(body, r#"{"event_id":"a"}"#);
+        assert_eq!(signature, sign(b"secret", &timestamp, body.as_bytes()));
+        let lines = std::fs::read_to_string(&dead_letter).unwrap();
+        std::fs::remove_file(&dead_letter).ok();
+        let record: serde_json::Value = serde_json::from_str(lines.trim()).unwrap();
+        assert_eq!(record["url"], format!("http://{}/missing", addr));
+        assert_eq!(record["attempts"], 2);
+        assert_eq!(record["payload"], r#"{"event_id":"a"}"#);
+    }
+}
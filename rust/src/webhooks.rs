@@ -0,0 +1,1462 @@
+//! Webhook subscriptions (`/admin/webhooks`).
+//!
+//! A consumer registers an HTTP(S) endpoint at runtime, optionally narrowed
+//! to some watched addresses, chains, event types and a minimum value in the
+//! asset's smallest unit. Every published event that matches a subscription,
+//! as received from the [event bus](crate::bus), except the `backfill`
+//! history of a cold start, is POSTed to its URL as the
+//! same JSON payload Redis destinations receive, with
+//! `X-Tracker-Subscription` and `X-Tracker-Event-Id` headers. Requests are
+//! retried with backoff; a subscription that keeps failing doesn't block
+//! publishing, its deliveries are dropped once the webhook queue is full.
+//!
+//! Subscriptions are saved to `WEBHOOKS_FILE` on every change and loaded
+//! from it at startup. Without it they live in memory and are lost on
+//! restart.
+//!
+//! Every delivery is logged per subscription, in publish order, with its
+//! outcome (queued, delivered after how many attempts, failed with which
+//! error, or dropped from a full queue) and payload. The log keeps the last
+//! [`LOG_SIZE`] deliveries of each subscription. After an outage a consumer
+//! can read the log and have a range of it sent again, from one event id
+//! through another, with [`Webhooks::redeliver`]. The log lives in memory
+//! only.
+//!
+//! An HTTP 200 only says the request arrived. A subscription with
+//! `ack_deadline_secs` also wants the consumer to confirm it processed each
+//! event, with [`Webhooks::acknowledge`]. A delivered event that isn't
+//! acknowledged within the deadline is sent again by the `webhook_acks` job,
+//! up to `max_redeliveries` times, and then logged as `unacknowledged` with
+//! an error. A delivery that fails or is dropped from a full queue is
+//! treated the same way, with a deadline that has already passed, so it is
+//! retried on the job's next run. Requests carry `X-Tracker-Ack-Deadline`, and a consumer that
+//! skips events it has already processed (by `X-Tracker-Event-Id`) handles
+//! each event exactly once. [`Webhooks::offset`] gives the consumer's
+//! position: the last event through which everything was acknowledged.
+//! While [`LOG_SIZE`] events wait for their acknowledgement, new ones are
+//! held back, in order, until the consumer catches up; past [`HELD_SIZE`]
+//! held events further ones are dropped, and logged as such. The
+//! acknowledgement state is saved next to `WEBHOOKS_FILE` by the
+//! `webhook_acks` job when it changed, and on shutdown, so events delivered
+//! before a restart are still redelivered after it. Held and unacknowledged
+//! events count against the delivery log's memory budget.
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex as StdMutex};
+
+use anyhow::{anyhow, bail, Context};
+use chrono::{DateTime, Utc};
+use ethers::types::U256;
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc::{self, error::TrySendError};
+use tokio::time::Duration;
+use tracing::{error, info, info_span, warn, Instrument, Span};
+
+use crate::amount::Amount;
+use crate::bus::BusSubscriber;
+use crate::config::Config;
+use crate::event_type::EventType;
+use crate::retry::retry_with_backoff;
+use crate::Event;
+
+/// Deliveries waiting for the webhook worker.
+const QUEUE_SIZE: usize = 1024;
+const RETRY_ATTEMPTS: usize = 3;
+const RETRY_BASE: Duration = Duration::from_millis(500);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+/// Deliveries remembered per subscription, and events sent to a consumer
+/// before it must acknowledge some.
+pub const LOG_SIZE: usize = 1000;
+/// Redeliveries of an unacknowledged event, unless the subscription sets
+/// `max_redeliveries`.
+pub const ACK_REDELIVERIES: u32 = 3;
+/// Events held back per subscription before new ones are dropped.
+pub const HELD_SIZE: usize = 10 * LOG_SIZE;
+
+/// `POST /admin/webhooks` body.
+#[derive(Deserialize, Debug, Default)]
+pub struct NewSubscription {
+    /// Caller-chosen id. Generated if omitted.
+    pub id: Option<String>,
+    pub url: String,
+    /// Watched addresses, matched against `from`, `to` and the address the
+    /// event was published for. Empty matches every address.
+    #[serde(default)]
+    pub addresses: Vec<String>,
+    #[serde(default)]
+    pub chains: Vec<String>,
+    #[serde(default)]
+    pub event_types: Vec<EventType>,
+    /// Smallest `value`, in the asset's smallest unit. Events without a
+    /// value don't match.
+    pub min_value: Option<String>,
+    /// Seconds the consumer has to acknowledge a delivered event; unset if
+    /// it doesn't acknowledge.
+    pub ack_deadline_secs: Option<u64>,
+    /// Times an unacknowledged event is sent again before it is alerted
+    /// on; [`ACK_REDELIVERIES`] if unset.
+    pub max_redeliveries: Option<u32>,
+}
+
+/// A registered webhook endpoint and its filter.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Subscription {
+    pub id: String,
+    pub url: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub addresses: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub chains: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub event_types: Vec<EventType>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_value: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ack_deadline_secs: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_redeliveries: Option<u32>,
+}
+
+impl Subscription {
+    pub fn matches(&self, event: &Event) -> bool {
+        let address_ok = self.addresses.is_empty()
+            || self.addresses.iter().any(|a| {
+                a.eq_ignore_ascii_case(&event.from)
+                    || a.eq_ignore_ascii_case(&event.to)
+                    || event
+                        .watched_address
+                        .as_deref()
+                        .is_some_and(|w| a.eq_ignore_ascii_case(w))
+            });
+        let value_ok = match &self.min_value {
+            None => true,
+            Some(min) => match (U256::from_dec_str(min), Amount::parse(&event.value, 0)) {
+                (Ok(min), Some(value)) => value.raw() >= min,
+                _ => false,
+            },
+        };
+        address_ok
+            && value_ok
+            && (self.chains.is_empty() || self.chains.iter().any(|c| *c == event.chain))
+            && (self.event_types.is_empty()
+                || self.event_types.iter().any(|t| *t == event.event_type))
+    }
+}
+
+/// `POST /admin/webhooks/{id}/redeliver` body.
+#[derive(Deserialize, Debug)]
+pub struct RedeliverRange {
+    /// First event to send again.
+    pub from_event_id: String,
+    /// Last event to send again; the most recent delivery if omitted.
+    pub to_event_id: Option<String>,
+}
+
+/// `POST /admin/webhooks/{id}/ack` body.
+#[derive(Deserialize, Debug)]
+pub struct Acknowledgement {
+    /// Events the consumer has processed.
+    pub event_ids: Vec<String>,
+}
+
+/// Where a consumer of a subscription with acknowledgements stands.
+#[derive(Serialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConsumerOffset {
+    /// Last delivered event that was acknowledged along with every event
+    /// delivered before it; `None` until there is one.
+    pub offset: Option<String>,
+    /// Delivered events waiting for an acknowledgement.
+    pub unacknowledged: usize,
+    /// Of those, the ones redelivered `max_redeliveries` times and alerted
+    /// on, oldest first. A late acknowledgement still clears them.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub overdue: Vec<String>,
+    /// Events not sent yet because [`LOG_SIZE`] are unacknowledged.
+    #[serde(skip_serializing_if = "is_zero")]
+    pub held: usize,
+    /// Acknowledgements since the subscription was registered.
+    pub acknowledged: u64,
+    /// Acknowledged ids that weren't waiting for one: unknown, or
+    /// acknowledged already.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub unknown: Vec<String>,
+}
+
+/// How a delivery ended.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Outcome {
+    /// Waiting for the worker.
+    Queued,
+    Delivered,
+    /// Every attempt failed.
+    Failed,
+    /// The webhook queue was full; nothing was sent.
+    Dropped,
+    /// Delivered, but not acknowledged after every redelivery.
+    Unacknowledged,
+}
+
+/// An entry of a subscription's delivery log.
+#[derive(Serialize, Debug, Clone)]
+pub struct DeliveryRecord {
+    pub event_id: String,
+    pub outcome: Outcome,
+    pub attempts: usize,
+    /// Last error, for failed deliveries.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Whether this was a manual redelivery.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub redelivery: bool,
+    pub at: DateTime<Utc>,
+    /// What was (or would have been) sent, for redelivery.
+    #[serde(skip)]
+    payload: String,
+}
+
+/// Estimated bytes of a delivery record besides its strings.
+const RECORD_OVERHEAD: usize = 128;
+
+impl DeliveryRecord {
+    fn estimated_bytes(&self) -> usize {
+        RECORD_OVERHEAD
+            + self.event_id.len()
+            + self.payload.len()
+            + self.error.as_ref().map_or(0, String::len)
+    }
+}
+
+/// Delivery records per subscription id, oldest first.
+type DeliveryLog = Arc<StdMutex<HashMap<String, VecDeque<DeliveryRecord>>>>;
+
+fn is_zero(n: &usize) -> bool {
+    *n == 0
+}
+
+/// A delivered event waiting for its acknowledgement.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct PendingAck {
+    event_id: String,
+    payload: String,
+    /// When it is sent again, or alerted on.
+    deadline: DateTime<Utc>,
+    redeliveries: u32,
+    /// Redelivered as often as allowed and alerted on.
+    overdue: bool,
+}
+
+/// An event held back until the consumer acknowledges earlier ones.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct HeldEvent {
+    event_id: String,
+    payload: String,
+}
+
+/// Acknowledgements of one subscription, in delivery order.
+#[derive(Serialize, Deserialize, Debug, Default)]
+struct AckState {
+    pending: BTreeMap<u64, PendingAck>,
+    /// Key of each pending event in `pending`.
+    #[serde(skip)]
+    index: HashMap<String, u64>,
+    /// Acknowledged events delivered after the oldest pending one.
+    acked: BTreeMap<u64, String>,
+    /// Events to send once fewer than [`LOG_SIZE`] are pending, oldest
+    /// first.
+    #[serde(default)]
+    held: VecDeque<HeldEvent>,
+    next: u64,
+    offset: Option<String>,
+    acknowledged: u64,
+}
+
+impl AckState {
+    /// Rebuild `index` after loading.
+    fn reindex(&mut self) {
+        self.index = self
+            .pending
+            .iter()
+            .map(|(seq, p)| (p.event_id.clone(), *seq))
+            .collect();
+    }
+
+    /// Wait for an acknowledgement of `event_id` until `deadline`. A
+    /// redelivered event keeps its place in the order.
+    fn delivered(&mut self, event_id: &str, payload: &str, deadline: DateTime<Utc>) {
+        if let Some(seq) = self.index.get(event_id) {
+            if let Some(pending) = self.pending.get_mut(seq) {
+                pending.deadline = deadline;
+            }
+            return;
+        }
+        self.pending.insert(
+            self.next,
+            PendingAck {
+                event_id: event_id.to_string(),
+                payload: payload.to_string(),
+                deadline,
+                redeliveries: 0,
+                overdue: false,
+            },
+        );
+        self.index.insert(event_id.to_string(), self.next);
+        self.next += 1;
+    }
+
+    /// Whether a new event must wait in `held` rather than be sent: the
+    /// consumer has [`LOG_SIZE`] events to acknowledge, or earlier events
+    /// are waiting already.
+    fn must_hold(&self) -> bool {
+        self.pending.len() >= LOG_SIZE || !self.held.is_empty()
+    }
+
+    /// Hold `event_id` back. Returns true if it is the first one held since
+    /// the consumer last caught up.
+    fn hold(&mut self, event_id: &str, payload: &str) -> bool {
+        self.held.push_back(HeldEvent {
+            event_id: event_id.to_string(),
+            payload: payload.to_string(),
+        });
+        self.held.len() == 1
+    }
+
+    /// Take the held events that fit below [`LOG_SIZE`] pending ones.
+    fn release(&mut self) -> Vec<HeldEvent> {
+        let room = LOG_SIZE.saturating_sub(self.pending.len());
+        let count = room.min(self.held.len());
+        self.held.drain(..count).collect()
+    }
+
+    /// Clear `event_id`; false if it wasn't pending.
+    fn acknowledge(&mut self, event_id: &str) -> bool {
+        let Some(seq) = self.index.remove(event_id) else {
+            return false;
+        };
+        self.pending.remove(&seq);
+        self.acked.insert(seq, event_id.to_string());
+        self.acknowledged += 1;
+        self.advance();
+        true
+    }
+
+    /// Move the offset over the acknowledged events before the oldest
+    /// pending one.
+    fn advance(&mut self) {
+        let oldest = self.pending.keys().next().copied().unwrap_or(u64::MAX);
+        while let Some(entry) = self.acked.first_entry() {
+            if *entry.key() > oldest {
+                break;
+            }
+            self.offset = Some(entry.remove());
+        }
+    }
+
+    /// Estimated bytes of the pending and held events.
+    fn estimated_bytes(&self) -> usize {
+        let pending = self
+            .pending
+            .values()
+            .map(|p| RECORD_OVERHEAD + p.event_id.len() + p.payload.len());
+        let held = self
+            .held
+            .iter()
+            .map(|h| RECORD_OVERHEAD + h.event_id.len() + h.payload.len());
+        pending.chain(held).sum()
+    }
+
+    fn offset(&self) -> ConsumerOffset {
+        ConsumerOffset {
+            offset: self.offset.clone(),
+            unacknowledged: self.pending.len(),
+            overdue: self
+                .pending
+                .values()
+                .filter(|p| p.overdue)
+                .map(|p| p.event_id.clone())
+                .collect(),
+            held: self.held.len(),
+            acknowledged: self.acknowledged,
+            unknown: Vec::new(),
+        }
+    }
+}
+
+/// Acknowledgement state per subscription id.
+type AckLog = Arc<StdMutex<HashMap<String, AckState>>>;
+
+/// Where the acknowledgement state is saved: next to the subscriptions,
+/// e.g. `webhooks.acks.json` for `webhooks.json`.
+fn acks_path(path: &Path) -> PathBuf {
+    path.with_extension("acks.json")
+}
+
+/// Write the serialized acknowledgement state to `path` through a temporary
+/// file.
+fn write_acks(path: &Path, json: serde_json::Result<String>) -> anyhow::Result<()> {
+    let tmp = path.with_extension("tmp");
+    std::fs::write(&tmp, json?)?;
+    std::fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+fn load_acks(path: &Path) -> anyhow::Result<HashMap<String, AckState>> {
+    let raw = std::fs::read_to_string(path)
+        .with_context(|| format!("failed to read webhook acknowledgements {}", path.display()))?;
+    let mut acks: HashMap<String, AckState> = serde_json::from_str(&raw).with_context(|| {
+        format!(
+            "failed to parse webhook acknowledgements {}",
+            path.display()
+        )
+    })?;
+    acks.values_mut().for_each(AckState::reindex);
+    Ok(acks)
+}
+
+/// One POST of an event to a subscription.
+struct WebhookDelivery {
+    subscription_id: String,
+    url: String,
+    event_id: String,
+    payload: String,
+    redelivery: bool,
+    /// The subscription's `ack_deadline_secs`.
+    ack_deadline_secs: Option<u64>,
+    /// The event's `publish` span, carrying its trace context.
+    span: Span,
+}
+
+impl WebhookDelivery {
+    /// Append this delivery to its subscription's log.
+    fn log(&self, log: &DeliveryLog, outcome: Outcome) {
+        let mut log = log.lock().unwrap();
+        let records = log.entry(self.subscription_id.clone()).or_default();
+        if records.len() == LOG_SIZE {
+            records.pop_front();
+        }
+        records.push_back(DeliveryRecord {
+            event_id: self.event_id.clone(),
+            outcome,
+            attempts: 0,
+            error: None,
+            redelivery: self.redelivery,
+            at: Utc::now(),
+            payload: self.payload.clone(),
+        });
+    }
+
+    /// Wait for the consumer's acknowledgement of this delivery until
+    /// `deadline`, if its subscription takes them. Returns whether it does.
+    fn await_ack(&self, acks: &mut HashMap<String, AckState>, deadline: DateTime<Utc>) -> bool {
+        if self.ack_deadline_secs.is_none() {
+            return false;
+        }
+        acks.entry(self.subscription_id.clone())
+            .or_default()
+            .delivered(&self.event_id, &self.payload, deadline);
+        true
+    }
+
+    /// Record the outcome of this queued delivery in its log entry, unless
+    /// the entry has been evicted meanwhile. The worker is FIFO, so that is
+    /// the oldest queued entry of the event.
+    fn finish(&self, log: &DeliveryLog, outcome: Outcome, attempts: usize, error: Option<String>) {
+        let mut log = log.lock().unwrap();
+        let record = log.get_mut(&self.subscription_id).and_then(|records| {
+            records
+                .iter_mut()
+                .find(|r| r.outcome == Outcome::Queued && r.event_id == self.event_id)
+        });
+        if let Some(record) = record {
+            record.outcome = outcome;
+            record.attempts = attempts;
+            record.error = error;
+            record.at = Utc::now();
+        }
+    }
+}
+
+/// Registered subscriptions. Cheap to clone; all clones share state.
+#[derive(Debug, Clone, Default)]
+pub struct Webhooks {
+    subscriptions: Arc<StdMutex<BTreeMap<String, Subscription>>>,
+    log: DeliveryLog,
+    acks: AckLog,
+    /// `WEBHOOKS_FILE`, rewritten on every change.
+    path: Option<Arc<PathBuf>>,
+    /// Whether `acks` changed since it was last saved.
+    acks_changed: Arc<AtomicBool>,
+    /// Queue of the delivery worker; `None` until started.
+    queue: Option<mpsc::Sender<WebhookDelivery>>,
+}
+
+impl Webhooks {
+    /// Load the subscriptions saved in `WEBHOOKS_FILE`, if any, and spawn the
+    /// delivery worker and a task dispatching the bus `events` to it.
+    pub fn start(cfg: &Config, mut events: BusSubscriber) -> anyhow::Result<Self> {
+        let path = cfg.webhooks_file.as_ref().map(PathBuf::from);
+        let subscriptions = match &path {
+            Some(path) if path.exists() => load(path)?,
+            _ => BTreeMap::new(),
+        };
+        if !subscriptions.is_empty() {
+            info!("Loaded {} webhook subscriptions", subscriptions.len());
+        }
+        let mut acks = match &path {
+            Some(path) if acks_path(path).exists() => load_acks(&acks_path(path))?,
+            _ => HashMap::new(),
+        };
+        acks.retain(|id, _| subscriptions.contains_key(id));
+        let waiting: usize = acks.values().map(|state| state.pending.len()).sum();
+        if waiting > 0 {
+            info!(
+                "Loaded {} webhook events waiting for acknowledgement",
+                waiting
+            );
+        }
+        let http = reqwest::Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .build()?;
+        let (tx, rx) = mpsc::channel(QUEUE_SIZE);
+        let log = DeliveryLog::default();
+        let acks = Arc::new(StdMutex::new(acks));
+        let acks_changed = Arc::new(AtomicBool::new(false));
+        tokio::spawn(run_webhook_worker(
+            http,
+            rx,
+            Arc::clone(&log),
+            Arc::clone(&acks),
+            Arc::clone(&acks_changed),
+        ));
+        let webhooks = Webhooks {
+            subscriptions: Arc::new(StdMutex::new(subscriptions)),
+            log,
+            acks,
+            path: path.map(Arc::new),
+            acks_changed,
+            queue: Some(tx),
+        };
+        let dispatcher = webhooks.clone();
+        tokio::spawn(async move {
+            while let Some(published) = events.recv().await {
+                if published.event.backfill {
+                    continue;
+                }
+                dispatcher.dispatch(&published.event, &published.payload, &published.span);
+            }
+        });
+        Ok(webhooks)
+    }
+
+    /// Validate, store and save a subscription.
+    pub fn register(&self, new: NewSubscription) -> anyhow::Result<Subscription> {
+        let url = reqwest::Url::parse(&new.url)
+            .map_err(|e| anyhow!("invalid url {:?}: {}", new.url, e))?;
+        if !matches!(url.scheme(), "http" | "https") {
+            bail!("url must be http or https, got {:?}", new.url);
+        }
+        if let Some(min) = &new.min_value {
+            U256::from_dec_str(min)
+                .map_err(|_| anyhow!("min_value must be an integer, got {:?}", min))?;
+        }
+        if new.ack_deadline_secs == Some(0) {
+            bail!("ack_deadline_secs must be positive");
+        }
+        if new.max_redeliveries.is_some() && new.ack_deadline_secs.is_none() {
+            bail!("max_redeliveries requires ack_deadline_secs");
+        }
+        let id = new
+            .id
+            .unwrap_or_else(|| format!("{:016x}", rand::random::<u64>()));
+        let subscription = Subscription {
+            id: id.clone(),
+            url: new.url,
+            addresses: new.addresses,
+            chains: new.chains,
+            event_types: new.event_types,
+            min_value: new.min_value,
+            ack_deadline_secs: new.ack_deadline_secs,
+            max_redeliveries: new.max_redeliveries,
+        };
+        let mut subscriptions = self.subscriptions.lock().unwrap();
+        if subscriptions.contains_key(&id) {
+            bail!("webhook {} already exists", id);
+        }
+        subscriptions.insert(id.clone(), subscription.clone());
+        if let Err(e) = self.save(&subscriptions) {
+            subscriptions.remove(&id);
+            return Err(e);
+        }
+        info!("Registered webhook {} -> {}", id, subscription.url);
+        Ok(subscription)
+    }
+
+    pub fn list(&self) -> Vec<Subscription> {
+        self.subscriptions
+            .lock()
+            .unwrap()
+            .values()
+            .cloned()
+            .collect()
+    }
+
+    pub fn get(&self, id: &str) -> Option<Subscription> {
+        self.subscriptions.lock().unwrap().get(id).cloned()
+    }
+
+    /// Remove a subscription. Returns false if there was none.
+    pub fn unregister(&self, id: &str) -> anyhow::Result<bool> {
+        let mut subscriptions = self.subscriptions.lock().unwrap();
+        let Some(removed) = subscriptions.remove(id) else {
+            return Ok(false);
+        };
+        if let Err(e) = self.save(&subscriptions) {
+            subscriptions.insert(removed.id.clone(), removed);
+            return Err(e);
+        }
+        self.log.lock().unwrap().remove(id);
+        let mut acks = self.acks.lock().unwrap();
+        if acks.remove(id).is_some() {
+            self.acks_changed.store(true, Ordering::Relaxed);
+        }
+        info!("Unregistered webhook {}", id);
+        Ok(true)
+    }
+
+    /// The delivery log of a subscription, most recent first. `None` if
+    /// there is no such subscription.
+    pub fn deliveries(&self, id: &str) -> Option<Vec<DeliveryRecord>> {
+        self.get(id)?;
+        let log = self.log.lock().unwrap();
+        let records = log.get(id).into_iter().flatten().rev().cloned().collect();
+        Some(records)
+    }
+
+    /// Number of records in the delivery logs and their estimated size in
+    /// bytes, along with the events held or waiting for an acknowledgement.
+    pub fn log_usage(&self) -> (usize, usize) {
+        let acks = self.acks_bytes();
+        let log = self.log.lock().unwrap();
+        log.values()
+            .flatten()
+            .fold((0, acks), |(records, bytes), record| {
+                (records + 1, bytes + record.estimated_bytes())
+            })
+    }
+
+    /// Estimated bytes of the events held or waiting for an acknowledgement.
+    fn acks_bytes(&self) -> usize {
+        let acks = self.acks.lock().unwrap();
+        acks.values().map(AckState::estimated_bytes).sum()
+    }
+
+    /// Drop the oldest records of the largest delivery logs until all of them
+    /// and the events held or waiting for an acknowledgement, which are
+    /// never dropped, fit in `max_bytes`, and return how many were removed.
+    /// Dropped deliveries can no longer be redelivered.
+    pub fn trim_log(&self, max_bytes: usize) -> usize {
+        let acks = self.acks_bytes();
+        let mut log = self.log.lock().unwrap();
+        let mut sizes: HashMap<String, usize> = log
+            .iter()
+            .map(|(id, records)| {
+                (
+                    id.clone(),
+                    records.iter().map(|r| r.estimated_bytes()).sum(),
+                )
+            })
+            .collect();
+        let mut total: usize = acks + sizes.values().sum::<usize>();
+        let mut removed = 0;
+        while total > max_bytes {
+            let Some((id, size)) = sizes.iter_mut().max_by_key(|(_, size)| **size) else {
+                break;
+            };
+            let Some(record) = log.get_mut(id).and_then(|records| records.pop_front()) else {
+                break;
+            };
+            *size -= record.estimated_bytes();
+            total -= record.estimated_bytes();
+            removed += 1;
+        }
+        removed
+    }
+
+    /// Queue the logged deliveries of subscription `id` from the first one of
+    /// `range.from_event_id` through the last one of `range.to_event_id`
+    /// again, each event once, in their original order. Returns how many
+    /// were queued.
+    pub fn redeliver(&self, id: &str, range: &RedeliverRange) -> anyhow::Result<usize> {
+        let subscription = self.get(id).ok_or_else(|| anyhow!("no webhook {}", id))?;
+        let selected: Vec<(String, String)> = {
+            let log = self.log.lock().unwrap();
+            let records: Vec<&DeliveryRecord> = log.get(id).into_iter().flatten().collect();
+            let start = records
+                .iter()
+                .position(|r| r.event_id == range.from_event_id)
+                .ok_or_else(|| {
+                    anyhow!(
+                        "event {} is not in the delivery log of webhook {}",
+                        range.from_event_id,
+                        id
+                    )
+                })?;
+            let end = match &range.to_event_id {
+                None => records.len() - 1,
+                Some(to) => records
+                    .iter()
+                    .rposition(|r| r.event_id == *to)
+                    .filter(|end| *end >= start)
+                    .ok_or_else(|| {
+                        anyhow!(
+                            "event {} is not in the delivery log of webhook {} after {}",
+                            to,
+                            id,
+                            range.from_event_id
+                        )
+                    })?,
+            };
+            let mut seen = HashSet::new();
+            records[start..=end]
+                .iter()
+                .filter(|r| seen.insert(r.event_id.as_str()))
+                .map(|r| (r.event_id.clone(), r.payload.clone()))
+                .collect()
+        };
+        let mut queued = 0;
+        for (event_id, payload) in selected {
+            let delivery = WebhookDelivery {
+                subscription_id: subscription.id.clone(),
+                url: subscription.url.clone(),
+                span: info_span!("redeliver", event_id = %event_id, webhook = %id),
+                event_id,
+                payload,
+                redelivery: true,
+                ack_deadline_secs: subscription.ack_deadline_secs,
+            };
+            if self.enqueue(delivery) {
+                queued += 1;
+            }
+        }
+        info!("Queued {} redeliveries for webhook {}", queued, id);
+        Ok(queued)
+    }
+
+    /// Record that the consumer of subscription `id` processed `event_ids`,
+    /// and return where it stands. Fails if the subscription doesn't take
+    /// acknowledgements.
+    pub fn acknowledge(&self, id: &str, event_ids: &[String]) -> anyhow::Result<ConsumerOffset> {
+        let subscription = self.get(id).ok_or_else(|| anyhow!("no webhook {}", id))?;
+        if subscription.ack_deadline_secs.is_none() {
+            bail!("webhook {} has no ack_deadline_secs", id);
+        }
+        let mut acks = self.acks.lock().unwrap();
+        let state = acks.entry(subscription.id).or_default();
+        let unknown: Vec<String> = event_ids
+            .iter()
+            .filter(|event_id| !state.acknowledge(event_id))
+            .cloned()
+            .collect();
+        let offset = ConsumerOffset {
+            unknown,
+            ..state.offset()
+        };
+        if offset.unknown.len() < event_ids.len() {
+            self.acks_changed.store(true, Ordering::Relaxed);
+        }
+        Ok(offset)
+    }
+
+    /// Where the consumer of subscription `id` stands. `None` if there is
+    /// no such subscription.
+    pub fn offset(&self, id: &str) -> Option<ConsumerOffset> {
+        self.get(id)?;
+        let acks = self.acks.lock().unwrap();
+        Some(acks.get(id).map(AckState::offset).unwrap_or_default())
+    }
+
+    /// Queue the delivered events whose acknowledgement is overdue at `now`
+    /// again, or log them as `unacknowledged` with an error once they were
+    /// redelivered `max_redeliveries` times. Then queue the held events of
+    /// consumers that acknowledged enough to take them. Returns how many
+    /// were queued.
+    pub fn check_acks(&self, now: DateTime<Utc>) -> usize {
+        let subscriptions = self.subscriptions.lock().unwrap().clone();
+        let mut due = Vec::new();
+        let mut released = Vec::new();
+        {
+            let mut acks = self.acks.lock().unwrap();
+            acks.retain(|id, _| subscriptions.contains_key(id));
+            for (id, state) in acks.iter_mut() {
+                let subscription = &subscriptions[id];
+                let Some(deadline_secs) = subscription.ack_deadline_secs else {
+                    continue;
+                };
+                let max = subscription.max_redeliveries.unwrap_or(ACK_REDELIVERIES);
+                for pending in state.pending.values_mut() {
+                    if pending.overdue || pending.deadline > now {
+                        continue;
+                    }
+                    if pending.redeliveries >= max {
+                        pending.overdue = true;
+                        error!(
+                            "Webhook {} did not acknowledge event {} after {} redeliveries",
+                            id, pending.event_id, pending.redeliveries
+                        );
+                    } else {
+                        pending.redeliveries += 1;
+                        // Until the redelivery lands and sets its own.
+                        pending.deadline = now + chrono::Duration::seconds(deadline_secs as i64);
+                    }
+                    due.push((subscription.clone(), pending.clone()));
+                }
+                let events = state.release();
+                if !events.is_empty() && state.held.is_empty() {
+                    info!("Webhook {} caught up; sending its held events", id);
+                }
+                released.extend(events.into_iter().map(|e| (subscription.clone(), e)));
+            }
+            if !due.is_empty() || !released.is_empty() {
+                self.acks_changed.store(true, Ordering::Relaxed);
+            }
+        }
+        self.save_acks();
+        let mut queued = 0;
+        for (subscription, pending) in due {
+            let delivery = WebhookDelivery {
+                span: info_span!(
+                    "ack_redeliver",
+                    event_id = %pending.event_id,
+                    webhook = %subscription.id
+                ),
+                subscription_id: subscription.id,
+                url: subscription.url,
+                event_id: pending.event_id,
+                payload: pending.payload,
+                redelivery: true,
+                ack_deadline_secs: subscription.ack_deadline_secs,
+            };
+            if pending.overdue {
+                delivery.log(&self.log, Outcome::Unacknowledged);
+            } else if self.enqueue(delivery) {
+                queued += 1;
+            }
+        }
+        if queued > 0 {
+            info!("Queued {} unacknowledged webhook events again", queued);
+        }
+        for (subscription, held) in released {
+            let delivery = WebhookDelivery {
+                span: info_span!(
+                    "ack_release",
+                    event_id = %held.event_id,
+                    webhook = %subscription.id
+                ),
+                subscription_id: subscription.id,
+                url: subscription.url,
+                event_id: held.event_id,
+                payload: held.payload,
+                redelivery: false,
+                ack_deadline_secs: subscription.ack_deadline_secs,
+            };
+            if self.enqueue(delivery) {
+                queued += 1;
+            }
+        }
+        queued
+    }
+
+    /// Queue `payload` for every subscription `event` matches.
+    pub(crate) fn dispatch(&self, event: &Event, payload: &str, span: &Span) {
+        if self.queue.is_none() {
+            return;
+        }
+        let matching: Vec<Subscription> = self
+            .subscriptions
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|s| s.matches(event))
+            .cloned()
+            .collect();
+        for subscription in matching {
+            let delivery = WebhookDelivery {
+                subscription_id: subscription.id,
+                url: subscription.url,
+                event_id: event.event_id.clone(),
+                payload: payload.to_string(),
+                redelivery: false,
+                ack_deadline_secs: subscription.ack_deadline_secs,
+                span: span.clone(),
+            };
+            if delivery.ack_deadline_secs.is_some() && self.hold(&delivery) {
+                continue;
+            }
+            self.enqueue(delivery);
+        }
+    }
+
+    /// Hold `delivery` back if its consumer has too many events to
+    /// acknowledge, alerting when that starts, or drop it if [`HELD_SIZE`]
+    /// are held already. Returns whether it wasn't sent.
+    fn hold(&self, delivery: &WebhookDelivery) -> bool {
+        let mut acks = self.acks.lock().unwrap();
+        let id = &delivery.subscription_id;
+        let state = acks.entry(id.clone()).or_default();
+        if !state.must_hold() {
+            return false;
+        }
+        if state.held.len() >= HELD_SIZE {
+            warn!(
+                "Webhook {} holds {} events, dropping event {}",
+                id, HELD_SIZE, delivery.event_id
+            );
+            drop(acks);
+            delivery.log(&self.log, Outcome::Dropped);
+            return true;
+        }
+        if state.hold(&delivery.event_id, &delivery.payload) {
+            error!(
+                "Webhook {} has {} unacknowledged events; holding new events until it acknowledges",
+                id,
+                state.pending.len()
+            );
+        }
+        self.acks_changed.store(true, Ordering::Relaxed);
+        true
+    }
+
+    /// Save the acknowledgement state next to `WEBHOOKS_FILE`, if it is set
+    /// and changed since the last save. Run by the `webhook_acks` job and on
+    /// shutdown. A failure is logged, and the next run tries again.
+    pub fn save_acks(&self) {
+        let Some(path) = &self.path else {
+            return;
+        };
+        let path = acks_path(path);
+        if !self.acks_changed.swap(false, Ordering::Relaxed) {
+            return;
+        }
+        let json = serde_json::to_string(&*self.acks.lock().unwrap());
+        if let Err(e) = write_acks(&path, json) {
+            self.acks_changed.store(true, Ordering::Relaxed);
+            error!(
+                "Failed to save webhook acknowledgements to {}: {:#}",
+                path.display(),
+                e
+            );
+        }
+    }
+
+    /// Hand a delivery to the worker and log it. If the queue is full it is
+    /// dropped with a warning and logged as such, so it can be redelivered
+    /// later; a subscription with acknowledgements gets it again from the
+    /// `webhook_acks` job.
+    fn enqueue(&self, delivery: WebhookDelivery) -> bool {
+        let Some(queue) = &self.queue else {
+            return false;
+        };
+        // Logged first so the worker always finds the entry to complete.
+        delivery.log(&self.log, Outcome::Queued);
+        match queue.try_send(delivery) {
+            Ok(()) => true,
+            Err(TrySendError::Full(d)) => {
+                warn!(
+                    "Webhook queue full, dropping event {} for webhook {}",
+                    d.event_id, d.subscription_id
+                );
+                d.finish(&self.log, Outcome::Dropped, 0, None);
+                self.retry_later(&d);
+                false
+            }
+            Err(TrySendError::Closed(d)) => {
+                error!("Webhook worker stopped");
+                d.finish(&self.log, Outcome::Failed, 0, Some("worker stopped".into()));
+                self.retry_later(&d);
+                false
+            }
+        }
+    }
+
+    /// Have the `webhook_acks` job send an undelivered `delivery` again on
+    /// its next run, if its subscription takes acknowledgements.
+    fn retry_later(&self, delivery: &WebhookDelivery) {
+        let mut acks = self.acks.lock().unwrap();
+        if delivery.await_ack(&mut acks, Utc::now()) {
+            self.acks_changed.store(true, Ordering::Relaxed);
+        }
+    }
+
+    /// Write all subscriptions to `WEBHOOKS_FILE` through a temporary file,
+    /// so a crash never leaves it half-written.
+    fn save(&self, subscriptions: &BTreeMap<String, Subscription>) -> anyhow::Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let list: Vec<&Subscription> = subscriptions.values().collect();
+        let tmp = path.with_extension("tmp");
+        std::fs::write(&tmp, serde_json::to_string_pretty(&list)?)
+            .and_then(|()| std::fs::rename(&tmp, path.as_path()))
+            .with_context(|| format!("failed to save webhooks to {}", path.display()))
+    }
+}
+
+fn load(path: &Path) -> anyhow::Result<BTreeMap<String, Subscription>> {
+    let raw = std::fs::read_to_string(path)
+        .with_context(|| format!("failed to read webhooks {}", path.display()))?;
+    let list: Vec<Subscription> = serde_json::from_str(&raw)
+        .with_context(|| format!("failed to parse webhooks {}", path.display()))?;
+    Ok(list.into_iter().map(|s| (s.id.clone(), s)).collect())
+}
+
+/// POST queued deliveries in order, retrying each with backoff, and log
+/// their outcome. Events of subscriptions with acknowledgements start
+/// waiting for theirs, marking `acks_changed`: until the deadline if
+/// delivered, or already overdue if every attempt failed.
+async fn run_webhook_worker(
+    http: reqwest::Client,
+    mut rx: mpsc::Receiver<WebhookDelivery>,
+    log: DeliveryLog,
+    acks: AckLog,
+    acks_changed: Arc<AtomicBool>,
+) {
+    while let Some(delivery) = rx.recv().await {
+        let span = delivery.span.clone();
+        async {
+            let mut attempts = 0;
+            let res: anyhow::Result<()> = {
+                let (http, delivery) = (&http, &delivery);
+                retry_with_backoff(RETRY_ATTEMPTS, RETRY_BASE, 2.0, || {
+                    attempts += 1;
+                    async move {
+                        let mut request = http
+                            .post(&delivery.url)
+                            .header(reqwest::header::CONTENT_TYPE, "application/json")
+                            .header("X-Tracker-Subscription", &delivery.subscription_id)
+                            .header("X-Tracker-Event-Id", &delivery.event_id);
+                        if let Some(secs) = delivery.ack_deadline_secs {
+                            request = request.header("X-Tracker-Ack-Deadline", secs);
+                        }
+                        request
+                            .body(delivery.payload.clone())
+                            .send()
+                            .await?
+                            .error_for_status()?;
+                        Ok::<(), anyhow::Error>(())
+                    }
+                })
+                .await
+            };
+            match res {
+                Ok(()) => {
+                    info!(
+                        "Delivered event {} to webhook {}",
+                        delivery.event_id, delivery.subscription_id
+                    );
+                    delivery.finish(&log, Outcome::Delivered, attempts, None);
+                    let secs = delivery.ack_deadline_secs.unwrap_or_default();
+                    let deadline = Utc::now() + chrono::Duration::seconds(secs as i64);
+                    let mut acks = acks.lock().unwrap();
+                    if delivery.await_ack(&mut acks, deadline) {
+                        acks_changed.store(true, Ordering::Relaxed);
+                    }
+                }
+                Err(e) => {
+                    error!(
+                        "Failed to deliver event {} to webhook {} after retries: {:?}",
+                        delivery.event_id, delivery.subscription_id, e
+                    );
+                    delivery.finish(&log, Outcome::Failed, attempts, Some(format!("{:#}", e)));
+                    let mut acks = acks.lock().unwrap();
+                    if delivery.await_ack(&mut acks, Utc::now()) {
+                        acks_changed.store(true, Ordering::Relaxed);
+                    }
+                }
+            }
+        }
+        .instrument(span)
+        .await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn new_subscription(id: &str) -> NewSubscription {
+        NewSubscription {
+            id: Some(id.into()),
+            url: "https://hooks.example/tracker".into(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_subscription_filters() {
+        let sub = Subscription {
+            id: "big-usdc".into(),
+            url: "https://hooks.example".into(),
+            addresses: vec!["0xABC".into()],
+            chains: vec!["ethereum".into()],
+            event_types: vec![EventType::Erc20Transfer],
+            min_value: Some("1000".into()),
+            ack_deadline_secs: None,
+            max_redeliveries: None,
+        };
+        let mut event = Event {
+            chain: "ethereum".into(),
+            event_type: EventType::Erc20Transfer,
+            to: "0xabc".into(),
+            value: "1000".into(),
+            ..Default::default()
+        };
+        assert!(sub.matches(&event));
+        event.value = "999".into();
+        assert!(!sub.matches(&event));
+        event.value = String::new();
+        assert!(!sub.matches(&event));
+        event.value = "5000".into();
+        event.to = "0xdef".into();
+        assert!(!sub.matches(&event));
+        event.watched_address = Some("0xabc".into());
+        assert!(sub.matches(&event));
+        event.chain = "solana".into();
+        assert!(!sub.matches(&event));
+    }
+
+    #[test]
+    fn test_register_validates_and_persists() {
+        let path = std::env::temp_dir().join(format!("webhooks-{}.json", std::process::id()));
+        let webhooks = Webhooks {
+            path: Some(Arc::new(path.clone())),
+            ..Default::default()
+        };
+        let invalid = [
+            NewSubscription {
+                url: "ftp://hooks.example".into(),
+                ..new_subscription("a")
+            },
+            NewSubscription {
+                url: "not a url".into(),
+                ..new_subscription("a")
+            },
+            NewSubscription {
+                min_value: Some("1.5".into()),
+                ..new_subscription("a")
+            },
+        ];
+        for new in invalid {
+            assert!(webhooks.register(new).is_err());
+        }
+
+        webhooks.register(new_subscription("a")).unwrap();
+        webhooks.register(new_subscription("b")).unwrap();
+        assert!(webhooks.register(new_subscription("a")).is_err());
+        assert!(webhooks.unregister("b").unwrap());
+        assert!(!webhooks.unregister("b").unwrap());
+
+        let saved = load(&path).unwrap();
+        assert_eq!(saved.keys().collect::<Vec<_>>(), ["a"]);
+        assert_eq!(saved["a"].url, "https://hooks.example/tracker");
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_delivery_log_and_redelivery() {
+        let (tx, mut rx) = mpsc::channel(2);
+        let webhooks = Webhooks {
+            queue: Some(tx),
+            ..Default::default()
+        };
+        webhooks.register(new_subscription("ops")).unwrap();
+        for id in ["e1", "e2", "e3"] {
+            let event = Event {
+                event_id: id.into(),
+                ..Default::default()
+            };
+            webhooks.dispatch(
+                &event,
+                &format!("{{\"event_id\":\"{}\"}}", id),
+                &Span::none(),
+            );
+        }
+        // The queue holds two; the worker fails the first and delivers the
+        // second.
+        let first = rx.try_recv().unwrap();
+        first.finish(&webhooks.log, Outcome::Failed, 3, Some("503".into()));
+        let second = rx.try_recv().unwrap();
+        second.finish(&webhooks.log, Outcome::Delivered, 1, None);
+
+        let log = webhooks.deliveries("ops").unwrap();
+        let summary: Vec<(&str, Outcome, usize)> = log
+            .iter()
+            .map(|r| (r.event_id.as_str(), r.outcome, r.attempts))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                ("e3", Outcome::Dropped, 0),
+                ("e2", Outcome::Delivered, 1),
+                ("e1", Outcome::Failed, 3),
+            ]
+        );
+        assert_eq!(log[2].error.as_deref(), Some("503"));
+        assert!(webhooks.deliveries("unknown").is_none());
+
+        let range = |from: &str, to: Option<&str>| RedeliverRange {
+            from_event_id: from.into(),
+            to_event_id: to.map(str::to_string),
+        };
+        assert!(webhooks.redeliver("ops", &range("e9", None)).is_err());
+        assert!(webhooks.redeliver("ops", &range("e2", Some("e1"))).is_err());
+        assert_eq!(
+            webhooks.redeliver("ops", &range("e1", Some("e3"))).unwrap(),
+            2
+        );
+        let resent = rx.try_recv().unwrap();
+        assert!(resent.redelivery);
+        assert_eq!(resent.payload, r#"{"event_id":"e1"}"#);
+        assert_eq!(rx.try_recv().unwrap().event_id, "e2");
+        // e3 didn't fit in the queue again.
+        let log = webhooks.deliveries("ops").unwrap();
+        assert_eq!(
+            (log[0].event_id.as_str(), log[0].outcome),
+            ("e3", Outcome::Dropped)
+        );
+        assert!(log[0].redelivery);
+    }
+
+    #[test]
+    fn test_unacknowledged_events_are_redelivered_then_alerted() {
+        let (tx, mut rx) = mpsc::channel(8);
+        let webhooks = Webhooks {
+            queue: Some(tx),
+            ..Default::default()
+        };
+        assert!(webhooks
+            .register(NewSubscription {
+                max_redeliveries: Some(1),
+                ..new_subscription("bad")
+            })
+            .is_err());
+        webhooks
+            .register(NewSubscription {
+                ack_deadline_secs: Some(60),
+                max_redeliveries: Some(1),
+                ..new_subscription("ledger")
+            })
+            .unwrap();
+        webhooks.register(new_subscription("plain")).unwrap();
+        assert!(webhooks.acknowledge("plain", &["e1".into()]).is_err());
+
+        let now = Utc::now();
+        {
+            let mut acks = webhooks.acks.lock().unwrap();
+            let state = acks.entry("ledger".into()).or_default();
+            for id in ["e1", "e2", "e3"] {
+                state.delivered(id, &format!("{{\"event_id\":\"{}\"}}", id), now);
+            }
+        }
+        let acked = webhooks
+            .acknowledge("ledger", &["e2".into(), "e9".into()])
+            .unwrap();
+        // e1 is still pending, so the offset can't move past it.
+        assert_eq!(acked.offset, None);
+        assert_eq!(acked.unacknowledged, 2);
+        assert_eq!(acked.unknown, ["e9"]);
+        webhooks.acknowledge("ledger", &["e1".into()]).unwrap();
+        assert_eq!(
+            webhooks.offset("ledger").unwrap().offset.as_deref(),
+            Some("e2")
+        );
+
+        // e3 is past its deadline: sent again once, then alerted on.
+        assert_eq!(webhooks.check_acks(now), 1);
+        let resent = rx.try_recv().unwrap();
+        assert_eq!(resent.event_id, "e3");
+        assert!(resent.redelivery);
+        assert_eq!(resent.ack_deadline_secs, Some(60));
+        assert_eq!(webhooks.check_acks(now), 0);
+        let later = now + chrono::Duration::seconds(61);
+        assert_eq!(webhooks.check_acks(later), 0);
+        assert!(rx.try_recv().is_err());
+        let offset = webhooks.offset("ledger").unwrap();
+        assert_eq!(offset.overdue, ["e3"]);
+        assert_eq!(offset.acknowledged, 2);
+        assert_eq!(
+            webhooks.deliveries("ledger").unwrap()[0].outcome,
+            Outcome::Unacknowledged
+        );
+
+        // A late acknowledgement still counts.
+        let acked = webhooks.acknowledge("ledger", &["e3".into()]).unwrap();
+        assert_eq!(acked.offset.as_deref(), Some("e3"));
+        assert_eq!(acked.unacknowledged, 0);
+        assert!(webhooks.offset("unknown").is_none());
+    }
+
+    #[test]
+    fn test_dropped_deliveries_wait_for_an_ack() {
+        let (tx, mut rx) = mpsc::channel(1);
+        let webhooks = Webhooks {
+            queue: Some(tx),
+            ..Default::default()
+        };
+        webhooks
+            .register(NewSubscription {
+                ack_deadline_secs: Some(60),
+                ..new_subscription("ledger")
+            })
+            .unwrap();
+        for id in ["e1", "e2"] {
+            let event = Event {
+                event_id: id.into(),
+                ..Default::default()
+            };
+            webhooks.dispatch(&event, "{}", &Span::none());
+        }
+        // e2 didn't fit in the queue, so it is overdue at once.
+        assert_eq!(webhooks.offset("ledger").unwrap().unacknowledged, 1);
+        assert_eq!(rx.try_recv().unwrap().event_id, "e1");
+        assert_eq!(webhooks.check_acks(Utc::now()), 1);
+        let resent = rx.try_recv().unwrap();
+        assert_eq!(resent.event_id, "e2");
+        assert!(resent.redelivery);
+    }
+
+    #[test]
+    fn test_consumers_behind_hold_events_and_acks_are_saved() {
+        let path = std::env::temp_dir().join(format!("webhooks-acks-{}.json", std::process::id()));
+        let (tx, mut rx) = mpsc::channel(8);
+        let webhooks = Webhooks {
+            path: Some(Arc::new(path.clone())),
+            queue: Some(tx),
+            ..Default::default()
+        };
+        webhooks
+            .register(NewSubscription {
+                ack_deadline_secs: Some(60),
+                ..new_subscription("ledger")
+            })
+            .unwrap();
+        let now = Utc::now();
+        {
+            let mut acks = webhooks.acks.lock().unwrap();
+            let state = acks.entry("ledger".into()).or_default();
+            for i in 0..LOG_SIZE {
+                state.delivered(
+                    &format!("e{}", i),
+                    "{}",
+                    now + chrono::Duration::seconds(60),
+                );
+            }
+        }
+
+        // LOG_SIZE events are unacknowledged: a new one waits instead of
+        // pushing out the oldest.
+        let event = Event {
+            event_id: "new".into(),
+            ..Default::default()
+        };
+        webhooks.dispatch(&event, r#"{"event_id":"new"}"#, &Span::none());
+        assert!(rx.try_recv().is_err());
+        let offset = webhooks.offset("ledger").unwrap();
+        assert_eq!((offset.unacknowledged, offset.held), (LOG_SIZE, 1));
+
+        // What a restart loads back, once the job saved it.
+        assert!(!acks_path(&path).exists());
+        webhooks.save_acks();
+        let saved = load_acks(&acks_path(&path)).unwrap();
+        assert_eq!(saved["ledger"].pending.len(), LOG_SIZE);
+        assert_eq!(saved["ledger"].index["e0"], 0);
+        assert_eq!(saved["ledger"].held.len(), 1);
+
+        webhooks.acknowledge("ledger", &["e0".into()]).unwrap();
+        assert_eq!(webhooks.check_acks(now), 1);
+        let released = rx.try_recv().unwrap();
+        assert_eq!(released.event_id, "new");
+        assert!(!released.redelivery);
+        assert_eq!(webhooks.offset("ledger").unwrap().held, 0);
+        let saved = load_acks(&acks_path(&path)).unwrap();
+        assert_eq!(saved["ledger"].offset.as_deref(), Some("e0"));
+        assert!(saved["ledger"].held.is_empty());
+
+        std::fs::remove_file(&path).unwrap();
+        std::fs::remove_file(acks_path(&path)).unwrap();
+    }
+
+    #[test]
+    fn test_trim_log_shrinks_largest_logs_first() {
+        let webhooks = Webhooks::default();
+        for (subscription, events) in [("big", 4), ("small", 1)] {
+            for i in 0..events {
+                let delivery = WebhookDelivery {
+                    subscription_id: subscription.into(),
+                    url: String::new(),
+                    event_id: format!("e{}", i),
+                    payload: "x".repeat(100),
+                    redelivery: false,
+                    ack_deadline_secs: None,
+                    span: Span::none(),
+                };
+                delivery.log(&webhooks.log, Outcome::Delivered);
+            }
+        }
+        let (records, bytes) = webhooks.log_usage();
+        assert_eq!(records, 5);
+        let record = bytes / 5;
+
+        assert_eq!(webhooks.trim_log(bytes), 0);
+        assert_eq!(webhooks.trim_log(2 * record), 3);
+        let log = webhooks.log.lock().unwrap();
+        assert_eq!(log["big"].front().unwrap().event_id, "e3");
+        assert_eq!(log["small"].len(), 1);
+        drop(log);
+
+        // Held events count against the budget, and push out records.
+        webhooks
+            .acks
+            .lock()
+            .unwrap()
+            .entry("big".into())
+            .or_default()
+            .hold("e9", &"x".repeat(100));
+        assert_eq!(webhooks.log_usage().1, 3 * record);
+        assert_eq!(webhooks.trim_log(2 * record), 1);
+        assert_eq!(webhooks.log_usage(), (1, 2 * record));
+    }
+
+    #[test]
+    fn test_held_events_are_capped() {
+        let (tx, _rx) = mpsc::channel(1);
+        let webhooks = Webhooks {
+            queue: Some(tx),
+            ..Default::default()
+        };
+        webhooks
+            .register(NewSubscription {
+                ack_deadline_secs: Some(60),
+                ..new_subscription("ledger")
+            })
+            .unwrap();
+        {
+            let mut acks = webhooks.acks.lock().unwrap();
+            let state = acks.entry("ledger".into()).or_default();
+            for i in 0..HELD_SIZE {
+                state.hold(&format!("e{}", i), "{}");
+            }
+        }
+        let event = Event {
+            event_id: "new".into(),
+            ..Default::default()
+        };
+        webhooks.dispatch(&event, "{}", &Span::none());
+        assert_eq!(webhooks.offset("ledger").unwrap().held, HELD_SIZE);
+        let log = webhooks.deliveries("ledger").unwrap();
+        assert_eq!(
+            (log[0].event_id.as_str(), log[0].outcome),
+            ("new", Outcome::Dropped)
+        );
+    }
+}
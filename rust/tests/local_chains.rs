@@ -0,0 +1,295 @@
+//! End-to-end harness against local chains.
+//!
+//! Spins up `anvil`, `solana-test-validator` and `redis-server` on free ports,
+//! starts the tracker binary in HTTP polling mode against them, sends scripted
+//! transfers and asserts the exact event stream that appears on Redis.
+//!
+//! Gated behind the `e2e` feature because it needs the Foundry and Solana CLI
+//! toolchains on PATH:
+//!
+//! ```bash
+//! cargo test --features e2e --test local_chains -- --nocapture
+//! ```
+//!
+//! `ANVIL_BIN`, `SOLANA_TEST_VALIDATOR_BIN` and `REDIS_SERVER_BIN` override the
+//! binaries used.
+#![cfg(feature = "e2e")]
+
+use ethers::providers::{Http, Middleware, Provider};
+use ethers::types::{Address, TransactionRequest, U256};
+use serde_json::Value;
+use solana_client::rpc_client::RpcClient;
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::native_token::LAMPORTS_PER_SOL;
+use solana_sdk::signature::{Keypair, Signer};
+use solana_sdk::system_instruction;
+use solana_sdk::transaction::Transaction;
+use std::net::TcpListener;
+use std::path::PathBuf;
+use std::process::{Child, Command, Stdio};
+use std::time::{Duration, Instant};
+use tokio_stream::StreamExt;
+
+/// Anvil's first two default dev accounts. The first is unlocked, so it can
+/// send without signing locally.
+const ANVIL_SENDER: &str = "0xf39fd6e51aad88f6f4ce6ab8827279cfffb92266";
+const ANVIL_RECIPIENT: &str = "0x70997970c51812dc3a010c7d01b50e0d17dc79c8";
+
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(60);
+const EVENT_TIMEOUT: Duration = Duration::from_secs(90);
+
+/// Kills and reaps the child process when dropped so failed assertions don't
+/// leak chains between runs.
+struct ChildGuard {
+    name: &'static str,
+    child: Child,
+}
+
+impl Drop for ChildGuard {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+        eprintln!("stopped {}", self.name);
+    }
+}
+
+fn free_port() -> u16 {
+    TcpListener::bind("127.0.0.1:0")
+        .and_then(|l| l.local_addr())
+        .map(|a| a.port())
+        .expect("no free port")
+}
+
+fn bin(env: &str, default: &str) -> String {
+    std::env::var(env).unwrap_or_else(|_| default.to_string())
+}
+
+fn spawn(name: &'static str, cmd: &mut Command) -> ChildGuard {
+    let child = cmd
+        .stdout(Stdio::null())
+        .stderr(Stdio::inherit())
+        .spawn()
+        .unwrap_or_else(|e| panic!("failed to start {}: {}", name, e));
+    ChildGuard { name, child }
+}
+
+async fn wait_until<F, Fut>(what: &str, mut ready: F)
+where
+    F: FnMut() -> Fut,
+    Fut: std::future::Future<Output = bool>,
+{
+    let deadline = Instant::now() + STARTUP_TIMEOUT;
+    while !ready().await {
+        assert!(Instant::now() < deadline, "{} did not become ready", what);
+        tokio::time::sleep(Duration::from_millis(250)).await;
+    }
+}
+
+struct LocalChains {
+    eth_url: String,
+    sol_url: String,
+    redis_url: String,
+    _ledger: PathBuf,
+    _processes: Vec<ChildGuard>,
+}
+
+impl LocalChains {
+    async fn start() -> Self {
+        let (eth_port, sol_port, redis_port) = (free_port(), free_port(), free_port());
+        let ledger = std::env::temp_dir().join(format!("tracker-e2e-ledger-{}", sol_port));
+
+        let processes = vec![
+            spawn(
+                "anvil",
+                Command::new(bin("ANVIL_BIN", "anvil")).args([
+                    "--port",
+                    &eth_port.to_string(),
+                    "--silent",
+                ]),
+            ),
+            spawn(
+                "solana-test-validator",
+                Command::new(bin("SOLANA_TEST_VALIDATOR_BIN", "solana-test-validator"))
+                    .args(["--reset", "--quiet", "--rpc-port", &sol_port.to_string()])
+                    .arg("--ledger")
+                    .arg(&ledger),
+            ),
+            spawn(
+                "redis-server",
+                Command::new(bin("REDIS_SERVER_BIN", "redis-server")).args([
+                    "--port",
+                    &redis_port.to_string(),
+                    "--save",
+                    "",
+                ]),
+            ),
+        ];
+
+        let chains = LocalChains {
+            eth_url: format!("http://127.0.0.1:{}", eth_port),
+            sol_url: format!("http://127.0.0.1:{}", sol_port),
+            redis_url: format!("redis://127.0.0.1:{}", redis_port),
+            _ledger: ledger,
+            _processes: processes,
+        };
+
+        let eth = Provider::<Http>::try_from(chains.eth_url.as_str()).unwrap();
+        wait_until("anvil", || async { eth.get_block_number().await.is_ok() }).await;
+
+        let sol_url = chains.sol_url.clone();
+        wait_until("solana-test-validator", || {
+            let sol_url = sol_url.clone();
+            async move {
+                tokio::task::spawn_blocking(move || RpcClient::new(sol_url).get_health().is_ok())
+                    .await
+                    .unwrap_or(false)
+            }
+        })
+        .await;
+
+        let redis = redis::Client::open(chains.redis_url.as_str()).unwrap();
+        wait_until("redis-server", || async {
+            redis.get_multiplexed_async_connection().await.is_ok()
+        })
+        .await;
+
+        chains
+    }
+
+    fn start_tracker(&self, watched_sol: &str, extra_env: &[(&str, &str)]) -> ChildGuard {
+        spawn(
+            "tracker",
+            Command::new(env!("CARGO_BIN_EXE_tracker_rs"))
+                .envs(extra_env.iter().copied())
+                .env_remove("CONFIG_FILE")
+                .env("ETH_RPC_URL", &self.eth_url)
+                .env("SOL_RPC_URL", &self.sol_url)
+                .env("REDIS_URL", &self.redis_url)
+                .env("ETH_NETWORK", "anvil")
+                .env("SOL_NETWORK", "localnet")
+                .env("WATCHED_ADDRESSES_ETH", ANVIL_RECIPIENT)
+                .env("WATCHED_ADDRESSES_SOL", watched_sol)
+                .env("RUST_LOG", "info"),
+        )
+    }
+}
+
+async fn send_eth_transfer(url: &str) -> String {
+    let provider = Provider::<Http>::try_from(url).unwrap();
+    let from: Address = ANVIL_SENDER.parse().unwrap();
+    let to: Address = ANVIL_RECIPIENT.parse().unwrap();
+    let tx = TransactionRequest::pay(to, U256::exp10(18)).from(from);
+    let receipt = provider
+        .send_transaction(tx, None)
+        .await
+        .expect("eth transfer failed")
+        .await
+        .expect("eth receipt failed")
+        .expect("eth transfer dropped");
+    format!("eth:{:?}", receipt.transaction_hash)
+}
+
+fn send_sol_transfer(url: &str, recipient: &Keypair) -> String {
+    let client = RpcClient::new_with_commitment(url.to_string(), CommitmentConfig::confirmed());
+    let payer = Keypair::new();
+    let airdrop = client
+        .request_airdrop(&payer.pubkey(), 2 * LAMPORTS_PER_SOL)
+        .expect("airdrop failed");
+    let deadline = Instant::now() + STARTUP_TIMEOUT;
+    while !client.confirm_transaction(&airdrop).unwrap_or(false) {
+        assert!(Instant::now() < deadline, "airdrop not confirmed");
+        std::thread::sleep(Duration::from_millis(250));
+    }
+
+    let ix =
+        system_instruction::transfer(&payer.pubkey(), &recipient.pubkey(), LAMPORTS_PER_SOL / 2);
+    let blockhash = client.get_latest_blockhash().unwrap();
+    let tx = Transaction::new_signed_with_payer(&[ix], Some(&payer.pubkey()), &[&payer], blockhash);
+    let signature = client
+        .send_and_confirm_transaction(&tx)
+        .expect("sol transfer failed");
+    format!("sol:{}", signature)
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_scripted_transfers_reach_redis() {
+    let chains = LocalChains::start().await;
+    let recipient = Keypair::new();
+
+    let redis = redis::Client::open(chains.redis_url.as_str()).unwrap();
+    let mut pubsub = redis.get_async_pubsub().await.unwrap();
+    pubsub.subscribe("cross_chain_events").await.unwrap();
+
+    let _tracker = chains.start_tracker(&recipient.pubkey().to_string(), &[]);
+
+    let eth_event = send_eth_transfer(&chains.eth_url).await;
+    let sol_url = chains.sol_url.clone();
+    let sol_event = tokio::task::spawn_blocking(move || send_sol_transfer(&sol_url, &recipient))
+        .await
+        .unwrap();
+
+    let mut stream = pubsub.on_message();
+    let mut events: Vec<Value> = Vec::new();
+    let deadline = tokio::time::Instant::now() + EVENT_TIMEOUT;
+    while events.len() < 2 {
+        let msg = tokio::time::timeout_at(deadline, stream.next())
+            .await
+            .unwrap_or_else(|_| panic!("timed out waiting for events, got {:?}", events))
+            .expect("redis subscription closed");
+        let payload: String = msg.get_payload().unwrap();
+        events.push(serde_json::from_str(&payload).unwrap());
+    }
+
+    // Nothing else should trickle in after the expected pair.
+    assert!(
+        tokio::time::timeout(Duration::from_secs(5), stream.next())
+            .await
+            .is_err(),
+        "unexpected extra event"
+    );
+
+    events.sort_by(|a, b| a["chain"].as_str().cmp(&b["chain"].as_str()));
+    let eth = &events[0];
+    assert_eq!(eth["event_id"], eth_event.as_str());
+    assert_eq!(eth["event_type"], "transfer");
+    assert_eq!(eth["network"], "anvil");
+    assert_eq!(eth["from"], ANVIL_SENDER);
+    assert_eq!(eth["to"], ANVIL_RECIPIENT);
+    assert_eq!(eth["value"], "1000000000000000000");
+
+    let sol = &events[1];
+    assert_eq!(sol["event_id"], sol_event.as_str());
+    assert_eq!(sol["event_type"], "solana_tx");
+    assert_eq!(sol["network"], "localnet");
+    assert!(sol["slot"].as_u64().is_some());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_replicas_with_distributed_dedup_publish_once() {
+    let chains = LocalChains::start().await;
+
+    let redis = redis::Client::open(chains.redis_url.as_str()).unwrap();
+    let mut pubsub = redis.get_async_pubsub().await.unwrap();
+    pubsub.subscribe("cross_chain_events").await.unwrap();
+
+    let replica = [("DISTRIBUTED_DEDUP", "true"), ("SOL_ENABLED", "false")];
+    let _first = chains.start_tracker("", &replica);
+    let _second = chains.start_tracker("", &replica);
+
+    let eth_event = send_eth_transfer(&chains.eth_url).await;
+
+    let mut stream = pubsub.on_message();
+    let msg = tokio::time::timeout(EVENT_TIMEOUT, stream.next())
+        .await
+        .expect("timed out waiting for the event")
+        .expect("redis subscription closed");
+    let event: Value = serde_json::from_str(&msg.get_payload::<String>().unwrap()).unwrap();
+    assert_eq!(event["event_id"], eth_event.as_str());
+
+    assert!(
+        tokio::time::timeout(Duration::from_secs(10), stream.next())
+            .await
+            .is_err(),
+        "second replica published a duplicate"
+    );
+}
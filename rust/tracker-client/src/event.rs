@@ -0,0 +1,633 @@
+//! The normalized event schema, as published by the tracker.
+//!
+//! Optional fields default when absent and unknown fields are ignored, so a
+//! client keeps working when the tracker adds fields or a destination
+//! projects a subset of them (`fields = [...]`).
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Event {
+    pub event_id: String,
+    /// `ethereum`, `solana`, an extra EVM chain such as `polygon`, `tron`,
+    /// or a Substrate chain such as `polkadot`.
+    pub chain: String,
+    pub network: String,
+    /// 1, 2, 3, … per chain and network; a jump means missed events.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sequence: Option<u64>,
+    /// Region of the listener that published the event, in active-active
+    /// deployments.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub region: Option<String>,
+    /// Listener that published the event; `sequence` is counted per
+    /// instance.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub instance_id: Option<String>,
+    /// Transaction hash, or signature on Solana.
+    pub tx_hash: String,
+    /// Unix seconds on Ethereum, RFC 3339 on Solana.
+    pub timestamp: String,
+    pub from: String,
+    pub to: String,
+    /// Amount in the smallest unit (wei, lamports, token base units).
+    pub value: String,
+    /// Symbol of the asset `value` is in: the token's, or the chain's native
+    /// currency.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub asset_symbol: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub asset_decimals: Option<u8>,
+    /// `value` scaled by `asset_decimals`, e.g. `1.5`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value_formatted: Option<String>,
+    /// Cross-chain asset the token or native currency is mapped to in the
+    /// tracker's `[[assets]]` config, e.g. `USDC`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub logical_asset: Option<String>,
+    /// `transfer`, `erc20_transfer`, `solana_tx`, `gap_repaired`,
+    /// `address_heartbeat`, `expected_transfer_received`,
+    /// `expected_transfer_missed`, `transaction_retracted`,
+    /// `portfolio_snapshot`, `unexpected_activity`, `sweep_detected`,
+    /// `validator_event`, `lst_mint`, `lst_redeem`, `lending_supply`,
+    /// `lending_borrow`, `lending_repay`, `lending_liquidation`,
+    /// `liquidation_risk`, `pending_transfer`, `stuck_transaction` or
+    /// `fee_spike`.
+    pub event_type: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub slot: Option<u64>,
+    /// Block of an EVM transfer, ERC-20 or lending event, or of a Tron or
+    /// Substrate event.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub block_number: Option<u64>,
+    /// Index of the log within its block (ERC-20 and lending events), or of
+    /// the event among its block's events on Substrate chains.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub log_index: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token: Option<Token>,
+    /// SPL Memo text of a Solana transaction.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memo: Option<String>,
+    /// 4-byte function selector of an Ethereum transaction's calldata.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub selector: Option<String>,
+    /// Signature for `selector`, if it is a well-known method.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub method: Option<String>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub spam: bool,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub spam_reasons: Vec<String>,
+    /// What was non-standard about an ERC-20 `Transfer` log, e.g.
+    /// `extra_data`.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub decode_warnings: Vec<String>,
+    /// `fee_on_transfer` or `rebasing`, for tokens whose balances don't
+    /// move by the logged amounts.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token_behavior: Option<String>,
+    /// Raw amount the recipient of a fee-on-transfer token received.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub received_value: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gap: Option<GapRange>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub heartbeat: Option<Heartbeat>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expectation: Option<Expectation>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub portfolio: Option<Portfolio>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub alert: Option<Alert>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sweep: Option<Sweep>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub validator: Option<Validator>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub liquid_staking: Option<LiquidStaking>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lending: Option<Lending>,
+    /// `pending_transfer` events with simulation only.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub simulation: Option<Simulation>,
+    /// `stuck_transaction` events only.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stuck: Option<Stuck>,
+    /// `fee_spike` events only.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fee_spike: Option<FeeSpike>,
+    /// Substrate chains only.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub substrate: Option<SubstrateBlock>,
+    /// Block explorer links, when the chain's explorer is known.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub explorer_url: Option<ExplorerUrls>,
+    /// W3C trace context of the tracker's publish span.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub traceparent: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Token {
+    pub address: String,
+    pub symbol: String,
+    pub decimals: u8,
+}
+
+/// Ethereum blocks reprocessed by the tracker's gap auditor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GapRange {
+    pub from_block: u64,
+    pub to_block: u64,
+}
+
+/// Monitoring status carried by `address_heartbeat` events.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Heartbeat {
+    pub address: String,
+    /// When the tracker last saw an event for the address (RFC 3339); `None`
+    /// if it hasn't since it started.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_activity_at: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_tx_hash: Option<String>,
+    /// Last processed block (Ethereum) or slot (Solana).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub checkpoint: Option<u64>,
+}
+
+/// The expected transfer resolved by an `expected_transfer_received` or
+/// `expected_transfer_missed` event.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Expectation {
+    pub id: String,
+    pub chain: String,
+    /// Receiving address.
+    pub address: String,
+    /// Token contract; `None` for the native currency.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+    /// Smallest unit, as a decimal string.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_amount: Option<String>,
+    /// Exact amount to match, after fingerprinting; set instead of
+    /// `min_amount`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub amount: Option<String>,
+    /// Solana expectations: the memo the payment must carry.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memo: Option<String>,
+    /// RFC 3339.
+    pub deadline: String,
+}
+
+/// Valuation carried by `portfolio_snapshot` events.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Portfolio {
+    pub name: String,
+    /// Ethereum block the balances were read at.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub block: Option<u64>,
+    /// Solana slot the balances were read at.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub slot: Option<u64>,
+    /// Sum of the priced positions' values, to two decimals.
+    pub total_value: String,
+    pub positions: Vec<Position>,
+}
+
+/// Balance of one asset across a portfolio's addresses.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Position {
+    /// Logical asset, or the symbol if the asset isn't mapped.
+    pub asset: String,
+    /// In whole units, e.g. `1.5`.
+    pub balance: String,
+    /// Configured price per whole unit; `None` if there is none.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub price: Option<String>,
+    /// `balance` × `price`, to two decimals.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value: Option<String>,
+}
+
+/// The rule that fired, carried by `unexpected_activity` and `liquidation_risk`
+/// events.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Alert {
+    /// `cold_storage_outgoing`, `liquidation_risk`, `stuck_transaction` or
+    /// `fee_spike`.
+    pub rule: String,
+    /// `low`, `medium`, `high` or `critical`.
+    pub severity: String,
+    pub address: String,
+    /// The address book label, if the entry has one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+}
+
+/// Transfers aggregated into a `sweep_detected` event.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Sweep {
+    pub destination: String,
+    /// Watched addresses that sent, in order of their first transfer.
+    pub sources: Vec<String>,
+    pub tx_hashes: Vec<String>,
+    pub window_secs: u64,
+}
+
+/// What changed about a monitored Solana validator (`validator_event`).
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Validator {
+    /// `delinquent`, `recovered`, `commission_changed` or `stake_changed`.
+    pub change: String,
+    pub vote_account: String,
+    pub identity: String,
+    pub delinquent: bool,
+    pub commission: u8,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub previous_commission: Option<u8>,
+    /// In lamports.
+    pub activated_stake: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub previous_activated_stake: Option<String>,
+    pub last_vote: u64,
+}
+
+/// Asset behind a liquid staking mint or redemption (`lst_mint` and
+/// `lst_redeem`).
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LiquidStaking {
+    /// `lido`, `marinade` or `jito`.
+    pub protocol: String,
+    /// `ETH` or `SOL`.
+    pub underlying_symbol: String,
+    pub underlying_decimals: u8,
+    /// In base units; absent when paid out later.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub underlying_value: Option<String>,
+}
+
+/// Aave or Compound position (`lending_*` and `liquidation_risk` events).
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Lending {
+    /// `aave_v3` or `compound_v2`.
+    pub protocol: String,
+    /// The Aave pool or Compound cToken.
+    pub market: String,
+    /// The supplier, borrower or liquidated account.
+    pub account: String,
+    /// Liquidations only; `collateral_value` is in base units.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub collateral_asset: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub collateral_value: Option<String>,
+    /// Aave borrows only, in ray (1e27 = 100% a year).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub borrow_rate: Option<String>,
+    /// Compound only: debt in the market after the action, in base units.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub account_debt: Option<String>,
+    /// Aave only: USD with 8 decimals.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total_collateral_base: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total_debt_base: Option<String>,
+    /// Aave only, with 18 decimals; absent without debt.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub health_factor: Option<String>,
+}
+
+/// Predicted outcome of a pending transaction (`pending_transfer` events).
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Simulation {
+    /// `trace`, or `call` without gas or effects.
+    pub method: String,
+    pub success: bool,
+    /// Why it would fail, e.g. `execution reverted`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// The `Error(string)` message of a revert.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub revert_reason: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gas_used: Option<u64>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub effects: Vec<Effect>,
+}
+
+/// ERC-20 transfer or approval a simulated transaction would emit.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Effect {
+    /// `transfer` or `approval`.
+    pub kind: String,
+    /// The token contract.
+    pub token: String,
+    /// Sender, or owner of an approval.
+    pub from: String,
+    /// Recipient, or spender of an approval.
+    pub to: String,
+    /// Raw amount, or allowance.
+    pub value: String,
+}
+
+/// Why a watched address's transaction is stuck (`stuck_transaction`
+/// events).
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Stuck {
+    /// `pending` or `nonce_gap`.
+    pub reason: String,
+    /// The nonce that isn't getting mined, or the first missing one.
+    pub nonce: u64,
+    pub confirmed_nonce: u64,
+    pub pending_nonce: u64,
+    /// `pending` only: how long transactions have waited at
+    /// `confirmed_nonce`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pending_secs: Option<u64>,
+    /// `nonce_gap` only: the lowest nonce waiting for the gap.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub queued_nonce: Option<u64>,
+}
+
+/// What a transaction paid for gas (`fee_spike` events). Amounts are in
+/// wei.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FeeSpike {
+    pub gas_used: u64,
+    /// Effective price per gas.
+    pub gas_price: String,
+    /// Base fee per gas of the block.
+    pub base_fee: String,
+    /// `gas_used` times `gas_price`.
+    pub fee: String,
+    /// What was paid above the base fee.
+    pub overpayment: String,
+}
+
+/// Where an event of a Substrate chain sits in its block.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SubstrateBlock {
+    pub block_number: u64,
+    pub block_hash: String,
+    /// Position of the event among its block's events.
+    pub event_index: u32,
+    /// Position of the emitting extrinsic; absent for events outside one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub extrinsic_index: Option<u32>,
+    /// Active staking era, on chains with staking.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub era: Option<u32>,
+}
+
+/// Block explorer links for an event's transaction and addresses.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ExplorerUrls {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tx: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub from: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub to: Option<String>,
+}
+
+impl Event {
+    /// Whether `address` is the sender or recipient, or the monitored address
+    /// of a heartbeat. EVM addresses compare case-insensitively.
+    pub fn involves(&self, address: &str) -> bool {
+        let eq = |a: &str| {
+            if address.starts_with("0x") {
+                a.eq_ignore_ascii_case(address)
+            } else {
+                a == address
+            }
+        };
+        eq(&self.from) || eq(&self.to) || self.heartbeat.as_ref().is_some_and(|h| eq(&h.address))
+    }
+}
+
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
+/// Decode one Pub/Sub message into events.
+///
+/// Handles single-event messages, newline-delimited batches
+/// (`batch_max_events`) and, with the `zstd` feature, zstd-compressed
+/// batches.
+pub fn decode(message: &[u8]) -> anyhow::Result<Vec<Event>> {
+    let decompressed;
+    let body = if message.starts_with(&ZSTD_MAGIC) {
+        decompressed = decompress(message)?;
+        decompressed.as_slice()
+    } else {
+        message
+    };
+    body.split(|b| *b == b'\n')
+        .filter(|line| !line.iter().all(u8::is_ascii_whitespace))
+        .map(|line| serde_json::from_slice(line).context("invalid event JSON"))
+        .collect()
+}
+
+#[cfg(feature = "zstd")]
+fn decompress(message: &[u8]) -> anyhow::Result<Vec<u8>> {
+    zstd::decode_all(message).context("invalid zstd batch")
+}
+
+#[cfg(not(feature = "zstd"))]
+fn decompress(_message: &[u8]) -> anyhow::Result<Vec<u8>> {
+    Err(anyhow::anyhow!(
+        "received a zstd batch; enable the `zstd` feature of tracker-client"
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GOLDEN: &str = include_str!("../../../tests/golden/replay-eth-anvil.events.json");
+
+    #[test]
+    fn test_golden_events_deserialize() {
+        let events: Vec<Event> = serde_json::from_str(GOLDEN).unwrap();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].event_type, "transfer");
+        assert_eq!(events[1].sequence, Some(2));
+        let token = events[1].token.as_ref().unwrap();
+        assert_eq!(token.decimals, 18);
+        assert!(events[1].involves("0x70997970C51812dc3A010C7d01b50e0d17dc79C8"));
+    }
+
+    #[test]
+    fn test_decode_single_and_batch() {
+        let single = br#"{"event_id":"sol:abc","chain":"solana","slot":7,"spam":true,"spam_reasons":["zero_value"],"future_field":1}"#;
+        let events = decode(single).unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].slot, Some(7));
+        assert!(events[0].spam);
+
+        let batch = b"{\"event_id\":\"a\"}\n{\"event_id\":\"b\"}\n";
+        let ids: Vec<String> = decode(batch)
+            .unwrap()
+            .into_iter()
+            .map(|e| e.event_id)
+            .collect();
+        assert_eq!(ids, vec!["a", "b"]);
+    }
+
+    #[test]
+    fn test_heartbeat_involves_its_address() {
+        let msg = br#"{"event_id":"heartbeat:ethereum:0xAb:1","chain":"ethereum","event_type":"address_heartbeat","heartbeat":{"address":"0xAb","checkpoint":42}}"#;
+        let event = decode(msg).unwrap().remove(0);
+        let heartbeat = event.heartbeat.as_ref().unwrap();
+        assert_eq!(heartbeat.checkpoint, Some(42));
+        assert_eq!(heartbeat.last_activity_at, None);
+        assert!(event.involves("0xab"));
+    }
+
+    #[test]
+    fn test_decode_expectation() {
+        let msg = br#"{"event_id":"expectation:order-1:missed","chain":"ethereum","event_type":"expected_transfer_missed","expectation":{"id":"order-1","chain":"ethereum","address":"0xab","min_amount":"5","deadline":"2025-01-01T00:00:00Z"}}"#;
+        let event = decode(msg).unwrap().remove(0);
+        let expectation = event.expectation.unwrap();
+        assert_eq!(expectation.id, "order-1");
+        assert_eq!(expectation.token, None);
+        assert_eq!(expectation.min_amount.as_deref(), Some("5"));
+        assert_eq!(expectation.amount, None);
+    }
+
+    #[test]
+    fn test_decode_portfolio() {
+        let msg = br#"{"event_id":"portfolio:treasury:1","event_type":"portfolio_snapshot","portfolio":{"name":"treasury","block":100,"total_value":"3000.00","positions":[{"asset":"ETH","balance":"1.5","price":"2000","value":"3000.00"},{"asset":"PEPE","balance":"7"}]}}"#;
+        let event = decode(msg).unwrap().remove(0);
+        let portfolio = event.portfolio.unwrap();
+        assert_eq!(portfolio.block, Some(100));
+        assert_eq!(portfolio.slot, None);
+        assert_eq!(portfolio.positions[0].value.as_deref(), Some("3000.00"));
+        assert_eq!(portfolio.positions[1].price, None);
+    }
+
+    #[test]
+    fn test_decode_alert() {
+        let msg = br#"{"event_id":"eth:0xabc:unexpected_activity","chain":"ethereum","event_type":"unexpected_activity","alert":{"rule":"cold_storage_outgoing","severity":"critical","address":"0xab"}}"#;
+        let event = decode(msg).unwrap().remove(0);
+        let alert = event.alert.unwrap();
+        assert_eq!(alert.severity, "critical");
+        assert_eq!(alert.label, None);
+    }
+
+    #[test]
+    fn test_decode_sweep() {
+        let msg = br#"{"event_id":"sweep:ethereum:0xff:0xabc","chain":"ethereum","to":"0xff","value":"4000000","event_type":"sweep_detected","sweep":{"destination":"0xff","sources":["0x01","0x02"],"tx_hashes":["0xdef","0xabc"],"window_secs":600}}"#;
+        let event = decode(msg).unwrap().remove(0);
+        let sweep = event.sweep.unwrap();
+        assert_eq!(sweep.sources, ["0x01", "0x02"]);
+        assert_eq!(sweep.tx_hashes.len(), 2);
+        assert_eq!(sweep.window_secs, 600);
+    }
+
+    #[test]
+    fn test_decode_validator() {
+        let msg = br#"{"event_id":"validator:Vote1:delinquent:250000000","chain":"solana","event_type":"validator_event","validator":{"change":"delinquent","vote_account":"Vote1","identity":"Node1","delinquent":true,"commission":5,"activated_stake":"1200000000000000","last_vote":250000000}}"#;
+        let event = decode(msg).unwrap().remove(0);
+        let validator = event.validator.unwrap();
+        assert_eq!(validator.change, "delinquent");
+        assert!(validator.delinquent);
+        assert_eq!(validator.previous_commission, None);
+    }
+
+    #[test]
+    fn test_decode_liquid_staking() {
+        let msg = br#"{"event_id":"eth:0xabc:7:lst_mint","chain":"ethereum","event_type":"lst_mint","value":"1000000000000000000","liquid_staking":{"protocol":"lido","underlying_symbol":"ETH","underlying_decimals":18,"underlying_value":"1000000000000000000"}}"#;
+        let event = decode(msg).unwrap().remove(0);
+        let staking = event.liquid_staking.unwrap();
+        assert_eq!(staking.protocol, "lido");
+        assert_eq!(staking.underlying_decimals, 18);
+        assert_eq!(
+            staking.underlying_value.as_deref(),
+            Some("1000000000000000000")
+        );
+    }
+
+    #[test]
+    fn test_decode_lending() {
+        let msg = br#"{"event_id":"eth:0xabc:3:0x01","chain":"ethereum","event_type":"lending_liquidation","value":"700","lending":{"protocol":"aave_v3","market":"0x87870bca3f3fd6335c3f4ce8392d69350b4fa4e2","account":"0x01","collateral_asset":"0x03","collateral_value":"2","total_collateral_base":"0","total_debt_base":"0"}}"#;
+        let event = decode(msg).unwrap().remove(0);
+        let lending = event.lending.unwrap();
+        assert_eq!(lending.protocol, "aave_v3");
+        assert_eq!(lending.collateral_value.as_deref(), Some("2"));
+        assert_eq!(lending.health_factor, None);
+    }
+
+    #[test]
+    fn test_decode_simulation() {
+        let msg = br#"{"event_id":"eth:0xabc:0x01:pending","chain":"ethereum","event_type":"pending_transfer","simulation":{"method":"trace","success":false,"error":"execution reverted","revert_reason":"nope","gas_used":23000,"effects":[{"kind":"approval","token":"0x03","from":"0x01","to":"0x02","value":"5"}]}}"#;
+        let event = decode(msg).unwrap().remove(0);
+        let simulation = event.simulation.unwrap();
+        assert!(!simulation.success);
+        assert_eq!(simulation.revert_reason.as_deref(), Some("nope"));
+        assert_eq!(simulation.effects[0].kind, "approval");
+    }
+
+    #[test]
+    fn test_decode_stuck() {
+        let msg = br#"{"event_id":"stuck:ethereum:0x01:6:nonce_gap","chain":"ethereum","event_type":"stuck_transaction","stuck":{"reason":"nonce_gap","nonce":6,"confirmed_nonce":5,"pending_nonce":6,"queued_nonce":8}}"#;
+        let event = decode(msg).unwrap().remove(0);
+        let stuck = event.stuck.unwrap();
+        assert_eq!(stuck.reason, "nonce_gap");
+        assert_eq!(stuck.queued_nonce, Some(8));
+        assert_eq!(stuck.pending_secs, None);
+    }
+
+    #[test]
+    fn test_decode_fee_spike() {
+        let msg = br#"{"event_id":"eth:0xabc:fee_spike","chain":"ethereum","event_type":"fee_spike","alert":{"rule":"fee_spike","severity":"high","address":"0x01"},"fee_spike":{"gas_used":21000,"gas_price":"400000000000","base_fee":"20000000000","fee":"8400000000000000","overpayment":"7980000000000000"}}"#;
+        let event = decode(msg).unwrap().remove(0);
+        let spike = event.fee_spike.unwrap();
+        assert_eq!(spike.gas_used, 21000);
+        assert_eq!(spike.overpayment, "7980000000000000");
+        assert_eq!(event.alert.unwrap().rule, "fee_spike");
+    }
+
+    #[test]
+    fn test_decode_substrate_block() {
+        let msg = br#"{"event_id":"polkadot:18000000-7","chain":"polkadot","event_type":"transfer","tx_hash":"18000000-2","substrate":{"block_number":18000000,"block_hash":"0xabc","event_index":7,"extrinsic_index":2}}"#;
+        let event = decode(msg).unwrap().remove(0);
+        let block = event.substrate.unwrap();
+        assert_eq!(block.block_number, 18_000_000);
+        assert_eq!(block.extrinsic_index, Some(2));
+        assert_eq!(block.era, None);
+    }
+
+    #[test]
+    fn test_decode_explorer_urls() {
+        let msg = br#"{"event_id":"a","chain":"solana","explorer_url":{"tx":"https://solscan.io/tx/5sig"}}"#;
+        let event = decode(msg).unwrap().remove(0);
+        let urls = event.explorer_url.unwrap();
+        assert_eq!(urls.tx.as_deref(), Some("https://solscan.io/tx/5sig"));
+        assert_eq!(urls.to, None);
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn test_decode_zstd_batch() {
+        let batch = b"{\"event_id\":\"a\"}\n{\"event_id\":\"b\"}\n";
+        let compressed = zstd::encode_all(&batch[..], 3).unwrap();
+        assert_eq!(decode(&compressed).unwrap().len(), 2);
+    }
+}
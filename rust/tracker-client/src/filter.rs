@@ -0,0 +1,101 @@
+//! Client-side event filtering.
+use crate::Event;
+
+/// Builder-style predicate over events. Each empty list matches everything;
+/// the default filter passes all events.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct EventFilter {
+    chains: Vec<String>,
+    event_types: Vec<String>,
+    addresses: Vec<String>,
+    regions: Vec<String>,
+    exclude_spam: bool,
+}
+
+impl EventFilter {
+    pub fn chain(mut self, chain: impl Into<String>) -> Self {
+        self.chains.push(chain.into());
+        self
+    }
+
+    pub fn event_type(mut self, event_type: impl Into<String>) -> Self {
+        self.event_types.push(event_type.into());
+        self
+    }
+
+    /// Only events sent from or to `address`.
+    pub fn address(mut self, address: impl Into<String>) -> Self {
+        self.addresses.push(address.into());
+        self
+    }
+
+    /// Only events published by listeners in `region`, to consume one copy
+    /// of an active-active deployment. Events without a region don't match.
+    pub fn region(mut self, region: impl Into<String>) -> Self {
+        self.regions.push(region.into());
+        self
+    }
+
+    /// Drop events the tracker tagged as spam.
+    pub fn exclude_spam(mut self) -> Self {
+        self.exclude_spam = true;
+        self
+    }
+
+    pub fn matches(&self, event: &Event) -> bool {
+        (self.chains.is_empty() || self.chains.contains(&event.chain))
+            && (self.event_types.is_empty() || self.event_types.contains(&event.event_type))
+            && (self.addresses.is_empty() || self.addresses.iter().any(|a| event.involves(a)))
+            && (self.regions.is_empty()
+                || event
+                    .region
+                    .as_ref()
+                    .is_some_and(|r| self.regions.contains(r)))
+            && !(self.exclude_spam && event.spam)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(chain: &str, event_type: &str, to: &str, spam: bool) -> Event {
+        Event {
+            chain: chain.into(),
+            event_type: event_type.into(),
+            to: to.into(),
+            spam,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_default_matches_everything() {
+        assert!(EventFilter::default().matches(&event("solana", "solana_tx", "x", true)));
+    }
+
+    #[test]
+    fn test_combined_filter() {
+        let filter = EventFilter::default()
+            .chain("ethereum")
+            .event_type("erc20_transfer")
+            .address("0xABC")
+            .exclude_spam();
+        assert!(filter.matches(&event("ethereum", "erc20_transfer", "0xabc", false)));
+        assert!(!filter.matches(&event("ethereum", "erc20_transfer", "0xabc", true)));
+        assert!(!filter.matches(&event("ethereum", "transfer", "0xabc", false)));
+        assert!(!filter.matches(&event("ethereum", "erc20_transfer", "0xdef", false)));
+        assert!(!filter.matches(&event("solana", "erc20_transfer", "0xabc", false)));
+    }
+
+    #[test]
+    fn test_region_filter() {
+        let filter = EventFilter::default().region("eu-west");
+        let mut event = event("ethereum", "transfer", "0xabc", false);
+        assert!(!filter.matches(&event));
+        event.region = Some("us-east".into());
+        assert!(!filter.matches(&event));
+        event.region = Some("eu-west".into());
+        assert!(filter.matches(&event));
+    }
+}
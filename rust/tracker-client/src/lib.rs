@@ -0,0 +1,31 @@
+//! Client library for consuming cross-chain tracker events.
+//!
+//! Provides the normalized [`Event`] schema as typed structs, [`decode`] for
+//! both per-event and batched (optionally zstd-compressed) messages, an
+//! [`EventFilter`] for narrowing a stream, and a Redis Pub/Sub [`Subscriber`]
+//! that reconnects on its own.
+//!
+//! ```no_run
+//! # async fn run() -> anyhow::Result<()> {
+//! use tracker_client::{EventFilter, Subscriber};
+//!
+//! let mut events = Subscriber::new("redis://localhost:6379", "cross_chain_events")?
+//!     .filter(EventFilter::default().chain("ethereum").exclude_spam())
+//!     .spawn();
+//! while let Some(event) = events.recv().await {
+//!     println!("{} {} {}", event.event_type, event.tx_hash, event.value);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+mod event;
+mod filter;
+mod subscriber;
+
+pub use event::{
+    decode, Alert, Effect, Event, Expectation, ExplorerUrls, FeeSpike, GapRange, Heartbeat,
+    Lending, LiquidStaking, Portfolio, Position, Simulation, Stuck, SubstrateBlock, Sweep, Token,
+    Validator,
+};
+pub use filter::EventFilter;
+pub use subscriber::Subscriber;
@@ -0,0 +1,106 @@
+//! Redis Pub/Sub subscriber with automatic reconnection.
+//!
+//! Pub/Sub has no replay, so events published while the subscriber is
+//! disconnected are lost; consumers that need every event should dedup on
+//! `event_id` and backfill from the Go API's `/transactions` endpoint after a
+//! reconnect.
+use std::time::Duration;
+
+use tokio::sync::mpsc;
+use tokio_stream::StreamExt;
+use tracing::{info, warn};
+
+use crate::{decode, Event, EventFilter};
+
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+pub struct Subscriber {
+    client: redis::Client,
+    channel: String,
+    filter: EventFilter,
+    buffer: usize,
+}
+
+impl Subscriber {
+    /// Fails only if `redis_url` can't be parsed; connecting happens in
+    /// [`Subscriber::spawn`].
+    pub fn new(redis_url: &str, channel: impl Into<String>) -> anyhow::Result<Self> {
+        Ok(Subscriber {
+            client: redis::Client::open(redis_url)?,
+            channel: channel.into(),
+            filter: EventFilter::default(),
+            buffer: 1024,
+        })
+    }
+
+    /// Only deliver events matching `filter`.
+    pub fn filter(mut self, filter: EventFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// Capacity of the channel returned by [`Subscriber::spawn`]. When it is
+    /// full the subscriber stops reading from Redis until there is room.
+    pub fn buffer(mut self, buffer: usize) -> Self {
+        self.buffer = buffer.max(1);
+        self
+    }
+
+    /// Subscribe in a background task and return the receiving end. The task
+    /// reconnects with exponential backoff (up to 30s) and exits once the
+    /// receiver is dropped. Messages that fail to decode are logged and
+    /// skipped.
+    pub fn spawn(self) -> mpsc::Receiver<Event> {
+        let (tx, rx) = mpsc::channel(self.buffer);
+        tokio::spawn(self.run(tx));
+        rx
+    }
+
+    async fn run(self, tx: mpsc::Sender<Event>) {
+        let mut backoff = Duration::from_millis(500);
+        while !tx.is_closed() {
+            match self.forward(&tx, &mut backoff).await {
+                Ok(()) => warn!("Subscription to {} ended; reconnecting", self.channel),
+                Err(e) => warn!(
+                    "Subscription to {} failed: {:?}; reconnecting in {:?}",
+                    self.channel, e, backoff
+                ),
+            }
+            if tx.is_closed() {
+                break;
+            }
+            tokio::time::sleep(backoff).await;
+            backoff = (backoff * 2).min(MAX_BACKOFF);
+        }
+    }
+
+    /// Forward messages until the connection drops or the receiver goes
+    /// away. Resets `backoff` once subscribed.
+    async fn forward(
+        &self,
+        tx: &mpsc::Sender<Event>,
+        backoff: &mut Duration,
+    ) -> anyhow::Result<()> {
+        let mut pubsub = self.client.get_async_pubsub().await?;
+        pubsub.subscribe(&self.channel).await?;
+        info!("Subscribed to {}", self.channel);
+        *backoff = Duration::from_millis(500);
+
+        let mut messages = pubsub.on_message();
+        while let Some(msg) = messages.next().await {
+            let events = match decode(msg.get_payload_bytes()) {
+                Ok(events) => events,
+                Err(e) => {
+                    warn!("Skipping undecodable message on {}: {:?}", self.channel, e);
+                    continue;
+                }
+            };
+            for event in events.into_iter().filter(|e| self.filter.matches(e)) {
+                if tx.send(event).await.is_err() {
+                    return Ok(());
+                }
+            }
+        }
+        Ok(())
+    }
+}